@@ -56,6 +56,86 @@ impl<'a> FlattenedDeviceTree<'a> {
 
         Ok(FdtMemoryRegion { base: reg_prop.u64(0)?, size: reg_prop.u64(1)? })
     }
+
+    /// Returns memory regions of devices marked with the `ace,mmio-deny` property. Accesses to these regions must never be emulated.
+    pub fn mmio_denied_regions<'b>(&'b self) -> impl Iterator<Item = FdtMemoryRegion> + 'b {
+        self.inner
+            .nodes()
+            .filter(|n| n.props().any(|p| Ok(p.name()? == "ace,mmio-deny")))
+            .iterator()
+            .filter_map(|n| {
+                let reg_prop = n.ok()?.props().find(|p| Ok(p.name()? == "reg")).ok()??;
+                Some(FdtMemoryRegion { base: reg_prop.u64(0).ok()?, size: reg_prop.u64(1).ok()? })
+            })
+    }
+
+    /// Returns the memory region, in which the previous boot stage placed the attestation key material for the security monitor. The
+    /// region is described by the `ace,attestation-handover` property holding the base address and the size as two 64-bit values.
+    pub fn attestation_handover(&self) -> Option<FdtMemoryRegion> {
+        let prop = self.inner.props().find(|p| Ok(p.name()? == "ace,attestation-handover")).ok()??;
+        Some(FdtMemoryRegion { base: prop.u64(0).ok()?, size: prop.u64(1).ok()? })
+    }
+
+    /// Returns the memory region of the initial ramdisk declared by the `linux,initrd-start` and `linux,initrd-end` properties of the
+    /// `chosen` node. Both properties can be encoded as 32-bit or 64-bit values.
+    pub fn initrd(&self) -> Option<FdtMemoryRegion> {
+        let chosen = self.inner.nodes().find(|n| Ok(n.name()? == "chosen")).ok()??;
+        let read_address = |name: &str| -> Option<u64> {
+            let prop = chosen.props().find(|p| Ok(p.name()? == name)).ok()??;
+            match prop.length() {
+                4 => Some(prop.u32(0).ok()? as u64),
+                8 => prop.u64(0).ok(),
+                _ => None,
+            }
+        };
+        let start = read_address("linux,initrd-start")?;
+        let end = read_address("linux,initrd-end")?;
+        Some(FdtMemoryRegion { base: start, size: end.checked_sub(start)? })
+    }
+
+    /// Returns memory regions declared by the `ace,code-regions` property of the `chosen` node. The property is a list of pairs of the
+    /// base address and the size, both encoded as 64-bit values.
+    pub fn code_regions(&self) -> impl Iterator<Item = FdtMemoryRegion> + '_ {
+        let chosen = self.inner.nodes().find(|n| Ok(n.name()? == "chosen")).ok().flatten();
+        let prop = chosen.and_then(|chosen| chosen.props().find(|p| Ok(p.name()? == "ace,code-regions")).ok().flatten());
+        let number_of_regions = prop.as_ref().map_or(0, |prop| prop.length() / (2 * core::mem::size_of::<u64>()));
+        (0..number_of_regions).filter_map(move |index| {
+            let prop = prop.as_ref()?;
+            Some(FdtMemoryRegion { base: prop.u64(2 * index).ok()?, size: prop.u64(2 * index + 1).ok()? })
+        })
+    }
+
+    /// Returns entries declared by the `ace,csr-emulation` property of the `chosen` node. The property is a list of triples of the CSR
+    /// number, the action, and the action's argument, all encoded as 64-bit values.
+    pub fn csr_emulation(&self) -> impl Iterator<Item = FdtCsrEmulation> + '_ {
+        let chosen = self.inner.nodes().find(|n| Ok(n.name()? == "chosen")).ok().flatten();
+        let prop = chosen.and_then(|chosen| chosen.props().find(|p| Ok(p.name()? == "ace,csr-emulation")).ok().flatten());
+        let number_of_entries = prop.as_ref().map_or(0, |prop| prop.length() / (3 * core::mem::size_of::<u64>()));
+        (0..number_of_entries).filter_map(move |index| {
+            let prop = prop.as_ref()?;
+            let (csr, action, value) = (prop.u64(3 * index).ok()?, prop.u64(3 * index + 1).ok()?, prop.u64(3 * index + 2).ok()?);
+            Some(FdtCsrEmulation { csr, action, value })
+        })
+    }
+
+    /// Returns true if the `chosen` node declares the `ace,broken-narrow-hfence` property, i.e., the processor does not correctly
+    /// implement `hfence.gvma` with the guest physical address or the VMID operand.
+    pub fn has_broken_narrow_hfence(&self) -> bool {
+        let chosen = self.inner.nodes().find(|n| Ok(n.name()? == "chosen")).ok().flatten();
+        chosen.map_or(false, |chosen| chosen.props().any(|p| Ok(p.name()? == "ace,broken-narrow-hfence")).unwrap_or(false))
+    }
+
+    /// Returns the value of the `ace,page-table-ratio` property of the `chosen` node, i.e., how many mapped pages a confidential VM
+    /// must own for every page of its page tables. The property is encoded as a 64-bit value.
+    pub fn page_table_ratio(&self) -> Option<u64> {
+        let chosen = self.inner.nodes().find(|n| Ok(n.name()? == "chosen")).ok()??;
+        chosen.props().find(|p| Ok(p.name()? == "ace,page-table-ratio")).ok()??.u64(0).ok()
+    }
+
+    /// Returns the size in bytes of the entire FDT blob as declared in its header.
+    pub fn total_size(&self) -> usize {
+        self.inner.totalsize()
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -64,6 +144,14 @@ pub struct FdtMemoryRegion {
     pub size: u64,
 }
 
+/// An entry of the CSR emulation policy of a confidential VM, see `FlattenedDeviceTree::csr_emulation`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FdtCsrEmulation {
+    pub csr: u64,
+    pub action: u64,
+    pub value: u64,
+}
+
 #[derive(Clone)]
 pub struct Hart<'a, 'dt> {
     inner: DevTreeNode<'a, 'dt>,
@@ -2,8 +2,13 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::architecture::SbiExtension::*;
-use crate::core::control_data::{ConfidentialVmId, ControlData, HardwareHart};
-use crate::core::transformations::{ExposeToConfidentialVm, InterHartRequest, PendingRequest};
+use crate::core::control_data::{ConfidentialVmId, ControlData, HardwareHart, HartQuiesce, PendingExit, TraceEvent};
+use crate::core::memory_protector::TlbShootdown;
+use crate::core::transformations::{
+    CppcRequest, CppcResult, CsrAccess, DbcnReadRequest, ExposeToConfidentialVm, ExposeToHypervisor, FwftRequest, FwftResult,
+    GuestPageFault, IllegalInstructionResult, InterHartRequest, MemoryFaultNotification, PendingRequest, SbiPmuRequest, SbiResult,
+    SbiVmRequest, SseRequest, SseResult, StealTimeRequest, VirtualizedCsrResult,
+};
 use crate::error::Error;
 use crate::non_confidential_flow::NonConfidentialFlow;
 
@@ -40,6 +45,12 @@ impl<'a> ConfidentialFlow<'a> {
     /// Moves in the finite state machine (FSM) from the confidential flow into non-confidential flow.
     pub fn into_non_confidential_flow(self) -> NonConfidentialFlow<'a> {
         let confidential_vm_id = self.confidential_vm_id();
+        let confidential_hart_id = self.confidential_hart_id();
+        self.hardware_hart.emit_trace_event(TraceEvent::ContextSwitch {
+            confidential_vm_id: confidential_vm_id.usize(),
+            confidential_hart_id,
+            into_confidential_hart: false,
+        });
         ControlData::try_confidential_vm(confidential_vm_id, |mut confidential_vm| {
             confidential_vm.return_confidential_hart(self.hardware_hart);
             Ok(NonConfidentialFlow::create(self.hardware_hart))
@@ -62,26 +73,42 @@ impl<'a> ConfidentialFlow<'a> {
         use crate::core::architecture::HsmExtension::*;
         use crate::core::architecture::IpiExtension::*;
         use crate::core::architecture::RfenceExtension::*;
-        use crate::core::architecture::SbiExtension;
         use crate::core::architecture::SrstExtension::*;
         use crate::core::architecture::TrapCause::*;
+        use crate::core::architecture::{BaseExtension, DbcnExtension, SbiExtension, StaExtension, TeeGuestExtension};
+        use crate::core::memory_protector::GuestMemoryAccess;
 
         let hardware_hart = unsafe { hardware_hart_pointer.as_mut().expect(crate::error::CTX_SWITCH_ERROR_MSG) };
         hardware_hart.confidential_hart_mut().store_volatile_control_status_registers_in_main_memory();
+        hardware_hart.guard_stack();
         let flow = Self::create(hardware_hart);
+        // The confidential hart is not executing anymore, which acknowledges a potential request to pause the confidential VM.
+        flow.hart_quiesce().exit_confidential_hart();
+        TlbShootdown::exit_confidential_hart(flow.hardware_hart.hart_id());
         let confidential_hart = flow.hardware_hart.confidential_hart();
 
         match confidential_hart.trap_reason() {
             Interrupt => interrupt::handle(flow),
             VsEcall(Ace(SharePageWithHypervisor)) => share_page::handle(confidential_hart.share_page_request(), flow),
             VsEcall(Ace(StopSharingPageWithHypervisor)) => unshare_page::handle(confidential_hart.unshare_page_request(), flow),
+            VsEcall(Ace(InflateMemoryBalloon)) => inflate_memory_balloon::handle(confidential_hart.memory_balloon_request(), flow),
+            VsEcall(Ace(DeflateMemoryBalloon)) => deflate_memory_balloon::handle(confidential_hart.memory_balloon_request(), flow),
+            VsEcall(Ace(ExtendMeasurement)) => extend_measurement::handle(confidential_hart.measurement_register_request(), flow),
+            VsEcall(Ace(ReadMeasurement)) => read_measurement::handle(confidential_hart.measurement_register_request(), flow),
+            VsEcall(Ace(GetMeasurementLog)) => get_measurement_log::handle(confidential_hart.get_measurement_log_request(), flow),
+            VsEcall(Ace(GetAttestationReport)) => attestation_report::handle(confidential_hart.attestation_report_request(), flow),
+            VsEcall(Ace(GetCertificateChain)) => certificate_chain::handle(confidential_hart.certificate_chain_request(), flow),
+            VsEcall(Ace(VerifyCodeIntegrity)) => verify_code_integrity::handle(confidential_hart.verify_code_integrity_request(), flow),
+            VsEcall(Ace(SealData)) => seal_data::handle(confidential_hart.seal_request(), flow),
+            VsEcall(Ace(UnsealData)) => unseal_data::handle(confidential_hart.unseal_request(), flow),
             VsEcall(Base(GetSpecVersion)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
             VsEcall(Base(GetImplId)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
             VsEcall(Base(GetImplVersion)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
-            VsEcall(Base(ProbeExtension)) => sbi_probe_extension::handle(confidential_hart.hypercall_request(), flow),
+            VsEcall(Base(ProbeExtension)) => sbi_probe_extension::handle(confidential_hart.probe_extension_request(), flow),
             VsEcall(Base(GetMvendorId)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
             VsEcall(Base(GetMarchid)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
             VsEcall(Base(GetMimpid)) => hypercall::handle(confidential_hart.hypercall_request(), flow),
+            VsEcall(Base(BaseExtension::Unknown(_, _))) => invalid_call::handle(flow),
             VsEcall(Ipi(SendIpi)) => sbi_ipi::handle(confidential_hart.sbi_ipi(), flow),
             VsEcall(Rfence(RemoteFenceI)) => sbi_ipi::handle(confidential_hart.sbi_remote_fence_i(), flow),
             VsEcall(Rfence(RemoteSfenceVma)) => sbi_ipi::handle(confidential_hart.sbi_remote_sfence_vma(), flow),
@@ -95,10 +122,45 @@ impl<'a> ConfidentialFlow<'a> {
             VsEcall(Hsm(HartSuspend)) => sbi_hsm_hart_suspend::handle(confidential_hart.sbi_hsm_hart_suspend(), flow),
             VsEcall(Hsm(HartGetStatus)) => sbi_hsm_hart_status::handle(confidential_hart.sbi_hsm_hart_status(), flow),
             VsEcall(Srst(SystemReset)) => sbi_srst::handle(flow),
+            VsEcall(SbiExtension::Pmu(function)) => sbi_pmu::handle(confidential_hart.sbi_pmu_request(function), flow),
+            VsEcall(SbiExtension::Nacl(_)) => invalid_call::handle(flow),
+            VsEcall(SbiExtension::Susp(_)) => invalid_call::handle(flow),
+            VsEcall(SbiExtension::Sta(StaExtension::SetSharedMemory)) => {
+                sbi_steal_time::handle(confidential_hart.steal_time_request(), flow)
+            }
+            VsEcall(SbiExtension::Sta(_)) => invalid_call::handle(flow),
+            VsEcall(SbiExtension::Sse(function)) => sbi_sse::handle(confidential_hart.sse_request(function), flow),
+            VsEcall(SbiExtension::Cppc(function)) => sbi_cppc::handle(confidential_hart.cppc_request(function), flow),
+            VsEcall(SbiExtension::Dbcn(DbcnExtension::ConsoleRead)) => sbi_dbcn_read::handle(confidential_hart.dbcn_read_request(), flow),
+            VsEcall(SbiExtension::Dbcn(_)) => invalid_call::handle(flow),
+            VsEcall(SbiExtension::Fwft(function)) => sbi_fwft::handle(confidential_hart.fwft_request(function), flow),
+            VsEcall(SbiExtension::TeeHost(_)) => invalid_call::handle(flow),
+            VsEcall(SbiExtension::TeeGuest(TeeGuestExtension::GetSealingKey)) => {
+                get_sealing_key::handle(confidential_hart.sealing_key_request(), flow)
+            }
+            VsEcall(SbiExtension::TeeGuest(TeeGuestExtension::GetEvidence)) => {
+                attestation_report::handle(confidential_hart.attestation_report_request(), flow)
+            }
+            VsEcall(SbiExtension::TeeGuest(TeeGuestExtension::ExtendMeasurement)) => {
+                extend_measurement::handle(confidential_hart.measurement_register_request(), flow)
+            }
+            VsEcall(SbiExtension::TeeGuest(_)) => invalid_call::handle(flow),
             VsEcall(SbiExtension::Unknown(_, _)) => invalid_call::handle(flow),
-            GuestLoadPageFault => guest_load_page_fault::handle(confidential_hart.guest_load_page_fault_request(), flow),
+            IllegalInstruction => illegal_instruction::handle(confidential_hart.illegal_instruction_request(), flow),
+            GuestInstructionPageFault => {
+                guest_instruction_page_fault::handle(confidential_hart.guest_page_fault(GuestMemoryAccess::Fetch), flow)
+            }
+            GuestLoadPageFault => guest_load_page_fault::handle(
+                confidential_hart.guest_page_fault(GuestMemoryAccess::Load),
+                confidential_hart.guest_load_page_fault_request(),
+                flow,
+            ),
             VirtualInstruction => virtual_instruction_request::handle(confidential_hart.virtual_instruction_request(), flow),
-            GuestStorePageFault => guest_store_page_fault::handle(confidential_hart.guest_store_page_fault_request(), flow),
+            GuestStorePageFault => guest_store_page_fault::handle(
+                confidential_hart.guest_page_fault(GuestMemoryAccess::Store),
+                confidential_hart.guest_store_page_fault_request(),
+                flow,
+            ),
             trap_reason => panic!("Bug: Incorrect interrupt delegation configuration: {:?}", trap_reason),
         }
     }
@@ -123,10 +185,16 @@ impl<'a> ConfidentialFlow<'a> {
                 confidential_flow.hardware_hart.guest_load_page_fault_result(request),
                 confidential_flow,
             ),
-            Some(GuestStorePageFault(request)) => guest_store_page_fault_result::handle(confidential_flow, request),
+            Some(GuestStorePageFault(request)) => guest_store_page_fault_result::handle(
+                confidential_flow.hardware_hart.guest_store_page_fault_result(request),
+                confidential_flow,
+            ),
             Some(SharePage(request)) => {
                 share_page_result::handle(confidential_flow.hardware_hart.share_page_result(), confidential_flow, request)
             }
+            Some(UnsharePage(request)) => {
+                unshare_page_result::handle(confidential_flow.hardware_hart.unshare_page_result(), confidential_flow, request)
+            }
             Some(SbiHsmHartStart()) => confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiHsmHartStart()),
             Some(SbiHsmHartStartPending()) => confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiHsmHartStartPending()),
             None => confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::Resume()),
@@ -138,8 +206,18 @@ impl<'a> ConfidentialFlow<'a> {
     pub fn exit_to_confidential_hart(self, transformation: ExposeToConfidentialVm) -> ! {
         self.hardware_hart.confidential_hart_mut().apply(transformation);
         self.hardware_hart.confidential_hart().load_volatile_control_status_registers_from_main_memory();
+        // Spins here while the confidential VM is paused.
+        self.hart_quiesce().enter_confidential_hart();
+        TlbShootdown::enter_confidential_hart(self.hardware_hart.hart_id());
         unsafe { exit_to_confidential_hart_asm() }
     }
+
+    /// Records why the confidential hart exits (see `PendingExit`), moves into the non-confidential flow, and applies the
+    /// transformation to the hypervisor's hart.
+    pub fn exit_to_hypervisor(self, transformation: ExposeToHypervisor) -> ! {
+        self.hardware_hart.confidential_hart_mut().set_pending_exit(PendingExit::from_transformation(&transformation));
+        self.into_non_confidential_flow().exit_to_hypervisor(transformation)
+    }
 }
 
 // ConfidentialFlow implementation that supports inter hart requests, including IPIs
@@ -183,6 +261,33 @@ impl<'a> ConfidentialFlow<'a> {
         // requests always succeeds.
         .unwrap();
     }
+
+    /// Returns true if an access to the given guest physical address must not be forwarded to the hypervisor for MMIO emulation, see
+    /// `ConfidentialVm::is_mmio_access_denied`.
+    pub fn is_mmio_access_denied(&self, address: usize) -> bool {
+        ControlData::try_confidential_vm(self.confidential_vm_id(), |confidential_vm| Ok(confidential_vm.is_mmio_access_denied(address)))
+            // We deny the access if we cannot check the policy, so that nothing is exposed to the hypervisor by mistake.
+            .unwrap_or(true)
+    }
+}
+
+// ConfidentialFlow implementation that supports the software update of accessed and dirty bits in G-stage page table entries.
+impl<'a> ConfidentialFlow<'a> {
+    /// Sets the accessed and dirty bits of the G-stage entry that caused the guest page fault. Returns error if the guest page fault
+    /// was not caused by these bits, in which case it must be handled like any other guest page fault.
+    pub fn update_accessed_dirty_bits(&self, page_fault: &GuestPageFault) -> Result<(), Error> {
+        ControlData::try_confidential_vm_mut(self.confidential_vm_id(), |mut confidential_vm| {
+            confidential_vm.memory_protector_mut().update_accessed_dirty_bits(page_fault.guest_physical_address(), page_fault.access())
+        })
+    }
+
+    /// Maps the confidential VM's page at the faulting guest physical address if the page is mapped on demand, see
+    /// `ConfidentialVmMemoryProtector::map_demand_page`. Returns error if the fault must be handled otherwise.
+    pub fn map_demand_page(&self, page_fault: &GuestPageFault) -> Result<(), Error> {
+        ControlData::try_confidential_vm_mut(self.confidential_vm_id(), |mut confidential_vm| {
+            confidential_vm.memory_protector_mut().map_demand_page(page_fault.guest_physical_address(), page_fault.access())
+        })
+    }
 }
 
 // ConfidentialFlow implementation that supports optional hart lifecycle transitions.
@@ -212,11 +317,104 @@ impl<'a> ConfidentialFlow<'a> {
     }
 }
 
+// ConfidentialFlow implementation that supports the virtualization of the performance monitoring unit.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_pmu(&mut self, request: SbiPmuRequest) -> Result<usize, Error> {
+        self.hardware_hart.handle_sbi_pmu(request)
+    }
+}
+
+// ConfidentialFlow implementation that supports the collaborative processor performance control.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_cppc(&mut self, request: CppcRequest) -> CppcResult {
+        self.hardware_hart.handle_sbi_cppc(request)
+    }
+}
+
+// ConfidentialFlow implementation that supports the negotiation of firmware features.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_fwft(&mut self, request: FwftRequest) -> FwftResult {
+        self.hardware_hart.handle_sbi_fwft(request)
+    }
+}
+
+// ConfidentialFlow implementation that supports the supervisor software events.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_sse(&mut self, request: SseRequest) -> Result<SseResult, Error> {
+        self.hardware_hart.handle_sbi_sse(request)
+    }
+}
+
+// ConfidentialFlow implementation that supports the debug console.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_dbcn_read(&mut self, request: DbcnReadRequest) -> Result<usize, Error> {
+        ControlData::try_confidential_vm_mut(self.confidential_vm_id(), |mut confidential_vm| {
+            self.hardware_hart.handle_sbi_dbcn_read(request, confidential_vm.memory_protector_mut())
+        })
+    }
+}
+
+// ConfidentialFlow implementation that supports the steal-time accounting.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn handle_sbi_steal_time(&mut self, request: StealTimeRequest) -> Result<(), Error> {
+        ControlData::try_confidential_vm_mut(self.confidential_vm_id(), |mut confidential_vm| {
+            self.hardware_hart.handle_sbi_steal_time(request, confidential_vm.memory_protector_mut())
+        })
+    }
+}
+
+// ConfidentialFlow implementation that supports the emulation of CSRs not accessible to confidential harts, e.g., the entropy source.
+impl<'a> ConfidentialFlow<'a> {
+    /// Emulates the trapped CSR access if the confidential VM's CSR emulation policy lists the CSR. Returns `None` otherwise.
+    pub fn emulate_csr_access(&mut self, access: &CsrAccess, instruction: usize) -> Option<ExposeToConfidentialVm> {
+        let confidential_hart = self.hardware_hart.confidential_hart_mut();
+        let emulation = confidential_hart.csr_emulation(access.csr())?;
+        Some(match confidential_hart.emulate_csr_access(access, emulation) {
+            Some(value) => ExposeToConfidentialVm::VirtualizedCsrResult(VirtualizedCsrResult::new(access.result_gpr(), value)),
+            None => ExposeToConfidentialVm::IllegalInstructionResult(IllegalInstructionResult::new(instruction)),
+        })
+    }
+}
+
+// ConfidentialFlow implementation that supports SBI calls answered by the security monitor instead of the hypervisor.
+impl<'a> ConfidentialFlow<'a> {
+    pub fn try_handle_sbi_call_locally(&self) -> Option<SbiResult> {
+        SbiVmRequest::try_handle_locally(self.hardware_hart.confidential_hart())
+    }
+}
+
+// ConfidentialFlow implementation that supports retrying operations that ran out of confidential memory.
+impl<'a> ConfidentialFlow<'a> {
+    /// Asks the hypervisor for confidential memory because the security monitor ran out of pages while changing the mapping of the
+    /// given guest physical address. The confidential hart's state is left unmodified and no request is pending, so the confidential
+    /// hart re-executes the trapped instruction once the hypervisor resumes it, retrying the operation.
+    pub fn retry_after_memory_fault(self, failed_address: usize, required_pages: usize) -> ! {
+        let notification = MemoryFaultNotification::new(failed_address, required_pages);
+        self.exit_to_hypervisor(ExposeToHypervisor::MemoryFaultNotification(notification))
+    }
+}
+
+// ConfidentialFlow implementation that supports the lazy floating-point context switch.
+impl<'a> ConfidentialFlow<'a> {
+    /// Restores the floating-point state of the confidential hart. See `HardwareHart::restore_fp_state` for details.
+    pub fn restore_fp_state(&mut self) {
+        self.hardware_hart.restore_fp_state();
+    }
+
+    pub fn is_fp_state_restored(&self) -> bool {
+        self.hardware_hart.is_fp_state_restored()
+    }
+}
+
 impl<'a> ConfidentialFlow<'a> {
     pub fn confidential_vm_id(&'a self) -> ConfidentialVmId {
         self.hardware_hart.confidential_hart().confidential_vm_id().expect("Bug: found dummy hart instead of a confidential hart")
     }
 
+    fn hart_quiesce(&self) -> &HartQuiesce {
+        self.hardware_hart.confidential_hart().hart_quiesce().expect("Bug: found dummy hart instead of a confidential hart")
+    }
+
     pub fn confidential_hart_id(&'a self) -> usize {
         self.hardware_hart.confidential_hart().confidential_hart_id()
     }
@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::handlers::sbi_probe_extension::SUPPORTED_EXTENSIONS;
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{AttestationReportRequest, ExposeToConfidentialVm, SbiResult};
+
+/// Handles a request from the confidential VM for a signed attestation report. On success, the report is written to the buffer in the
+/// confidential VM's memory and the size of the report is returned.
+pub fn handle(request: AttestationReportRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let allowed_extensions = SUPPORTED_EXTENSIONS.map(|(extension_id, _)| extension_id);
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.issue_attestation_report(&request, &allowed_extensions)
+    })
+    .and_then(|report_size| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(report_size))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{CertificateChainRequest, ExposeToConfidentialVm, SbiResult};
+
+/// Handles a request from the confidential VM for the certificate chain of the attestation key. The chain is written to the buffer in
+/// the confidential VM's memory and its size in bytes is returned.
+pub fn handle(request: CertificateChainRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.write_certificate_chain(&request)
+    })
+    .and_then(|certificate_chain_size| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(certificate_chain_size))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
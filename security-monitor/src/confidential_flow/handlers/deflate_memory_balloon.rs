@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, MemoryBalloonRequest, SbiResult};
+use crate::error::Error;
+
+/// Handles a request from the confidential VM to get back a region of its memory that it gave to the hypervisor before. The region
+/// must be exactly one of the regions given back. The security monitor backs it with zeroed pages of the confidential memory, so the
+/// confidential VM never observes the content of the memory from the time it did not own it.
+pub fn handle(request: Result<MemoryBalloonRequest, Error>, confidential_flow: ConfidentialFlow) -> ! {
+    let request = match request {
+        Ok(v) => v,
+        Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
+    };
+
+    match ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.memory_protector_mut().restore_region(request.address(), request.size_in_bytes())
+    }) {
+        Ok(_) => confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiResult(SbiResult::success(0))),
+        // The confidential hart repeats the request after the hypervisor provided enough memory to back the whole region.
+        Err(Error::OutOfPages()) => confidential_flow.retry_after_memory_fault(request.address().usize(), request.number_of_pages()),
+        Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
+    }
+}
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, MeasurementRegisterRequest, SbiResult};
+
+/// Handles a request from the confidential VM to extend one of its runtime measurement registers with a SHA-384 digest. The digest
+/// is read from the confidential VM's memory, so the hypervisor never learns what the confidential VM measured.
+pub fn handle(request: MeasurementRegisterRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.extend_measurement(request.index(), request.buffer_address())
+    })
+    .and_then(|_| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(0))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, GetMeasurementLogRequest, SbiResult};
+
+/// Handles a request from the confidential VM for its measurement log. The entries are written to the buffer in the confidential VM's
+/// memory and the total number of entries is returned in `a1`. The confidential VM forwards the log to a verifier together with an
+/// attestation report, so the verifier can check the measured components individually.
+pub fn handle(request: GetMeasurementLogRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.write_measurement_log(&request)
+    })
+    .and_then(|number_of_entries| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(number_of_entries))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
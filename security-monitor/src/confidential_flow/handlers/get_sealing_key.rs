@@ -0,0 +1,24 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::architecture::SbiError;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, SealingKeyRequest};
+use crate::error::Error;
+
+/// Handles a request of the confidential VM for its sealing key (CoVE `tvm_get_sealing_key`). On success, the key is written to the
+/// buffer in the confidential VM's memory and its size is returned. The request is denied with `SBI_ERR_DENIED` if the key would be
+/// exposed to the hypervisor.
+pub fn handle(request: SealingKeyRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.write_sealing_key(&request)
+    })
+    .and_then(|key_size| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(key_size))))
+    .unwrap_or_else(|error| match error {
+        Error::SealingKeyDenied() => ExposeToConfidentialVm::SbiResult(SbiResult::failure(SbiError::Denied.code())),
+        error => error.into_confidential_transformation(),
+    });
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
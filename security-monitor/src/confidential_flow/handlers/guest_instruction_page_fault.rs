@@ -0,0 +1,17 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::architecture::CAUSE_FETCH_ACCESS;
+use crate::core::transformations::{ExposeToConfidentialVm, GuestPageFault, MmioAccessFault};
+
+/// Resumes the confidential hart after setting the accessed bit of the page from which it fetches instructions, if the hardware does not
+/// set it, or after mapping the page if it is mapped on demand. The hypervisor cannot emulate instruction fetches, so any other fetch
+/// from memory that the confidential VM does not own is reflected to the confidential hart as an instruction access fault.
+pub fn handle(page_fault: GuestPageFault, confidential_flow: ConfidentialFlow) -> ! {
+    if confidential_flow.update_accessed_dirty_bits(&page_fault).is_ok() || confidential_flow.map_demand_page(&page_fault).is_ok() {
+        return confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::Resume());
+    }
+    let access_fault = MmioAccessFault::new(CAUSE_FETCH_ACCESS.into(), page_fault.fault_address());
+    confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::MmioAccessFault(access_fault))
+}
@@ -2,15 +2,31 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToHypervisor, GuestLoadPageFaultRequest, MmioLoadRequest, PendingRequest};
+use crate::core::architecture::CAUSE_LOAD_ACCESS;
+use crate::core::transformations::{
+    ExposeToConfidentialVm, ExposeToHypervisor, GuestLoadPageFaultRequest, GuestPageFault, MmioAccessFault, MmioLoadRequest, PendingRequest,
+};
 use crate::error::Error;
 
-pub fn handle(load_fault_request: Result<(GuestLoadPageFaultRequest, MmioLoadRequest), Error>, confidential_flow: ConfidentialFlow) -> ! {
+/// Forwards the MMIO load to the hypervisor for emulation. Loads from regions denied by the confidential VM's MMIO policy, or from the
+/// confidential VM's own memory, never reach the hypervisor. Instead, the confidential hart observes a load access fault. A load from the
+/// confidential VM's own page that faulted only because the hardware does not set the accessed bit is resumed without involving the
+/// hypervisor, like a load from a page that is mapped on demand and has not been accessed before.
+pub fn handle(
+    page_fault: GuestPageFault, load_fault_request: Result<(GuestLoadPageFaultRequest, MmioLoadRequest), Error>,
+    confidential_flow: ConfidentialFlow,
+) -> ! {
+    if confidential_flow.update_accessed_dirty_bits(&page_fault).is_ok() || confidential_flow.map_demand_page(&page_fault).is_ok() {
+        return confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::Resume());
+    }
     match load_fault_request {
+        Ok((_, mmio)) if confidential_flow.is_mmio_access_denied(mmio.fault_address()) => {
+            let access_fault = MmioAccessFault::new(CAUSE_LOAD_ACCESS.into(), mmio.stval());
+            confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::MmioAccessFault(access_fault))
+        }
         Ok((request, mmio)) => confidential_flow
             .set_pending_request(PendingRequest::GuestLoadPageFault(request))
-            .into_non_confidential_flow()
             .exit_to_hypervisor(ExposeToHypervisor::MmioLoadRequest(mmio)),
-        Err(error) => confidential_flow.into_non_confidential_flow().exit_to_hypervisor(error.into_non_confidential_transformation()),
+        Err(error) => confidential_flow.exit_to_hypervisor(error.into_non_confidential_transformation()),
     }
 }
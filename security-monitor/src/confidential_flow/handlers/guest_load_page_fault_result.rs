@@ -2,8 +2,14 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToConfidentialVm, GuestLoadPageFaultResult};
+use crate::core::transformations::{ExposeToConfidentialVm, GuestAccessFaultResult, GuestLoadPageFaultResult};
 
-pub fn handle(load_fault_result: GuestLoadPageFaultResult, confidential_flow: ConfidentialFlow) -> ! {
-    confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::GuestLoadPageFaultResult(load_fault_result))
+/// Completes the MMIO load of the confidential hart with the value provided by the hypervisor. If the hypervisor refused to emulate
+/// the load, the confidential hart observes a load page fault.
+pub fn handle(load_fault_result: Result<GuestLoadPageFaultResult, GuestAccessFaultResult>, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = match load_fault_result {
+        Ok(result) => ExposeToConfidentialVm::GuestLoadPageFaultResult(result),
+        Err(denied) => ExposeToConfidentialVm::GuestAccessFaultDenied(denied),
+    };
+    confidential_flow.exit_to_confidential_hart(transformation)
 }
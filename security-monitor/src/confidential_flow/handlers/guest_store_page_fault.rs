@@ -2,17 +2,32 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToHypervisor, GuestStorePageFaultRequest, MmioStoreRequest, PendingRequest};
+use crate::core::architecture::CAUSE_STORE_ACCESS;
+use crate::core::transformations::{
+    ExposeToConfidentialVm, ExposeToHypervisor, GuestPageFault, GuestStorePageFaultRequest, MmioAccessFault, MmioStoreRequest,
+    PendingRequest,
+};
 use crate::error::Error;
 
+/// Forwards the MMIO store to the hypervisor for emulation. Stores to regions denied by the confidential VM's MMIO policy, or to the
+/// confidential VM's own memory, never reach the hypervisor, so the stored value is not exposed. Instead, the confidential hart observes a
+/// store access fault. A store to the confidential VM's own page that faulted only because the hardware does not set the accessed or dirty
+/// bit is resumed without involving the hypervisor. So is a store to a page that is mapped on demand, once the page has been mapped.
 pub fn handle(
-    store_page_fault_request: Result<(GuestStorePageFaultRequest, MmioStoreRequest), Error>, confidential_flow: ConfidentialFlow,
+    page_fault: GuestPageFault, store_page_fault_request: Result<(GuestStorePageFaultRequest, MmioStoreRequest), Error>,
+    confidential_flow: ConfidentialFlow,
 ) -> ! {
+    if confidential_flow.update_accessed_dirty_bits(&page_fault).is_ok() || confidential_flow.map_demand_page(&page_fault).is_ok() {
+        return confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::Resume());
+    }
     match store_page_fault_request {
+        Ok((_, mmio)) if confidential_flow.is_mmio_access_denied(mmio.fault_address()) => {
+            let access_fault = MmioAccessFault::new(CAUSE_STORE_ACCESS.into(), mmio.stval());
+            confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::MmioAccessFault(access_fault))
+        }
         Ok((request, mmio)) => confidential_flow
             .set_pending_request(PendingRequest::GuestStorePageFault(request))
-            .into_non_confidential_flow()
             .exit_to_hypervisor(ExposeToHypervisor::MmioStoreRequest(mmio)),
-        Err(error) => confidential_flow.into_non_confidential_flow().exit_to_hypervisor(error.into_non_confidential_transformation()),
+        Err(error) => confidential_flow.exit_to_hypervisor(error.into_non_confidential_transformation()),
     }
 }
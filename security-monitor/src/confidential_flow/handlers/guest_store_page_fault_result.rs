@@ -2,9 +2,14 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToConfidentialVm, GuestStorePageFaultRequest, GuestStorePageFaultResult};
+use crate::core::transformations::{ExposeToConfidentialVm, GuestAccessFaultResult, GuestStorePageFaultResult};
 
-pub fn handle(confidential_flow: ConfidentialFlow, request: GuestStorePageFaultRequest) -> ! {
-    let transformation = ExposeToConfidentialVm::GuestStorePageFaultResult(GuestStorePageFaultResult::new(request));
+/// Completes the MMIO store of the confidential hart. If the hypervisor refused to emulate the store, the confidential hart observes
+/// a store page fault.
+pub fn handle(store_fault_result: Result<GuestStorePageFaultResult, GuestAccessFaultResult>, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = match store_fault_result {
+        Ok(result) => ExposeToConfidentialVm::GuestStorePageFaultResult(result),
+        Err(denied) => ExposeToConfidentialVm::GuestAccessFaultDenied(denied),
+    };
     confidential_flow.exit_to_confidential_hart(transformation)
 }
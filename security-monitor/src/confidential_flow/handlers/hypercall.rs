@@ -2,12 +2,14 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToHypervisor, PendingRequest, SbiRequest};
+use crate::core::transformations::{ExposeToConfidentialVm, ExposeToHypervisor, PendingRequest, SbiRequest};
 
-/// Handles a hypercall from a confidential hart to hypervisor.
+/// Handles a hypercall from a confidential hart to hypervisor. Calls that the security monitor answers itself (see
+/// `SbiVmRequest::try_handle_locally`) return to the confidential hart without involving the hypervisor.
 pub fn handle(sbi_request: SbiRequest, confidential_flow: ConfidentialFlow) -> ! {
-    confidential_flow
-        .set_pending_request(PendingRequest::SbiRequest())
-        .into_non_confidential_flow()
-        .exit_to_hypervisor(ExposeToHypervisor::SbiRequest(sbi_request))
+    if let Some(sbi_result) = confidential_flow.try_handle_sbi_call_locally() {
+        confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiResult(sbi_result))
+    }
+
+    confidential_flow.set_pending_request(PendingRequest::SbiRequest()).exit_to_hypervisor(ExposeToHypervisor::SbiRequest(sbi_request))
 }
@@ -0,0 +1,29 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, IllegalInstructionRequest, IllegalInstructionResult};
+
+/// Handles the illegal instruction exception raised by a confidential hart. The exception is never forwarded to the hypervisor, so
+/// the faulting instruction is not revealed to it.
+///
+/// Accesses to CSRs that are not exposed to confidential VMs, e.g., the hardware entropy source (`seed`) or `misa`, trap. We emulate
+/// the ones listed in the confidential VM's `CsrEmulationPolicy`.
+///
+/// A confidential hart is scheduled with the floating-point (FP) unit disabled, so its first FP instruction raises the illegal
+/// instruction exception. In such a case, we restore the confidential hart's FP state and resume the confidential hart at the same
+/// instruction. All other illegal instructions, and FP instructions executed after the FP state has been restored, are genuine and we
+/// reflect them to the confidential hart's VS-mode trap handler. If the hardware does not report the faulting instruction, an illegal
+/// instruction traps at most twice before the confidential hart observes it.
+pub fn handle(request: IllegalInstructionRequest, mut confidential_flow: ConfidentialFlow) -> ! {
+    let emulated_csr_access = request.csr_access().and_then(|access| confidential_flow.emulate_csr_access(&access, request.instruction()));
+    let transformation = if let Some(transformation) = emulated_csr_access {
+        transformation
+    } else if request.may_require_floating_point_unit() && !confidential_flow.is_fp_state_restored() {
+        confidential_flow.restore_fp_state();
+        ExposeToConfidentialVm::Resume()
+    } else {
+        ExposeToConfidentialVm::IllegalInstructionResult(IllegalInstructionResult::new(request.instruction()))
+    };
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
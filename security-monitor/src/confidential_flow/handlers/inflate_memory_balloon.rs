@@ -0,0 +1,24 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, MemoryBalloonRequest, SbiResult};
+use crate::error::Error;
+
+/// Handles a request from the confidential VM to give a region of its memory back to the hypervisor. The security monitor unmaps and
+/// clears the pages of the region and returns them to the page allocator, from which the hypervisor reclaims them like any other free
+/// confidential memory. Later accesses of the confidential VM to the region cause access faults. The request is rejected if the region
+/// is not aligned to 4KiB, or it contains a shared page or only a part of a huge page.
+pub fn handle(request: Result<MemoryBalloonRequest, Error>, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = request
+        .and_then(|request| {
+            ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+                confidential_vm.memory_protector_mut().remove_region(request.address(), request.size_in_bytes())
+            })
+        })
+        .and_then(|_| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
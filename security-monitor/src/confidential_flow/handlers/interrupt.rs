@@ -35,10 +35,10 @@ pub fn handle(mut confidential_flow: ConfidentialFlow) -> ! {
     if mip & (MIE_MTIP_MASK | MIE_STIP_MASK) > 0 {
         // inject timer interrupt to the hypervisor
         let transformation = ExposeToHypervisor::InterruptRequest(InterruptRequest::new(MIE_STIP));
-        confidential_flow.into_non_confidential_flow().exit_to_hypervisor(transformation)
+        confidential_flow.exit_to_hypervisor(transformation)
     } else {
         // resume the hypervisor, it will trap again in the security monitor to process these interrupts
         let transformation = ExposeToHypervisor::SbiResult(SbiResult::success(0));
-        confidential_flow.into_non_confidential_flow().exit_to_hypervisor(transformation)
+        confidential_flow.exit_to_hypervisor(transformation)
     }
 }
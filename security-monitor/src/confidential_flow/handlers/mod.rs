@@ -1,24 +1,44 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+pub mod attestation_report;
+pub mod certificate_chain;
+pub mod deflate_memory_balloon;
+pub mod extend_measurement;
+pub mod get_measurement_log;
+pub mod get_sealing_key;
+pub mod guest_instruction_page_fault;
 pub mod guest_load_page_fault;
 pub mod guest_load_page_fault_result;
 pub mod guest_store_page_fault;
 pub mod guest_store_page_fault_result;
 pub mod hypercall;
 pub mod hypercall_result;
+pub mod illegal_instruction;
+pub mod inflate_memory_balloon;
 pub mod interrupt;
 pub mod invalid_call;
+pub mod read_measurement;
+pub mod sbi_cppc;
+pub mod sbi_dbcn_read;
+pub mod sbi_fwft;
 pub mod sbi_hsm_hart_start;
 pub mod sbi_hsm_hart_status;
 pub mod sbi_hsm_hart_stop;
 pub mod sbi_hsm_hart_suspend;
 pub mod sbi_ipi;
+pub mod sbi_pmu;
 pub mod sbi_probe_extension;
 pub mod sbi_rfence_nop;
 pub mod sbi_srst;
+pub mod sbi_sse;
+pub mod sbi_steal_time;
+pub mod seal_data;
 pub mod share_page;
 pub mod share_page_result;
 pub mod shutdown_confidential_hart;
+pub mod unseal_data;
 pub mod unshare_page;
+pub mod unshare_page_result;
+pub mod verify_code_integrity;
 pub mod virtual_instruction_request;
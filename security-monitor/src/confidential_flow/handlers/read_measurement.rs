@@ -0,0 +1,23 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, MeasurementRegisterRequest, MeasurementRegisterValue, SbiResult};
+
+/// Handles a request from the confidential VM to read one of its measurement registers. The 48-byte value of the register is written
+/// to the buffer in the confidential VM's memory. If the confidential VM passes no buffer, the value is returned in registers instead
+/// (see `MeasurementRegisterValue`), which lets in-guest agents log measurements without setting up a buffer.
+pub fn handle(request: MeasurementRegisterRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        if request.is_register_read() {
+            let digest = confidential_vm.measurement_value(request.index())?;
+            return Ok(ExposeToConfidentialVm::MeasurementRegisterValue(MeasurementRegisterValue::new(digest)));
+        }
+        confidential_vm.read_measurement(request.index(), request.buffer_address())?;
+        Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(0)))
+    })
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
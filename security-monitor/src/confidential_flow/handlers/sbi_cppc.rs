@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{CppcRequest, CppcResult, ExposeToConfidentialVm, SbiResult};
+
+/// Handles the call of the SBI CPPC extension locally in the security monitor.
+///
+/// # Security
+///
+/// The performance and frequency registers reflect the load and the power state of the entire physical machine, and the performance
+/// hints of a confidential VM reflect its own load. The call is served from virtual registers of the confidential hart (see
+/// `CppcVirtualizer`), so it is neither forwarded to the hypervisor nor reads any performance CSR of the physical hart.
+pub fn handle(request: CppcRequest, mut confidential_flow: ConfidentialFlow) -> ! {
+    let sbi_result = match confidential_flow.handle_sbi_cppc(request) {
+        CppcResult::Success(value) => SbiResult::success(value),
+        CppcResult::Failure(error) => SbiResult::failure(error.code()),
+    };
+    confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiResult(sbi_result))
+}
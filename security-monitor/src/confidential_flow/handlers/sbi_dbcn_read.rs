@@ -0,0 +1,16 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{DbcnReadRequest, ExposeToConfidentialVm, SbiResult};
+use crate::error::Error;
+
+/// Handles the `console read` call of the SBI debug console extension locally in the security monitor. Forwarding the call would
+/// reveal the console input to the hypervisor, which reads it to its own memory before copying it to the guest.
+pub fn handle(request: Result<DbcnReadRequest, Error>, mut confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = request
+        .and_then(|request| confidential_flow.handle_sbi_dbcn_read(request))
+        .map(|number_of_bytes_read| ExposeToConfidentialVm::SbiResult(SbiResult::success(number_of_bytes_read)))
+        .unwrap_or_else(|error| error.into_confidential_transformation());
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, FwftRequest, FwftResult, SbiResult};
+
+/// Handles the call of the SBI FWFT extension locally in the security monitor.
+///
+/// # Security
+///
+/// Features are set only if their configuration is confined to the confidential hart (see `FwftVirtualizer`). Requests that would
+/// make the security monitor or the hypervisor handle traps on behalf of the confidential hart are denied. The call is never forwarded
+/// to the hypervisor, so the hypervisor cannot misreport the state of a feature.
+pub fn handle(request: FwftRequest, mut confidential_flow: ConfidentialFlow) -> ! {
+    let sbi_result = match confidential_flow.handle_sbi_fwft(request) {
+        FwftResult::Success(value) => SbiResult::success(value),
+        FwftResult::Failure(error) => SbiResult::failure(error.code()),
+    };
+    confidential_flow.exit_to_confidential_hart(ExposeToConfidentialVm::SbiResult(sbi_result))
+}
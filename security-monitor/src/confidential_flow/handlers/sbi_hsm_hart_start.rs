@@ -21,7 +21,6 @@ pub fn handle(request: SbiHsmHartStart, confidential_flow: ConfidentialFlow) ->
     }) {
         Ok(_) => confidential_flow
             .set_pending_request(PendingRequest::SbiHsmHartStart())
-            .into_non_confidential_flow()
             .exit_to_hypervisor(ExposeToHypervisor::SbiRequest(SbiRequest::kvm_hsm_hart_start(confidential_hart_id))),
         Err(error) => {
             // starting a confidential hart might fail if the incoming request is invalid. For example, the confidential
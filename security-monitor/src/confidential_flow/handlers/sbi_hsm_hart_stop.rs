@@ -12,9 +12,7 @@ use crate::core::transformations::{ExposeToHypervisor, SbiRequest};
 /// stopped confidential hart. Only another confidential hart of the confidential VM can start the confidential hart.
 pub fn handle(mut confidential_flow: ConfidentialFlow) -> ! {
     match confidential_flow.stop_confidential_hart() {
-        Ok(_) => confidential_flow
-            .into_non_confidential_flow()
-            .exit_to_hypervisor(ExposeToHypervisor::SbiRequest(SbiRequest::kvm_hsm_hart_stop())),
+        Ok(_) => confidential_flow.exit_to_hypervisor(ExposeToHypervisor::SbiRequest(SbiRequest::kvm_hsm_hart_stop())),
         Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
     }
 }
@@ -13,9 +13,7 @@ use crate::core::transformations::{ExposeToHypervisor, SbiHsmHartSuspend, SbiReq
 /// state.
 pub fn handle(request: SbiHsmHartSuspend, mut confidential_flow: ConfidentialFlow) -> ! {
     match confidential_flow.suspend_confidential_hart(request) {
-        Ok(_) => confidential_flow
-            .into_non_confidential_flow()
-            .exit_to_hypervisor(ExposeToHypervisor::SbiRequest(SbiRequest::kvm_hsm_hart_suspend())),
+        Ok(_) => confidential_flow.exit_to_hypervisor(ExposeToHypervisor::SbiRequest(SbiRequest::kvm_hsm_hart_suspend())),
         Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
     }
 }
@@ -0,0 +1,15 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiPmuRequest, SbiResult};
+
+/// Handles the call of the SBI PMU extension locally in the security monitor. Physical counters are shared by all security domains,
+/// so the confidential hart gets access only to virtual counters.
+pub fn handle(request: SbiPmuRequest, mut confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = confidential_flow
+        .handle_sbi_pmu(request)
+        .map(|value| ExposeToConfidentialVm::SbiResult(SbiResult::success(value)))
+        .unwrap_or_else(|error| error.into_confidential_transformation());
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
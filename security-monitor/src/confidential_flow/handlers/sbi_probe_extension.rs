@@ -3,20 +3,60 @@
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
 use crate::core::architecture::*;
-use crate::core::transformations::{ExposeToConfidentialVm, SbiRequest, SbiResult};
+use crate::core::transformations::{ExposeToConfidentialVm, ProbeExtensionRequest, SbiResult};
 
-/// Handles a hypercall from a confidential hart to hypervisor.
-pub fn handle(sbi_request: SbiRequest, confidential_flow: ConfidentialFlow) -> ! {
-    let extension_id = sbi_request.a0();
-    let response = match extension_id {
-        AceExtension::EXTID => 1,
-        BaseExtension::EXTID => 1,
-        IpiExtension::EXTID => 1,
-        RfenceExtension::EXTID => 1,
-        HsmExtension::EXTID => 1,
-        SrstExtension::EXTID => 1,
-        _ => 0,
-    };
+/// SBI extensions that the security monitor exposes to confidential VMs together with their implementation versions. Extensions
+/// not listed here are denied by the security monitor, even if the hypervisor supports them.
+pub const SUPPORTED_EXTENSIONS: [(usize, usize); 12] = [
+    (AceExtension::EXTID, 1),
+    (BaseExtension::EXTID, 1),
+    (IpiExtension::EXTID, 1),
+    (RfenceExtension::EXTID, 1),
+    (HsmExtension::EXTID, 1),
+    (SrstExtension::EXTID, 1),
+    (PmuExtension::EXTID, 1),
+    (StaExtension::EXTID, 1),
+    (SseExtension::EXTID, 1),
+    (CppcExtension::EXTID, 1),
+    (FwftExtension::EXTID, 1),
+    (TeeGuestExtension::EXTID, 1),
+];
+
+/// Handles the probe of an SBI extension by a confidential hart.
+///
+/// The probe is answered by the security monitor based on its own policy and never forwarded to the hypervisor. Otherwise, a
+/// confidential VM could discover an extension that the security monitor will later deny. Returns 0 for denied extensions and the
+/// implementation version for supported ones.
+pub fn handle(request: ProbeExtensionRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let response = implementation_version(request.extension_id);
     let transformation = ExposeToConfidentialVm::SbiResult(SbiResult::success(response));
     confidential_flow.exit_to_confidential_hart(transformation)
 }
+
+fn implementation_version(extension_id: usize) -> usize {
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .find(|(supported_extension_id, _)| *supported_extension_id == extension_id)
+        .map_or(0, |(_, implementation_version)| *implementation_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_extension_probes_with_implementation_version() {
+        for (extension_id, version) in SUPPORTED_EXTENSIONS {
+            assert_eq!(implementation_version(extension_id), version);
+            assert_ne!(version, 0);
+        }
+    }
+
+    #[test]
+    fn denied_extension_probes_as_unavailable() {
+        // These extensions are implemented by the hypervisor or the firmware but denied to confidential VMs.
+        for extension_id in [NaclExtension::EXTID, SuspExtension::EXTID, DbcnExtension::EXTID, TeeHostExtension::EXTID, 0x0a00_0000] {
+            assert_eq!(implementation_version(extension_id), 0);
+        }
+    }
+}
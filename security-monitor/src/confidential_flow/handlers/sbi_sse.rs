@@ -0,0 +1,15 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, SseRequest};
+
+/// Handles the call of the SBI supervisor software events extension locally in the security monitor. Delivering an event changes the
+/// confidential hart's program counter, which the hypervisor must not control.
+pub fn handle(request: SseRequest, mut confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = confidential_flow
+        .handle_sbi_sse(request)
+        .map(ExposeToConfidentialVm::SseResult)
+        .unwrap_or_else(|error| error.into_confidential_transformation());
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
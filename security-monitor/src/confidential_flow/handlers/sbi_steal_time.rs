@@ -0,0 +1,16 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, StealTimeRequest};
+use crate::error::Error;
+
+/// Handles the `set shared memory` call of the SBI steal-time accounting extension locally in the security monitor. The hypervisor
+/// cannot write to the confidential VM's memory, so the security monitor measures and publishes the steal time on its behalf.
+pub fn handle(request: Result<StealTimeRequest, Error>, mut confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = request
+        .and_then(|request| confidential_flow.handle_sbi_steal_time(request))
+        .map(|_| ExposeToConfidentialVm::SbiResult(SbiResult::success(0)))
+        .unwrap_or_else(|error| error.into_confidential_transformation());
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
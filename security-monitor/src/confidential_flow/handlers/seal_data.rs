@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, SealRequest};
+
+/// Handles a request from the confidential VM to seal data, e.g., a disk encryption key, to its launch measurement. The confidential
+/// VM can store the sealed data outside of its memory and unseal it after a reboot. On success, the sealed data is written to the
+/// buffer in the confidential VM's memory and its size is returned.
+pub fn handle(request: SealRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.seal_data(&request)
+    })
+    .and_then(|sealed_data_size| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(sealed_data_size))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
@@ -2,6 +2,7 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
 use crate::core::transformations::{ExposeToHypervisor, PendingRequest, SbiRequest, SharePageRequest};
 use crate::error::Error;
 
@@ -9,12 +10,19 @@ use crate::error::Error;
 ///
 /// Control flows to the hypervisor when the sharing of the given `guest physical address` is allowed. The hypervisor is requested to
 /// allocate a page of non-confidential memory and return back the `host physical address` of this page. Control flows back to the
-/// confidential hart if the request was invalid, e.g., the `guest physical address` was not correct.
+/// confidential hart if the request was invalid, e.g., the `guest physical address` was not correct or the page overlaps a page that
+/// is already shared.
 pub fn handle(request: Result<(SharePageRequest, SbiRequest), Error>, confidential_flow: ConfidentialFlow) -> ! {
+    let request = request.and_then(|(share_page_request, sbi_request)| {
+        ControlData::try_confidential_vm(confidential_flow.confidential_vm_id(), |confidential_vm| {
+            let address = share_page_request.confidential_vm_virtual_address();
+            confidential_vm.shared_page_table().ensure_not_shared(address, share_page_request.page_size())
+        })?;
+        Ok((share_page_request, sbi_request))
+    });
     match request {
         Ok((share_page_request, sbi_request)) => confidential_flow
             .set_pending_request(PendingRequest::SharePage(share_page_request))
-            .into_non_confidential_flow()
             .exit_to_hypervisor(ExposeToHypervisor::SbiRequest(sbi_request)),
         Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
     }
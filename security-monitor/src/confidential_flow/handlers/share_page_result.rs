@@ -5,10 +5,13 @@ use crate::confidential_flow::ConfidentialFlow;
 use crate::core::control_data::ControlData;
 use crate::core::page_allocator::SharedPage;
 use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, SharePageRequest, SharePageResult};
+use crate::error::Error;
 
 /// Handles a response from the hypervisor about the creation of a shared page.
 ///
-/// Control always flows to the confidential VM.
+/// Control flows to the confidential VM unless the security monitor ran out of memory while mapping the shared page. Another
+/// confidential hart might have shared an overlapping page while the hypervisor handled this request, so the shared page table is
+/// checked again before the page is mapped.
 pub fn handle(share_page_result: SharePageResult, confidential_flow: ConfidentialFlow, request: SharePageRequest) -> ! {
     let confidential_vm_id = confidential_flow.confidential_vm_id();
 
@@ -19,16 +22,26 @@ pub fn handle(share_page_result: SharePageResult, confidential_flow: Confidentia
         confidential_flow.exit_to_confidential_hart(transformation);
     }
 
+    let address = request.confidential_vm_virtual_address().usize();
     let shared_page = match SharedPage::new(share_page_result.hypervisor_page_address(), request) {
         Ok(v) => v,
         Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
     };
 
-    let transformation = ControlData::try_confidential_vm_mut(confidential_vm_id, |mut confidential_vm| {
-        confidential_vm.memory_protector_mut().map_shared_page(shared_page)
-    })
-    .and_then(|_| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(0))))
-    .unwrap_or_else(|error| error.into_confidential_transformation());
+    // Mapping the shared page might require new page tables. If there are no free pages, the hypervisor is asked for memory and the
+    // confidential hart repeats the request afterwards.
+    let result = ControlData::try_confidential_vm_mut(confidential_vm_id, |mut confidential_vm| {
+        let required_pages = confidential_vm.memory_protector().max_pages_to_map_page();
+        match confidential_vm.share_page(shared_page) {
+            Err(Error::OutOfPages()) => Ok(Some(required_pages)),
+            result => result.map(|_| None),
+        }
+    });
+    let transformation = match result {
+        Ok(Some(required_pages)) => confidential_flow.retry_after_memory_fault(address, required_pages),
+        Ok(None) => ExposeToConfidentialVm::SbiResult(SbiResult::success(0)),
+        Err(error) => error.into_confidential_transformation(),
+    };
 
     confidential_flow.exit_to_confidential_hart(transformation)
 }
@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, UnsealRequest};
+
+/// Handles a request from the confidential VM to recover data it sealed before. Unsealing fails if the data was sealed by a
+/// confidential VM with a different launch measurement, so a modified kernel image cannot recover secrets of the original one. On
+/// success, the recovered data is written to the buffer in the confidential VM's memory and its size is returned.
+pub fn handle(request: UnsealRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.unseal_data(&request)
+    })
+    .and_then(|data_size| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(data_size))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
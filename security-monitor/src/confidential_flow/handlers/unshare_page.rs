@@ -3,19 +3,30 @@
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
 use crate::core::control_data::ControlData;
-use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, UnsharePageRequest};
+use crate::core::transformations::{ExposeToHypervisor, PendingRequest, SbiRequest, UnsharePageRequest};
 use crate::error::Error;
 
 /// Handles a request from the confidential VM to unshare a page that was previously shared with the hypervisor.
+///
+/// The security monitor first makes the page private again by mapping a zeroed page of the confidential memory at the page's `guest
+/// physical address`. Only then control flows to the hypervisor, which is informed that it can reuse its page. Control flows back to
+/// the confidential hart if the request was invalid, e.g., the `guest physical address` did not map a shared page.
 pub fn handle(request: Result<UnsharePageRequest, Error>, confidential_flow: ConfidentialFlow) -> ! {
-    let transformation = match request {
-        Ok(unshare_page_request) => ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
-            confidential_vm.memory_protector_mut().unmap_shared_page(unshare_page_request.confidential_vm_virtual_address())
-        })
-        .and_then(|_| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(0))))
-        .unwrap_or_else(|error| error.into_confidential_transformation()),
-        Err(error) => error.into_confidential_transformation(),
+    let unshare_page_request = match request {
+        Ok(v) => v,
+        Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
     };
 
-    confidential_flow.exit_to_confidential_hart(transformation)
+    let address = unshare_page_request.confidential_vm_virtual_address();
+    match ControlData::try_confidential_vm_mut(confidential_flow.confidential_vm_id(), |mut confidential_vm| {
+        confidential_vm.unshare_page(address)
+    }) {
+        Ok(shared_page) => confidential_flow.set_pending_request(PendingRequest::UnsharePage(unshare_page_request)).exit_to_hypervisor(
+            ExposeToHypervisor::SbiRequest(SbiRequest::kvm_ace_page_out(address.usize(), shared_page.non_confidential_address())),
+        ),
+        // Backing the address with a page of the confidential memory requires a free page. The confidential hart repeats the request
+        // after the hypervisor provided more memory.
+        Err(Error::OutOfPages()) => confidential_flow.retry_after_memory_fault(address.usize(), 1),
+        Err(error) => confidential_flow.exit_to_confidential_hart(error.into_confidential_transformation()),
+    }
 }
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, UnsharePageRequest, UnsharePageResult};
+
+/// Handles a response from the hypervisor about releasing the page that the confidential VM stopped sharing. The page has already
+/// been made private before the hypervisor was informed, so nothing must be rolled back when the hypervisor reports an error.
+///
+/// Control always flows to the confidential VM.
+pub fn handle(unshare_page_result: UnsharePageResult, confidential_flow: ConfidentialFlow, _request: UnsharePageRequest) -> ! {
+    let transformation = if unshare_page_result.is_error() {
+        ExposeToConfidentialVm::SbiResult(SbiResult::failure(unshare_page_result.response_code()))
+    } else {
+        ExposeToConfidentialVm::SbiResult(SbiResult::success(0))
+    };
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::confidential_flow::ConfidentialFlow;
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToConfidentialVm, SbiResult, VerifyCodeIntegrityRequest};
+
+/// Handles a request from the confidential VM to compare the current content of a code region with its content at launch. Returns 1
+/// if the content is unchanged and 0 otherwise. The region must be one of the code regions that the confidential VM declared in its
+/// device tree, because only those regions were hashed when the confidential VM was created.
+pub fn handle(request: VerifyCodeIntegrityRequest, confidential_flow: ConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(confidential_flow.confidential_vm_id(), |confidential_vm| {
+        confidential_vm.verify_code_integrity(request.address(), request.size_in_bytes())
+    })
+    .and_then(|is_unchanged| Ok(ExposeToConfidentialVm::SbiResult(SbiResult::success(is_unchanged as usize))))
+    .unwrap_or_else(|error| error.into_confidential_transformation());
+
+    confidential_flow.exit_to_confidential_hart(transformation)
+}
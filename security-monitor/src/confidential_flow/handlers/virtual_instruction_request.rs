@@ -2,17 +2,24 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
-use crate::core::transformations::{ExposeToConfidentialVm, VirtualInstructionRequest, VirtualInstructionResult};
+use crate::core::transformations::{ExposeToConfidentialVm, IllegalInstructionResult, VirtualInstructionRequest, VirtualInstructionResult};
 
 const WFI_INSTRUCTION: usize = 0x10500073;
 
-pub fn handle(request: VirtualInstructionRequest, confidential_flow: ConfidentialFlow) -> ! {
+/// Handles the virtual instruction exception raised by a confidential hart. Reads of counters trap if they are not enabled in
+/// `hcounteren`, either by the hypervisor or because the confidential VM's `CsrEmulationPolicy` virtualizes them. We emulate accesses
+/// to CSRs listed in the policy, so that, e.g., the confidential hart observes the same time regardless of whether `rdtime` traps. WFI
+/// traps according to `WfiPolicy` and completes as a no-op, which the specification permits because WFI is only a hint. Other
+/// instructions, e.g., accesses to CSRs gated by `Smstateen`, raise the illegal instruction exception in the confidential hart.
+pub fn handle(request: VirtualInstructionRequest, mut confidential_flow: ConfidentialFlow) -> ! {
     let transformation = if request.instruction == WFI_INSTRUCTION {
         ExposeToConfidentialVm::VirtualInstructionResult(VirtualInstructionResult::new(request.instruction_length))
+    } else if let Some(transformation) =
+        request.csr_access().and_then(|access| confidential_flow.emulate_csr_access(&access, request.instruction))
+    {
+        transformation
     } else {
-        // TODO: add support for some CSR manipulation
-        // TODO: for not supported instructions, inject illegal instruction exception to the guest
-        panic!("Not supported virtual instruction: {:x}", request.instruction);
+        ExposeToConfidentialVm::IllegalInstructionResult(IllegalInstructionResult::new(request.instruction))
     };
     confidential_flow.exit_to_confidential_hart(transformation)
 }
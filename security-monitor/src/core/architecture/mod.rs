@@ -5,9 +5,11 @@ pub use riscv::control_status_registers::*;
 pub use riscv::fence::*;
 pub use riscv::hart_architectural_state::*;
 pub use riscv::{
-    are_bits_enabled, decode_result_register, disable_bit, disable_bits, enable_bit, enable_bits, is_bit_enabled, put_hart_to_sleep,
-    specification, AceExtension, BaseExtension, FloatingPointRegisters, GeneralPurposeRegister, GeneralPurposeRegisters,
-    HartLifecycleState, HsmExtension, IpiExtension, RfenceExtension, SbiExtension, SrstExtension, TrapCause,
+    are_bits_enabled, decode_result_register, decode_store_size, disable_bit, disable_bits, enable_bit, enable_bits, halt_hart,
+    is_bit_enabled, put_hart_to_sleep, specification, transformed_instruction, AceExtension, BaseExtension, CppcExtension, DbcnExtension,
+    FloatingPointRegisters, FwftExtension, GeneralPurposeRegister, GeneralPurposeRegisters, HartLifecycleState, HsmExtension, IpiExtension,
+    NaclExtension, PmuExtension, RfenceExtension, SbiError, SbiExtension, SrstExtension, SseExtension, StaExtension, SuspExtension,
+    TeeGuestExtension, TeeHostExtension, TrapCause,
 };
 
 mod riscv;
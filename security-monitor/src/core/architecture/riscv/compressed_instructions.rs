@@ -40,6 +40,11 @@ pub fn decode_result_register(mtinst: usize) -> Result<GeneralPurposeRegister, E
             const INSN_MASK_C_LWSP: usize = 0xe003;
             const INSN_MATCH_C_SWSP: usize = 0xc002;
             const INSN_MASK_C_SWSP: usize = 0xe003;
+            // Zcb extension
+            const INSN_MATCH_C_SB: usize = 0x8800;
+            const INSN_MASK_C_SB: usize = 0xfc03;
+            const INSN_MATCH_C_SH: usize = 0x8c00;
+            const INSN_MASK_C_SH: usize = 0xfc43;
 
             let log_regbytes = 3; // for 64b!
             let shift_right = |x: usize, y: isize| {
@@ -80,6 +85,9 @@ pub fn decode_result_register(mtinst: usize) -> Result<GeneralPurposeRegister, E
                 let index = shift_right(mtinst, SH_RS2C as isize - log_regbytes as isize) & reg_mask;
                 let index = index / 8;
                 Ok(index as u32)
+            } else if mtinst & INSN_MASK_C_SB == INSN_MATCH_C_SB || mtinst & INSN_MASK_C_SH == INSN_MATCH_C_SH {
+                let index = 8 + rv_x(mtinst, SH_RS2C, 3);
+                Ok(index as u32)
             } else {
                 Err(Error::InvalidRiscvInstruction(mtinst))
             }
@@ -87,3 +95,73 @@ pub fn decode_result_register(mtinst: usize) -> Result<GeneralPurposeRegister, E
     }?;
     Ok(GeneralPurposeRegister::from_index(register_index as usize).ok_or(Error::InvalidRiscvInstruction(mtinst))?)
 }
+
+/// Returns the number of bytes written by the store instruction. Recognizes compressed stores, including `c.sb` and `c.sh` from the
+/// Zcb extension.
+pub fn decode_store_size(mtinst: usize) -> Result<usize, Error> {
+    use riscv_decode::Instruction::{Sb, Sd, Sh, Sw};
+    // (mask, match, size in bytes)
+    const COMPRESSED_STORES: [(usize, usize, usize); 6] =
+        [(0xfc03, 0x8800, 1), (0xfc43, 0x8c00, 2), (0xe003, 0xc000, 4), (0xe003, 0xc002, 4), (0xe003, 0xe000, 8), (0xe003, 0xe002, 8)];
+    match riscv_decode::decode(mtinst as u32) {
+        Ok(Sb(_)) => Ok(1),
+        Ok(Sh(_)) => Ok(2),
+        Ok(Sw(_)) => Ok(4),
+        Ok(Sd(_)) => Ok(8),
+        _ => COMPRESSED_STORES
+            .iter()
+            .find(|(mask, pattern, _)| mtinst & mask == *pattern)
+            .map(|(_, _, size)| *size)
+            .ok_or(Error::InvalidRiscvInstruction(mtinst)),
+    }
+}
+
+/// Returns the transformed instruction that the H-extension defines for `htinst` on guest-page faults caused by explicit loads and
+/// stores: the fields encoding the address offset (`rs1` and the immediate) are zeroed, bit 0 is set, and bit 1 stays cleared if the
+/// trapped instruction was compressed. For other instructions, zero is returned, which informs that no instruction is provided.
+pub fn transformed_instruction(mtinst: usize) -> usize {
+    const OPCODE_MASK: usize = 0x7c;
+    const OPCODE_LOAD: usize = 0x00;
+    const OPCODE_STORE: usize = 0x20;
+    // Keeps rd, funct3, and opcode
+    const LOAD_MASK: usize = 0x0000_7fff;
+    // Keeps rs2, funct3, and opcode
+    const STORE_MASK: usize = 0x01f0_707f;
+    let mask = match mtinst & OPCODE_MASK {
+        OPCODE_LOAD => LOAD_MASK,
+        OPCODE_STORE => STORE_MASK,
+        _ => return 0,
+    };
+    (mtinst & mask) | 0x1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_keeps_destination_register_and_width() {
+        // lw a0, 8(a1)
+        assert_eq!(transformed_instruction(0x0085a503), 0x00002503);
+    }
+
+    #[test]
+    fn store_keeps_source_register_and_width() {
+        // sd a5, 16(a0)
+        assert_eq!(transformed_instruction(0x00f53823), 0x00f03023);
+    }
+
+    #[test]
+    fn compressed_access_reported_by_hardware_stays_compressed() {
+        // The hardware reports `c.lw a0, 0(a1)` as the transformed `lw a0` with bit 1 cleared.
+        assert_eq!(transformed_instruction(0x00002501), 0x00002501);
+    }
+
+    #[test]
+    fn other_instructions_are_not_provided() {
+        // add a0, a0, a1
+        assert_eq!(transformed_instruction(0x00b50533), 0);
+        // A compressed instruction that was not transformed by the hardware, c.lw a0, 0(a1)
+        assert_eq!(transformed_instruction(0x4188), 0);
+    }
+}
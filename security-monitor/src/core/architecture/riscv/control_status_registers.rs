@@ -7,11 +7,14 @@
 #![allow(unused)]
 pub use super::specification::*;
 use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 pub struct ControlStatusRegister {
     pub mepc: ReadWriteRiscvCsr<CSR_MEPC>,
     pub mcause: ReadWriteRiscvCsr<CSR_MCAUSE>,
     pub medeleg: ReadWriteRiscvCsr<CSR_MEDELEG>,
+    pub menvcfg: ReadWriteRiscvCsr<CSR_MENVCFG>,
+    pub mstateen0: ReadWriteRiscvCsr<CSR_MSTATEEN0>,
     pub mideleg: ReadWriteRiscvCsr<CSR_MIDELEG>,
     pub mie: ReadWriteRiscvCsr<CSR_MIE>,
     pub mip: ReadWriteRiscvCsr<CSR_MIP>,
@@ -22,6 +25,14 @@ pub struct ControlStatusRegister {
     pub mtvec: ReadWriteRiscvCsr<CSR_MTVEC>,
     pub mscratch: ReadWriteRiscvCsr<CSR_MSCRATCH>,
     pub mhartid: ReadWriteRiscvCsr<CSR_MHARTID>,
+    pub misa: ReadWriteRiscvCsr<CSR_MISA>,
+    pub mvendorid: ReadWriteRiscvCsr<CSR_MVENDORID>,
+    pub marchid: ReadWriteRiscvCsr<CSR_MARCHID>,
+    pub mimpid: ReadWriteRiscvCsr<CSR_MIMPID>,
+    pub mcycle: ReadWriteRiscvCsr<CSR_MCYCLE>,
+    pub minstret: ReadWriteRiscvCsr<CSR_MINSTRET>,
+    pub seed: ReadWriteRiscvCsr<CSR_SEED>,
+    pub time: ReadWriteRiscvCsr<CSR_TIME>,
     // S-mode
     pub sstatus: ReadWriteRiscvCsr<CSR_SSTATUS>,
     pub sepc: ReadWriteRiscvCsr<CSR_SEPC>,
@@ -33,8 +44,10 @@ pub struct ControlStatusRegister {
     pub stval: ReadWriteRiscvCsr<CSR_STVAL>,
     pub sscratch: ReadWriteRiscvCsr<CSR_SSCRATCH>,
     pub stimecmp: ReadWriteRiscvCsr<CSR_STIMECMP>,
+    pub sstateen0: ReadWriteRiscvCsr<CSR_SSTATEEN0>,
     // HS-mode
     pub hstatus: ReadWriteRiscvCsr<CSR_HSTATUS>,
+    pub hcounteren: ReadWriteRiscvCsr<CSR_HCOUNTEREN>,
     pub hedeleg: ReadWriteRiscvCsr<CSR_HEDELEG>,
     pub hideleg: ReadWriteRiscvCsr<CSR_HIDELEG>,
     pub htinst: ReadWriteRiscvCsr<CSR_HTINST>,
@@ -44,6 +57,8 @@ pub struct ControlStatusRegister {
     pub hie: ReadWriteRiscvCsr<CSR_HIE>,
     pub hip: ReadWriteRiscvCsr<CSR_HIP>,
     pub hgatp: ReadWriteRiscvCsr<CSR_HGATP>,
+    pub henvcfg: ReadWriteRiscvCsr<CSR_HENVCFG>,
+    pub hstateen0: ReadWriteRiscvCsr<CSR_HSTATEEN0>,
     // VS-mode
     pub vsstatus: ReadWriteRiscvCsr<CSR_VSSTATUS>,
     pub vsie: ReadWriteRiscvCsr<CSR_VSIE>,
@@ -63,12 +78,17 @@ pub struct ControlStatusRegister {
     pub pmpcfg0: ReadWriteRiscvCsr<CSR_PMPCFG0>,
     pub pmpaddr0: ReadWriteRiscvCsr<CSR_PMPADDR0>,
     pub pmpaddr1: ReadWriteRiscvCsr<CSR_PMPADDR1>,
+    pub pmpaddr2: ReadWriteRiscvCsr<CSR_PMPADDR2>,
+    pub pmpaddr3: ReadWriteRiscvCsr<CSR_PMPADDR3>,
+    pub pmpaddr4: ReadWriteRiscvCsr<CSR_PMPADDR4>,
 }
 
 pub const CSR: &ControlStatusRegister = &ControlStatusRegister {
     mepc: ReadWriteRiscvCsr::new(),
     mcause: ReadWriteRiscvCsr::new(),
     medeleg: ReadWriteRiscvCsr::new(),
+    menvcfg: ReadWriteRiscvCsr::new(),
+    mstateen0: ReadWriteRiscvCsr::new(),
     mideleg: ReadWriteRiscvCsr::new(),
     mie: ReadWriteRiscvCsr::new(),
     mip: ReadWriteRiscvCsr::new(),
@@ -79,6 +99,14 @@ pub const CSR: &ControlStatusRegister = &ControlStatusRegister {
     mtvec: ReadWriteRiscvCsr::new(),
     mscratch: ReadWriteRiscvCsr::new(),
     mhartid: ReadWriteRiscvCsr::new(),
+    misa: ReadWriteRiscvCsr::new(),
+    mvendorid: ReadWriteRiscvCsr::new(),
+    marchid: ReadWriteRiscvCsr::new(),
+    mimpid: ReadWriteRiscvCsr::new(),
+    mcycle: ReadWriteRiscvCsr::new(),
+    minstret: ReadWriteRiscvCsr::new(),
+    seed: ReadWriteRiscvCsr::new(),
+    time: ReadWriteRiscvCsr::new(),
     // S-mode
     sstatus: ReadWriteRiscvCsr::new(),
     sepc: ReadWriteRiscvCsr::new(),
@@ -90,8 +118,10 @@ pub const CSR: &ControlStatusRegister = &ControlStatusRegister {
     stval: ReadWriteRiscvCsr::new(),
     sscratch: ReadWriteRiscvCsr::new(),
     stimecmp: ReadWriteRiscvCsr::new(),
+    sstateen0: ReadWriteRiscvCsr::new(),
     // HS-mode
     hstatus: ReadWriteRiscvCsr::new(),
+    hcounteren: ReadWriteRiscvCsr::new(),
     hedeleg: ReadWriteRiscvCsr::new(),
     hideleg: ReadWriteRiscvCsr::new(),
     htinst: ReadWriteRiscvCsr::new(),
@@ -101,6 +131,8 @@ pub const CSR: &ControlStatusRegister = &ControlStatusRegister {
     hie: ReadWriteRiscvCsr::new(),
     hip: ReadWriteRiscvCsr::new(),
     hgatp: ReadWriteRiscvCsr::new(),
+    henvcfg: ReadWriteRiscvCsr::new(),
+    hstateen0: ReadWriteRiscvCsr::new(),
     // VS-mode
     vsstatus: ReadWriteRiscvCsr::new(),
     vsie: ReadWriteRiscvCsr::new(),
@@ -120,6 +152,9 @@ pub const CSR: &ControlStatusRegister = &ControlStatusRegister {
     pmpcfg0: ReadWriteRiscvCsr::new(),
     pmpaddr0: ReadWriteRiscvCsr::new(),
     pmpaddr1: ReadWriteRiscvCsr::new(),
+    pmpaddr2: ReadWriteRiscvCsr::new(),
+    pmpaddr3: ReadWriteRiscvCsr::new(),
+    pmpaddr4: ReadWriteRiscvCsr::new(),
 };
 
 #[derive(Copy, Clone)]
@@ -146,6 +181,20 @@ impl<const V: u16> ReadWriteRiscvCsr<V> {
         }
     }
 
+    /// Atomically writes the CSR and returns its previous value. Some CSRs, like `seed`, must be accessed with a read-write
+    /// instruction.
+    #[inline]
+    pub fn swap(&self, val_to_set: usize) -> usize {
+        let r: usize;
+        unsafe {
+            asm!("csrrw {rd}, {csr}, {rs1}",
+                 rd = out(reg) r,
+                 csr = const V,
+                 rs1 = in(reg) val_to_set);
+        }
+        r
+    }
+
     #[inline]
     pub fn read_and_set_bit(&self, bit: usize) -> usize {
         self.read_and_set_bits(1 << bit)
@@ -181,23 +230,81 @@ impl<const V: u16> ReadWriteRiscvCsr<V> {
     }
 }
 
+/// The bitmap of G-stage address translation modes implemented by the hardware, indexed by the codes of the modes. It is set during
+/// the boot by `HgatpMode::probe_supported_modes`.
+static SUPPORTED_HGATP_MODES: AtomicUsize = AtomicUsize::new(0);
+static IS_SMSTATEEN_ENABLED: AtomicBool = AtomicBool::new(false);
+
 #[repr(usize)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum HgatpMode {
+    Sv39x4 = 8,
+    Sv48x4 = 9,
     Sv57x4 = 10,
 }
 
 impl HgatpMode {
+    // All modes ordered by the size of the guest physical address space they translate, starting from the smallest one.
+    const ALL: [HgatpMode; 3] = [HgatpMode::Sv39x4, HgatpMode::Sv48x4, HgatpMode::Sv57x4];
+
     fn code(self) -> usize {
         self as usize
     }
 
     fn from_code(code: usize) -> Option<Self> {
         match code {
+            8 => Some(HgatpMode::Sv39x4),
+            9 => Some(HgatpMode::Sv48x4),
             10 => Some(HgatpMode::Sv57x4),
             _ => None,
         }
     }
+
+    /// Returns the width of guest physical addresses translated in this mode. It is 2 bits wider than the width of virtual addresses in
+    /// the corresponding S-stage mode because the root page table of the G-stage translation is 4 times larger.
+    pub fn guest_physical_address_bits(&self) -> usize {
+        match self {
+            HgatpMode::Sv39x4 => 41,
+            HgatpMode::Sv48x4 => 50,
+            HgatpMode::Sv57x4 => 59,
+        }
+    }
+
+    /// Discovers which modes the hardware implements. The write of an unsupported mode to `hgatp` has no effect, so a mode is supported
+    /// if it reads back. The security monitor assumes that all harts implement the same modes, thus it probes only the boot hart.
+    pub fn probe_supported_modes() {
+        let original_hgatp = CSR.hgatp.read();
+        let supported_modes = Self::ALL
+            .iter()
+            .filter(|mode| {
+                CSR.hgatp.set(mode.code() << Hgatp::HGATP64_MODE_SHIFT);
+                Hgatp::from(CSR.hgatp.read()).mode() == Some(**mode)
+            })
+            .fold(0, |bitmap, mode| bitmap | (1 << mode.code()));
+        CSR.hgatp.set(original_hgatp);
+        SUPPORTED_HGATP_MODES.store(supported_modes, Ordering::Release);
+    }
+
+    /// Records the given modes as implemented by the hardware. Tests use it instead of `probe_supported_modes`, which accesses `hgatp`.
+    #[cfg(test)]
+    pub fn set_supported_modes(modes: &[HgatpMode]) {
+        let supported_modes = modes.iter().fold(0, |bitmap, mode| bitmap | (1 << mode.code()));
+        SUPPORTED_HGATP_MODES.store(supported_modes, Ordering::Release);
+    }
+
+    pub fn is_supported(&self) -> bool {
+        SUPPORTED_HGATP_MODES.load(Ordering::Acquire) & (1 << self.code()) != 0
+    }
+
+    /// Returns the supported mode with the smallest guest physical address space that still contains all addresses below the given
+    /// one. Smaller address spaces need fewer page table levels, so every G-stage translation requires fewer memory accesses.
+    pub fn smallest_covering(guest_physical_address_end: usize) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.is_supported() && guest_physical_address_end <= 1usize << mode.guest_physical_address_bits())
+    }
+
+    pub fn largest_supported() -> Option<Self> {
+        Self::ALL.into_iter().rev().find(|mode| mode.is_supported())
+    }
 }
 
 pub struct Hgatp {
@@ -207,9 +314,20 @@ pub struct Hgatp {
 impl Hgatp {
     const HGATP64_MODE_SHIFT: usize = 60;
     const HGATP64_VMID_SHIFT: usize = 44;
+    const HGATP64_VMID_MASK: usize = 0x3FFF;
     const PAGE_SHIFT: usize = 12;
     const HGATP_PPN_MASK: usize = 0x0000FFFFFFFFFFF;
 
+    /// Returns the number of VMID bits implemented by the hardware (VMIDLEN). The VMID field is WARL, thus only implemented bits read
+    /// back as set after writing ones to the entire field.
+    pub fn probe_vmid_length() -> usize {
+        let original_hgatp = CSR.hgatp.read();
+        CSR.hgatp.set(original_hgatp | (Self::HGATP64_VMID_MASK << Self::HGATP64_VMID_SHIFT));
+        let vmid = (CSR.hgatp.read() >> Self::HGATP64_VMID_SHIFT) & Self::HGATP64_VMID_MASK;
+        CSR.hgatp.set(original_hgatp);
+        vmid.count_ones() as usize
+    }
+
     pub fn from(bits: usize) -> Self {
         Self { bits }
     }
@@ -227,9 +345,88 @@ impl Hgatp {
     pub fn mode(&self) -> Option<HgatpMode> {
         HgatpMode::from_code((self.bits >> Self::HGATP64_MODE_SHIFT) & 0b1111)
     }
+}
+
+#[repr(usize)]
+#[derive(Clone, Copy, Debug)]
+pub enum SatpMode {
+    Bare = 0,
+    Sv39 = 8,
+    Sv48 = 9,
+    Sv57 = 10,
+}
+
+impl SatpMode {
+    fn from_code(code: usize) -> Option<Self> {
+        match code {
+            0 => Some(SatpMode::Bare),
+            8 => Some(SatpMode::Sv39),
+            9 => Some(SatpMode::Sv48),
+            10 => Some(SatpMode::Sv57),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of page table levels that the translation walks, zero if the translation is disabled.
+    pub fn levels(&self) -> usize {
+        match self {
+            SatpMode::Bare => 0,
+            SatpMode::Sv39 => 3,
+            SatpMode::Sv48 => 4,
+            SatpMode::Sv57 => 5,
+        }
+    }
+}
+
+/// The supervisor address translation and protection register. For a virtualized hart, it is the `vsatp` register that controls
+/// the VS-stage translation of guest virtual addresses into guest physical addresses.
+pub struct Satp {
+    bits: usize,
+}
+
+impl Satp {
+    const SATP64_MODE_SHIFT: usize = 60;
+    const PAGE_SHIFT: usize = 12;
+    const SATP_PPN_MASK: usize = 0x00000FFFFFFFFFFF;
+
+    pub fn from(bits: usize) -> Self {
+        Self { bits }
+    }
+
+    /// Returns the guest physical address of the root page table.
+    pub fn address(&self) -> usize {
+        (self.bits & Self::SATP_PPN_MASK) << Self::PAGE_SHIFT
+    }
+
+    pub fn mode(&self) -> Option<SatpMode> {
+        SatpMode::from_code((self.bits >> Self::SATP64_MODE_SHIFT) & 0b1111)
+    }
+}
+
+/// Computes the value of the `hgatp` register that enables the G-stage address translation in the given mode, rooted at the page table
+/// with the given physical page number, and tagging the address translation caches with the given VMID.
+pub fn compute_hgatp(root_ppn: usize, mode: HgatpMode, vmid: u16) -> usize {
+    let vmid = (vmid as usize) & Hgatp::HGATP64_VMID_MASK;
+    (mode.code() << Hgatp::HGATP64_MODE_SHIFT) | (vmid << Hgatp::HGATP64_VMID_SHIFT) | (root_ppn & Hgatp::HGATP_PPN_MASK)
+}
+
+/// The Smstateen extension gates accesses from lower privilege levels to the state of other extensions, e.g., the AIA or context
+/// CSRs. Without it, a confidential hart can access such state only if the hardware does not implement the gated extensions at all.
+pub struct Smstateen {}
+
+impl Smstateen {
+    /// The hstateen0 value of confidential harts. A confidential hart can use only sstateen0, which the security monitor switches
+    /// together with the rest of its state, to gate its own U-mode. All other state is gated because the security monitor does not
+    /// switch it between security domains, so a confidential hart would share it with the hypervisor.
+    pub const CONFIDENTIAL_HART_HSTATEEN0: usize = 1 << CSR_STATEEN0_SE0;
+
+    /// Makes the security monitor switch hstateen0 and sstateen0 between security domains. Called during the boot if all harts
+    /// implement the extension.
+    pub fn enable() {
+        IS_SMSTATEEN_ENABLED.store(true, Ordering::Release);
+    }
 
-    pub fn new(address: usize, mode: HgatpMode, vmid: usize) -> Self {
-        let ppn = (address >> Self::PAGE_SHIFT) & Self::HGATP_PPN_MASK;
-        Self { bits: (vmid << Self::HGATP64_VMID_SHIFT) | (mode.code() << Self::HGATP64_MODE_SHIFT) | ppn }
+    pub fn is_enabled() -> bool {
+        IS_SMSTATEEN_ENABLED.load(Ordering::Acquire)
     }
 }
@@ -11,6 +11,46 @@ pub fn hfence_gvma() {
     unsafe { core::arch::asm!("hfence.gvma") };
 }
 
+/// Fences G-stage translations of the guest physical address for all VMIDs. The instruction takes the address shifted right by 2.
+pub fn hfence_gvma_gpa(guest_physical_address: usize) {
+    unsafe { core::arch::asm!("hfence.gvma {0}, zero", in(reg) guest_physical_address >> 2) };
+}
+
+/// Fences all G-stage translations tagged with the VMID.
+pub fn hfence_gvma_vmid(vmid: usize) {
+    unsafe { core::arch::asm!("hfence.gvma zero, {0}", in(reg) vmid) };
+}
+
+/// Fences G-stage translations of the guest physical address tagged with the VMID.
+pub fn hfence_gvma_gpa_vmid(guest_physical_address: usize, vmid: usize) {
+    unsafe { core::arch::asm!("hfence.gvma {0}, {1}", in(reg) guest_physical_address >> 2, in(reg) vmid) };
+}
+
+/// Orders preceding stores to page tables before the following `hinval.gvma` instructions (Svinval extension).
+pub fn sfence_w_inval() {
+    unsafe { core::arch::asm!(".option push", ".option arch, +svinval", "sfence.w.inval", ".option pop") };
+}
+
+/// Orders the preceding `hinval.gvma` instructions before the following implicit references to page tables (Svinval extension).
+pub fn sfence_inval_ir() {
+    unsafe { core::arch::asm!(".option push", ".option arch, +svinval", "sfence.inval.ir", ".option pop") };
+}
+
+/// Invalidates G-stage translations of the guest physical address tagged with the VMID, like `hfence_gvma_gpa_vmid` but without
+/// ordering. Must be bracketed by `sfence_w_inval` and `sfence_inval_ir` (Svinval extension).
+pub fn hinval_gvma_gpa_vmid(guest_physical_address: usize, vmid: usize) {
+    unsafe {
+        core::arch::asm!(
+            ".option push",
+            ".option arch, +svinval",
+            "hinval.gvma {0}, {1}",
+            ".option pop",
+            in(reg) guest_physical_address >> 2,
+            in(reg) vmid
+        )
+    };
+}
+
 pub fn hfence_vvma() {
     unsafe { core::arch::asm!("hfence.vvma") };
 }
@@ -1,6 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::specification::CSR_MSTATUS_MPP;
 use crate::core::architecture::*;
 
 /// HartArchitecturalState is the dump state of the processor's core, called in RISC-V a hardware thread (HART).
@@ -26,6 +27,7 @@ pub struct HartArchitecturalState {
     // S-mode
     pub sstatus: usize,
     pub hstatus: usize,
+    pub hcounteren: usize,
     pub sepc: usize,
     pub scounteren: usize,
     pub sip: usize,
@@ -34,12 +36,16 @@ pub struct HartArchitecturalState {
     pub stvec: usize,
     pub stval: usize,
     pub sscratch: usize,
+    // state-enable CSRs provided by the Smstateen extension
+    pub sstateen0: usize,
+    pub hstateen0: usize,
     // virtualization-related
     pub hvip: usize,
     pub hgeip: usize,
     pub hie: usize,
     pub hip: usize,
     pub hgatp: usize,
+    pub henvcfg: usize,
     pub hedeleg: usize,
     pub hideleg: usize,
     pub htinst: usize,
@@ -88,8 +94,12 @@ impl HartArchitecturalState {
             stvec: CSR.stvec.read(),
             stval: CSR.stval.read(),
             sscratch: CSR.sscratch.read(),
+            // The state-enable CSRs of the confidential hart are set by the security monitor.
+            sstateen0: 0,
+            hstateen0: 0,
             // HS-mode
             hstatus: CSR.hstatus.read(),
+            hcounteren: CSR.hcounteren.read(),
             hedeleg: CSR.hedeleg.read(),
             hideleg: CSR.hideleg.read(),
             htinst: CSR.htinst.read(),
@@ -99,6 +109,7 @@ impl HartArchitecturalState {
             hie: CSR.hie.read(),
             hip: CSR.hip.read(),
             hgatp: CSR.hgatp.read(),
+            henvcfg: CSR.henvcfg.read(),
             // VS-mode
             vsstatus: CSR.vsstatus.read(),
             vsie: CSR.vsie.read(),
@@ -124,6 +135,7 @@ impl HartArchitecturalState {
             gprs: GeneralPurposeRegisters::empty(),
             sstatus: 0,
             hstatus: 0,
+            hcounteren: 0,
             hedeleg: 0,
             hideleg: 0,
             htinst: 0,
@@ -146,6 +158,7 @@ impl HartArchitecturalState {
             hip: 0,
             vsatp: 0,
             hgatp: 0,
+            henvcfg: 0,
             fprs: FloatingPointRegisters::empty(),
             fcsr: 0,
             sip: 0,
@@ -154,6 +167,8 @@ impl HartArchitecturalState {
             stval: 0,
             stvec: 0,
             sscratch: 0,
+            sstateen0: 0,
+            hstateen0: 0,
             mepc: 0,
             medeleg: 0,
             mideleg: 0,
@@ -186,6 +201,7 @@ impl HartArchitecturalState {
         self.sscratch = CSR.sscratch.read();
         // HS-mode
         self.hstatus = CSR.hstatus.read();
+        self.hcounteren = CSR.hcounteren.read();
         self.hedeleg = CSR.hedeleg.read();
         self.hideleg = CSR.hideleg.read();
         self.htinst = CSR.htinst.read();
@@ -195,6 +211,7 @@ impl HartArchitecturalState {
         self.hie = CSR.hie.read();
         self.hip = CSR.hip.read();
         self.hgatp = CSR.hgatp.read();
+        self.henvcfg = CSR.henvcfg.read();
         // VS-mode
         self.vsstatus = CSR.vsstatus.read();
         self.vsie = CSR.vsie.read();
@@ -208,9 +225,12 @@ impl HartArchitecturalState {
         // timer-related
         self.vstimecmp = CSR.vstimecmp.read();
         self.htimedelta = CSR.htimedelta.read();
-        // F-extension
-        // self.fprs = existing.fprs.clone();
-        self.fcsr = CSR.fcsr.read();
+        // Smstateen
+        if Smstateen::is_enabled() {
+            self.sstateen0 = CSR.sstateen0.read();
+            self.hstateen0 = CSR.hstateen0.read();
+        }
+        // F-extension state is switched lazily, see `store_floating_point_registers_in_main_memory`
     }
 
     pub fn load_control_status_registers_from_main_memory(&self) {
@@ -232,6 +252,7 @@ impl HartArchitecturalState {
         CSR.sscratch.set(self.sscratch);
         // HS-mode
         CSR.hstatus.set(self.hstatus);
+        CSR.hcounteren.set(self.hcounteren);
         CSR.hedeleg.set(self.hedeleg);
         CSR.hideleg.set(self.hideleg);
         CSR.htinst.set(self.htinst);
@@ -241,6 +262,7 @@ impl HartArchitecturalState {
         CSR.hie.set(self.hie);
         // CSR.hip.set(self.hip);
         CSR.hgatp.set(self.hgatp);
+        CSR.henvcfg.set(self.henvcfg);
         // VS-mode
         CSR.vsstatus.set(self.vsstatus);
         CSR.vsie.set(self.vsie);
@@ -254,8 +276,44 @@ impl HartArchitecturalState {
         // timer-related
         CSR.vstimecmp.set(self.vstimecmp);
         CSR.htimedelta.set(self.htimedelta);
-        // F-extension
-        // self.fprs = existing.fprs.clone();
+        // Smstateen
+        if Smstateen::is_enabled() {
+            CSR.sstateen0.set(self.sstateen0);
+            CSR.hstateen0.set(self.hstateen0);
+        }
+        // F-extension state is switched lazily, see `load_floating_point_registers_from_main_memory`
+    }
+
+    /// Overwrites the state with values that cannot resume any computation, so that a hart whose state became inconsistent, e.g.,
+    /// due to a panic in the middle of a trap handler, halts instead of executing with garbage registers. Registers are cleared,
+    /// interrupts are disabled, and `mret` would lead to the halt loop. The loop is part of the security monitor, which is
+    /// inaccessible to lower privilege levels, so the only privilege level allowed in `mstatus` is the machine mode with all other
+    /// bits cleared.
+    pub fn restore_known_safe_defaults(&mut self) {
+        self.gprs = GeneralPurposeRegisters::empty();
+        self.mepc = halt_hart as usize;
+        self.mstatus = 0b11 << CSR_MSTATUS_MPP;
+        self.mie = 0;
+        self.mip = 0;
+        self.sie = 0;
+        self.sip = 0;
+        self.hie = 0;
+        self.hvip = 0;
+        self.vsie = 0;
+        self.vsip = 0;
+    }
+
+    /// Dumps floating-point registers and the fcsr of the physical hart executing this code to the main memory. The caller must enable
+    /// the floating-point unit (mstatus.FS) before calling this function.
+    pub fn store_floating_point_registers_in_main_memory(&mut self) {
+        unsafe { self.fprs.store_in_main_memory() };
+        self.fcsr = CSR.fcsr.read();
+    }
+
+    /// Loads floating-point registers and the fcsr from the main memory into the physical hart executing this code. The caller must
+    /// enable the floating-point unit (mstatus.FS) before calling this function.
+    pub fn load_floating_point_registers_from_main_memory(&self) {
+        unsafe { self.fprs.load_from_main_memory() };
         CSR.fcsr.set(self.fcsr);
     }
 }
@@ -310,5 +368,10 @@ pub const HART_T3_OFFSET: usize = hart_gpr_offset(GeneralPurposeRegister::t3);
 pub const HART_T4_OFFSET: usize = hart_gpr_offset(GeneralPurposeRegister::t4);
 pub const HART_T5_OFFSET: usize = hart_gpr_offset(GeneralPurposeRegister::t5);
 pub const HART_T6_OFFSET: usize = hart_gpr_offset(GeneralPurposeRegister::t6);
+// The context switch stores registers with `sd` and loads them with `ld`, so every offset must be 8-byte aligned and fit in a 12-bit
+// signed immediate. Registers are stored in the order of their numbers, thus checking `ra` and `t6` covers all offsets in between.
+const _: () = assert!(memoffset::offset_of!(HartArchitecturalState, gprs) == 0);
+const _: () = assert!(core::mem::size_of::<usize>() == core::mem::size_of::<u64>());
+const _: () = assert!(HART_RA_OFFSET % core::mem::size_of::<u64>() == 0 && HART_T6_OFFSET < 2048);
 pub const HART_MEPC_OFFSET: usize = memoffset::offset_of!(HartArchitecturalState, mepc);
 pub const HART_MSTATUS_OFFSET: usize = memoffset::offset_of!(HartArchitecturalState, mstatus);
@@ -2,12 +2,13 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 #![allow(unused)]
-pub use compressed_instructions::decode_result_register;
+pub use compressed_instructions::{decode_result_register, decode_store_size, transformed_instruction};
 pub use floating_point_registers::FloatingPointRegisters;
 pub use general_purpose_registers::{GeneralPurposeRegister, GeneralPurposeRegisters};
 pub use hart_lifecycle_state::HartLifecycleState;
 pub use supervisor_binary_interface::{
-    AceExtension, BaseExtension, HsmExtension, IpiExtension, RfenceExtension, SbiExtension, SrstExtension,
+    AceExtension, BaseExtension, CppcExtension, DbcnExtension, FwftExtension, HsmExtension, IpiExtension, NaclExtension, PmuExtension,
+    RfenceExtension, SbiError, SbiExtension, SrstExtension, SseExtension, StaExtension, SuspExtension, TeeGuestExtension, TeeHostExtension,
 };
 pub use trap_cause::TrapCause;
 
@@ -29,6 +30,13 @@ pub fn put_hart_to_sleep() {
     }
 }
 
+/// Parks the executing hart forever.
+pub extern "C" fn halt_hart() -> ! {
+    loop {
+        put_hart_to_sleep();
+    }
+}
+
 #[inline]
 pub fn enable_bit(register_value: &mut usize, bit_index: usize) {
     enable_bits(register_value, 1 << bit_index);
@@ -6,6 +6,8 @@
 #![allow(unused)]
 
 pub const ECALL_INSTRUCTION_LENGTH: usize = 4;
+// Instructions are 16-bit aligned because we support the compressed instructions extension (C)
+pub const INSTRUCTION_ALIGNMENT: usize = 2;
 
 pub const CAUSE_INTERRUPT_BIT: usize = 63;
 pub const STVEC_MODE_VECTORED: usize = 0b11;
@@ -33,6 +35,7 @@ pub const CSR_SIE: u16 = 0x104;
 pub const CSR_STVEC: u16 = 0x105;
 pub const CSR_SCOUNTEREN: u16 = 0x106;
 pub const CSR_SENVCFG: u16 = 0x10a;
+pub const CSR_SSTATEEN0: u16 = 0x10c;
 pub const CSR_SSCRATCH: u16 = 0x140;
 pub const CSR_SEPC: u16 = 0x141;
 pub const CSR_SCAUSE: u16 = 0x142;
@@ -68,6 +71,7 @@ pub const CSR_HCOUNTEREN: u16 = 0x606;
 pub const CSR_HGEIE: u16 = 0x607;
 pub const CSR_HVICTL: u16 = 0x609;
 pub const CSR_HENVCFG: u16 = 0x60a;
+pub const CSR_HSTATEEN0: u16 = 0x60c;
 pub const CSR_HTVAL: u16 = 0x643;
 pub const CSR_HIP: u16 = 0x644;
 pub const CSR_HVIP: u16 = 0x645;
@@ -98,6 +102,7 @@ pub const CSR_MIE: u16 = 0x304;
 pub const CSR_MTVEC: u16 = 0x305;
 pub const CSR_MCOUNTEREN: u16 = 0x306;
 pub const CSR_MENVCFG: u16 = 0x30a;
+pub const CSR_MSTATEEN0: u16 = 0x30c;
 pub const CSR_MCOUNTINHIBIT: u16 = 0x320;
 pub const CSR_MSCRATCH: u16 = 0x340;
 pub const CSR_MEPC: u16 = 0x341;
@@ -364,6 +369,24 @@ pub const CSR_MSTATUS_MPP: usize = 11;
 pub const CSR_MSTATUS_GVA: usize = 38;
 pub const CSR_MSTATUS_MPV: usize = 39;
 pub const CSR_MSTATUS_MPRV: usize = 17;
+pub const CSR_MSTATUS_TW: usize = 21;
+pub const CSR_MSTATUS_FS: usize = 13;
+pub const CSR_MSTATUS_FS_MASK: usize = 0b11 << CSR_MSTATUS_FS;
+pub const CSR_MSTATUS_FS_CLEAN: usize = 0b10 << CSR_MSTATUS_FS;
+pub const CSR_MSTATUS_FS_DIRTY: usize = 0b11 << CSR_MSTATUS_FS;
+pub const CSR_MSTATUS_XS: usize = 15;
+pub const CSR_MSTATUS_XS_MASK: usize = 0b11 << CSR_MSTATUS_XS;
+pub const CSR_MSTATUS_XS_DIRTY: usize = 0b11 << CSR_MSTATUS_XS;
+pub const CSR_MSTATUS_SD: usize = 63;
+
+pub const CSR_MENVCFG_ADUE: usize = 61;
+
+// The PMM field of henvcfg selects the pointer masking length (PMLEN) of VS-mode and VU-mode.
+pub const CSR_HENVCFG_PMM: usize = 32;
+pub const CSR_HENVCFG_PMM_MASK: usize = 0b11 << CSR_HENVCFG_PMM;
+
+// The SE0 bit of mstateen0 and hstateen0 enables accesses to the state-enable CSRs of the lower privilege levels.
+pub const CSR_STATEEN0_SE0: usize = 63;
 
 pub const CSR_HSTATUS_SPVP: usize = 8;
 pub const CSR_HSTATUS_VTW: usize = 21;
@@ -402,9 +425,18 @@ pub const PMP_NA4_MASK: usize = 0b10000;
 pub const PMP_NAPOT_MASK: usize = 0b11000;
 pub const PMP_PERMISSION_NONE_MASK: usize = 0;
 pub const PMP_PERMISSION_RWX_MASK: usize = 0b111;
+pub const PMP_LOCK_MASK: usize = 0b1000_0000;
 pub const PMP_CONFIG_SHIFT: usize = 8;
 pub const PMP_ADDRESS_SHIFT: u16 = 2;
 
 pub const MTVEC_BASE_SHIFT: usize = 2;
 
+pub const SEED_OPST_SHIFT: usize = 30;
+pub const SEED_OPST_MASK: usize = 0b11;
+pub const SEED_OPST_BIST: usize = 0b00;
+pub const SEED_OPST_WAIT: usize = 0b01;
+pub const SEED_OPST_ES16: usize = 0b10;
+pub const SEED_OPST_DEAD: usize = 0b11;
+pub const SEED_ENTROPY_MASK: usize = 0xffff;
+
 pub const CSR_STATUS_SIE: usize = 1;
@@ -11,6 +11,16 @@ pub enum SbiExtension {
     Rfence(RfenceExtension),
     Hsm(HsmExtension),
     Srst(SrstExtension),
+    Nacl(NaclExtension),
+    Pmu(PmuExtension),
+    Susp(SuspExtension),
+    Sta(StaExtension),
+    Sse(SseExtension),
+    Cppc(CppcExtension),
+    Dbcn(DbcnExtension),
+    Fwft(FwftExtension),
+    TeeHost(TeeHostExtension),
+    TeeGuest(TeeGuestExtension),
     Unknown(usize, usize),
 }
 
@@ -23,19 +33,88 @@ impl SbiExtension {
             (RfenceExtension::EXTID, function_id) => Self::Rfence(RfenceExtension::from_function_id(function_id)),
             (HsmExtension::EXTID, function_id) => Self::Hsm(HsmExtension::from_function_id(function_id)),
             (SrstExtension::EXTID, function_id) => Self::Srst(SrstExtension::from_function_id(function_id)),
+            (NaclExtension::EXTID, function_id) => Self::Nacl(NaclExtension::from_function_id(function_id)),
+            (PmuExtension::EXTID, function_id) => Self::Pmu(PmuExtension::from_function_id(function_id)),
+            (SuspExtension::EXTID, function_id) => Self::Susp(SuspExtension::from_function_id(function_id)),
+            (StaExtension::EXTID, function_id) => Self::Sta(StaExtension::from_function_id(function_id)),
+            (SseExtension::EXTID, function_id) => Self::Sse(SseExtension::from_function_id(function_id)),
+            (CppcExtension::EXTID, function_id) => Self::Cppc(CppcExtension::from_function_id(function_id)),
+            (DbcnExtension::EXTID, function_id) => Self::Dbcn(DbcnExtension::from_function_id(function_id)),
+            (FwftExtension::EXTID, function_id) => Self::Fwft(FwftExtension::from_function_id(function_id)),
+            (TeeHostExtension::EXTID, function_id) => Self::TeeHost(TeeHostExtension::from_function_id(function_id)),
+            (TeeGuestExtension::EXTID, function_id) => Self::TeeGuest(TeeGuestExtension::from_function_id(function_id)),
             (extension_id, function_id) => Self::Unknown(extension_id, function_id),
         }
     }
+
+    /// Returns the number of argument registers (starting from a0) that the SBI function defines, as specified by the SBI 2.0
+    /// specification and the documentation of the ACE extension. Returns `None` if the function is unknown.
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::Ace(function) => function.number_of_arguments(),
+            Self::Base(function) => function.number_of_arguments(),
+            Self::Ipi(function) => function.number_of_arguments(),
+            Self::Rfence(function) => function.number_of_arguments(),
+            Self::Hsm(function) => function.number_of_arguments(),
+            Self::Srst(function) => function.number_of_arguments(),
+            Self::Nacl(function) => function.number_of_arguments(),
+            Self::Pmu(function) => function.number_of_arguments(),
+            Self::Susp(function) => function.number_of_arguments(),
+            Self::Sta(function) => function.number_of_arguments(),
+            Self::Sse(function) => function.number_of_arguments(),
+            Self::Cppc(function) => function.number_of_arguments(),
+            Self::Dbcn(function) => function.number_of_arguments(),
+            Self::Fwft(function) => function.number_of_arguments(),
+            Self::TeeHost(function) => function.number_of_arguments(),
+            Self::TeeGuest(function) => function.number_of_arguments(),
+            Self::Unknown(_, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum AceExtension {
     SharePageWithHypervisor,
     StopSharingPageWithHypervisor,
+    InflateMemoryBalloon,
+    DeflateMemoryBalloon,
     PromoteToConfidentialVm,
+    CreateConfidentialVm,
+    AddConfidentialVmMemory,
+    AddConfidentialHart,
+    FinalizeConfidentialVm,
     ResumeConfidentialHart,
+    ConfidentialHartRunstate,
+    ConfidentialHartExit,
     TerminateConfidentialVm,
+    ReclaimConfidentialVmMemory,
+    RotateConfidentialVmMemoryKey,
+    ReadConfidentialHartRegister,
+    WriteConfidentialHartRegister,
+    ConvertToConfidentialMemory,
+    ReleaseConfidentialMemory,
+    GlobalMemoryFence,
+    ConfirmMemoryConversion,
+    ExtendMeasurement,
+    ReadMeasurement,
+    GetAttestationReport,
+    GetCertificateChain,
+    VerifyCodeIntegrity,
+    SealData,
+    UnsealData,
+    GetMeasurementLog,
+    GetConfidentialVmMeasurement,
+    GetSecurityMonitorInfo,
+    GetMemoryInfo,
+    GetConfidentialVmMemoryInfo,
     PrintDebugInfo,
+    #[cfg(feature = "declassification_log")]
+    ReadDeclassificationLog,
+    ReadTraceBuffer,
+    #[cfg(feature = "pmp_audit_log")]
+    ReadPmpAuditLog,
+    ReadTlbFenceCounters,
+    ReadAccessedDirtyCounters,
     Unknown(usize, usize),
 }
 
@@ -46,14 +125,96 @@ impl AceExtension {
     pub fn from_function_id(function_id: usize) -> Self {
         match function_id {
             1000 => Self::PromoteToConfidentialVm,
+            1001 => Self::CreateConfidentialVm,
+            1002 => Self::AddConfidentialVmMemory,
+            1003 => Self::AddConfidentialHart,
+            1004 => Self::FinalizeConfidentialVm,
             1010 => Self::ResumeConfidentialHart,
+            1011 => Self::ConfidentialHartRunstate,
+            1012 => Self::ConfidentialHartExit,
             2000 => Self::SharePageWithHypervisor,
             2001 => Self::StopSharingPageWithHypervisor,
+            2002 => Self::InflateMemoryBalloon,
+            2003 => Self::DeflateMemoryBalloon,
             3001 => Self::TerminateConfidentialVm,
+            3002 => Self::ReclaimConfidentialVmMemory,
+            3003 => Self::RotateConfidentialVmMemoryKey,
+            4000 => Self::ReadConfidentialHartRegister,
+            4001 => Self::WriteConfidentialHartRegister,
+            5000 => Self::ConvertToConfidentialMemory,
+            5001 => Self::ReleaseConfidentialMemory,
+            5002 => Self::GlobalMemoryFence,
+            5003 => Self::ConfirmMemoryConversion,
+            6000 => Self::ExtendMeasurement,
+            6001 => Self::ReadMeasurement,
+            6002 => Self::GetAttestationReport,
+            6003 => Self::GetCertificateChain,
+            6004 => Self::VerifyCodeIntegrity,
+            6005 => Self::SealData,
+            6006 => Self::UnsealData,
+            6007 => Self::GetMeasurementLog,
+            6010 => Self::GetConfidentialVmMeasurement,
+            7000 => Self::GetSecurityMonitorInfo,
+            7001 => Self::GetMemoryInfo,
+            7002 => Self::GetConfidentialVmMemoryInfo,
             9000 => Self::PrintDebugInfo,
+            #[cfg(feature = "declassification_log")]
+            9001 => Self::ReadDeclassificationLog,
+            9002 => Self::ReadTraceBuffer,
+            #[cfg(feature = "pmp_audit_log")]
+            9003 => Self::ReadPmpAuditLog,
+            9004 => Self::ReadTlbFenceCounters,
+            9005 => Self::ReadAccessedDirtyCounters,
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::SharePageWithHypervisor => Some(1),
+            Self::StopSharingPageWithHypervisor => Some(1),
+            Self::InflateMemoryBalloon => Some(2),
+            Self::DeflateMemoryBalloon => Some(2),
+            Self::PromoteToConfidentialVm => Some(6),
+            Self::CreateConfidentialVm => Some(2),
+            Self::AddConfidentialVmMemory => Some(3),
+            Self::AddConfidentialHart => Some(2),
+            Self::FinalizeConfidentialVm => Some(1),
+            Self::ResumeConfidentialHart => Some(0),
+            Self::ConfidentialHartRunstate => Some(0),
+            Self::ConfidentialHartExit => Some(0),
+            Self::TerminateConfidentialVm => Some(0),
+            Self::ReclaimConfidentialVmMemory => Some(1),
+            Self::RotateConfidentialVmMemoryKey => Some(0),
+            Self::ReadConfidentialHartRegister => Some(1),
+            Self::WriteConfidentialHartRegister => Some(2),
+            Self::ConvertToConfidentialMemory => Some(2),
+            Self::ReleaseConfidentialMemory => Some(2),
+            Self::GlobalMemoryFence => Some(0),
+            Self::ConfirmMemoryConversion => Some(2),
+            Self::ExtendMeasurement => Some(2),
+            Self::ReadMeasurement => Some(2),
+            Self::GetAttestationReport => Some(3),
+            Self::GetCertificateChain => Some(2),
+            Self::VerifyCodeIntegrity => Some(2),
+            Self::SealData => Some(4),
+            Self::UnsealData => Some(4),
+            Self::GetMeasurementLog => Some(3),
+            Self::GetConfidentialVmMeasurement => Some(2),
+            Self::GetSecurityMonitorInfo => Some(1),
+            Self::GetMemoryInfo => Some(1),
+            Self::GetConfidentialVmMemoryInfo => Some(1),
+            Self::PrintDebugInfo => Some(0),
+            #[cfg(feature = "declassification_log")]
+            Self::ReadDeclassificationLog => Some(1),
+            Self::ReadTraceBuffer => Some(1),
+            #[cfg(feature = "pmp_audit_log")]
+            Self::ReadPmpAuditLog => Some(1),
+            Self::ReadTlbFenceCounters => Some(1),
+            Self::ReadAccessedDirtyCounters => Some(1),
+            Self::Unknown(_, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -83,6 +244,14 @@ impl BaseExtension {
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::ProbeExtension => Some(1),
+            Self::Unknown(_, _) => None,
+            _ => Some(0),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -100,6 +269,13 @@ impl IpiExtension {
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::SendIpi => Some(2),
+            Self::Unknown(_, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -129,6 +305,19 @@ impl RfenceExtension {
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::RemoteFenceI => Some(2),
+            Self::RemoteSfenceVma => Some(4),
+            Self::RemoteSfenceVmaAsid => Some(5),
+            Self::RemoteHfenceGvmaVmid => Some(5),
+            Self::RemoteHfenceGvma => Some(4),
+            Self::RemoteHfenceVvmaAsid => Some(5),
+            Self::RemoteHfenceVvma => Some(4),
+            Self::Unknown(_, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -156,6 +345,16 @@ impl HsmExtension {
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::HartStart => Some(3),
+            Self::HartStop => Some(0),
+            Self::HartGetStatus => Some(1),
+            Self::HartSuspend => Some(3),
+            Self::Unknown(_, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -174,4 +373,492 @@ impl SrstExtension {
             _ => Self::Unknown(Self::EXTID, function_id),
         }
     }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::SystemReset => Some(2),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI nested acceleration extension. The hypervisor uses it to register a memory region shared with the security monitor.
+#[derive(Debug)]
+pub enum NaclExtension {
+    ProbeFeature,
+    SetSharedMemory,
+    SyncCsr,
+    SyncHfence,
+    SyncSret,
+    Unknown(usize, usize),
+}
+
+impl NaclExtension {
+    pub const EXTID: usize = 0x4E41434C;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::ProbeFeature,
+            1 => Self::SetSharedMemory,
+            2 => Self::SyncCsr,
+            3 => Self::SyncHfence,
+            4 => Self::SyncSret,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::ProbeFeature => Some(1),
+            Self::SetSharedMemory => Some(3),
+            Self::SyncCsr => Some(1),
+            Self::SyncHfence => Some(1),
+            Self::SyncSret => Some(0),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PmuExtension {
+    NumCounters,
+    CounterGetInfo,
+    CounterConfigMatching,
+    CounterStart,
+    CounterStop,
+    CounterFwRead,
+    CounterFwReadHi,
+    SnapshotSetShmem,
+    Unknown(usize, usize),
+}
+
+impl PmuExtension {
+    pub const EXTID: usize = 0x504D55;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::NumCounters,
+            1 => Self::CounterGetInfo,
+            2 => Self::CounterConfigMatching,
+            3 => Self::CounterStart,
+            4 => Self::CounterStop,
+            5 => Self::CounterFwRead,
+            6 => Self::CounterFwReadHi,
+            7 => Self::SnapshotSetShmem,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::NumCounters => Some(0),
+            Self::CounterGetInfo => Some(1),
+            Self::CounterConfigMatching => Some(5),
+            Self::CounterStart => Some(4),
+            Self::CounterStop => Some(3),
+            Self::CounterFwRead => Some(1),
+            Self::CounterFwReadHi => Some(1),
+            Self::SnapshotSetShmem => Some(3),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI system suspend extension. The hypervisor uses it to suspend the entire system to RAM.
+#[derive(Debug)]
+pub enum SuspExtension {
+    SystemSuspend,
+    Unknown(usize, usize),
+}
+
+impl SuspExtension {
+    pub const EXTID: usize = 0x53555350;
+    pub const SYSTEM_SUSPEND_FID: usize = 0x0;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            Self::SYSTEM_SUSPEND_FID => Self::SystemSuspend,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::SystemSuspend => Some(3),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StaExtension {
+    SetSharedMemory,
+    Unknown(usize, usize),
+}
+
+impl StaExtension {
+    pub const EXTID: usize = 0x535441;
+    pub const SET_SHARED_MEMORY_FID: usize = 0x0;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            Self::SET_SHARED_MEMORY_FID => Self::SetSharedMemory,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::SetSharedMemory => Some(3),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI supervisor software events extension. Events are delivered to the supervisor by diverting its execution to a registered
+/// handler, similarly to non-maskable interrupts.
+#[derive(Debug)]
+pub enum SseExtension {
+    ReadAttributes,
+    WriteAttributes,
+    Register,
+    Unregister,
+    Enable,
+    Disable,
+    Complete,
+    Inject,
+    HartUnmask,
+    HartMask,
+    Unknown(usize, usize),
+}
+
+impl SseExtension {
+    pub const EXTID: usize = 0x535345;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::ReadAttributes,
+            1 => Self::WriteAttributes,
+            2 => Self::Register,
+            3 => Self::Unregister,
+            4 => Self::Enable,
+            5 => Self::Disable,
+            6 => Self::Complete,
+            7 => Self::Inject,
+            8 => Self::HartUnmask,
+            9 => Self::HartMask,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::ReadAttributes => Some(5),
+            Self::WriteAttributes => Some(5),
+            Self::Register => Some(3),
+            Self::Unregister => Some(1),
+            Self::Enable => Some(1),
+            Self::Disable => Some(1),
+            Self::Complete => Some(0),
+            Self::Inject => Some(2),
+            Self::HartUnmask => Some(0),
+            Self::HartMask => Some(0),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI collaborative processor performance control (CPPC) extension. It gives the supervisor access to the performance and
+/// frequency scaling registers of the platform.
+#[derive(Debug)]
+pub enum CppcExtension {
+    Probe,
+    Read,
+    ReadHi,
+    Write,
+    Unknown(usize, usize),
+}
+
+impl CppcExtension {
+    pub const EXTID: usize = 0x43505043;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::Probe,
+            1 => Self::Read,
+            2 => Self::ReadHi,
+            3 => Self::Write,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::Probe => Some(1),
+            Self::Read => Some(1),
+            Self::ReadHi => Some(1),
+            Self::Write => Some(2),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI debug console (DBCN) extension. It gives the supervisor byte-oriented access to the platform's console.
+#[derive(Debug)]
+pub enum DbcnExtension {
+    ConsoleWrite,
+    ConsoleRead,
+    ConsoleWriteByte,
+    Unknown(usize, usize),
+}
+
+impl DbcnExtension {
+    pub const EXTID: usize = 0x4442434E;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::ConsoleWrite,
+            1 => Self::ConsoleRead,
+            2 => Self::ConsoleWriteByte,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::ConsoleWrite => Some(3),
+            Self::ConsoleRead => Some(3),
+            Self::ConsoleWriteByte => Some(1),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The SBI firmware features (FWFT) extension. It lets the supervisor enable and query features of the hart that are controlled by
+/// a higher privilege level, e.g., the delegation of misaligned access exceptions or pointer masking.
+#[derive(Debug)]
+pub enum FwftExtension {
+    Set,
+    Get,
+    Unknown(usize, usize),
+}
+
+impl FwftExtension {
+    pub const EXTID: usize = 0x46574654;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::Set,
+            1 => Self::Get,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::Set => Some(3),
+            Self::Get => Some(1),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The TEE Host Interface of the RISC-V CoVE specification. It lets a CoVE-aware hypervisor (e.g., KVM-CoVE) build and run confidential
+/// VMs (TVMs in the CoVE terminology) without using the ACE extension. The functions are implemented on top of the same operations
+/// as their ACE counterparts. Functions without an ACE counterpart, e.g., adding zero pages or querying the TSM information, are
+/// reported as unknown.
+#[derive(Debug)]
+pub enum TeeHostExtension {
+    ConvertPages,
+    ReclaimPages,
+    GlobalFence,
+    CreateTvm,
+    FinalizeTvm,
+    DestroyTvm,
+    AddTvmMeasuredPages,
+    CreateTvmVcpu,
+    RunTvmVcpu,
+    Unknown(usize, usize),
+}
+
+impl TeeHostExtension {
+    pub const EXTID: usize = 0x41544545;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            1 => Self::ConvertPages,
+            2 => Self::ReclaimPages,
+            3 => Self::GlobalFence,
+            5 => Self::CreateTvm,
+            6 => Self::FinalizeTvm,
+            8 => Self::DestroyTvm,
+            11 => Self::AddTvmMeasuredPages,
+            14 => Self::CreateTvmVcpu,
+            15 => Self::RunTvmVcpu,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::ConvertPages => Some(2),
+            Self::ReclaimPages => Some(2),
+            Self::GlobalFence => Some(0),
+            Self::CreateTvm => Some(2),
+            Self::FinalizeTvm => Some(3),
+            Self::DestroyTvm => Some(1),
+            Self::AddTvmMeasuredPages => Some(6),
+            Self::CreateTvmVcpu => Some(3),
+            Self::RunTvmVcpu => Some(2),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// The TEE Guest Interface of the RISC-V CoVE specification, called by the kernel of a confidential VM. The functions are implemented
+/// on top of the same operations as their ACE counterparts, except for the sealing key, which the ACE extension never reveals.
+#[derive(Debug)]
+pub enum TeeGuestExtension {
+    GetSealingKey,
+    GetEvidence,
+    ExtendMeasurement,
+    Unknown(usize, usize),
+}
+
+impl TeeGuestExtension {
+    pub const EXTID: usize = 0x47544545;
+
+    pub fn from_function_id(function_id: usize) -> Self {
+        match function_id {
+            0 => Self::GetSealingKey,
+            1 => Self::GetEvidence,
+            2 => Self::ExtendMeasurement,
+            _ => Self::Unknown(Self::EXTID, function_id),
+        }
+    }
+
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        match self {
+            Self::GetSealingKey => Some(2),
+            Self::GetEvidence => Some(3),
+            Self::ExtendMeasurement => Some(2),
+            Self::Unknown(_, _) => None,
+        }
+    }
+}
+
+/// Standard error codes returned in `a0` by SBI implementations. Successful calls return 0, errors are negative values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SbiError {
+    Failed,
+    NotSupported,
+    InvalidParam,
+    Denied,
+    InvalidAddress,
+    AlreadyAvailable,
+    AlreadyStarted,
+    AlreadyStopped,
+    NoSharedMemory,
+    DeniedLocked,
+    Unknown(isize),
+}
+
+impl SbiError {
+    /// Returns the value of `a0` that reports this error.
+    pub fn code(&self) -> usize {
+        let code: isize = match self {
+            Self::Failed => -1,
+            Self::NotSupported => -2,
+            Self::InvalidParam => -3,
+            Self::Denied => -4,
+            Self::InvalidAddress => -5,
+            Self::AlreadyAvailable => -6,
+            Self::AlreadyStarted => -7,
+            Self::AlreadyStopped => -8,
+            Self::NoSharedMemory => -9,
+            Self::DeniedLocked => -14,
+            Self::Unknown(code) => *code,
+        };
+        code as usize
+    }
+
+    /// Interprets the value of `a0` returned by the SBI implementation. Returns `None` if the value does not represent an error.
+    pub fn from_code(a0: usize) -> Option<Self> {
+        match a0 as isize {
+            code if code >= 0 => None,
+            -1 => Some(Self::Failed),
+            -2 => Some(Self::NotSupported),
+            -3 => Some(Self::InvalidParam),
+            -4 => Some(Self::Denied),
+            -5 => Some(Self::InvalidAddress),
+            -6 => Some(Self::AlreadyAvailable),
+            -7 => Some(Self::AlreadyStarted),
+            -8 => Some(Self::AlreadyStopped),
+            -9 => Some(Self::NoSharedMemory),
+            -14 => Some(Self::DeniedLocked),
+            code => Some(Self::Unknown(code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENSION_IDS: [usize; 16] = [
+        AceExtension::EXTID,
+        BaseExtension::EXTID,
+        IpiExtension::EXTID,
+        RfenceExtension::EXTID,
+        HsmExtension::EXTID,
+        SrstExtension::EXTID,
+        NaclExtension::EXTID,
+        PmuExtension::EXTID,
+        SuspExtension::EXTID,
+        StaExtension::EXTID,
+        SseExtension::EXTID,
+        CppcExtension::EXTID,
+        DbcnExtension::EXTID,
+        FwftExtension::EXTID,
+        TeeHostExtension::EXTID,
+        TeeGuestExtension::EXTID,
+    ];
+
+    #[test]
+    fn known_functions_use_only_argument_registers() {
+        for extension_id in EXTENSION_IDS {
+            for function_id in 0..64 {
+                let extension = SbiExtension::decode(extension_id, function_id);
+                let number_of_arguments = extension.number_of_arguments();
+                // Arguments are passed in a0-a5, a6 and a7 hold the function and extension IDs.
+                assert!(number_of_arguments.unwrap_or(0) <= 6, "{:?}", extension);
+                // Only functions unknown to the security monitor have no arity, in which case all arguments are forwarded.
+                assert_eq!(number_of_arguments.is_none(), format!("{:?}", extension).contains("Unknown"), "{:?}", extension);
+            }
+        }
+    }
+
+    #[test]
+    fn arity_follows_sbi_specification() {
+        for (extension_id, function_id, number_of_arguments) in [
+            (BaseExtension::EXTID, 0, 0),
+            (BaseExtension::EXTID, 3, 1),
+            (IpiExtension::EXTID, 0, 2),
+            (RfenceExtension::EXTID, 0, 2),
+            (RfenceExtension::EXTID, 1, 4),
+            (RfenceExtension::EXTID, 2, 5),
+            (HsmExtension::EXTID, HsmExtension::HART_START_FID, 3),
+            (HsmExtension::EXTID, HsmExtension::HART_STOP_FID, 0),
+            (HsmExtension::EXTID, HsmExtension::HART_STATUS_FID, 1),
+            (SrstExtension::EXTID, SrstExtension::SYSTEM_RESET_FID, 2),
+        ] {
+            assert_eq!(SbiExtension::decode(extension_id, function_id).number_of_arguments(), Some(number_of_arguments));
+        }
+    }
+
+    #[test]
+    fn unknown_extension_has_no_arity() {
+        assert_eq!(SbiExtension::decode(0x0a00_0000, 0).number_of_arguments(), None);
+        assert_eq!(SbiExtension::decode(BaseExtension::EXTID, 0x100).number_of_arguments(), None);
+    }
 }
@@ -45,4 +45,27 @@ impl TrapCause {
             }
         }
     }
+
+    /// Returns the value of `mcause` that the hardware writes for this trap. Interrupts are reported as the supervisor software
+    /// interrupt, because the security monitor does not distinguish between interrupts when decoding the trap cause.
+    #[cfg(feature = "testing")]
+    pub fn code(&self) -> usize {
+        let code = match self {
+            Self::Interrupt => return (1 << CAUSE_INTERRUPT_BIT) | MIE_SSIP,
+            Self::IllegalInstruction => CAUSE_ILLEGAL_INSTRUCTION,
+            Self::LoadAddressMisaligned => CAUSE_MISALIGNED_LOAD,
+            Self::LoadAccessFault => CAUSE_LOAD_ACCESS,
+            Self::StoreAddressMisaligned => CAUSE_MISALIGNED_STORE,
+            Self::StoreAccessFault => CAUSE_STORE_ACCESS,
+            Self::VsEcall(_) => CAUSE_VIRTUAL_SUPERVISOR_ECALL,
+            Self::HsEcall(_) => CAUSE_SUPERVISOR_ECALL,
+            Self::MachineEcall => CAUSE_MACHINE_ECALL,
+            Self::GuestInstructionPageFault => CAUSE_FETCH_GUEST_PAGE_FAULT,
+            Self::GuestLoadPageFault => CAUSE_LOAD_GUEST_PAGE_FAULT,
+            Self::VirtualInstruction => CAUSE_VIRTUAL_INSTRUCTION,
+            Self::GuestStorePageFault => CAUSE_STORE_GUEST_PAGE_FAULT,
+            Self::Unknown(cause) => *cause,
+        };
+        code.into()
+    }
 }
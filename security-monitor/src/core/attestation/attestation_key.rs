@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::attestation::KeyHandover;
+use crate::core::crypto::Secret;
+use crate::core::measurement::HmacSha384;
+use crate::error::Error;
+use alloc::vec::Vec;
+use spin::Once;
+
+/// The key with which the security monitor signs attestation reports, together with the certificate chain that endorses it. It is set
+/// once during the initialization and the key never leaves the security monitor's memory. If the previous boot stage did not hand
+/// over valid key material, the security monitor rejects all attestation requests.
+static ATTESTATION_KEY: Once<AttestationKey> = Once::new();
+
+pub struct AttestationKey {
+    value: Secret<{ HmacSha384::MAC_SIZE_IN_BYTES }>,
+    certificate_chain: Vec<u8>,
+}
+
+impl AttestationKey {
+    const SIZE_IN_BYTES: usize = HmacSha384::MAC_SIZE_IN_BYTES;
+    /// A publicly known key that makes attestation reports reproducible, so that their format can be verified without the hardware.
+    /// It must never be used in production because anyone can forge reports signed with it. Host tests always use this key.
+    #[cfg(any(test, feature = "attestation_test_key"))]
+    const TEST_VECTOR: [u8; Self::SIZE_IN_BYTES] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15,
+        0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b,
+        0x2c, 0x2d, 0x2e, 0x2f,
+    ];
+
+    /// Installs the key material handed over by the previous boot stage. Attestation stays disabled if there is none.
+    #[cfg(not(any(test, feature = "attestation_test_key")))]
+    pub fn init(key_handover: Option<KeyHandover>) {
+        if let Some(key_handover) = key_handover {
+            let (value, certificate_chain) = key_handover.into_parts();
+            ATTESTATION_KEY.call_once(|| Self { value, certificate_chain });
+        }
+    }
+
+    #[cfg(any(test, feature = "attestation_test_key"))]
+    pub fn init(_key_handover: Option<KeyHandover>) {
+        ATTESTATION_KEY.call_once(|| Self { value: Secret::new(Self::TEST_VECTOR), certificate_chain: Vec::new() });
+    }
+
+    /// Returns the signature of the data. Returns error if the security monitor has no attestation key.
+    pub fn sign(data: &[u8]) -> Result<[u8; HmacSha384::MAC_SIZE_IN_BYTES], Error> {
+        let mut mac = HmacSha384::new(Self::get()?.value.expose());
+        mac.update(data);
+        Ok(mac.finalize())
+    }
+
+    /// Returns a key derived from the attestation key for the given purpose. Derived keys are computed as `HMAC(key, label || 0 ||
+    /// context)`, so keys derived for different labels or contexts are independent. Labels never start with the version of the
+    /// attestation report, thus a derived key never equals the signature of a report. Returns error if the security monitor has no
+    /// attestation key.
+    pub fn derive_key(label: &[u8], context: &[u8]) -> Result<Secret<{ HmacSha384::MAC_SIZE_IN_BYTES }>, Error> {
+        let mut mac = HmacSha384::new(Self::get()?.value.expose());
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(context);
+        Ok(Secret::new(mac.finalize()))
+    }
+
+    /// Returns the certificate chain endorsing the attestation key, as handed over by the previous boot stage.
+    pub fn certificate_chain() -> Result<&'static [u8], Error> {
+        Ok(&Self::get()?.certificate_chain)
+    }
+
+    fn get() -> Result<&'static Self, Error> {
+        ATTESTATION_KEY.get().ok_or(Error::AttestationKeyUnavailable())
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::AceExtension;
+use crate::core::attestation::{AttestationKey, TcbInfo};
+use crate::core::control_data::MeasurementRegisters;
+use crate::core::crypto::ED25519_PUBLIC_KEY_SIZE_IN_BYTES;
+use crate::core::measurement::{HmacSha384, Sha384};
+use crate::error::Error;
+
+/// The attestation evidence that the security monitor issues to a confidential VM. The report binds the challenge provided by the
+/// confidential VM (e.g., a nonce of the relying party) to the confidential VM's measurements and configuration, and to the security
+/// monitor that issued it.
+///
+/// The report has a fixed size and is built on the stack. All integers are encoded in little endian:
+///   * 0x000: format version (u64),
+///   * 0x008: security monitor identity (u64), i.e., the SBI extension id of the security monitor,
+///   * 0x010: TCB information of the security monitor (64 bytes), i.e., its version, security version number, and build identity, see
+///     `TcbInfo`,
+///   * 0x050: challenge (64 bytes),
+///   * 0x090: configuration flags (u64), bit 0 is set for debuggable confidential VMs, bit 1 is set if the security monitor verified the
+///     signature over the kernel image at launch,
+///   * 0x098: Ed25519 public key of the kernel image signer (32 bytes), zero if the launch signature was not verified,
+///   * 0x0b8: ids of SBI extensions available to the confidential VM (16 x u64), unused entries are zero,
+///   * 0x138: measurement registers in the order of their indices (8 x 48 bytes),
+///   * 0x2b8: HMAC-SHA384 of all preceding bytes computed with the attestation key (48 bytes).
+pub struct AttestationReport {
+    bytes: [u8; Self::SIZE_IN_BYTES],
+}
+
+impl AttestationReport {
+    pub const CHALLENGE_SIZE_IN_BYTES: usize = 64;
+    pub const SIZE_IN_BYTES: usize = Self::SIGNATURE_OFFSET + HmacSha384::MAC_SIZE_IN_BYTES;
+    const FORMAT_VERSION: u64 = 4;
+    const DEBUGGABLE_FLAG: u64 = 0x1;
+    const LAUNCH_SIGNATURE_VERIFIED_FLAG: u64 = 0x2;
+    const MAX_NUMBER_OF_EXTENSIONS: usize = 16;
+    const SIGNATURE_OFFSET: usize = 2 * 8
+        + TcbInfo::SIZE_IN_BYTES
+        + Self::CHALLENGE_SIZE_IN_BYTES
+        + 8
+        + ED25519_PUBLIC_KEY_SIZE_IN_BYTES
+        + Self::MAX_NUMBER_OF_EXTENSIONS * 8
+        + MeasurementRegisters::NUMBER_OF_REGISTERS * Sha384::DIGEST_SIZE_IN_BYTES;
+
+    /// Serializes and signs the report. Returns error if there are more SBI extensions than the report can hold or if the security
+    /// monitor has no attestation key.
+    pub fn new(
+        challenge: &[u8; Self::CHALLENGE_SIZE_IN_BYTES], measurements: &MeasurementRegisters, is_debuggable: bool,
+        launch_signer: Option<&[u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES]>, allowed_extensions: &[usize],
+    ) -> Result<Self, Error> {
+        assure!(allowed_extensions.len() <= Self::MAX_NUMBER_OF_EXTENSIONS, Error::InvalidArgument())?;
+        let mut report = Self { bytes: [0u8; Self::SIZE_IN_BYTES] };
+        let mut offset = 0;
+        let mut append = |data: &[u8]| {
+            report.bytes[offset..offset + data.len()].copy_from_slice(data);
+            offset += data.len();
+        };
+        append(&Self::FORMAT_VERSION.to_le_bytes());
+        append(&(AceExtension::EXTID as u64).to_le_bytes());
+        append(TcbInfo::current().as_bytes());
+        append(challenge);
+        let mut flags = if is_debuggable { Self::DEBUGGABLE_FLAG } else { 0 };
+        if launch_signer.is_some() {
+            flags |= Self::LAUNCH_SIGNATURE_VERIFIED_FLAG;
+        }
+        append(&flags.to_le_bytes());
+        append(launch_signer.unwrap_or(&[0u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES]));
+        (0..Self::MAX_NUMBER_OF_EXTENSIONS)
+            .map(|index| allowed_extensions.get(index).map_or(0, |extension_id| *extension_id as u64))
+            .for_each(|extension_id| append(&extension_id.to_le_bytes()));
+        measurements.iter().for_each(|measurement| append(&measurement.value[..Sha384::DIGEST_SIZE_IN_BYTES]));
+        let signature = AttestationKey::sign(&report.bytes[..Self::SIGNATURE_OFFSET])?;
+        report.bytes[Self::SIGNATURE_OFFSET..].copy_from_slice(&signature);
+        Ok(report)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::control_data::ConfidentialVmMeasurement;
+
+    const CHALLENGE: [u8; AttestationReport::CHALLENGE_SIZE_IN_BYTES] = [0x5a; AttestationReport::CHALLENGE_SIZE_IN_BYTES];
+    const LAUNCH_SIGNER: [u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES] = [0x33; ED25519_PUBLIC_KEY_SIZE_IN_BYTES];
+
+    // Host tests sign reports with the fixed test vector of the attestation key.
+    fn test_key() -> [u8; HmacSha384::MAC_SIZE_IN_BYTES] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    fn measurements() -> MeasurementRegisters {
+        let mut launch_measurements = [ConfidentialVmMeasurement::empty(); MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS];
+        launch_measurements.iter_mut().enumerate().for_each(|(index, measurement)| measurement.value.fill(index as u8 + 1));
+        MeasurementRegisters::new(launch_measurements)
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn report_follows_documented_layout() {
+        AttestationKey::init(None);
+        let extensions = [AceExtension::EXTID, 0x10];
+        let report = AttestationReport::new(&CHALLENGE, &measurements(), true, Some(&LAUNCH_SIGNER), &extensions).unwrap();
+        let bytes = report.as_bytes();
+        assert_eq!(bytes.len(), 0x2b8 + HmacSha384::MAC_SIZE_IN_BYTES);
+        assert_eq!(u64_at(bytes, 0x000), AttestationReport::FORMAT_VERSION);
+        assert_eq!(u64_at(bytes, 0x008), AceExtension::EXTID as u64);
+        assert_eq!(&bytes[0x010..0x050], TcbInfo::current().as_bytes());
+        assert_eq!(&bytes[0x050..0x090], &CHALLENGE);
+        assert_eq!(u64_at(bytes, 0x090), AttestationReport::DEBUGGABLE_FLAG | AttestationReport::LAUNCH_SIGNATURE_VERIFIED_FLAG);
+        assert_eq!(&bytes[0x098..0x0b8], &LAUNCH_SIGNER);
+        assert_eq!([u64_at(bytes, 0x0b8), u64_at(bytes, 0x0c0), u64_at(bytes, 0x0c8)], [AceExtension::EXTID as u64, 0x10, 0]);
+        for index in 0..MeasurementRegisters::NUMBER_OF_REGISTERS {
+            let offset = 0x138 + index * Sha384::DIGEST_SIZE_IN_BYTES;
+            let expected = if index < MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS { index as u8 + 1 } else { 0 };
+            assert!(bytes[offset..offset + Sha384::DIGEST_SIZE_IN_BYTES].iter().all(|byte| *byte == expected));
+        }
+    }
+
+    #[test]
+    fn report_is_signed_with_attestation_key() {
+        AttestationKey::init(None);
+        let report = AttestationReport::new(&CHALLENGE, &measurements(), false, None, &[]).unwrap();
+        let bytes = report.as_bytes();
+        let mut mac = HmacSha384::new(&test_key());
+        mac.update(&bytes[..0x2b8]);
+        assert_eq!(&bytes[0x2b8..], &mac.finalize());
+        assert_eq!(u64_at(bytes, 0x090), 0);
+        assert!(bytes[0x098..0x0b8].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn too_many_extensions_are_rejected() {
+        AttestationKey::init(None);
+        let extensions = [0x10; AttestationReport::MAX_NUMBER_OF_EXTENSIONS + 1];
+        assert!(matches!(AttestationReport::new(&CHALLENGE, &measurements(), false, None, &extensions), Err(Error::InvalidArgument())));
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::crypto::{constant_time_eq, constant_time_select, Secret};
+use crate::core::measurement::{HmacSha384, Sha384};
+use crate::error::Error;
+use alloc::vec::Vec;
+
+/// The attestation key material that the previous boot stage (e.g., the hardware root of trust) hands over to the security monitor. The
+/// previous boot stage places the structure in memory and describes its location with the `ace,attestation-handover` FDT property.
+///
+/// All integers are encoded in little endian:
+///   * 0x00: magic (u32), `KEY_HANDOVER_MAGIC`,
+///   * 0x04: format version (u32), `KEY_HANDOVER_VERSION`,
+///   * 0x08: key type (u32), `KEY_TYPE_HMAC_SHA384` is the only supported type,
+///   * 0x0c: size of the key in bytes (u32), must match the key type,
+///   * 0x10: size of the certificate chain in bytes (u32), at most `MAX_CERTIFICATE_CHAIN_SIZE`,
+///   * 0x14: reserved (u32), must be zero,
+///   * 0x18: the key followed by the certificate chain,
+///   * at the end: SHA-384 digest of all preceding bytes (48 bytes), which detects truncated or corrupted structures.
+pub struct KeyHandover {
+    key: Secret<{ HmacSha384::MAC_SIZE_IN_BYTES }>,
+    certificate_chain: Vec<u8>,
+}
+
+impl KeyHandover {
+    const KEY_HANDOVER_MAGIC: u32 = 0x4b454341;
+    const KEY_HANDOVER_VERSION: u32 = 1;
+    const KEY_TYPE_HMAC_SHA384: u32 = 1;
+    const MAX_CERTIFICATE_CHAIN_SIZE: usize = 16 * 1024;
+    const HEADER_SIZE: usize = 0x18;
+
+    /// Parses and validates the handover structure. Returns error if the structure is malformed. On success, the key material is copied
+    /// to the security monitor's memory, so the caller can erase the source.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let field = |offset: usize| -> Result<u32, Error> {
+            let value = bytes.get(offset..offset + 4).ok_or(Error::MalformedKeyHandover())?;
+            Ok(u32::from_le_bytes(value.try_into().map_err(|_| Error::MalformedKeyHandover())?))
+        };
+        assure!(field(0x00)? == Self::KEY_HANDOVER_MAGIC, Error::MalformedKeyHandover())?;
+        assure!(field(0x04)? == Self::KEY_HANDOVER_VERSION, Error::MalformedKeyHandover())?;
+        assure!(field(0x08)? == Self::KEY_TYPE_HMAC_SHA384, Error::MalformedKeyHandover())?;
+        let key_size = field(0x0c)? as usize;
+        let certificate_chain_size = field(0x10)? as usize;
+        assure!(field(0x14)? == 0, Error::MalformedKeyHandover())?;
+        assure!(key_size == HmacSha384::MAC_SIZE_IN_BYTES, Error::MalformedKeyHandover())?;
+        assure!(certificate_chain_size <= Self::MAX_CERTIFICATE_CHAIN_SIZE, Error::MalformedKeyHandover())?;
+
+        // The sizes are bounded above, so the offsets cannot overflow.
+        let certificate_chain_offset = Self::HEADER_SIZE + key_size;
+        let digest_offset = certificate_chain_offset + certificate_chain_size;
+        let expected_digest = bytes.get(digest_offset..digest_offset + Sha384::DIGEST_SIZE_IN_BYTES).ok_or(Error::MalformedKeyHandover())?;
+        let is_intact = constant_time_eq(&Sha384::digest(&bytes[..digest_offset]), expected_digest);
+
+        // The digest covers the key, so we do not branch on the comparison before the key is copied. A corrupted key is replaced with
+        // zeros and never leaves this function.
+        let mut key = [0u8; HmacSha384::MAC_SIZE_IN_BYTES];
+        key.copy_from_slice(&bytes[Self::HEADER_SIZE..certificate_chain_offset]);
+        let key = Secret::new(constant_time_select(is_intact, &key, &[0u8; HmacSha384::MAC_SIZE_IN_BYTES]));
+        assure!(is_intact, Error::MalformedKeyHandover())?;
+        let certificate_chain = bytes[certificate_chain_offset..digest_offset].to_vec();
+        Ok(Self { key, certificate_chain })
+    }
+
+    pub fn into_parts(self) -> (Secret<{ HmacSha384::MAC_SIZE_IN_BYTES }>, Vec<u8>) {
+        (self.key, self.certificate_chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; HmacSha384::MAC_SIZE_IN_BYTES] = [0x4b; HmacSha384::MAC_SIZE_IN_BYTES];
+    const CERTIFICATE_CHAIN: &[u8] = b"certificate chain";
+
+    // Returns the handover structure after applying the modification to its header and content, with a digest that matches them.
+    fn handover_with(modify: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let header = [KeyHandover::KEY_HANDOVER_MAGIC, KeyHandover::KEY_HANDOVER_VERSION, KeyHandover::KEY_TYPE_HMAC_SHA384];
+        header.iter().for_each(|field| bytes.extend_from_slice(&field.to_le_bytes()));
+        bytes.extend_from_slice(&(KEY.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(CERTIFICATE_CHAIN.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&KEY);
+        bytes.extend_from_slice(CERTIFICATE_CHAIN);
+        modify(&mut bytes);
+        let digest = Sha384::digest(&bytes);
+        bytes.extend_from_slice(&digest);
+        bytes
+    }
+
+    fn set_field(bytes: &mut Vec<u8>, offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn is_rejected(bytes: &[u8]) -> bool {
+        matches!(KeyHandover::parse(bytes), Err(Error::MalformedKeyHandover()))
+    }
+
+    #[test]
+    fn valid_handover_is_copied() {
+        let (key, certificate_chain) = KeyHandover::parse(&handover_with(|_| {})).unwrap().into_parts();
+        assert_eq!(key.expose(), &KEY);
+        assert_eq!(certificate_chain, CERTIFICATE_CHAIN);
+    }
+
+    #[test]
+    fn malformed_header_is_rejected() {
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x00, 0x12345678))));
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x04, KeyHandover::KEY_HANDOVER_VERSION + 1))));
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x08, KeyHandover::KEY_TYPE_HMAC_SHA384 + 1))));
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x0c, KEY.len() as u32 - 1))));
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x14, 1))));
+    }
+
+    #[test]
+    fn oversized_certificate_chain_is_rejected() {
+        let size = KeyHandover::MAX_CERTIFICATE_CHAIN_SIZE + 1;
+        assert!(is_rejected(&handover_with(|bytes| {
+            set_field(bytes, 0x10, size as u32);
+            bytes.resize(KeyHandover::HEADER_SIZE + KEY.len() + size, 0);
+        })));
+    }
+
+    #[test]
+    fn truncated_handover_is_rejected() {
+        let bytes = handover_with(|_| {});
+        for length in [0, 3, KeyHandover::HEADER_SIZE, bytes.len() - 1] {
+            assert!(is_rejected(&bytes[..length]));
+        }
+        // A certificate chain size pointing beyond the structure.
+        assert!(is_rejected(&handover_with(|bytes| set_field(bytes, 0x10, CERTIFICATE_CHAIN.len() as u32 + 64))));
+    }
+
+    #[test]
+    fn corrupted_handover_is_rejected() {
+        let valid = handover_with(|_| {});
+        for offset in [KeyHandover::HEADER_SIZE, KeyHandover::HEADER_SIZE + KEY.len(), valid.len() - 1] {
+            let mut bytes = valid.clone();
+            bytes[offset] ^= 0x01;
+            assert!(is_rejected(&bytes));
+        }
+    }
+}
@@ -0,0 +1,14 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+pub use attestation_key::AttestationKey;
+pub use attestation_report::AttestationReport;
+pub use key_handover::KeyHandover;
+pub use sealed_data::SealedData;
+pub use tcb_info::{TcbInfo, SECURITY_VERSION_NUMBER};
+
+mod attestation_key;
+mod attestation_report;
+mod key_handover;
+mod sealed_data;
+mod tcb_info;
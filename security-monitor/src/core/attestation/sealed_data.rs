@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::attestation::AttestationKey;
+use crate::core::crypto::{constant_time_eq, Secret};
+use crate::core::entropy::{ChaCha20, EntropyPool};
+use crate::core::measurement::{HmacSha384, Sha384};
+use crate::error::Error;
+use alloc::vec::Vec;
+
+/// Data that a confidential VM sealed to its launch measurement. Sealed data is encrypted and authenticated with keys derived from the
+/// attestation key and the launch digest, so only a confidential VM with the same launch measurement, running on a security monitor
+/// that holds the same attestation key, recovers it. The confidential VM can thus store sealed data in the non-confidential memory or
+/// on an untrusted disk.
+///
+/// The sealed data has the following layout. All integers are encoded in little endian:
+///   * 0x00: format version (u64),
+///   * 0x08: size of the data (u64),
+///   * 0x10: nonce (12 bytes) followed by 4 zero bytes,
+///   * 0x20: data encrypted with the ChaCha20 key stream,
+///   * HMAC-SHA384 of all preceding bytes (48 bytes).
+pub struct SealedData {}
+
+impl SealedData {
+    pub const MAX_DATA_SIZE_IN_BYTES: usize = 4096;
+    pub const OVERHEAD_IN_BYTES: usize = Self::HEADER_SIZE_IN_BYTES + HmacSha384::MAC_SIZE_IN_BYTES;
+    const HEADER_SIZE_IN_BYTES: usize = 0x20;
+    const NONCE_OFFSET: usize = 0x10;
+    const NONCE_SIZE_IN_BYTES: usize = 12;
+    const FORMAT_VERSION: u64 = 1;
+    const ENCRYPTION_KEY_SIZE_IN_BYTES: usize = 32;
+    const ENCRYPTION_KEY_LABEL: &'static [u8] = b"ACE sealing encryption key";
+    const AUTHENTICATION_KEY_LABEL: &'static [u8] = b"ACE sealing authentication key";
+    const GUEST_KEY_LABEL: &'static [u8] = b"ACE sealing guest key";
+
+    /// Returns the sealed data. Every call uses a fresh nonce, so sealing the same data twice yields different sealed data. Returns
+    /// error if the data is too large, or there is no attestation key or no entropy source.
+    pub fn seal(data: &[u8], launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Result<Vec<u8>, Error> {
+        assure!(data.len() <= Self::MAX_DATA_SIZE_IN_BYTES, Error::InvalidArgument())?;
+        let mut nonce = [0u8; Self::NONCE_SIZE_IN_BYTES];
+        EntropyPool::fill(&mut nonce)?;
+        Self::seal_with_nonce(data, &nonce, launch_digest)
+    }
+
+    fn seal_with_nonce(
+        data: &[u8], nonce: &[u8; Self::NONCE_SIZE_IN_BYTES], launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES],
+    ) -> Result<Vec<u8>, Error> {
+        let mut sealed_data = Vec::with_capacity(data.len() + Self::OVERHEAD_IN_BYTES);
+        sealed_data.extend_from_slice(&Self::FORMAT_VERSION.to_le_bytes());
+        sealed_data.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        sealed_data.extend_from_slice(nonce);
+        sealed_data.resize(Self::HEADER_SIZE_IN_BYTES, 0);
+        sealed_data.extend_from_slice(data);
+        Self::apply_key_stream(&mut sealed_data[Self::HEADER_SIZE_IN_BYTES..], nonce, launch_digest)?;
+        let tag = Self::tag(&sealed_data, launch_digest)?;
+        sealed_data.extend_from_slice(&tag);
+        Ok(sealed_data)
+    }
+
+    /// Returns the data recovered from the sealed data. Returns error if the sealed data was modified or it was sealed by a confidential
+    /// VM with a different launch measurement.
+    pub fn unseal(sealed_data: &[u8], launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Result<Vec<u8>, Error> {
+        assure!(sealed_data.len() >= Self::OVERHEAD_IN_BYTES, Error::SealedDataCorrupted())?;
+        let (authenticated_data, tag) = sealed_data.split_at(sealed_data.len() - HmacSha384::MAC_SIZE_IN_BYTES);
+        // The tag is verified before any field is interpreted, so the content of a forged header is never used.
+        assure!(constant_time_eq(&Self::tag(authenticated_data, launch_digest)?, tag), Error::SealedDataCorrupted())?;
+        let (header, encrypted_data) = authenticated_data.split_at(Self::HEADER_SIZE_IN_BYTES);
+        let format_version = u64::from_le_bytes(header[0..8].try_into().map_err(|_| Error::SealedDataCorrupted())?);
+        let data_size = u64::from_le_bytes(header[8..16].try_into().map_err(|_| Error::SealedDataCorrupted())?);
+        assure!(format_version == Self::FORMAT_VERSION, Error::SealedDataCorrupted())?;
+        assure!(data_size == encrypted_data.len() as u64, Error::SealedDataCorrupted())?;
+        let nonce = header[Self::NONCE_OFFSET..Self::NONCE_OFFSET + Self::NONCE_SIZE_IN_BYTES]
+            .try_into()
+            .map_err(|_| Error::SealedDataCorrupted())?;
+        let mut data = encrypted_data.to_vec();
+        Self::apply_key_stream(&mut data, &nonce, launch_digest)?;
+        Ok(data)
+    }
+
+    /// Returns the sealing key handed to the confidential VM itself, so that it can seal data with its own algorithms. The key is
+    /// derived with a distinct label, thus it reveals nothing about the keys that protect sealed data.
+    pub fn guest_key(launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Result<Secret<{ HmacSha384::MAC_SIZE_IN_BYTES }>, Error> {
+        AttestationKey::derive_key(Self::GUEST_KEY_LABEL, launch_digest)
+    }
+
+    fn apply_key_stream(
+        data: &mut [u8], nonce: &[u8; Self::NONCE_SIZE_IN_BYTES], launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES],
+    ) -> Result<(), Error> {
+        let derived_key = AttestationKey::derive_key(Self::ENCRYPTION_KEY_LABEL, launch_digest)?;
+        let key = Secret::new(
+            derived_key.expose()[..Self::ENCRYPTION_KEY_SIZE_IN_BYTES].try_into().map_err(|_| Error::AttestationKeyUnavailable())?,
+        );
+        let mut chacha20 = ChaCha20::new(key.expose(), nonce);
+        data.chunks_mut(ChaCha20::BLOCK_SIZE_IN_BYTES).for_each(|chunk| {
+            let key_stream = chacha20.next_block();
+            chunk.iter_mut().zip(key_stream.iter()).for_each(|(byte, key_stream_byte)| *byte ^= key_stream_byte);
+        });
+        Ok(())
+    }
+
+    fn tag(
+        authenticated_data: &[u8], launch_digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES],
+    ) -> Result<[u8; HmacSha384::MAC_SIZE_IN_BYTES], Error> {
+        let key = AttestationKey::derive_key(Self::AUTHENTICATION_KEY_LABEL, launch_digest)?;
+        let mut mac = HmacSha384::new(key.expose());
+        mac.update(authenticated_data);
+        Ok(mac.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: [u8; SealedData::NONCE_SIZE_IN_BYTES] = [0x4e; SealedData::NONCE_SIZE_IN_BYTES];
+    const LAUNCH_DIGEST: [u8; Sha384::DIGEST_SIZE_IN_BYTES] = [0x1d; Sha384::DIGEST_SIZE_IN_BYTES];
+    const DATA: &[u8] = b"disk encryption key of the confidential VM";
+
+    fn sealed_data() -> Vec<u8> {
+        AttestationKey::init(None);
+        SealedData::seal_with_nonce(DATA, &NONCE, &LAUNCH_DIGEST).unwrap()
+    }
+
+    #[test]
+    fn sealed_data_is_unsealed() {
+        let sealed_data = sealed_data();
+        assert_eq!(sealed_data.len(), DATA.len() + SealedData::OVERHEAD_IN_BYTES);
+        assert_eq!(SealedData::unseal(&sealed_data, &LAUNCH_DIGEST).unwrap(), DATA);
+    }
+
+    #[test]
+    fn sealed_data_does_not_contain_plaintext() {
+        let sealed_data = sealed_data();
+        assert!(!sealed_data.windows(DATA.len()).any(|window| window == DATA));
+    }
+
+    #[test]
+    fn other_launch_digest_fails_integrity_check() {
+        let sealed_data = sealed_data();
+        let other_launch_digest = [0x2e; Sha384::DIGEST_SIZE_IN_BYTES];
+        assert!(matches!(SealedData::unseal(&sealed_data, &other_launch_digest), Err(Error::SealedDataCorrupted())));
+    }
+
+    #[test]
+    fn flipped_byte_is_rejected() {
+        let sealed_data = sealed_data();
+        let ciphertext_offset = SealedData::HEADER_SIZE_IN_BYTES;
+        let tag_offset = sealed_data.len() - HmacSha384::MAC_SIZE_IN_BYTES;
+        for offset in [ciphertext_offset, tag_offset - 1, tag_offset, sealed_data.len() - 1] {
+            let mut modified_sealed_data = sealed_data.clone();
+            modified_sealed_data[offset] ^= 0x01;
+            assert!(matches!(SealedData::unseal(&modified_sealed_data, &LAUNCH_DIGEST), Err(Error::SealedDataCorrupted())));
+        }
+    }
+
+    #[test]
+    fn forged_header_is_rejected_before_parsing() {
+        // The size field claims more data than present, which would make the parser read beyond the buffer if it trusted the header.
+        let mut sealed_data = sealed_data();
+        sealed_data[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(SealedData::unseal(&sealed_data, &LAUNCH_DIGEST), Err(Error::SealedDataCorrupted())));
+        // Sealed data shorter than the header and the tag is rejected without being interpreted.
+        assert!(matches!(
+            SealedData::unseal(&sealed_data[..SealedData::OVERHEAD_IN_BYTES - 1], &LAUNCH_DIGEST),
+            Err(Error::SealedDataCorrupted())
+        ));
+    }
+}
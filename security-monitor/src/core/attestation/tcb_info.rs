@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_protector::TlbFence;
+
+/// The security version number (SVN) of the security monitor. Increment it with every release that fixes a vulnerability, so that
+/// relying parties can reject evidence issued by a security monitor that is known to be vulnerable.
+pub const SECURITY_VERSION_NUMBER: u64 = 1;
+
+/// Identifies the trusted computing base (TCB) of the security monitor, i.e., the exact build that issues attestation evidence. The
+/// information is fixed at compile time, except for the ISA extensions detected during the boot, and has a fixed size. All integers
+/// are encoded in little endian:
+///   * 0x00: security monitor version (u64), encoded as `major << 32 | minor << 16 | patch`,
+///   * 0x08: security version number (u64),
+///   * 0x10: bitmap of features (u64), compile-time features in the lower 32 bits, see `FEATURES`, and ISA extensions used by the security
+///     monitor in the upper 32 bits, see `capabilities`,
+///   * 0x18: build identity (40 bytes), the ASCII git commit hash from the `ACE_BUILD_ID` variable set at compile time, zero padded.
+pub struct TcbInfo {
+    bytes: [u8; Self::SIZE_IN_BYTES],
+}
+
+impl TcbInfo {
+    pub const SIZE_IN_BYTES: usize = 3 * 8 + Self::BUILD_ID_SIZE_IN_BYTES;
+    const BUILD_ID_SIZE_IN_BYTES: usize = 40;
+    // Features that change the security properties of the security monitor. The bit of a feature is its index in this array.
+    const FEATURES: [bool; 5] = [
+        cfg!(feature = "verbose"),
+        cfg!(feature = "declassification_log"),
+        cfg!(feature = "memory-encryption"),
+        cfg!(feature = "attestation_test_key"),
+        cfg!(feature = "pmp_audit_log"),
+    ];
+
+    pub fn current() -> Self {
+        let mut bytes = [0u8; Self::SIZE_IN_BYTES];
+        bytes[0..8].copy_from_slice(&Self::version().to_le_bytes());
+        bytes[8..16].copy_from_slice(&SECURITY_VERSION_NUMBER.to_le_bytes());
+        bytes[16..24].copy_from_slice(&Self::features().to_le_bytes());
+        let build_id = option_env!("ACE_BUILD_ID").unwrap_or("").as_bytes();
+        let build_id_length = build_id.len().min(Self::BUILD_ID_SIZE_IN_BYTES);
+        bytes[24..24 + build_id_length].copy_from_slice(&build_id[..build_id_length]);
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; Self::SIZE_IN_BYTES] {
+        &self.bytes
+    }
+
+    pub fn version() -> u64 {
+        let parse = |value: &str| value.parse::<u64>().unwrap_or(0);
+        let major = parse(env!("CARGO_PKG_VERSION_MAJOR"));
+        let minor = parse(env!("CARGO_PKG_VERSION_MINOR"));
+        let patch = parse(env!("CARGO_PKG_VERSION_PATCH"));
+        (major << 32) | (minor << 16) | patch
+    }
+
+    fn features() -> u64 {
+        const CAPABILITIES_SHIFT: u64 = 32;
+        let features =
+            Self::FEATURES.iter().enumerate().filter(|(_, is_enabled)| **is_enabled).fold(0, |bitmap, (bit, _)| bitmap | (1 << bit));
+        features | (Self::capabilities() << CAPABILITIES_SHIFT)
+    }
+
+    /// ISA extensions detected during the boot that change how the security monitor operates. Bit 0 is set if TLB invalidations are
+    /// batched with the Svinval extension.
+    fn capabilities() -> u64 {
+        TlbFence::is_svinval_enabled() as u64
+    }
+}
@@ -4,20 +4,37 @@
 use crate::core::architecture::{
     is_bit_enabled, GeneralPurposeRegister, HartArchitecturalState, HartLifecycleState, TrapCause, CSR, ECALL_INSTRUCTION_LENGTH, *,
 };
-use crate::core::control_data::ConfidentialVmId;
+use crate::core::control_data::{
+    ConfidentialVmId, CppcVirtualizer, CsrEmulation, CsrEmulationPolicy, FwftVirtualizer, HartQuiesce, PendingExit, PmuVirtualizer,
+    SseVirtualizer, StealTimeState, TimeVirtualizer, TrapDelegation, VcpuRunstate, WfiPolicy,
+};
+use crate::core::crypto::zeroize;
+use crate::core::entropy::{EntropyPool, VirtualSeed};
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::{ConfidentialVmMemoryProtector, GuestMemoryAccess, PageTableWalker};
 use crate::core::transformations::{
-    EnabledInterrupts, ExposeToConfidentialVm, GuestLoadPageFaultRequest, GuestLoadPageFaultResult, GuestStorePageFaultRequest,
-    GuestStorePageFaultResult, InjectedInterrupts, InterHartRequest, MmioLoadRequest, MmioStoreRequest, PendingRequest, SbiHsmHartStart,
-    SbiHsmHartStatus, SbiHsmHartSuspend, SbiIpi, SbiRemoteFenceI, SbiRemoteSfenceVma, SbiRemoteSfenceVmaAsid, SbiRequest, SbiResult,
-    SharePageRequest, UnsharePageRequest, VirtualInstructionRequest, VirtualInstructionResult,
+    AttestationReportRequest, CertificateChainRequest, CppcRequest, CsrAccess, DbcnReadRequest, DebugRegister, EnabledInterrupts,
+    ExposeToConfidentialVm, FwftRequest, GetMeasurementLogRequest, GuestAccessFaultResult, GuestLoadPageFaultRequest,
+    GuestLoadPageFaultResult, GuestPageFault, GuestStorePageFaultRequest, GuestStorePageFaultResult, IllegalInstructionRequest,
+    IllegalInstructionResult, InjectedInterrupts, InterHartRequest, MeasurementRegisterRequest, MeasurementRegisterValue,
+    MemoryBalloonRequest, MmioAccessFault, MmioLoadRequest, MmioStoreRequest, MmioStoreWidth, PendingRequest, ProbeExtensionRequest,
+    SbiHsmHartStart, SbiHsmHartStatus, SbiHsmHartSuspend, SbiIpi, SbiPmuRequest, SbiRemoteFenceI, SbiRemoteSfenceVma,
+    SbiRemoteSfenceVmaAsid, SbiRequest, SbiResult, SealRequest, SealingKeyRequest, SharePageRequest, SseInterruptedState, SseRequest,
+    SseResult, StealTimeRequest, UnsealRequest, UnsharePageRequest, VerifyCodeIntegrityRequest, VirtualInstructionRequest,
+    VirtualInstructionResult, VirtualizedCsr, VirtualizedCsrResult,
 };
 use crate::error::Error;
+use alloc::sync::Arc;
+use alloc::vec;
 
 extern "C" {
     // Assembly function that is an entry point to the security monitor from the hypervisor or a virtual machine.
     fn enter_from_confidential_hart_asm();
 }
 
+// Fails the build if a change of the layout moves the hart state away from the beginning of the `ConfidentialHart`.
+const _: () = assert!(memoffset::offset_of!(ConfidentialHart, confidential_hart_state) == 0);
+
 /// ConfidentialHart represents the dump state of the confidential VM's hart (aka vcpu). The only publicly exposed way
 /// to modify the confidential hart architectural state (registers/CSRs) is by calling the constructor or applying a
 /// transformation.
@@ -36,9 +53,36 @@ pub struct ConfidentialHart {
     /// A pending request indicates that the confidential hart sent a request to the hypervisor and is waiting for its
     /// reply. The pending request defines the expected response.
     pending_request: Option<PendingRequest>,
+    /// Summarizes the lifecycle state and the pending request for the hypervisor's scheduler. It is updated on every transition of
+    /// either of them.
+    vcpu_runstate: VcpuRunstate,
+    // Why the confidential hart last exited to the hypervisor, if the exit had a reason other than returning a result.
+    pending_exit: Option<PendingExit>,
+    // Shared with all confidential harts of the same confidential VM to coordinate pausing them. Dummy harts do not have it.
+    hart_quiesce: Option<Arc<HartQuiesce>>,
+    // Shared with all confidential harts of the same confidential VM. Dummy harts do not have it, so they emulate no CSR.
+    csr_emulation_policy: Option<Arc<CsrEmulationPolicy>>,
+    pmu_virtualizer: PmuVirtualizer,
+    sse_virtualizer: SseVirtualizer,
+    cppc_virtualizer: CppcVirtualizer,
+    fwft_virtualizer: FwftVirtualizer,
+    virtual_seed: VirtualSeed,
+    // Registered by the confidential hart with the SBI steal-time accounting extension.
+    steal_time: Option<StealTimeState>,
+    // The stored `htimedelta` excludes the time virtualizer's epoch, so it is the offset inherited from the VM at creation.
+    time_virtualizer: TimeVirtualizer,
 }
 
 impl ConfidentialHart {
+    /// By default, WFI traps in the security monitor, so that an idle confidential hart does not stall the physical hart on which the
+    /// hypervisor scheduled it. The `wfi_pass_through` feature selects the native WFI behavior instead.
+    const WFI_POLICY: WfiPolicy =
+        if cfg!(feature = "wfi_pass_through") { WfiPolicy::PassThrough } else { WfiPolicy::TrapToSecurityMonitor };
+    /// The bit of the hypervisor extension in `misa`.
+    const MISA_HYPERVISOR_EXTENSION: usize = 1 << (b'H' - b'A');
+    /// The size of the serialized boot state, see `boot_state`.
+    pub const BOOT_STATE_SIZE_IN_BYTES: usize = 35 * 8;
+
     /// Constructs a dummy hart. This dummy hart carries no confidential information. It is used to indicate that a real
     /// confidential hart has been assigned to a hardware hart for execution.
     pub fn dummy(id: usize) -> Self {
@@ -59,46 +103,87 @@ impl ConfidentialHart {
         Self::new(confidential_hart_state, HartLifecycleState::Stopped)
     }
 
+    /// Constructs a confidential hart in the reset state for a confidential VM that is constructed by the hypervisor, i.e., there is no
+    /// non-confidential hart whose state could be inherited. The confidential hart will execute in the VS-mode once started.
+    pub fn from_reset_state(id: usize) -> Self {
+        let mut confidential_hart_state = HartArchitecturalState::empty(id);
+        confidential_hart_state.mstatus = (1 << CSR_MSTATUS_MPP) | (1 << CSR_MSTATUS_MPV);
+        Self::new(confidential_hart_state, HartLifecycleState::Stopped)
+    }
+
     /// Constructs a confidential hart with the state of the non-confidential hart that made a call to promote the VM to confidential VM
     pub fn from_vm_hart(id: usize, non_confidential_hart_state: &HartArchitecturalState) -> Self {
         let hart_architectural_state = HartArchitecturalState::from_existing(id, non_confidential_hart_state);
         let mut confidential_hart = Self::new(hart_architectural_state, HartLifecycleState::Started);
         confidential_hart.pending_request = Some(PendingRequest::SbiRequest());
+        confidential_hart.vcpu_runstate = VcpuRunstate::BlockedOnSbi;
         confidential_hart
     }
 
     /// Constructs a new confidential hart based on the given architectural state. It configures CSRs to a well-known initial state in which
     /// a confidential hart will execute securely.
     fn new(mut confidential_hart_state: HartArchitecturalState, lifecycle_state: HartLifecycleState) -> Self {
-        confidential_hart_state.sstatus = (1 << CSR_SSTATUS_SPIE) | (1 << CSR_SSTATUS_UXL) | (0b10 << CSR_SSTATUS_FS);
-        confidential_hart_state.hstatus = (1 << CSR_HSTATUS_VTW) | (1 << CSR_HSTATUS_SPVP) | (1 << CSR_HSTATUS_UXL);
-        // Delegate VS-level interrupts directly to the confidential VM. All other interrupts will trap in the security monitor.
-        confidential_hart_state.mideleg = MIE_VSSIP_MASK | MIE_VSTIP_MASK | MIE_VSEIP_MASK;
-        confidential_hart_state.hideleg = confidential_hart_state.mideleg;
+        // The floating-point unit is disabled (FS=Off) because the floating-point state is restored lazily on the first FP instruction.
+        confidential_hart_state.sstatus = (1 << CSR_SSTATUS_SPIE) | (1 << CSR_SSTATUS_UXL);
+        disable_bits(&mut confidential_hart_state.mstatus, CSR_MSTATUS_FS_MASK);
+        confidential_hart_state.hstatus = (1 << CSR_HSTATUS_SPVP) | (1 << CSR_HSTATUS_UXL);
+        // Accesses to state-enable-gated CSRs that the security monitor does not switch raise virtual instruction exceptions.
+        confidential_hart_state.hstateen0 = Smstateen::CONFIDENTIAL_HART_HSTATEEN0;
+        Self::WFI_POLICY.apply(&mut confidential_hart_state.hstatus, &mut confidential_hart_state.mstatus);
+        TrapDelegation::CONFIDENTIAL_HART.apply(&mut confidential_hart_state);
         // the `vsie` register reflects `hie`, so we set up `hie` allowing only VS-level interrupts
         confidential_hart_state.hie = confidential_hart_state.mideleg;
         // Allow only hypervisor's timer interrupts to preemt confidential VM's execution
         confidential_hart_state.mie = MIE_STIP_MASK;
-        // Delegate exceptions that can be handled directly in the confidential VM
-        confidential_hart_state.medeleg = (1 << CAUSE_MISALIGNED_FETCH)
-            | (1 << CAUSE_FETCH_ACCESS)
-            | (1 << CAUSE_ILLEGAL_INSTRUCTION)
-            | (1 << CAUSE_BREAKPOINT)
-            | (1 << CAUSE_MISALIGNED_LOAD)
-            | (1 << CAUSE_LOAD_ACCESS)
-            | (1 << CAUSE_MISALIGNED_STORE)
-            | (1 << CAUSE_STORE_ACCESS)
-            | (1 << CAUSE_USER_ECALL)
-            | (1 << CAUSE_FETCH_PAGE_FAULT)
-            | (1 << CAUSE_LOAD_PAGE_FAULT)
-            | (1 << CAUSE_STORE_PAGE_FAULT);
-        confidential_hart_state.hedeleg = confidential_hart_state.medeleg;
         // Setup the M-mode trap handler to the security monitor's entry point
         confidential_hart_state.mtvec = enter_from_confidential_hart_asm as usize;
 
         // TODO: clear CSRs that are not relevant for the confidential VM execution
 
-        Self { confidential_vm_id: None, confidential_hart_state, lifecycle_state, pending_request: None }
+        let vcpu_runstate = match lifecycle_state {
+            HartLifecycleState::Stopped | HartLifecycleState::Shutdown => VcpuRunstate::Stopped,
+            HartLifecycleState::Suspended => VcpuRunstate::Sleeping,
+            HartLifecycleState::Started | HartLifecycleState::StartPending => VcpuRunstate::Running,
+        };
+        Self {
+            confidential_vm_id: None,
+            confidential_hart_state,
+            lifecycle_state,
+            pending_request: None,
+            vcpu_runstate,
+            pending_exit: None,
+            hart_quiesce: None,
+            csr_emulation_policy: None,
+            pmu_virtualizer: PmuVirtualizer::default(),
+            sse_virtualizer: SseVirtualizer::default(),
+            cppc_virtualizer: CppcVirtualizer::default(),
+            fwft_virtualizer: FwftVirtualizer::default(),
+            virtual_seed: VirtualSeed::default(),
+            steal_time: None,
+            time_virtualizer: TimeVirtualizer::default(),
+        }
+    }
+
+    /// Serializes the part of the architectural state that determines where and how the boot hart starts executing the confidential
+    /// VM's image. A verifier reproduces the serialization from the expected entry state to check the launch measurement. The layout
+    /// is a sequence of 64-bit little-endian values without padding:
+    ///
+    /// | Offset | Value                                                                  |
+    /// |--------|------------------------------------------------------------------------|
+    /// | 0x000  | `mepc`, the address of the instruction at which the boot hart resumes  |
+    /// | 0x008  | general purpose registers `x1` to `x31`, in the order of their indices |
+    /// | 0x100  | `vsstatus`                                                             |
+    /// | 0x108  | `vstvec`                                                               |
+    /// | 0x110  | `vsatp`                                                                |
+    ///
+    /// Other CSRs are not serialized because the security monitor sets them to fixed values when it creates the confidential hart.
+    pub fn boot_state(&self) -> [u8; Self::BOOT_STATE_SIZE_IN_BYTES] {
+        let state = &self.confidential_hart_state;
+        let gprs = (1..32).filter_map(GeneralPurposeRegister::from_index).map(|register| state.gpr(register));
+        let values = core::iter::once(state.mepc).chain(gprs).chain([state.vsstatus, state.vstvec, state.vsatp]);
+        let mut boot_state = [0u8; Self::BOOT_STATE_SIZE_IN_BYTES];
+        boot_state.chunks_exact_mut(8).zip(values).for_each(|(chunk, value)| chunk.copy_from_slice(&(value as u64).to_le_bytes()));
+        boot_state
     }
 
     pub fn set_confidential_vm_id(&mut self, confidential_vm_id: ConfidentialVmId) {
@@ -109,18 +194,140 @@ impl ConfidentialHart {
         self.confidential_vm_id
     }
 
+    pub fn set_hart_quiesce(&mut self, hart_quiesce: Arc<HartQuiesce>) {
+        self.hart_quiesce = Some(hart_quiesce);
+    }
+
+    pub fn hart_quiesce(&self) -> Option<&HartQuiesce> {
+        self.hart_quiesce.as_deref()
+    }
+
+    pub fn set_csr_emulation_policy(&mut self, csr_emulation_policy: Arc<CsrEmulationPolicy>) {
+        self.csr_emulation_policy = Some(csr_emulation_policy);
+    }
+
+    pub(super) fn pmu_virtualizer_mut(&mut self) -> &mut PmuVirtualizer {
+        &mut self.pmu_virtualizer
+    }
+
+    pub(super) fn cppc_virtualizer_mut(&mut self) -> &mut CppcVirtualizer {
+        &mut self.cppc_virtualizer
+    }
+
+    pub(super) fn fwft_virtualizer_mut(&mut self) -> &mut FwftVirtualizer {
+        &mut self.fwft_virtualizer
+    }
+
+    /// Executes the call of the SBI supervisor software events extension. The event delivery modifies the confidential hart's state,
+    /// so it happens when the returned result is applied to the confidential hart.
+    pub fn handle_ecall_sse(&mut self, request: SseRequest) -> Result<SseResult, Error> {
+        self.sse_virtualizer.handle(request, self.confidential_hart_id())
+    }
+
+    /// Registers the confidential VM's memory where the steal time of this confidential hart is published, or disables the steal-time
+    /// accounting. The content of the memory is initialized immediately, so the confidential hart never reads stale values.
+    pub fn set_steal_time(&mut self, request: StealTimeRequest, memory_protector: &mut ConfidentialVmMemoryProtector) -> Result<(), Error> {
+        self.steal_time = None;
+        if let Some(address) = request.address() {
+            let mut steal_time = StealTimeState::new(address);
+            steal_time.publish(memory_protector)?;
+            self.steal_time = Some(steal_time);
+        }
+        Ok(())
+    }
+
+    /// Reads the pending console input into a buffer in the security monitor's memory and copies it to the confidential hart's buffer.
+    /// The input, e.g., a typed password, thus never passes through memory accessible to the hypervisor. Returns the number of bytes
+    /// read, which is zero if there is no pending input.
+    pub fn handle_sbi_dbcn_read(
+        &self, request: DbcnReadRequest, memory_protector: &mut ConfidentialVmMemoryProtector,
+    ) -> Result<usize, Error> {
+        let mut buffer = vec![0u8; request.number_of_bytes()];
+        // Safety: the buffer is owned by the security monitor and OpenSBI writes to it at most as many bytes as its length.
+        let number_of_bytes_read = unsafe { opensbi_sys::sbi_ngets(buffer.as_mut_ptr() as *mut _, buffer.len() as _) } as usize;
+        let result =
+            memory_protector.write_unaligned_bytes(request.address(), &buffer[..number_of_bytes_read]).map(|_| number_of_bytes_read);
+        zeroize(&mut buffer);
+        result
+    }
+
+    /// Publishes the time during which this confidential hart did not execute. The steal-time accounting is disabled if the registered
+    /// memory is no longer accessible, because the confidential hart must be resumed anyway.
+    pub fn publish_steal_time(&mut self, memory_protector: &mut ConfidentialVmMemoryProtector) {
+        if let Some(steal_time) = self.steal_time.as_mut() {
+            if steal_time.resume(memory_protector).is_err() {
+                self.steal_time = None;
+            }
+        }
+    }
+
+    /// Returns how the confidential VM's CSR emulation policy handles accesses to the CSR, or `None` if the policy does not list it.
+    pub fn csr_emulation(&self, csr: u16) -> Option<CsrEmulation> {
+        self.csr_emulation_policy.as_ref().and_then(|policy| policy.emulation(csr))
+    }
+
+    /// Emulates the CSR access according to the policy's entry. Returns the value for the destination register, or `None` if the access
+    /// must raise the illegal instruction exception in the confidential hart.
+    pub fn emulate_csr_access(&mut self, access: &CsrAccess, emulation: CsrEmulation) -> Option<usize> {
+        match emulation {
+            CsrEmulation::Constant(value) if !access.is_write() => Some(value),
+            CsrEmulation::Virtualized(csr) if csr.requires_write_access() == access.is_write() => Some(self.read_virtualized_csr(csr)),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the CSR as emulated for the confidential hart. Reading `seed` consumes the confidential hart's virtual
+    /// entropy. `misa` reports the extensions of the hardware hart except the hypervisor extension, which confidential harts cannot use.
+    fn read_virtualized_csr(&mut self, csr: VirtualizedCsr) -> usize {
+        match csr {
+            VirtualizedCsr::Misa => CSR.misa.read() & !Self::MISA_HYPERVISOR_EXTENSION,
+            VirtualizedCsr::Seed => self.virtual_seed.read(),
+            VirtualizedCsr::Time => self.read_virtual_time(),
+            VirtualizedCsr::Cycle => self.pmu_virtualizer.read_cycle_csr(),
+        }
+    }
+
     pub fn confidential_hart_id(&self) -> usize {
         self.confidential_hart_state.id
     }
 
+    /// Takes the pending request in order to declassify the hypervisor's response to it. From now on, the confidential hart is no longer
+    /// blocked on the hypervisor.
     pub fn take_request(&mut self) -> Option<PendingRequest> {
-        self.pending_request.take()
+        let pending_request = self.pending_request.take();
+        if pending_request.is_some() && self.lifecycle_state == HartLifecycleState::Started {
+            self.vcpu_runstate = VcpuRunstate::Running;
+        }
+        pending_request
+    }
+
+    pub fn has_pending_request(&self) -> bool {
+        self.pending_request.is_some()
+    }
+
+    pub fn vcpu_runstate(&self) -> VcpuRunstate {
+        self.vcpu_runstate
+    }
+
+    pub fn pending_exit(&self) -> Option<PendingExit> {
+        self.pending_exit
+    }
+
+    pub fn set_pending_exit(&mut self, pending_exit: Option<PendingExit>) {
+        self.pending_exit = pending_exit;
     }
 
     pub fn is_dummy(&self) -> bool {
         self.confidential_vm_id.is_none()
     }
 
+    /// Returns the walker of the VS-stage page tables that this confidential hart currently uses. VS-level CSRs are live on the hardware
+    /// hart while the confidential hart executes, so the walker must be used before the confidential hart is returned to the
+    /// confidential VM.
+    pub fn guest_page_table_walker(&self) -> PageTableWalker {
+        PageTableWalker::new(CSR.vsatp.read())
+    }
+
     /// Returns true if this confidential hart can be scheduled on the physical hart.
     pub fn is_executable(&self) -> bool {
         let hart_states_allowed_to_resume = [HartLifecycleState::Started, HartLifecycleState::StartPending, HartLifecycleState::Suspended];
@@ -132,6 +339,10 @@ impl ConfidentialHart {
     /// domain, like hypervisor.
     pub fn set_pending_request(&mut self, request: PendingRequest) -> Result<(), Error> {
         assure!(self.pending_request.is_none(), Error::PendingRequest())?;
+        self.vcpu_runstate = match request {
+            PendingRequest::GuestLoadPageFault(_) | PendingRequest::GuestStorePageFault(_) => VcpuRunstate::BlockedOnMmio,
+            _ => VcpuRunstate::BlockedOnSbi,
+        };
         self.pending_request = Some(request);
         Ok(())
     }
@@ -139,24 +350,85 @@ impl ConfidentialHart {
     /// Dumps control and status registers (CSRs) of the physical hart executing this code to the main memory.
     pub fn store_control_status_registers_in_main_memory(&mut self) -> EnabledInterrupts {
         self.confidential_hart_state.store_control_status_registers_in_main_memory();
+        self.confidential_hart_state.htimedelta = self.time_virtualizer.inherited_htimedelta(self.confidential_hart_state.htimedelta);
+        self.pmu_virtualizer.pause();
+        if let Some(steal_time) = self.steal_time.as_mut() {
+            steal_time.deschedule();
+        }
         // TODO: when moving to CoVE, exposing enabled interrupts becomes an explicit hypercall. We should adapt the same strategy, which
         // would also better reflect out current approach for information declassification.
         self.enabled_interrupts()
     }
 
+    /// Advances the confidential hart's time by a random number of ticks. It is the hook invoked at every context switch to the
+    /// confidential hart, after its CSRs have been loaded. The confidential hart observes the time through `htimedelta`, which the
+    /// hardware adds both to the value returned by `rdtime` and to the time compared against `vstimecmp`, so timers armed by the
+    /// confidential VM remain consistent with the time it reads.
+    pub fn virtualize_mtime(&mut self) {
+        let mut random_bytes = [0u8; core::mem::size_of::<usize>()];
+        // Without entropy, the time is not perturbed, which is still correct, only less private.
+        if EntropyPool::fill(&mut random_bytes).is_ok() {
+            self.time_virtualizer.perturb(usize::from_le_bytes(random_bytes));
+        }
+        CSR.htimedelta.set(self.time_virtualizer.virtualized_htimedelta(self.confidential_hart_state.htimedelta));
+    }
+
+    /// Returns the time as observed by the confidential hart. Used to emulate `rdtime` when reading the time CSR traps because the
+    /// hypervisor has not enabled it in `hcounteren`.
+    fn read_virtual_time(&self) -> usize {
+        self.time_virtualizer.time(CSR.time.read(), self.confidential_hart_state.htimedelta)
+    }
+
     pub fn store_volatile_control_status_registers_in_main_memory(&mut self) {
         self.confidential_hart_state.mepc = CSR.mepc.read();
         self.confidential_hart_state.mstatus = CSR.mstatus.read();
     }
 
-    /// Loads control and status registers (CSRs) from the main memory into the physical hart executing this code.
+    /// Loads control and status registers (CSRs) from the main memory into the physical hart executing this code. The trap delegation
+    /// and the WFI policy are always programmed to the security monitor's fixed configuration. The hypervisor's delegation is restored
+    /// from the hardware hart's state when the confidential hart stops executing on this physical hart. The confidential hart reads
+    /// counters that the hypervisor enabled in `hcounteren`, except the ones that the CSR emulation policy virtualizes, and inherits the
+    /// hypervisor's `henvcfg`, except the pointer masking length that it negotiated with the SBI FWFT extension.
     pub fn load_control_status_registers_from_main_memory(&mut self, interrupts_to_inject: InjectedInterrupts) {
+        self.apply_fixed_configuration();
+        let trapped_counters = self.csr_emulation_policy.as_ref().map_or(0, |policy| policy.trapped_counters());
+        self.confidential_hart_state.hcounteren = CSR.hcounteren.read() & !trapped_counters;
+        self.confidential_hart_state.henvcfg = self.fwft_virtualizer.henvcfg(CSR.henvcfg.read());
         self.confidential_hart_state.load_control_status_registers_from_main_memory();
+        self.pmu_virtualizer.resume();
         // TODO: when moving to CoVE, injecting interrupts becomes an explicit request from the hypervisor to security monitor. We should
         // adapt the same strategy, which would also better reflect out current approach for information declassification.
         self.apply_injected_interrupts(interrupts_to_inject);
     }
 
+    /// Overwrites the parts of the stored state that the security monitor fixes for all confidential harts. The stored state might
+    /// originate from the hypervisor, e.g., when it was inherited during the VM promotion, so we do not trust it. Otherwise, the
+    /// hypervisor could delegate exceptions of the confidential VM to the VS-mode it controls, keep them to observe the confidential VM,
+    /// or change the semantics of WFI that the confidential VM relies on.
+    fn apply_fixed_configuration(&mut self) {
+        TrapDelegation::CONFIDENTIAL_HART.apply(&mut self.confidential_hart_state);
+        Self::WFI_POLICY.apply(&mut self.confidential_hart_state.hstatus, &mut self.confidential_hart_state.mstatus);
+    }
+
+    /// Loads floating-point registers from the main memory into the physical hart and enables the floating-point unit for the
+    /// confidential hart, so that its floating-point instructions execute natively. The unit is enabled in the clean state, so the
+    /// hardware marks it dirty only if the confidential hart modifies the floating-point state.
+    pub fn load_floating_point_state(&mut self) {
+        self.confidential_hart_state.load_floating_point_registers_from_main_memory();
+        disable_bits(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_FS_MASK);
+        enable_bits(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_FS_CLEAN);
+    }
+
+    /// Dumps floating-point registers of the physical hart to the main memory, if the confidential hart modified them, and disables the
+    /// floating-point unit for the confidential hart, so that the next floating-point instruction after rescheduling traps in the
+    /// security monitor.
+    pub fn store_floating_point_state(&mut self) {
+        if self.confidential_hart_state.mstatus & CSR_MSTATUS_FS_MASK == CSR_MSTATUS_FS_DIRTY {
+            self.confidential_hart_state.store_floating_point_registers_in_main_memory();
+        }
+        disable_bits(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_FS_MASK);
+    }
+
     /// Loads control and status registers (CSRs) that might have changed during execution of the security monitor. This function should be
     /// called just before exiting to the assembly context switch, so when we are sure that these CSRs have their final values.
     pub fn load_volatile_control_status_registers_from_main_memory(&self) {
@@ -178,26 +450,42 @@ impl ConfidentialHart {
 
     /// Changes the lifecycle state of the hart into the `StartPending` state. Confidential hart's state is set as if
     /// the hart was reset. This function is called as a response of another confidential hart (typically a boot hart)
-    /// to start another confidential hart. Returns error if the confidential hart is not in stopped state.
-    pub fn transition_from_stopped_to_start_pending(&mut self, request: SbiHsmHartStart) -> Result<(), Error> {
+    /// to start another confidential hart. Returns error if the confidential hart is not in stopped state or the start address is
+    /// not in the confidential VM's memory.
+    pub fn transition_from_stopped_to_start_pending(
+        &mut self, request: SbiHsmHartStart, memory_protector: &ConfidentialVmMemoryProtector,
+    ) -> Result<(), Error> {
         // A hypervisor might try to schedule a stopped confidential hart. This is forbidden.
         assure!(self.lifecycle_state == HartLifecycleState::Stopped, Error::CannotStartNotStoppedHart())?;
         // if this is a dummy hart, then the confidential hart is already running on some other physical hart.
         assure_not!(self.is_dummy(), Error::HartAlreadyRunning())?;
+        // Following the SBI documentation of the function `hart start` in the HSM extension, only vsatp, vsstatus.SIE,
+        // a0, a1 have defined values, all other registers are in an undefined state.
+        self.set_entry_point(memory_protector, request.start_address, self.confidential_hart_id(), request.opaque)?;
         // let's set up the confidential hart so that it can be run
         self.lifecycle_state = HartLifecycleState::StartPending;
         self.pending_request = Some(PendingRequest::SbiHsmHartStartPending());
-        // Following the SBI documentation of the function `hart start` in the HSM extension, only vsatp, vsstatus.SIE,
-        // a0, a1 have defined values, all other registers are in an undefined state. The hart will start
-        // executing in the supervisor mode with disabled MMU (vsatp=0).
+        self.vcpu_runstate = VcpuRunstate::Running;
+        // The hart will start executing in the supervisor mode with disabled MMU (vsatp=0).
         self.confidential_hart_state.vsatp = 0;
         // start the new confidential hart with interrupts disabled
         disable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_SPIE);
         disable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_MPIE);
         disable_bit(&mut self.confidential_hart_state.vsstatus, CSR_STATUS_SIE);
-        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, self.confidential_hart_id());
-        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, request.opaque);
-        self.confidential_hart_state.mepc = request.start_address;
+        Ok(())
+    }
+
+    /// Sets the address at which the confidential hart starts its execution and the values of its a0 and a1 registers. The hart starts
+    /// with the disabled MMU, so the address is a guest physical address. Returns `Error::InvalidArgument` if the address is not
+    /// instruction-aligned or it is not owned by the confidential VM whose memory is protected by the given memory protector.
+    pub fn set_entry_point(
+        &mut self, memory_protector: &ConfidentialVmMemoryProtector, address: usize, arg0: usize, arg1: usize,
+    ) -> Result<(), Error> {
+        assure!(address % INSTRUCTION_ALIGNMENT == 0, Error::InvalidArgument())?;
+        assure!(memory_protector.owns(ConfidentialVmPhysicalAddress::new(address)), Error::InvalidArgument())?;
+        self.confidential_hart_state.mepc = address;
+        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, arg0);
+        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, arg1);
         Ok(())
     }
 
@@ -206,6 +494,7 @@ impl ConfidentialHart {
         assert!(!self.is_dummy());
         if self.lifecycle_state == HartLifecycleState::StartPending {
             self.lifecycle_state = HartLifecycleState::Started;
+            self.vcpu_runstate = VcpuRunstate::Running;
         }
     }
 
@@ -213,6 +502,7 @@ impl ConfidentialHart {
         assert!(!self.is_dummy());
         assure!(self.lifecycle_state == HartLifecycleState::Started, Error::CannotSuspedNotStartedHart())?;
         self.lifecycle_state = HartLifecycleState::Suspended;
+        self.vcpu_runstate = VcpuRunstate::Sleeping;
         Ok(())
     }
 
@@ -220,6 +510,9 @@ impl ConfidentialHart {
         assert!(!self.is_dummy());
         assure!(self.lifecycle_state == HartLifecycleState::Started, Error::CannotStopNotStartedHart())?;
         self.lifecycle_state = HartLifecycleState::Stopped;
+        self.vcpu_runstate = VcpuRunstate::Stopped;
+        // Registered events do not survive the hart stop, so a restarted hart cannot be diverted to a stale handler.
+        self.sse_virtualizer = SseVirtualizer::default();
         Ok(())
     }
 
@@ -227,12 +520,21 @@ impl ConfidentialHart {
         assert!(!self.is_dummy());
         assure!(self.lifecycle_state == HartLifecycleState::Suspended, Error::CannotStartNotSuspendedHart())?;
         self.lifecycle_state = HartLifecycleState::Started;
+        self.vcpu_runstate = VcpuRunstate::Running;
         Ok(())
     }
 
     pub fn transition_to_shutdown(&mut self) {
         assert!(!self.is_dummy());
         self.lifecycle_state = HartLifecycleState::Shutdown;
+        self.vcpu_runstate = VcpuRunstate::Stopped;
+    }
+
+    /// Drops the request awaiting a response from the hypervisor and shuts the confidential hart down, so that the response can never
+    /// be applied to it. Used when the confidential VM is terminated while the hypervisor is still processing the request.
+    pub fn cancel_pending_request(&mut self) {
+        self.pending_request = None;
+        self.transition_to_shutdown();
     }
 }
 
@@ -243,7 +545,11 @@ impl ConfidentialHart {
             ExposeToConfidentialVm::SbiResult(v) => self.apply_sbi_result(v),
             ExposeToConfidentialVm::GuestLoadPageFaultResult(v) => self.apply_guest_load_page_fault_result(v),
             ExposeToConfidentialVm::VirtualInstructionResult(v) => self.apply_virtual_instruction_result(v),
+            ExposeToConfidentialVm::IllegalInstructionResult(v) => self.apply_illegal_instruction_result(v),
+            ExposeToConfidentialVm::VirtualizedCsrResult(v) => self.apply_virtualized_csr_result(v),
+            ExposeToConfidentialVm::MmioAccessFault(v) => self.apply_mmio_access_fault(v),
             ExposeToConfidentialVm::GuestStorePageFaultResult(v) => self.apply_guest_store_page_fault_result(v),
+            ExposeToConfidentialVm::GuestAccessFaultDenied(v) => self.apply_guest_access_fault_denied(v),
             ExposeToConfidentialVm::SbiIpi(v) => self.apply_sbi_ipi(v),
             ExposeToConfidentialVm::SbiRemoteFenceI(v) => self.apply_sbi_remote_fence_i(v),
             ExposeToConfidentialVm::SbiRemoteSfenceVma(v) => self.apply_sbi_remote_sfence_vma(v),
@@ -251,10 +557,23 @@ impl ConfidentialHart {
             ExposeToConfidentialVm::SbiHsmHartStartPending() => self.transition_from_start_pending_to_started(),
             ExposeToConfidentialVm::SbiHsmHartStart() => self.apply_sbi_result_success(),
             ExposeToConfidentialVm::SbiSrstSystemReset() => self.transition_to_shutdown(),
+            ExposeToConfidentialVm::DebugRegisterWrite(register, value) => self.apply_debug_register_write(register, value),
+            ExposeToConfidentialVm::SseResult(v) => self.apply_sse_result(v),
+            ExposeToConfidentialVm::MeasurementRegisterValue(v) => self.apply_measurement_register_value(v),
             ExposeToConfidentialVm::Resume() => {}
         }
     }
 
+    fn apply_debug_register_write(&mut self, register: DebugRegister, value: usize) {
+        match register {
+            DebugRegister::Pc => self.confidential_hart_state.mepc = value,
+            DebugRegister::Gpr(gpr) => self.confidential_hart_state.set_gpr(gpr, value),
+            DebugRegister::Sstatus => self.confidential_hart_state.vsstatus = value,
+            DebugRegister::Scause => self.confidential_hart_state.vscause = value,
+            DebugRegister::Stval => self.confidential_hart_state.vstval = value,
+        }
+    }
+
     fn apply_injected_interrupts(&mut self, result: InjectedInterrupts) {
         self.confidential_hart_state.hvip = result.hvip;
     }
@@ -281,6 +600,7 @@ impl ConfidentialHart {
     fn apply_sbi_result(&mut self, result: SbiResult) {
         self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, result.a0());
         self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, result.a1());
+        result.extended_values().for_each(|(gpr, value)| self.confidential_hart_state.set_gpr(gpr, value));
         self.confidential_hart_state.mepc += result.pc_offset();
     }
 
@@ -290,6 +610,80 @@ impl ConfidentialHart {
         self.confidential_hart_state.mepc += ECALL_INSTRUCTION_LENGTH;
     }
 
+    fn apply_measurement_register_value(&mut self, result: MeasurementRegisterValue) {
+        use GeneralPurposeRegister::*;
+        self.confidential_hart_state.set_gpr(a0, 0);
+        self.confidential_hart_state.set_gpr(a1, result.digest_size());
+        [a2, a3, a4, a5, a6, a7].into_iter().zip(result.words()).for_each(|(gpr, word)| self.confidential_hart_state.set_gpr(gpr, word));
+        self.confidential_hart_state.mepc += ECALL_INSTRUCTION_LENGTH;
+    }
+
+    fn apply_sse_result(&mut self, result: SseResult) {
+        match result {
+            SseResult::Success => self.apply_sbi_result_success(),
+            SseResult::EventCompleted(interrupted_state) => self.complete_sse_event(interrupted_state),
+        }
+        // Enabling, injecting or completing an event might make another event deliverable.
+        self.deliver_pending_sse_event();
+    }
+
+    /// Diverts the confidential hart to the handler of a pending event. Like for traps, the interrupted program counter and privilege
+    /// mode are stored in vsepc and vsstatus.SPP, and interrupts are disabled. VS-level CSRs are live on the hardware hart at this point.
+    fn deliver_pending_sse_event(&mut self) {
+        let vsstatus = CSR.vsstatus.read();
+        let interrupted_state = SseInterruptedState::new(
+            CSR.vsepc.read(),
+            is_bit_enabled(vsstatus, CSR_SSTATUS_SPP),
+            is_bit_enabled(vsstatus, CSR_SSTATUS_SPIE),
+            self.confidential_hart_state.gpr(GeneralPurposeRegister::a6),
+            self.confidential_hart_state.gpr(GeneralPurposeRegister::a7),
+        );
+        if let Some((entry_pc, entry_arg)) = self.sse_virtualizer.start_pending_event(interrupted_state) {
+            CSR.vsepc.set(self.confidential_hart_state.mepc);
+            if is_bit_enabled(self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP) {
+                CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPP);
+            } else {
+                CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPP);
+            }
+            if is_bit_enabled(CSR.vsstatus.read_and_clear_bit(CSR_VSSTATUS_SIE), CSR_VSSTATUS_SIE) {
+                CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPIE);
+            } else {
+                CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPIE);
+            }
+            self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, self.confidential_hart_id());
+            self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a7, entry_arg);
+            enable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP);
+            self.confidential_hart_state.mepc = entry_pc;
+        }
+    }
+
+    /// Resumes the execution interrupted by the event at the address in vsepc, which the handler is allowed to change, and restores
+    /// the state overwritten by the event delivery.
+    fn complete_sse_event(&mut self, interrupted_state: SseInterruptedState) {
+        self.confidential_hart_state.mepc = CSR.vsepc.read();
+        if is_bit_enabled(CSR.vsstatus.read(), CSR_SSTATUS_SPP) {
+            enable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP);
+        } else {
+            disable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP);
+        }
+        if is_bit_enabled(CSR.vsstatus.read(), CSR_SSTATUS_SPIE) {
+            CSR.vsstatus.read_and_set_bit(CSR_VSSTATUS_SIE);
+        }
+        CSR.vsepc.set(interrupted_state.sepc());
+        if interrupted_state.spp() {
+            CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPP);
+        } else {
+            CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPP);
+        }
+        if interrupted_state.spie() {
+            CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPIE);
+        } else {
+            CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPIE);
+        }
+        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, interrupted_state.a6());
+        self.confidential_hart_state.set_gpr(GeneralPurposeRegister::a7, interrupted_state.a7());
+    }
+
     fn apply_guest_load_page_fault_result(&mut self, result: GuestLoadPageFaultResult) {
         self.confidential_hart_state.set_gpr(result.result_gpr(), result.value());
         self.confidential_hart_state.mepc += result.instruction_length();
@@ -299,9 +693,65 @@ impl ConfidentialHart {
         self.confidential_hart_state.mepc += result.instruction_length();
     }
 
+    fn apply_guest_access_fault_denied(&mut self, result: GuestAccessFaultResult) {
+        self.inject_exception(result.code(), result.stval());
+    }
+
     fn apply_virtual_instruction_result(&mut self, result: VirtualInstructionResult) {
         self.confidential_hart_state.mepc += result.instruction_length();
     }
+
+    fn apply_illegal_instruction_result(&mut self, result: IllegalInstructionResult) {
+        self.inject_exception(CAUSE_ILLEGAL_INSTRUCTION.into(), result.instruction());
+    }
+
+    fn apply_virtualized_csr_result(&mut self, result: VirtualizedCsrResult) {
+        self.confidential_hart_state.set_gpr(result.result_gpr(), result.value());
+        self.confidential_hart_state.mepc += result.instruction_length();
+    }
+
+    fn apply_mmio_access_fault(&mut self, result: MmioAccessFault) {
+        self.inject_exception(result.code(), result.stval());
+    }
+
+    /// Reflects the exception to the confidential hart's VS-mode trap handler, as if the exception was delegated to the confidential VM
+    /// by the hardware. VS-level CSRs are written directly because they are not stored in the main memory while the confidential hart
+    /// executes on the hardware hart.
+    fn inject_exception(&mut self, cause: usize, tval: usize) {
+        CSR.vsepc.set(self.confidential_hart_state.mepc);
+        CSR.vscause.set(cause);
+        CSR.vstval.set(tval);
+        // vsstatus.SPP stores the privilege mode (VS or VU) in which the exception occurred.
+        if is_bit_enabled(self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP) {
+            CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPP);
+        } else {
+            CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPP);
+        }
+        if is_bit_enabled(CSR.vsstatus.read_and_clear_bit(CSR_VSSTATUS_SIE), CSR_VSSTATUS_SIE) {
+            CSR.vsstatus.read_and_set_bit(CSR_SSTATUS_SPIE);
+        } else {
+            CSR.vsstatus.read_and_clear_bit(CSR_SSTATUS_SPIE);
+        }
+        // The trap handler executes in VS-mode. Exceptions always jump to the base address, also when the vectored mode is enabled.
+        enable_bit(&mut self.confidential_hart_state.mstatus, CSR_MSTATUS_MPP);
+        self.confidential_hart_state.mepc = CSR.vstvec.read() & !STVEC_MODE_VECTORED;
+    }
+}
+
+// Methods that let test harnesses drive the trap handlers without faulting code.
+#[cfg(feature = "testing")]
+impl ConfidentialHart {
+    /// Makes the physical hart look as if the confidential hart trapped with the given cause at its current program counter, so the
+    /// next `trap_reason` and the requests decoded from the trap CSRs see the injected trap. For guest page faults, `tval` is also
+    /// the faulting guest physical address, as if the confidential VM had disabled VS-stage translation. `mtinst` is cleared, so the
+    /// trapped instruction is never decoded from it. Must be called on a confidential hart assigned to the physical hart.
+    pub fn inject_virtual_fault(&mut self, cause: TrapCause, tval: usize) {
+        CSR.mcause.set(cause.code());
+        CSR.mtval.set(tval);
+        CSR.mtval2.set(tval >> 2);
+        CSR.mtinst.set(0);
+        CSR.mepc.set(self.confidential_hart_state.mepc);
+    }
 }
 
 // Methods to declassify portions of confidential hart state.
@@ -313,6 +763,18 @@ impl ConfidentialHart {
         TrapCause::from(cause, extension_id, function_id)
     }
 
+    /// Returns the value of the register as seen by the confidential VM. This function must only be called on a confidential hart that
+    /// is not assigned to a hardware hart, so that its state is stored in the main memory.
+    pub fn debug_register(&self, register: DebugRegister) -> usize {
+        match register {
+            DebugRegister::Pc => self.confidential_hart_state.mepc,
+            DebugRegister::Gpr(gpr) => self.confidential_hart_state.gpr(gpr),
+            DebugRegister::Sstatus => self.confidential_hart_state.vsstatus,
+            DebugRegister::Scause => self.confidential_hart_state.vscause,
+            DebugRegister::Stval => self.confidential_hart_state.vstval,
+        }
+    }
+
     pub fn hypercall_request(&self) -> SbiRequest {
         SbiRequest::from_hart_state(&self.confidential_hart_state)
     }
@@ -324,19 +786,31 @@ impl ConfidentialHart {
         VirtualInstructionRequest { instruction, instruction_length }
     }
 
+    pub fn illegal_instruction_request(&self) -> IllegalInstructionRequest {
+        // According to the RISC-V privilege spec, mtval might store the faulting instruction
+        IllegalInstructionRequest::new(CSR.mtval.read())
+    }
+
+    pub fn guest_page_fault(&self, access: GuestMemoryAccess) -> GuestPageFault {
+        // The hardware writes the guest physical address shifted right by two bits to mtval2.
+        let guest_physical_address = ConfidentialVmPhysicalAddress::new(CSR.mtval2.read() << 2);
+        GuestPageFault::new(guest_physical_address, CSR.mtval.read(), access)
+    }
+
     pub fn guest_load_page_fault_request(&self) -> Result<(GuestLoadPageFaultRequest, MmioLoadRequest), Error> {
         let mcause = CSR.mcause.read();
         let mtinst = CSR.mtinst.read();
         let mtval = CSR.mtval.read();
         let mtval2 = CSR.mtval2.read();
 
-        // According to the RISC-V privilege spec, mtinst encodes faulted instruction (bit 0 is 1) or a pseudo instruction
-        assert!(mtinst & 0x1 > 0);
+        // According to the RISC-V privilege spec, mtinst encodes faulted instruction (bit 0 is 1) or a pseudo instruction. Pseudo
+        // instructions describe implicit accesses of the VS-stage page walk, which are never MMIO accesses.
+        assure!(mtinst & 0x1 > 0, Error::InvalidRiscvInstruction(mtinst))?;
         let instruction = mtinst | 0x3;
         let instruction_length = if is_bit_enabled(mtinst, 1) { riscv_decode::instruction_length(instruction as u16) } else { 2 };
         let gpr = crate::core::architecture::decode_result_register(instruction)?;
 
-        let load_fault_request = GuestLoadPageFaultRequest::new(instruction_length, gpr);
+        let load_fault_request = GuestLoadPageFaultRequest::new(instruction_length, gpr, mtval);
         let mmio_load_request = MmioLoadRequest::new(mcause, mtval, mtval2, mtinst);
 
         Ok((load_fault_request, mmio_load_request))
@@ -348,15 +822,17 @@ impl ConfidentialHart {
         let mtval = CSR.mtval.read();
         let mtval2 = CSR.mtval2.read();
 
-        // According to the RISC-V privilege spec, mtinst encodes faulted instruction (bit 0 is 1) or a pseudo instruction
-        assert!(mtinst & 0x1 > 0);
+        // According to the RISC-V privilege spec, mtinst encodes faulted instruction (bit 0 is 1) or a pseudo instruction. Pseudo
+        // instructions describe implicit accesses of the VS-stage page walk, which are never MMIO accesses.
+        assure!(mtinst & 0x1 > 0, Error::InvalidRiscvInstruction(mtinst))?;
         let instruction = mtinst | 0x3;
         let instruction_length = if is_bit_enabled(mtinst, 1) { riscv_decode::instruction_length(instruction as u16) } else { 2 };
         let gpr = crate::core::architecture::decode_result_register(instruction)?;
         let gpr_value = self.confidential_hart_state.gpr(gpr);
+        let width = MmioStoreWidth::from_size_in_bytes(decode_store_size(instruction)?)?;
 
-        let guest_store_page_fault_request = GuestStorePageFaultRequest::new(instruction_length);
-        let mmio_store_request = MmioStoreRequest::new(mcause, mtval, mtval2, mtinst, gpr, gpr_value);
+        let guest_store_page_fault_request = GuestStorePageFaultRequest::new(instruction_length, mtval);
+        let mmio_store_request = MmioStoreRequest::new(mcause, mtval, mtval2, mtinst, gpr, gpr_value, width);
 
         Ok((guest_store_page_fault_request, mmio_store_request))
     }
@@ -368,17 +844,96 @@ impl ConfidentialHart {
         Ok((share_page_request, sbi_request))
     }
 
+    pub fn measurement_register_request(&self) -> MeasurementRegisterRequest {
+        let index = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let buffer_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        MeasurementRegisterRequest::new(index, buffer_address)
+    }
+
+    pub fn get_measurement_log_request(&self) -> GetMeasurementLogRequest {
+        let first_entry = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let buffer_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        GetMeasurementLogRequest::new(first_entry, buffer_address, buffer_size)
+    }
+
+    pub fn verify_code_integrity_request(&self) -> VerifyCodeIntegrityRequest {
+        let address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let size_in_bytes = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        VerifyCodeIntegrityRequest::new(address, size_in_bytes)
+    }
+
+    pub fn attestation_report_request(&self) -> AttestationReportRequest {
+        let challenge_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let report_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let report_buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        AttestationReportRequest::new(challenge_address, report_address, report_buffer_size)
+    }
+
+    pub fn certificate_chain_request(&self) -> CertificateChainRequest {
+        let buffer_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        CertificateChainRequest::new(buffer_address, buffer_size)
+    }
+
+    pub fn seal_request(&self) -> SealRequest {
+        let data_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let data_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let output_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        let output_buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a3);
+        SealRequest::new(data_address, data_size, output_address, output_buffer_size)
+    }
+
+    pub fn sealing_key_request(&self) -> SealingKeyRequest {
+        let buffer_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        SealingKeyRequest::new(buffer_address, buffer_size)
+    }
+
+    pub fn unseal_request(&self) -> UnsealRequest {
+        let sealed_data_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let sealed_data_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let output_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        let output_buffer_size = self.confidential_hart_state.gpr(GeneralPurposeRegister::a3);
+        UnsealRequest::new(sealed_data_address, sealed_data_size, output_address, output_buffer_size)
+    }
+
+    pub fn dbcn_read_request(&self) -> Result<DbcnReadRequest, Error> {
+        let number_of_bytes = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let address_lo = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let address_hi = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        DbcnReadRequest::new(number_of_bytes, address_lo, address_hi)
+    }
+
+    pub fn steal_time_request(&self) -> Result<StealTimeRequest, Error> {
+        let address_lo = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let address_hi = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let flags = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        StealTimeRequest::new(address_lo, address_hi, flags)
+    }
+
     pub fn unshare_page_request(&self) -> Result<UnsharePageRequest, Error> {
         let page_to_unshare_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
         Ok(UnsharePageRequest::new(page_to_unshare_address)?)
     }
 
+    pub fn memory_balloon_request(&self) -> Result<MemoryBalloonRequest, Error> {
+        let address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let size_in_bytes = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        MemoryBalloonRequest::new(address, size_in_bytes)
+    }
+
     pub fn sbi_ipi(&self) -> InterHartRequest {
         let hart_mask = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
         let hart_mask_base = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
         InterHartRequest::SbiIpi(SbiIpi::new(hart_mask, hart_mask_base))
     }
 
+    pub fn probe_extension_request(&self) -> ProbeExtensionRequest {
+        let extension_id = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        ProbeExtensionRequest::new(extension_id)
+    }
+
     pub fn sbi_hsm_hart_start(&self) -> SbiHsmHartStart {
         let confidential_hart_id = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
         let start_address = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
@@ -398,6 +953,67 @@ impl ConfidentialHart {
         SbiHsmHartStatus::new(confidential_hart_id)
     }
 
+    pub fn sbi_pmu_request(&self, function: PmuExtension) -> SbiPmuRequest {
+        let a0 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let a1 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let a2 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        let a3 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a3);
+        match function {
+            PmuExtension::NumCounters => SbiPmuRequest::NumCounters,
+            PmuExtension::CounterGetInfo => SbiPmuRequest::CounterGetInfo { counter_index: a0 },
+            PmuExtension::CounterConfigMatching => {
+                SbiPmuRequest::CounterConfigMatching { counter_index_base: a0, counter_index_mask: a1, config_flags: a2, event_index: a3 }
+            }
+            PmuExtension::CounterStart => {
+                SbiPmuRequest::CounterStart { counter_index_base: a0, counter_index_mask: a1, start_flags: a2, initial_value: a3 }
+            }
+            PmuExtension::CounterStop => SbiPmuRequest::CounterStop { counter_index_base: a0, counter_index_mask: a1, stop_flags: a2 },
+            PmuExtension::CounterFwRead => SbiPmuRequest::CounterFwRead { counter_index: a0 },
+            PmuExtension::CounterFwReadHi => SbiPmuRequest::CounterFwReadHi { counter_index: a0 },
+            _ => SbiPmuRequest::Unsupported,
+        }
+    }
+
+    pub fn cppc_request(&self, function: CppcExtension) -> CppcRequest {
+        let a0 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let a1 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        match function {
+            CppcExtension::Probe => CppcRequest::Probe { register_id: a0 },
+            CppcExtension::Read => CppcRequest::Read { register_id: a0 },
+            CppcExtension::ReadHi => CppcRequest::ReadHi { register_id: a0 },
+            CppcExtension::Write => CppcRequest::Write { register_id: a0, value: a1 },
+            _ => CppcRequest::Unsupported,
+        }
+    }
+
+    pub fn fwft_request(&self, function: FwftExtension) -> FwftRequest {
+        let a0 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let a1 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let a2 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        match function {
+            FwftExtension::Set => FwftRequest::Set { feature: a0, value: a1, flags: a2 },
+            FwftExtension::Get => FwftRequest::Get { feature: a0 },
+            _ => FwftRequest::Unsupported,
+        }
+    }
+
+    pub fn sse_request(&self, function: SseExtension) -> SseRequest {
+        let a0 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let a1 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let a2 = self.confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        match function {
+            SseExtension::Register => SseRequest::Register { event_id: a0, entry_pc: a1, entry_arg: a2 },
+            SseExtension::Unregister => SseRequest::Unregister { event_id: a0 },
+            SseExtension::Enable => SseRequest::Enable { event_id: a0 },
+            SseExtension::Disable => SseRequest::Disable { event_id: a0 },
+            SseExtension::Complete => SseRequest::Complete,
+            SseExtension::Inject => SseRequest::Inject { event_id: a0, hart_id: a1 },
+            SseExtension::HartUnmask => SseRequest::HartUnmask,
+            SseExtension::HartMask => SseRequest::HartMask,
+            _ => SseRequest::Unsupported,
+        }
+    }
+
     pub fn sbi_remote_fence_i(&self) -> InterHartRequest {
         let hart_mask = self.confidential_hart_state.gpr(GeneralPurposeRegister::a0);
         let hart_mask_base = self.confidential_hart_state.gpr(GeneralPurposeRegister::a1);
@@ -425,3 +1041,115 @@ impl ConfidentialHart {
         EnabledInterrupts::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    // Verifiers reproduce the serialization independently, so it must not change unnoticed. The expected bytes are written out rather
+    // than computed with the code under test.
+    const EXPECTED_BOOT_STATE: &str = concat!(
+        "0000208000000000",                                                 // mepc
+        "0000000000000000000040800000000000000000000000000000000000000000", // x1 to x4
+        "0000000000000000000000000000000000000000000000000000000000000000", // x5 to x8
+        "0000000000000000010000000000000000002082000000000000000000000000", // x9 to x12
+        "0000000000000000000000000000000000000000000000000000000000000000", // x13 to x16
+        "0000000000000000000000000000000000000000000000000000000000000000", // x17 to x20
+        "0000000000000000000000000000000000000000000000000000000000000000", // x21 to x24
+        "0000000000000000000000000000000000000000000000000000000000000000", // x25 to x28
+        "000000000000000000000000000000000000000000000000",                 // x29 to x31
+        "0000000002000000",                                                 // vsstatus
+        "0001208000000000",                                                 // vstvec
+        "0002080000000080",                                                 // vsatp
+    );
+
+    #[test]
+    fn boot_state_matches_golden_serialization() {
+        let mut confidential_hart = ConfidentialHart::from_reset_state(0);
+        let state = &mut confidential_hart.confidential_hart_state;
+        state.mepc = 0x8020_0000;
+        state.set_gpr(GeneralPurposeRegister::sp, 0x8040_0000);
+        state.set_gpr(GeneralPurposeRegister::a0, 0x1);
+        state.set_gpr(GeneralPurposeRegister::a1, 0x8220_0000);
+        state.vsstatus = 0x2_0000_0000;
+        state.vstvec = 0x8020_0100;
+        state.vsatp = 0x8000_0000_0008_0200;
+        let boot_state = confidential_hart.boot_state();
+        let boot_state = boot_state.iter().map(|byte| alloc::format!("{:02x}", byte)).collect::<String>();
+        assert_eq!(boot_state, EXPECTED_BOOT_STATE);
+    }
+
+    #[test]
+    fn cancelled_mmio_request_is_never_completed() {
+        let mut confidential_hart = ConfidentialHart::from_reset_state(1);
+        confidential_hart.set_confidential_vm_id(ConfidentialVmId::new(1));
+        confidential_hart.lifecycle_state = HartLifecycleState::Started;
+        let request = GuestLoadPageFaultRequest::new(4, GeneralPurposeRegister::a0, 0x4000_1000);
+        confidential_hart.set_pending_request(PendingRequest::GuestLoadPageFault(request)).unwrap();
+        assert_eq!(confidential_hart.vcpu_runstate(), VcpuRunstate::BlockedOnMmio);
+
+        confidential_hart.cancel_pending_request();
+
+        // The hypervisor's late response has nothing to complete and the hart is never resumed to observe it.
+        assert!(!confidential_hart.has_pending_request());
+        assert!(confidential_hart.take_request().is_none());
+        assert!(!confidential_hart.is_executable());
+        assert!(confidential_hart.lifecycle_state() == &HartLifecycleState::Shutdown);
+        assert_eq!(confidential_hart.vcpu_runstate(), VcpuRunstate::Stopped);
+    }
+
+    #[test]
+    fn entering_confidential_hart_sets_wfi_trapping_chosen_by_security_monitor() {
+        let is_wfi_trapped = ConfidentialHart::WFI_POLICY == WfiPolicy::TrapToSecurityMonitor;
+        for hypervisor_hstatus in [0, 1 << CSR_HSTATUS_VTW] {
+            let mut confidential_hart = ConfidentialHart::from_reset_state(0);
+            confidential_hart.confidential_hart_state.hstatus = hypervisor_hstatus;
+            confidential_hart.confidential_hart_state.mstatus = 1 << CSR_MSTATUS_TW;
+            confidential_hart.apply_fixed_configuration();
+            assert_eq!(is_bit_enabled(confidential_hart.confidential_hart_state.hstatus, CSR_HSTATUS_VTW), is_wfi_trapped);
+            assert!(!is_bit_enabled(confidential_hart.confidential_hart_state.mstatus, CSR_MSTATUS_TW));
+        }
+    }
+
+    // csrr a0, mvendorid
+    const READ_MVENDORID: usize = 0xf110_2573;
+    // csrw mvendorid, a0
+    const WRITE_MVENDORID: usize = 0xf115_1073;
+    // csrr a0, mimpid
+    const READ_MIMPID: usize = 0xf130_2573;
+
+    fn hart_emulating_mvendorid(mvendorid: usize) -> ConfidentialHart {
+        let mut confidential_hart = ConfidentialHart::from_reset_state(0);
+        let policy = CsrEmulationPolicy::with_entries(&[(CSR_MVENDORID, CsrEmulation::Constant(mvendorid))]);
+        confidential_hart.set_csr_emulation_policy(Arc::new(policy));
+        confidential_hart
+    }
+
+    /// Emulates the CSR access like the illegal instruction handler does. Returns `None` if the access is reflected to the confidential
+    /// hart as the illegal instruction exception.
+    fn emulate(confidential_hart: &mut ConfidentialHart, instruction: usize) -> Option<usize> {
+        let access = CsrAccess::decode(instruction).unwrap();
+        let emulation = confidential_hart.csr_emulation(access.csr())?;
+        confidential_hart.emulate_csr_access(&access, emulation)
+    }
+
+    #[test]
+    fn emulated_csr_read_writes_destination_register() {
+        let mut confidential_hart = hart_emulating_mvendorid(0x489);
+        confidential_hart.confidential_hart_state.mepc = 0x8020_0000;
+        let value = emulate(&mut confidential_hart, READ_MVENDORID).unwrap();
+        let access = CsrAccess::decode(READ_MVENDORID).unwrap();
+        confidential_hart.apply(ExposeToConfidentialVm::VirtualizedCsrResult(VirtualizedCsrResult::new(access.result_gpr(), value)));
+        assert_eq!(confidential_hart.confidential_hart_state.gpr(GeneralPurposeRegister::a0), 0x489);
+        assert_eq!(confidential_hart.confidential_hart_state.mepc, 0x8020_0004);
+    }
+
+    #[test]
+    fn unhandled_csr_access_raises_illegal_instruction() {
+        let mut confidential_hart = hart_emulating_mvendorid(0x489);
+        // A constant CSR cannot be written and a CSR that the policy does not list is not emulated at all.
+        assert_eq!(emulate(&mut confidential_hart, WRITE_MVENDORID), None);
+        assert_eq!(emulate(&mut confidential_hart, READ_MIMPID), None);
+    }
+}
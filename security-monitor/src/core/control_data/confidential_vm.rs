@@ -2,21 +2,54 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::architecture::HartLifecycleState;
-use crate::core::control_data::{ConfidentialHart, ConfidentialVmId, ConfidentialVmMeasurement, HardwareHart};
+use crate::core::attestation::{AttestationKey, AttestationReport, SealedData};
+use crate::core::control_data::{
+    ConfidentialHart, ConfidentialVmId, ConfidentialVmMeasurement, CsrEmulationPolicy, HardwareHart, HartPlacement, HartQuiesce,
+    HartQuiesceGuard, MeasuredComponent, MeasurementLog, MeasurementLogEntry, MeasurementRegisters, MmioPolicy, PendingExit,
+    SharedPageTable, VcpuRunstate,
+};
+use crate::core::crypto::{constant_time_eq, zeroize, ED25519_PUBLIC_KEY_SIZE_IN_BYTES};
 use crate::core::interrupt_controller::InterruptController;
-use crate::core::memory_protector::ConfidentialVmMemoryProtector;
-use crate::core::transformations::{InterHartRequest, SbiHsmHartStart};
+use crate::core::measurement::Sha384;
+use crate::core::memory_encryption::MemoryKeySlot;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::{ConfidentialVmMemoryProtector, VmidAllocator};
+use crate::core::page_allocator::SharedPage;
+use crate::core::transformations::{
+    AttestationReportRequest, CertificateChainRequest, DebugRegister, ExposeToConfidentialVm, GetMeasurementLogRequest, InterHartRequest,
+    SbiHsmHartStart, SealRequest, SealingKeyRequest, UnsealRequest,
+};
 use crate::error::Error;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use spin::{Mutex, MutexGuard};
 
 pub struct ConfidentialVm {
     id: ConfidentialVmId,
-    measurements: [ConfidentialVmMeasurement; 4],
+    // A debuggable confidential VM allows the hypervisor to inspect and modify its harts' registers. This setting is reflected in
+    // the confidential VM's measurement, so a relying party can refuse to provision secrets to a debuggable confidential VM.
+    is_debuggable: bool,
+    measurements: MeasurementRegisters,
+    // Every digest extended into the measurement registers, so that a verifier can replay and interpret the final values.
+    measurement_log: MeasurementLog,
     confidential_harts: Vec<ConfidentialHart>,
+    // Ids of hardware harts currently executing confidential harts, updated together with the swap of a confidential hart.
+    hart_placement: HartPlacement,
     memory_protector: ConfidentialVmMemoryProtector,
+    // Pages currently shared with the hypervisor. Every entry is also mapped by the memory protector.
+    shared_page_table: SharedPageTable,
+    // The key slot of the memory encryption engine, wiped when the confidential VM is dropped after its memory has been reclaimed.
+    memory_key_slot: MemoryKeySlot,
+    mmio_policy: MmioPolicy,
+    // The public key that signed the kernel image verified at promotion (secure launch), reported in the attestation evidence.
+    launch_signer: Option<[u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES]>,
+    // Guest physical address, size, and digest of code regions hashed at promotion. The guest can later check that their content has
+    // not changed since the launch.
+    code_regions: Vec<(ConfidentialVmPhysicalAddress, usize, [u8; Sha384::DIGEST_SIZE_IN_BYTES])>,
     inter_hart_requests: BTreeMap<usize, Mutex<Vec<InterHartRequest>>>,
+    hart_quiesce: Arc<HartQuiesce>,
 }
 
 impl ConfidentialVm {
@@ -25,6 +58,7 @@ impl ConfidentialVm {
     /// A maximum number of inter hart requests that can be buffered.
     const MAX_NUMBER_OF_REMOTE_HART_REQUESTS: usize = 64;
     pub const MAX_NUMBER_OF_HARTS_PER_VM: usize = 1024;
+    pub const MAX_NUMBER_OF_CODE_REGIONS: usize = 8;
 
     /// Constructs a new confidential VM.
     ///
@@ -32,27 +66,251 @@ impl ConfidentialVm {
     ///
     /// The id of the confidential VM must be unique.
     pub fn new(
-        id: ConfidentialVmId, mut confidential_harts: Vec<ConfidentialHart>, measurements: [ConfidentialVmMeasurement; 4],
-        mut memory_protector: ConfidentialVmMemoryProtector,
+        id: ConfidentialVmId, mut confidential_harts: Vec<ConfidentialHart>,
+        launch_measurements: [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS],
+        memory_protector: ConfidentialVmMemoryProtector, memory_key_slot: MemoryKeySlot, mmio_policy: MmioPolicy, is_debuggable: bool,
     ) -> Self {
-        memory_protector.set_confidential_vm_id(id);
         let mut inter_hart_requests = BTreeMap::new();
+        let hart_quiesce = Arc::new(HartQuiesce::default());
+        let csr_emulation_policy = Arc::new(CsrEmulationPolicy::default());
         confidential_harts.iter_mut().for_each(|confidential_hart| {
             confidential_hart.set_confidential_vm_id(id);
+            confidential_hart.set_hart_quiesce(hart_quiesce.clone());
+            confidential_hart.set_csr_emulation_policy(csr_emulation_policy.clone());
             let inter_hart_requests_buffer = Mutex::new(Vec::with_capacity(Self::AVG_NUMBER_OF_REMOTE_HART_REQUESTS));
             inter_hart_requests.insert(confidential_hart.confidential_hart_id(), inter_hart_requests_buffer);
         });
-        Self { id, measurements, confidential_harts, memory_protector, inter_hart_requests }
+        let measurements = MeasurementRegisters::new(launch_measurements);
+        let hart_placement = HartPlacement::new(confidential_harts.len());
+        Self {
+            id,
+            is_debuggable,
+            measurements,
+            measurement_log: MeasurementLog::empty(),
+            confidential_harts,
+            hart_placement,
+            memory_protector,
+            shared_page_table: SharedPageTable::empty(),
+            memory_key_slot,
+            mmio_policy,
+            launch_signer: None,
+            code_regions: Vec::new(),
+            inter_hart_requests,
+            hart_quiesce,
+        }
+    }
+
+    /// Records the public key whose signature over the kernel image the security monitor verified before creating this confidential VM.
+    pub fn with_launch_signer(mut self, launch_signer: Option<[u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES]>) -> Self {
+        self.launch_signer = launch_signer;
+        self
+    }
+
+    /// Replaces the default CSR emulation policy of all confidential harts with the one declared for this confidential VM.
+    pub fn with_csr_emulation_policy(mut self, csr_emulation_policy: CsrEmulationPolicy) -> Self {
+        let csr_emulation_policy = Arc::new(csr_emulation_policy);
+        self.confidential_harts
+            .iter_mut()
+            .for_each(|confidential_hart| confidential_hart.set_csr_emulation_policy(csr_emulation_policy.clone()));
+        self
+    }
+
+    /// Records launch-time digests of code regions, see `verify_code_integrity`.
+    pub fn with_code_regions(
+        mut self, code_regions: Vec<(ConfidentialVmPhysicalAddress, usize, [u8; Sha384::DIGEST_SIZE_IN_BYTES])>,
+    ) -> Self {
+        self.code_regions = code_regions;
+        self
+    }
+
+    /// Records the log of the launch measurements. It must describe exactly the extensions that produced the launch measurements
+    /// passed to `new`.
+    pub fn with_measurement_log(mut self, measurement_log: MeasurementLog) -> Self {
+        self.measurement_log = measurement_log;
+        self
     }
 
     pub fn confidential_vm_id(&self) -> ConfidentialVmId {
         self.id
     }
 
+    pub fn memory_protector(&self) -> &ConfidentialVmMemoryProtector {
+        &self.memory_protector
+    }
+
     pub fn memory_protector_mut(&mut self) -> &mut ConfidentialVmMemoryProtector {
         &mut self.memory_protector
     }
 
+    pub fn shared_page_table(&self) -> &SharedPageTable {
+        &self.shared_page_table
+    }
+
+    /// Maps the page of the hypervisor's memory into the confidential VM's address space and records it in the shared page table.
+    /// Returns error if the page overlaps a page that the confidential VM already shares, in which case nothing is mapped.
+    pub fn share_page(&mut self, shared_page: SharedPage) -> Result<(), Error> {
+        let (address, page_size) = (shared_page.confidential_vm_virtual_address(), shared_page.page_size());
+        self.shared_page_table.insert(address, page_size)?;
+        self.memory_protector.map_shared_page(shared_page).inspect_err(|_| {
+            let _ = self.shared_page_table.remove(address);
+        })
+    }
+
+    /// Makes the shared page private again, see `ConfidentialVmMemoryProtector::unmap_shared_page`. Returns error if the confidential
+    /// VM does not share a page at the given address.
+    pub fn unshare_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<SharedPage, Error> {
+        let page_size = self.shared_page_table.remove(address)?;
+        self.memory_protector.unmap_shared_page(address).inspect_err(|_| {
+            let _ = self.shared_page_table.insert(address, page_size);
+        })
+    }
+
+    /// Removes all shared pages from the confidential VM's address space, so that the hypervisor can reuse its pages, e.g., convert
+    /// them to confidential memory, while the confidential VM's memory is still being reclaimed.
+    pub fn unshare_all_pages(&mut self) {
+        let addresses = self.shared_page_table.drain();
+        self.memory_protector.remove_shared_pages(&addresses);
+    }
+
+    pub fn measurements(&self) -> &MeasurementRegisters {
+        &self.measurements
+    }
+
+    /// Extends the runtime measurement register with the digest stored in the confidential VM's memory at the given guest physical
+    /// address.
+    pub fn extend_measurement(&mut self, index: usize, digest_address: ConfidentialVmPhysicalAddress) -> Result<(), Error> {
+        let mut digest = [0u8; Sha384::DIGEST_SIZE_IN_BYTES];
+        self.memory_protector.read_bytes(digest_address, &mut digest)?;
+        self.measurements.extend(index, &digest)?;
+        self.measurement_log.record(index, MeasuredComponent::Runtime, None, &digest);
+        Ok(())
+    }
+
+    /// Copies as many measurement log entries, starting from the requested one, as fit in the buffer in the confidential VM's memory
+    /// (see `MeasurementLogEntry::to_bytes` for the layout). Returns the total number of entries in the log, so the confidential VM can
+    /// read a log that does not fit in a single buffer in several calls.
+    pub fn write_measurement_log(&mut self, request: &GetMeasurementLogRequest) -> Result<usize, Error> {
+        let max_number_of_entries = request.buffer_size() / MeasurementLogEntry::SIZE_IN_BYTES;
+        let entries = self.measurement_log.entries_from(request.first_entry()).take(max_number_of_entries);
+        entries.enumerate().try_for_each(|(index, entry)| {
+            let offset_in_bytes = index * MeasurementLogEntry::SIZE_IN_BYTES;
+            let address = request.buffer_address().usize().checked_add(offset_in_bytes).ok_or(Error::InvalidArgument())?;
+            self.memory_protector.write_bytes(ConfidentialVmPhysicalAddress::new(address), &entry.to_bytes())
+        })?;
+        Ok(self.measurement_log.number_of_entries())
+    }
+
+    /// Copies the value of the measurement register to the confidential VM's memory at the given guest physical address.
+    pub fn read_measurement(&mut self, index: usize, buffer_address: ConfidentialVmPhysicalAddress) -> Result<(), Error> {
+        let digest = self.measurement_value(index)?;
+        self.memory_protector.write_bytes(buffer_address, &digest)
+    }
+
+    /// Returns the value of the measurement register. The value is read from the same registers that are embedded in attestation
+    /// reports, so both always agree. Returns error if the register does not exist.
+    pub fn measurement_value(&self, index: usize) -> Result<[u8; Sha384::DIGEST_SIZE_IN_BYTES], Error> {
+        let mut digest = [0u8; Sha384::DIGEST_SIZE_IN_BYTES];
+        digest.copy_from_slice(&self.measurements.read(index)?.value[..Sha384::DIGEST_SIZE_IN_BYTES]);
+        Ok(digest)
+    }
+
+    /// Re-hashes the code region and returns true if its content equals the content hashed at promotion. The region must match
+    /// exactly one of the regions recorded at promotion, otherwise there is no reference digest and an error is returned.
+    pub fn verify_code_integrity(&self, address: ConfidentialVmPhysicalAddress, size_in_bytes: usize) -> Result<bool, Error> {
+        let (_, _, launch_digest) = self
+            .code_regions
+            .iter()
+            .find(|(region_address, region_size, _)| *region_address == address && *region_size == size_in_bytes)
+            .ok_or(Error::CodeRegionNotMeasured())?;
+        let current_digest = self.memory_protector.digest_of_region(address, size_in_bytes)?;
+        Ok(constant_time_eq(&current_digest, launch_digest))
+    }
+
+    /// Writes a signed attestation report for the challenge to the confidential VM's memory and returns the size of the report. Returns
+    /// error if the report does not fit in the buffer or the challenge or the buffer are not in the confidential VM's memory.
+    pub fn issue_attestation_report(&mut self, request: &AttestationReportRequest, allowed_extensions: &[usize]) -> Result<usize, Error> {
+        assure!(request.report_buffer_size() >= AttestationReport::SIZE_IN_BYTES, Error::InvalidArgument())?;
+        let mut challenge = [0u8; AttestationReport::CHALLENGE_SIZE_IN_BYTES];
+        self.memory_protector.read_bytes(request.challenge_address(), &mut challenge)?;
+        let report =
+            AttestationReport::new(&challenge, &self.measurements, self.is_debuggable, self.launch_signer.as_ref(), allowed_extensions)?;
+        self.memory_protector.write_bytes(request.report_address(), report.as_bytes())?;
+        Ok(AttestationReport::SIZE_IN_BYTES)
+    }
+
+    /// Writes the certificate chain of the attestation key to the confidential VM's memory and returns its size. The chain is padded with
+    /// zeros to the word size. Returns error if the padded chain does not fit in the buffer.
+    pub fn write_certificate_chain(&mut self, request: &CertificateChainRequest) -> Result<usize, Error> {
+        let certificate_chain = AttestationKey::certificate_chain()?;
+        let word_size = core::mem::size_of::<usize>();
+        let padded_size = certificate_chain.len().div_ceil(word_size) * word_size;
+        assure!(request.buffer_size() >= padded_size, Error::InvalidArgument())?;
+        let (full_words, remainder) = certificate_chain.split_at(certificate_chain.len() - certificate_chain.len() % word_size);
+        self.memory_protector.write_bytes(request.buffer_address(), full_words)?;
+        if !remainder.is_empty() {
+            let mut last_word = [0u8; core::mem::size_of::<usize>()];
+            last_word[..remainder.len()].copy_from_slice(remainder);
+            let address = request.buffer_address().usize().checked_add(full_words.len()).ok_or(Error::InvalidArgument())?;
+            self.memory_protector.write_bytes(ConfidentialVmPhysicalAddress::new(address), &last_word)?;
+        }
+        Ok(certificate_chain.len())
+    }
+
+    /// Seals the data to the launch measurement of the confidential VM, writes the sealed data to the confidential VM's memory, and
+    /// returns its size. The size of the data must be a multiple of the word size. Returns error if the sealed data does not fit in
+    /// the output buffer.
+    pub fn seal_data(&mut self, request: &SealRequest) -> Result<usize, Error> {
+        assure!(request.data_size() <= SealedData::MAX_DATA_SIZE_IN_BYTES, Error::InvalidArgument())?;
+        assure!(request.data_size() % core::mem::size_of::<usize>() == 0, Error::InvalidArgument())?;
+        assure!(request.output_buffer_size() >= request.data_size() + SealedData::OVERHEAD_IN_BYTES, Error::InvalidArgument())?;
+        let mut data = vec![0u8; request.data_size()];
+        let sealed_data = self
+            .memory_protector
+            .read_bytes(request.data_address(), &mut data)
+            .and_then(|_| SealedData::seal(&data, &self.measurements.launch_digest()));
+        zeroize(&mut data);
+        let sealed_data = sealed_data?;
+        self.memory_protector.write_bytes(request.output_address(), &sealed_data)?;
+        Ok(sealed_data.len())
+    }
+
+    /// Recovers the data sealed by a confidential VM with the same launch measurement, writes it to the confidential VM's memory, and
+    /// returns its size. Returns error if the sealed data is corrupted, was sealed by a different confidential VM, or the recovered data
+    /// does not fit in the output buffer.
+    pub fn unseal_data(&mut self, request: &UnsealRequest) -> Result<usize, Error> {
+        let max_sealed_data_size = SealedData::MAX_DATA_SIZE_IN_BYTES + SealedData::OVERHEAD_IN_BYTES;
+        assure!(request.sealed_data_size() <= max_sealed_data_size, Error::InvalidArgument())?;
+        assure!(request.sealed_data_size() % core::mem::size_of::<usize>() == 0, Error::InvalidArgument())?;
+        let mut sealed_data = vec![0u8; request.sealed_data_size()];
+        self.memory_protector.read_bytes(request.sealed_data_address(), &mut sealed_data)?;
+        let mut data = SealedData::unseal(&sealed_data, &self.measurements.launch_digest())?;
+        let result = match request.output_buffer_size() >= data.len() {
+            true => self.memory_protector.write_bytes(request.output_address(), &data).map(|_| data.len()),
+            false => Err(Error::InvalidArgument()),
+        };
+        zeroize(&mut data);
+        result
+    }
+
+    /// Writes the confidential VM's sealing key to its memory and returns the size of the key. The key depends only on the launch
+    /// measurement, so the confidential VM obtains the same key after a reboot. Returns error if the buffer is too small or the
+    /// confidential VM is debuggable, because the hypervisor could then read the key from the confidential VM's memory.
+    pub fn write_sealing_key(&mut self, request: &SealingKeyRequest) -> Result<usize, Error> {
+        assure_not!(self.is_debuggable, Error::SealingKeyDenied())?;
+        let key = SealedData::guest_key(&self.measurements.launch_digest())?;
+        assure!(request.buffer_size() >= key.expose().len(), Error::InvalidArgument())?;
+        self.memory_protector.write_bytes(request.buffer_address(), key.expose())?;
+        Ok(key.expose().len())
+    }
+
+    /// Returns true if the confidential VM's MMIO policy forbids emulating accesses to the given guest physical address, or if the
+    /// address belongs to the confidential VM's own memory, mapped or not. A guest page fault on the confidential memory, e.g., a load
+    /// from an execute-only page, is not an MMIO access, and forwarding it would reveal to the hypervisor which confidential page is
+    /// accessed.
+    pub fn is_mmio_access_denied(&self, address: usize) -> bool {
+        self.mmio_policy.is_forwarding_denied(address, |address| self.memory_protector.owns(ConfidentialVmPhysicalAddress::new(address)))
+    }
+
     /// Assigns a confidential hart of the confidential VM to the hardware hart. The hardware memory isolation mechanism
     /// is reconfigured to enforce memory access control for the confidential VM. Returns error if the confidential VM's
     /// virtual hart has been already stolen or is in the `Stopped` state.
@@ -61,7 +319,9 @@ impl ConfidentialVm {
     ///
     /// If confidential hart is assigned to the hardware hart, then the hardware hart is configured to enforce memory access control of
     /// the confidential VM.
-    pub fn steal_confidential_hart(&mut self, confidential_hart_id: usize, hardware_hart: &mut HardwareHart) -> Result<(), Error> {
+    pub fn steal_confidential_hart(
+        &mut self, confidential_hart_id: usize, hardware_hart: &mut HardwareHart, vmid_allocator: &Mutex<VmidAllocator>,
+    ) -> Result<(), Error> {
         let confidential_hart = self.confidential_harts.get(confidential_hart_id).ok_or(Error::InvalidHartId())?;
         // The hypervisor might try to schedule the same confidential hart on different physical harts. We detect it
         // because after a confidential_hart is scheduled for the first time, its token is stolen and the
@@ -70,11 +330,19 @@ impl ConfidentialVm {
         assure_not!(confidential_hart.is_dummy(), Error::HartAlreadyRunning())?;
         // The hypervisor might try to schedule a confidential hart that has never been started. This is forbidden.
         assure!(confidential_hart.is_executable(), Error::HartNotExecutable())?;
+        // No confidential hart can be resumed while the confidential VM is paused.
+        assure_not!(self.hart_quiesce.is_paused(), Error::ConfidentialVmPaused())?;
+        // The hardware hart must be recorded before the context switch, which cannot be undone.
+        self.hart_placement.place(confidential_hart_id, hardware_hart.hart_id())?;
+
+        // The confidential hart has not executed since it was returned to the hypervisor, so this time was stolen from it.
+        self.confidential_harts[confidential_hart_id].publish_steal_time(&mut self.memory_protector);
 
         // Context switch: store content of processor registers in the hypervisor hart's memory and load the processor registers values
         // of the confidential VM to the processor registers
         let interrupts_to_inject = hardware_hart.store_control_status_registers_in_main_memory();
         self.confidential_harts[confidential_hart_id].load_control_status_registers_from_main_memory(interrupts_to_inject);
+        self.confidential_harts[confidential_hart_id].virtualize_mtime();
 
         // We can now assign the confidential hart to the hardware hart. The code below this line must not throw an
         // error.
@@ -83,7 +351,7 @@ impl ConfidentialVm {
         // It is safe to invoke below unsafe code because at this point we are in the confidential flow part of the
         // finite state machine and the virtual hart is assigned to the hardware hart. We must reconfigure the hardware memory isolation
         // mechanism to enforce that the confidential virtual machine has access only to the memory regions it owns.
-        unsafe { self.memory_protector.enable() };
+        unsafe { self.memory_protector.enable(vmid_allocator) };
 
         Ok(())
     }
@@ -99,8 +367,12 @@ impl ConfidentialVm {
         let confidential_hart_id = hardware_hart.confidential_hart.confidential_hart_id();
         assert!(self.confidential_harts.len() > confidential_hart_id);
 
+        // The floating-point state is switched lazily, so the physical hart might still hold the confidential hart's FP registers.
+        hardware_hart.save_fp_state();
+
         // Return the confidential hart to the confidential machine.
         core::mem::swap(&mut hardware_hart.confidential_hart, &mut self.confidential_harts[confidential_hart_id]);
+        self.hart_placement.remove(confidential_hart_id);
 
         // Switch context between security domains.
         let enabled_interrupts = self.confidential_harts[confidential_hart_id].store_control_status_registers_in_main_memory();
@@ -111,18 +383,49 @@ impl ConfidentialVm {
         // associated with a dummy virtual hart.
         // It is safe to invoke below unsafe code because at this point we are transitioning from the confidential flow part of the
         // finite state machine to the non-confidential part and the virtual hart is still assigned to the hardware hart.
-        unsafe { hardware_hart.enable_hypervisor_memory_protector() };
+        // Executing the hypervisor without the isolation of the confidential memory would expose all confidential VMs, so the failure
+        // is fatal: the panic clears the confidential memory and halts the hart.
+        if let Err(error) = unsafe { hardware_hart.enable_hypervisor_memory_protector(self.memory_protector.vmid()) } {
+            panic!("Could not isolate the confidential memory from the hypervisor: {:?}", error);
+        }
     }
 
     pub fn are_all_harts_shutdown(&self) -> bool {
         self.confidential_harts.iter().filter(|hart| hart.lifecycle_state() != &HartLifecycleState::Shutdown).count() == 0
     }
 
+    /// Cancels in-flight exchanges with the hypervisor, e.g., an MMIO load awaiting its emulated value, by shutting down every
+    /// confidential hart with a pending request. Late responses are thus never applied while the confidential VM's memory is being
+    /// reclaimed. Returns error, without cancelling any request, if a confidential hart is executing or can execute without a response
+    /// from the hypervisor.
+    pub fn cancel_pending_requests(&mut self) -> Result<(), Error> {
+        assure!(
+            self.confidential_harts
+                .iter()
+                .all(|hart| hart.lifecycle_state() == &HartLifecycleState::Shutdown || hart.has_pending_request()),
+            Error::HartAlreadyRunning()
+        )?;
+        self.confidential_harts.iter_mut().filter(|hart| hart.has_pending_request()).for_each(|hart| hart.cancel_pending_request());
+        Ok(())
+    }
+
+    /// Zeroizes and returns to the page allocator at most `max_number_of_pages` pages of the confidential VM's memory. Returns true
+    /// if the confidential VM's memory has been entirely reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// All confidential harts must be in the `Shutdown` state, so that the confidential VM will never execute again.
+    pub fn reclaim_memory(&mut self, max_number_of_pages: usize) -> bool {
+        assert!(self.are_all_harts_shutdown());
+        self.memory_protector.reclaim_pages(max_number_of_pages)
+    }
+
     /// Transits the confidential hart's lifecycle state to `StartPending`. Returns error if the confidential hart is
-    /// not in the `Stopped` state or a confidential hart with the requested id does not exist.
+    /// not in the `Stopped` state, a confidential hart with the requested id does not exist, or the start address is not
+    /// within the confidential VM's physical memory.
     pub fn transit_confidential_hart_to_start_pending(&mut self, request: SbiHsmHartStart) -> Result<(), Error> {
         let hart = self.confidential_harts.get_mut(request.confidential_hart_id).ok_or(Error::InvalidHartId())?;
-        hart.transition_from_stopped_to_start_pending(request)?;
+        hart.transition_from_stopped_to_start_pending(request, &self.memory_protector)?;
         Ok(())
     }
 
@@ -136,44 +439,110 @@ impl ConfidentialVm {
         (0..self.confidential_harts.len())
             .filter(|confidential_hart_id| inter_hart_request.is_hart_selected(*confidential_hart_id))
             .try_for_each(|confidential_hart_id| {
-                let is_assigned_to_hardware_hart = { self.confidential_harts[confidential_hart_id].is_dummy() };
-                if !is_assigned_to_hardware_hart {
-                    // The confidential hart that should receive an InterHartRequest is not running on any hardware
-                    // hart. Thus, we can apply the InterHartRequest directly.
-                    let transition = inter_hart_request.clone().into_expose_to_confidential_vm();
-                    self.confidential_harts[confidential_hart_id].apply(transition);
-                } else {
-                    // The confidential hart that should receive an InterHartRequest is currently running on a hardware
-                    // hart. We add the InterHartRequest to a per confidential hart queue and then interrupt that
-                    // hardware hart with IPI. Consequently, the hardware hart running the target confidential hart will
-                    // trap into the security monitor, which will execute InterHartRequests on the targetted
-                    // confidential hart.
-                    self.try_inter_hart_requests(confidential_hart_id, |ref mut inter_hart_requests| {
-                        assure!(
-                            inter_hart_requests.len() < Self::MAX_NUMBER_OF_REMOTE_HART_REQUESTS,
-                            Error::ReachedMaxNumberOfRemoteHartRequests()
-                        )?;
-                        inter_hart_requests.push(inter_hart_request.clone());
-                        // TODO: should we also inject IPI so that the interrupted confidential hart is aware of the
-                        // inter hart request?
-                        Ok(())
-                    })?;
-                    let confidential_hart = &self.confidential_harts[confidential_hart_id];
-                    let id_of_hardware_hart_running_confidential_hart = confidential_hart.confidential_hart_id();
-                    InterruptController::try_read(|interrupt_controller| {
-                        interrupt_controller.send_ipi(id_of_hardware_hart_running_confidential_hart)
-                    })?;
+                match self.hart_placement.hardware_hart_id(confidential_hart_id) {
+                    None => {
+                        // The confidential hart that should receive an InterHartRequest is not running on any hardware
+                        // hart. Thus, we can apply the InterHartRequest directly.
+                        let transition = inter_hart_request.clone().into_expose_to_confidential_vm();
+                        self.confidential_harts[confidential_hart_id].apply(transition);
+                    }
+                    Some(hardware_hart_id) => {
+                        // The confidential hart that should receive an InterHartRequest is currently running on a hardware
+                        // hart. We add the InterHartRequest to a per confidential hart queue and then interrupt that
+                        // hardware hart with IPI. Consequently, the hardware hart running the target confidential hart will
+                        // trap into the security monitor, which will execute InterHartRequests on the targetted
+                        // confidential hart.
+                        self.try_inter_hart_requests(confidential_hart_id, |ref mut inter_hart_requests| {
+                            assure!(
+                                inter_hart_requests.len() < Self::MAX_NUMBER_OF_REMOTE_HART_REQUESTS,
+                                Error::ReachedMaxNumberOfRemoteHartRequests()
+                            )?;
+                            inter_hart_requests.push(inter_hart_request.clone());
+                            // TODO: should we also inject IPI so that the interrupted confidential hart is aware of the
+                            // inter hart request?
+                            Ok(())
+                        })?;
+                        InterruptController::try_read(|interrupt_controller| interrupt_controller.send_ipi(hardware_hart_id))?;
+                    }
                 }
                 Ok(())
             })
     }
 
+    /// Pauses execution of all confidential harts of this confidential VM. Running confidential harts are interrupted with an IPI and
+    /// the function spins until all of them trapped into the security monitor. Returns a guard, which prevents confidential harts from
+    /// being resumed until the guard is dropped. Returns error if the confidential VM is already paused or sending an IPI failed.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not execute a confidential hart of this confidential VM, otherwise it would wait for itself. The mscratch
+    /// register must contain the value expected by OpenSBI because IPIs are sent using OpenSBI.
+    pub fn pause_all_harts(&mut self) -> Result<HartQuiesceGuard, Error> {
+        let guard = HartQuiesceGuard::new(self.hart_quiesce.clone())?;
+        self.hart_placement.hardware_hart_ids().try_for_each(|hardware_hart_id| {
+            InterruptController::try_read(|interrupt_controller| interrupt_controller.send_ipi(hardware_hart_id))
+        })?;
+        guard.wait_for_acknowledgments();
+        Ok(guard)
+    }
+
+    /// Re-encrypts the memory of the confidential VM with a fresh key, limiting the amount of data encrypted under a single key in
+    /// long-running confidential VMs. Confidential harts are paused for the duration of the re-encryption. Returns error if the
+    /// confidential VM cannot be paused or there is no free key slot.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `pause_all_harts`.
+    pub fn rotate_memory_key(&mut self) -> Result<(), Error> {
+        let _guard = self.pause_all_harts()?;
+        self.memory_key_slot.rotate(&self.memory_protector)
+    }
+
     /// Returns the lifecycle state of the confidential hart
     pub fn confidential_hart_lifecycle_state(&self, confidential_hart_id: usize) -> Result<HartLifecycleState, Error> {
         assure!(confidential_hart_id < self.confidential_harts.len(), Error::InvalidHartId())?;
         Ok(self.confidential_harts[confidential_hart_id].lifecycle_state().clone())
     }
 
+    /// Returns the runstate of the confidential hart. A confidential hart that executes on a hardware hart is represented by a dummy
+    /// hart, whose runstate is always `Running`.
+    pub fn confidential_hart_runstate(&self, confidential_hart_id: usize) -> Result<VcpuRunstate, Error> {
+        let confidential_hart = self.confidential_harts.get(confidential_hart_id).ok_or(Error::InvalidHartId())?;
+        Ok(confidential_hart.vcpu_runstate())
+    }
+
+    /// Returns why the confidential hart last exited to the hypervisor. A confidential hart that executes on a hardware hart is
+    /// represented by a dummy hart, which has never exited.
+    pub fn confidential_hart_pending_exit(&self, confidential_hart_id: usize) -> Result<Option<PendingExit>, Error> {
+        let confidential_hart = self.confidential_harts.get(confidential_hart_id).ok_or(Error::InvalidHartId())?;
+        Ok(confidential_hart.pending_exit())
+    }
+
+    /// Returns the value of a confidential hart's register. Returns error if the confidential VM is not debuggable, the confidential hart
+    /// does not exist, or it is currently running on a hardware hart.
+    pub fn read_confidential_hart_register(&self, confidential_hart_id: usize, register: DebugRegister) -> Result<usize, Error> {
+        let confidential_hart = self.debuggable_confidential_hart(confidential_hart_id)?;
+        Ok(confidential_hart.debug_register(register))
+    }
+
+    /// Sets the value of a confidential hart's register. Returns error if the confidential VM is not debuggable, the confidential hart
+    /// does not exist, or it is currently running on a hardware hart.
+    pub fn write_confidential_hart_register(
+        &mut self, confidential_hart_id: usize, register: DebugRegister, value: usize,
+    ) -> Result<(), Error> {
+        self.debuggable_confidential_hart(confidential_hart_id)?;
+        self.confidential_harts[confidential_hart_id].apply(ExposeToConfidentialVm::DebugRegisterWrite(register, value));
+        Ok(())
+    }
+
+    fn debuggable_confidential_hart(&self, confidential_hart_id: usize) -> Result<&ConfidentialHart, Error> {
+        assure!(self.is_debuggable, Error::NotDebuggableConfidentialVm())?;
+        let confidential_hart = self.confidential_harts.get(confidential_hart_id).ok_or(Error::InvalidHartId())?;
+        // The state of a confidential hart running on a hardware hart is not in the main memory.
+        assure_not!(confidential_hart.is_dummy(), Error::HartAlreadyRunning())?;
+        Ok(confidential_hart)
+    }
+
     pub fn try_inter_hart_requests<F, O>(&mut self, confidential_hart_id: usize, op: O) -> Result<F, Error>
     where O: FnOnce(MutexGuard<'_, Vec<InterHartRequest>>) -> Result<F, Error> {
         op(self.inter_hart_requests.get(&confidential_hart_id).ok_or(Error::InvalidHartId())?.lock())
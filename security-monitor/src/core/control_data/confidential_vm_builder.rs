@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::HartLifecycleState;
+use crate::core::control_data::{
+    ConfidentialHart, ConfidentialVm, ConfidentialVmId, ConfidentialVmMeasurement, MeasuredComponent, MeasurementLog, MeasurementRegisters,
+    MmioPolicy,
+};
+use crate::core::crypto::constant_time_eq;
+use crate::core::measurement::Sha384;
+use crate::core::memory_encryption::MemoryKeySlot;
+use crate::core::memory_layout::{ConfidentialVmPhysicalAddress, MemoryLayout, NonConfidentialMemoryAddress};
+use crate::core::memory_protector::{ConfidentialVmMemoryProtector, PageSize};
+use crate::core::page_allocator::{Allocated, Page, PageGuard};
+use crate::core::transformations::SbiHsmHartStart;
+use crate::error::Error;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// A confidential VM that the hypervisor constructs step by step: it creates an empty confidential VM, then adds memory regions and
+/// harts, and finally finalizes it. Harts extend the running measurement as they are added, so the measurement reflects the order of
+/// hart ids. The hypervisor is free to add memory regions in any order, thus memory is measured independently of that order (see
+/// `finalize`). A confidential VM under construction can never execute. Finalization consumes the builder, thus no content can be
+/// added to a finalized confidential VM.
+pub struct ConfidentialVmBuilder {
+    id: ConfidentialVmId,
+    is_debuggable: bool,
+    // Added memory is measured but mapped only on the first access, so that large confidential VMs launch faster.
+    maps_memory_on_demand: bool,
+    measurements: [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS],
+    measurement_log: MeasurementLog,
+    confidential_harts: Vec<ConfidentialHart>,
+    memory_protector: ConfidentialVmMemoryProtector,
+    // The digests of added 4KiB chunks (see `ConfidentialVmMeasurement::page_digests`) indexed by their guest physical addresses.
+    page_digests: BTreeMap<usize, [u8; Sha384::DIGEST_SIZE_IN_BYTES]>,
+}
+
+impl ConfidentialVmBuilder {
+    const MEMORY_MEASUREMENT_INDEX: usize = 0;
+    const HARTS_MEASUREMENT_INDEX: usize = 1;
+    const CONFIGURATION_MEASUREMENT_INDEX: usize = 3;
+
+    /// Creates an empty confidential VM whose guest physical address space ends at `guest_physical_address_end`. Memory regions
+    /// cannot be added above this address. If the end is not given, the confidential VM can use the largest address space the
+    /// hardware translates.
+    pub fn new(
+        id: ConfidentialVmId, is_debuggable: bool, maps_memory_on_demand: bool, guest_physical_address_end: Option<usize>,
+    ) -> Result<Self, Error> {
+        let memory_protector = ConfidentialVmMemoryProtector::empty(guest_physical_address_end)?;
+        let measurements = [ConfidentialVmMeasurement::empty(); MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS];
+        Ok(Self {
+            id,
+            is_debuggable,
+            maps_memory_on_demand,
+            measurements,
+            measurement_log: MeasurementLog::empty(),
+            confidential_harts: Vec::new(),
+            memory_protector,
+            page_digests: BTreeMap::new(),
+        })
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.id
+    }
+
+    /// Copies `number_of_pages` 4KiB pages from the non-confidential memory starting at `source_address` to the confidential memory and
+    /// maps them in the confidential VM's address space starting at `address`. The region is mapped with the largest pages that fit
+    /// the alignment of guest physical addresses and the remaining size of the region, so that large regions consume fewer page tables
+    /// and TLB entries. Returns error if any page is not entirely in the non-confidential memory or if any guest physical address is
+    /// already mapped. Pages mapped before the error remain mapped and are measured at finalization. If the confidential VM maps memory
+    /// on demand, the pages are measured in the same way but are mapped when the confidential VM accesses them for the first time.
+    pub fn add_memory_region(
+        &mut self, address: ConfidentialVmPhysicalAddress, source_address: usize, number_of_pages: usize,
+    ) -> Result<(), Error> {
+        let source_address = NonConfidentialMemoryAddress::new(source_address as *mut usize)?;
+        let size_in_bytes = number_of_pages.checked_mul(PageSize::Size4KiB.in_bytes()).ok_or(Error::InvalidArgument())?;
+        let mut offset_in_bytes = 0;
+        while offset_in_bytes < size_in_bytes {
+            let guest_address = address.usize().checked_add(offset_in_bytes).ok_or(Error::InvalidArgument())?;
+            let page_address = MemoryLayout::read().non_confidential_address_at_offset(&source_address, offset_in_bytes)?;
+            let page = Self::copy_to_largest_fitting_page(guest_address, page_address, size_in_bytes - offset_in_bytes)?;
+            offset_in_bytes += page.size().in_bytes();
+            let guest_address = ConfidentialVmPhysicalAddress::new(guest_address);
+            let page_digests = ConfidentialVmMeasurement::page_digests(guest_address, &page)?;
+            match self.maps_memory_on_demand {
+                true => self.memory_protector.add_demand_page(guest_address, page)?,
+                false => self.memory_protector.map_confidential_page(guest_address, page)?,
+            }
+            self.page_digests.extend(page_digests.into_iter().map(|(address, digest)| (address.usize(), digest)));
+        }
+        Ok(())
+    }
+
+    /// Copies the content located at the given address of the non-confidential memory to the largest page that fits the guest physical
+    /// address and the remaining size of the region. Falls back to smaller pages if the page allocator has no free page of the larger
+    /// size.
+    fn copy_to_largest_fitting_page(
+        guest_address: usize, source_address: NonConfidentialMemoryAddress, remaining_in_bytes: usize,
+    ) -> Result<Page<Allocated>, Error> {
+        let mut page_size = PageSize::largest_fitting(guest_address, remaining_in_bytes).ok_or(Error::AddressNotAligned())?;
+        loop {
+            match PageGuard::acquire(1, page_size) {
+                Ok(mut page_guard) => {
+                    page_guard.copy_from_non_confidential_memory(source_address)?;
+                    return Ok(page_guard.commit().remove(0));
+                }
+                Err(Error::OutOfPages()) if page_size > PageSize::smallest() => {
+                    // The below unwrap is safe because a page larger than the smallest page has a smaller page size.
+                    page_size = page_size.smaller().unwrap();
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Adds a confidential hart with the next free id. If `start_address` is not zero, the confidential hart starts at this guest
+    /// physical address with `a0` set to its id and `a1` set to `opaque`, like after the SBI HSM `hart start` call. Otherwise, it stays
+    /// in the `Stopped` state until another confidential hart starts it. Returns error if the id is not the next free id or the start
+    /// address is not in the confidential VM's memory.
+    pub fn add_hart(&mut self, confidential_hart_id: usize, start_address: usize, opaque: usize) -> Result<(), Error> {
+        assure!(confidential_hart_id == self.confidential_harts.len(), Error::InvalidHartId())?;
+        assure!(confidential_hart_id < ConfidentialVm::MAX_NUMBER_OF_HARTS_PER_VM, Error::ReachedMaxNumberOfHartsPerVm())?;
+        let mut confidential_hart = ConfidentialHart::from_reset_state(confidential_hart_id);
+        // A hart without the confidential VM's id is a dummy hart, which cannot be started.
+        confidential_hart.set_confidential_vm_id(self.id);
+        if start_address != 0 {
+            let request = SbiHsmHartStart::new(confidential_hart_id, start_address, opaque);
+            confidential_hart.transition_from_stopped_to_start_pending(request, &self.memory_protector)?;
+        }
+        let mut hasher = Sha384::default();
+        [confidential_hart_id, start_address, opaque].iter().for_each(|value| hasher.update(&(*value as u64).to_le_bytes()));
+        let digest = hasher.finalize();
+        self.measurement_log.extend(&mut self.measurements, Self::HARTS_MEASUREMENT_INDEX, MeasuredComponent::Hart, None, &digest);
+        self.confidential_harts.push(confidential_hart);
+        Ok(())
+    }
+
+    /// Starts the boot hart, which was added without a start address, at the given guest physical address with `a1` set to `opaque`.
+    /// The entry is measured like the entry of a hart added with a start address. Returns error if the boot hart has not been added,
+    /// has already been started, or the start address is not in the confidential VM's memory.
+    pub fn start_boot_hart(&mut self, start_address: usize, opaque: usize) -> Result<(), Error> {
+        let boot_hart = self.confidential_harts.first_mut().ok_or(Error::NoBootHart())?;
+        boot_hart.transition_from_stopped_to_start_pending(SbiHsmHartStart::new(0, start_address, opaque), &self.memory_protector)?;
+        let mut hasher = Sha384::default();
+        [0, start_address, opaque].iter().for_each(|value| hasher.update(&(*value as u64).to_le_bytes()));
+        let digest = hasher.finalize();
+        self.measurement_log.extend(&mut self.measurements, Self::HARTS_MEASUREMENT_INDEX, MeasuredComponent::Hart, None, &digest);
+        Ok(())
+    }
+
+    /// Returns error if the confidential VM cannot be finalized because the boot hart has not been added or it has no start address.
+    pub fn assure_finalizable(&self) -> Result<(), Error> {
+        let boot_hart = self.confidential_harts.first().ok_or(Error::NoBootHart())?;
+        assure!(boot_hart.lifecycle_state() == &HartLifecycleState::StartPending, Error::NoBootHart())
+    }
+
+    /// Locks the content of the confidential VM and turns it into a runnable confidential VM. Returns error if the launch digest differs
+    /// from the expected one. In such a case, the confidential VM is dropped together with its memory.
+    ///
+    /// The memory measurement is a pure function of the final memory image. Every added 4KiB chunk of memory contributes the record
+    /// `SHA-384(gpa || content)`, computed when the memory is added, and the records are folded with the extend operation in the
+    /// ascending order of guest physical addresses at finalization:
+    ///
+    /// ```text
+    /// measurement = 0
+    /// for (gpa, content) in 4KiB chunks sorted by gpa:
+    ///     measurement = SHA-384(measurement || SHA-384(gpa || content))
+    /// ```
+    ///
+    /// A confidential VM created by promotion is measured in the same way, so a relying party computes the same reference value for an
+    /// image regardless of how the confidential VM was created, in which order the hypervisor donated its pages, or which page sizes
+    /// map it.
+    pub fn finalize(mut self, expected_launch_digest: Option<&[u8; Sha384::DIGEST_SIZE_IN_BYTES]>) -> Result<ConfidentialVm, Error> {
+        self.assure_finalizable()?;
+        self.measurement_log.extend_with_memory(&mut self.measurements, Self::MEMORY_MEASUREMENT_INDEX, &self.page_digests);
+        let configuration = ConfidentialVmMeasurement::from_configuration(self.is_debuggable);
+        self.measurement_log.assign_configuration(&mut self.measurements, Self::CONFIGURATION_MEASUREMENT_INDEX, configuration);
+        if let Some(expected_launch_digest) = expected_launch_digest {
+            let launch_digest = MeasurementRegisters::new(self.measurements).launch_digest();
+            assure!(constant_time_eq(&launch_digest, expected_launch_digest), Error::LaunchMeasurementMismatch())?;
+        }
+        // The content is encrypted under the confidential VM's key only after it has been measured, so the measurement is computed over
+        // the plaintext provided by the hypervisor.
+        let memory_key_slot = MemoryKeySlot::assign(&self.memory_protector)?;
+        Ok(ConfidentialVm::new(
+            self.id,
+            self.confidential_harts,
+            self.measurements,
+            self.memory_protector,
+            memory_key_slot,
+            MmioPolicy::empty(),
+            self.is_debuggable,
+        )
+        .with_measurement_log(self.measurement_log))
+    }
+}
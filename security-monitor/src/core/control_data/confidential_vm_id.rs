@@ -2,14 +2,34 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 
+/// The identifier of a confidential VM. The lowest bits store the index of the slot in the control data that holds the confidential
+/// VM, so that the confidential VM is found in constant time. The remaining bits store the generation of the slot, which changes every
+/// time the slot is released. Thus, an identifier of a destroyed confidential VM never refers to a confidential VM that later occupies
+/// the same slot.
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone)]
 pub struct ConfidentialVmId(usize);
 
 impl ConfidentialVmId {
+    const SLOT_BITS: usize = 10;
+    pub const MAX_NUMBER_OF_SLOTS: usize = 1 << Self::SLOT_BITS;
+    pub const MAX_GENERATION: usize = usize::MAX >> Self::SLOT_BITS;
+
     pub fn new(value: usize) -> Self {
         Self(value)
     }
 
+    pub fn from_slot(slot: usize, generation: usize) -> Self {
+        Self(((generation & Self::MAX_GENERATION) << Self::SLOT_BITS) | (slot & (Self::MAX_NUMBER_OF_SLOTS - 1)))
+    }
+
+    pub fn slot(&self) -> usize {
+        self.0 & (Self::MAX_NUMBER_OF_SLOTS - 1)
+    }
+
+    pub fn generation(&self) -> usize {
+        self.0 >> Self::SLOT_BITS
+    }
+
     pub fn usize(&self) -> usize {
         self.0
     }
@@ -1,8 +1,15 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::PageSize;
+use crate::core::page_allocator::{Allocated, Page};
+use crate::error::Error;
+use alloc::vec::Vec;
 
 const MAX_HASH_SIZE: usize = 512; // 512b for SHA-512
+const DEBUGGABLE_FLAG: u8 = 0x1;
 
 #[derive(Clone, Copy)]
 pub struct ConfidentialVmMeasurement {
@@ -13,4 +20,156 @@ impl ConfidentialVmMeasurement {
     pub const fn empty() -> Self {
         Self { value: [0u8; MAX_HASH_SIZE / 8] }
     }
+
+    /// Constructs a measurement reflecting the configuration of the confidential VM. A relying party inspects it during attestation to
+    /// learn, for example, that the confidential VM is debuggable.
+    pub fn from_configuration(is_debuggable: bool) -> Self {
+        let mut measurement = Self::empty();
+        if is_debuggable {
+            measurement.value[0] |= DEBUGGABLE_FLAG;
+        }
+        measurement
+    }
+
+    /// Extends the measurement with the given digest using the canonical extend operation:
+    /// `measurement = SHA-384(measurement || digest)`. The extend operation is not commutative, so the final measurement reflects also
+    /// the order in which digests were extended.
+    pub fn extend(&mut self, digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) {
+        let mut hasher = Sha384::default();
+        hasher.update(&self.value[..Sha384::DIGEST_SIZE_IN_BYTES]);
+        hasher.update(digest);
+        self.value[..Sha384::DIGEST_SIZE_IN_BYTES].copy_from_slice(&hasher.finalize());
+    }
+
+    /// Returns the digests with which the memory measurement is extended for the page. Pages are measured in 4KiB chunks, each one
+    /// contributing `SHA-384(address || content)`, so the measurement does not depend on the page sizes with which the memory is mapped.
+    /// Hashing the address makes the measurement reflect the memory layout, not only the memory content.
+    pub fn page_digests(
+        address: ConfidentialVmPhysicalAddress, page: &Page<Allocated>,
+    ) -> Result<Vec<(ConfidentialVmPhysicalAddress, [u8; Sha384::DIGEST_SIZE_IN_BYTES])>, Error> {
+        let chunk_size_in_bytes = PageSize::Size4KiB.in_bytes();
+        (0..page.size().in_bytes())
+            .step_by(chunk_size_in_bytes)
+            .map(|chunk_offset_in_bytes| {
+                let chunk_address = ConfidentialVmPhysicalAddress::new(address.usize() + chunk_offset_in_bytes);
+                let chunk = chunk_offset_in_bytes..chunk_offset_in_bytes + chunk_size_in_bytes;
+                let words = chunk.step_by(core::mem::size_of::<usize>()).map(|offset_in_bytes| page.read(offset_in_bytes));
+                Ok((chunk_address, Self::chunk_digest(chunk_address, words)?))
+            })
+            .collect()
+    }
+
+    fn chunk_digest(
+        chunk_address: ConfidentialVmPhysicalAddress, mut words: impl Iterator<Item = Result<usize, Error>>,
+    ) -> Result<[u8; Sha384::DIGEST_SIZE_IN_BYTES], Error> {
+        let mut hasher = Sha384::default();
+        hasher.update(&(chunk_address.usize() as u64).to_le_bytes());
+        words.try_for_each(|word| word.map(|word| hasher.update(&word.to_le_bytes())))?;
+        Ok(hasher.finalize())
+    }
+}
+
+/// The measurement registers of a confidential VM, similar to the platform configuration registers (PCRs) of a TPM. The first
+/// `NUMBER_OF_LAUNCH_REGISTERS` registers hold the measurements taken when the confidential VM was created and are locked afterwards.
+/// The remaining registers start empty and the confidential VM extends them at runtime, for example, with digests of loaded kernel
+/// modules. The confidential VM can read all registers.
+pub struct MeasurementRegisters {
+    registers: [ConfidentialVmMeasurement; Self::NUMBER_OF_REGISTERS],
+}
+
+impl MeasurementRegisters {
+    pub const NUMBER_OF_REGISTERS: usize = 8;
+    pub const NUMBER_OF_LAUNCH_REGISTERS: usize = 4;
+
+    pub fn new(launch_measurements: [ConfidentialVmMeasurement; Self::NUMBER_OF_LAUNCH_REGISTERS]) -> Self {
+        let mut registers = [ConfidentialVmMeasurement::empty(); Self::NUMBER_OF_REGISTERS];
+        registers[..Self::NUMBER_OF_LAUNCH_REGISTERS].copy_from_slice(&launch_measurements);
+        Self { registers }
+    }
+
+    pub fn read(&self, index: usize) -> Result<&ConfidentialVmMeasurement, Error> {
+        self.registers.get(index).ok_or(Error::InvalidMeasurementRegister())
+    }
+
+    /// Returns the digest stored in the launch measurement register. Returns error if the register does not hold a launch measurement.
+    pub fn read_launch_measurement(&self, index: usize) -> Result<[u8; Sha384::DIGEST_SIZE_IN_BYTES], Error> {
+        assure!(index < Self::NUMBER_OF_LAUNCH_REGISTERS, Error::InvalidMeasurementRegister())?;
+        let mut digest = [0u8; Sha384::DIGEST_SIZE_IN_BYTES];
+        digest.copy_from_slice(&self.registers[index].value[..Sha384::DIGEST_SIZE_IN_BYTES]);
+        Ok(digest)
+    }
+
+    /// Returns the digest of all launch measurement registers, `SHA-384(register 0 || ... || register 3)`, which identifies the
+    /// confidential VM's initial content and configuration with a single value.
+    pub fn launch_digest(&self) -> [u8; Sha384::DIGEST_SIZE_IN_BYTES] {
+        let mut hasher = Sha384::default();
+        self.registers[..Self::NUMBER_OF_LAUNCH_REGISTERS]
+            .iter()
+            .for_each(|register| hasher.update(&register.value[..Sha384::DIGEST_SIZE_IN_BYTES]));
+        hasher.finalize()
+    }
+
+    /// Extends the runtime measurement register with the digest. Returns error if the register does not exist or holds a launch
+    /// measurement.
+    pub fn extend(&mut self, index: usize, digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Result<(), Error> {
+        assure!(index >= Self::NUMBER_OF_LAUNCH_REGISTERS, Error::MeasurementRegisterLocked())?;
+        self.registers.get_mut(index).ok_or(Error::InvalidMeasurementRegister())?.extend(digest);
+        Ok(())
+    }
+
+    /// Returns all registers in the order of their indices, which is the order in which they appear in the attestation evidence.
+    pub fn iter(&self) -> impl Iterator<Item = &ConfidentialVmMeasurement> {
+        self.registers.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS_PER_CHUNK: usize = 4096 / core::mem::size_of::<usize>();
+    // A known image of two 4KiB chunks: (guest physical address, the word repeated over the whole chunk).
+    const IMAGE: [(usize, usize); 2] = [(0x8000_0000, 0x0123_4567_89ab_cdef), (0x8000_1000, 0)];
+    // Reference values computed independently of the security monitor for `IMAGE` and a debuggable confidential VM.
+    const MEMORY_MEASUREMENT: &str = "f8c6acd7689d61e7323c7e4fbc52fcbfba11b753e982dbe42eb75079e7075f3a4b6f92fb69715944aad3c36983e14d6d";
+    const LAUNCH_DIGEST: &str = "695724b21371b93d83acd16ab778416b6a4713edf13fc1e7ff5ce5f490415b393b178978cbef917ed33cfad973bd8da7";
+
+    fn to_hex(digest: &[u8]) -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    // Measures the image as the promotion does: chunks in the ascending order of their guest physical addresses.
+    fn measure_image() -> MeasurementRegisters {
+        let mut measurements = [ConfidentialVmMeasurement::empty(); MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS];
+        for (address, word) in IMAGE {
+            let words = (0..WORDS_PER_CHUNK).map(|_| Ok(word));
+            measurements[0].extend(&ConfidentialVmMeasurement::chunk_digest(ConfidentialVmPhysicalAddress::new(address), words).unwrap());
+        }
+        measurements[3] = ConfidentialVmMeasurement::from_configuration(true);
+        MeasurementRegisters::new(measurements)
+    }
+
+    #[test]
+    fn launch_measurement_matches_reference_value() {
+        let measurements = measure_image();
+        assert_eq!(to_hex(&measurements.read_launch_measurement(0).unwrap()), MEMORY_MEASUREMENT);
+        assert_eq!(to_hex(&measurements.launch_digest()), LAUNCH_DIGEST);
+    }
+
+    #[test]
+    fn runtime_measurements_are_not_launch_measurements() {
+        let mut measurements = measure_image();
+        for index in MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS..MeasurementRegisters::NUMBER_OF_REGISTERS {
+            measurements.extend(index, &[0xab; Sha384::DIGEST_SIZE_IN_BYTES]).unwrap();
+            assert!(matches!(measurements.read_launch_measurement(index), Err(Error::InvalidMeasurementRegister())));
+        }
+        assert_eq!(to_hex(&measurements.launch_digest()), LAUNCH_DIGEST);
+    }
+
+    #[test]
+    fn launch_measurements_are_locked() {
+        let mut measurements = measure_image();
+        assert!(matches!(measurements.extend(0, &[0xab; Sha384::DIGEST_SIZE_IN_BYTES]), Err(Error::MeasurementRegisterLocked())));
+        assert_eq!(to_hex(&measurements.read_launch_measurement(0).unwrap()), MEMORY_MEASUREMENT);
+    }
 }
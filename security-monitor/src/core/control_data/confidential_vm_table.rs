@@ -0,0 +1,167 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::{ConfidentialVm, ConfidentialVmBuilder, ConfidentialVmId};
+use crate::error::Error;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// A confidential VM in one of the stages of its lifecycle.
+pub enum StoredConfidentialVm {
+    // The hypervisor is still constructing the confidential VM. It cannot be resumed until it is finalized.
+    UnderConstruction(Box<Mutex<ConfidentialVmBuilder>>),
+    // The confidential VM can be resumed.
+    Finalized(Box<Mutex<ConfidentialVm>>),
+    // The confidential VM can never be resumed again but still owns confidential memory that the hypervisor reclaims in batches.
+    InTeardown(Box<Mutex<ConfidentialVm>>),
+}
+
+struct Slot<T> {
+    generation: usize,
+    confidential_vm: Option<T>,
+}
+
+/// Stores confidential VMs in a fixed number of slots indexed by the confidential VM identifier. Every access checks that the
+/// generation encoded in the identifier matches the generation of the slot, so identifiers of released slots are rejected. The control
+/// data stores `StoredConfidentialVm`s in it.
+pub struct ConfidentialVmTable<T> {
+    slots: Vec<Slot<T>>,
+}
+
+impl<T> ConfidentialVmTable<T> {
+    pub fn empty() -> Self {
+        let slots = (0..ConfidentialVmId::MAX_NUMBER_OF_SLOTS).map(|_| Slot { generation: 0, confidential_vm: None }).collect();
+        Self { slots }
+    }
+
+    /// Returns the identifier that a new confidential VM would get, or error if all slots are in use.
+    pub fn free_id(&self) -> Result<ConfidentialVmId, Error> {
+        self.slots
+            .iter()
+            .position(|slot| slot.confidential_vm.is_none())
+            .map(|index| ConfidentialVmId::from_slot(index, self.slots[index].generation))
+            .ok_or(Error::TooManyConfidentialVms())
+    }
+
+    pub fn get(&self, id: ConfidentialVmId) -> Result<&T, Error> {
+        self.slot(id)?.confidential_vm.as_ref().ok_or(Error::InvalidConfidentialVmId())
+    }
+
+    /// Stores the confidential VM in the free slot that the identifier refers to.
+    pub fn insert(&mut self, id: ConfidentialVmId, confidential_vm: T) -> Result<(), Error> {
+        let slot = self.slot_mut(id)?;
+        assure!(slot.confidential_vm.is_none(), Error::InvalidConfidentialVmId())?;
+        slot.confidential_vm = Some(confidential_vm);
+        Ok(())
+    }
+
+    /// Moves the confidential VM to the next stage of its lifecycle. The confidential VM is consumed by the operation, so if the
+    /// operation fails the slot is released.
+    pub fn replace<O>(&mut self, id: ConfidentialVmId, op: O) -> Result<(), Error>
+    where O: FnOnce(T) -> Result<T, Error> {
+        let confidential_vm = self.slot_mut(id)?.confidential_vm.take().ok_or(Error::InvalidConfidentialVmId())?;
+        match op(confidential_vm) {
+            Ok(confidential_vm) => self.insert(id, confidential_vm),
+            Err(error) => {
+                self.release(id);
+                Err(error)
+            }
+        }
+    }
+
+    /// Removes the confidential VM and releases its slot. The slot's generation changes, so the identifier becomes stale.
+    pub fn remove(&mut self, id: ConfidentialVmId) -> Result<T, Error> {
+        let confidential_vm = self.slot_mut(id)?.confidential_vm.take().ok_or(Error::InvalidConfidentialVmId())?;
+        self.release(id);
+        Ok(confidential_vm)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.confidential_vm.as_ref())
+    }
+
+    fn release(&mut self, id: ConfidentialVmId) {
+        if let Ok(slot) = self.slot_mut(id) {
+            slot.confidential_vm = None;
+            slot.generation = (slot.generation + 1) & ConfidentialVmId::MAX_GENERATION;
+        }
+    }
+
+    fn slot(&self, id: ConfidentialVmId) -> Result<&Slot<T>, Error> {
+        self.slots.get(id.slot()).filter(|slot| slot.generation == id.generation()).ok_or(Error::InvalidConfidentialVmId())
+    }
+
+    fn slot_mut(&mut self, id: ConfidentialVmId) -> Result<&mut Slot<T>, Error> {
+        self.slots.get_mut(id.slot()).filter(|slot| slot.generation == id.generation()).ok_or(Error::InvalidConfidentialVmId())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_with(confidential_vm: u32) -> (ConfidentialVmTable<u32>, ConfidentialVmId) {
+        let mut table = ConfidentialVmTable::empty();
+        let id = table.free_id().unwrap();
+        table.insert(id, confidential_vm).unwrap();
+        (table, id)
+    }
+
+    #[test]
+    fn confidential_vm_is_found_by_id() {
+        let (table, id) = table_with(1);
+        assert_eq!(table.get(id).ok(), Some(&1));
+        assert!(!table.is_empty());
+    }
+
+    #[test]
+    fn stale_id_is_rejected_after_teardown() {
+        let (mut table, stale_id) = table_with(1);
+        assert_eq!(table.remove(stale_id).ok(), Some(1));
+        // A new confidential VM reuses the slot, but gets an identifier with another generation.
+        let id = table.free_id().unwrap();
+        table.insert(id, 2).unwrap();
+        assert_eq!(id.slot(), stale_id.slot());
+        assert_ne!(id, stale_id);
+        assert!(matches!(table.get(stale_id), Err(Error::InvalidConfidentialVmId())));
+        assert!(matches!(table.remove(stale_id), Err(Error::InvalidConfidentialVmId())));
+        assert!(table.insert(stale_id, 3).is_err());
+        assert_eq!(table.get(id).ok(), Some(&2));
+    }
+
+    #[test]
+    fn failed_replacement_releases_slot() {
+        let (mut table, id) = table_with(1);
+        assert!(table.replace(id, |_| Err(Error::InvalidConfidentialVmId())).is_err());
+        assert!(table.get(id).is_err());
+        assert!(table.is_empty());
+        let (mut table, id) = table_with(1);
+        table.replace(id, |confidential_vm| Ok(confidential_vm + 1)).unwrap();
+        assert_eq!(table.get(id).ok(), Some(&2));
+    }
+
+    #[test]
+    fn generation_wraps_around() {
+        let mut table = ConfidentialVmTable::empty();
+        table.slots[0].generation = ConfidentialVmId::MAX_GENERATION;
+        let id = table.free_id().unwrap();
+        table.insert(id, 1).unwrap();
+        table.remove(id).unwrap();
+        assert_eq!(table.free_id().unwrap(), ConfidentialVmId::from_slot(0, 0));
+    }
+
+    #[test]
+    fn full_table_has_no_free_id() {
+        let mut table = ConfidentialVmTable::empty();
+        for confidential_vm in 0..ConfidentialVmId::MAX_NUMBER_OF_SLOTS {
+            let id = table.free_id().unwrap();
+            table.insert(id, confidential_vm).unwrap();
+        }
+        assert!(matches!(table.free_id(), Err(Error::TooManyConfidentialVms())));
+    }
+}
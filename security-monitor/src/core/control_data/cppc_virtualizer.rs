@@ -0,0 +1,165 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::SbiError;
+use crate::core::transformations::{CppcRequest, CppcResult};
+
+/// Virtualizes the SBI Collaborative Processor Performance Control (CPPC) extension for a confidential hart.
+///
+/// Performance targets requested by a confidential VM reveal its load, and the capabilities and feedback counters of the physical hart
+/// reveal the load and the power state of other security domains. Thus, neither is passed through. The confidential hart sees a fixed
+/// virtual performance scale and its control registers retain the written hints, which are validated against the scale but never
+/// reach the hypervisor or the physical hart. Feedback counters are not supported.
+pub struct CppcVirtualizer {
+    desired_performance: usize,
+    minimum_performance: usize,
+    maximum_performance: usize,
+    cppc_enable: usize,
+    autonomous_selection_enable: usize,
+    energy_performance_preference: usize,
+}
+
+impl Default for CppcVirtualizer {
+    fn default() -> Self {
+        Self {
+            desired_performance: Self::NOMINAL_PERFORMANCE,
+            minimum_performance: Self::LOWEST_PERFORMANCE,
+            maximum_performance: Self::HIGHEST_PERFORMANCE,
+            cppc_enable: 0,
+            autonomous_selection_enable: 0,
+            energy_performance_preference: 0,
+        }
+    }
+}
+
+impl CppcVirtualizer {
+    const HIGHEST_PERFORMANCE_ID: usize = 0x00;
+    const NOMINAL_PERFORMANCE_ID: usize = 0x01;
+    const LOWEST_NONLINEAR_PERFORMANCE_ID: usize = 0x02;
+    const LOWEST_PERFORMANCE_ID: usize = 0x03;
+    const DESIRED_PERFORMANCE_ID: usize = 0x05;
+    const MINIMUM_PERFORMANCE_ID: usize = 0x06;
+    const MAXIMUM_PERFORMANCE_ID: usize = 0x07;
+    const CPPC_ENABLE_ID: usize = 0x0e;
+    const AUTONOMOUS_SELECTION_ENABLE_ID: usize = 0x0f;
+    const ENERGY_PERFORMANCE_PREFERENCE_ID: usize = 0x11;
+    const LAST_ACPI_REGISTER_ID: usize = 0x14;
+    const TRANSITION_LATENCY_ID: usize = 0x8000_0000;
+
+    const HIGHEST_PERFORMANCE: usize = 100;
+    const NOMINAL_PERFORMANCE: usize = 100;
+    const LOWEST_NONLINEAR_PERFORMANCE: usize = 1;
+    const LOWEST_PERFORMANCE: usize = 1;
+    const MAX_ENERGY_PERFORMANCE_PREFERENCE: usize = 0xff;
+    // All supported registers are 32-bit wide.
+    const REGISTER_WIDTH_IN_BITS: usize = 32;
+
+    /// Executes the CPPC call on the virtual registers of the confidential hart.
+    pub fn handle(&mut self, request: CppcRequest) -> CppcResult {
+        match request {
+            CppcRequest::Probe { register_id } => match self.read_register(register_id) {
+                Ok(_) => CppcResult::Success(Self::REGISTER_WIDTH_IN_BITS),
+                Err(SbiError::NotSupported) => CppcResult::Success(0),
+                Err(error) => CppcResult::Failure(error),
+            },
+            CppcRequest::Read { register_id } => self.read_register(register_id).map_or_else(CppcResult::Failure, CppcResult::Success),
+            // Registers are at most 64-bit wide, so on RV64 their upper halves are always read by the `read` call.
+            CppcRequest::ReadHi { register_id } => {
+                self.read_register(register_id).map_or_else(CppcResult::Failure, |_| CppcResult::Success(0))
+            }
+            CppcRequest::Write { register_id, value } => {
+                self.write_register(register_id, value).map_or_else(CppcResult::Failure, |_| CppcResult::Success(0))
+            }
+            CppcRequest::Unsupported => CppcResult::Failure(SbiError::NotSupported),
+        }
+    }
+
+    fn read_register(&self, register_id: usize) -> Result<usize, SbiError> {
+        match register_id {
+            Self::HIGHEST_PERFORMANCE_ID => Ok(Self::HIGHEST_PERFORMANCE),
+            Self::NOMINAL_PERFORMANCE_ID => Ok(Self::NOMINAL_PERFORMANCE),
+            Self::LOWEST_NONLINEAR_PERFORMANCE_ID => Ok(Self::LOWEST_NONLINEAR_PERFORMANCE),
+            Self::LOWEST_PERFORMANCE_ID => Ok(Self::LOWEST_PERFORMANCE),
+            Self::DESIRED_PERFORMANCE_ID => Ok(self.desired_performance),
+            Self::MINIMUM_PERFORMANCE_ID => Ok(self.minimum_performance),
+            Self::MAXIMUM_PERFORMANCE_ID => Ok(self.maximum_performance),
+            Self::CPPC_ENABLE_ID => Ok(self.cppc_enable),
+            Self::AUTONOMOUS_SELECTION_ENABLE_ID => Ok(self.autonomous_selection_enable),
+            Self::ENERGY_PERFORMANCE_PREFERENCE_ID => Ok(self.energy_performance_preference),
+            register_id if register_id <= Self::LAST_ACPI_REGISTER_ID || register_id == Self::TRANSITION_LATENCY_ID => {
+                Err(SbiError::NotSupported)
+            }
+            _ => Err(SbiError::InvalidParam),
+        }
+    }
+
+    fn write_register(&mut self, register_id: usize, value: usize) -> Result<(), SbiError> {
+        let performance_range = Self::LOWEST_PERFORMANCE..=Self::HIGHEST_PERFORMANCE;
+        let (register, valid_values) = match register_id {
+            Self::DESIRED_PERFORMANCE_ID => (&mut self.desired_performance, performance_range),
+            Self::MINIMUM_PERFORMANCE_ID => (&mut self.minimum_performance, performance_range),
+            Self::MAXIMUM_PERFORMANCE_ID => (&mut self.maximum_performance, performance_range),
+            Self::CPPC_ENABLE_ID => (&mut self.cppc_enable, 0..=1),
+            Self::AUTONOMOUS_SELECTION_ENABLE_ID => (&mut self.autonomous_selection_enable, 0..=1),
+            Self::ENERGY_PERFORMANCE_PREFERENCE_ID => {
+                (&mut self.energy_performance_preference, 0..=Self::MAX_ENERGY_PERFORMANCE_PREFERENCE)
+            }
+            // Capability registers are read-only.
+            register_id => return Err(self.read_register(register_id).map_or_else(|error| error, |_| SbiError::Denied)),
+        };
+        if !valid_values.contains(&value) {
+            return Err(SbiError::InvalidParam);
+        }
+        *register = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unvirtualized_register_is_not_supported() {
+        let mut cppc_virtualizer = CppcVirtualizer::default();
+        // Reference performance and the feedback counters are ACPI registers the virtualizer does not implement.
+        for register_id in [0x04, 0x09, 0x0a, CppcVirtualizer::LAST_ACPI_REGISTER_ID, CppcVirtualizer::TRANSITION_LATENCY_ID] {
+            // The SBI specification reports unimplemented registers as zero bits wide when probed.
+            assert_eq!(cppc_virtualizer.handle(CppcRequest::Probe { register_id }), CppcResult::Success(0));
+            assert_eq!(cppc_virtualizer.handle(CppcRequest::Read { register_id }), CppcResult::Failure(SbiError::NotSupported));
+            assert_eq!(cppc_virtualizer.handle(CppcRequest::ReadHi { register_id }), CppcResult::Failure(SbiError::NotSupported));
+            assert_eq!(cppc_virtualizer.handle(CppcRequest::Write { register_id, value: 1 }), CppcResult::Failure(SbiError::NotSupported));
+        }
+    }
+
+    #[test]
+    fn reserved_register_is_invalid() {
+        let mut cppc_virtualizer = CppcVirtualizer::default();
+        let register_id = CppcVirtualizer::LAST_ACPI_REGISTER_ID + 1;
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Probe { register_id }), CppcResult::Failure(SbiError::InvalidParam));
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Read { register_id }), CppcResult::Failure(SbiError::InvalidParam));
+    }
+
+    #[test]
+    fn virtualized_register_retains_written_hint() {
+        let mut cppc_virtualizer = CppcVirtualizer::default();
+        let register_id = CppcVirtualizer::DESIRED_PERFORMANCE_ID;
+        assert_eq!(
+            cppc_virtualizer.handle(CppcRequest::Probe { register_id }),
+            CppcResult::Success(CppcVirtualizer::REGISTER_WIDTH_IN_BITS)
+        );
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Write { register_id, value: 42 }), CppcResult::Success(0));
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Read { register_id }), CppcResult::Success(42));
+        let value = CppcVirtualizer::HIGHEST_PERFORMANCE + 1;
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Write { register_id, value }), CppcResult::Failure(SbiError::InvalidParam));
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Read { register_id }), CppcResult::Success(42));
+    }
+
+    #[test]
+    fn capability_register_is_read_only() {
+        let mut cppc_virtualizer = CppcVirtualizer::default();
+        let register_id = CppcVirtualizer::HIGHEST_PERFORMANCE_ID;
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Write { register_id, value: 1 }), CppcResult::Failure(SbiError::Denied));
+        assert_eq!(cppc_virtualizer.handle(CppcRequest::Read { register_id }), CppcResult::Success(CppcVirtualizer::HIGHEST_PERFORMANCE));
+    }
+}
@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::{CSR_CYCLE, CSR_MISA, CSR_SEED, CSR_TIME};
+use crate::core::transformations::VirtualizedCsr;
+use crate::error::Error;
+use alloc::collections::BTreeMap;
+use flattened_device_tree::FlattenedDeviceTree;
+
+/// Defines how the security monitor emulates trapped accesses of a confidential VM's harts to CSRs. Accesses to CSRs that the policy
+/// does not list are handled as if the policy did not exist, e.g., they raise the illegal instruction exception in the confidential
+/// hart. The policy is fixed when the confidential VM is created.
+pub struct CsrEmulationPolicy {
+    entries: BTreeMap<u16, CsrEmulation>,
+    // Counters, in the layout of `hcounteren`, that the confidential VM reads via the policy instead of directly from the hardware.
+    trapped_counters: usize,
+}
+
+impl CsrEmulationPolicy {
+    const MAX_NUMBER_OF_ENTRIES: usize = 32;
+    const NUMBER_OF_COUNTERS: u16 = 32;
+    const DENY_ACTION: u64 = 0;
+    const CONSTANT_ACTION: u64 = 1;
+    const VIRTUALIZED_ACTION: u64 = 2;
+
+    /// Builds the policy from the confidential VM's device tree, on top of the default policy. Counters listed in the device tree
+    /// always trap, regardless of what the hypervisor enabled in `hcounteren`. Returns error if the device tree declares an unknown
+    /// action, a CSR that the security monitor cannot virtualize, or more entries than the security monitor supports.
+    pub fn from_device_tree(device_tree: &FlattenedDeviceTree) -> Result<Self, Error> {
+        let mut policy = Self::default();
+        for (index, entry) in device_tree.csr_emulation().enumerate() {
+            assure!(index < Self::MAX_NUMBER_OF_ENTRIES, Error::InvalidCsrEmulationPolicy())?;
+            let csr = u16::try_from(entry.csr).ok().filter(|csr| *csr <= 0xfff).ok_or(Error::InvalidCsrEmulationPolicy())?;
+            let emulation = match entry.action {
+                Self::DENY_ACTION => CsrEmulation::Deny,
+                Self::CONSTANT_ACTION => {
+                    CsrEmulation::Constant(usize::try_from(entry.value).map_err(|_| Error::InvalidCsrEmulationPolicy())?)
+                }
+                Self::VIRTUALIZED_ACTION => {
+                    CsrEmulation::Virtualized(VirtualizedCsr::from_csr(csr).ok_or(Error::InvalidCsrEmulationPolicy())?)
+                }
+                _ => return Err(Error::InvalidCsrEmulationPolicy()),
+            };
+            if let Some(counter) = csr.checked_sub(CSR_CYCLE).filter(|counter| *counter < Self::NUMBER_OF_COUNTERS) {
+                policy.trapped_counters |= 1 << counter;
+            }
+            policy.entries.insert(csr, emulation);
+        }
+        Ok(policy)
+    }
+
+    /// Returns how accesses to the CSR are emulated, or `None` if the policy does not list the CSR.
+    pub fn emulation(&self, csr: u16) -> Option<CsrEmulation> {
+        self.entries.get(&csr).copied()
+    }
+
+    pub fn trapped_counters(&self) -> usize {
+        self.trapped_counters
+    }
+
+    /// Builds the policy with the given entries on top of the default policy, like `from_device_tree` does for a device tree that
+    /// declares them.
+    #[cfg(test)]
+    pub fn with_entries(entries: &[(u16, CsrEmulation)]) -> Self {
+        let mut policy = Self::default();
+        policy.entries.extend(entries.iter().copied());
+        policy
+    }
+}
+
+impl Default for CsrEmulationPolicy {
+    /// The policy of confidential VMs that do not declare one. The entropy source and `misa` are virtualized, and so is `time` when
+    /// the hypervisor has not enabled it in `hcounteren`.
+    fn default() -> Self {
+        let entries = [CSR_SEED, CSR_MISA, CSR_TIME]
+            .into_iter()
+            .filter_map(|csr| Some((csr, CsrEmulation::Virtualized(VirtualizedCsr::from_csr(csr)?))))
+            .collect();
+        Self { entries, trapped_counters: 0 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CsrEmulation {
+    /// Accesses raise the illegal instruction exception in the confidential hart.
+    Deny,
+    /// Reads return the constant. Writes raise the illegal instruction exception.
+    Constant(usize),
+    /// Accesses are emulated with the confidential hart's virtualized state, see `VirtualizedCsr`.
+    Virtualized(VirtualizedCsr),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_virtualizes_time_but_not_cycle() {
+        let policy = CsrEmulationPolicy::default();
+        assert!(policy.emulation(CSR_TIME) == Some(CsrEmulation::Virtualized(VirtualizedCsr::Time)));
+        // The cycle counter is virtualized only if the confidential VM declares it, see `PmuVirtualizer::read_cycle_csr`.
+        assert!(policy.emulation(CSR_CYCLE).is_none());
+        assert_eq!(policy.trapped_counters(), 0);
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::specification::{CSR_HENVCFG_PMM, CSR_HENVCFG_PMM_MASK};
+use crate::core::architecture::{SbiError, CSR};
+use crate::core::transformations::{FwftRequest, FwftResult};
+
+/// Virtualizes the SBI firmware features (FWFT) extension for a confidential hart.
+///
+/// Only features whose configuration is confined to the confidential hart can be set. Misaligned load and store exceptions are always
+/// delegated to the confidential hart, because handling them outside of it would expose the faulting addresses to the hypervisor, so
+/// a request to stop delegating them is denied. The pointer masking length is applied to the `PMM` field of `henvcfg` whenever the
+/// confidential hart is scheduled on a physical hart, so it does not depend on the value that the hypervisor configured for itself.
+#[derive(Default)]
+pub struct FwftVirtualizer {
+    pointer_masking_length: usize,
+    // Bitmap indexed by the feature id. A locked feature cannot be set until the confidential hart is destroyed.
+    locked_features: usize,
+}
+
+impl FwftVirtualizer {
+    const MISALIGNED_EXC_DELEG_ID: usize = 0x0;
+    const LANDING_PAD_ID: usize = 0x1;
+    const PTE_AD_HW_UPDATING_ID: usize = 0x4;
+    const POINTER_MASKING_PMLEN_ID: usize = 0x5;
+
+    const LOCK_FLAG: usize = 1 << 0;
+    // Pointer masking lengths defined by the Smnpm extension, from the shortest one, with their encodings in the `PMM` field.
+    const POINTER_MASKING_LENGTHS: [(usize, usize); 3] = [(0, 0b00), (7, 0b10), (16, 0b11)];
+
+    /// Executes the FWFT call on the features of the confidential hart. The confidential hart's `henvcfg` must be loaded into the
+    /// physical hart, because setting the pointer masking length probes which lengths the hardware implements.
+    pub fn handle(&mut self, request: FwftRequest) -> FwftResult {
+        let henvcfg = CSR.henvcfg.read();
+        // `PMM` is a WARL field, so a length is implemented if its encoding reads back from `henvcfg`.
+        let result = self.execute(request, |length| {
+            CSR.henvcfg.set(Self::with_pointer_masking_length(henvcfg, length));
+            CSR.henvcfg.read() & CSR_HENVCFG_PMM_MASK == Self::with_pointer_masking_length(0, length)
+        });
+        CSR.henvcfg.set(self.henvcfg(henvcfg));
+        result
+    }
+
+    /// Returns the value of `henvcfg` with the confidential hart's pointer masking length replacing the one of the given value.
+    pub fn henvcfg(&self, henvcfg: usize) -> usize {
+        Self::with_pointer_masking_length(henvcfg, self.pointer_masking_length)
+    }
+
+    fn execute(&mut self, request: FwftRequest, is_pointer_masking_length_implemented: impl FnMut(usize) -> bool) -> FwftResult {
+        match request {
+            FwftRequest::Set { feature, value, flags } => self
+                .set_feature(feature, value, flags, is_pointer_masking_length_implemented)
+                .map_or_else(FwftResult::Failure, |_| FwftResult::Success(0)),
+            FwftRequest::Get { feature } => self.get_feature(feature).map_or_else(FwftResult::Failure, FwftResult::Success),
+            FwftRequest::Unsupported => FwftResult::Failure(SbiError::NotSupported),
+        }
+    }
+
+    fn get_feature(&self, feature: usize) -> Result<usize, SbiError> {
+        match feature {
+            Self::MISALIGNED_EXC_DELEG_ID => Ok(1),
+            Self::POINTER_MASKING_PMLEN_ID => Ok(self.pointer_masking_length),
+            // Landing pads, shadow stacks, double traps, and hardware updates of VS-stage accessed and dirty bits.
+            Self::LANDING_PAD_ID..=Self::PTE_AD_HW_UPDATING_ID => Err(SbiError::NotSupported),
+            _ => Err(SbiError::InvalidParam),
+        }
+    }
+
+    fn set_feature(
+        &mut self, feature: usize, value: usize, flags: usize, is_pointer_masking_length_implemented: impl FnMut(usize) -> bool,
+    ) -> Result<(), SbiError> {
+        if flags & !Self::LOCK_FLAG != 0 {
+            return Err(SbiError::InvalidParam);
+        }
+        self.get_feature(feature)?;
+        if self.locked_features & (1 << feature) != 0 {
+            return Err(SbiError::DeniedLocked);
+        }
+        match (feature, value) {
+            (Self::MISALIGNED_EXC_DELEG_ID, 1) => {}
+            (Self::MISALIGNED_EXC_DELEG_ID, 0) => return Err(SbiError::Denied),
+            (Self::POINTER_MASKING_PMLEN_ID, _) => self.set_pointer_masking_length(value, is_pointer_masking_length_implemented)?,
+            _ => return Err(SbiError::InvalidParam),
+        }
+        if flags & Self::LOCK_FLAG != 0 {
+            self.locked_features |= 1 << feature;
+        }
+        Ok(())
+    }
+
+    /// Applies the shortest pointer masking length implemented by the hardware that is not shorter than the requested one.
+    fn set_pointer_masking_length(
+        &mut self, requested_length: usize, mut is_implemented: impl FnMut(usize) -> bool,
+    ) -> Result<(), SbiError> {
+        self.pointer_masking_length = Self::POINTER_MASKING_LENGTHS
+            .iter()
+            .map(|(length, _)| *length)
+            .filter(|length| *length >= requested_length)
+            .find(|length| is_implemented(*length))
+            .ok_or(SbiError::InvalidParam)?;
+        Ok(())
+    }
+
+    fn with_pointer_masking_length(henvcfg: usize, length: usize) -> usize {
+        let encoding = Self::POINTER_MASKING_LENGTHS
+            .iter()
+            .find(|(supported_length, _)| *supported_length == length)
+            .map_or(0, |(_, encoding)| *encoding);
+        (henvcfg & !CSR_HENVCFG_PMM_MASK) | (encoding << CSR_HENVCFG_PMM)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pointer masking lengths implemented by the emulated hardware.
+    const IMPLEMENTED_LENGTHS: [usize; 2] = [0, 16];
+
+    fn execute(fwft_virtualizer: &mut FwftVirtualizer, request: FwftRequest) -> FwftResult {
+        fwft_virtualizer.execute(request, |length| IMPLEMENTED_LENGTHS.contains(&length))
+    }
+
+    fn set(feature: usize, value: usize, flags: usize) -> FwftRequest {
+        FwftRequest::Set { feature, value, flags }
+    }
+
+    fn get(feature: usize) -> FwftRequest {
+        FwftRequest::Get { feature }
+    }
+
+    #[test]
+    fn misaligned_exceptions_stay_delegated() {
+        let mut fwft_virtualizer = FwftVirtualizer::default();
+        assert_eq!(execute(&mut fwft_virtualizer, get(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID)), FwftResult::Success(1));
+        assert_eq!(execute(&mut fwft_virtualizer, set(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID, 1, 0)), FwftResult::Success(0));
+        assert_eq!(
+            execute(&mut fwft_virtualizer, set(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID, 0, 0)),
+            FwftResult::Failure(SbiError::Denied)
+        );
+        assert_eq!(
+            execute(&mut fwft_virtualizer, set(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID, 2, 0)),
+            FwftResult::Failure(SbiError::InvalidParam)
+        );
+    }
+
+    #[test]
+    fn features_not_confined_to_hart_are_not_supported() {
+        let mut fwft_virtualizer = FwftVirtualizer::default();
+        for feature in FwftVirtualizer::LANDING_PAD_ID..=FwftVirtualizer::PTE_AD_HW_UPDATING_ID {
+            assert_eq!(execute(&mut fwft_virtualizer, get(feature)), FwftResult::Failure(SbiError::NotSupported));
+            assert_eq!(execute(&mut fwft_virtualizer, set(feature, 1, 0)), FwftResult::Failure(SbiError::NotSupported));
+        }
+        assert_eq!(execute(&mut fwft_virtualizer, get(0x6)), FwftResult::Failure(SbiError::InvalidParam));
+        assert_eq!(execute(&mut fwft_virtualizer, FwftRequest::Unsupported), FwftResult::Failure(SbiError::NotSupported));
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        let mut fwft_virtualizer = FwftVirtualizer::default();
+        let request = set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 16, 0b10);
+        assert_eq!(execute(&mut fwft_virtualizer, request), FwftResult::Failure(SbiError::InvalidParam));
+        assert_eq!(execute(&mut fwft_virtualizer, get(FwftVirtualizer::POINTER_MASKING_PMLEN_ID)), FwftResult::Success(0));
+    }
+
+    #[test]
+    fn shortest_implemented_pointer_masking_length_is_applied() {
+        let mut fwft_virtualizer = FwftVirtualizer::default();
+        // A length of 7 bits is not implemented, so the next longer one is applied.
+        assert_eq!(execute(&mut fwft_virtualizer, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 7, 0)), FwftResult::Success(0));
+        assert_eq!(execute(&mut fwft_virtualizer, get(FwftVirtualizer::POINTER_MASKING_PMLEN_ID)), FwftResult::Success(16));
+        let henvcfg = 0x1 | (0b10 << CSR_HENVCFG_PMM);
+        assert_eq!(fwft_virtualizer.henvcfg(henvcfg), 0x1 | (0b11 << CSR_HENVCFG_PMM));
+        assert_eq!(
+            execute(&mut fwft_virtualizer, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 17, 0)),
+            FwftResult::Failure(SbiError::InvalidParam)
+        );
+        assert_eq!(execute(&mut fwft_virtualizer, get(FwftVirtualizer::POINTER_MASKING_PMLEN_ID)), FwftResult::Success(16));
+        assert_eq!(execute(&mut fwft_virtualizer, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 0, 0)), FwftResult::Success(0));
+        assert_eq!(fwft_virtualizer.henvcfg(henvcfg), 0x1);
+    }
+
+    #[test]
+    fn features_are_configured_per_hart() {
+        let (mut first_hart, mut second_hart) = (FwftVirtualizer::default(), FwftVirtualizer::default());
+        let lock = FwftVirtualizer::LOCK_FLAG;
+        assert_eq!(execute(&mut first_hart, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 16, lock)), FwftResult::Success(0));
+        assert_eq!(execute(&mut second_hart, get(FwftVirtualizer::POINTER_MASKING_PMLEN_ID)), FwftResult::Success(0));
+        assert_eq!(execute(&mut second_hart, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 0, 0)), FwftResult::Success(0));
+        assert_eq!(second_hart.henvcfg(0b11 << CSR_HENVCFG_PMM), 0);
+    }
+
+    #[test]
+    fn locked_feature_cannot_be_set() {
+        let mut fwft_virtualizer = FwftVirtualizer::default();
+        let lock = FwftVirtualizer::LOCK_FLAG;
+        assert_eq!(execute(&mut fwft_virtualizer, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 16, lock)), FwftResult::Success(0));
+        assert_eq!(
+            execute(&mut fwft_virtualizer, set(FwftVirtualizer::POINTER_MASKING_PMLEN_ID, 0, 0)),
+            FwftResult::Failure(SbiError::DeniedLocked)
+        );
+        assert_eq!(execute(&mut fwft_virtualizer, get(FwftVirtualizer::POINTER_MASKING_PMLEN_ID)), FwftResult::Success(16));
+        // Other features are not locked.
+        assert_eq!(execute(&mut fwft_virtualizer, set(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID, 1, lock)), FwftResult::Success(0));
+        assert_eq!(
+            execute(&mut fwft_virtualizer, set(FwftVirtualizer::MISALIGNED_EXC_DELEG_ID, 1, 0)),
+            FwftResult::Failure(SbiError::DeniedLocked)
+        );
+    }
+}
@@ -3,18 +3,36 @@
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::architecture::specification::*;
 use crate::core::architecture::{
-    are_bits_enabled, disable_bit, enable_bit, GeneralPurposeRegister, HartArchitecturalState, TrapCause, CSR,
+    are_bits_enabled, disable_bit, enable_bit, transformed_instruction, GeneralPurposeRegister, HartArchitecturalState, SbiError,
+    TrapCause, CSR,
 };
-use crate::core::control_data::ConfidentialHart;
-use crate::core::memory_protector::HypervisorMemoryProtector;
+use crate::core::control_data::{ConfidentialHart, NaclSharedMemory, TraceBuffer, TraceEvent};
+use crate::core::entropy::EntropyPool;
+use crate::core::memory_protector::{ConfidentialVmMemoryProtector, HypervisorMemoryProtector, PageSize, Vmid};
 use crate::core::page_allocator::{Allocated, Page, UnAllocated};
 use crate::core::transformations::{
-    EnabledInterrupts, ExposeToHypervisor, GuestLoadPageFaultRequest, GuestLoadPageFaultResult, InjectedInterrupts, InterruptRequest,
-    MmioLoadRequest, MmioStoreRequest, OpensbiRequest, OpensbiResult, PromoteToConfidentialVm, ResumeRequest, SbiRequest, SbiResult,
-    SbiVmRequest, SharePageResult, TerminateRequest,
+    AccessedDirtyCountersRequest, AddHartRequest, AddMemoryRegionRequest, ConvertToConfidentialRequest, CppcRequest, CppcResult,
+    CreateConfidentialVmRequest, DbcnReadRequest, EnabledInterrupts, ExposeToHypervisor, FinalizeRequest, FwftRequest, FwftResult,
+    GetMemoryInfoRequest, GetVmMeasurementRequest, GetVmMemoryInfoRequest, GuestAccessFaultResult, GuestLoadPageFaultRequest,
+    GuestLoadPageFaultResult, GuestStorePageFaultRequest, GuestStorePageFaultResult, HartRunstateRequest, InjectedInterrupts,
+    InterruptRequest, MemoryConversionRequest, MemoryFaultNotification, MmioLoadRequest, MmioStoreRequest, NaclSharedMemoryRequest,
+    OpensbiRequest, OpensbiResult, PromoteToConfidentialVm, ReadRegisterRequest, ReclaimMemoryRequest, ReclaimToNonConfidentialRequest,
+    ResumeRequest, RotateMemoryKeyRequest, SbiPmuRequest, SbiRequest, SbiResult, SbiVmRequest, SecurityMonitorInfoRequest, SharePageResult,
+    SseRequest, SseResult, StealTimeRequest, TerminateRequest, TlbFenceCountersRequest, TraceBufferRequest, UnsharePageResult,
+    WriteRegisterRequest,
 };
+use crate::error::Error;
 
 pub const HART_STACK_ADDRESS_OFFSET: usize = memoffset::offset_of!(HardwareHart, stack_address);
+// The context switch loads the stack pointer from `HART_STACK_ADDRESS_OFFSET` bytes past the address in mscratch, which is the address
+// of `non_confidential_hart_state` (see `HardwareHart::address`). Both agree only if the hart state is the first field. The `ld`
+// instruction encodes the offset as a 12-bit signed immediate. Reordering the fields so that any of this breaks fails the build.
+const _: () = assert!(memoffset::offset_of!(HardwareHart, non_confidential_hart_state) == 0);
+const _: () = assert!(HART_STACK_ADDRESS_OFFSET % core::mem::size_of::<usize>() == 0 && HART_STACK_ADDRESS_OFFSET < 2048);
+/// The number of traps into the security monitor after which the hart replaces its stack canary with a fresh random value.
+const STACK_CANARY_ROTATION_INTERVAL: usize = 1024;
+/// The number of the most recent trace events retained by every hart. Encoded events of a full buffer fill exactly one 4KiB page.
+pub const TRACE_BUFFER_CAPACITY: usize = 128;
 
 #[repr(C)]
 pub struct HardwareHart {
@@ -29,29 +47,84 @@ pub struct HardwareHart {
     // The stack_address is redundant (we can learn the stack_address from the page assigned to the stack) but we need
     // it because this is the way to expose it to assembly
     pub(super) stack_address: usize,
+    // The value stored in the lowest word of the stack page. The stack grows downwards, so the canary is overwritten only when the
+    // stack overflows.
+    stack_canary: usize,
+    traps_since_stack_canary_rotation: usize,
     // We need to store the OpenSBI's mscratch value because OpenSBI uses mscratch to track some of its internal
     // data structures and our security monitor also uses mscratch to keep track of the address of the hart state
     // in memory.
     previous_mscratch: usize,
+    // The floating-point (FP) state is switched lazily between the hypervisor and a confidential hart. The flag is set when the
+    // FP registers of the physical hart hold the state of the confidential hart assigned to this hardware hart. Whether the confidential
+    // hart modified them is tracked by the hardware in the confidential hart's mstatus.FS.
+    is_fp_state_restored: bool,
+    // The FS and XS fields of the hypervisor's mstatus, recorded when the hypervisor resumed the confidential hart.
+    hypervisor_fs_xs_state: usize,
     // We keep the virtual hart that is associated with this hardware hart. The virtual hart can be 1) a dummy hart
     // in case there is any confidential VM's virtual hart associated to it, or 2) an confidential VM's virtual hart.
     // In the latter case, the hardware hart and confidential VM's control data swap their virtual harts (a dummy
     // hart with the confidential VM's virtual hart)
     pub(super) confidential_hart: ConfidentialHart,
+    // The memory shared with the hypervisor, registered via the SBI NACL extension. If not registered, the security monitor falls back
+    // to exchanging information with the hypervisor via CSRs.
+    nacl_shared_memory: Option<NaclSharedMemory>,
+    trace_buffer: TraceBuffer<TRACE_BUFFER_CAPACITY>,
 }
 
 impl HardwareHart {
+    // Used when the platform has no entropy source. A fixed canary still detects accidental stack overflows.
+    const FALLBACK_STACK_CANARY: usize = 0x5ec0_c0de_ca4a_f1e5;
+
     pub fn init(id: usize, stack: Page<UnAllocated>, hypervisor_memory_protector: HypervisorMemoryProtector) -> Self {
-        Self {
+        let mut hardware_hart = Self {
             non_confidential_hart_state: HartArchitecturalState::empty(id),
             hypervisor_memory_protector,
             stack_address: stack.end_address(),
             stack: stack.zeroize(),
+            stack_canary: 0,
+            traps_since_stack_canary_rotation: 0,
             previous_mscratch: 0,
+            is_fp_state_restored: false,
+            hypervisor_fs_xs_state: 0,
             confidential_hart: ConfidentialHart::dummy(id),
+            nacl_shared_memory: None,
+            trace_buffer: TraceBuffer::empty(),
+        };
+        hardware_hart.rotate_stack_canary();
+        hardware_hart
+    }
+
+    /// Checks the stack canary and rotates it every `STACK_CANARY_ROTATION_INTERVAL` traps. This function must be called whenever
+    /// the hart traps into the security monitor. Panics if the canary has been overwritten, because the stack overflowed and the
+    /// security monitor's state can no longer be trusted.
+    pub fn guard_stack(&mut self) {
+        let is_canary_intact = self.stack.read(0).is_ok_and(|canary| canary == self.stack_canary);
+        assert!(is_canary_intact, "Bug. Stack overflow detected on hart {}", self.non_confidential_hart_state.id);
+        self.traps_since_stack_canary_rotation += 1;
+        if self.traps_since_stack_canary_rotation >= STACK_CANARY_ROTATION_INTERVAL {
+            self.rotate_stack_canary();
         }
     }
 
+    /// Writes a fresh random canary to the lowest word of the stack page, so that a canary leaked in the past does not help to
+    /// overwrite the stack unnoticed. The current canary remains if the entropy source is not able to deliver a new one.
+    pub fn rotate_stack_canary(&mut self) {
+        let mut canary = [0u8; core::mem::size_of::<usize>()];
+        self.stack_canary = match EntropyPool::fill(&mut canary) {
+            Ok(_) => usize::from_le_bytes(canary),
+            Err(_) if self.stack_canary == 0 => Self::FALLBACK_STACK_CANARY,
+            Err(_) => self.stack_canary,
+        };
+        // The offset 0 is word-aligned and within the page, so the write never fails.
+        let _ = self.stack.write(0, self.stack_canary);
+        self.traps_since_stack_canary_rotation = 0;
+    }
+
+    pub fn hart_id(&self) -> usize {
+        self.non_confidential_hart_state.id
+    }
+
     pub fn address(&self) -> usize {
         core::ptr::addr_of!(self.non_confidential_hart_state) as usize
     }
@@ -65,6 +138,21 @@ impl HardwareHart {
         self.previous_mscratch = current_mscratch;
     }
 
+    /// Checks that the trap delegation configured in `medeleg` and `mideleg` lets the security monitor observe all traps it relies on
+    /// while the hypervisor executes. Environment calls from HS- and VS-mode carry security monitor calls, and machine-level interrupts
+    /// deliver inter-hart requests and timer interrupts to the security monitor. Returns error with the cause of the first trap that
+    /// is delegated to a lower privilege level.
+    pub fn validate_trap_delegation(&self) -> Result<(), Error> {
+        let medeleg = CSR.medeleg.read();
+        [CAUSE_SUPERVISOR_ECALL, CAUSE_VIRTUAL_SUPERVISOR_ECALL, CAUSE_MACHINE_ECALL]
+            .iter()
+            .try_for_each(|cause| assure!(medeleg & (1 << *cause) == 0, Error::InvalidTrapDelegation(usize::from(*cause))))?;
+        let mideleg = CSR.mideleg.read();
+        [MIE_MSIP, MIE_MTIP, MIE_MEIP].iter().try_for_each(|interrupt| {
+            assure!(mideleg & (1 << *interrupt) == 0, Error::InvalidTrapDelegation((1 << CAUSE_INTERRUPT_BIT) | *interrupt))
+        })
+    }
+
     pub fn confidential_hart(&self) -> &ConfidentialHart {
         &self.confidential_hart
     }
@@ -73,25 +161,137 @@ impl HardwareHart {
         &mut self.confidential_hart
     }
 
-    pub unsafe fn enable_hypervisor_memory_protector(&self) {
-        self.hypervisor_memory_protector.enable(self.non_confidential_hart_state.hgatp)
+    /// Returns true if the floating-point registers of the physical hart hold the state of the assigned confidential hart.
+    pub fn is_fp_state_restored(&self) -> bool {
+        self.is_fp_state_restored
+    }
+
+    /// Restores the floating-point (FP) state of the confidential hart assigned to this hardware hart. The FP state of the hypervisor is
+    /// stored in the main memory before being overwritten.
+    ///
+    /// A confidential hart is always scheduled with the FP unit disabled (mstatus.FS=Off), so its first FP instruction traps in the
+    /// security monitor. Only then we restore its FP state and set mstatus.FS to Clean so that subsequent FP instructions execute
+    /// natively. Confidential VMs that do not use FP never pay the cost of switching 32 FP registers.
+    pub fn restore_fp_state(&mut self) {
+        if self.is_fp_state_restored {
+            return;
+        }
+        // The security monitor must enable the FP unit for itself to access FP registers.
+        CSR.mstatus.read_and_set_bits(CSR_MSTATUS_FS_DIRTY);
+        self.non_confidential_hart_state.store_floating_point_registers_in_main_memory();
+        self.confidential_hart.load_floating_point_state();
+        self.is_fp_state_restored = true;
+    }
+
+    /// Saves the floating-point (FP) state of the confidential hart assigned to this hardware hart and restores the FP state of the
+    /// hypervisor. This function must be called before the confidential hart is unassigned from the hardware hart. It is a no-op if the
+    /// confidential hart has not used the FP unit since it was scheduled. The FP registers of the confidential hart are stored only if
+    /// it modified them.
+    pub fn save_fp_state(&mut self) {
+        if !self.is_fp_state_restored {
+            return;
+        }
+        CSR.mstatus.read_and_set_bits(CSR_MSTATUS_FS_DIRTY);
+        self.confidential_hart.store_floating_point_state();
+        self.non_confidential_hart_state.load_floating_point_registers_from_main_memory();
+        self.is_fp_state_restored = false;
+    }
+
+    /// Handles the call of the SBI PMU extension made by the confidential hart assigned to this hardware hart. The call is never
+    /// forwarded to the hypervisor: the confidential hart only observes virtual counters that count its own execution.
+    pub fn handle_sbi_pmu(&mut self, request: SbiPmuRequest) -> Result<usize, Error> {
+        self.confidential_hart.pmu_virtualizer_mut().handle(request)
+    }
+
+    /// Handles the call of the SBI CPPC extension made by the confidential hart assigned to this hardware hart. Performance hints stay
+    /// in the security monitor, so the hypervisor learns neither that the call was made nor the requested performance.
+    pub fn handle_sbi_cppc(&mut self, request: CppcRequest) -> CppcResult {
+        self.confidential_hart.cppc_virtualizer_mut().handle(request)
+    }
+
+    /// Handles the call of the SBI FWFT extension made by the confidential hart assigned to this hardware hart. Features are
+    /// configured only for the confidential hart, so the hypervisor's own configuration of the physical hart is not affected.
+    pub fn handle_sbi_fwft(&mut self, request: FwftRequest) -> FwftResult {
+        self.confidential_hart.fwft_virtualizer_mut().handle(request)
+    }
+
+    pub fn handle_sbi_dbcn_read(
+        &mut self, request: DbcnReadRequest, memory_protector: &mut ConfidentialVmMemoryProtector,
+    ) -> Result<usize, Error> {
+        self.confidential_hart.handle_sbi_dbcn_read(request, memory_protector)
+    }
+
+    pub fn handle_sbi_sse(&mut self, request: SseRequest) -> Result<SseResult, Error> {
+        self.confidential_hart.handle_ecall_sse(request)
+    }
+
+    pub fn handle_sbi_steal_time(
+        &mut self, request: StealTimeRequest, memory_protector: &mut ConfidentialVmMemoryProtector,
+    ) -> Result<(), Error> {
+        self.confidential_hart.set_steal_time(request, memory_protector)
+    }
+
+    pub fn set_nacl_shared_memory(&mut self, nacl_shared_memory: Option<NaclSharedMemory>) {
+        self.nacl_shared_memory = nacl_shared_memory;
+    }
+
+    /// Records the event in the trace buffer of this hart. The oldest event is overwritten when the buffer is full.
+    pub fn emit_trace_event(&mut self, event: TraceEvent) {
+        self.trace_buffer.record(event);
+    }
+
+    pub fn trace_buffer(&self) -> &TraceBuffer<TRACE_BUFFER_CAPACITY> {
+        &self.trace_buffer
+    }
+
+    #[cfg(feature = "pmp_audit_log")]
+    pub fn hypervisor_memory_protector(&self) -> &HypervisorMemoryProtector {
+        &self.hypervisor_memory_protector
+    }
+
+    pub fn hypervisor_memory_protector_mut(&mut self) -> &mut HypervisorMemoryProtector {
+        &mut self.hypervisor_memory_protector
+    }
+
+    /// Restores the hypervisor's memory isolation. The VMID of the confidential VM that has just been executing on this hart is needed
+    /// to fence its translations before the hypervisor's address translation is loaded.
+    pub unsafe fn enable_hypervisor_memory_protector(&mut self, confidential_vm_vmid: Option<&Vmid>) -> Result<(), Error> {
+        self.hypervisor_memory_protector.enable(self.non_confidential_hart_state.hgatp, confidential_vm_vmid)
     }
 
     /// Dumps control and status registers (CSRs) of the physical hart executing this code to the main memory.
     pub fn store_control_status_registers_in_main_memory(&mut self) -> InjectedInterrupts {
         self.non_confidential_hart_state.store_control_status_registers_in_main_memory();
+        self.store_hypervisor_fs_xs_state();
         // TODO: when moving to CoVE, injecting interrupts becomes an explicit request from the hypervisor to security monitor. We should
         // adapt the same strategy, which would also better reflect out current approach for information declassification.
         self.interrupts_to_inject()
     }
 
+    /// Records the FS and XS fields of the hypervisor's mstatus and turns the FP unit off. The security monitor enables the FP unit
+    /// only when it switches the FP state, which marks the FP state dirty in mstatus. The recorded fields are restored when the hypervisor
+    /// resumes, so its context switch code saves and restores FP and extension state only if the hypervisor itself made it dirty. XS
+    /// is read-only, the hardware derives it from the state of the other extensions, so it is only recorded. Whether the recorded state
+    /// is dirty is also exported to the hypervisor through the NACL shared memory, if registered.
+    pub fn store_hypervisor_fs_xs_state(&mut self) {
+        let mstatus = CSR.mstatus.read_and_clear_bits(CSR_MSTATUS_FS_MASK);
+        self.hypervisor_fs_xs_state = mstatus & (CSR_MSTATUS_FS_MASK | CSR_MSTATUS_XS_MASK);
+        if let Some(memory) = self.nacl_shared_memory.as_ref() {
+            let _ = memory.set_fp_state_status(self.hypervisor_fs_xs_state);
+        }
+    }
+
     pub fn store_volatile_control_status_registers_in_main_memory(&mut self) {
         self.non_confidential_hart_state.mepc = CSR.mepc.read();
         self.non_confidential_hart_state.mstatus = CSR.mstatus.read();
+        self.non_confidential_hart_state.hgatp = CSR.hgatp.read();
     }
 
     /// Loads control and status registers (CSRs) from the main memory into the physical hart executing this code.
     pub fn load_control_status_registers_from_main_memory(&mut self, enabled_interrupts: EnabledInterrupts) {
+        // The FP unit might have been used on behalf of the confidential hart, see `store_hypervisor_fs_xs_state`.
+        let mstatus = &mut self.non_confidential_hart_state.mstatus;
+        *mstatus = (*mstatus & !(CSR_MSTATUS_FS_MASK | CSR_MSTATUS_XS_MASK)) | self.hypervisor_fs_xs_state;
         self.non_confidential_hart_state.load_control_status_registers_from_main_memory();
         // TODO: when moving to CoVE, exposing enabled interrupts becomes an explicit hypercall. We should adapt the same strategy, which
         // would also better reflect out current approach for information declassification.
@@ -104,6 +304,22 @@ impl HardwareHart {
         CSR.mepc.set(self.non_confidential_hart_state.mepc);
         CSR.mstatus.set(self.non_confidential_hart_state.mstatus);
     }
+
+    /// Returns error if mstatus, mepc, or hgatp of the physical hart diverge from the hypervisor's state stored in the main memory,
+    /// which indicates that the security monitor corrupted them while handling the request. Must be called after
+    /// `load_volatile_control_status_registers_from_main_memory`. The SD bit of mstatus is ignored because the hardware derives it from
+    /// the FS, VS, and XS fields.
+    pub fn check_csrs_consistency(&self) -> Result<(), Error> {
+        let state = &self.non_confidential_hart_state;
+        let sd_mask = 1 << CSR_MSTATUS_SD;
+        [
+            (CSR_MSTATUS, state.mstatus & !sd_mask, CSR.mstatus.read() & !sd_mask),
+            (CSR_MEPC, state.mepc, CSR.mepc.read()),
+            (CSR_HGATP, state.hgatp, CSR.hgatp.read()),
+        ]
+        .into_iter()
+        .try_for_each(|(csr, expected, found)| assure!(expected == found, Error::InconsistentCsr(csr, expected, found)))
+    }
 }
 
 impl HardwareHart {
@@ -117,27 +333,53 @@ impl HardwareHart {
             ExposeToHypervisor::MmioStoreRequest(v) => self.apply_mmio_store_request(v),
             ExposeToHypervisor::InterruptRequest(v) => self.apply_interrupt_request(v),
             ExposeToHypervisor::EnabledInterrupts(v) => self.apply_enabled_interrupts(v),
+            ExposeToHypervisor::MemoryFaultNotification(v) => self.apply_memory_fault_notification(v),
         }
     }
 
     fn apply_enabled_interrupts(&mut self, result: &EnabledInterrupts) {
+        log_declassification!(EnabledInterrupts, Csr(CSR_VSIE), InterruptMask);
         CSR.vsie.set(result.vsie);
     }
 
     fn apply_sbi_result(&mut self, result: &SbiResult) {
+        log_declassification!(SbiResult, Gpr(GeneralPurposeRegister::a0), SbiReturnValue);
+        log_declassification!(SbiResult, Gpr(GeneralPurposeRegister::a1), SbiReturnValue);
+        for (gpr, _) in result.extended_values() {
+            log_declassification!(SbiResult, Gpr(gpr), SbiReturnValue);
+        }
+        self.write_sbi_result(result);
+    }
+
+    fn write_sbi_result(&mut self, result: &SbiResult) {
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, result.a0());
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, result.a1());
+        result.extended_values().for_each(|(gpr, value)| self.non_confidential_hart_state.set_gpr(gpr, value));
         self.non_confidential_hart_state.mepc += result.pc_offset();
     }
 
     fn apply_opensbi_result(&mut self, result: &OpensbiResult) {
-        self.non_confidential_hart_state.mstatus = result.trap_regs.mstatus.try_into().unwrap();
-        self.non_confidential_hart_state.mepc = result.trap_regs.mepc.try_into().unwrap();
-        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, result.trap_regs.a0.try_into().unwrap());
-        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, result.trap_regs.a1.try_into().unwrap());
+        self.non_confidential_hart_state.mstatus = result.mstatus();
+        self.non_confidential_hart_state.mepc = result.mepc();
+        let sbi_result = SbiResult::from_opensbi_retval(result.a0(), result.a1()).unwrap_or_else(|error| {
+            // The hypervisor made the call, thus it receives the error and decides whether to retry the call or give up.
+            debug!("OpenSBI returned an error: {:?}", error);
+            SbiResult::from_opensbi_error(error)
+        });
+        // The result comes from the firmware, not from a confidential VM, thus there is nothing declassified to log.
+        self.write_sbi_result(&sbi_result);
     }
 
     fn apply_sbi_vm_request(&mut self, request: &SbiVmRequest) {
+        log_declassification!(SbiVmRequest, Csr(CSR_SCAUSE), TrapCause);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a7), SbiCallIdentifier);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a6), SbiCallIdentifier);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a0), SbiArgument);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a1), SbiArgument);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a2), SbiArgument);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a3), SbiArgument);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a4), SbiArgument);
+        log_declassification!(SbiVmRequest, Gpr(GeneralPurposeRegister::a5), SbiArgument);
         CSR.scause.set(CAUSE_VIRTUAL_SUPERVISOR_ECALL.into());
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a7, request.sbi_request().extension_id());
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, request.sbi_request().function_id());
@@ -151,6 +393,15 @@ impl HardwareHart {
     }
 
     fn apply_sbi_request(&mut self, request: &SbiRequest) {
+        log_declassification!(SbiRequest, Csr(CSR_SCAUSE), TrapCause);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a7), SbiCallIdentifier);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a6), SbiCallIdentifier);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a0), SbiArgument);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a1), SbiArgument);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a2), SbiArgument);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a3), SbiArgument);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a4), SbiArgument);
+        log_declassification!(SbiRequest, Gpr(GeneralPurposeRegister::a5), SbiArgument);
         CSR.scause.set(CAUSE_VIRTUAL_SUPERVISOR_ECALL.into());
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a7, request.extension_id());
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, request.function_id());
@@ -163,30 +414,82 @@ impl HardwareHart {
         self.apply_trap(false);
     }
 
+    /// Exposes the notification as a call of the KVM ACE extension, like other requests that the security monitor makes to the
+    /// hypervisor on behalf of the confidential hart. Only the fault address and the number of required pages are declassified.
+    fn apply_memory_fault_notification(&mut self, notification: &MemoryFaultNotification) {
+        log_declassification!(MemoryFaultNotification, Csr(CSR_SCAUSE), TrapCause);
+        log_declassification!(MemoryFaultNotification, Gpr(GeneralPurposeRegister::a7), SbiCallIdentifier);
+        log_declassification!(MemoryFaultNotification, Gpr(GeneralPurposeRegister::a6), SbiCallIdentifier);
+        log_declassification!(MemoryFaultNotification, Gpr(GeneralPurposeRegister::a0), FaultAddress);
+        log_declassification!(MemoryFaultNotification, Gpr(GeneralPurposeRegister::a1), SbiArgument);
+        let request = SbiRequest::kvm_ace_memory_fault(notification.failed_address(), notification.required_pages());
+        CSR.scause.set(CAUSE_VIRTUAL_SUPERVISOR_ECALL.into());
+        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a7, request.extension_id());
+        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, request.function_id());
+        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a0, request.a0());
+        self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a1, request.a1());
+        self.apply_trap(false);
+    }
+
     fn apply_mmio_load_request(&mut self, request: &MmioLoadRequest) {
+        self.emit_trace_event(TraceEvent::MmioFault { address: request.fault_address(), is_store: false });
+        log_declassification!(MmioLoadRequest, Csr(CSR_SCAUSE), TrapCause);
+        log_declassification!(MmioLoadRequest, Csr(CSR_STVAL), FaultAddress);
+        log_declassification!(MmioLoadRequest, Csr(CSR_HTVAL), FaultAddress);
         CSR.scause.set(request.code());
         // KVM uses htval and stval to recreate the fault address
         CSR.stval.set(request.stval());
         CSR.htval.set(request.htval());
-        // Hack: we do not allow the hypervisor to look into the guest memory but we have to inform him about the instruction that caused
-        // exception. our approach is to expose this instruction via vsscratch. In future, we should move to RISC-V NACL extensions.
-        CSR.vsscratch.set(request.instruction());
+        log_declassification!(MmioLoadRequest, Csr(CSR_HTINST), FaultingInstruction);
+        #[cfg(feature = "declassification_log")]
+        if self.nacl_shared_memory.is_none() {
+            log_declassification!(MmioLoadRequest, Csr(CSR_VSSCRATCH), FaultingInstruction);
+        }
+        self.expose_faulting_instruction(request.instruction());
+        self.clear_guest_access_status();
         self.apply_trap(true);
     }
 
     fn apply_mmio_store_request(&mut self, request: &MmioStoreRequest) {
+        self.emit_trace_event(TraceEvent::MmioFault { address: request.fault_address(), is_store: true });
+        log_declassification!(MmioStoreRequest, Csr(CSR_SCAUSE), TrapCause);
+        log_declassification!(MmioStoreRequest, Csr(CSR_STVAL), FaultAddress);
+        log_declassification!(MmioStoreRequest, Csr(CSR_HTVAL), FaultAddress);
+        log_declassification!(MmioStoreRequest, Gpr(request.gpr()), StoreValue);
         CSR.scause.set(request.code());
         // KVM uses htval and stval to recreate the fault address
         CSR.stval.set(request.stval());
         CSR.htval.set(request.htval());
-        self.non_confidential_hart_state.set_gpr(request.gpr(), request.gpr_value());
-        // Hack: we do not allow the hypervisor to look into the guest memory but we have to inform him about the instruction that caused
-        // exception. our approach is to expose this instruction via vsscratch. In future, we should move to RISC-V NACL extensions.
-        CSR.vsscratch.set(request.instruction());
+        // The value to store is exposed in the NACL shared memory, in the slot of the register that holds it. Without the shared memory, we
+        // expose it in the hypervisor's register.
+        if !self.nacl_shared_memory.as_ref().is_some_and(|memory| memory.set_gpr(request.gpr(), request.gpr_value()).is_ok()) {
+            self.non_confidential_hart_state.set_gpr(request.gpr(), request.gpr_value());
+        }
+        log_declassification!(MmioStoreRequest, Csr(CSR_HTINST), FaultingInstruction);
+        #[cfg(feature = "declassification_log")]
+        if self.nacl_shared_memory.is_none() {
+            log_declassification!(MmioStoreRequest, Csr(CSR_VSSCRATCH), FaultingInstruction);
+        }
+        self.expose_faulting_instruction(request.instruction());
+        self.clear_guest_access_status();
         self.apply_trap(true);
     }
 
+    /// Informs the hypervisor about the instruction that caused the MMIO exception, because we do not allow the hypervisor to look into the
+    /// guest memory. Like the hardware does for guest-page faults, we write the transformed instruction to `htinst`, so that a hypervisor
+    /// following the H-extension can emulate the access. The same value is exposed in the `htinst` slot of the NACL shared memory. If the
+    /// hypervisor has not registered the shared memory, we additionally expose the instruction via vsscratch for hypervisors that predate
+    /// the `htinst` support.
+    fn expose_faulting_instruction(&mut self, instruction: usize) {
+        let htinst = transformed_instruction(instruction);
+        CSR.htinst.set(htinst);
+        if !self.nacl_shared_memory.as_ref().is_some_and(|memory| memory.set_csr(CSR_HTINST, htinst).is_ok()) {
+            CSR.vsscratch.set(instruction);
+        }
+    }
+
     fn apply_interrupt_request(&mut self, request: &InterruptRequest) {
+        log_declassification!(InterruptRequest, Csr(CSR_SCAUSE), TrapCause);
         CSR.scause.set(request.code() | SCAUSE_INTERRUPT_MASK);
         self.apply_trap(false);
     }
@@ -214,10 +517,13 @@ impl HardwareHart {
         // According to the spec, hstatus:SPVP and sstatus.SPP have the same value when transitioning from VS to HS mode.
         CSR.sstatus.read_and_set_bit(CSR_SSTATUS_SPP);
 
+        // Only traps caused by guest memory accesses carry the trapped instruction. Otherwise, `htinst` is zero like for ecalls and
+        // interrupts, so that the hypervisor never decodes an instruction of a previous trap.
         if encoded_guest_virtual_address {
             CSR.hstatus.read_and_set_bit(CSR_HSTATUS_GVA);
         } else {
             CSR.hstatus.read_and_clear_bit(CSR_HSTATUS_GVA);
+            CSR.htinst.set(0);
         }
     }
 }
@@ -229,6 +535,10 @@ impl HardwareHart {
         let extension_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a7);
         let function_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a6);
         let trap_reason = TrapCause::from(cause, extension_id, function_id);
+        self.emit_trace_event(match trap_reason {
+            TrapCause::HsEcall(_) | TrapCause::VsEcall(_) => TraceEvent::SbiCall { extension_id, function_id },
+            _ => TraceEvent::TrapEntry { cause },
+        });
 
         // `ecall` from the hypervisor carry additional information that must be restored.
         match trap_reason {
@@ -246,8 +556,24 @@ impl HardwareHart {
         SbiResult::ecall(&self.non_confidential_hart_state)
     }
 
-    pub fn guest_load_page_fault_result(&self, request: GuestLoadPageFaultRequest) -> GuestLoadPageFaultResult {
-        GuestLoadPageFaultResult::new(&self.non_confidential_hart_state, request)
+    pub fn guest_load_page_fault_result(
+        &self, request: GuestLoadPageFaultRequest,
+    ) -> Result<GuestLoadPageFaultResult, GuestAccessFaultResult> {
+        assure_not!(self.is_guest_access_denied(), GuestAccessFaultResult::load_denied(&request))?;
+        // The hypervisor returns the loaded value in the NACL shared memory if registered, otherwise in its register.
+        let value = self
+            .nacl_shared_memory
+            .as_ref()
+            .and_then(|memory| memory.gpr(request.result_gpr()).ok())
+            .unwrap_or_else(|| self.non_confidential_hart_state.gpr(request.result_gpr()));
+        Ok(GuestLoadPageFaultResult::new(value, request))
+    }
+
+    pub fn guest_store_page_fault_result(
+        &self, request: GuestStorePageFaultRequest,
+    ) -> Result<GuestStorePageFaultResult, GuestAccessFaultResult> {
+        assure_not!(self.is_guest_access_denied(), GuestAccessFaultResult::store_denied(&request))?;
+        Ok(GuestStorePageFaultResult::new(request))
     }
 
     pub fn sbi_vm_request(&self) -> SbiVmRequest {
@@ -259,17 +585,221 @@ impl HardwareHart {
         ResumeRequest::new(confidential_vm_id, confidential_hart_id)
     }
 
+    pub fn hart_runstate_request(&self) -> HartRunstateRequest {
+        let (confidential_vm_id, confidential_hart_id) = self.read_security_monitor_call_arguments();
+        HartRunstateRequest::new(confidential_vm_id, confidential_hart_id)
+    }
+
+    pub fn create_confidential_vm_request(&self) -> CreateConfidentialVmRequest {
+        CreateConfidentialVmRequest::new(
+            self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0),
+            self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1),
+        )
+    }
+
+    pub fn add_memory_region_request(&self) -> AddMemoryRegionRequest {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        let address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let source_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let number_of_pages = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        AddMemoryRegionRequest::new(confidential_vm_id, address, source_address, number_of_pages)
+    }
+
+    pub fn add_hart_request(&self) -> AddHartRequest {
+        let (confidential_vm_id, confidential_hart_id) = self.read_security_monitor_call_arguments();
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let opaque = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        AddHartRequest::new(confidential_vm_id, confidential_hart_id, start_address, opaque)
+    }
+
+    pub fn finalize_request(&self) -> Result<FinalizeRequest, Error> {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        let expected_launch_digest_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        FinalizeRequest::new(confidential_vm_id, expected_launch_digest_address)
+    }
+
     pub fn terminate_request(&self) -> TerminateRequest {
         let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
         TerminateRequest::new(confidential_vm_id)
     }
 
+    pub fn rotate_memory_key_request(&self) -> RotateMemoryKeyRequest {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        RotateMemoryKeyRequest::new(confidential_vm_id)
+    }
+
+    pub fn get_vm_measurement_request(&self) -> GetVmMeasurementRequest {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        let index = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        GetVmMeasurementRequest::new(confidential_vm_id, index, buffer_address)
+    }
+
+    pub fn get_vm_memory_info_request(&self) -> GetVmMemoryInfoRequest {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        GetVmMemoryInfoRequest::new(confidential_vm_id, buffer_address)
+    }
+
+    pub fn reclaim_memory_request(&self) -> ReclaimMemoryRequest {
+        let (confidential_vm_id, _) = self.read_security_monitor_call_arguments();
+        let max_number_of_pages = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        ReclaimMemoryRequest::new(confidential_vm_id, max_number_of_pages)
+    }
+
+    /// Builds the request to convert pages from the arguments of the CoVE `convert_pages` call: the base address and the number of
+    /// 4KiB pages.
+    pub fn convert_pages_request(&self) -> ConvertToConfidentialRequest {
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let number_of_pages = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        ConvertToConfidentialRequest::new(start_address, Self::pages_to_bytes(number_of_pages))
+    }
+
+    pub fn reclaim_pages_request(&self) -> ReclaimToNonConfidentialRequest {
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let number_of_pages = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        ReclaimToNonConfidentialRequest::new(start_address, Self::pages_to_bytes(number_of_pages))
+    }
+
+    /// The parameters of the CoVE `create_tvm` call describe pages donated for the confidential VM's control structures. The security
+    /// monitor allocates these structures from the confidential memory itself, so the parameters are ignored.
+    pub fn create_tvm_request(&self) -> CreateConfidentialVmRequest {
+        CreateConfidentialVmRequest::new(0, 0)
+    }
+
+    pub fn finalize_tvm_request(&self) -> Result<FinalizeRequest, Error> {
+        let confidential_vm_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let opaque = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        Ok(FinalizeRequest::new(confidential_vm_id, 0)?.with_boot_hart_entry(start_address, opaque))
+    }
+
+    pub fn destroy_tvm_request(&self) -> TerminateRequest {
+        TerminateRequest::new(self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0))
+    }
+
+    /// Builds the request to add memory from the arguments of the CoVE `add_tvm_measured_pages` call. The destination pages chosen by
+    /// the hypervisor are ignored because the security monitor allocates the confidential memory itself. The page type selects the
+    /// size of pages: 0 for 4KiB, 1 for 2MiB, and 2 for 1GiB. An invalid page type or number of pages yields a region that is too
+    /// large to be added.
+    pub fn add_tvm_measured_pages_request(&self) -> AddMemoryRegionRequest {
+        let confidential_vm_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let source_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let page_type = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a3);
+        let number_of_pages = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a4);
+        let address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a5);
+        let number_of_4kib_pages = [PageSize::Size4KiB, PageSize::Size2MiB, PageSize::Size1GiB]
+            .get(page_type)
+            .and_then(|page_size| number_of_pages.checked_mul(page_size.in_bytes() / PageSize::Size4KiB.in_bytes()))
+            .unwrap_or(usize::MAX);
+        AddMemoryRegionRequest::new(confidential_vm_id, address, source_address, number_of_4kib_pages)
+    }
+
+    /// The CoVE `create_tvm_vcpu` call does not define the entry of the confidential hart, so the hart is added in the `Stopped`
+    /// state. The boot hart's entry is provided when the confidential VM is finalized.
+    pub fn create_tvm_vcpu_request(&self) -> AddHartRequest {
+        let confidential_vm_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let confidential_hart_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        AddHartRequest::new(confidential_vm_id, confidential_hart_id, 0, 0)
+    }
+
+    pub fn run_tvm_vcpu_request(&self) -> ResumeRequest {
+        let confidential_vm_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let confidential_hart_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        ResumeRequest::new(confidential_vm_id, confidential_hart_id)
+    }
+
+    fn pages_to_bytes(number_of_pages: usize) -> usize {
+        // An overflowing size is turned into an invalid one, which the memory conversion rejects.
+        number_of_pages.checked_mul(PageSize::Size4KiB.in_bytes()).unwrap_or(usize::MAX)
+    }
+
+    pub fn get_memory_info_request(&self) -> GetMemoryInfoRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        GetMemoryInfoRequest::new(buffer_address)
+    }
+
+    pub fn security_monitor_info_request(&self) -> SecurityMonitorInfoRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        SecurityMonitorInfoRequest::new(buffer_address)
+    }
+
+    pub fn accessed_dirty_counters_request(&self) -> AccessedDirtyCountersRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        AccessedDirtyCountersRequest::new(buffer_address)
+    }
+
+    pub fn tlb_fence_counters_request(&self) -> TlbFenceCountersRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        TlbFenceCountersRequest::new(buffer_address)
+    }
+
+    pub fn trace_buffer_request(&self) -> TraceBufferRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        TraceBufferRequest::new(buffer_address)
+    }
+
+    pub fn convert_to_confidential_request(&self) -> ConvertToConfidentialRequest {
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let size_in_bytes = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        ConvertToConfidentialRequest::new(start_address, size_in_bytes)
+    }
+
+    pub fn reclaim_to_non_confidential_request(&self) -> ReclaimToNonConfidentialRequest {
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let size_in_bytes = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        ReclaimToNonConfidentialRequest::new(start_address, size_in_bytes)
+    }
+
+    pub fn memory_conversion_request(&self) -> MemoryConversionRequest {
+        let start_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let size_in_bytes = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        MemoryConversionRequest::new(start_address, size_in_bytes)
+    }
+
+    #[cfg(feature = "pmp_audit_log")]
+    pub fn pmp_audit_log_request(&self) -> crate::core::transformations::PmpAuditLogRequest {
+        let buffer_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        crate::core::transformations::PmpAuditLogRequest::new(buffer_address)
+    }
+
+    #[cfg(feature = "declassification_log")]
+    pub fn declassification_log_request(&self) -> crate::core::transformations::DeclassificationLogRequest {
+        let index = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        crate::core::transformations::DeclassificationLogRequest::new(index)
+    }
+
+    pub fn nacl_shared_memory_request(&self) -> NaclSharedMemoryRequest {
+        let address_lo = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let address_hi = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        let flags = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a2);
+        NaclSharedMemoryRequest::new(address_lo, address_hi, flags)
+    }
+
+    pub fn read_register_request(&self) -> ReadRegisterRequest {
+        let (confidential_vm_id, confidential_hart_id) = self.read_security_monitor_call_arguments();
+        let register_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        ReadRegisterRequest::new(confidential_vm_id, confidential_hart_id, register_id)
+    }
+
+    pub fn write_register_request(&self) -> WriteRegisterRequest {
+        let (confidential_vm_id, confidential_hart_id) = self.read_security_monitor_call_arguments();
+        let register_id = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        let value = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
+        WriteRegisterRequest::new(confidential_vm_id, confidential_hart_id, register_id, value)
+    }
+
     pub fn share_page_result(&self) -> SharePageResult {
         let is_error = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
         let hypervisor_page_address = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a1);
         SharePageResult::new(is_error, hypervisor_page_address)
     }
 
+    pub fn unshare_page_result(&self) -> UnsharePageResult {
+        let response_code = self.non_confidential_hart_state.gpr(GeneralPurposeRegister::a0);
+        UnsharePageResult::new(response_code)
+    }
+
     pub fn opensbi_request(&self) -> OpensbiRequest {
         OpensbiRequest::new(&self.non_confidential_hart_state)
     }
@@ -288,11 +818,30 @@ impl HardwareHart {
         self.non_confidential_hart_state.set_gpr(GeneralPurposeRegister::a6, CSR.vsepc.read());
     }
 
+    /// Returns true if the hypervisor refused to emulate the MMIO access of the confidential hart. The hypervisor reports it by writing
+    /// the SBI `denied` error code to the NACL shared memory, if registered, or to the `vstval` CSR. We compare against this exact
+    /// value, so that a stale value left in `vstval` by a previous execution is unlikely to be misinterpreted as a denial.
+    fn is_guest_access_denied(&self) -> bool {
+        let status = match self.nacl_shared_memory.as_ref().map(|memory| memory.guest_access_status()) {
+            Some(Ok(status)) => status,
+            _ => CSR.vstval.read(),
+        };
+        status == SbiError::Denied.code()
+    }
+
+    fn clear_guest_access_status(&self) {
+        if let Some(memory) = self.nacl_shared_memory.as_ref() {
+            let _ = memory.clear_guest_access_status();
+        }
+    }
+
     fn read_security_monitor_call_arguments(&self) -> (usize, usize) {
-        // Arguments to security monitor calls are stored in vs* CSRs because we cannot use regular general purpose registers (GRPs). GRPs
-        // might carry SBI- or MMIO-related reponses, so using GRPs would destroy the communication between the hypervisor and confidential
-        // VM. This is a hackish (temporal?) solution, we should probably move to the RISC-V NACL extension that solves these problems by
-        // using shared memory region in which the SBI- and MMIO-related information is transfered.
+        if let Some(Ok(arguments)) = self.nacl_shared_memory.as_ref().map(|memory| memory.security_monitor_call_arguments()) {
+            return arguments;
+        }
+        // Without the NACL shared memory, arguments to security monitor calls are stored in vs* CSRs because we cannot use regular
+        // general purpose registers (GRPs). GRPs might carry SBI- or MMIO-related reponses, so using GRPs would destroy the communication
+        // between the hypervisor and confidential VM.
         (CSR.vstvec.read(), CSR.vsscratch.read())
     }
 }
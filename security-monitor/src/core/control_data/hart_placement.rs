@@ -0,0 +1,76 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+use alloc::vec::Vec;
+
+/// Maps confidential harts of a confidential VM to the hardware harts currently executing them. Cross-hart operations (e.g., IPIs,
+/// fences, pausing the confidential VM) use it to find the hardware hart they must interrupt.
+///
+/// The table is owned by the confidential VM and updated while the confidential VM is locked, in the same critical section in which
+/// the confidential hart is swapped with the dummy hart. Thus, the placement of a confidential hart never differs from the actual
+/// assignment as observed by other hardware harts.
+pub struct HartPlacement {
+    hardware_hart_ids: Vec<Option<usize>>,
+}
+
+impl HartPlacement {
+    pub fn new(number_of_confidential_harts: usize) -> Self {
+        Self { hardware_hart_ids: (0..number_of_confidential_harts).map(|_| None).collect() }
+    }
+
+    /// Records that the hardware hart executes the confidential hart. Returns error if the confidential hart does not exist or is
+    /// already placed on a hardware hart.
+    pub fn place(&mut self, confidential_hart_id: usize, hardware_hart_id: usize) -> Result<(), Error> {
+        let placement = self.hardware_hart_ids.get_mut(confidential_hart_id).ok_or(Error::InvalidHartId())?;
+        assure!(placement.is_none(), Error::HartAlreadyRunning())?;
+        *placement = Some(hardware_hart_id);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, confidential_hart_id: usize) {
+        self.hardware_hart_ids[confidential_hart_id] = None;
+    }
+
+    /// Returns the id of the hardware hart executing the confidential hart or `None` if the confidential hart is not scheduled or
+    /// does not exist.
+    pub fn hardware_hart_id(&self, confidential_hart_id: usize) -> Option<usize> {
+        self.hardware_hart_ids.get(confidential_hart_id).copied().flatten()
+    }
+
+    /// Returns ids of all hardware harts executing confidential harts of the confidential VM.
+    pub fn hardware_hart_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hardware_hart_ids.iter().filter_map(|hardware_hart_id| *hardware_hart_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placed_confidential_hart_is_found_and_cleared() {
+        let mut hart_placement = HartPlacement::new(5);
+        hart_placement.place(2, 3).unwrap();
+        assert_eq!(hart_placement.hardware_hart_id(2), Some(3));
+        assert_eq!(hart_placement.hardware_hart_ids().collect::<Vec<_>>(), [3]);
+        hart_placement.remove(2);
+        assert_eq!(hart_placement.hardware_hart_id(2), None);
+        assert_eq!(hart_placement.hardware_hart_ids().count(), 0);
+    }
+
+    #[test]
+    fn confidential_hart_is_not_placed_twice() {
+        let mut hart_placement = HartPlacement::new(5);
+        hart_placement.place(2, 3).unwrap();
+        assert!(matches!(hart_placement.place(2, 4), Err(Error::HartAlreadyRunning())));
+        assert_eq!(hart_placement.hardware_hart_id(2), Some(3));
+    }
+
+    #[test]
+    fn nonexistent_confidential_hart_is_not_placed() {
+        let mut hart_placement = HartPlacement::new(5);
+        assert!(matches!(hart_placement.place(5, 3), Err(Error::InvalidHartId())));
+        assert_eq!(hart_placement.hardware_hart_id(5), None);
+    }
+}
@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Tracks how many confidential harts of a confidential VM execute the confidential VM's code and allows pausing all of them.
+///
+/// It is shared between the confidential VM and all its confidential harts. This is needed because a hardware hart that executes a
+/// confidential hart must be able to observe a pause request without taking the confidential VM's lock, which is held by the hart
+/// requesting the pause.
+#[derive(Default)]
+pub struct HartQuiesce {
+    is_pause_requested: AtomicBool,
+    number_of_running_harts: AtomicUsize,
+}
+
+impl HartQuiesce {
+    /// Registers that a hardware hart is about to execute a confidential hart. Spins while the confidential VM is paused.
+    ///
+    /// The counter is incremented before the pause request is checked, so that the hart requesting the pause either observes this hart
+    /// as running or this hart observes the pause request.
+    pub fn enter_confidential_hart(&self) {
+        loop {
+            self.number_of_running_harts.fetch_add(1, Ordering::SeqCst);
+            if !self.is_pause_requested.load(Ordering::SeqCst) {
+                return;
+            }
+            self.number_of_running_harts.fetch_sub(1, Ordering::SeqCst);
+            while self.is_pause_requested.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Registers that a hardware hart stopped executing a confidential hart because it trapped into the security monitor.
+    pub fn exit_confidential_hart(&self) {
+        self.number_of_running_harts.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_pause_requested.load(Ordering::SeqCst)
+    }
+
+    fn request_pause(&self) -> Result<(), Error> {
+        self.is_pause_requested
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .map_err(|_| Error::ConfidentialVmPaused())?;
+        Ok(())
+    }
+
+    fn wait_until_all_harts_paused(&self) {
+        while self.number_of_running_harts.load(Ordering::SeqCst) > 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn resume(&self) {
+        self.is_pause_requested.store(false, Ordering::SeqCst);
+    }
+}
+
+/// While this guard exists, no confidential hart of the confidential VM executes. Confidential harts are allowed to execute again
+/// when the guard is dropped.
+pub struct HartQuiesceGuard {
+    hart_quiesce: Arc<HartQuiesce>,
+}
+
+impl HartQuiesceGuard {
+    /// Requests all confidential harts to pause. Returns error if the confidential VM is already paused.
+    pub(super) fn new(hart_quiesce: Arc<HartQuiesce>) -> Result<Self, Error> {
+        hart_quiesce.request_pause()?;
+        Ok(Self { hart_quiesce })
+    }
+
+    /// Spins until every confidential hart that was running acknowledged the pause by trapping into the security monitor.
+    pub(super) fn wait_for_acknowledgments(&self) {
+        self.hart_quiesce.wait_until_all_harts_paused();
+    }
+}
+
+impl Drop for HartQuiesceGuard {
+    fn drop(&mut self) {
+        self.hart_quiesce.resume();
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::{ConfidentialVmMeasurement, MeasurementRegisters};
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::PageSize;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Identifies what a measurement log entry measures. The values are part of the interface with verifiers.
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeasuredComponent {
+    /// A 4KiB chunk of the initial memory content, see `ConfidentialVmMeasurement::page_digests`.
+    Memory = 0,
+    /// The entry point and the initial registers of the boot hart.
+    BootHart = 1,
+    /// The VS-stage page tables with which the boot hart starts.
+    VsStagePageTables = 2,
+    DeviceTree = 3,
+    Initrd = 4,
+    /// A hart added during the staged construction of the confidential VM.
+    Hart = 5,
+    /// The configuration of the confidential VM. Unlike other components, it is not extended but assigned to the register.
+    Configuration = 6,
+    /// A digest extended by the confidential VM at runtime.
+    Runtime = 7,
+}
+
+pub struct MeasurementLogEntry {
+    register: usize,
+    component: MeasuredComponent,
+    // Guest physical address and size of the measured region, if the component is a memory region.
+    region: Option<(ConfidentialVmPhysicalAddress, usize)>,
+    digest: [u8; Sha384::DIGEST_SIZE_IN_BYTES],
+}
+
+impl MeasurementLogEntry {
+    /// Every entry is exposed as four little-endian u64 fields (register, component, address, size) followed by the digest.
+    pub const SIZE_IN_BYTES: usize = 4 * core::mem::size_of::<u64>() + Sha384::DIGEST_SIZE_IN_BYTES;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE_IN_BYTES] {
+        let (address, size) = self.region.map(|(address, size)| (address.usize(), size)).unwrap_or((0, 0));
+        let mut bytes = [0u8; Self::SIZE_IN_BYTES];
+        [self.register, self.component as usize, address, size]
+            .iter()
+            .zip(bytes.chunks_exact_mut(core::mem::size_of::<u64>()))
+            .for_each(|(value, chunk)| chunk.copy_from_slice(&(*value as u64).to_le_bytes()));
+        bytes[4 * core::mem::size_of::<u64>()..].copy_from_slice(&self.digest);
+        bytes
+    }
+}
+
+/// The event log of a confidential VM's measurement registers, similar to the TCG event log of a TPM. It lists every digest that
+/// contributed to a measurement register in the order in which it was extended, so a verifier can check individual components
+/// (kernel, device tree, memory ranges) instead of only the final value. A verifier reproduces a register by replaying its entries:
+///
+/// ```text
+/// register = 0
+/// for entry in log where entry.register == index:
+///     if entry.component == Configuration: register = entry.digest
+///     else:                                register = SHA-384(register || entry.digest)
+/// ```
+///
+/// The log is stored in the confidential memory and grows with the size of the initial memory image, because every 4KiB chunk of the
+/// image has its own entry.
+pub struct MeasurementLog {
+    entries: Vec<MeasurementLogEntry>,
+}
+
+impl MeasurementLog {
+    pub fn empty() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Extends the launch measurement register with the digest and records the extension in the log.
+    pub fn extend(
+        &mut self, measurements: &mut [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS], register: usize,
+        component: MeasuredComponent, region: Option<(ConfidentialVmPhysicalAddress, usize)>, digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES],
+    ) {
+        measurements[register].extend(digest);
+        self.record(register, component, region, digest);
+    }
+
+    /// Extends the launch measurement register with the digests of 4KiB chunks of memory, indexed by their guest physical addresses,
+    /// in the ascending order of these addresses and records every extension in the log. Thus, the measurement does not depend on the
+    /// order in which the chunks were added to the map.
+    pub fn extend_with_memory(
+        &mut self, measurements: &mut [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS], register: usize,
+        chunk_digests: &BTreeMap<usize, [u8; Sha384::DIGEST_SIZE_IN_BYTES]>,
+    ) {
+        chunk_digests.iter().for_each(|(address, digest)| {
+            let region = Some((ConfidentialVmPhysicalAddress::new(*address), PageSize::Size4KiB.in_bytes()));
+            self.extend(measurements, register, MeasuredComponent::Memory, region, digest);
+        });
+    }
+
+    /// Assigns the configuration measurement to the launch measurement register and records the assignment in the log.
+    pub fn assign_configuration(
+        &mut self, measurements: &mut [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS], register: usize,
+        configuration: ConfidentialVmMeasurement,
+    ) {
+        measurements[register] = configuration;
+        let mut digest = [0u8; Sha384::DIGEST_SIZE_IN_BYTES];
+        digest.copy_from_slice(&configuration.value[..Sha384::DIGEST_SIZE_IN_BYTES]);
+        self.record(register, MeasuredComponent::Configuration, None, &digest);
+    }
+
+    /// Records a digest that the caller has already extended into the given register.
+    pub fn record(
+        &mut self, register: usize, component: MeasuredComponent, region: Option<(ConfidentialVmPhysicalAddress, usize)>,
+        digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES],
+    ) {
+        self.entries.push(MeasurementLogEntry { register, component, region, digest: *digest });
+    }
+
+    pub fn number_of_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns entries starting from the given index in the order in which they were recorded.
+    pub fn entries_from(&self, first_entry: usize) -> impl Iterator<Item = &MeasurementLogEntry> {
+        self.entries.iter().skip(first_entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMORY_REGISTER: usize = 0;
+    // Digests of 4KiB chunks of a known image, listed in the order in which the hypervisor donates them.
+    const DONATIONS: [(usize, u8); 4] = [(0x8000_3000, 3), (0x8000_0000, 0), (0x8020_0000, 4), (0x8000_1000, 1)];
+
+    fn launch_registers() -> [ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS] {
+        [ConfidentialVmMeasurement::empty(); MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS]
+    }
+
+    fn measure_donations(
+        donations: impl Iterator<Item = (usize, u8)>,
+    ) -> ([ConfidentialVmMeasurement; MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS], MeasurementLog) {
+        let chunk_digests: BTreeMap<_, _> = donations.map(|(address, byte)| (address, [byte; Sha384::DIGEST_SIZE_IN_BYTES])).collect();
+        let mut measurements = launch_registers();
+        let mut measurement_log = MeasurementLog::empty();
+        measurement_log.extend_with_memory(&mut measurements, MEMORY_REGISTER, &chunk_digests);
+        (measurements, measurement_log)
+    }
+
+    #[test]
+    fn memory_measurement_does_not_depend_on_donation_order() {
+        let (measurements, measurement_log) = measure_donations(DONATIONS.into_iter());
+        let (reversed_measurements, reversed_measurement_log) = measure_donations(DONATIONS.into_iter().rev());
+        assert_eq!(measurements[MEMORY_REGISTER].value, reversed_measurements[MEMORY_REGISTER].value);
+        let entries: Vec<_> = measurement_log.entries_from(0).map(|entry| entry.to_bytes()).collect();
+        let reversed_entries: Vec<_> = reversed_measurement_log.entries_from(0).map(|entry| entry.to_bytes()).collect();
+        assert_eq!(entries, reversed_entries);
+    }
+
+    #[test]
+    fn memory_is_measured_in_guest_physical_address_order() {
+        let (measurements, measurement_log) = measure_donations(DONATIONS.into_iter());
+        // The promotion extends the register while walking the page tables in the ascending order of guest physical addresses.
+        let mut sorted_donations = DONATIONS;
+        sorted_donations.sort();
+        let mut expected_measurements = launch_registers();
+        sorted_donations.iter().for_each(|(_, byte)| expected_measurements[MEMORY_REGISTER].extend(&[*byte; Sha384::DIGEST_SIZE_IN_BYTES]));
+        assert_eq!(measurements[MEMORY_REGISTER].value, expected_measurements[MEMORY_REGISTER].value);
+        let addresses: Vec<_> = measurement_log.entries_from(0).map(|entry| entry.region.unwrap().0.usize()).collect();
+        assert_eq!(addresses, sorted_donations.map(|(address, _)| address));
+    }
+
+    #[test]
+    fn replaying_log_reproduces_registers() {
+        let (mut measurements, mut measurement_log) = measure_donations(DONATIONS.into_iter());
+        measurement_log.extend(&mut measurements, 1, MeasuredComponent::Hart, None, &[0xab; Sha384::DIGEST_SIZE_IN_BYTES]);
+        measurement_log.assign_configuration(&mut measurements, 3, ConfidentialVmMeasurement::from_configuration(true));
+        // A verifier replays the log as described in the documentation of `MeasurementLog`.
+        let mut replayed_measurements = launch_registers();
+        measurement_log.entries_from(0).for_each(|entry| match entry.component {
+            MeasuredComponent::Configuration => {
+                replayed_measurements[entry.register].value[..Sha384::DIGEST_SIZE_IN_BYTES].copy_from_slice(&entry.digest)
+            }
+            _ => replayed_measurements[entry.register].extend(&entry.digest),
+        });
+        measurements.iter().zip(replayed_measurements.iter()).for_each(|(register, replayed)| assert_eq!(register.value, replayed.value));
+    }
+
+    #[test]
+    fn entry_encoding() {
+        let (_, measurement_log) = measure_donations(DONATIONS.into_iter().take(1));
+        let bytes = measurement_log.entries_from(0).next().unwrap().to_bytes();
+        assert_eq!(bytes[0..8], (MEMORY_REGISTER as u64).to_le_bytes());
+        assert_eq!(bytes[8..16], (MeasuredComponent::Memory as u64).to_le_bytes());
+        assert_eq!(bytes[16..24], 0x8000_3000u64.to_le_bytes());
+        assert_eq!(bytes[24..32], 0x1000u64.to_le_bytes());
+        assert_eq!(bytes[32..], [3; Sha384::DIGEST_SIZE_IN_BYTES]);
+    }
+}
@@ -0,0 +1,118 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+use alloc::vec::Vec;
+use flattened_device_tree::FlattenedDeviceTree;
+
+/// Defines which guest physical address ranges of a confidential VM must never be forwarded to the hypervisor for MMIO emulation.
+/// Loads and stores to these ranges are reflected back to the confidential VM as access faults. The policy is fixed when the
+/// confidential VM is created.
+pub struct MmioPolicy {
+    // Each region is represented as a pair of the start (inclusive) and end (exclusive) guest physical addresses.
+    denied_regions: Vec<(usize, usize)>,
+}
+
+impl MmioPolicy {
+    const MAX_NUMBER_OF_DENIED_REGIONS: usize = 32;
+
+    pub fn empty() -> Self {
+        Self { denied_regions: Vec::new() }
+    }
+
+    /// Builds the policy from the confidential VM's device tree. Returns error if the device tree declares an invalid region or more
+    /// regions than the security monitor supports.
+    pub fn from_device_tree(device_tree: &FlattenedDeviceTree) -> Result<Self, Error> {
+        let mut policy = Self::empty();
+        for region in device_tree.mmio_denied_regions() {
+            policy.deny_region(region.base, region.size)?;
+        }
+        Ok(policy)
+    }
+
+    fn deny_region(&mut self, base: u64, size: u64) -> Result<(), Error> {
+        assure!(self.denied_regions.len() < Self::MAX_NUMBER_OF_DENIED_REGIONS, Error::InvalidMmioPolicy())?;
+        let start = usize::try_from(base).map_err(|_| Error::InvalidMmioPolicy())?;
+        let size = usize::try_from(size).map_err(|_| Error::InvalidMmioPolicy())?;
+        let end = start.checked_add(size).ok_or(Error::InvalidMmioPolicy())?;
+        assure!(size > 0, Error::InvalidMmioPolicy())?;
+        self.denied_regions.push((start, end));
+        Ok(())
+    }
+
+    pub fn is_denied(&self, address: usize) -> bool {
+        self.denied_regions.iter().any(|(start, end)| *start <= address && address < *end)
+    }
+
+    /// Classifies a guest page fault on the given guest physical address. Returns true if the fault must not be forwarded to the
+    /// hypervisor as an MMIO access, because the address is in a denied region or, as decided by `is_confidential_memory`, belongs to
+    /// the confidential VM's own memory. Only faults outside both are emulated MMIO accesses.
+    pub fn is_forwarding_denied(&self, address: usize, is_confidential_memory: impl FnOnce(usize) -> bool) -> bool {
+        self.is_denied(address) || is_confidential_memory(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIDENTIAL_MEMORY: (usize, usize) = (0x8000_0000, 0x9000_0000);
+    const DENIED_REGION: (usize, usize) = (0x1000_0000, 0x1000_1000);
+    const MMIO_ADDRESS: usize = 0x1000_2000;
+
+    fn is_confidential_memory(address: usize) -> bool {
+        CONFIDENTIAL_MEMORY.0 <= address && address < CONFIDENTIAL_MEMORY.1
+    }
+
+    fn policy() -> MmioPolicy {
+        MmioPolicy { denied_regions: alloc::vec![DENIED_REGION] }
+    }
+
+    #[test]
+    fn access_to_denied_region_is_denied() {
+        let mut policy = MmioPolicy::empty();
+        policy.deny_region(0x1000_0000, 0x1000).unwrap();
+        policy.deny_region(0x2000_0000, 0x10).unwrap();
+        for address in [0x1000_0000, 0x1000_0fff, 0x2000_0000, 0x2000_000f] {
+            assert!(policy.is_denied(address));
+        }
+        for address in [0x0fff_ffff, 0x1000_1000, 0x2000_0010] {
+            assert!(!policy.is_denied(address));
+        }
+        assert!(!MmioPolicy::empty().is_denied(0x1000_0000));
+    }
+
+    #[test]
+    fn invalid_region_is_rejected() {
+        let mut policy = MmioPolicy::empty();
+        assert!(policy.deny_region(0x1000_0000, 0).is_err());
+        assert!(policy.deny_region(u64::MAX, 2).is_err());
+        for i in 0..MmioPolicy::MAX_NUMBER_OF_DENIED_REGIONS {
+            policy.deny_region(0x1000 * i as u64, 0x1000).unwrap();
+        }
+        assert!(policy.deny_region(0x1000_0000, 0x1000).is_err());
+        assert_eq!(policy.denied_regions.len(), MmioPolicy::MAX_NUMBER_OF_DENIED_REGIONS);
+    }
+
+    #[test]
+    fn mmio_region_fault_is_forwarded() {
+        assert!(!policy().is_forwarding_denied(MMIO_ADDRESS, is_confidential_memory));
+        assert!(!MmioPolicy::empty().is_forwarding_denied(MMIO_ADDRESS, is_confidential_memory));
+    }
+
+    #[test]
+    fn confidential_memory_fault_is_not_forwarded() {
+        for address in [CONFIDENTIAL_MEMORY.0, CONFIDENTIAL_MEMORY.0 + 0x1234, CONFIDENTIAL_MEMORY.1 - 1] {
+            assert!(policy().is_forwarding_denied(address, is_confidential_memory));
+            assert!(MmioPolicy::empty().is_forwarding_denied(address, is_confidential_memory));
+        }
+        assert!(!policy().is_forwarding_denied(CONFIDENTIAL_MEMORY.1, is_confidential_memory));
+    }
+
+    #[test]
+    fn denied_region_fault_is_not_forwarded() {
+        assert!(policy().is_forwarding_denied(DENIED_REGION.0, is_confidential_memory));
+        assert!(policy().is_forwarding_denied(DENIED_REGION.1 - 1, is_confidential_memory));
+        assert!(!policy().is_forwarding_denied(DENIED_REGION.1, is_confidential_memory));
+    }
+}
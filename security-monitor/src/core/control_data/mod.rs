@@ -3,14 +3,53 @@
 // SPDX-License-Identifier: Apache-2.0
 pub use confidential_hart::ConfidentialHart;
 pub use confidential_vm::ConfidentialVm;
+pub use confidential_vm_builder::ConfidentialVmBuilder;
 pub use confidential_vm_id::ConfidentialVmId;
-pub use confidential_vm_measurement::ConfidentialVmMeasurement;
-pub use hardware_hart::{HardwareHart, HART_STACK_ADDRESS_OFFSET};
+pub use confidential_vm_measurement::{ConfidentialVmMeasurement, MeasurementRegisters};
+pub use cppc_virtualizer::CppcVirtualizer;
+pub use csr_emulation_policy::{CsrEmulation, CsrEmulationPolicy};
+pub use fwft_virtualizer::FwftVirtualizer;
+pub use hardware_hart::{HardwareHart, HART_STACK_ADDRESS_OFFSET, TRACE_BUFFER_CAPACITY};
+pub use hart_placement::HartPlacement;
+pub use hart_quiesce::{HartQuiesce, HartQuiesceGuard};
+pub use measurement_log::{MeasuredComponent, MeasurementLog, MeasurementLogEntry};
+pub use mmio_policy::MmioPolicy;
+pub use nacl_shared_memory::NaclSharedMemory;
+pub use pending_exit::PendingExit;
+pub use pmu_virtualizer::PmuVirtualizer;
+pub use shared_page_table::SharedPageTable;
+pub use sse_virtualizer::SseVirtualizer;
+pub use steal_time_state::StealTimeState;
 pub use storage::{ControlData, CONTROL_DATA};
+pub use time_virtualizer::TimeVirtualizer;
+pub use trace_buffer::{TraceBuffer, TraceEvent};
+pub use trap_delegation::TrapDelegation;
+pub use vcpu_runstate::VcpuRunstate;
+pub use wfi_policy::WfiPolicy;
 
 mod confidential_hart;
 mod confidential_vm;
+mod confidential_vm_builder;
 mod confidential_vm_id;
 mod confidential_vm_measurement;
+mod confidential_vm_table;
+mod cppc_virtualizer;
+mod csr_emulation_policy;
+mod fwft_virtualizer;
 mod hardware_hart;
+mod hart_placement;
+mod hart_quiesce;
+mod measurement_log;
+mod mmio_policy;
+mod nacl_shared_memory;
+mod pending_exit;
+mod pmu_virtualizer;
+mod shared_page_table;
+mod sse_virtualizer;
+mod steal_time_state;
 mod storage;
+mod time_virtualizer;
+mod trace_buffer;
+mod trap_delegation;
+mod vcpu_runstate;
+mod wfi_policy;
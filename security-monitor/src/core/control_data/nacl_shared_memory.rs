@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::specification::{CSR_MSTATUS_FS_DIRTY, CSR_MSTATUS_FS_MASK, CSR_MSTATUS_XS_DIRTY, CSR_MSTATUS_XS_MASK};
+use crate::core::architecture::GeneralPurposeRegister;
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::PageSize;
+use crate::error::Error;
+
+/// The memory region shared between the hypervisor and the security monitor, registered by the hypervisor on a physical hart with
+/// the `set shared memory` call of the SBI nested acceleration (NACL) extension. The security monitor uses it to exchange
+/// MMIO- and call-related information with the hypervisor, so that it does not have to abuse registers for this purpose.
+///
+/// The layout follows the SBI specification of the NACL extension:
+///   * the scratch area (4KiB) starts at the beginning of the shared memory. It begins with the area used by `sync sret` that stores
+///     general purpose registers x0-x31, followed by the autoswap area. The security monitor uses the following unused bytes to receive
+///     arguments of its own calls, see `SECURITY_MONITOR_CALL_ARGUMENTS_OFFSET`, followed by the status of the last MMIO access and the
+///     status of the hypervisor's FP state, see `set_fp_state_status`.
+///   * the CSR area (8KiB) follows the scratch area. It contains one slot per CSR, see `csr_slot`.
+pub struct NaclSharedMemory {
+    base_address: usize,
+}
+
+impl NaclSharedMemory {
+    const SCRATCH_AREA_SIZE: usize = 0x1000;
+    const SRET_AREA_OFFSET: usize = 0x0;
+    const SECURITY_MONITOR_CALL_ARGUMENTS_OFFSET: usize = 0x280;
+    const GUEST_ACCESS_STATUS_OFFSET: usize = Self::SECURITY_MONITOR_CALL_ARGUMENTS_OFFSET + 2 * core::mem::size_of::<usize>();
+    const FP_STATE_STATUS_OFFSET: usize = Self::GUEST_ACCESS_STATUS_OFFSET + core::mem::size_of::<usize>();
+    const FP_STATE_DIRTY: usize = 1 << 0;
+    const EXTENSION_STATE_DIRTY: usize = 1 << 1;
+    const CSR_AREA_OFFSET: usize = Self::SCRATCH_AREA_SIZE;
+    const CSR_AREA_SIZE: usize = (usize::BITS as usize / 8) * 1024;
+    pub const SIZE_IN_BYTES: usize = Self::SCRATCH_AREA_SIZE + Self::CSR_AREA_SIZE;
+
+    /// Validates the shared memory provided by the hypervisor. Returns error if the address is not aligned to 4KiB or if the shared
+    /// memory is not entirely located in the non-confidential memory.
+    pub fn new(base_address: usize) -> Result<Self, Error> {
+        assure!(base_address % PageSize::Size4KiB.in_bytes() == 0, Error::InvalidArgument())?;
+        let last_word_offset = Self::SIZE_IN_BYTES - core::mem::size_of::<usize>();
+        let shared_memory = Self { base_address };
+        shared_memory.address_at_offset(0)?;
+        shared_memory.address_at_offset(last_word_offset)?;
+        Ok(shared_memory)
+    }
+
+    pub fn gpr(&self, register: GeneralPurposeRegister) -> Result<usize, Error> {
+        self.read(Self::SRET_AREA_OFFSET + register.index() * core::mem::size_of::<usize>())
+    }
+
+    pub fn set_gpr(&self, register: GeneralPurposeRegister, value: usize) -> Result<(), Error> {
+        self.write(Self::SRET_AREA_OFFSET + register.index() * core::mem::size_of::<usize>(), value)
+    }
+
+    pub fn set_csr(&self, csr: u16, value: usize) -> Result<(), Error> {
+        self.write(Self::CSR_AREA_OFFSET + Self::csr_slot(csr) * core::mem::size_of::<usize>(), value)
+    }
+
+    /// Returns the id of the confidential VM and the id of the confidential hart, which the hypervisor passes as arguments to the
+    /// security monitor calls.
+    pub fn security_monitor_call_arguments(&self) -> Result<(usize, usize), Error> {
+        let confidential_vm_id = self.read(Self::SECURITY_MONITOR_CALL_ARGUMENTS_OFFSET)?;
+        let confidential_hart_id = self.read(Self::SECURITY_MONITOR_CALL_ARGUMENTS_OFFSET + core::mem::size_of::<usize>())?;
+        Ok((confidential_vm_id, confidential_hart_id))
+    }
+
+    /// Returns the status of the last MMIO access that the hypervisor was asked to emulate. It is stored right after the arguments of
+    /// the security monitor calls.
+    pub fn guest_access_status(&self) -> Result<usize, Error> {
+        self.read(Self::GUEST_ACCESS_STATUS_OFFSET)
+    }
+
+    /// Resets the status before exposing a new MMIO access, so that the status of a previous access is never taken for the current one.
+    pub fn clear_guest_access_status(&self) -> Result<(), Error> {
+        self.write(Self::GUEST_ACCESS_STATUS_OFFSET, 0)
+    }
+
+    /// Reports whether the hypervisor's FP and extension state were dirty when it resumed a confidential hart, given the FS and XS
+    /// fields of its mstatus. Bit 0 is set if the FP state was dirty and bit 1 if the extension state was dirty, so the hypervisor's
+    /// context switch code saves this state only if it is set.
+    pub fn set_fp_state_status(&self, fs_xs_state: usize) -> Result<(), Error> {
+        self.write(Self::FP_STATE_STATUS_OFFSET, Self::fp_state_status(fs_xs_state))
+    }
+
+    fn fp_state_status(fs_xs_state: usize) -> usize {
+        let is_fp_state_dirty = fs_xs_state & CSR_MSTATUS_FS_MASK == CSR_MSTATUS_FS_DIRTY;
+        let is_extension_state_dirty = fs_xs_state & CSR_MSTATUS_XS_MASK == CSR_MSTATUS_XS_DIRTY;
+        (is_fp_state_dirty as usize * Self::FP_STATE_DIRTY) | (is_extension_state_dirty as usize * Self::EXTENSION_STATE_DIRTY)
+    }
+
+    /// The SBI NACL extension maps a 12-bit CSR number to a slot in the CSR area by dropping bits 8 and 9, which encode the lowest
+    /// privilege level allowed to access the CSR.
+    fn csr_slot(csr: u16) -> usize {
+        let csr = csr as usize;
+        ((csr & 0xc00) >> 2) | (csr & 0xff)
+    }
+
+    fn read(&self, offset_in_bytes: usize) -> Result<usize, Error> {
+        let address = self.address_at_offset(offset_in_bytes)?;
+        // Safety: the address is in the non-confidential memory, so reading it cannot leak confidential information.
+        Ok(unsafe { address.read() })
+    }
+
+    fn write(&self, offset_in_bytes: usize, value: usize) -> Result<(), Error> {
+        let address = self.address_at_offset(offset_in_bytes)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(value) };
+        Ok(())
+    }
+
+    /// The hypervisor can convert the shared memory to confidential memory after registering it, so we check that the address is in
+    /// the non-confidential memory on every access.
+    fn address_at_offset(&self, offset_in_bytes: usize) -> Result<NonConfidentialMemoryAddress, Error> {
+        let address = self.base_address.checked_add(offset_in_bytes).ok_or(Error::MemoryAccessAuthorization())?;
+        NonConfidentialMemoryAddress::new(address as *mut usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::specification::{CSR_MSTATUS_FS_CLEAN, CSR_MSTATUS_XS};
+
+    #[test]
+    fn dirty_fp_state_is_reported() {
+        assert_eq!(NaclSharedMemory::fp_state_status(CSR_MSTATUS_FS_DIRTY), NaclSharedMemory::FP_STATE_DIRTY);
+        assert_eq!(
+            NaclSharedMemory::fp_state_status(CSR_MSTATUS_FS_DIRTY | CSR_MSTATUS_XS_DIRTY),
+            NaclSharedMemory::FP_STATE_DIRTY | NaclSharedMemory::EXTENSION_STATE_DIRTY
+        );
+    }
+
+    #[test]
+    fn clean_or_disabled_fp_state_is_not_reported() {
+        assert_eq!(NaclSharedMemory::fp_state_status(0), 0);
+        assert_eq!(NaclSharedMemory::fp_state_status(CSR_MSTATUS_FS_CLEAN), 0);
+        // Extensions in the initial or clean state do not have to be saved.
+        assert_eq!(NaclSharedMemory::fp_state_status(0b01 << CSR_MSTATUS_XS), 0);
+        assert_eq!(NaclSharedMemory::fp_state_status(0b10 << CSR_MSTATUS_XS), 0);
+    }
+
+    #[test]
+    fn dirty_extension_state_is_reported() {
+        assert_eq!(NaclSharedMemory::fp_state_status(CSR_MSTATUS_XS_DIRTY), NaclSharedMemory::EXTENSION_STATE_DIRTY);
+    }
+}
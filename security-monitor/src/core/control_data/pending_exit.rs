@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::HsmExtension;
+use crate::core::transformations::{ExposeToHypervisor, MmioStoreWidth};
+
+/// The reason why a confidential hart last exited to the hypervisor. The hypervisor of an asynchronous interface, like CoVE, polls the
+/// reason after the confidential hart stopped executing instead of inferring it from the registers and CSRs that the security monitor
+/// exposed during the exit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PendingExit {
+    // The confidential hart loaded from MMIO at the guest physical address.
+    MmioLoad { guest_physical_address: usize },
+    // The confidential hart stored to MMIO at the guest physical address.
+    MmioStore { guest_physical_address: usize, width: MmioStoreWidth },
+    // The confidential hart made an SBI call that the hypervisor handles.
+    Sbi { extension_id: usize, function_id: usize },
+    // The confidential hart stopped or suspended itself.
+    HartIdle,
+    // The security monitor could not handle a fault of the confidential hart without the hypervisor's help.
+    Fault { guest_physical_address: usize },
+    // An interrupt for the hypervisor preempted the confidential hart.
+    InterruptPending,
+}
+
+impl PendingExit {
+    /// Returns the reason of the exit to the hypervisor with the given transformation. Returns `None` for exits that only return the
+    /// result of a call, e.g., because the security monitor failed to handle the confidential hart's trap.
+    pub fn from_transformation(transformation: &ExposeToHypervisor) -> Option<Self> {
+        match transformation {
+            ExposeToHypervisor::MmioLoadRequest(request) => Some(Self::MmioLoad { guest_physical_address: request.fault_address() }),
+            ExposeToHypervisor::MmioStoreRequest(request) => {
+                Some(Self::MmioStore { guest_physical_address: request.fault_address(), width: request.width() })
+            }
+            ExposeToHypervisor::SbiRequest(request) => match (request.extension_id(), request.function_id()) {
+                (HsmExtension::EXTID, HsmExtension::HART_STOP_FID | HsmExtension::HART_SUSPEND_FID) => Some(Self::HartIdle),
+                (extension_id, function_id) => Some(Self::Sbi { extension_id, function_id }),
+            },
+            ExposeToHypervisor::MemoryFaultNotification(notification) => {
+                Some(Self::Fault { guest_physical_address: notification.failed_address() })
+            }
+            ExposeToHypervisor::InterruptRequest(_) | ExposeToHypervisor::EnabledInterrupts(_) => Some(Self::InterruptPending),
+            _ => None,
+        }
+    }
+
+    /// Returns the value that the security monitor reports to the hypervisor.
+    pub fn code(&self) -> usize {
+        match self {
+            Self::MmioLoad { .. } => 1,
+            Self::MmioStore { .. } => 2,
+            Self::Sbi { .. } => 3,
+            Self::HartIdle => 4,
+            Self::Fault { .. } => 5,
+            Self::InterruptPending => 6,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::GeneralPurposeRegister;
+    use crate::core::transformations::{MmioLoadRequest, MmioStoreRequest, SbiRequest, SbiResult};
+
+    const STORE_GUEST_PAGE_FAULT: usize = 23;
+    const LOAD_GUEST_PAGE_FAULT: usize = 21;
+    // The guest physical address 0x4000_1000 shifted right by 2 bits, as reported by the hardware in `htval`.
+    const HTVAL: usize = 0x1000_0400;
+
+    #[test]
+    fn mmio_store_exit_reports_normalized_access() {
+        // A halfword store to the guest virtual address 0x7ff0_0002, whose page offset the guest physical address shares.
+        let request = MmioStoreRequest::new(
+            STORE_GUEST_PAGE_FAULT,
+            0x7ff0_0002,
+            HTVAL,
+            0x00a5_1023,
+            GeneralPurposeRegister::a0,
+            0x1122_3344,
+            MmioStoreWidth::Halfword,
+        );
+        // Only the stored bytes of the source register reach the hypervisor.
+        assert_eq!(request.gpr_value(), 0x3344);
+        let pending_exit = PendingExit::from_transformation(&ExposeToHypervisor::MmioStoreRequest(request));
+        assert_eq!(pending_exit, Some(PendingExit::MmioStore { guest_physical_address: 0x4000_1002, width: MmioStoreWidth::Halfword }));
+        assert_eq!(pending_exit.unwrap().code(), 2);
+    }
+
+    #[test]
+    fn mmio_load_exit_reports_guest_physical_address() {
+        let request = MmioLoadRequest::new(LOAD_GUEST_PAGE_FAULT, 0x7ff0_0001, HTVAL, 0x0005_4503);
+        let pending_exit = PendingExit::from_transformation(&ExposeToHypervisor::MmioLoadRequest(request));
+        assert_eq!(pending_exit, Some(PendingExit::MmioLoad { guest_physical_address: 0x4000_1001 }));
+        assert_eq!(pending_exit.unwrap().code(), 1);
+    }
+
+    #[test]
+    fn stopping_or_suspending_hart_is_idle_exit() {
+        for request in [SbiRequest::kvm_hsm_hart_stop(), SbiRequest::kvm_hsm_hart_suspend()] {
+            assert_eq!(PendingExit::from_transformation(&ExposeToHypervisor::SbiRequest(request)), Some(PendingExit::HartIdle));
+        }
+        let request = SbiRequest::kvm_hsm_hart_start(1);
+        assert_eq!(
+            PendingExit::from_transformation(&ExposeToHypervisor::SbiRequest(request)),
+            Some(PendingExit::Sbi { extension_id: HsmExtension::EXTID, function_id: HsmExtension::HART_START_FID })
+        );
+    }
+
+    #[test]
+    fn returning_result_has_no_exit_reason() {
+        assert_eq!(PendingExit::from_transformation(&ExposeToHypervisor::SbiResult(SbiResult::success(0))), None);
+    }
+}
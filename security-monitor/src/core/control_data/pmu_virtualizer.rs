@@ -0,0 +1,298 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+#[cfg(not(test))]
+use crate::core::architecture::CSR;
+use crate::core::transformations::SbiPmuRequest;
+use crate::error::Error;
+
+/// Virtualizes the SBI Performance Monitoring Unit (PMU) extension for a confidential hart.
+///
+/// Hardware performance counters are shared by all security domains executing on a physical hart, so exposing them to a confidential
+/// VM would create a timing channel between VMs. Instead, the confidential hart sees a small number of virtual counters that count
+/// only a safe subset of events and only while this confidential hart executes. Virtual counters are reported as firmware counters,
+/// so the confidential VM reads them via the SBI call and never accesses the physical counters directly.
+#[derive(Default)]
+pub struct PmuVirtualizer {
+    counters: [VirtualCounter; Self::NUMBER_OF_COUNTERS],
+    // Backs the `cycle` CSR when the confidential VM's CSR emulation policy virtualizes it. It is not one of the SBI counters.
+    cycle_csr: VirtualCounter,
+}
+
+impl PmuVirtualizer {
+    const NUMBER_OF_COUNTERS: usize = 2;
+    // The most significant bit of the counter info marks a firmware counter.
+    const FIRMWARE_COUNTER_INFO: usize = 1 << (usize::BITS - 1);
+    const CONFIG_FLAG_SKIP_MATCH: usize = 1 << 0;
+    const CONFIG_FLAG_CLEAR_VALUE: usize = 1 << 1;
+    const CONFIG_FLAG_AUTO_START: usize = 1 << 2;
+    const START_FLAG_SET_INIT_VALUE: usize = 1 << 0;
+    const STOP_FLAG_RESET: usize = 1 << 0;
+
+    /// Executes the PMU call on virtual counters and returns the value for the confidential hart. Returns error if the call is not
+    /// supported, refers to a counter that does not exist or is not configured, or requests an event that is not exposed to confidential
+    /// VMs.
+    pub fn handle(&mut self, request: SbiPmuRequest) -> Result<usize, Error> {
+        match request {
+            SbiPmuRequest::NumCounters => Ok(Self::NUMBER_OF_COUNTERS),
+            SbiPmuRequest::CounterGetInfo { counter_index } => {
+                assure!(counter_index < Self::NUMBER_OF_COUNTERS, Error::InvalidArgument())?;
+                Ok(Self::FIRMWARE_COUNTER_INFO)
+            }
+            SbiPmuRequest::CounterConfigMatching { counter_index_base, counter_index_mask, config_flags, event_index } => {
+                self.configure_counter(counter_index_base, counter_index_mask, config_flags, event_index)
+            }
+            SbiPmuRequest::CounterStart { counter_index_base, counter_index_mask, start_flags, initial_value } => {
+                Self::selected_counters(counter_index_base, counter_index_mask).try_for_each(|counter_index| {
+                    let initial_value = (start_flags & Self::START_FLAG_SET_INIT_VALUE != 0).then_some(initial_value);
+                    self.counters[counter_index].start(initial_value)
+                })?;
+                Ok(0)
+            }
+            SbiPmuRequest::CounterStop { counter_index_base, counter_index_mask, stop_flags } => {
+                Self::selected_counters(counter_index_base, counter_index_mask)
+                    .try_for_each(|counter_index| self.counters[counter_index].stop(stop_flags & Self::STOP_FLAG_RESET != 0))?;
+                Ok(0)
+            }
+            SbiPmuRequest::CounterFwRead { counter_index } => {
+                self.counters.get_mut(counter_index).ok_or(Error::InvalidArgument())?.read().ok_or(Error::InvalidArgument())
+            }
+            // Counters are XLEN bits wide, so there are no upper bits to read.
+            SbiPmuRequest::CounterFwReadHi { counter_index } => {
+                assure!(counter_index < Self::NUMBER_OF_COUNTERS, Error::InvalidArgument())?;
+                Ok(0)
+            }
+            SbiPmuRequest::Unsupported => Err(Error::UnsupportedPmuEvent()),
+        }
+    }
+
+    /// Returns the number of cycles that the confidential hart executed since it first read the virtualized `cycle` CSR. The value
+    /// never decreases and does not advance while other security domains execute.
+    pub fn read_cycle_csr(&mut self) -> usize {
+        if self.cycle_csr.event.is_none() {
+            self.cycle_csr.configure(PmuEvent::CpuCycles, true);
+            self.cycle_csr.is_running = true;
+            self.cycle_csr.take_snapshot();
+        }
+        self.cycle_csr.read().unwrap_or(0)
+    }
+
+    /// Stops counting events when the confidential hart is descheduled from the physical hart, so that virtual counters never reflect
+    /// the activity of other security domains.
+    pub fn pause(&mut self) {
+        self.counters.iter_mut().chain(core::iter::once(&mut self.cycle_csr)).for_each(|counter| counter.accumulate());
+    }
+
+    /// Continues counting events when the confidential hart is scheduled on a physical hart.
+    pub fn resume(&mut self) {
+        self.counters.iter_mut().chain(core::iter::once(&mut self.cycle_csr)).for_each(|counter| counter.take_snapshot());
+    }
+
+    fn configure_counter(
+        &mut self, counter_index_base: usize, counter_index_mask: usize, config_flags: usize, event_index: usize,
+    ) -> Result<usize, Error> {
+        let event = PmuEvent::from_event_index(event_index).ok_or(Error::UnsupportedPmuEvent())?;
+        let counter_index = match config_flags & Self::CONFIG_FLAG_SKIP_MATCH {
+            0 => Self::selected_counters(counter_index_base, counter_index_mask)
+                .find(|counter_index| self.counters[*counter_index].event.is_none())
+                .ok_or(Error::InvalidArgument())?,
+            _ => Self::selected_counters(counter_index_base, counter_index_mask).next().ok_or(Error::InvalidArgument())?,
+        };
+        let counter = &mut self.counters[counter_index];
+        counter.configure(event, config_flags & Self::CONFIG_FLAG_CLEAR_VALUE != 0);
+        if config_flags & Self::CONFIG_FLAG_AUTO_START != 0 {
+            counter.start(None)?;
+        }
+        Ok(counter_index)
+    }
+
+    fn selected_counters(counter_index_base: usize, counter_index_mask: usize) -> impl Iterator<Item = usize> {
+        (0..Self::NUMBER_OF_COUNTERS).filter(move |counter_index| {
+            counter_index
+                .checked_sub(counter_index_base)
+                .filter(|offset| *offset < usize::BITS as usize)
+                .is_some_and(|offset| counter_index_mask & (1 << offset) != 0)
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct VirtualCounter {
+    event: Option<PmuEvent>,
+    is_running: bool,
+    value: usize,
+    // The value of the physical counter when we last accounted its increments to this virtual counter.
+    physical_counter_snapshot: usize,
+}
+
+impl VirtualCounter {
+    fn configure(&mut self, event: PmuEvent, clear_value: bool) {
+        self.accumulate();
+        if self.event != Some(event) {
+            self.is_running = false;
+        }
+        self.event = Some(event);
+        if clear_value {
+            self.value = 0;
+        }
+    }
+
+    fn start(&mut self, initial_value: Option<usize>) -> Result<(), Error> {
+        assure!(self.event.is_some(), Error::InvalidArgument())?;
+        if let Some(initial_value) = initial_value {
+            self.value = initial_value;
+        }
+        self.is_running = true;
+        self.take_snapshot();
+        Ok(())
+    }
+
+    fn stop(&mut self, reset: bool) -> Result<(), Error> {
+        assure!(self.event.is_some(), Error::InvalidArgument())?;
+        self.accumulate();
+        self.is_running = false;
+        if reset {
+            *self = Self::default();
+        }
+        Ok(())
+    }
+
+    fn read(&mut self) -> Option<usize> {
+        self.accumulate();
+        self.event.map(|_| self.value)
+    }
+
+    fn accumulate(&mut self) {
+        if let Some(event) = self.event.filter(|_| self.is_running) {
+            let physical_counter_value = event.read_physical_counter();
+            self.value = self.value.wrapping_add(physical_counter_value.wrapping_sub(self.physical_counter_snapshot));
+            self.physical_counter_snapshot = physical_counter_value;
+        }
+    }
+
+    fn take_snapshot(&mut self) {
+        if let Some(event) = self.event.filter(|_| self.is_running) {
+            self.physical_counter_snapshot = event.read_physical_counter();
+        }
+    }
+}
+
+/// Events that are safe to expose to a confidential VM because their values depend only on the confidential hart's own execution.
+#[derive(Clone, Copy, PartialEq)]
+enum PmuEvent {
+    CpuCycles,
+    Instructions,
+}
+
+impl PmuEvent {
+    const HARDWARE_GENERAL_EVENT_TYPE: usize = 0;
+    const CPU_CYCLES_CODE: usize = 1;
+    const INSTRUCTIONS_CODE: usize = 2;
+
+    /// Decodes the event index defined by the SBI PMU extension: bits 16-19 store the event type and bits 0-15 the event code.
+    fn from_event_index(event_index: usize) -> Option<Self> {
+        match ((event_index >> 16) & 0xf, event_index & 0xffff) {
+            (Self::HARDWARE_GENERAL_EVENT_TYPE, Self::CPU_CYCLES_CODE) => Some(Self::CpuCycles),
+            (Self::HARDWARE_GENERAL_EVENT_TYPE, Self::INSTRUCTIONS_CODE) => Some(Self::Instructions),
+            _ => None,
+        }
+    }
+
+    /// Translates the event into the physical counter that counts it.
+    #[cfg(not(test))]
+    fn read_physical_counter(&self) -> usize {
+        match self {
+            Self::CpuCycles => CSR.mcycle.read(),
+            Self::Instructions => CSR.minstret.read(),
+        }
+    }
+
+    /// Host tests cannot read the physical counters, so they set the values that the counters return.
+    #[cfg(test)]
+    fn read_physical_counter(&self) -> usize {
+        tests::physical_counter(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    std::thread_local! {
+        static MCYCLE: Cell<usize> = const { Cell::new(0) };
+        static MINSTRET: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub(super) fn physical_counter(event: PmuEvent) -> usize {
+        match event {
+            PmuEvent::CpuCycles => MCYCLE.with(|mcycle| mcycle.get()),
+            PmuEvent::Instructions => MINSTRET.with(|minstret| minstret.get()),
+        }
+    }
+
+    fn set_mcycle(value: usize) {
+        MCYCLE.with(|mcycle| mcycle.set(value));
+    }
+
+    #[test]
+    fn cycle_csr_starts_at_zero() {
+        let mut pmu_virtualizer = PmuVirtualizer::default();
+        set_mcycle(0x1234_5678);
+        assert_eq!(pmu_virtualizer.read_cycle_csr(), 0);
+        set_mcycle(0x1234_5778);
+        assert_eq!(pmu_virtualizer.read_cycle_csr(), 0x100);
+    }
+
+    #[test]
+    fn cycle_csr_excludes_cycles_of_other_security_domains() {
+        let mut pmu_virtualizer = PmuVirtualizer::default();
+        set_mcycle(1000);
+        pmu_virtualizer.read_cycle_csr();
+        set_mcycle(1600);
+        pmu_virtualizer.pause();
+        // The hypervisor and other confidential VMs execute in between.
+        set_mcycle(50_000);
+        pmu_virtualizer.resume();
+        set_mcycle(50_100);
+        assert_eq!(pmu_virtualizer.read_cycle_csr(), 700);
+    }
+
+    #[test]
+    fn cycle_csr_never_decreases() {
+        let mut pmu_virtualizer = PmuVirtualizer::default();
+        let (mut mcycle, mut last_cycle) = (10, 0);
+        // The number of cycles the confidential hart executes before it reads the counter and whether it is descheduled afterwards.
+        for (executed_cycles, is_descheduled) in [(0, false), (10, true), (1, false), (0, true), (0, false), (5_000, true)] {
+            mcycle += executed_cycles;
+            set_mcycle(mcycle);
+            let cycle = pmu_virtualizer.read_cycle_csr();
+            assert!(cycle >= last_cycle);
+            last_cycle = cycle;
+            if is_descheduled {
+                pmu_virtualizer.pause();
+                mcycle += 1_000_000;
+                set_mcycle(mcycle);
+                pmu_virtualizer.resume();
+            }
+        }
+        assert_eq!(last_cycle, 5_011);
+    }
+
+    #[test]
+    fn cycle_csrs_of_confidential_vms_are_independent() {
+        let mut first_pmu_virtualizer = PmuVirtualizer::default();
+        let mut second_pmu_virtualizer = PmuVirtualizer::default();
+        set_mcycle(100);
+        first_pmu_virtualizer.read_cycle_csr();
+        set_mcycle(300);
+        first_pmu_virtualizer.pause();
+        second_pmu_virtualizer.read_cycle_csr();
+        set_mcycle(350);
+        assert_eq!(second_pmu_virtualizer.read_cycle_csr(), 50);
+        second_pmu_virtualizer.pause();
+        first_pmu_virtualizer.resume();
+        set_mcycle(400);
+        assert_eq!(first_pmu_virtualizer.read_cycle_csr(), 250);
+    }
+}
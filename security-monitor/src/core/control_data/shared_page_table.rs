@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::PageSize;
+use crate::error::Error;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// The registry of pages that a confidential VM currently shares with the hypervisor, indexed by their guest physical addresses.
+/// The page table of the confidential VM maps shared pages too, but the registry answers whether a region overlaps any shared page
+/// without a page walk per page size, and lists all shared pages when the confidential VM is torn down.
+pub struct SharedPageTable {
+    shared_pages: BTreeMap<usize, PageSize>,
+}
+
+impl SharedPageTable {
+    pub fn empty() -> Self {
+        Self { shared_pages: BTreeMap::new() }
+    }
+
+    /// Returns error if any part of the page at the given address overlaps a page that is already shared. A page shared with a
+    /// different size, e.g., a 4KiB page inside a shared 2MiB page, is rejected as well.
+    pub fn ensure_not_shared(&self, address: ConfidentialVmPhysicalAddress, page_size: PageSize) -> Result<(), Error> {
+        let start = address.usize();
+        let end = start.checked_add(page_size.in_bytes()).ok_or(Error::InvalidArgument())?;
+        let overlaps_preceding_page = self
+            .shared_pages
+            .range(..=start)
+            .next_back()
+            .is_some_and(|(shared_start, shared_size)| shared_start + shared_size.in_bytes() > start);
+        let overlaps_following_page = self.shared_pages.range(start..end).next().is_some();
+        assure_not!(overlaps_preceding_page || overlaps_following_page, Error::PageAlreadyShared())
+    }
+
+    /// Records the page as shared. Returns error if it overlaps a page that is already shared.
+    pub fn insert(&mut self, address: ConfidentialVmPhysicalAddress, page_size: PageSize) -> Result<(), Error> {
+        self.ensure_not_shared(address, page_size)?;
+        self.shared_pages.insert(address.usize(), page_size);
+        Ok(())
+    }
+
+    /// Removes the record of the page shared at the given address. Returns error if no shared page starts at this address.
+    pub fn remove(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<PageSize, Error> {
+        self.shared_pages.remove(&address.usize()).ok_or(Error::AddressTranslationFailed())
+    }
+
+    /// Removes all records and returns the addresses of the shared pages in the ascending order.
+    pub fn drain(&mut self) -> Vec<ConfidentialVmPhysicalAddress> {
+        core::mem::take(&mut self.shared_pages).into_keys().map(ConfidentialVmPhysicalAddress::new).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARED_HUGE_PAGE: usize = 0x8020_0000;
+    const SHARED_PAGE: usize = 0x8000_1000;
+
+    fn address(address: usize) -> ConfidentialVmPhysicalAddress {
+        ConfidentialVmPhysicalAddress::new(address)
+    }
+
+    fn drain(shared_page_table: &mut SharedPageTable) -> Vec<usize> {
+        shared_page_table.drain().iter().map(|address| address.usize()).collect()
+    }
+
+    fn shared_page_table() -> SharedPageTable {
+        let mut shared_page_table = SharedPageTable::empty();
+        shared_page_table.insert(address(SHARED_HUGE_PAGE), PageSize::Size2MiB).unwrap();
+        shared_page_table.insert(address(SHARED_PAGE), PageSize::Size4KiB).unwrap();
+        shared_page_table
+    }
+
+    #[test]
+    fn overlapping_pages_are_rejected() {
+        let mut shared_page_table = shared_page_table();
+        for (page_address, page_size) in [
+            (SHARED_PAGE, PageSize::Size4KiB),
+            (SHARED_HUGE_PAGE, PageSize::Size4KiB),
+            (SHARED_HUGE_PAGE + 0x1f_f000, PageSize::Size4KiB),
+            (0x8000_0000, PageSize::Size2MiB),
+        ] {
+            assert!(matches!(shared_page_table.insert(address(page_address), page_size), Err(Error::PageAlreadyShared())));
+        }
+        assert_eq!(drain(&mut shared_page_table), [SHARED_PAGE, SHARED_HUGE_PAGE]);
+    }
+
+    #[test]
+    fn adjacent_pages_are_not_overlapping() {
+        let shared_page_table = shared_page_table();
+        for page_address in [SHARED_PAGE - 0x1000, SHARED_PAGE + 0x1000, SHARED_HUGE_PAGE - 0x1000, SHARED_HUGE_PAGE + 0x20_0000] {
+            assert!(shared_page_table.ensure_not_shared(address(page_address), PageSize::Size4KiB).is_ok());
+        }
+    }
+
+    #[test]
+    fn page_at_end_of_address_space_is_rejected() {
+        let shared_page_table = SharedPageTable::empty();
+        assert!(matches!(
+            shared_page_table.ensure_not_shared(address(usize::MAX & !0xfff), PageSize::Size2MiB),
+            Err(Error::InvalidArgument())
+        ));
+    }
+
+    #[test]
+    fn unshared_page_can_be_shared_again() {
+        let mut shared_page_table = shared_page_table();
+        assert_eq!(shared_page_table.remove(address(SHARED_HUGE_PAGE)).unwrap(), PageSize::Size2MiB);
+        assert!(matches!(shared_page_table.remove(address(SHARED_HUGE_PAGE)), Err(Error::AddressTranslationFailed())));
+        shared_page_table.insert(address(SHARED_HUGE_PAGE + 0x1000), PageSize::Size4KiB).unwrap();
+    }
+
+    #[test]
+    fn only_start_of_shared_page_can_be_unshared() {
+        let mut shared_page_table = shared_page_table();
+        assert!(matches!(shared_page_table.remove(address(SHARED_HUGE_PAGE + 0x1000)), Err(Error::AddressTranslationFailed())));
+        assert!(shared_page_table.ensure_not_shared(address(SHARED_HUGE_PAGE + 0x1000), PageSize::Size4KiB).is_err());
+    }
+
+    #[test]
+    fn teardown_drains_all_shared_pages() {
+        let mut shared_page_table = shared_page_table();
+        assert_eq!(drain(&mut shared_page_table), [SHARED_PAGE, SHARED_HUGE_PAGE]);
+        assert!(drain(&mut shared_page_table).is_empty());
+        shared_page_table.insert(address(SHARED_PAGE), PageSize::Size4KiB).unwrap();
+    }
+}
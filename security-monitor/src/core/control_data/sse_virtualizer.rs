@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::transformations::{SseInterruptedState, SseRequest, SseResult};
+use crate::error::Error;
+
+/// Virtualizes the SBI supervisor software events (SSE) extension for a confidential hart.
+///
+/// Events are delivered by diverting the confidential hart's execution to the handler it registered, so the security monitor must own
+/// the delivery: the hypervisor cannot change the confidential hart's program counter. Only local events are supported. The RAS event
+/// is reserved for errors reported by the trusted firmware, and the software event can be injected by the confidential hart itself.
+pub struct SseVirtualizer {
+    events: [SseEvent; Self::NUMBER_OF_EVENTS],
+    // Index of the event whose handler currently executes together with the state it interrupted. Handlers do not nest.
+    running_event: Option<(usize, SseInterruptedState)>,
+    is_masked: bool,
+}
+
+impl Default for SseVirtualizer {
+    fn default() -> Self {
+        // Following the SSE specification, events are masked until the supervisor declares it is ready to handle them.
+        Self { events: [SseEvent::default(); Self::NUMBER_OF_EVENTS], running_event: None, is_masked: true }
+    }
+}
+
+impl SseVirtualizer {
+    // Supported event ids ordered by their priority. Lower index means higher priority.
+    const SUPPORTED_EVENTS: [usize; 2] = [Self::LOCAL_RAS_EVENT_ID, Self::LOCAL_SOFTWARE_EVENT_ID];
+    const NUMBER_OF_EVENTS: usize = Self::SUPPORTED_EVENTS.len();
+    const LOCAL_RAS_EVENT_ID: usize = 0x0000_0000;
+    const LOCAL_SOFTWARE_EVENT_ID: usize = 0xffff_0000;
+
+    /// Executes the SSE call on virtual events of the confidential hart with the given id. Returns error if the call is not supported,
+    /// refers to an event that is not exposed to confidential VMs, or is not allowed in the current state of the event.
+    pub fn handle(&mut self, request: SseRequest, confidential_hart_id: usize) -> Result<SseResult, Error> {
+        match request {
+            SseRequest::Register { event_id, entry_pc, entry_arg } => {
+                // Handlers are entered with a jump, so the entry point must be aligned to the smallest instruction size.
+                assure!(entry_pc % 2 == 0, Error::AddressNotAligned())?;
+                let event = self.event_mut(event_id)?;
+                assure!(event.state == SseEventState::Unused, Error::InvalidSseEventState())?;
+                *event = SseEvent { state: SseEventState::Registered, entry_pc, entry_arg, is_pending: event.is_pending };
+            }
+            SseRequest::Unregister { event_id } => {
+                let event = self.event_mut(event_id)?;
+                assure!([SseEventState::Registered, SseEventState::Enabled].contains(&event.state), Error::InvalidSseEventState())?;
+                event.state = SseEventState::Unused;
+            }
+            SseRequest::Enable { event_id } => self.event_mut(event_id)?.transition(SseEventState::Registered, SseEventState::Enabled)?,
+            SseRequest::Disable { event_id } => self.event_mut(event_id)?.transition(SseEventState::Enabled, SseEventState::Registered)?,
+            SseRequest::Complete => {
+                let (index, interrupted_state) = self.running_event.take().ok_or(Error::InvalidSseEventState())?;
+                self.events[index].state = SseEventState::Enabled;
+                return Ok(SseResult::EventCompleted(interrupted_state));
+            }
+            SseRequest::Inject { event_id, hart_id } => {
+                // Local events target a single hart and we do not forward them to other confidential harts.
+                assure!(hart_id == confidential_hart_id, Error::InvalidHartId())?;
+                self.event_mut(event_id)?.is_pending = true;
+            }
+            SseRequest::HartUnmask => {
+                assure!(self.is_masked, Error::InvalidSseEventState())?;
+                self.is_masked = false;
+            }
+            SseRequest::HartMask => {
+                assure_not!(self.is_masked, Error::InvalidSseEventState())?;
+                self.is_masked = true;
+            }
+            SseRequest::Unsupported => return Err(Error::UnsupportedSseEvent()),
+        }
+        Ok(SseResult::Success)
+    }
+
+    /// Starts the handler of the highest priority pending event, if events are unmasked and no other handler is running. Returns the
+    /// entry point and the argument of the handler.
+    pub fn start_pending_event(&mut self, interrupted_state: SseInterruptedState) -> Option<(usize, usize)> {
+        if self.is_masked || self.running_event.is_some() {
+            return None;
+        }
+        let index = self.events.iter().position(|event| event.is_pending && event.state == SseEventState::Enabled)?;
+        let event = &mut self.events[index];
+        event.is_pending = false;
+        event.state = SseEventState::Running;
+        self.running_event = Some((index, interrupted_state));
+        Some((event.entry_pc, event.entry_arg))
+    }
+
+    fn event_mut(&mut self, event_id: usize) -> Result<&mut SseEvent, Error> {
+        let index = Self::SUPPORTED_EVENTS.iter().position(|id| *id == event_id).ok_or(Error::UnsupportedSseEvent())?;
+        Ok(&mut self.events[index])
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct SseEvent {
+    state: SseEventState,
+    entry_pc: usize,
+    entry_arg: usize,
+    is_pending: bool,
+}
+
+impl SseEvent {
+    fn transition(&mut self, from: SseEventState, to: SseEventState) -> Result<(), Error> {
+        assure!(self.state == from, Error::InvalidSseEventState())?;
+        self.state = to;
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum SseEventState {
+    #[default]
+    Unused,
+    Registered,
+    Enabled,
+    Running,
+}
@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::CSR;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::ConfidentialVmMemoryProtector;
+use crate::error::Error;
+
+/// Accounts the time during which a confidential hart was ready to execute but did not execute because the hypervisor scheduled
+/// something else on the physical hart. The security monitor publishes it in the confidential VM's memory registered with the SBI
+/// steal-time accounting (STA) extension.
+///
+/// The published structure follows the SBI specification, all integers are encoded in little endian:
+///   * 0x00: sequence (u32), odd while the security monitor updates the structure,
+///   * 0x04: flags (u32), always zero,
+///   * 0x08: steal time (u64) in the units of the `time` CSR,
+///   * 0x10: preempted (u8), always zero because the structure is published only when the confidential hart resumes,
+///   * 0x11: padding up to 64 bytes.
+pub struct StealTimeState {
+    address: ConfidentialVmPhysicalAddress,
+    sequence: u32,
+    steal_time: u64,
+    descheduled_at: Option<usize>,
+}
+
+impl StealTimeState {
+    pub const SIZE_IN_BYTES: usize = 64;
+    const HEADER_SIZE_IN_BYTES: usize = 8;
+
+    pub fn new(address: ConfidentialVmPhysicalAddress) -> Self {
+        Self { address, sequence: 0, steal_time: 0, descheduled_at: None }
+    }
+
+    /// Records the time at which the confidential hart stopped executing.
+    pub fn deschedule(&mut self) {
+        self.descheduled_at = Some(CSR.time.read());
+    }
+
+    /// Adds the time elapsed since the confidential hart stopped executing to the steal time and publishes it to the confidential VM.
+    pub fn resume(&mut self, memory_protector: &mut ConfidentialVmMemoryProtector) -> Result<(), Error> {
+        if let Some(descheduled_at) = self.descheduled_at.take() {
+            self.steal_time = self.steal_time.wrapping_add(CSR.time.read().wrapping_sub(descheduled_at) as u64);
+        }
+        self.publish(memory_protector)
+    }
+
+    /// Writes the structure to the confidential VM's memory. Other confidential harts of the confidential VM might read it at the same
+    /// time, so the sequence is odd while the content changes.
+    pub fn publish(&mut self, memory_protector: &mut ConfidentialVmMemoryProtector) -> Result<(), Error> {
+        self.sequence = self.sequence.wrapping_add(1);
+        memory_protector.write_bytes(self.address, &self.to_bytes()[..Self::HEADER_SIZE_IN_BYTES])?;
+        self.sequence = self.sequence.wrapping_add(1);
+        memory_protector.write_bytes(self.address, &self.to_bytes())
+    }
+
+    fn to_bytes(&self) -> [u8; Self::SIZE_IN_BYTES] {
+        let mut bytes = [0u8; Self::SIZE_IN_BYTES];
+        bytes[0x00..0x04].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[0x08..0x10].copy_from_slice(&self.steal_time.to_le_bytes());
+        bytes
+    }
+}
@@ -1,9 +1,12 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::control_data::{ConfidentialVm, ConfidentialVmId};
+use crate::core::control_data::confidential_vm_table::{ConfidentialVmTable, StoredConfidentialVm};
+use crate::core::control_data::{ConfidentialVm, ConfidentialVmBuilder, ConfidentialVmId, HardwareHart};
+use crate::core::memory_protector::VmidAllocator;
+use crate::core::transformations::FinalizeRequest;
 use crate::error::{Error, NOT_INITIALIZED_CONTROL_DATA};
-use alloc::collections::BTreeMap;
+use alloc::boxed::Box;
 use spin::{Mutex, MutexGuard, Once, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// The control data region is located in the confidential memory. It is visible only to the security monitor. The
@@ -14,44 +17,167 @@ use spin::{Mutex, MutexGuard, Once, RwLock, RwLockReadGuard, RwLockWriteGuard};
 pub static CONTROL_DATA: Once<RwLock<ControlData>> = Once::new();
 
 pub struct ControlData {
-    confidential_vms: BTreeMap<ConfidentialVmId, Mutex<ConfidentialVm>>,
+    // Confidential VMs in all stages of their lifecycle. The hypervisor refers to them on every resume, so they are indexed by
+    // their identifiers in constant time.
+    confidential_vms: ConfidentialVmTable<StoredConfidentialVm>,
+    // VMIDs are assigned when confidential VMs become runnable and returned when they are terminated. Harts resuming confidential VMs
+    // hold only the read lock of the control data, so the allocator has its own lock.
+    vmid_allocator: Mutex<VmidAllocator>,
 }
 
 impl ControlData {
     pub fn new() -> Self {
-        Self { confidential_vms: BTreeMap::new() }
+        Self { confidential_vms: ConfidentialVmTable::empty(), vmid_allocator: Mutex::new(VmidAllocator::empty()) }
+    }
+
+    /// Sizes the VMID space after the number of VMID bits implemented by the hardware. Must be called during the boot before any
+    /// confidential VM is created.
+    pub fn initialize_vmid_allocator(vmid_length: usize) -> Result<(), Error> {
+        ControlData::try_write(|control_data| {
+            control_data.vmid_allocator.lock().initialize(vmid_length);
+            Ok(())
+        })
     }
 
     pub fn unique_id(&self) -> Result<ConfidentialVmId, Error> {
-        self.confidential_vms
-            .keys()
-            .max()
-            .map(|v| v.usize().checked_add(1))
-            .unwrap_or(Some(0))
-            .and_then(|max_id| Some(ConfidentialVmId::new(max_id)))
-            .ok_or(Error::TooManyConfidentialVms())
+        // Slots of confidential VMs in teardown are not free, so their identifiers are not reused while the hardware might still cache
+        // their translations.
+        self.confidential_vms.free_id()
     }
 
-    pub fn insert_confidential_vm(&mut self, confidential_vm: ConfidentialVm) -> Result<ConfidentialVmId, Error> {
+    pub fn insert_confidential_vm(&mut self, mut confidential_vm: ConfidentialVm) -> Result<ConfidentialVmId, Error> {
         let id = confidential_vm.confidential_vm_id();
-        match self.confidential_vms.contains_key(&id) {
-            false => {
-                self.confidential_vms.insert(id, Mutex::new(confidential_vm));
-                Ok(id)
+        confidential_vm.memory_protector_mut().assign_vmid(&mut self.vmid_allocator.lock());
+        self.confidential_vms.insert(id, StoredConfidentialVm::Finalized(Box::new(Mutex::new(confidential_vm))))?;
+        Ok(id)
+    }
+
+    pub fn insert_confidential_vm_builder(&mut self, builder: ConfidentialVmBuilder) -> Result<ConfidentialVmId, Error> {
+        let id = builder.confidential_vm_id();
+        self.confidential_vms.insert(id, StoredConfidentialVm::UnderConstruction(Box::new(Mutex::new(builder))))?;
+        Ok(id)
+    }
+
+    /// Turns the confidential VM under construction into a runnable confidential VM. Returns error if there is no such confidential VM
+    /// under construction or it is incomplete. An incomplete confidential VM stays under construction, while a confidential VM whose
+    /// launch digest differs from the expected one is destroyed. If the request carries the entry of the boot hart, the boot hart is
+    /// started before the confidential VM is finalized.
+    pub fn finalize_confidential_vm(request: &FinalizeRequest) -> Result<(), Error> {
+        let confidential_vm_id = request.confidential_vm_id();
+        ControlData::try_write(|control_data| {
+            let ControlData { confidential_vms, vmid_allocator } = &mut **control_data;
+            match confidential_vms.get(confidential_vm_id)? {
+                StoredConfidentialVm::UnderConstruction(builder) => {
+                    let mut builder = builder.lock();
+                    if let Some((start_address, opaque)) = request.boot_hart_entry() {
+                        builder.start_boot_hart(start_address, opaque)?;
+                    }
+                    builder.assure_finalizable()?
+                }
+                StoredConfidentialVm::Finalized(_) => return Err(Error::ConfidentialVmAlreadyFinalized()),
+                StoredConfidentialVm::InTeardown(_) => return Err(Error::InvalidConfidentialVmId()),
             }
-            true => Err(Error::InvalidConfidentialVmId()),
-        }
+            confidential_vms.replace(confidential_vm_id, |stored_confidential_vm| match stored_confidential_vm {
+                StoredConfidentialVm::UnderConstruction(builder) => {
+                    let mut confidential_vm = (*builder).into_inner().finalize(request.expected_launch_digest())?;
+                    confidential_vm.memory_protector_mut().assign_vmid(&mut vmid_allocator.lock());
+                    Ok(StoredConfidentialVm::Finalized(Box::new(Mutex::new(confidential_vm))))
+                }
+                _ => Err(Error::InvalidConfidentialVmId()),
+            })?;
+            debug!("ConfidentialVM[{:?}] finalized", confidential_vm_id);
+            Ok(())
+        })
+    }
+
+    /// Assigns the confidential hart to the hardware hart, see `ConfidentialVm::steal_confidential_hart`.
+    pub fn steal_confidential_hart(
+        confidential_vm_id: ConfidentialVmId, confidential_hart_id: usize, hardware_hart: &mut HardwareHart,
+    ) -> Result<(), Error> {
+        ControlData::try_read(|control_data| {
+            let mut confidential_vm = control_data.confidential_vm(confidential_vm_id)?;
+            confidential_vm.steal_confidential_hart(confidential_hart_id, hardware_hart, &control_data.vmid_allocator)
+        })
     }
 
     pub fn confidential_vm(&self, id: ConfidentialVmId) -> Result<MutexGuard<'_, ConfidentialVm>, Error> {
-        self.confidential_vms.get(&id).ok_or(Error::InvalidConfidentialVmId()).and_then(|v| Ok(v.lock()))
+        match self.confidential_vms.get(id)? {
+            StoredConfidentialVm::Finalized(confidential_vm) => Ok(confidential_vm.lock()),
+            _ => Err(Error::InvalidConfidentialVmId()),
+        }
     }
 
-    pub fn remove_confidential_vm(confidential_vm_id: ConfidentialVmId) -> Result<Mutex<ConfidentialVm>, Error> {
+    /// Removes the confidential VM from the set of confidential VMs that can be resumed and starts its teardown. A confidential VM under
+    /// construction has never executed, so it is destroyed immediately. Requests that confidential harts sent to the hypervisor are
+    /// cancelled before the teardown starts (see `ConfidentialVm::cancel_pending_requests`). The memory of the confidential VM is not
+    /// released here because it might take long time. Instead, the hypervisor reclaims it in batches using
+    /// `ControlData::reclaim_confidential_vm_memory`.
+    pub fn remove_confidential_vm(confidential_vm_id: ConfidentialVmId) -> Result<(), Error> {
         ControlData::try_write(|control_data| {
-            assure!(control_data.confidential_vm(confidential_vm_id)?.are_all_harts_shutdown(), Error::HartAlreadyRunning())?;
+            match control_data.confidential_vms.get(confidential_vm_id)? {
+                StoredConfidentialVm::UnderConstruction(_) => {
+                    control_data.confidential_vms.remove(confidential_vm_id)?;
+                    return Ok(());
+                }
+                StoredConfidentialVm::Finalized(confidential_vm) => {
+                    let mut confidential_vm = confidential_vm.lock();
+                    confidential_vm.cancel_pending_requests()?;
+                    confidential_vm.unshare_all_pages();
+                    confidential_vm.memory_protector_mut().release_vmid(&mut control_data.vmid_allocator.lock());
+                }
+                StoredConfidentialVm::InTeardown(_) => return Err(Error::InvalidConfidentialVmId()),
+            }
+            control_data.confidential_vms.replace(confidential_vm_id, |stored_confidential_vm| match stored_confidential_vm {
+                StoredConfidentialVm::Finalized(confidential_vm) => Ok(StoredConfidentialVm::InTeardown(confidential_vm)),
+                _ => Err(Error::InvalidConfidentialVmId()),
+            })?;
             debug!("ConfidentialVM[{:?}] removed from the control data structure", confidential_vm_id);
-            control_data.confidential_vms.remove(&confidential_vm_id).ok_or(Error::InvalidConfidentialVmId())
+            Ok(())
+        })
+    }
+
+    /// Reclaims at most `max_number_of_pages` pages of a confidential VM that is in teardown. Returns true when the confidential VM's
+    /// memory has been entirely reclaimed. In such a case, the confidential VM's control structures are deallocated too and its
+    /// identifier becomes stale.
+    ///
+    /// Only the lock of the confidential VM is held while pages are zeroized, so other harts can concurrently access the control data.
+    /// No lock is held while this hart waits for other harts to fence translations of the confidential VM. The mscratch register must
+    /// contain the value expected by OpenSBI, see `PendingTlbShootdown::complete`.
+    pub fn reclaim_confidential_vm_memory(confidential_vm_id: ConfidentialVmId, max_number_of_pages: usize) -> Result<bool, Error> {
+        let tlb_shootdown = ControlData::try_read(|control_data| match control_data.confidential_vms.get(confidential_vm_id)? {
+            StoredConfidentialVm::InTeardown(confidential_vm) => Ok(confidential_vm.lock().memory_protector_mut().fence_on_all_harts()),
+            _ => Err(Error::InvalidConfidentialVmId()),
+        })?;
+        tlb_shootdown.complete()?;
+        let is_reclaimed = ControlData::try_read(|control_data| match control_data.confidential_vms.get(confidential_vm_id)? {
+            StoredConfidentialVm::InTeardown(confidential_vm) => Ok(confidential_vm.lock().reclaim_memory(max_number_of_pages)),
+            _ => Err(Error::InvalidConfidentialVmId()),
+        })?;
+        if is_reclaimed {
+            ControlData::try_write(|control_data| {
+                control_data.confidential_vms.remove(confidential_vm_id)?;
+                debug!("ConfidentialVM[{:?}] memory reclaimed", confidential_vm_id);
+                Ok(())
+            })?;
+        }
+        Ok(is_reclaimed)
+    }
+
+    /// Returns true if the confidential memory holds data of any confidential VM, including confidential VMs under construction and in
+    /// teardown.
+    pub fn contains_confidential_vm_data() -> Result<bool, Error> {
+        ControlData::try_read(|control_data| Ok(!control_data.confidential_vms.is_empty()))
+    }
+
+    /// Returns true if any confidential VM has mapped a shared page located in the given region of the non-confidential memory.
+    pub fn is_shared_with_confidential_vms(memory_start: usize, memory_end: usize) -> Result<bool, Error> {
+        ControlData::try_read(|control_data| {
+            Ok(control_data.confidential_vms.iter().any(|stored_confidential_vm| match stored_confidential_vm {
+                StoredConfidentialVm::Finalized(confidential_vm) => {
+                    confidential_vm.lock().memory_protector().contains_shared_page(memory_start, memory_end)
+                }
+                _ => false,
+            }))
         })
     }
 
@@ -70,8 +196,89 @@ impl ControlData {
         Self::try_read(|mr| op(mr.confidential_vm(confidential_vm_id)?))
     }
 
+    /// Executes the operation on the confidential VM under construction. Returns `Error::ConfidentialVmAlreadyFinalized` if the
+    /// confidential VM has been finalized, so that its content can no longer be changed.
+    pub fn try_confidential_vm_builder<F, O>(confidential_vm_id: ConfidentialVmId, op: O) -> Result<F, Error>
+    where O: FnOnce(MutexGuard<'_, ConfidentialVmBuilder>) -> Result<F, Error> {
+        Self::try_read(|control_data| match control_data.confidential_vms.get(confidential_vm_id)? {
+            StoredConfidentialVm::UnderConstruction(builder) => op(builder.lock()),
+            StoredConfidentialVm::Finalized(_) => Err(Error::ConfidentialVmAlreadyFinalized()),
+            StoredConfidentialVm::InTeardown(_) => Err(Error::InvalidConfidentialVmId()),
+        })
+    }
+
     pub fn try_confidential_vm_mut<F, O>(confidential_vm_id: ConfidentialVmId, op: O) -> Result<F, Error>
     where O: FnOnce(MutexGuard<'_, ConfidentialVm>) -> Result<F, Error> {
         Self::try_read(|m| op(m.confidential_vm(confidential_vm_id)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::{HartLifecycleState, HgatpMode};
+    use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+    use crate::core::page_allocator::test_memory::{setup, CONTENT, PAGE_SIZE};
+    use crate::core::page_allocator::PageAllocator;
+
+    const MEMORY_START: usize = 0x8000_0000;
+
+    /// Creates a confidential VM under construction that owns a single page of memory, copied from the beginning of the non-confidential
+    /// memory, and whose boot hart starts at the beginning of this page.
+    fn construct_confidential_vm(non_confidential_memory_start: usize) -> ConfidentialVmId {
+        CONTROL_DATA.call_once(|| RwLock::new(ControlData::new()));
+        HgatpMode::set_supported_modes(&[HgatpMode::Sv39x4]);
+        let confidential_vm_id = ControlData::try_write(|control_data| {
+            let builder = ConfidentialVmBuilder::new(control_data.unique_id()?, false, false, Some(1 << 32))?;
+            control_data.insert_confidential_vm_builder(builder)
+        })
+        .unwrap();
+        ControlData::try_confidential_vm_builder(confidential_vm_id, |mut builder| {
+            builder.add_memory_region(ConfidentialVmPhysicalAddress::new(MEMORY_START), non_confidential_memory_start, 1)?;
+            builder.add_hart(0, MEMORY_START, 0)
+        })
+        .unwrap();
+        confidential_vm_id
+    }
+
+    /// Destroys the confidential VM together with its memory. It has never executed, so there are no translations to fence.
+    fn destroy(confidential_vm_id: ConfidentialVmId) {
+        ControlData::try_write(|control_data| control_data.confidential_vms.remove(confidential_vm_id).map(|_| ())).unwrap();
+    }
+
+    #[test]
+    fn staged_construction_produces_runnable_confidential_vm() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let confidential_vm_id = construct_confidential_vm(non_confidential_memory_start);
+        ControlData::finalize_confidential_vm(&FinalizeRequest::new(confidential_vm_id.usize(), 0).unwrap()).unwrap();
+        ControlData::try_confidential_vm(confidential_vm_id, |confidential_vm| {
+            assert!(confidential_vm.confidential_hart_lifecycle_state(0)? == HartLifecycleState::StartPending);
+            let page_address = confidential_vm.memory_protector().translate(ConfidentialVmPhysicalAddress::new(MEMORY_START))?;
+            // Safety: the address is the start of a page owned by the confidential VM, which is not executing.
+            assert_eq!(unsafe { (page_address.as_usize() as *const usize).read() }, CONTENT);
+            Ok(())
+        })
+        .unwrap();
+        destroy(confidential_vm_id);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn finalized_confidential_vm_cannot_be_changed() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let confidential_vm_id = construct_confidential_vm(non_confidential_memory_start);
+        let request = FinalizeRequest::new(confidential_vm_id.usize(), 0).unwrap();
+        ControlData::finalize_confidential_vm(&request).unwrap();
+
+        let address = ConfidentialVmPhysicalAddress::new(MEMORY_START + PAGE_SIZE.in_bytes());
+        let result = ControlData::try_confidential_vm_builder(confidential_vm_id, |mut builder| {
+            builder.add_memory_region(address, non_confidential_memory_start, 1)
+        });
+        assert!(matches!(result, Err(Error::ConfidentialVmAlreadyFinalized())));
+        let result = ControlData::try_confidential_vm_builder(confidential_vm_id, |mut builder| builder.add_hart(1, 0, 0));
+        assert!(matches!(result, Err(Error::ConfidentialVmAlreadyFinalized())));
+        assert!(matches!(ControlData::finalize_confidential_vm(&request), Err(Error::ConfidentialVmAlreadyFinalized())));
+        destroy(confidential_vm_id);
+    }
+}
@@ -0,0 +1,86 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// Virtualizes the time observed by a confidential hart. Every time the confidential hart is scheduled, its time advances by a random
+/// number of ticks, so gaps in the time caused by descheduling do not align exactly with the scheduling of co-located VMs. The sum of
+/// all perturbations is the epoch, which is added to the `htimedelta` inherited from the hypervisor. The epoch only grows, so the
+/// confidential hart's time never goes backwards.
+#[derive(Default)]
+pub struct TimeVirtualizer {
+    epoch: usize,
+}
+
+impl TimeVirtualizer {
+    /// The upper bound (exclusive) of the number of ticks by which the time advances at every context switch.
+    pub const MAX_PERTURBATION: usize = 1 << 8;
+
+    /// Advances the epoch by the perturbation derived from the given random value.
+    pub fn perturb(&mut self, random_value: usize) {
+        self.epoch = self.epoch.wrapping_add(random_value % Self::MAX_PERTURBATION);
+    }
+
+    /// Returns the value of `htimedelta` with which the hardware presents the virtualized time to the confidential hart, given the
+    /// `htimedelta` inherited from the hypervisor.
+    pub fn virtualized_htimedelta(&self, htimedelta: usize) -> usize {
+        htimedelta.wrapping_add(self.epoch)
+    }
+
+    /// Returns the inherited `htimedelta`, given the value read from the hardware while the confidential hart executed.
+    pub fn inherited_htimedelta(&self, virtualized_htimedelta: usize) -> usize {
+        virtualized_htimedelta.wrapping_sub(self.epoch)
+    }
+
+    /// Returns the time observed by the confidential hart, given the physical time and the inherited `htimedelta`.
+    pub fn time(&self, time: usize, htimedelta: usize) -> usize {
+        time.wrapping_add(self.virtualized_htimedelta(htimedelta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTIMEDELTA: usize = 0x1000;
+
+    #[test]
+    fn perturbation_is_bounded() {
+        let mut time_virtualizer = TimeVirtualizer::default();
+        time_virtualizer.perturb(usize::MAX);
+        assert_eq!(time_virtualizer.time(0, 0), usize::MAX % TimeVirtualizer::MAX_PERTURBATION);
+        assert!(time_virtualizer.time(0, 0) < TimeVirtualizer::MAX_PERTURBATION);
+    }
+
+    #[test]
+    fn time_never_goes_backwards() {
+        let mut time_virtualizer = TimeVirtualizer::default();
+        let mut last_observed_time = 0;
+        for (time, random_value) in [(10, 0), (10, 3), (11, 0xff), (500, 0x1234), (500, 0)] {
+            time_virtualizer.perturb(random_value);
+            let observed_time = time_virtualizer.time(time, HTIMEDELTA);
+            assert!(observed_time >= last_observed_time);
+            last_observed_time = observed_time;
+        }
+    }
+
+    #[test]
+    fn inherited_htimedelta_is_preserved_across_context_switches() {
+        let mut time_virtualizer = TimeVirtualizer::default();
+        let mut htimedelta = HTIMEDELTA;
+        for random_value in [7, 0x80, 0xffff] {
+            time_virtualizer.perturb(random_value);
+            let virtualized_htimedelta = time_virtualizer.virtualized_htimedelta(htimedelta);
+            assert_ne!(virtualized_htimedelta, htimedelta);
+            htimedelta = time_virtualizer.inherited_htimedelta(virtualized_htimedelta);
+            assert_eq!(htimedelta, HTIMEDELTA);
+        }
+    }
+
+    #[test]
+    fn emulated_time_matches_hardware_time() {
+        let mut time_virtualizer = TimeVirtualizer::default();
+        time_virtualizer.perturb(42);
+        let time = 0x10_0000;
+        assert_eq!(time_virtualizer.time(time, HTIMEDELTA), time + time_virtualizer.virtualized_htimedelta(HTIMEDELTA));
+    }
+}
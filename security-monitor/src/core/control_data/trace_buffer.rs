@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// An event in the execution of the security monitor on a physical hart. The hypervisor can read the trace, so events carry only
+/// information that the hypervisor observes anyway, e.g., the cause of its own traps or the address of an MMIO access it emulates.
+/// In particular, traps of confidential harts handled entirely by the security monitor are not traced.
+#[derive(Clone, Copy)]
+pub enum TraceEvent {
+    /// The hypervisor trapped into the security monitor with the given `mcause`.
+    TrapEntry { cause: usize },
+    /// The hypervisor or a non-confidential VM made an SBI call that trapped into the security monitor.
+    SbiCall { extension_id: usize, function_id: usize },
+    /// A confidential hart accessed an MMIO region and the access is forwarded to the hypervisor for emulation.
+    MmioFault { address: usize, is_store: bool },
+    /// The physical hart switched between the hypervisor and a confidential hart.
+    ContextSwitch { confidential_vm_id: usize, confidential_hart_id: usize, into_confidential_hart: bool },
+}
+
+impl TraceEvent {
+    /// Encodes the event as its kind followed by two arguments.
+    fn encode(&self) -> [usize; 3] {
+        match self {
+            Self::TrapEntry { cause } => [1, *cause, 0],
+            Self::SbiCall { extension_id, function_id } => [2, *extension_id, *function_id],
+            Self::MmioFault { address, is_store } => [3, *address, *is_store as usize],
+            Self::ContextSwitch { confidential_vm_id, confidential_hart_id, into_confidential_hart } => {
+                [4 | ((*into_confidential_hart as usize) << 8), *confidential_vm_id, *confidential_hart_id]
+            }
+        }
+    }
+}
+
+/// A ring buffer retaining the `N` most recent trace events of a physical hart. Every physical hart has its own buffer, so recording
+/// an event does not require any synchronization and never stalls the execution.
+pub struct TraceBuffer<const N: usize> {
+    events: [Option<TraceEvent>; N],
+    // The total number of events recorded since the boot. The next event is stored at `number_of_records % N`.
+    number_of_records: usize,
+}
+
+impl<const N: usize> TraceBuffer<N> {
+    /// The size of a single encoded event, see `encoded_events`.
+    pub const ENTRY_SIZE_IN_BYTES: usize = 4 * core::mem::size_of::<usize>();
+    pub const SIZE_IN_BYTES: usize = N * Self::ENTRY_SIZE_IN_BYTES;
+
+    pub const fn empty() -> Self {
+        Self { events: [None; N], number_of_records: 0 }
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        self.events[self.number_of_records % N] = Some(event);
+        self.number_of_records = self.number_of_records.wrapping_add(1);
+    }
+
+    pub fn number_of_records(&self) -> usize {
+        self.number_of_records
+    }
+
+    /// Returns the retained events from the oldest to the most recent one. Each event is encoded in four words: its sequence number
+    /// counting from the boot, its kind, and two arguments. The sequence numbers allow the reader to detect events that were
+    /// overwritten before the buffer was read.
+    pub fn encoded_events(&self) -> impl Iterator<Item = [usize; 4]> + '_ {
+        let number_of_retained_events = core::cmp::min(self.number_of_records, N);
+        let oldest = self.number_of_records.wrapping_sub(number_of_retained_events);
+        (0..number_of_retained_events).filter_map(move |index| {
+            let sequence_number = oldest.wrapping_add(index);
+            let [kind, first_argument, second_argument] = self.events[sequence_number % N]?.encode();
+            Some([sequence_number, kind, first_argument, second_argument])
+        })
+    }
+}
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::specification::*;
+use crate::core::architecture::HartArchitecturalState;
+
+/// Defines which exceptions (`medeleg` and `hedeleg`) and interrupts (`mideleg` and `hideleg`) are delegated to the VS-mode of a
+/// confidential hart. The delegation is applied every time a confidential hart is scheduled on a physical hart, so it never depends on
+/// values left by the hypervisor. The hypervisor's delegation is part of the hardware hart's state, which is loaded back to the
+/// physical hart when the confidential hart stops executing on it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrapDelegation {
+    exceptions: usize,
+    interrupts: usize,
+}
+
+impl TrapDelegation {
+    /// Exceptions delegated to the confidential VM are the ones that the guest OS handles without the security monitor or the
+    /// hypervisor observing them: misaligned accesses, access faults and page faults of the VS-stage translation, breakpoints, and
+    /// environment calls from VU-mode. Guest-page faults, virtual instructions, and environment calls from VS-mode are not delegated
+    /// because the security monitor handles or forwards them. Illegal instruction exceptions are not delegated because the security
+    /// monitor emulates accesses to some CSRs and because floating-point instructions executed while the floating-point unit is off
+    /// signal the first use of the unit, see the lazy floating-point context switch. All other illegal instructions are reflected to the
+    /// confidential hart. Interrupts delegated to the confidential VM are the VS-level software, timer, and external interrupts. All
+    /// other interrupts trap in the security monitor.
+    pub const CONFIDENTIAL_HART: Self = Self {
+        exceptions: (1 << CAUSE_MISALIGNED_FETCH)
+            | (1 << CAUSE_FETCH_ACCESS)
+            | (1 << CAUSE_BREAKPOINT)
+            | (1 << CAUSE_MISALIGNED_LOAD)
+            | (1 << CAUSE_LOAD_ACCESS)
+            | (1 << CAUSE_MISALIGNED_STORE)
+            | (1 << CAUSE_STORE_ACCESS)
+            | (1 << CAUSE_USER_ECALL)
+            | (1 << CAUSE_FETCH_PAGE_FAULT)
+            | (1 << CAUSE_LOAD_PAGE_FAULT)
+            | (1 << CAUSE_STORE_PAGE_FAULT),
+        interrupts: MIE_VSSIP_MASK | MIE_VSTIP_MASK | MIE_VSEIP_MASK,
+    };
+
+    /// Writes the delegation to the state, from which it is loaded to the physical hart when the hart is scheduled.
+    pub fn apply(&self, state: &mut HartArchitecturalState) {
+        state.medeleg = self.exceptions;
+        state.hedeleg = self.exceptions;
+        state.mideleg = self.interrupts;
+        state.hideleg = self.interrupts;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HYPERVISOR_EXCEPTIONS: usize = 0xb1ff;
+    const HYPERVISOR_INTERRUPTS: usize = 0x444;
+
+    fn hypervisor_state(id: usize) -> HartArchitecturalState {
+        let mut state = HartArchitecturalState::empty(id);
+        state.medeleg = HYPERVISOR_EXCEPTIONS;
+        state.hedeleg = HYPERVISOR_EXCEPTIONS;
+        state.mideleg = HYPERVISOR_INTERRUPTS;
+        state.hideleg = HYPERVISOR_INTERRUPTS;
+        state
+    }
+
+    #[test]
+    fn scheduling_confidential_hart_forces_delegation() {
+        // The confidential hart's state is inherited from the hypervisor, e.g., during the VM promotion.
+        let mut confidential_hart_state = hypervisor_state(1);
+        TrapDelegation::CONFIDENTIAL_HART.apply(&mut confidential_hart_state);
+        let exceptions = TrapDelegation::CONFIDENTIAL_HART.exceptions;
+        assert_eq!((confidential_hart_state.medeleg, confidential_hart_state.hedeleg), (exceptions, exceptions));
+        let interrupts = MIE_VSSIP_MASK | MIE_VSTIP_MASK | MIE_VSEIP_MASK;
+        assert_eq!((confidential_hart_state.mideleg, confidential_hart_state.hideleg), (interrupts, interrupts));
+    }
+
+    #[test]
+    fn traps_handled_by_security_monitor_are_not_delegated() {
+        let exceptions = TrapDelegation::CONFIDENTIAL_HART.exceptions;
+        for cause in [
+            CAUSE_ILLEGAL_INSTRUCTION,
+            CAUSE_VIRTUAL_SUPERVISOR_ECALL,
+            CAUSE_FETCH_GUEST_PAGE_FAULT,
+            CAUSE_LOAD_GUEST_PAGE_FAULT,
+            CAUSE_VIRTUAL_INSTRUCTION,
+            CAUSE_STORE_GUEST_PAGE_FAULT,
+        ] {
+            assert_eq!(exceptions & (1 << cause), 0, "cause {}", cause);
+        }
+        assert_eq!(TrapDelegation::CONFIDENTIAL_HART.interrupts & (MIE_SSIP_MASK | MIE_STIP_MASK | MIE_SEIP_MASK), 0);
+    }
+
+    #[test]
+    fn hypervisor_delegation_is_kept_for_exit() {
+        let hardware_hart_state = hypervisor_state(0);
+        let mut confidential_hart_state = hypervisor_state(1);
+        TrapDelegation::CONFIDENTIAL_HART.apply(&mut confidential_hart_state);
+        // The hardware hart's state, which is loaded to the physical hart when the confidential hart exits, is not affected.
+        assert_eq!((hardware_hart_state.medeleg, hardware_hart_state.hedeleg), (HYPERVISOR_EXCEPTIONS, HYPERVISOR_EXCEPTIONS));
+        assert_eq!((hardware_hart_state.mideleg, hardware_hart_state.hideleg), (HYPERVISOR_INTERRUPTS, HYPERVISOR_INTERRUPTS));
+    }
+}
@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// Describes what a confidential hart is waiting for, so that the hypervisor can make scheduling decisions without resuming the
+/// confidential hart only to learn that it cannot make progress. Unlike `HartLifecycleState`, which follows the SBI HSM specification,
+/// the runstate also reflects requests that the confidential hart sent to the hypervisor and that are still waiting for a response.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VcpuRunstate {
+    // The confidential hart either executes on a hardware hart or is ready to be resumed.
+    Running,
+    // The confidential hart made an SBI call that the hypervisor must handle before the confidential hart can continue.
+    BlockedOnSbi,
+    // The confidential hart accessed MMIO and waits for the hypervisor to emulate the access.
+    BlockedOnMmio,
+    // The confidential hart suspended itself and waits for an interrupt or a hypervisor's decision to resume it.
+    Sleeping,
+    // The confidential hart does not execute any code, i.e., it has not been started yet, stopped itself, or has been shutdown.
+    Stopped,
+}
+
+impl VcpuRunstate {
+    /// Returns the value that the security monitor reports to the hypervisor.
+    pub fn code(&self) -> usize {
+        match self {
+            Self::Running => 0,
+            Self::BlockedOnSbi => 1,
+            Self::BlockedOnMmio => 2,
+            Self::Sleeping => 3,
+            Self::Stopped => 4,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::{disable_bits, enable_bits, CSR_HSTATUS_VTW, CSR_MSTATUS_TW};
+
+/// Defines what happens when a confidential hart executes the WFI instruction. The policy is applied every time a confidential hart
+/// is scheduled on a physical hart, so the guest never observes WFI semantics that depend on the state left by the hypervisor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WfiPolicy {
+    // WFI raises a virtual instruction exception that the security monitor handles, giving it a chance to yield the physical hart.
+    TrapToSecurityMonitor,
+    // WFI executes natively and stalls the physical hart until an interrupt becomes pending.
+    PassThrough,
+}
+
+impl WfiPolicy {
+    /// Configures `hstatus.VTW` according to the policy. `mstatus.TW` is always cleared because it takes precedence over
+    /// `hstatus.VTW` and would turn every WFI into an illegal instruction exception.
+    pub fn apply(&self, hstatus: &mut usize, mstatus: &mut usize) {
+        disable_bits(mstatus, 1 << CSR_MSTATUS_TW);
+        match self {
+            Self::TrapToSecurityMonitor => enable_bits(hstatus, 1 << CSR_HSTATUS_VTW),
+            Self::PassThrough => disable_bits(hstatus, 1 << CSR_HSTATUS_VTW),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// Compares two byte slices in time that depends only on their lengths, not on their content. Measurements and MACs must be compared
+/// this way, otherwise the position of the first differing byte leaks to whoever can time the comparison. The lengths are not secret.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b.iter()).fold(0u8, |difference, (x, y)| difference | (x ^ y));
+    // Prevents the compiler from turning the accumulation into an early-exit comparison.
+    core::hint::black_box(difference) == 0
+}
+
+/// Returns `if_true` if the choice is true, `if_false` otherwise, without branching on the choice. Use it instead of `if` when the
+/// choice is derived from secret data.
+pub fn constant_time_select<const N: usize>(choice: bool, if_true: &[u8; N], if_false: &[u8; N]) -> [u8; N] {
+    // All ones if the choice is true, all zeros otherwise. The compiler cannot see through the black box to reintroduce a branch.
+    let mask = core::hint::black_box(0u8.wrapping_sub(choice as u8));
+    core::array::from_fn(|i| (if_true[i] & mask) | (if_false[i] & !mask))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_slices_are_equal() {
+        assert!(constant_time_eq(b"measurement", b"measurement"));
+        assert!(constant_time_eq(&[], &[]));
+    }
+
+    #[test]
+    fn unequal_slices_are_not_equal() {
+        assert!(!constant_time_eq(b"measurement", b"measuremenT"));
+        assert!(!constant_time_eq(b"measurement", b"Measurement"));
+    }
+
+    #[test]
+    fn slices_of_different_lengths_are_not_equal() {
+        assert!(!constant_time_eq(b"measurement", b"measurement2"));
+        assert!(!constant_time_eq(b"measurement", &[]));
+    }
+
+    #[test]
+    fn select_returns_chosen_array() {
+        let if_true = [0xa5; 16];
+        let if_false = [0x5a; 16];
+        assert_eq!(constant_time_select(true, &if_true, &if_false), if_true);
+        assert_eq!(constant_time_select(false, &if_true, &if_false), if_false);
+        assert_eq!(constant_time_select(true, &[], &[]), []);
+    }
+}
@@ -0,0 +1,375 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::crypto::constant_time_eq;
+use crate::core::measurement::Sha512;
+
+pub const ED25519_PUBLIC_KEY_SIZE_IN_BYTES: usize = 32;
+pub const ED25519_SIGNATURE_SIZE_IN_BYTES: usize = 64;
+
+/// Verifies the Ed25519 signature (RFC 8032) of the message. Returns false if the public key is not a valid curve point, the signature
+/// is not canonical, or it does not match the message. Only public data is processed, so the verification does not have to execute in
+/// constant time.
+///
+/// The arithmetic follows TweetNaCl: field elements are represented by 16 limbs of 16 bits and points by extended coordinates.
+pub fn ed25519_verify(
+    public_key: &[u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES], message: &[u8], signature: &[u8; ED25519_SIGNATURE_SIZE_IN_BYTES],
+) -> bool {
+    let (encoded_r, s) = signature.split_at(32);
+    // Rejecting s >= L prevents malleable signatures, i.e., different valid signatures of the same message.
+    if !is_scalar_canonical(s) {
+        return false;
+    }
+    let negated_public_key = match decode_negated_point(public_key) {
+        Some(point) => point,
+        None => return false,
+    };
+
+    let mut hasher = Sha512::default();
+    hasher.update(encoded_r);
+    hasher.update(public_key);
+    hasher.update(message);
+    let k = reduce_scalar(&hasher.finalize());
+
+    // The signature is valid if R = [s]B - [k]A.
+    let mut point = scalar_multiply(&negated_public_key, &k);
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(s);
+    add_points(&mut point, &scalar_multiply(&base_point(), &s_bytes));
+    constant_time_eq(&encode_point(&point), encoded_r)
+}
+
+type FieldElement = [i64; 16];
+type Point = [FieldElement; 4];
+
+const ZERO: FieldElement = [0; 16];
+const ONE: FieldElement = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+const D: FieldElement =
+    [0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898, 0x7779, 0x4079, 0x8cc7, 0xfe73, 0x2b6f, 0x6cee, 0x5203];
+const D2: FieldElement =
+    [0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130, 0xeef3, 0x80f2, 0x198e, 0xfce7, 0x56df, 0xd9dc, 0x2406];
+const BASE_X: FieldElement =
+    [0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c, 0xfdd6, 0xe231, 0xc0a4, 0x53fe, 0xcd6e, 0x36d3, 0x2169];
+const BASE_Y: FieldElement =
+    [0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666];
+// The square root of -1
+const SQRT_M1: FieldElement =
+    [0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7, 0x3dfb, 0x0099, 0x2b4d, 0xdf0b, 0x4fc1, 0x2480, 0x2b83];
+// The order of the base point in little endian
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0x10,
+];
+
+fn carry(a: &mut FieldElement) {
+    for i in 0..16 {
+        a[i] += 1 << 16;
+        let c = a[i] >> 16;
+        if i < 15 {
+            a[i + 1] += c - 1;
+        } else {
+            // 2^256 = 38 modulo 2^255 - 19
+            a[0] += 38 * (c - 1);
+        }
+        a[i] -= c << 16;
+    }
+}
+
+fn swap_if(a: &mut FieldElement, b: &mut FieldElement, condition: bool) {
+    let mask = !((condition as i64) - 1);
+    for i in 0..16 {
+        let t = mask & (a[i] ^ b[i]);
+        a[i] ^= t;
+        b[i] ^= t;
+    }
+}
+
+fn encode(a: &FieldElement) -> [u8; 32] {
+    let mut t = *a;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    // Subtracts the modulus at most twice to get the canonical representation.
+    for _ in 0..2 {
+        let mut m = ZERO;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let borrow = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        swap_if(&mut t, &mut m, borrow == 0);
+    }
+    let mut bytes = [0u8; 32];
+    for i in 0..16 {
+        bytes[2 * i] = (t[i] & 0xff) as u8;
+        bytes[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8; 32]) -> FieldElement {
+    let mut a = ZERO;
+    for i in 0..16 {
+        a[i] = bytes[2 * i] as i64 + ((bytes[2 * i + 1] as i64) << 8);
+    }
+    a[15] &= 0x7fff;
+    a
+}
+
+fn is_equal(a: &FieldElement, b: &FieldElement) -> bool {
+    encode(a) == encode(b)
+}
+
+fn parity(a: &FieldElement) -> u8 {
+    encode(a)[0] & 1
+}
+
+fn add(a: &FieldElement, b: &FieldElement) -> FieldElement {
+    core::array::from_fn(|i| a[i] + b[i])
+}
+
+fn subtract(a: &FieldElement, b: &FieldElement) -> FieldElement {
+    core::array::from_fn(|i| a[i] - b[i])
+}
+
+fn multiply(a: &FieldElement, b: &FieldElement) -> FieldElement {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut result = ZERO;
+    result.copy_from_slice(&t[..16]);
+    carry(&mut result);
+    carry(&mut result);
+    result
+}
+
+fn square(a: &FieldElement) -> FieldElement {
+    multiply(a, a)
+}
+
+fn invert(a: &FieldElement) -> FieldElement {
+    // a^(p - 2)
+    let mut c = *a;
+    for i in (0..=253).rev() {
+        c = square(&c);
+        if i != 2 && i != 4 {
+            c = multiply(&c, a);
+        }
+    }
+    c
+}
+
+fn power_2_252_minus_3(a: &FieldElement) -> FieldElement {
+    let mut c = *a;
+    for i in (0..=250).rev() {
+        c = square(&c);
+        if i != 1 {
+            c = multiply(&c, a);
+        }
+    }
+    c
+}
+
+fn base_point() -> Point {
+    [BASE_X, BASE_Y, ONE, multiply(&BASE_X, &BASE_Y)]
+}
+
+fn add_points(p: &mut Point, q: &Point) {
+    let a = multiply(&subtract(&p[1], &p[0]), &subtract(&q[1], &q[0]));
+    let b = multiply(&add(&p[0], &p[1]), &add(&q[0], &q[1]));
+    let c = multiply(&multiply(&p[3], &q[3]), &D2);
+    let d = multiply(&p[2], &q[2]);
+    let d = add(&d, &d);
+    let e = subtract(&b, &a);
+    let f = subtract(&d, &c);
+    let g = add(&d, &c);
+    let h = add(&b, &a);
+    *p = [multiply(&e, &f), multiply(&h, &g), multiply(&g, &f), multiply(&e, &h)];
+}
+
+fn swap_points_if(p: &mut Point, q: &mut Point, condition: bool) {
+    for i in 0..4 {
+        swap_if(&mut p[i], &mut q[i], condition);
+    }
+}
+
+fn scalar_multiply(point: &Point, scalar: &[u8; 32]) -> Point {
+    let mut p = [ZERO, ONE, ONE, ZERO];
+    let mut q = *point;
+    for i in (0..256).rev() {
+        let bit = (scalar[i / 8] >> (i & 7)) & 1 == 1;
+        swap_points_if(&mut p, &mut q, bit);
+        add_points(&mut q, &p);
+        let doubled = p;
+        add_points(&mut p, &doubled);
+        swap_points_if(&mut p, &mut q, bit);
+    }
+    p
+}
+
+fn encode_point(p: &Point) -> [u8; 32] {
+    let z_inverse = invert(&p[2]);
+    let x = multiply(&p[0], &z_inverse);
+    let y = multiply(&p[1], &z_inverse);
+    let mut bytes = encode(&y);
+    bytes[31] ^= parity(&x) << 7;
+    bytes
+}
+
+/// Decodes the point and returns its negation, which is what the verification equation needs. Returns `None` if the encoding does not
+/// represent a point on the curve.
+fn decode_negated_point(bytes: &[u8; 32]) -> Option<Point> {
+    let z = ONE;
+    let y = decode(bytes);
+    // x^2 = (y^2 - 1) / (d * y^2 + 1)
+    let y_squared = square(&y);
+    let numerator = subtract(&y_squared, &z);
+    let denominator = add(&z, &multiply(&y_squared, &D));
+
+    let denominator_2 = square(&denominator);
+    let denominator_4 = square(&denominator_2);
+    let denominator_6 = multiply(&denominator_4, &denominator_2);
+    let mut t = multiply(&multiply(&denominator_6, &numerator), &denominator);
+    t = power_2_252_minus_3(&t);
+    t = multiply(&multiply(&multiply(&t, &numerator), &denominator), &denominator);
+    let mut x = multiply(&t, &denominator);
+
+    if !is_equal(&multiply(&square(&x), &denominator), &numerator) {
+        x = multiply(&x, &SQRT_M1);
+    }
+    if !is_equal(&multiply(&square(&x), &denominator), &numerator) {
+        return None;
+    }
+    if parity(&x) == bytes[31] >> 7 {
+        x = subtract(&ZERO, &x);
+    }
+    Some([x, y, z, multiply(&x, &y)])
+}
+
+/// Returns true if the little-endian scalar is lower than the order of the base point.
+fn is_scalar_canonical(scalar: &[u8]) -> bool {
+    for i in (0..32).rev() {
+        let (byte, order_byte) = (scalar[i] as i64, L[i]);
+        if byte != order_byte {
+            return byte < order_byte;
+        }
+    }
+    false
+}
+
+/// Reduces the 512-bit little-endian number modulo the order of the base point.
+fn reduce_scalar(bytes: &[u8; 64]) -> [u8; 32] {
+    let mut x: [i64; 64] = core::array::from_fn(|i| bytes[i] as i64);
+    for i in (32..64).rev() {
+        let mut c = 0;
+        let mut j = i - 32;
+        while j < i - 12 {
+            x[j] += c - 16 * x[i] * L[j - (i - 32)];
+            c = (x[j] + 128) >> 8;
+            x[j] -= c << 8;
+            j += 1;
+        }
+        x[j] += c;
+        x[i] = 0;
+    }
+    let mut c = 0;
+    for j in 0..32 {
+        x[j] += c - (x[31] >> 4) * L[j];
+        c = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= c * L[j];
+    }
+    let mut result = [0u8; 32];
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        result[i] = (x[i] & 255) as u8;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // TEST 1, TEST 2, and TEST 3 from RFC 8032, Section 7.1: (public key, message, signature).
+    const RFC8032_VECTORS: [(&str, &str, &str); 3] = [
+        (
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+    ];
+
+    fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        assert_eq!(hex.len(), 2 * N);
+        bytes.iter_mut().enumerate().for_each(|(i, byte)| *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap());
+        bytes
+    }
+
+    fn vector(index: usize) -> ([u8; 32], Vec<u8>, [u8; 64]) {
+        let (public_key, message, signature) = RFC8032_VECTORS[index];
+        let message = (0..message.len()).step_by(2).map(|i| u8::from_str_radix(&message[i..i + 2], 16).unwrap()).collect();
+        (from_hex(public_key), message, from_hex(signature))
+    }
+
+    #[test]
+    fn rfc8032_signatures_are_valid() {
+        for index in 0..RFC8032_VECTORS.len() {
+            let (public_key, message, signature) = vector(index);
+            assert!(ed25519_verify(&public_key, &message, &signature), "vector {}", index + 1);
+        }
+    }
+
+    #[test]
+    fn signature_of_another_message_is_invalid() {
+        let (public_key, _, signature) = vector(1);
+        assert!(!ed25519_verify(&public_key, &[0x73], &signature));
+        let (_, message, signature) = vector(2);
+        assert!(!ed25519_verify(&public_key, &message, &signature));
+    }
+
+    #[test]
+    fn modified_signature_is_invalid() {
+        let (public_key, message, signature) = vector(2);
+        for byte in [0, 31, 32, 63] {
+            let mut modified_signature = signature;
+            modified_signature[byte] ^= 0x01;
+            assert!(!ed25519_verify(&public_key, &message, &modified_signature), "byte {}", byte);
+        }
+    }
+
+    #[test]
+    fn non_canonical_scalar_is_rejected() {
+        // s + L is a valid solution of the verification equation, but accepting it would make signatures malleable.
+        let (public_key, message, mut signature) = vector(0);
+        let mut carry = 0;
+        for (byte, l) in signature[32..].iter_mut().zip(L.iter()) {
+            let sum = *byte as i64 + l + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!ed25519_verify(&public_key, &message, &signature));
+    }
+}
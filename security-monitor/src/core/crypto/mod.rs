@@ -0,0 +1,10 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+pub use constant_time::{constant_time_eq, constant_time_select};
+pub use ed25519::{ed25519_verify, ED25519_PUBLIC_KEY_SIZE_IN_BYTES, ED25519_SIGNATURE_SIZE_IN_BYTES};
+pub use secret::{zeroize, Secret};
+
+mod constant_time;
+mod ed25519;
+mod secret;
@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Key material that is erased from memory when dropped. The content is reachable only via `expose`, so copies of it do not
+/// spread unnoticed.
+pub struct Secret<const N: usize> {
+    value: [u8; N],
+}
+
+impl<const N: usize> Secret<N> {
+    pub fn new(value: [u8; N]) -> Self {
+        Self { value }
+    }
+
+    pub fn expose(&self) -> &[u8; N] {
+        &self.value
+    }
+}
+
+impl<const N: usize> Drop for Secret<N> {
+    fn drop(&mut self) {
+        zeroize(&mut self.value);
+    }
+}
+
+/// Overwrites the bytes with zeros. Unlike `fill(0)`, the writes are never optimized out, even if the memory is not read again.
+pub fn zeroize(bytes: &mut [u8]) {
+    // Safety: the pointer comes from a mutable reference, so it is valid and aligned for the write.
+    bytes.iter_mut().for_each(|byte| unsafe { core::ptr::write_volatile(byte, 0) });
+    // Prevents reordering subsequent accesses, e.g., releasing the memory, before the erasure.
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::ManuallyDrop;
+
+    #[test]
+    fn secret_is_exposed() {
+        let secret = Secret::new([0x42; 48]);
+        assert_eq!(secret.expose(), &[0x42; 48]);
+    }
+
+    #[test]
+    fn secret_is_zeroized_on_drop() {
+        let mut secret = ManuallyDrop::new(Secret::new([0x42; 48]));
+        let value = secret.value.as_ptr();
+        // Safety: the secret is not used after being dropped. ManuallyDrop keeps its storage alive, so the pointer remains valid
+        // and the content left behind by the drop can be inspected.
+        unsafe { ManuallyDrop::drop(&mut secret) };
+        let value = unsafe { core::ptr::read_volatile(value as *const [u8; 48]) };
+        assert_eq!(value, [0; 48]);
+    }
+
+    #[test]
+    fn bytes_are_zeroized() {
+        let mut bytes = [0xff; 17];
+        zeroize(&mut bytes);
+        assert_eq!(bytes, [0; 17]);
+    }
+}
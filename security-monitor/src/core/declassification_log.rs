@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::GeneralPurposeRegister;
+use spin::Mutex;
+
+/// A trace of the registers that the security monitor wrote when exposing confidential information to the hypervisor. Each entry
+/// records where the value went and what kind of value it was, but never the value itself, so that the log does not become another
+/// channel leaking confidential data. The log is a ring buffer, so only the most recent entries are retained.
+static DECLASSIFICATION_LOG: Mutex<DeclassificationLog> = Mutex::new(DeclassificationLog::empty());
+
+/// Records a declassification event. Use the `log_declassification!` macro instead of calling this function directly, so that the
+/// call disappears when the `declassification_log` feature is disabled.
+pub fn record(exit_type: ExitType, register: DeclassifiedRegister, category: ValueCategory) {
+    DECLASSIFICATION_LOG.lock().record(DeclassificationEntry { exit_type, register, category });
+}
+
+/// Returns the entry at the given position counting from the oldest entry still retained in the log.
+pub fn read(index: usize) -> Option<DeclassificationEntry> {
+    DECLASSIFICATION_LOG.lock().read(index)
+}
+
+struct DeclassificationLog {
+    entries: [Option<DeclassificationEntry>; Self::CAPACITY],
+    // The total number of entries recorded since the boot. The next entry is stored at `number_of_records % CAPACITY`.
+    number_of_records: usize,
+}
+
+impl DeclassificationLog {
+    const CAPACITY: usize = 256;
+
+    const fn empty() -> Self {
+        Self { entries: [None; Self::CAPACITY], number_of_records: 0 }
+    }
+
+    fn record(&mut self, entry: DeclassificationEntry) {
+        self.entries[self.number_of_records % Self::CAPACITY] = Some(entry);
+        self.number_of_records = self.number_of_records.wrapping_add(1);
+    }
+
+    fn read(&self, index: usize) -> Option<DeclassificationEntry> {
+        let number_of_retained_entries = core::cmp::min(self.number_of_records, Self::CAPACITY);
+        if index >= number_of_retained_entries {
+            return None;
+        }
+        let oldest = self.number_of_records.wrapping_sub(number_of_retained_entries);
+        self.entries[oldest.wrapping_add(index) % Self::CAPACITY]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeclassificationEntry {
+    exit_type: ExitType,
+    register: DeclassifiedRegister,
+    category: ValueCategory,
+}
+
+impl DeclassificationEntry {
+    /// Encodes the entry into a single register, so it can be returned to the hypervisor: bits 0-7 store the exit type, bits 8-23
+    /// store the register, and bits 24-31 store the category of the value.
+    pub fn encode(&self) -> usize {
+        (self.exit_type as usize) | (self.register.encode() << 8) | ((self.category as usize) << 24)
+    }
+}
+
+/// The transformation that exposed the value to the hypervisor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum ExitType {
+    SbiRequest = 1,
+    SbiResult = 2,
+    SbiVmRequest = 3,
+    MmioLoadRequest = 4,
+    MmioStoreRequest = 5,
+    InterruptRequest = 6,
+    EnabledInterrupts = 7,
+    MemoryFaultNotification = 8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeclassifiedRegister {
+    Csr(u16),
+    Gpr(GeneralPurposeRegister),
+}
+
+impl DeclassifiedRegister {
+    const GPR_FLAG: usize = 0x1000;
+
+    /// CSRs are encoded with their 12-bit address, general purpose registers with their index and the bit 12 set.
+    fn encode(&self) -> usize {
+        match self {
+            Self::Csr(address) => *address as usize,
+            Self::Gpr(register) => Self::GPR_FLAG | register.index(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum ValueCategory {
+    TrapCause = 1,
+    FaultAddress = 2,
+    FaultingInstruction = 3,
+    StoreValue = 4,
+    SbiCallIdentifier = 5,
+    SbiArgument = 6,
+    SbiReturnValue = 7,
+    InterruptMask = 8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::specification::{CSR_HTVAL, CSR_SCAUSE, CSR_STVAL};
+
+    fn entry(register: DeclassifiedRegister, category: ValueCategory) -> DeclassificationEntry {
+        DeclassificationEntry { exit_type: ExitType::MmioLoadRequest, register, category }
+    }
+
+    #[test]
+    fn entries_are_read_from_oldest() {
+        let mut log = DeclassificationLog::empty();
+        assert_eq!(log.read(0), None);
+        let entries = [
+            entry(DeclassifiedRegister::Csr(CSR_SCAUSE), ValueCategory::TrapCause),
+            entry(DeclassifiedRegister::Csr(CSR_STVAL), ValueCategory::FaultAddress),
+            entry(DeclassifiedRegister::Csr(CSR_HTVAL), ValueCategory::FaultAddress),
+        ];
+        entries.iter().for_each(|entry| log.record(*entry));
+        for (index, entry) in entries.iter().enumerate() {
+            assert_eq!(log.read(index), Some(*entry));
+        }
+        assert_eq!(log.read(entries.len()), None);
+    }
+
+    #[test]
+    fn only_most_recent_entries_are_retained() {
+        let mut log = DeclassificationLog::empty();
+        let stale = entry(DeclassifiedRegister::Csr(CSR_SCAUSE), ValueCategory::TrapCause);
+        let recent = entry(DeclassifiedRegister::Gpr(GeneralPurposeRegister::a0), ValueCategory::SbiArgument);
+        log.record(stale);
+        (0..DeclassificationLog::CAPACITY).for_each(|_| log.record(recent));
+        assert!((0..DeclassificationLog::CAPACITY).all(|index| log.read(index) == Some(recent)));
+        assert_eq!(log.read(DeclassificationLog::CAPACITY), None);
+    }
+
+    #[test]
+    fn encoding_contains_no_value() {
+        let csr = entry(DeclassifiedRegister::Csr(CSR_HTVAL), ValueCategory::FaultAddress);
+        assert_eq!(csr.encode(), ExitType::MmioLoadRequest as usize | (0x643 << 8) | (ValueCategory::FaultAddress as usize) << 24);
+        let gpr = DeclassificationEntry {
+            exit_type: ExitType::MmioStoreRequest,
+            register: DeclassifiedRegister::Gpr(GeneralPurposeRegister::a1),
+            category: ValueCategory::StoreValue,
+        };
+        assert_eq!(gpr.encode(), 5 | ((0x1000 | 11) << 8) | (4 << 24));
+    }
+}
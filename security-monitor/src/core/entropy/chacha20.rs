@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// The ChaCha20 block function as defined in RFC 8439. We use it as a deterministic random bit generator that stretches a short seed
+/// of hardware entropy into a long stream of random bytes, and as the stream cipher that encrypts sealed data.
+pub struct ChaCha20 {
+    state: [u32; 16],
+}
+
+impl ChaCha20 {
+    pub const BLOCK_SIZE_IN_BYTES: usize = 64;
+    const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+    const COUNTER_INDEX: usize = 12;
+
+    pub fn new(key: &[u8; 32], nonce: &[u8; 12]) -> Self {
+        let mut state = [0u32; 16];
+        state[..4].copy_from_slice(&Self::CONSTANTS);
+        key.chunks_exact(4).enumerate().for_each(|(i, word)| state[4 + i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        nonce.chunks_exact(4).enumerate().for_each(|(i, word)| state[13 + i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        Self { state }
+    }
+
+    /// Returns the next key stream block and increments the block counter.
+    pub fn next_block(&mut self) -> [u8; Self::BLOCK_SIZE_IN_BYTES] {
+        let mut working_state = self.state;
+        for _ in 0..10 {
+            Self::quarter_round(&mut working_state, 0, 4, 8, 12);
+            Self::quarter_round(&mut working_state, 1, 5, 9, 13);
+            Self::quarter_round(&mut working_state, 2, 6, 10, 14);
+            Self::quarter_round(&mut working_state, 3, 7, 11, 15);
+            Self::quarter_round(&mut working_state, 0, 5, 10, 15);
+            Self::quarter_round(&mut working_state, 1, 6, 11, 12);
+            Self::quarter_round(&mut working_state, 2, 7, 8, 13);
+            Self::quarter_round(&mut working_state, 3, 4, 9, 14);
+        }
+        let mut block = [0u8; Self::BLOCK_SIZE_IN_BYTES];
+        working_state.iter().zip(self.state.iter()).enumerate().for_each(|(i, (word, initial_word))| {
+            block[4 * i..4 * i + 4].copy_from_slice(&word.wrapping_add(*initial_word).to_le_bytes());
+        });
+        self.state[Self::COUNTER_INDEX] = self.state[Self::COUNTER_INDEX].wrapping_add(1);
+        block
+    }
+
+    fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_left(16);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_left(12);
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_left(8);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_left(7);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The key, nonce, and serialized block with the block counter 1 from RFC 8439, Section 2.3.2.
+    const RFC8439_NONCE: [u8; 12] = [0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00];
+    const RFC8439_BLOCK: &str = concat!(
+        "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e",
+        "d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+    );
+
+    fn rfc8439_key() -> [u8; 32] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn block_function_matches_rfc8439() {
+        let mut chacha20 = ChaCha20::new(&rfc8439_key(), &RFC8439_NONCE);
+        // The block counter starts at 0, so the second block is the one with the block counter 1.
+        chacha20.next_block();
+        let block = chacha20.next_block();
+        let expected: [u8; ChaCha20::BLOCK_SIZE_IN_BYTES] =
+            core::array::from_fn(|i| u8::from_str_radix(&RFC8439_BLOCK[2 * i..2 * i + 2], 16).unwrap());
+        assert_eq!(block, expected);
+    }
+
+    #[test]
+    fn consecutive_blocks_differ() {
+        let mut chacha20 = ChaCha20::new(&rfc8439_key(), &RFC8439_NONCE);
+        assert_ne!(chacha20.next_block(), chacha20.next_block());
+    }
+}
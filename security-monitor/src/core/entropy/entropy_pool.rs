@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::*;
+use crate::error::Error;
+use spin::{Mutex, Once};
+
+/// The security monitor's source of hardware entropy, i.e., the `seed` CSR defined by the Zkr extension. It is initialized only if all
+/// harts implement the Zkr extension because accessing the `seed` CSR on other harts raises an exception in the security monitor.
+static ENTROPY_POOL: Once<Mutex<EntropyPool>> = Once::new();
+
+pub struct EntropyPool {
+    last_sample: Option<u16>,
+    number_of_repetitions: usize,
+}
+
+impl EntropyPool {
+    /// The number of times we poll the entropy source while it is not ready before giving up.
+    const MAX_NUMBER_OF_POLLS: usize = 1024;
+    /// A healthy entropy source is extremely unlikely to return the same 16-bit sample this many times in a row.
+    const MAX_NUMBER_OF_REPETITIONS: usize = 4;
+
+    pub fn init() {
+        ENTROPY_POOL.call_once(|| Mutex::new(Self { last_sample: None, number_of_repetitions: 0 }));
+    }
+
+    /// Fills the buffer with fresh hardware entropy. Returns error if the platform has no entropy source, the entropy source failed
+    /// the health test, or it did not produce enough entropy in a bounded time.
+    pub fn fill(buffer: &mut [u8]) -> Result<(), Error> {
+        let mut entropy_pool = ENTROPY_POOL.get().ok_or(Error::EntropySourceUnavailable())?.lock();
+        buffer.chunks_mut(core::mem::size_of::<u16>()).try_for_each(|chunk| {
+            let sample = entropy_pool.next_sample()?.to_le_bytes();
+            chunk.copy_from_slice(&sample[..chunk.len()]);
+            Ok(())
+        })
+    }
+
+    fn next_sample(&mut self) -> Result<u16, Error> {
+        for _ in 0..Self::MAX_NUMBER_OF_POLLS {
+            // The entropy source wipes the sample after a read, so the same entropy is never returned twice.
+            let seed = CSR.seed.swap(0);
+            match (seed >> SEED_OPST_SHIFT) & SEED_OPST_MASK {
+                SEED_OPST_ES16 => return self.health_test((seed & SEED_ENTROPY_MASK) as u16),
+                SEED_OPST_DEAD => return Err(Error::EntropySourceFailure()),
+                _ => core::hint::spin_loop(),
+            }
+        }
+        Err(Error::EntropySourceNotReady())
+    }
+
+    /// A simplified repetition count test that detects an entropy source stuck at a single value.
+    fn health_test(&mut self, sample: u16) -> Result<u16, Error> {
+        if self.last_sample == Some(sample) {
+            self.number_of_repetitions += 1;
+            assure!(self.number_of_repetitions < Self::MAX_NUMBER_OF_REPETITIONS, Error::EntropySourceFailure())?;
+        } else {
+            self.last_sample = Some(sample);
+            self.number_of_repetitions = 1;
+        }
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stuck_entropy_source_fails_health_test() {
+        let mut entropy_pool = EntropyPool { last_sample: None, number_of_repetitions: 0 };
+        for _ in 1..EntropyPool::MAX_NUMBER_OF_REPETITIONS {
+            assert_eq!(entropy_pool.health_test(0xabcd).unwrap(), 0xabcd);
+        }
+        assert!(matches!(entropy_pool.health_test(0xabcd), Err(Error::EntropySourceFailure())));
+    }
+
+    #[test]
+    fn changing_samples_pass_health_test() {
+        let mut entropy_pool = EntropyPool { last_sample: None, number_of_repetitions: 0 };
+        for i in 0..4 * EntropyPool::MAX_NUMBER_OF_REPETITIONS {
+            let sample = if i % EntropyPool::MAX_NUMBER_OF_REPETITIONS == 0 { 0x1111 } else { 0x2222 };
+            assert!(entropy_pool.health_test(sample).is_ok());
+        }
+    }
+}
@@ -0,0 +1,10 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+pub use chacha20::ChaCha20;
+pub use entropy_pool::EntropyPool;
+pub use virtual_seed::VirtualSeed;
+
+mod chacha20;
+mod entropy_pool;
+mod virtual_seed;
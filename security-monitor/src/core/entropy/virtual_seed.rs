@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use super::chacha20::ChaCha20;
+use super::EntropyPool;
+use crate::core::architecture::*;
+use crate::error::Error;
+
+/// Emulates the `seed` CSR for a confidential hart.
+///
+/// Values come from a deterministic random bit generator (DRBG) owned by the confidential hart and seeded with fresh hardware entropy.
+/// Every confidential hart, and thus every confidential VM, has its own DRBG with an independently drawn key, so one confidential VM
+/// cannot learn or influence the entropy delivered to another. The DRBG is reseeded after a fixed number of samples, which paces the
+/// consumption of hardware entropy even if a confidential hart spins reading the `seed` CSR.
+pub struct VirtualSeed {
+    drbg: Option<ChaCha20>,
+    block: [u8; ChaCha20::BLOCK_SIZE_IN_BYTES],
+    offset_in_block: usize,
+    number_of_samples_since_reseed: usize,
+}
+
+impl Default for VirtualSeed {
+    fn default() -> Self {
+        Self {
+            drbg: None,
+            block: [0; ChaCha20::BLOCK_SIZE_IN_BYTES],
+            offset_in_block: ChaCha20::BLOCK_SIZE_IN_BYTES,
+            number_of_samples_since_reseed: 0,
+        }
+    }
+}
+
+impl VirtualSeed {
+    const SAMPLE_SIZE_IN_BYTES: usize = core::mem::size_of::<u16>();
+    const RESEED_INTERVAL: usize = 1 << 16;
+    const NONCE: [u8; 12] = [0; 12];
+
+    /// Returns the value of the `seed` CSR as seen by the confidential hart: the operational status in bits 30-31 and, if the status is
+    /// ES16, 16 bits of entropy in bits 0-15.
+    pub fn read(&mut self) -> usize {
+        if self.drbg.is_none() || self.number_of_samples_since_reseed >= Self::RESEED_INTERVAL {
+            if let Err(error) = self.reseed() {
+                return match error {
+                    Error::EntropySourceNotReady() => SEED_OPST_WAIT << SEED_OPST_SHIFT,
+                    _ => SEED_OPST_DEAD << SEED_OPST_SHIFT,
+                };
+            }
+        }
+        (SEED_OPST_ES16 << SEED_OPST_SHIFT) | self.next_sample() as usize
+    }
+
+    fn reseed(&mut self) -> Result<(), Error> {
+        let mut key = [0u8; 32];
+        EntropyPool::fill(&mut key)?;
+        self.drbg = Some(ChaCha20::new(&key, &Self::NONCE));
+        // Discard samples generated with the previous key.
+        self.offset_in_block = ChaCha20::BLOCK_SIZE_IN_BYTES;
+        self.number_of_samples_since_reseed = 0;
+        Ok(())
+    }
+
+    fn next_sample(&mut self) -> u16 {
+        if self.offset_in_block >= ChaCha20::BLOCK_SIZE_IN_BYTES {
+            if let Some(drbg) = self.drbg.as_mut() {
+                self.block = drbg.next_block();
+            }
+            self.offset_in_block = 0;
+        }
+        let sample = u16::from_le_bytes([self.block[self.offset_in_block], self.block[self.offset_in_block + 1]]);
+        // Wipe the sample, so it does not remain in the security monitor's memory after being handed to the confidential hart.
+        self.block[self.offset_in_block..self.offset_in_block + Self::SAMPLE_SIZE_IN_BYTES].fill(0);
+        self.offset_in_block += Self::SAMPLE_SIZE_IN_BYTES;
+        self.number_of_samples_since_reseed += 1;
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUMBER_OF_SAMPLES: usize = 256;
+
+    // Creates the virtual seed CSR of a confidential hart whose DRBG has been seeded with the given key drawn from the entropy pool.
+    fn seeded(key: [u8; 32]) -> VirtualSeed {
+        VirtualSeed { drbg: Some(ChaCha20::new(&key, &VirtualSeed::NONCE)), ..Default::default() }
+    }
+
+    fn stream(virtual_seed: &mut VirtualSeed) -> [usize; NUMBER_OF_SAMPLES] {
+        core::array::from_fn(|_| virtual_seed.read())
+    }
+
+    #[test]
+    fn samples_have_es16_status() {
+        let mut virtual_seed = seeded([1; 32]);
+        for seed in stream(&mut virtual_seed) {
+            assert_eq!((seed >> SEED_OPST_SHIFT) & SEED_OPST_MASK, SEED_OPST_ES16);
+            assert_eq!(seed & !(SEED_ENTROPY_MASK | (SEED_OPST_MASK << SEED_OPST_SHIFT)), 0);
+        }
+    }
+
+    #[test]
+    fn confidential_vms_get_independent_entropy_streams() {
+        let first_vm_stream = stream(&mut seeded([1; 32]));
+        let second_vm_stream = stream(&mut seeded([2; 32]));
+        let number_of_equal_samples = first_vm_stream.iter().zip(second_vm_stream.iter()).filter(|(a, b)| a == b).count();
+        // Two independent 16-bit streams of this length match at a few positions at most.
+        assert!(number_of_equal_samples < 4, "{} equal samples", number_of_equal_samples);
+    }
+
+    #[test]
+    fn samples_are_wiped_after_read() {
+        let mut virtual_seed = seeded([1; 32]);
+        stream(&mut virtual_seed);
+        assert!(virtual_seed.block.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn spinning_hart_triggers_reseed() {
+        let mut virtual_seed = seeded([1; 32]);
+        virtual_seed.number_of_samples_since_reseed = VirtualSeed::RESEED_INTERVAL;
+        // The entropy pool is not initialized in tests, so the reseed fails and the confidential hart observes a dead entropy source
+        // instead of samples from the exhausted DRBG.
+        assert_eq!((virtual_seed.read() >> SEED_OPST_SHIFT) & SEED_OPST_MASK, SEED_OPST_DEAD);
+    }
+}
@@ -7,7 +7,7 @@ use allocator::HeapAllocator;
 mod allocator;
 
 /// global allocator allocates memory on the security monitor's heap.
-#[global_allocator]
+#[cfg_attr(not(test), global_allocator)]
 static mut HEAP_ALLOCATOR: HeapAllocator = HeapAllocator::empty();
 
 pub(super) fn init_heap(start_address: ConfidentialMemoryAddress, heap_size: usize) {
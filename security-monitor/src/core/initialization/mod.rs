@@ -1,11 +1,17 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{fence_wo, CAUSE_SUPERVISOR_ECALL, CAUSE_VIRTUAL_SUPERVISOR_ECALL, CSR, MTVEC_BASE_SHIFT};
+use crate::core::architecture::{
+    fence_wo, Hgatp, HgatpMode, Smstateen, CAUSE_SUPERVISOR_ECALL, CAUSE_VIRTUAL_SUPERVISOR_ECALL, CSR, CSR_MENVCFG_ADUE, CSR_STATEEN0_SE0,
+    MTVEC_BASE_SHIFT,
+};
+use crate::core::attestation::{AttestationKey, KeyHandover};
 use crate::core::control_data::{ControlData, HardwareHart, CONTROL_DATA};
+use crate::core::crypto::zeroize;
+use crate::core::entropy::EntropyPool;
 use crate::core::interrupt_controller::InterruptController;
-use crate::core::memory_layout::{ConfidentialMemoryAddress, MemoryLayout};
-use crate::core::memory_protector::{HypervisorMemoryProtector, PageSize};
+use crate::core::memory_layout::{ConfidentialMemoryAddress, MemoryLayout, NonConfidentialMemoryAddress};
+use crate::core::memory_protector::{AccessedDirtyUpdate, HypervisorMemoryProtector, PageSize, PageTableUsage, TlbFence, TlbShootdown};
 use crate::core::page_allocator::{Page, PageAllocator, UnAllocated};
 use crate::error::{Error, HardwareFeatures, InitType, NOT_INITIALIZED_HART, NOT_INITIALIZED_HARTS};
 use alloc::vec::Vec;
@@ -64,15 +70,67 @@ fn init_security_monitor(flattened_device_tree_address: *const u8) -> Result<(),
 
     let number_of_harts = verify_harts(&fdt)?;
 
+    // G-stage translation modes are optional, so every confidential VM gets the smallest mode implemented by this hardware that
+    // covers its guest physical address space.
+    HgatpMode::probe_supported_modes();
+    ControlData::initialize_vmid_allocator(Hgatp::probe_vmid_length())?;
+    if fdt.has_broken_narrow_hfence() {
+        TlbFence::disable_narrow_fences();
+    }
+    if is_svinval_supported(&fdt) {
+        TlbFence::enable_svinval();
+    }
+    if let Some(ratio) = fdt.page_table_ratio() {
+        PageTableUsage::set_data_pages_per_page_table_page(ratio as usize);
+    }
+    // Without hardware updates, the security monitor sets the accessed and dirty bits of G-stage entries when confidential harts fault.
+    if is_svadu_supported(&fdt) {
+        AccessedDirtyUpdate::enable_hardware_updates();
+    }
+    // Without Smstateen, there are no state-enable CSRs to switch and the gated extensions are not implemented either.
+    if is_smstateen_supported(&fdt) {
+        Smstateen::enable();
+    }
+
     // Prepares memory required to store physical hart state
     prepare_harts(number_of_harts)?;
 
+    // Confidential VMs get entropy only if the security monitor can draw it from the hardware entropy source on every hart.
+    if is_entropy_source_supported(&fdt) {
+        EntropyPool::init();
+    }
+
+    // Without the attestation key, confidential VMs can still execute but cannot obtain attestation reports.
+    let key_handover = receive_attestation_key_handover(&fdt);
+    if let Err(_error) = &key_handover {
+        debug!("Attestation is disabled: {:?}", _error);
+    }
+    AttestationKey::init(key_handover.ok());
+
     // TODO: lock access to attestation keys/seed/credentials.
 
     // if we reached this line, then the security monitor control data has been correctly initialized.
     Ok(())
 }
 
+/// Copies the attestation key material handed over by the previous boot stage to the security monitor's memory and erases the source,
+/// also when the key material is malformed. Returns error if the FDT does not describe the key material, the key material is not in the
+/// non-confidential memory, or it is malformed.
+fn receive_attestation_key_handover(fdt: &FlattenedDeviceTree) -> Result<KeyHandover, Error> {
+    let region = fdt.attestation_handover().ok_or(Error::AttestationKeyUnavailable())?;
+    let start = usize::try_from(region.base).map_err(|_| Error::MalformedKeyHandover())?;
+    let size = usize::try_from(region.size).map_err(|_| Error::MalformedKeyHandover())?;
+    let last_byte = start.checked_add(size).and_then(|end| end.checked_sub(1)).ok_or(Error::MalformedKeyHandover())?;
+    // The key material must not overlap the confidential memory, which the security monitor has already started using.
+    NonConfidentialMemoryAddress::new(start as *mut usize)?;
+    NonConfidentialMemoryAddress::new(last_byte as *mut usize)?;
+    // Safety: the region is entirely in the non-confidential memory, which nobody else accesses during the boot.
+    let source = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, size) };
+    let key_handover = KeyHandover::parse(source);
+    zeroize(source);
+    key_handover
+}
+
 /// Parses the flattened device tree (FDT) and reads the number of physical harts in the system. It verifies that these
 /// harts support extensions required by ACE. Error is returned if FDT is incorrectly structured or exist a hart that
 /// does not support required extensions.
@@ -104,6 +162,36 @@ fn verify_harts(fdt: &FlattenedDeviceTree) -> Result<usize, Error> {
     Ok(fdt.harts().count())
 }
 
+/// Returns true if all harts implement the Zkr extension, i.e., they provide the `seed` CSR.
+fn is_entropy_source_supported(fdt: &FlattenedDeviceTree) -> bool {
+    const ENTROPY_SOURCE_EXTENSION: &str = "zkr";
+    is_multi_letter_extension_supported(fdt, ENTROPY_SOURCE_EXTENSION)
+}
+
+/// Returns true if all harts implement the Svinval extension, i.e., they can batch invalidations of address translations.
+fn is_svinval_supported(fdt: &FlattenedDeviceTree) -> bool {
+    const SVINVAL_EXTENSION: &str = "svinval";
+    is_multi_letter_extension_supported(fdt, SVINVAL_EXTENSION)
+}
+
+/// Returns true if all harts implement the Svadu extension, i.e., they can update the accessed and dirty bits of page table entries.
+fn is_svadu_supported(fdt: &FlattenedDeviceTree) -> bool {
+    const SVADU_EXTENSION: &str = "svadu";
+    is_multi_letter_extension_supported(fdt, SVADU_EXTENSION)
+}
+
+/// Returns true if all harts implement the Smstateen extension, i.e., they can gate accesses of lower privilege levels to the state
+/// of other extensions.
+fn is_smstateen_supported(fdt: &FlattenedDeviceTree) -> bool {
+    const SMSTATEEN_EXTENSION: &str = "smstateen";
+    is_multi_letter_extension_supported(fdt, SMSTATEEN_EXTENSION)
+}
+
+fn is_multi_letter_extension_supported(fdt: &FlattenedDeviceTree, extension: &str) -> bool {
+    const FDT_RISCV_ISA: &str = "riscv,isa";
+    fdt.harts().all(|hart| hart.property_str(FDT_RISCV_ISA).unwrap_or("").split('_').skip(1).any(|ext| ext == extension))
+}
+
 fn initialize_memory_layout(fdt: &FlattenedDeviceTree) -> Result<(ConfidentialMemoryAddress, *const usize), Error> {
     // TODO: FDT may contain multiple regions. For now, we assume there is only one region in the FDT.
     // This assumption is fine for the emulated environment (QEMU).
@@ -189,11 +277,21 @@ fn prepare_harts(number_of_harts: usize) -> Result<(), Error> {
         debug!("Hart[{}] stack {:x}-{:x}", hart_id, stack.start_address(), stack.end_address());
         harts_states.insert(hart_id, HardwareHart::init(hart_id, stack, hypervisor_memory_protector));
     }
+    TlbShootdown::initialize(number_of_harts);
     HARTS_STATES.call_once(|| Mutex::new(harts_states));
     fence_wo();
     Ok(())
 }
 
+/// Returns the address of the state of the hardware hart executing this code. Returns `None` if the hart is not set up yet, if its
+/// mscratch currently holds the OpenSBI's value, or if the hart states are locked, e.g., because another hart panicked while holding
+/// the lock. Intended only for the panic handler, which cannot reach the hart state through the control flows.
+pub fn executing_hart_state_address() -> Option<usize> {
+    let harts = HARTS_STATES.get()?.try_lock()?;
+    let address = harts.get(CSR.mhartid.read())?.address();
+    (CSR.mscratch.read() == address).then_some(address)
+}
+
 /// Enables entry points to the security monitor by taking control over some interrupts and protecting confidential
 /// memory region using hardware isolation mechanisms.
 #[no_mangle]
@@ -233,9 +331,30 @@ extern "C" fn ace_setup_this_hart() {
     CSR.medeleg.read_and_clear_bit(CAUSE_SUPERVISOR_ECALL.into());
     CSR.medeleg.read_and_clear_bit(CAUSE_VIRTUAL_SUPERVISOR_ECALL.into());
     debug!("medeleg={:b}", CSR.medeleg.read());
+    // The firmware might have delegated traps in a way that the writes above cannot fix, e.g., because of a hardware bug. The security
+    // monitor is not installed on such a hart, so the hypervisor will not be able to create confidential VMs.
+    if let Err(_error) = hart.validate_trap_delegation() {
+        debug!("Invalid trap delegation on hardware hart id={}: {:?}", hart_id, _error);
+        return;
+    }
+
+    // The menvcfg.ADUE bit enables hardware updates of the accessed and dirty bits for the G-stage translation, and also for the
+    // hypervisor's own single-stage translation. Both handle the hardware updates as well as page faults.
+    if AccessedDirtyUpdate::are_hardware_updates_enabled() {
+        CSR.menvcfg.read_and_set_bit(CSR_MENVCFG_ADUE);
+    }
+
+    // The hypervisor and confidential harts must be able to access their hstateen0 and sstateen0, which the security monitor switches
+    // between security domains.
+    if Smstateen::is_enabled() {
+        CSR.mstateen0.read_and_set_bit(CSR_STATEEN0_SE0);
+    }
 
     // Set up the trap vector, so that the exceptions are handled by the security monitor.
     let trap_vector_address = enter_from_hypervisor_or_vm_asm as usize;
     debug!("Hardware hart id={} registered trap handler at address: {:x}", hart_id, trap_vector_address);
     CSR.mtvec.set((trap_vector_address >> MTVEC_BASE_SHIFT) << MTVEC_BASE_SHIFT);
+
+    // From now on, the hart traps into the security monitor, so it can take part in the synchronization of the PMP configuration.
+    hart.hypervisor_memory_protector_mut().register_hart();
 }
@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::crypto::zeroize;
+use crate::core::measurement::Sha384;
+
+/// HMAC with SHA-384 as defined in RFC 2104. The whole state lives on the stack, so computing a MAC never allocates memory.
+pub struct HmacSha384 {
+    inner: Sha384,
+    outer_key_pad: [u8; Sha384::BLOCK_SIZE_IN_BYTES],
+}
+
+impl HmacSha384 {
+    pub const MAC_SIZE_IN_BYTES: usize = Sha384::DIGEST_SIZE_IN_BYTES;
+    const INNER_PAD: u8 = 0x36;
+    const OUTER_PAD: u8 = 0x5c;
+
+    /// Keys longer than the SHA-384 block are hashed first, shorter keys are padded with zeros.
+    pub fn new(key: &[u8]) -> Self {
+        let mut block_sized_key = [0u8; Sha384::BLOCK_SIZE_IN_BYTES];
+        if key.len() > Sha384::BLOCK_SIZE_IN_BYTES {
+            block_sized_key[..Sha384::DIGEST_SIZE_IN_BYTES].copy_from_slice(&Sha384::digest(key));
+        } else {
+            block_sized_key[..key.len()].copy_from_slice(key);
+        }
+        let mut inner_key_pad = block_sized_key.map(|byte| byte ^ Self::INNER_PAD);
+        let outer_key_pad = block_sized_key.map(|byte| byte ^ Self::OUTER_PAD);
+        let mut inner = Sha384::default();
+        inner.update(&inner_key_pad);
+        // Do not leave copies of the key on the stack.
+        zeroize(&mut block_sized_key);
+        zeroize(&mut inner_key_pad);
+        Self { inner, outer_key_pad }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(mut self) -> [u8; Self::MAC_SIZE_IN_BYTES] {
+        let inner_digest = self.inner.finalize();
+        let mut outer = Sha384::default();
+        outer.update(&self.outer_key_pad);
+        outer.update(&inner_digest);
+        zeroize(&mut self.outer_key_pad);
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test cases 1, 2, and 6 from RFC 4231, Section 4: (key, data, HMAC-SHA-384).
+    const RFC4231_VECTORS: [(&[u8], &[u8], &str); 3] = [
+        (
+            &[0x0b; 20],
+            b"Hi There",
+            "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6",
+        ),
+        (
+            b"Jefe",
+            b"what do ya want for nothing?",
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649",
+        ),
+        (
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c60c2ef6ab4030fe8296248df163f44952",
+        ),
+    ];
+
+    fn to_hex(mac: &[u8]) -> String {
+        mac.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn mac_matches_rfc4231() {
+        for (key, data, expected_mac) in RFC4231_VECTORS {
+            let mut mac = HmacSha384::new(key);
+            mac.update(data);
+            assert_eq!(to_hex(&mac.finalize()), expected_mac);
+        }
+    }
+
+    #[test]
+    fn incremental_updates_match_single_update() {
+        let (key, data, expected_mac) = RFC4231_VECTORS[1];
+        let mut mac = HmacSha384::new(key);
+        data.chunks(5).for_each(|chunk| mac.update(chunk));
+        assert_eq!(to_hex(&mac.finalize()), expected_mac);
+    }
+}
@@ -0,0 +1,10 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+pub use hmac_sha384::HmacSha384;
+pub use sha384::Sha384;
+pub use sha512::Sha512;
+
+mod hmac_sha384;
+mod sha384;
+mod sha512;
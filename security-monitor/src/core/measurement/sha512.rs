@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::measurement::Sha384;
+
+/// The SHA-512 hash function as defined in FIPS 180-4. It shares the compression function with SHA-384, only the initial values and
+/// the size of the digest differ. The security monitor uses it only where a scheme mandates it, e.g., Ed25519.
+pub struct Sha512 {
+    hasher: Sha384,
+}
+
+impl Default for Sha512 {
+    fn default() -> Self {
+        Self { hasher: Sha384::with_initial_state(Self::INITIAL_STATE) }
+    }
+}
+
+impl Sha512 {
+    pub const DIGEST_SIZE_IN_BYTES: usize = 64;
+    const INITIAL_STATE: [u64; 8] = [
+        0x6a09e667f3bcc908,
+        0xbb67ae8584caa73b,
+        0x3c6ef372fe94f82b,
+        0xa54ff53a5f1d36f1,
+        0x510e527fade682d1,
+        0x9b05688c2b3e6c1f,
+        0x1f83d9abfb41bd6b,
+        0x5be0cd19137e2179,
+    ];
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; Self::DIGEST_SIZE_IN_BYTES] {
+        let mut digest = [0u8; Self::DIGEST_SIZE_IN_BYTES];
+        digest
+            .chunks_exact_mut(8)
+            .zip(self.hasher.finalize_state().iter())
+            .for_each(|(chunk, word)| chunk.copy_from_slice(&word.to_be_bytes()));
+        digest
+    }
+}
@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use super::platform;
+use crate::core::crypto::Secret;
+use crate::core::entropy::EntropyPool;
+use crate::core::memory_protector::ConfidentialVmMemoryProtector;
+use crate::error::Error;
+use spin::Mutex;
+
+/// Bitmap of the key slots of the memory encryption engine that are assigned to confidential VMs.
+static OCCUPIED_KEY_SLOTS: Mutex<u64> = Mutex::new(0);
+
+/// A key slot of the memory encryption engine that is exclusively assigned to one confidential VM. The memory of the confidential VM is
+/// encrypted with the key programmed in the slot, so a key slot is never shared between confidential VMs and is wiped when dropped.
+///
+/// Without the `memory-encryption` feature, the platform has no key slots and all operations succeed without any effect. All
+/// confidential VMs then share a logical slot, which is never reserved.
+pub struct MemoryKeySlot {
+    index: usize,
+}
+
+impl MemoryKeySlot {
+    const KEY_SIZE_IN_BYTES: usize = 32;
+
+    /// Reserves a free key slot, programs it with a fresh random key, and encrypts all pages of the confidential VM with it. Returns
+    /// error if all key slots are in use, there is no entropy to generate the key, or the memory encryption engine failed.
+    pub fn assign(memory_protector: &ConfidentialVmMemoryProtector) -> Result<Self, Error> {
+        let key_slot = Self::reserve()?;
+        key_slot.program_fresh_key()?;
+        key_slot.encrypt_memory(memory_protector)?;
+        Ok(key_slot)
+    }
+
+    /// Re-keys the confidential VM's memory: a new slot with a fresh key is reserved, the pages are re-encrypted in place under the new
+    /// key, and the old slot is wiped. The caller must ensure that no confidential hart of the confidential VM executes. On error, the
+    /// confidential VM keeps its current key.
+    pub fn rotate(&mut self, memory_protector: &ConfidentialVmMemoryProtector) -> Result<(), Error> {
+        let mut key_slot = Self::assign(memory_protector)?;
+        core::mem::swap(self, &mut key_slot);
+        // Dropping the previous slot wipes its key.
+        Ok(())
+    }
+
+    fn reserve() -> Result<Self, Error> {
+        if cfg!(not(feature = "memory-encryption")) {
+            return Ok(Self { index: 0 });
+        }
+        let index = Self::reserve_free_index(&mut OCCUPIED_KEY_SLOTS.lock(), platform::number_of_key_slots())?;
+        Ok(Self { index })
+    }
+
+    /// Marks the first free slot in the bitmap of occupied slots as occupied and returns its index.
+    fn reserve_free_index(occupied_key_slots: &mut u64, number_of_key_slots: usize) -> Result<usize, Error> {
+        let number_of_key_slots = number_of_key_slots.min(u64::BITS as usize);
+        let index = (0..number_of_key_slots).find(|index| *occupied_key_slots & (1 << index) == 0).ok_or(Error::NoFreeMemoryKeySlot())?;
+        *occupied_key_slots |= 1 << index;
+        Ok(index)
+    }
+
+    fn program_fresh_key(&self) -> Result<(), Error> {
+        if cfg!(not(feature = "memory-encryption")) {
+            return Ok(());
+        }
+        let mut key = [0u8; Self::KEY_SIZE_IN_BYTES];
+        EntropyPool::fill(&mut key)?;
+        // Erases the key from the security monitor's memory once it has been programmed.
+        let key = Secret::new(key);
+        platform::program_key_slot(self.index, key.expose())
+    }
+
+    fn encrypt_memory(&self, memory_protector: &ConfidentialVmMemoryProtector) -> Result<(), Error> {
+        memory_protector
+            .for_each_confidential_page(&mut |_, page| platform::assign_key_slot(page.start_address(), page.size().in_bytes(), self.index))
+    }
+
+    #[cfg(not(test))]
+    fn wipe(&self) -> Result<(), Error> {
+        platform::wipe_key_slot(self.index)
+    }
+
+    /// Host tests have no memory encryption engine, so they record which slots were wiped and decide whether the wipe fails.
+    #[cfg(test)]
+    fn wipe(&self) -> Result<(), Error> {
+        tests::wipe_key_slot(self.index)
+    }
+}
+
+impl Drop for MemoryKeySlot {
+    fn drop(&mut self) {
+        // A slot that could not be wiped still holds a key that decrypts memory of the confidential VM, so we never reuse it.
+        if self.wipe().is_ok() {
+            *OCCUPIED_KEY_SLOTS.lock() &= !(1 << self.index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    std::thread_local! {
+        static WIPED_KEY_SLOTS: Cell<u64> = const { Cell::new(0) };
+        static IS_WIPE_FAILING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub(super) fn wipe_key_slot(index: usize) -> Result<(), Error> {
+        assure_not!(IS_WIPE_FAILING.with(|is_wipe_failing| is_wipe_failing.get()), Error::MemoryEncryptionFailed())?;
+        WIPED_KEY_SLOTS.with(|wiped_key_slots| wiped_key_slots.set(wiped_key_slots.get() | (1 << index)));
+        Ok(())
+    }
+
+    fn is_occupied(index: usize) -> bool {
+        *OCCUPIED_KEY_SLOTS.lock() & (1 << index) != 0
+    }
+
+    #[test]
+    fn free_key_slots_are_assigned_exclusively() {
+        let mut occupied_key_slots = 0b10;
+        assert_eq!(MemoryKeySlot::reserve_free_index(&mut occupied_key_slots, 3).ok(), Some(0));
+        assert_eq!(MemoryKeySlot::reserve_free_index(&mut occupied_key_slots, 3).ok(), Some(2));
+        assert!(matches!(MemoryKeySlot::reserve_free_index(&mut occupied_key_slots, 3), Err(Error::NoFreeMemoryKeySlot())));
+        assert_eq!(occupied_key_slots, 0b111);
+        // The bitmap tracks at most 64 slots, even if the engine has more.
+        let mut occupied_key_slots = u64::MAX;
+        assert!(MemoryKeySlot::reserve_free_index(&mut occupied_key_slots, 128).is_err());
+    }
+
+    // Other tests drop the logical slot 0, so these tests use slots that are not assigned elsewhere.
+    #[test]
+    fn dropped_key_slot_is_wiped_and_released() {
+        *OCCUPIED_KEY_SLOTS.lock() |= 1 << 5;
+        drop(MemoryKeySlot { index: 5 });
+        assert_eq!(WIPED_KEY_SLOTS.with(|wiped_key_slots| wiped_key_slots.get()), 1 << 5);
+        assert!(!is_occupied(5));
+    }
+
+    #[test]
+    fn key_slot_that_cannot_be_wiped_is_never_released() {
+        *OCCUPIED_KEY_SLOTS.lock() |= 1 << 6;
+        IS_WIPE_FAILING.with(|is_wipe_failing| is_wipe_failing.set(true));
+        drop(MemoryKeySlot { index: 6 });
+        assert!(is_occupied(6));
+        *OCCUPIED_KEY_SLOTS.lock() &= !(1 << 6);
+    }
+}
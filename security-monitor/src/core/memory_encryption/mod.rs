@@ -0,0 +1,7 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+pub use memory_key_slot::MemoryKeySlot;
+
+mod memory_key_slot;
+mod platform;
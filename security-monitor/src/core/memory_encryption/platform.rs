@@ -0,0 +1,65 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+
+#[cfg(feature = "memory-encryption")]
+extern "C" {
+    // Functions provided by the platform code that drives the key-management registers of the memory encryption engine. All of them
+    // return 0 on success.
+    fn ace_platform_number_of_memory_key_slots() -> usize;
+    fn ace_platform_program_memory_key_slot(slot: usize, key: *const u8) -> isize;
+    // Re-encrypts the memory region in place with the key programmed in the slot, so that the region is accessible only under it.
+    fn ace_platform_assign_memory_key_slot(address: usize, size_in_bytes: usize, slot: usize) -> isize;
+    #[cfg(not(test))]
+    fn ace_platform_wipe_memory_key_slot(slot: usize) -> isize;
+}
+
+#[cfg(feature = "memory-encryption")]
+pub(super) fn number_of_key_slots() -> usize {
+    // Safety: the function does not take any arguments and only reports the capability of the platform.
+    unsafe { ace_platform_number_of_memory_key_slots() }
+}
+
+#[cfg(feature = "memory-encryption")]
+pub(super) fn program_key_slot(slot: usize, key: &[u8; 32]) -> Result<(), Error> {
+    // Safety: the key is a valid buffer of the size expected by the platform and the slot is owned by the caller.
+    let result = unsafe { ace_platform_program_memory_key_slot(slot, key.as_ptr()) };
+    assure!(result == 0, Error::MemoryEncryptionFailed())
+}
+
+#[cfg(feature = "memory-encryption")]
+pub(super) fn assign_key_slot(address: usize, size_in_bytes: usize, slot: usize) -> Result<(), Error> {
+    // Safety: the caller owns the memory region because it is the memory of a page token.
+    let result = unsafe { ace_platform_assign_memory_key_slot(address, size_in_bytes, slot) };
+    assure!(result == 0, Error::MemoryEncryptionFailed())
+}
+
+// Host tests replace the wipe of a key slot, see `MemoryKeySlot::wipe`.
+#[cfg(all(feature = "memory-encryption", not(test)))]
+pub(super) fn wipe_key_slot(slot: usize) -> Result<(), Error> {
+    // Safety: the slot is owned by the caller, so no memory in use is encrypted with its key anymore.
+    let result = unsafe { ace_platform_wipe_memory_key_slot(slot) };
+    assure!(result == 0, Error::MemoryEncryptionFailed())
+}
+
+/// Without a memory encryption engine, there is a single logical slot shared by all confidential VMs.
+#[cfg(not(feature = "memory-encryption"))]
+pub(super) fn number_of_key_slots() -> usize {
+    1
+}
+
+#[cfg(not(feature = "memory-encryption"))]
+pub(super) fn program_key_slot(_slot: usize, _key: &[u8; 32]) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(not(feature = "memory-encryption"))]
+pub(super) fn assign_key_slot(_address: usize, _size_in_bytes: usize, _slot: usize) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(all(not(feature = "memory-encryption"), not(test)))]
+pub(super) fn wipe_key_slot(_slot: usize) -> Result<(), Error> {
+    Ok(())
+}
@@ -2,6 +2,7 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::error::Error;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use pointers_utility::{ptr_byte_add_mut, ptr_byte_offset};
 
 /// The wrapper over a raw pointer that is guaranteed to be an address located in the confidential memory region.
@@ -67,4 +68,14 @@ impl ConfidentialMemoryAddress {
     pub unsafe fn write_volatile(&self, value: usize) {
         self.0.write_volatile(value);
     }
+
+    /// Atomically sets the bits of the mask in the usize-sized sequence of bytes and returns its previous value.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure that the pointer is aligned to the size of usize and that the memory is accessed only atomically while this
+    /// function executes.
+    pub unsafe fn fetch_or(&self, mask: usize) -> usize {
+        (*(self.0 as *const AtomicUsize)).fetch_or(mask, Ordering::AcqRel)
+    }
 }
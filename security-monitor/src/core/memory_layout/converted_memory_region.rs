@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+
+/// A memory region that the hypervisor converted from the non-confidential memory into the confidential memory at runtime. The region
+/// is naturally aligned and its size is a power of two, so that a single PMP entry can protect it. The conversion state is tracked for
+/// the entire region, not for its pages, because the hypervisor's access to the region is granted and revoked by that PMP entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvertedMemoryRegion {
+    start: usize,
+    size_in_bytes: usize,
+    state: ConversionState,
+    // Set when the hypervisor requested a global memory fence after the last state change. Only then the transitive state can complete,
+    // because harts might otherwise never be asked to apply the PMP configuration reflecting the new state.
+    is_fenced: bool,
+}
+
+/// The conversion of memory between the non-confidential and confidential pools requires reconfiguring PMPs on all harts. Harts do it
+/// lazily, so a region remains in a transitive state until all harts applied the new PMP configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConversionState {
+    /// The security monitor took the ownership of the region but some harts might still allow the hypervisor to access it.
+    Converting,
+    /// All harts deny the hypervisor access to the region and its pages are owned by the page allocator.
+    Confidential,
+    /// The region's pages have been zeroized and removed from the page allocator. Some harts might still deny the hypervisor access to
+    /// the region.
+    Releasing,
+}
+
+impl ConvertedMemoryRegion {
+    pub(super) fn new(start: usize, size_in_bytes: usize) -> Self {
+        Self { start, size_in_bytes, state: ConversionState::Converting, is_fenced: false }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.start + self.size_in_bytes
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+
+    pub fn state(&self) -> ConversionState {
+        self.state
+    }
+
+    pub(super) fn set_state(&mut self, state: ConversionState) {
+        self.state = state;
+        self.is_fenced = false;
+    }
+
+    /// Records that the hypervisor requested a global memory fence. Only regions in a transitive state wait for the fence.
+    pub(super) fn fence(&mut self) {
+        if self.state != ConversionState::Confidential {
+            self.is_fenced = true;
+        }
+    }
+
+    /// Returns error if no global memory fence was requested since the region's last state change, because no hart is then guaranteed
+    /// to ever apply the PMP configuration reflecting the new state.
+    pub fn assure_confirmable(&self) -> Result<(), Error> {
+        assure!(self.state == ConversionState::Confidential || self.is_fenced, Error::MemoryConversionNotFenced())
+    }
+
+    /// Returns error if the region's pages are not owned by the page allocator, i.e., the conversion of the region has not been
+    /// confirmed yet or the region is already being released.
+    pub fn assure_reclaimable(&self) -> Result<(), Error> {
+        assure!(self.state == ConversionState::Confidential, Error::InvalidMemoryRegion())
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.start <= address && address < self.end()
+    }
+
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: usize = 0x8000_0000;
+    const SIZE: usize = 0x20_0000;
+
+    #[test]
+    fn conversion_completes_after_mark_fence_and_confirm() {
+        let mut region = ConvertedMemoryRegion::new(START, SIZE);
+        assert_eq!(region.state(), ConversionState::Converting);
+        assert!(matches!(region.assure_confirmable(), Err(Error::MemoryConversionNotFenced())));
+        region.fence();
+        assert!(region.assure_confirmable().is_ok());
+        region.set_state(ConversionState::Confidential);
+        assert!(region.assure_confirmable().is_ok());
+        assert!(region.assure_reclaimable().is_ok());
+    }
+
+    #[test]
+    fn reclaim_before_confirmed_conversion_is_rejected() {
+        let mut region = ConvertedMemoryRegion::new(START, SIZE);
+        assert!(matches!(region.assure_reclaimable(), Err(Error::InvalidMemoryRegion())));
+        region.fence();
+        assert!(matches!(region.assure_reclaimable(), Err(Error::InvalidMemoryRegion())));
+    }
+
+    #[test]
+    fn release_requires_new_fence() {
+        let mut region = ConvertedMemoryRegion::new(START, SIZE);
+        region.fence();
+        region.set_state(ConversionState::Confidential);
+        // A fence requested while the region is confidential does not cover its later release.
+        region.fence();
+        region.set_state(ConversionState::Releasing);
+        assert!(matches!(region.assure_confirmable(), Err(Error::MemoryConversionNotFenced())));
+        assert!(matches!(region.assure_reclaimable(), Err(Error::InvalidMemoryRegion())));
+        region.fence();
+        assert!(region.assure_confirmable().is_ok());
+    }
+
+    #[test]
+    fn overlaps_detects_shared_bytes_only() {
+        let region = ConvertedMemoryRegion::new(START, SIZE);
+        assert!(region.overlaps(START, START + 1));
+        assert!(region.overlaps(START - 1, START + SIZE + 1));
+        assert!(region.overlaps(START + SIZE - 1, START + SIZE));
+        assert!(!region.overlaps(START - SIZE, START));
+        assert!(!region.overlaps(START + SIZE, START + 2 * SIZE));
+        assert!(region.contains(START) && !region.contains(START + SIZE));
+    }
+}
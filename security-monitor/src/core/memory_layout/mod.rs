@@ -3,19 +3,25 @@
 // SPDX-License-Identifier: Apache-2.0
 pub use confidential_memory_address::ConfidentialMemoryAddress;
 pub use confidential_vm_physical_address::ConfidentialVmPhysicalAddress;
+pub use converted_memory_region::{ConversionState, ConvertedMemoryRegion};
 pub use non_confidential_memory_address::NonConfidentialMemoryAddress;
 
 use crate::core::memory_protector::PageSize;
 use crate::error::{Error, InitType};
 use pointers_utility::{ptr_align, ptr_byte_add_mut, ptr_byte_offset};
-use spin::Once;
+use spin::{Once, RwLock};
 
 mod confidential_memory_address;
 mod confidential_vm_physical_address;
+mod converted_memory_region;
 mod non_confidential_memory_address;
 
 const NOT_INITIALIZED_MEMORY_LAYOUT: &str = "Bug. Could not access MemoryLayout because is has not been initialized";
 
+/// The maximum number of memory regions that can be converted from the non-confidential into the confidential memory at runtime. Every
+/// such region requires a dedicated PMP entry.
+pub const MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS: usize = 2;
+
 /// MEMORY_LAYOUT is a static variable (private to this module) that is set during the system boot and never changes
 /// later -- this is guaranteed by Once<>. It stores an instance of the `MemoryLayout`. The only way to get a shared
 /// access to this instance is by calling `MemoryLayout::read()` function.
@@ -23,11 +29,16 @@ static MEMORY_LAYOUT: Once<MemoryLayout> = Once::new();
 
 /// Provides an interface to offset addresses that are guaranteed to remain inside the same memory region, i.e.,
 /// confidential or non-confidential memory.
+///
+/// The confidential memory consists of the region defined during the boot and memory regions that the hypervisor converted from the
+/// non-confidential memory at runtime. A converted region is not part of the non-confidential memory, regardless of its conversion
+/// state.
 pub struct MemoryLayout {
     non_confidential_memory_start: *mut usize,
     non_confidential_memory_end: *const usize,
     confidential_memory_start: *mut usize,
     confidential_memory_end: *const usize,
+    converted_memory_regions: RwLock<[Option<ConvertedMemoryRegion>; MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS]>,
 }
 
 /// Send+Sync are not automatically declared on the `MemoryLayout` type because it stores internally raw pointers that
@@ -75,6 +86,7 @@ impl MemoryLayout {
             non_confidential_memory_end,
             confidential_memory_start,
             confidential_memory_end,
+            converted_memory_regions: RwLock::new([None; MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS]),
         });
 
         Ok((ConfidentialMemoryAddress::new(confidential_memory_start), confidential_memory_end))
@@ -85,8 +97,8 @@ impl MemoryLayout {
     pub fn confidential_address_at_offset(
         &self, address: &ConfidentialMemoryAddress, offset_in_bytes: usize,
     ) -> Result<ConfidentialMemoryAddress, Error> {
-        let incremented_address =
-            unsafe { address.add(offset_in_bytes, self.confidential_memory_end) }.map_err(|_| Error::MemoryAccessAuthorization())?;
+        let upper_bound = self.confidential_region_end(address.as_usize()).ok_or(Error::MemoryAccessAuthorization())?;
+        let incremented_address = unsafe { address.add(offset_in_bytes, upper_bound) }.map_err(|_| Error::MemoryAccessAuthorization())?;
         Ok(incremented_address)
     }
 
@@ -95,7 +107,8 @@ impl MemoryLayout {
     pub fn confidential_address_at_offset_bounded(
         &self, address: &ConfidentialMemoryAddress, offset_in_bytes: usize, upper_bound: *const usize,
     ) -> Result<ConfidentialMemoryAddress, Error> {
-        assure!(upper_bound <= self.confidential_memory_end, Error::MemoryAccessAuthorization())?;
+        let region_end = self.confidential_region_end(address.as_usize()).ok_or(Error::MemoryAccessAuthorization())?;
+        assure!(upper_bound <= region_end, Error::MemoryAccessAuthorization())?;
         Ok(self.confidential_address_at_offset(address, offset_in_bytes)?)
     }
 
@@ -106,12 +119,112 @@ impl MemoryLayout {
     ) -> Result<NonConfidentialMemoryAddress, Error> {
         let incremented_address =
             unsafe { address.add(offset_in_bytes, self.non_confidential_memory_end) }.map_err(|_| Error::MemoryAccessAuthorization())?;
+        assure_not!(self.is_in_converted_memory_region(incremented_address.usize()), Error::MemoryAccessAuthorization())?;
         Ok(incremented_address)
     }
 
     /// Returns true if the raw pointer is inside the non-confidential memory.
     pub fn is_in_non_confidential_range(&self, address: *const usize) -> bool {
-        self.non_confidential_memory_start as *const usize <= address && address < self.non_confidential_memory_end
+        self.non_confidential_memory_start as *const usize <= address
+            && address < self.non_confidential_memory_end
+            && !self.is_in_converted_memory_region(address as usize)
+    }
+
+    /// Starts the conversion of a non-confidential memory region into the confidential memory. From now on, the security monitor does
+    /// not treat this region as the non-confidential memory. Returns error if the region is not a naturally aligned and power-of-two
+    /// sized region of the non-confidential memory, if it overlaps with another converted region, or if there is no free PMP entry to
+    /// protect it.
+    pub fn add_converted_memory_region(&self, start: usize, size_in_bytes: usize) -> Result<(), Error> {
+        let is_size_valid = size_in_bytes.is_power_of_two() && size_in_bytes >= PageSize::smallest().in_bytes();
+        assure!(is_size_valid && start % size_in_bytes == 0, Error::InvalidMemoryRegion())?;
+        let end = start.checked_add(size_in_bytes).ok_or(Error::InvalidMemoryRegion())?;
+        assure!(
+            self.non_confidential_memory_start as usize <= start && end <= self.non_confidential_memory_end as usize,
+            Error::InvalidMemoryRegion()
+        )?;
+        let mut regions = self.converted_memory_regions.write();
+        assure_not!(regions.iter().flatten().any(|region| region.overlaps(start, end)), Error::InvalidMemoryRegion())?;
+        let free_slot = regions.iter_mut().find(|region| region.is_none()).ok_or(Error::TooManyConvertedMemoryRegions())?;
+        *free_slot = Some(ConvertedMemoryRegion::new(start, size_in_bytes));
+        Ok(())
+    }
+
+    /// Returns the converted memory region that has exactly the given boundaries.
+    pub fn converted_memory_region(&self, start: usize, size_in_bytes: usize) -> Option<ConvertedMemoryRegion> {
+        self.converted_memory_regions
+            .read()
+            .iter()
+            .flatten()
+            .find(|region| region.start() == start && region.size_in_bytes() == size_in_bytes)
+            .copied()
+    }
+
+    /// Returns a snapshot of all converted memory regions. The index of a region in the returned array is stable during the region's
+    /// lifetime.
+    pub fn converted_memory_regions(&self) -> [Option<ConvertedMemoryRegion>; MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS] {
+        *self.converted_memory_regions.read()
+    }
+
+    /// Atomically changes the state of the converted memory region. Returns false if the region is not in the expected state, so that
+    /// only one of the harts concurrently processing the same region performs the transition.
+    pub fn change_converted_memory_region_state(&self, region: &ConvertedMemoryRegion, from: ConversionState, to: ConversionState) -> bool {
+        self.converted_memory_regions
+            .write()
+            .iter_mut()
+            .flatten()
+            .find(|r| r.start() == region.start() && r.size_in_bytes() == region.size_in_bytes() && r.state() == from)
+            .map(|r| r.set_state(to))
+            .is_some()
+    }
+
+    /// Marks all converted memory regions in a transitive state as fenced, i.e., covered by the PMP synchronization requested right
+    /// after this call. The conversion of a region can complete only after all harts applied the PMP configuration of a fence that
+    /// followed the region's last state change.
+    pub fn fence_converted_memory_regions(&self) {
+        self.converted_memory_regions.write().iter_mut().flatten().for_each(|region| region.fence());
+    }
+
+    /// Removes the converted memory region that has been released back to the hypervisor. Returns false if the region is not in the
+    /// `Releasing` state.
+    pub fn remove_converted_memory_region(&self, region: &ConvertedMemoryRegion) -> bool {
+        let is_released =
+            |r: &Option<ConvertedMemoryRegion>| r.is_some_and(|r| r.start() == region.start() && r.state() == ConversionState::Releasing);
+        self.converted_memory_regions.write().iter_mut().find(|r| is_released(r)).map(|r| *r = None).is_some()
+    }
+
+    /// Returns the boundaries of the converted memory region. Returns error if the region is not part of the confidential memory.
+    pub fn converted_memory_region_boundary(
+        &self, region: &ConvertedMemoryRegion,
+    ) -> Result<(ConfidentialMemoryAddress, *const usize), Error> {
+        assure!(self.confidential_region_end(region.start()) == Some(region.end() as *const usize), Error::InvalidMemoryRegion())?;
+        Ok((ConfidentialMemoryAddress::new(region.start() as *mut usize), region.end() as *const usize))
+    }
+
+    /// Writes 0s to the entire converted memory region.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own the converted memory region and guarantee that no other hart accesses it.
+    pub unsafe fn clear_converted_memory_region(&self, region: &ConvertedMemoryRegion) {
+        Self::clear_memory_region(region.start() as *mut usize, region.end() as *const usize);
+    }
+
+    /// Returns the end of the confidential memory region that contains the given address or `None` if the address is not in the
+    /// confidential memory.
+    fn confidential_region_end(&self, address: usize) -> Option<*const usize> {
+        if self.confidential_memory_start as usize <= address && address < self.confidential_memory_end as usize {
+            return Some(self.confidential_memory_end);
+        }
+        self.converted_memory_regions
+            .read()
+            .iter()
+            .flatten()
+            .find(|region| region.state() == ConversionState::Confidential && region.contains(address))
+            .map(|region| region.end() as *const usize)
+    }
+
+    fn is_in_converted_memory_region(&self, address: usize) -> bool {
+        self.converted_memory_regions.read().iter().flatten().any(|region| region.contains(address))
     }
 
     /// Clears all confidential memory, writting to it 0s.
@@ -121,13 +234,22 @@ impl MemoryLayout {
     /// Caller must guarantee that there is no other thread that can write to confidential memory during execution of
     /// this function.
     pub unsafe fn clear_confidential_memory(&self) {
-        // We can safely cast the below offset to usize because the constructor guarantees that the confidential memory
-        // range is valid, and so the memory size must be a valid usize
-        let memory_size = ptr_byte_offset(self.confidential_memory_end, self.confidential_memory_start) as usize;
+        Self::clear_memory_region(self.confidential_memory_start, self.confidential_memory_end);
+        self.converted_memory_regions
+            .read()
+            .iter()
+            .flatten()
+            .filter(|region| region.state() == ConversionState::Confidential)
+            .for_each(|region| Self::clear_memory_region(region.start() as *mut usize, region.end() as *const usize));
+    }
+
+    unsafe fn clear_memory_region(memory_start: *mut usize, memory_end: *const usize) {
+        // We can safely cast the below offset to usize because callers guarantee that the memory range is valid, and so the memory size
+        // must be a valid usize
+        let memory_size = ptr_byte_offset(memory_end, memory_start) as usize;
         let usize_alligned_offsets = (0..memory_size).step_by(core::mem::size_of::<usize>());
         usize_alligned_offsets.for_each(|offset_in_bytes| {
-            let _ = ptr_byte_add_mut(self.confidential_memory_start, offset_in_bytes, self.confidential_memory_end)
-                .and_then(|ptr| Ok(ptr.write_volatile(0)));
+            let _ = ptr_byte_add_mut(memory_start, offset_in_bytes, memory_end).and_then(|ptr| Ok(ptr.write_volatile(0)));
         });
     }
 
@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static UPDATE_COUNTERS: [AtomicUsize; AccessedDirtyUpdate::NUMBER_OF_COUNTERS] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+static ARE_HARDWARE_UPDATES_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The type of the memory access that caused a guest page fault.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GuestMemoryAccess {
+    Fetch,
+    Load,
+    Store,
+}
+
+/// The accessed and dirty bits of a G-stage leaf entry that the security monitor sets on behalf of the hardware. Without the Svadu
+/// extension, the hardware raises a guest page fault on an access to a page whose accessed bit is clear and on a store to a page whose
+/// dirty bit is clear, expecting the software to set the bit.
+#[derive(Clone, Copy, Debug)]
+pub struct AccessedDirtyUpdate {
+    sets_accessed: bool,
+    sets_dirty: bool,
+}
+
+impl AccessedDirtyUpdate {
+    pub const NUMBER_OF_COUNTERS: usize = 2;
+
+    /// Returns the update of the entry's bits that the access requires, or None if the access requires no update.
+    pub fn required(was_accessed: bool, is_dirty: bool, access: GuestMemoryAccess) -> Option<Self> {
+        let update = Self { sets_accessed: !was_accessed, sets_dirty: !is_dirty && access == GuestMemoryAccess::Store };
+        (update.sets_accessed || update.sets_dirty).then_some(update)
+    }
+
+    pub fn sets_accessed(&self) -> bool {
+        self.sets_accessed
+    }
+
+    pub fn sets_dirty(&self) -> bool {
+        self.sets_dirty
+    }
+
+    /// Lets the hardware set the accessed and dirty bits of G-stage entries. Called during the boot if all harts implement the Svadu
+    /// extension. The security monitor still sets the bits when a guest page fault shows that the hardware did not.
+    pub fn enable_hardware_updates() {
+        ARE_HARDWARE_UPDATES_ENABLED.store(true, Ordering::Release);
+    }
+
+    pub fn are_hardware_updates_enabled() -> bool {
+        ARE_HARDWARE_UPDATES_ENABLED.load(Ordering::Acquire)
+    }
+
+    /// Returns how many accessed bits and how many dirty bits, in this order, the security monitor set since the boot.
+    pub fn counters() -> [usize; Self::NUMBER_OF_COUNTERS] {
+        [&UPDATE_COUNTERS[0], &UPDATE_COUNTERS[1]].map(|counter| counter.load(Ordering::Relaxed))
+    }
+
+    pub fn count(&self) {
+        if self.sets_accessed {
+            UPDATE_COUNTERS[0].fetch_add(1, Ordering::Relaxed);
+        }
+        if self.sets_dirty {
+            UPDATE_COUNTERS[1].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
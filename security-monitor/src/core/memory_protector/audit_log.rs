@@ -0,0 +1,86 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// A change of the PMP configuration that controls the hypervisor's access to memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum PmpOperation {
+    /// The hypervisor was granted access to a single confidential page.
+    GrantTemporaryAccess = 1,
+    /// The access granted with `GrantTemporaryAccess` expired. The region is empty because the PMP entry is cleared entirely.
+    RevokeTemporaryAccess = 2,
+    /// A region converted into the confidential memory is protected from the hypervisor.
+    ProtectConvertedMemory = 3,
+    /// A region released from the confidential memory is accessible to the hypervisor again.
+    ReleaseConvertedMemory = 4,
+    /// The access to the confidential memory was closed before exiting to the hypervisor.
+    Enable = 5,
+}
+
+/// A physical memory region affected by a PMP operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryRegion {
+    start: usize,
+    size_in_bytes: usize,
+}
+
+impl MemoryRegion {
+    pub fn new(start: usize, size_in_bytes: usize) -> Self {
+        Self { start, size_in_bytes }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AuditEntry {
+    timestamp: u64,
+    operation: PmpOperation,
+    region: MemoryRegion,
+}
+
+impl AuditEntry {
+    /// Encodes the entry in four words: the value of the `time` CSR when the operation happened, the operation, and the start and the
+    /// size of the region.
+    fn encode(&self) -> [usize; 4] {
+        [self.timestamp as usize, self.operation as usize, self.region.start, self.region.size_in_bytes]
+    }
+}
+
+/// A ring buffer retaining the most recent changes of the PMP configuration on a physical hart. Changes that widen the hypervisor's
+/// access to memory are security-relevant events, so a management VM can collect the log to audit them.
+pub struct AuditLog {
+    entries: [Option<AuditEntry>; Self::CAPACITY],
+    // The total number of entries recorded since the boot. The next entry is stored at `number_of_records % CAPACITY`.
+    number_of_records: usize,
+}
+
+impl AuditLog {
+    pub const CAPACITY: usize = 256;
+    pub const ENTRY_SIZE_IN_BYTES: usize = 4 * core::mem::size_of::<usize>();
+    pub const SIZE_IN_BYTES: usize = Self::CAPACITY * Self::ENTRY_SIZE_IN_BYTES;
+
+    pub const fn empty() -> Self {
+        Self { entries: [None; Self::CAPACITY], number_of_records: 0 }
+    }
+
+    pub fn record(&mut self, operation: PmpOperation, region: MemoryRegion) {
+        let timestamp = crate::core::architecture::CSR.time.read() as u64;
+        self.entries[self.number_of_records % Self::CAPACITY] = Some(AuditEntry { timestamp, operation, region });
+        self.number_of_records = self.number_of_records.wrapping_add(1);
+    }
+
+    pub fn number_of_records(&self) -> usize {
+        self.number_of_records
+    }
+
+    /// Returns the encoded retained entries from the oldest to the most recent one, see `AuditEntry::encode`.
+    pub fn encoded_entries(&self) -> impl Iterator<Item = [usize; 4]> + '_ {
+        let number_of_retained_entries = core::cmp::min(self.number_of_records, Self::CAPACITY);
+        let oldest = self.number_of_records.wrapping_sub(number_of_retained_entries);
+        (0..number_of_retained_entries).filter_map(move |index| Some(self.entries[oldest.wrapping_add(index) % Self::CAPACITY]?.encode()))
+    }
+}
@@ -1,13 +1,21 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{HartArchitecturalState, Hgatp};
-use crate::core::control_data::ConfidentialVmId;
-use crate::core::memory_layout::{ConfidentialMemoryAddress, ConfidentialVmPhysicalAddress};
+use crate::core::architecture::{compute_hgatp, HartArchitecturalState, Hgatp, Satp};
+use crate::core::crypto::zeroize;
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::{ConfidentialMemoryAddress, ConfidentialVmPhysicalAddress, MemoryLayout};
+use crate::core::memory_protector::demand_page_tracker::DemandPageTracker;
 use crate::core::memory_protector::mmu::RootPageTable;
-use crate::core::memory_protector::{mmu, pmp};
-use crate::core::page_allocator::SharedPage;
+use crate::core::memory_protector::{
+    mmu, pmp, AccessPermissions, GuestMemoryAccess, PageSize, PageTableUsage, PendingTlbShootdown, TlbShootdown, Vmid, VmidAllocator,
+};
+use crate::core::page_allocator::{Allocated, Page, PageAllocator, SharedPage};
 use crate::error::Error;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::Mutex;
 
 /// Exposes an interface to configure the hardware memory isolation component in a way that
 /// it protects accesses to the memory which the ConfidentialVM does not own.
@@ -16,6 +24,14 @@ pub struct ConfidentialVmMemoryProtector {
     root_page_table: RootPageTable,
     // stores the value of the hypervisor G-stage address translation protocol register.
     hgatp: usize,
+    // tags the G-stage address translations cached by the hardware, assigned once the confidential VM is created.
+    vmid: Option<Vmid>,
+    // stores pages of the confidential VM that are mapped on the first access.
+    demand_pages: DemandPageTracker,
+    // regions that the confidential VM gave back to the hypervisor, indexed by their start addresses and pointing to their end addresses.
+    absent_regions: BTreeMap<usize, usize>,
+    // requested once the confidential VM stopped executing, must complete before its pages are reclaimed.
+    tlb_shootdown: Option<PendingTlbShootdown>,
 }
 
 impl ConfidentialVmMemoryProtector {
@@ -29,34 +45,437 @@ impl ConfidentialVmMemoryProtector {
     pub fn from_vm_state(hart_state: &HartArchitecturalState) -> Result<Self, Error> {
         let hgatp = Hgatp::from(hart_state.hgatp);
         let root_page_table = mmu::copy_mmu_configuration_from_non_confidential_memory(hgatp)?;
-        Ok(Self { root_page_table, hgatp: 0 })
+        Ok(Self {
+            root_page_table,
+            hgatp: 0,
+            vmid: None,
+            demand_pages: DemandPageTracker::empty(),
+            absent_regions: BTreeMap::new(),
+            tlb_shootdown: None,
+        })
     }
 
-    pub fn set_confidential_vm_id(&mut self, id: ConfidentialVmId) {
-        let hgatp = Hgatp::new(self.root_page_table.address(), self.root_page_table.paging_system().hgatp_mode(), id.usize());
-        self.hgatp = hgatp.bits();
+    /// Constructs the memory protector of a confidential VM that does not own any memory yet. Memory is added with
+    /// `map_confidential_page` while the confidential VM is being constructed. The G-stage translation mode is the smallest one that
+    /// translates all guest physical addresses below `guest_physical_address_end` (see `mmu::empty_mmu_configuration`).
+    pub fn empty(guest_physical_address_end: Option<usize>) -> Result<Self, Error> {
+        let root_page_table = mmu::empty_mmu_configuration(guest_physical_address_end)?;
+        Ok(Self {
+            root_page_table,
+            hgatp: 0,
+            vmid: None,
+            demand_pages: DemandPageTracker::empty(),
+            absent_regions: BTreeMap::new(),
+            tlb_shootdown: None,
+        })
+    }
+
+    /// Assigns a VMID to the confidential VM. Confidential VMs have distinct VMIDs, so the hardware can keep their address translations
+    /// cached side by side. If the hardware does not implement VMIDs, the confidential VM uses the VMID 0 and, because it holds no VMID,
+    /// all translations are fenced whenever it starts or stops executing on a hart.
+    pub fn assign_vmid(&mut self, vmid_allocator: &mut VmidAllocator) {
+        self.vmid = vmid_allocator.alloc();
+        let vmid_value = self.vmid.map_or(0, |vmid| vmid.value());
+        self.hgatp = compute_hgatp(self.root_page_table.ppn(), self.root_page_table.paging_system().hgatp_mode(), vmid_value);
+    }
+
+    /// Returns the VMID of a confidential VM that will never execute again to the allocator. Every hart fences its translations before
+    /// it executes a confidential VM that gets the same VMID, see `fence_on_all_harts`.
+    pub fn release_vmid(&mut self, vmid_allocator: &mut VmidAllocator) {
+        if let Some(vmid) = self.vmid.take() {
+            self.fence_on_all_harts();
+            vmid_allocator.free(vmid);
+        }
+    }
+
+    pub fn vmid(&self) -> Option<&Vmid> {
+        self.vmid.as_ref()
     }
 
     /// Modifies the configuration of the underlying hardware memory isolation component (e.g., MMU) in a way that a
     /// shared page is mapped into the address space of the confidential VM.
     pub fn map_shared_page(&mut self, shared_page: SharedPage) -> Result<(), Error> {
-        self.root_page_table.map_shared_page(shared_page)?;
-        super::tlb::tlb_shutdown();
+        // The hypervisor might have converted the shared page's memory into the confidential memory since the shared page was created.
+        let address = shared_page.non_confidential_address() as *const usize;
+        assure!(MemoryLayout::read().is_in_non_confidential_range(address), Error::MemoryAccessAuthorization())?;
+        let (address, page_size) = (shared_page.confidential_vm_virtual_address(), shared_page.page_size());
+        let overlaps_owned_memory =
+            self.demand_pages.overlaps(address, page_size.in_bytes()) || self.overlaps_absent_region(address.usize(), page_size.in_bytes());
+        assure_not!(overlaps_owned_memory, Error::AddressAlreadyMapped())?;
+        let fenced_region = self.root_page_table.map_shared_page(shared_page)?.unwrap_or((address, page_size));
+        super::tlb::fence_guest_physical_addresses(&[fenced_region], self.vmid.as_ref());
         Ok(())
     }
 
-    /// Modifies the configuration of the underlying hardware memory isolation component (e.g., MMU) in a way that a
-    /// shared page is unmapped from the address space of the confidential VM.
-    pub fn unmap_shared_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<(), Error> {
-        self.root_page_table.unmap_shared_page(address)?;
-        super::tlb::tlb_shutdown();
+    /// Returns the maximum number of pages that the security monitor allocates when it maps a page in the confidential VM's address
+    /// space, so that the hypervisor knows how much memory to provide when the mapping failed due to a lack of memory.
+    pub fn max_pages_to_map_page(&self) -> usize {
+        self.root_page_table.max_pages_to_map_page()
+    }
+
+    pub fn page_table_usage(&self) -> &PageTableUsage {
+        self.root_page_table.usage()
+    }
+
+    /// Maps a page owned by the confidential VM at the given guest physical address. Returns error if the address is not aligned to the
+    /// page size or is already mapped. No TLB flush is needed because the confidential VM has never executed.
+    pub fn map_confidential_page(&mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>) -> Result<(), Error> {
+        if address.usize() % page.size().in_bytes() != 0 {
+            PageAllocator::release_zeroed_page(page.deallocate());
+            return Err(Error::AddressNotAligned());
+        }
+        self.root_page_table.map_confidential_page(address, page)
+    }
+
+    /// Records a page owned by the confidential VM that is mapped at the given guest physical address only when the confidential VM
+    /// accesses it for the first time (see `map_demand_page`). Returns error if the address is not aligned to the page size or is
+    /// already mapped.
+    pub fn add_demand_page(&mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>) -> Result<(), Error> {
+        let error = if address.usize() % page.size().in_bytes() != 0 {
+            Error::AddressNotAligned()
+        } else if self.translate(address).is_ok() {
+            Error::AddressAlreadyMapped()
+        } else {
+            return self.demand_pages.insert(address, page);
+        };
+        PageAllocator::release_zeroed_page(page.deallocate());
+        Err(error)
+    }
+
+    /// Maps the page recorded with `add_demand_page` that contains the guest physical address and fences its stale translations on this
+    /// hart. If another hart mapped the page since this hart faulted, only the stale translation is fenced, provided that the page
+    /// permits the access. Returns error if the address is neither in a recorded page nor mapped, or if the mapping failed, e.g., due
+    /// to a lack of memory for page tables, in which case the page stays recorded.
+    pub fn map_demand_page(&mut self, address: ConfidentialVmPhysicalAddress, access: GuestMemoryAccess) -> Result<(), Error> {
+        let (page_address, page) = match self.demand_pages.take(address) {
+            Some(demand_page) => demand_page,
+            None => {
+                let (_, permissions) = self.root_page_table.host_translation(address)?;
+                let is_permitted = match access {
+                    GuestMemoryAccess::Fetch => permissions.can_execute,
+                    GuestMemoryAccess::Load => permissions.can_read,
+                    GuestMemoryAccess::Store => permissions.can_write,
+                };
+                assure!(is_permitted, Error::MemoryAccessAuthorization())?;
+                let page_size = *self.root_page_table.confidential_page(address)?.size();
+                let page_address = ConfidentialVmPhysicalAddress::new(address.usize() & !(page_size.in_bytes() - 1));
+                super::tlb::fence_guest_physical_address(page_address, page_size, self.vmid.as_ref());
+                return Ok(());
+            }
+        };
+        let page_size = *page.size();
+        if let Err((error, page)) = self.root_page_table.try_map_confidential_page(page_address, page) {
+            return self.demand_pages.insert(page_address, page).and(Err(error));
+        }
+        super::tlb::fence_guest_physical_address(page_address, page_size, self.vmid.as_ref());
+        Ok(())
+    }
+
+    /// Returns true if the confidential VM owns the memory at the given guest physical address, regardless of whether it is mapped
+    /// yet or it was given back to the hypervisor with `remove_region`.
+    pub fn owns(&self, address: ConfidentialVmPhysicalAddress) -> bool {
+        self.translate(address).is_ok() || self.demand_pages.overlaps(address, 1) || self.overlaps_absent_region(address.usize(), 1)
+    }
+
+    /// Unmaps the pages owned by the confidential VM in the given region, clears them, and returns them to the page allocator, from
+    /// which the hypervisor can reclaim the memory (memory ballooning). The region is recorded as absent until `restore_region` backs
+    /// it with memory again, so that accesses to it fault instead of being forwarded to the hypervisor as MMIO. Returns error, leaving
+    /// the address space unchanged, if any address in the region is not backed by a page owned by the confidential VM, e.g., it maps
+    /// a shared page, or if a page extends beyond the region.
+    pub fn remove_region(&mut self, address: ConfidentialVmPhysicalAddress, size_in_bytes: usize) -> Result<(), Error> {
+        let end = address.usize().checked_add(size_in_bytes).ok_or(Error::InvalidMemoryRegion())?;
+        let mut page_addresses = Vec::new();
+        let mut current_address = address.usize();
+        while current_address < end {
+            let page_address = ConfidentialVmPhysicalAddress::new(current_address);
+            let page_size = match (self.root_page_table.confidential_page(page_address), self.demand_pages.get(page_address)) {
+                (Ok(page), _) | (Err(_), Some((_, page))) => *page.size(),
+                (Err(_), None) if self.root_page_table.host_translation(page_address).is_ok() => return Err(Error::PageAlreadyShared()),
+                (Err(error), None) => return Err(error),
+            };
+            // Pages are aligned to their size, so a page that starts at the current address is not shared with the preceding region.
+            assure!(current_address % page_size.in_bytes() == 0, Error::InvalidMemoryRegion())?;
+            assure!(page_size.in_bytes() <= end - current_address, Error::InvalidMemoryRegion())?;
+            page_addresses.push(page_address);
+            current_address += page_size.in_bytes();
+        }
+        let mut pages = Vec::with_capacity(page_addresses.len());
+        let mut unmapped_regions = Vec::new();
+        for page_address in page_addresses {
+            match self.demand_pages.take(page_address) {
+                Some((_, page)) => pages.push(page),
+                None => {
+                    let page = self.root_page_table.unmap_confidential_page(page_address)?;
+                    unmapped_regions.push((page_address, *page.size()));
+                    pages.push(page);
+                }
+            }
+        }
+        super::tlb::fence_guest_physical_addresses(&unmapped_regions, self.vmid.as_ref());
+        PageAllocator::release_zeroed_pages(pages.into_iter().map(|page| page.deallocate()).collect());
+        self.absent_regions.insert(address.usize(), end);
         Ok(())
     }
 
+    /// Backs the region given back with `remove_region` with zeroed 4KiB pages of the confidential memory. Returns error if the region
+    /// does not match exactly a region given back before. If the security monitor runs out of memory, the pages mapped so far are
+    /// removed again and the region stays absent, so that the request can be repeated once the hypervisor provided more memory.
+    pub fn restore_region(&mut self, address: ConfidentialVmPhysicalAddress, size_in_bytes: usize) -> Result<(), Error> {
+        let start = address.usize();
+        let end = start.checked_add(size_in_bytes).ok_or(Error::InvalidMemoryRegion())?;
+        assure!(self.absent_regions.get(&start) == Some(&end), Error::InvalidMemoryRegion())?;
+        let regions: Vec<_> = (start..end)
+            .step_by(PageSize::Size4KiB.in_bytes())
+            .map(|page_address| (ConfidentialVmPhysicalAddress::new(page_address), PageSize::Size4KiB))
+            .collect();
+        for (index, (page_address, _)) in regions.iter().enumerate() {
+            let result =
+                PageAllocator::acquire_zeroed_page().and_then(|page| self.root_page_table.map_confidential_page(*page_address, page));
+            if let Err(error) = result {
+                let mapped_regions = &regions[..index];
+                let pages: Vec<_> = mapped_regions
+                    .iter()
+                    .filter_map(|(page_address, _)| self.root_page_table.unmap_confidential_page(*page_address).ok())
+                    .collect();
+                super::tlb::fence_guest_physical_addresses(mapped_regions, self.vmid.as_ref());
+                PageAllocator::release_zeroed_pages(pages.into_iter().map(|page| page.deallocate()).collect());
+                return Err(error);
+            }
+        }
+        self.absent_regions.remove(&start);
+        super::tlb::fence_guest_physical_addresses(&regions, self.vmid.as_ref());
+        Ok(())
+    }
+
+    fn overlaps_absent_region(&self, start: usize, size_in_bytes: usize) -> bool {
+        match start.checked_add(size_in_bytes) {
+            Some(end) => self.absent_regions.range(..end).next_back().is_some_and(|(_, region_end)| *region_end > start),
+            None => true,
+        }
+    }
+
+    /// Modifies the configuration of the underlying hardware memory isolation component (e.g., MMU) in a way that a
+    /// shared page is unmapped from the address space of the confidential VM. The guest physical address is backed by a zeroed page of
+    /// the confidential memory afterwards, so the confidential VM never observes content written by the hypervisor. Returns the
+    /// unmapped shared page, which the hypervisor can reuse after the TLB shutdown completes.
+    ///
+    /// Shared pages are located in the non-confidential memory, which the hypervisor accesses without any PMP entry. Unsharing a page
+    /// thus does not require reconfiguring the PMP, only removing the mapping from the confidential VM's page table.
+    ///
+    /// Once the address is private again, the page tables on the path to it are merged back into huge pages where possible, so that
+    /// repeated sharing and unsharing does not leave the address space fragmented into 4KiB mappings.
+    pub fn unmap_shared_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<SharedPage, Error> {
+        let page = PageAllocator::acquire_zeroed_page()?;
+        let shared_page = self.root_page_table.unmap_shared_page(address, page)?;
+        super::tlb::fence_guest_physical_address(address, shared_page.page_size(), self.vmid.as_ref());
+        self.root_page_table.merge_pages(address);
+        Ok(shared_page)
+    }
+
+    /// Removes the shared pages from the address space of the confidential VM, leaving their guest physical addresses unmapped. Used
+    /// when the confidential VM is torn down, so no zeroed pages of the confidential memory are needed to back the addresses. The
+    /// translations of all pages are fenced at once. Addresses that do not map a shared page are skipped. Returns the removed pages.
+    pub fn remove_shared_pages(&mut self, addresses: &[ConfidentialVmPhysicalAddress]) -> Vec<SharedPage> {
+        let shared_pages: Vec<_> = addresses.iter().filter_map(|address| self.root_page_table.remove_shared_page(*address).ok()).collect();
+        let regions: Vec<_> = shared_pages.iter().map(|page| (page.confidential_vm_virtual_address(), page.page_size())).collect();
+        super::tlb::fence_guest_physical_addresses(&regions, self.vmid.as_ref());
+        shared_pages
+    }
+
     pub fn translate(&self, address: ConfidentialVmPhysicalAddress) -> Result<&ConfidentialMemoryAddress, Error> {
         self.root_page_table.translate(address)
     }
 
+    /// Emulates the hardware update of the accessed and dirty bits of the G-stage leaf entry that maps the guest physical address, after
+    /// the access faulted because these bits were clear. The bits are set in the software and the hardware representations of the page
+    /// table, and the stale translation is fenced. Returns error if the guest page fault was not caused by these bits, e.g., because the
+    /// address is not mapped or the entry does not permit the access.
+    pub fn update_accessed_dirty_bits(&mut self, address: ConfidentialVmPhysicalAddress, access: GuestMemoryAccess) -> Result<(), Error> {
+        let (page_size, update) = self.root_page_table.set_accessed_dirty_bits(address, access)?;
+        update.count();
+        let page_address = ConfidentialVmPhysicalAddress::new(address.usize() & !(page_size.in_bytes() - 1));
+        super::tlb::fence_guest_physical_address(page_address, page_size, self.vmid.as_ref());
+        Ok(())
+    }
+
+    /// Returns the host physical address to which the guest physical address translates, either in the confidential memory or in a
+    /// shared page, together with the permissions that the G-stage translation grants.
+    pub fn host_translation(&self, address: ConfidentialVmPhysicalAddress) -> Result<(usize, AccessPermissions), Error> {
+        self.root_page_table.host_translation(address)
+    }
+
+    /// Reads a word from the confidential VM's memory at the given guest physical address. Returns error if the address is not aligned
+    /// to the size of the word or it is not mapped to a page owned by the confidential VM.
+    pub fn read_word(&self, address: ConfidentialVmPhysicalAddress) -> Result<usize, Error> {
+        let page = self.root_page_table.confidential_page(address)?;
+        page.read(Self::word_offset_in_page(address, page)?)
+    }
+
+    /// Writes a word to the confidential VM's memory at the given guest physical address. Returns error if the address is not aligned
+    /// to the size of the word or it is not mapped to a page owned by the confidential VM.
+    pub fn write_word(&mut self, address: ConfidentialVmPhysicalAddress, value: usize) -> Result<(), Error> {
+        let page = self.root_page_table.confidential_page_mut(address)?;
+        let offset_in_bytes = Self::word_offset_in_page(address, page)?;
+        page.write(offset_in_bytes, value)
+    }
+
+    /// Fills the buffer with the content of the confidential VM's memory starting at the given guest physical address. The address
+    /// and the size of the buffer must be multiples of the word size.
+    pub fn read_bytes(&self, address: ConfidentialVmPhysicalAddress, buffer: &mut [u8]) -> Result<(), Error> {
+        assure!(buffer.len() % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+        buffer.chunks_exact_mut(core::mem::size_of::<usize>()).enumerate().try_for_each(|(word_index, chunk)| {
+            let word_address = Self::word_address(address, word_index)?;
+            chunk.copy_from_slice(&self.read_word(word_address)?.to_le_bytes());
+            Ok(())
+        })
+    }
+
+    /// Copies the buffer to the confidential VM's memory starting at the given guest physical address. The address and the size of
+    /// the buffer must be multiples of the word size.
+    pub fn write_bytes(&mut self, address: ConfidentialVmPhysicalAddress, buffer: &[u8]) -> Result<(), Error> {
+        assure!(buffer.len() % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+        buffer.chunks_exact(core::mem::size_of::<usize>()).enumerate().try_for_each(|(word_index, chunk)| {
+            let word_address = Self::word_address(address, word_index)?;
+            // The chunk has exactly the size of the word, so the conversion never fails.
+            let value = usize::from_le_bytes(chunk.try_into().map_err(|_| Error::InvalidArgument())?);
+            self.write_word(word_address, value)
+        })
+    }
+
+    /// Copies the buffer to the confidential VM's memory starting at the given guest physical address. Unlike `write_bytes`, neither
+    /// the address nor the size of the buffer have to be multiples of the word size. Bytes of the words that the buffer covers only
+    /// partially are preserved. The memory is not modified if any part of the region is not mapped to pages owned by the confidential VM.
+    pub fn write_unaligned_bytes(&mut self, address: ConfidentialVmPhysicalAddress, buffer: &[u8]) -> Result<(), Error> {
+        let word_size = core::mem::size_of::<usize>();
+        let offset_in_first_word = address.usize() % word_size;
+        let end_address = address.usize().checked_add(buffer.len()).ok_or(Error::InvalidArgument())?;
+        let aligned_end_address = end_address.checked_next_multiple_of(word_size).ok_or(Error::InvalidArgument())?;
+        let aligned_address = ConfidentialVmPhysicalAddress::new(address.usize() - offset_in_first_word);
+        let mut words = vec![0u8; aligned_end_address - aligned_address.usize()];
+        let result = self.read_bytes(aligned_address, &mut words).and_then(|_| {
+            words[offset_in_first_word..offset_in_first_word + buffer.len()].copy_from_slice(buffer);
+            self.write_bytes(aligned_address, &words)
+        });
+        zeroize(&mut words);
+        result
+    }
+
+    /// Returns the digest of the size and the content of the confidential VM's memory region. Returns error if any part of the region
+    /// is not mapped to pages owned by the confidential VM.
+    pub fn digest_of_region(
+        &self, address: ConfidentialVmPhysicalAddress, size_in_bytes: usize,
+    ) -> Result<[u8; Sha384::DIGEST_SIZE_IN_BYTES], Error> {
+        let word_size = core::mem::size_of::<usize>();
+        let start = address.usize();
+        let end = start.checked_add(size_in_bytes).ok_or(Error::InvalidArgument())?;
+        let mut hasher = Sha384::default();
+        hasher.update(&(size_in_bytes as u64).to_le_bytes());
+        // The memory is read in words, so bytes of the first and the last word that lie outside the region are skipped.
+        for word_address in (start - start % word_size..end).step_by(word_size) {
+            let word = self.read_word(ConfidentialVmPhysicalAddress::new(word_address))?.to_le_bytes();
+            let from = start.saturating_sub(word_address);
+            let to = word_size.min(end - word_address);
+            hasher.update(&word[from..to]);
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Returns the digest of the VS-stage page tables referenced by the given `vsatp` value or `None` if the VS-stage address
+    /// translation is disabled. Page tables are visited depth-first in the order of their entries, so the same page table configuration
+    /// always results in the same digest. A page table referenced more than once, e.g., by a malformed self-referential entry, is hashed
+    /// only on its first visit, which guarantees that the walk terminates.
+    pub fn digest_of_vs_stage_page_tables(&self, vsatp: usize) -> Result<Option<[u8; Sha384::DIGEST_SIZE_IN_BYTES]>, Error> {
+        let satp = Satp::from(vsatp);
+        let levels = satp.mode().ok_or(Error::UnsupportedPagingMode())?.levels();
+        if levels == 0 {
+            return Ok(None);
+        }
+        let mut hasher = Sha384::default();
+        let mut visited = BTreeSet::new();
+        let root_address = ConfidentialVmPhysicalAddress::new(satp.address());
+        self.hash_vs_stage_page_table(root_address, levels - 1, &mut visited, &mut hasher)?;
+        Ok(Some(hasher.finalize()))
+    }
+
+    fn hash_vs_stage_page_table(
+        &self, address: ConfidentialVmPhysicalAddress, level: usize, visited: &mut BTreeSet<usize>, hasher: &mut Sha384,
+    ) -> Result<(), Error> {
+        const PAGE_TABLE_SIZE_IN_BYTES: usize = 4096;
+        const VALID_BIT: usize = 0b1;
+        const PERMISSION_BITS: usize = 0b1110;
+        const PTE_PPN_SHIFT: usize = 10;
+        const PTE_PPN_MASK: usize = 0x00000FFFFFFFFFFF;
+        const PAGE_SHIFT: usize = 12;
+
+        if !visited.insert(address.usize()) {
+            return Ok(());
+        }
+        // The address and the level are hashed with the content, so the digest reflects where the guest placed its page tables.
+        hasher.update(&(address.usize() as u64).to_le_bytes());
+        hasher.update(&(level as u64).to_le_bytes());
+        hasher.update(&self.digest_of_region(address, PAGE_TABLE_SIZE_IN_BYTES)?);
+        if level == 0 {
+            return Ok(());
+        }
+        for index in 0..PAGE_TABLE_SIZE_IN_BYTES / core::mem::size_of::<usize>() {
+            let entry = self.read_word(Self::word_address(address, index)?)?;
+            // A valid entry without read, write, and execute permissions points to the page table of the next level.
+            if entry & VALID_BIT != 0 && entry & PERMISSION_BITS == 0 {
+                let next_address = ((entry >> PTE_PPN_SHIFT) & PTE_PPN_MASK) << PAGE_SHIFT;
+                self.hash_vs_stage_page_table(ConfidentialVmPhysicalAddress::new(next_address), level - 1, visited, hasher)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn word_address(address: ConfidentialVmPhysicalAddress, word_index: usize) -> Result<ConfidentialVmPhysicalAddress, Error> {
+        let offset_in_bytes = word_index.checked_mul(core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        Ok(ConfidentialVmPhysicalAddress::new(address.usize().checked_add(offset_in_bytes).ok_or(Error::InvalidArgument())?))
+    }
+
+    fn word_offset_in_page(address: ConfidentialVmPhysicalAddress, page: &Page<Allocated>) -> Result<usize, Error> {
+        assure!(address.usize() % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+        Ok(address.usize() % page.size().in_bytes())
+    }
+
+    /// Returns true if the confidential VM has mapped a shared page located in the given region of the non-confidential memory.
+    pub fn contains_shared_page(&self, memory_start: usize, memory_end: usize) -> bool {
+        self.root_page_table.contains_shared_page(memory_start, memory_end)
+    }
+
+    /// Calls the operation on every page owned by the confidential VM together with the guest physical address at which the page is
+    /// mapped. Mapped pages are visited in the ascending order of guest physical addresses, followed by pages that have not been mapped
+    /// on demand yet, in the same order.
+    pub fn for_each_confidential_page(
+        &self, op: &mut dyn FnMut(ConfidentialVmPhysicalAddress, &Page<Allocated>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.root_page_table.for_each_confidential_page(op)?;
+        self.demand_pages.pages().try_for_each(|(address, page)| op(address, page))
+    }
+
+    /// Prints all G-stage mappings of the confidential VM, both of its own pages and of pages shared with the hypervisor.
+    #[cfg(feature = "verbose")]
+    pub fn dump_mappings(&self) {
+        self.root_page_table.for_each_mapping(&mut |address, host_physical_address, page_size, permissions| {
+            debug!("G-stage mapping {:x} -> {:x} ({:?}, {:?})", address.usize(), host_physical_address, page_size, permissions);
+        });
+    }
+
+    /// Returns at most `max_number_of_pages` pages owned by the confidential VM back to the page allocator. Returns true if all pages
+    /// have been reclaimed. Must only be called for a confidential VM that will never execute again, after the shootdown returned by
+    /// `fence_on_all_harts` has completed.
+    pub fn reclaim_pages(&mut self, max_number_of_pages: usize) -> bool {
+        self.root_page_table.reclaim_pages(max_number_of_pages)
+    }
+
+    /// Requests fencing translations on all harts, once for the lifetime of a confidential VM that stopped executing. Stale translations
+    /// might still be cached by any hart that executed the confidential VM, so the returned shootdown must complete before any page can
+    /// be allocated again. Page tables are reclaimed too, so all translations are fenced, not single addresses. Translations cannot be
+    /// cached again afterwards because none of the confidential VM's harts is ever resumed.
+    pub fn fence_on_all_harts(&mut self) -> PendingTlbShootdown {
+        *self.tlb_shootdown.get_or_insert_with(TlbShootdown::request)
+    }
+
     /// Reconfigures hardware to enable access initiated from this physical hart to memory regions owned by the
     /// confidential VM and deny access to all other memory regions.
     ///
@@ -65,9 +484,63 @@ impl ConfidentialVmMemoryProtector {
     /// Caller must guarantee that the security monitor will transition in the finite state machine to the `confidential
     /// flow` and that the hgatp argument contains the correct id and the root page table address of the confidential VM
     /// that will be executed next.
-    pub unsafe fn enable(&self) {
+    pub unsafe fn enable(&mut self, vmid_allocator: &Mutex<VmidAllocator>) {
+        // The VMID might have been reassigned to another confidential VM after all VMIDs had been in use.
+        if let Some(vmid) = self.vmid.as_mut().filter(|vmid| vmid_allocator.lock().refresh(vmid)) {
+            self.hgatp = compute_hgatp(self.root_page_table.ppn(), self.root_page_table.paging_system().hgatp_mode(), vmid.value());
+        }
         pmp::open_access_to_confidential_memory();
         mmu::enable_address_translation(self.hgatp);
-        super::tlb::tlb_shutdown();
+        super::tlb::fence_domain_switch(self.vmid.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::HgatpMode;
+    use crate::core::page_allocator::test_memory::setup;
+
+    const ROOT_PAGE_TABLE_ADDRESS: usize = 0x8000_0000;
+    const NEXT_PAGE_TABLE_ADDRESS: usize = 0x8000_1000;
+    const SV39_VSATP: usize = (8 << 60) | (ROOT_PAGE_TABLE_ADDRESS >> 12);
+    const VALID_BIT: usize = 0b1;
+    const WRITE_BIT: usize = 0b100;
+    const READ_WRITE_EXECUTE_BITS: usize = 0b1110;
+
+    /// Returns the digest of VS-stage page tables in which the first entry of the root page table points to the page table of the next
+    /// level and the second entry is the given leaf entry. The rest of the confidential VM's memory is the same for all leaf entries.
+    fn digest_with_leaf_entry(leaf_entry: usize) -> Option<[u8; Sha384::DIGEST_SIZE_IN_BYTES]> {
+        HgatpMode::set_supported_modes(&[HgatpMode::Sv39x4]);
+        let mut memory_protector = ConfidentialVmMemoryProtector::empty(Some(1 << 32)).unwrap();
+        for address in [ROOT_PAGE_TABLE_ADDRESS, NEXT_PAGE_TABLE_ADDRESS] {
+            let page = PageAllocator::acquire_zeroed_page().unwrap();
+            memory_protector.map_confidential_page(ConfidentialVmPhysicalAddress::new(address), page).unwrap();
+        }
+        let pointer_entry = ((NEXT_PAGE_TABLE_ADDRESS >> 12) << 10) | VALID_BIT;
+        memory_protector.write_word(ConfidentialVmPhysicalAddress::new(ROOT_PAGE_TABLE_ADDRESS), pointer_entry).unwrap();
+        memory_protector.write_word(ConfidentialVmPhysicalAddress::new(ROOT_PAGE_TABLE_ADDRESS + 8), leaf_entry).unwrap();
+        memory_protector.digest_of_vs_stage_page_tables(SV39_VSATP).unwrap()
+    }
+
+    #[test]
+    fn vs_stage_page_tables_are_measured_only_when_translation_is_enabled() {
+        let (_lock, _) = setup();
+        HgatpMode::set_supported_modes(&[HgatpMode::Sv39x4]);
+        let memory_protector = ConfidentialVmMemoryProtector::empty(Some(1 << 32)).unwrap();
+        assert_eq!(memory_protector.digest_of_vs_stage_page_tables(0).unwrap(), None);
+        assert!(memory_protector.digest_of_vs_stage_page_tables(SV39_VSATP).is_err());
+    }
+
+    #[test]
+    fn images_differing_only_in_vs_stage_page_tables_have_different_digests() {
+        let (_lock, _) = setup();
+        let leaf_entry = ((0x8020_0000 >> 12) << 10) | READ_WRITE_EXECUTE_BITS | VALID_BIT;
+        let digest = digest_with_leaf_entry(leaf_entry);
+        assert!(digest.is_some());
+        assert_eq!(digest_with_leaf_entry(leaf_entry), digest);
+        let remapped_leaf_entry = ((0x8040_0000 >> 12) << 10) | READ_WRITE_EXECUTE_BITS | VALID_BIT;
+        assert_ne!(digest_with_leaf_entry(remapped_leaf_entry), digest);
+        assert_ne!(digest_with_leaf_entry(leaf_entry & !WRITE_BIT), digest);
     }
 }
@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::page_allocator::{Allocated, Page, PageAllocator};
+use crate::error::Error;
+use alloc::collections::BTreeMap;
+
+/// Tracks pages that a confidential VM owns but that are not mapped in its address space yet, indexed by the guest physical addresses
+/// at which they are mapped on the first access. The pages have been populated and measured like mapped pages, so the confidential VM
+/// observes the same content regardless of when a page gets mapped.
+pub(super) struct DemandPageTracker {
+    pages: BTreeMap<usize, Page<Allocated>>,
+}
+
+impl DemandPageTracker {
+    pub fn empty() -> Self {
+        Self { pages: BTreeMap::new() }
+    }
+
+    /// Records the page to be mapped at the given address. Returns error if it overlaps a page that is already tracked, in which
+    /// case the page is returned to the page allocator.
+    pub fn insert(&mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>) -> Result<(), Error> {
+        if self.overlaps(address, page.size().in_bytes()) {
+            PageAllocator::release_zeroed_page(page.deallocate());
+            return Err(Error::AddressAlreadyMapped());
+        }
+        self.pages.insert(address.usize(), page);
+        Ok(())
+    }
+
+    /// Returns true if any part of the region starting at the given address overlaps a tracked page.
+    pub fn overlaps(&self, address: ConfidentialVmPhysicalAddress, size_in_bytes: usize) -> bool {
+        let start = address.usize();
+        match start.checked_add(size_in_bytes) {
+            Some(end) => self.find(start).is_some() || self.pages.range(start..end).next().is_some(),
+            None => true,
+        }
+    }
+
+    /// Stops tracking the page containing the given address and returns it together with the address at which it must be mapped.
+    pub fn take(&mut self, address: ConfidentialVmPhysicalAddress) -> Option<(ConfidentialVmPhysicalAddress, Page<Allocated>)> {
+        let start = self.find(address.usize())?;
+        self.pages.remove(&start).map(|page| (ConfidentialVmPhysicalAddress::new(start), page))
+    }
+
+    /// Returns the tracked page containing the given address together with the address at which it must be mapped.
+    pub fn get(&self, address: ConfidentialVmPhysicalAddress) -> Option<(ConfidentialVmPhysicalAddress, &Page<Allocated>)> {
+        let start = self.find(address.usize())?;
+        self.pages.get(&start).map(|page| (ConfidentialVmPhysicalAddress::new(start), page))
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = (ConfidentialVmPhysicalAddress, &Page<Allocated>)> {
+        self.pages.iter().map(|(address, page)| (ConfidentialVmPhysicalAddress::new(*address), page))
+    }
+
+    fn find(&self, address: usize) -> Option<usize> {
+        let (start, page) = self.pages.range(..=address).next_back()?;
+        (address < start + page.size().in_bytes()).then_some(*start)
+    }
+}
+
+impl Drop for DemandPageTracker {
+    fn drop(&mut self) {
+        core::mem::take(&mut self.pages).into_values().for_each(|page| PageAllocator::release_zeroed_page(page.deallocate()));
+    }
+}
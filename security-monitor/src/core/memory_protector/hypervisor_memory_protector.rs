@@ -1,17 +1,151 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::memory_layout::MemoryLayout;
-use crate::core::memory_protector::{iopmp, mmu, pmp};
+use crate::core::memory_layout::{ConversionState, MemoryLayout};
+use crate::core::memory_protector::{iopmp, mmu, pmp, Vmid};
+#[cfg(feature = "pmp_audit_log")]
+use crate::core::memory_protector::{AuditLog, MemoryRegion, PmpOperation};
+use crate::core::page_allocator::{Allocated, Page};
 use crate::error::Error;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// The version of the PMP configuration of memory regions converted between the non-confidential and confidential memory. Harts apply
+/// the new configuration lazily, when they trap into the security monitor. It is read without a lock on every trap, so that harts
+/// whose configuration is up to date do not contend for `PMP_SYNCHRONIZATION`. It is changed only while holding that lock.
+static PMP_CONFIGURATION_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+/// Tracks how many harts applied the latest PMP configuration of converted memory regions.
+static PMP_SYNCHRONIZATION: Mutex<PmpSynchronization> =
+    Mutex::new(PmpSynchronization { number_of_harts: 0, number_of_synchronized_harts: 0 });
+
+struct PmpSynchronization {
+    // harts on which the security monitor has been installed, see `HypervisorMemoryProtector::register_hart`.
+    number_of_harts: usize,
+    number_of_synchronized_harts: usize,
+}
 
 /// Exposes an interface to configure the hardware memory isolation component to set memory access protection preventing
 /// the hypervisor from accessing memory it does not own.
-pub struct HypervisorMemoryProtector {}
+pub struct HypervisorMemoryProtector {
+    // Set when the hypervisor has been granted access to a confidential page that must be revoked on its next trap into the
+    // security monitor.
+    temporary_access_expires_on_trap: bool,
+    // The version of the PMP configuration of converted memory regions applied on this hart.
+    pmp_configuration_version: usize,
+    #[cfg(feature = "pmp_audit_log")]
+    audit_log: AuditLog,
+}
 
 impl HypervisorMemoryProtector {
     pub fn create() -> Self {
-        Self {}
+        Self {
+            temporary_access_expires_on_trap: false,
+            pmp_configuration_version: 0,
+            #[cfg(feature = "pmp_audit_log")]
+            audit_log: AuditLog::empty(),
+        }
+    }
+
+    /// Returns the log of changes of the PMP configuration on this hart. Closing the access granted with the `AccessDuration::Scoped`
+    /// duration is not recorded because it happens before the security monitor returns the control to the hypervisor.
+    #[cfg(feature = "pmp_audit_log")]
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    /// Grants the hypervisor access to a single confidential page, for example, to let the hypervisor copy in a virtio descriptor.
+    /// Exactly one PMP entry is reconfigured to cover the page. The access is revoked when the returned grant is revoked or, depending on
+    /// the `duration`, dropped.
+    ///
+    /// # Security
+    ///
+    /// The caller must ensure that the page does not contain confidential information, because the hypervisor can read it.
+    pub fn grant_temporary_access(&mut self, page: &Page<Allocated>, duration: AccessDuration) -> TemporaryGrant {
+        self.open_temporary_access::<PmpTemporaryAccess>(page.start_address(), page.size().in_bytes(), duration)
+    }
+
+    /// Revokes access granted with the `AccessDuration::UntilNextTrap` duration. This function must be called whenever the hypervisor
+    /// traps into the security monitor.
+    pub fn revoke_expired_temporary_access(&mut self) {
+        self.close_expired_temporary_access::<PmpTemporaryAccess>()
+    }
+
+    fn open_temporary_access<E: TemporaryAccessEntry>(
+        &mut self, address: usize, size_in_bytes: usize, duration: AccessDuration,
+    ) -> TemporaryGrant<E> {
+        E::open(address, size_in_bytes);
+        #[cfg(feature = "pmp_audit_log")]
+        self.audit_log.record(PmpOperation::GrantTemporaryAccess, MemoryRegion::new(address, size_in_bytes));
+        self.temporary_access_expires_on_trap = duration == AccessDuration::UntilNextTrap;
+        TemporaryGrant { duration, entry: PhantomData }
+    }
+
+    fn close_expired_temporary_access<E: TemporaryAccessEntry>(&mut self) {
+        if self.temporary_access_expires_on_trap {
+            E::close();
+            self.temporary_access_expires_on_trap = false;
+            #[cfg(feature = "pmp_audit_log")]
+            self.audit_log.record(PmpOperation::RevokeTemporaryAccess, MemoryRegion::empty());
+        }
+    }
+
+    /// Applies the latest PMP configuration of converted memory regions on this hart. This function must be called whenever the
+    /// hypervisor traps into the security monitor.
+    pub fn synchronize_pmp_configuration(&mut self) {
+        if self.pmp_configuration_version == PMP_CONFIGURATION_VERSION.load(Ordering::Acquire) {
+            return;
+        }
+        let mut synchronization = PMP_SYNCHRONIZATION.lock();
+        self.apply_pmp_configuration();
+        synchronization.number_of_synchronized_harts += 1;
+    }
+
+    /// Announces that the PMP configuration of converted memory regions has changed and must be applied on all harts.
+    pub fn request_pmp_synchronization() {
+        let mut synchronization = PMP_SYNCHRONIZATION.lock();
+        PMP_CONFIGURATION_VERSION.fetch_add(1, Ordering::AcqRel);
+        synchronization.number_of_synchronized_harts = 0;
+    }
+
+    /// Returns true if all harts on which the security monitor is installed applied the latest PMP configuration of converted memory
+    /// regions. Harts apply it only when they trap into the security monitor, so a hart that never traps, e.g., because the hypervisor
+    /// stopped it using the SBI HSM extension or keeps it in a loop with interrupts disabled, keeps this function returning false until
+    /// the hypervisor makes it trap. Such a hart delays the conversion but cannot access a converted region, because regions become
+    /// confidential only after all harts deny access to them. PMP entries used by the security monitor keep their configuration when
+    /// the firmware stops and restarts a hart, because the firmware programs only the entries following them.
+    pub fn is_pmp_synchronized() -> bool {
+        let synchronization = PMP_SYNCHRONIZATION.lock();
+        synchronization.number_of_synchronized_harts == synchronization.number_of_harts
+    }
+
+    /// Includes this hart in the PMP synchronization and applies the latest PMP configuration of converted memory regions. Must be called
+    /// exactly once per hart, after the security monitor has been installed on it, so that harts on which the installation failed never
+    /// block the conversion of memory regions.
+    pub fn register_hart(&mut self) {
+        let mut synchronization = PMP_SYNCHRONIZATION.lock();
+        self.apply_pmp_configuration();
+        synchronization.number_of_harts += 1;
+        synchronization.number_of_synchronized_harts += 1;
+    }
+
+    /// Must be called while holding the lock of `PMP_SYNCHRONIZATION`, so that the version cannot change concurrently.
+    fn apply_pmp_configuration(&mut self) {
+        MemoryLayout::read().converted_memory_regions().iter().enumerate().for_each(|(slot, region)| {
+            let protected_region =
+                region.filter(|region| region.state() != ConversionState::Releasing).map(|region| (region.start(), region.size_in_bytes()));
+            pmp::configure_converted_memory_region(slot, protected_region);
+            #[cfg(feature = "pmp_audit_log")]
+            if let Some(region) = region {
+                let operation = match protected_region {
+                    Some(_) => PmpOperation::ProtectConvertedMemory,
+                    None => PmpOperation::ReleaseConvertedMemory,
+                };
+                self.audit_log.record(operation, MemoryRegion::new(region.start(), region.size_in_bytes()));
+            }
+        });
+        self.pmp_configuration_version = PMP_CONFIGURATION_VERSION.load(Ordering::Acquire);
     }
 
     /// Configures the memory protection mechanism on the hart which executes this function.  
@@ -34,15 +168,230 @@ impl HypervisorMemoryProtector {
     }
 
     /// Reconfigures hardware to enable memory accesses initiated from this physical hart to memory regions owned by the
-    /// hypervisor and denies accesses to all other memory regions.
+    /// hypervisor and denies accesses to all other memory regions. Translations of the confidential VM that executed on this hart,
+    /// identified by its VMID, are fenced. Returns error, before enabling the hypervisor's address translation, if the PMP configuration
+    /// does not isolate the confidential memory. The caller must not execute the hypervisor then.
     ///
     /// # Safety
     ///
     /// Caller must guarantee that the security monitor will transition in the finite state machine to the
     /// `non-confidential flow` and eventually to the hypervisor code.
-    pub unsafe fn enable(&self, hgatp: usize) {
+    pub unsafe fn enable(&mut self, hgatp: usize, confidential_vm_vmid: Option<&Vmid>) -> Result<(), Error> {
+        self.enable_isolation::<PmpIsolation>(hgatp, confidential_vm_vmid)
+    }
+
+    unsafe fn enable_isolation<I: ConfidentialMemoryIsolation>(
+        &mut self, hgatp: usize, confidential_vm_vmid: Option<&Vmid>,
+    ) -> Result<(), Error> {
+        I::close_access_to_confidential_memory()?;
+        I::enable_hypervisor_address_translation(hgatp, confidential_vm_vmid);
+        #[cfg(feature = "pmp_audit_log")]
+        {
+            let (confidential_memory_start, confidential_memory_end) = MemoryLayout::read().confidential_memory_boundary();
+            let confidential_memory = MemoryRegion::new(confidential_memory_start, confidential_memory_end - confidential_memory_start);
+            self.audit_log.record(PmpOperation::Enable, confidential_memory);
+        }
+        Ok(())
+    }
+}
+
+/// Defines for how long the hypervisor can access a page granted by `HypervisorMemoryProtector::grant_temporary_access`.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AccessDuration {
+    /// The access is revoked as soon as the grant is revoked or dropped.
+    Scoped,
+    /// The access outlives the grant, so that the hypervisor can use it after the security monitor exits to the hypervisor. The access is
+    /// revoked on the next hypervisor's trap into the security monitor.
+    UntilNextTrap,
+}
+
+/// The hardware that isolates the confidential memory from the hypervisor executing on this hart.
+pub trait ConfidentialMemoryIsolation {
+    /// Denies accesses to the confidential memory. Returns error if the hardware still allows them afterwards.
+    fn close_access_to_confidential_memory() -> Result<(), Error>;
+
+    /// Switches the address translation to the hypervisor's one, after fencing the translations of the confidential VM identified by
+    /// the VMID.
+    ///
+    /// # Safety
+    ///
+    /// The access to the confidential memory must be closed.
+    unsafe fn enable_hypervisor_address_translation(hgatp: usize, confidential_vm_vmid: Option<&Vmid>);
+}
+
+/// Isolates the confidential memory using the PMP entries of the security monitor, see `pmp::close_access_to_confidential_memory`.
+pub struct PmpIsolation;
+
+impl ConfidentialMemoryIsolation for PmpIsolation {
+    fn close_access_to_confidential_memory() -> Result<(), Error> {
         pmp::close_access_to_confidential_memory();
+        pmp::ensure_access_to_confidential_memory_closed()
+    }
+
+    unsafe fn enable_hypervisor_address_translation(hgatp: usize, confidential_vm_vmid: Option<&Vmid>) {
+        // The hgatp register still holds the confidential VM's VMID.
+        super::tlb::fence_domain_switch(confidential_vm_vmid);
         mmu::enable_address_translation(hgatp);
-        super::tlb::tlb_shutdown();
+    }
+}
+
+/// The hardware entry that opens the hypervisor's access to a single confidential page.
+pub trait TemporaryAccessEntry {
+    fn open(address: usize, size_in_bytes: usize);
+
+    fn close();
+}
+
+/// Grants the temporary access using the PMP entry reserved for it, see `pmp::open_temporary_access`.
+pub struct PmpTemporaryAccess;
+
+impl TemporaryAccessEntry for PmpTemporaryAccess {
+    fn open(address: usize, size_in_bytes: usize) {
+        pmp::open_temporary_access(address, size_in_bytes);
+    }
+
+    fn close() {
+        pmp::close_temporary_access();
+    }
+}
+
+/// A token representing the hypervisor's temporary access to a confidential page.
+#[must_use]
+pub struct TemporaryGrant<E: TemporaryAccessEntry = PmpTemporaryAccess> {
+    duration: AccessDuration,
+    entry: PhantomData<E>,
+}
+
+impl<E: TemporaryAccessEntry> TemporaryGrant<E> {
+    /// Immediately revokes the hypervisor's access to the page, regardless of the access duration.
+    pub fn revoke(mut self) {
+        self.duration = AccessDuration::Scoped;
+    }
+}
+
+impl<E: TemporaryAccessEntry> Drop for TemporaryGrant<E> {
+    fn drop(&mut self) {
+        if self.duration == AccessDuration::Scoped {
+            E::close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const PAGE_ADDRESS: usize = 0x8020_0000;
+    const PAGE_SIZE: usize = 0x1000;
+    const HGATP: usize = 0x8000_0000_0008_0200;
+
+    std::thread_local! {
+        static OPEN_REGION: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+        static NUMBER_OF_CLOSES: Cell<usize> = const { Cell::new(0) };
+        static LOCKED_PMP_ENTRY: Cell<Option<usize>> = const { Cell::new(None) };
+        static HYPERVISOR_HGATP: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    struct MockEntry;
+
+    impl TemporaryAccessEntry for MockEntry {
+        fn open(address: usize, size_in_bytes: usize) {
+            OPEN_REGION.with(|region| region.set(Some((address, size_in_bytes))));
+        }
+
+        fn close() {
+            OPEN_REGION.with(|region| region.set(None));
+            NUMBER_OF_CLOSES.with(|closes| closes.set(closes.get() + 1));
+        }
+    }
+
+    // Fails closing the access to the confidential memory if the test locked a PMP entry.
+    struct MockIsolation;
+
+    impl ConfidentialMemoryIsolation for MockIsolation {
+        fn close_access_to_confidential_memory() -> Result<(), Error> {
+            LOCKED_PMP_ENTRY.with(|entry| entry.get()).map_or(Ok(()), |pmp_index| Err(Error::PmpEntryLocked(pmp_index)))
+        }
+
+        unsafe fn enable_hypervisor_address_translation(hgatp: usize, _confidential_vm_vmid: Option<&Vmid>) {
+            HYPERVISOR_HGATP.with(|hypervisor_hgatp| hypervisor_hgatp.set(Some(hgatp)));
+        }
+    }
+
+    fn open_region() -> Option<(usize, usize)> {
+        OPEN_REGION.with(|region| region.get())
+    }
+
+    fn number_of_closes() -> usize {
+        NUMBER_OF_CLOSES.with(|closes| closes.get())
+    }
+
+    fn grant(protector: &mut HypervisorMemoryProtector, duration: AccessDuration) -> TemporaryGrant<MockEntry> {
+        protector.open_temporary_access::<MockEntry>(PAGE_ADDRESS, PAGE_SIZE, duration)
+    }
+
+    #[test]
+    fn scoped_grant_expires_when_dropped() {
+        let mut protector = HypervisorMemoryProtector::create();
+        let grant = grant(&mut protector, AccessDuration::Scoped);
+        assert_eq!(open_region(), Some((PAGE_ADDRESS, PAGE_SIZE)));
+        drop(grant);
+        assert_eq!(open_region(), None);
+        // The access has already been closed, so the next trap has nothing to revoke.
+        protector.close_expired_temporary_access::<MockEntry>();
+        assert_eq!(number_of_closes(), 1);
+    }
+
+    #[test]
+    fn grant_until_next_trap_outlives_the_token() {
+        let mut protector = HypervisorMemoryProtector::create();
+        drop(grant(&mut protector, AccessDuration::UntilNextTrap));
+        assert_eq!(open_region(), Some((PAGE_ADDRESS, PAGE_SIZE)));
+        assert_eq!(number_of_closes(), 0);
+    }
+
+    #[test]
+    fn grant_until_next_trap_expires_on_trap() {
+        let mut protector = HypervisorMemoryProtector::create();
+        drop(grant(&mut protector, AccessDuration::UntilNextTrap));
+        protector.close_expired_temporary_access::<MockEntry>();
+        assert_eq!(open_region(), None);
+        // Later traps do not touch the entry again.
+        protector.close_expired_temporary_access::<MockEntry>();
+        assert_eq!(number_of_closes(), 1);
+    }
+
+    #[test]
+    fn revoked_grant_closes_access_immediately() {
+        for duration in [AccessDuration::Scoped, AccessDuration::UntilNextTrap] {
+            let mut protector = HypervisorMemoryProtector::create();
+            grant(&mut protector, duration).revoke();
+            assert_eq!(open_region(), None);
+        }
+    }
+
+    #[test]
+    fn trap_without_grant_does_not_close_access() {
+        let mut protector = HypervisorMemoryProtector::create();
+        protector.close_expired_temporary_access::<MockEntry>();
+        assert_eq!(number_of_closes(), 0);
+    }
+
+    #[test]
+    fn isolated_confidential_memory_enables_hypervisor_address_translation() {
+        let mut protector = HypervisorMemoryProtector::create();
+        assert!(unsafe { protector.enable_isolation::<MockIsolation>(HGATP, None) }.is_ok());
+        assert_eq!(HYPERVISOR_HGATP.with(|hgatp| hgatp.get()), Some(HGATP));
+    }
+
+    #[test]
+    fn isolation_failure_prevents_entry_to_hypervisor() {
+        let mut protector = HypervisorMemoryProtector::create();
+        LOCKED_PMP_ENTRY.with(|entry| entry.set(Some(1)));
+        let result = unsafe { protector.enable_isolation::<MockIsolation>(HGATP, None) };
+        assert!(matches!(result, Err(Error::PmpEntryLocked(1))));
+        // The hart still uses the confidential VM's address translation, so the caller must not execute the hypervisor.
+        assert_eq!(HYPERVISOR_HGATP.with(|hgatp| hgatp.get()), None);
     }
 }
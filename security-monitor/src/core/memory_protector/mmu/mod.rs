@@ -1,27 +1,44 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{Hgatp, CSR};
+use crate::core::architecture::{Hgatp, HgatpMode, CSR};
 use crate::core::memory_layout::NonConfidentialMemoryAddress;
 use crate::error::Error;
 pub use page_size::PageSize;
 pub use page_table::RootPageTable;
+pub use page_table_usage::PageTableUsage;
 pub use paging_system::PagingSystem;
 
 mod page_size;
 mod page_table;
 mod page_table_entry;
 mod page_table_memory;
+mod page_table_usage;
+mod page_table_validator;
 mod paging_system;
 
 pub fn copy_mmu_configuration_from_non_confidential_memory(hgatp: Hgatp) -> Result<RootPageTable, Error> {
     let paging_mode = hgatp.mode().ok_or_else(|| Error::UnsupportedPagingMode())?;
+    assure!(paging_mode.is_supported(), Error::UnsupportedPagingMode())?;
     let paging_system = PagingSystem::from(&paging_mode).ok_or_else(|| Error::UnsupportedPagingMode())?;
     let root_page_address = NonConfidentialMemoryAddress::new(hgatp.address() as *mut usize)?;
     let root_page_table = RootPageTable::copy_from_non_confidential_memory(root_page_address, paging_system)?;
     Ok(root_page_table)
 }
 
+/// Creates an empty page table configuration using the supported paging system with the smallest guest physical address space that
+/// contains all addresses below the given one. If the end of the address space is not known, the paging system with the largest
+/// guest physical address space is used. Returns error if the hardware cannot translate the requested address space.
+pub fn empty_mmu_configuration(guest_physical_address_end: Option<usize>) -> Result<RootPageTable, Error> {
+    let paging_mode = match guest_physical_address_end {
+        Some(address) => HgatpMode::smallest_covering(address),
+        None => HgatpMode::largest_supported(),
+    }
+    .ok_or(Error::GuestPhysicalAddressSpaceTooLarge())?;
+    let paging_system = PagingSystem::from(&paging_mode).ok_or_else(|| Error::UnsupportedPagingMode())?;
+    RootPageTable::empty(paging_system)
+}
+
 pub fn enable_address_translation(hgatp: usize) {
     // Enable MMU for HS,VS,VS,U modes. It is safe to invoke below code because we have access to this register (run in the M-mode) and
     // hgatp is the content of the HGATP register calculated by the security monitor when recreating page tables of a confidential virtual
@@ -50,6 +50,13 @@ impl PageSize {
         PageSize::Size4KiB
     }
 
+    /// Returns the largest page size that maps a part of the region starting at the given address and spanning `remaining` bytes,
+    /// i.e., the largest page to which the address is aligned and which does not exceed the region. Returns `None` if the address is
+    /// not aligned to the smallest page or the region is smaller than the smallest page.
+    pub fn largest_fitting(address: usize, remaining: usize) -> Option<PageSize> {
+        Self::all_from_largest_to_smallest().into_iter().find(|size| address % size.in_bytes() == 0 && size.in_bytes() <= remaining)
+    }
+
     pub fn all_from_largest_to_smallest() -> alloc::vec::Vec<PageSize> {
         alloc::vec![Self::Size128TiB, Self::Size512GiB, Self::Size1GiB, Self::Size2MiB, Self::Size4KiB]
     }
@@ -6,10 +6,14 @@ use crate::core::memory_protector::mmu::page_table_entry::{
     PageTableAddress, PageTableBits, PageTableConfiguration, PageTableEntry, PageTablePermission,
 };
 use crate::core::memory_protector::mmu::page_table_memory::PageTableMemory;
+use crate::core::memory_protector::mmu::page_table_usage::PageTableUsage;
+use crate::core::memory_protector::mmu::page_table_validator::PageTableValidator;
 use crate::core::memory_protector::mmu::paging_system::{PageTableLevel, PagingSystem};
-use crate::core::page_allocator::{PageAllocator, SharedPage};
+use crate::core::memory_protector::{AccessPermissions, AccessedDirtyUpdate, GuestMemoryAccess, PageSize};
+use crate::core::page_allocator::{Allocated, Page, PageAllocator, PageGuard, SharedPage};
 use crate::error::Error;
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 pub struct RootPageTable {
@@ -18,25 +22,136 @@ pub struct RootPageTable {
 }
 
 impl RootPageTable {
+    /// Copies the page table configuration that the hypervisor built in the non-confidential memory. Returns error if any of its
+    /// entries is malformed (see `PageTableValidator`).
     pub fn copy_from_non_confidential_memory(address: NonConfidentialMemoryAddress, paging_system: PagingSystem) -> Result<Self, Error> {
-        let page_table = PageTable::copy_from_non_confidential_memory(address, paging_system, paging_system.levels())?;
+        let mut validator = PageTableValidator::new(paging_system);
+        validator.check_page_table(address.usize(), paging_system.levels(), 0)?;
+        let usage = Arc::new(PageTableUsage::default());
+        let level = paging_system.levels();
+        let page_table = PageTable::copy_from_non_confidential_memory(address, paging_system, level, 0, &mut validator, &usage)?;
         Ok(Self { paging_system, page_table })
     }
 
-    pub fn map_shared_page(&mut self, shared_page: SharedPage) -> Result<(), Error> {
+    /// Creates a page table configuration that does not map any memory.
+    pub fn empty(paging_system: PagingSystem) -> Result<Self, Error> {
+        let page_table = PageTable::empty(paging_system, paging_system.levels(), &Arc::new(PageTableUsage::default()))?;
+        Ok(Self { paging_system, page_table })
+    }
+
+    /// Returns how much confidential memory the page tables and the pages they map use.
+    pub fn usage(&self) -> &PageTableUsage {
+        self.page_table.page_table_memory.usage()
+    }
+
+    /// Maps the shared page and returns the guest physical address and size of the huge page that was split to make room for it, if
+    /// any. The caller fences translations of the split huge page, which cover the shared page too.
+    pub fn map_shared_page(&mut self, shared_page: SharedPage) -> Result<Option<(ConfidentialVmPhysicalAddress, PageSize)>, Error> {
+        let address = shared_page.confidential_vm_virtual_address().usize();
+        assure!(self.paging_system.translates(address), Error::GuestPhysicalAddressSpaceTooLarge())?;
         self.page_table.map_shared_page(self.paging_system, shared_page)
     }
 
-    pub fn unmap_shared_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<(), Error> {
-        self.page_table.unmap_shared_page(self.paging_system, address)
+    /// Returns the maximum number of pages allocated to map a single 4KiB page, i.e., the pages of all intermediary page tables that
+    /// might have to be created on the path from the root page table to the leaf entry.
+    pub fn max_pages_to_map_page(&self) -> usize {
+        core::iter::successors(self.paging_system.levels().lower(), |level| level.lower())
+            .map(|level| self.paging_system.configuration_pages(level))
+            .sum()
+    }
+
+    /// Returns error if any part of the page is outside of the guest physical address space translated by the paging system. The page
+    /// table takes over the page token, which exists once per confidential page, so no confidential page is mapped twice.
+    pub fn map_confidential_page(&mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>) -> Result<(), Error> {
+        self.try_map_confidential_page(address, page).map_err(|(error, page)| {
+            PageAllocator::release_zeroed_page(page.deallocate());
+            error
+        })
+    }
+
+    /// Maps the page like `map_confidential_page` but hands the page back on error instead of releasing it, so that its content
+    /// survives a failed mapping.
+    pub fn try_map_confidential_page(
+        &mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>,
+    ) -> Result<(), (Error, Page<Allocated>)> {
+        let last_address = address.usize().checked_add(page.size().in_bytes() - 1);
+        if !last_address.is_some_and(|last_address| self.paging_system.translates(last_address)) {
+            return Err((Error::GuestPhysicalAddressSpaceTooLarge(), page));
+        }
+        self.page_table.map_confidential_page(self.paging_system, address, page)
+    }
+
+    pub fn unmap_shared_page(&mut self, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>) -> Result<SharedPage, Error> {
+        self.page_table.unmap_shared_page(self.paging_system, address, page)
+    }
+
+    pub fn remove_shared_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<SharedPage, Error> {
+        self.page_table.remove_shared_page(self.paging_system, address)
+    }
+
+    /// Invalidates the leaf entry that maps the page owned by the confidential VM at the given guest physical address and returns the
+    /// page. Returns error if the address does not map such a page or is not the first address of the page.
+    pub fn unmap_confidential_page(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<Page<Allocated>, Error> {
+        self.page_table.unmap_confidential_page(self.paging_system, address)
+    }
+
+    pub fn merge_pages(&mut self, address: ConfidentialVmPhysicalAddress) {
+        self.page_table.merge_pages(self.paging_system, address)
     }
 
     pub fn translate(&self, address: ConfidentialVmPhysicalAddress) -> Result<&ConfidentialMemoryAddress, Error> {
         self.page_table.translate(self.paging_system, address)
     }
 
-    pub fn address(&self) -> usize {
-        self.page_table.address()
+    /// Returns the host physical address to which the guest physical address translates and the permissions of the G-stage leaf entry.
+    pub fn host_translation(&self, address: ConfidentialVmPhysicalAddress) -> Result<(usize, AccessPermissions), Error> {
+        self.page_table.host_translation(self.paging_system, address)
+    }
+
+    pub fn contains_shared_page(&self, memory_start: usize, memory_end: usize) -> bool {
+        self.page_table.contains_shared_page(memory_start, memory_end)
+    }
+
+    /// Sets the accessed and dirty bits required by the access in the leaf entry that maps the guest physical address. Returns the size of
+    /// the mapped page and the update of the bits, or error if the entry does not permit the access or the bits are already set.
+    pub fn set_accessed_dirty_bits(
+        &mut self, address: ConfidentialVmPhysicalAddress, access: GuestMemoryAccess,
+    ) -> Result<(PageSize, AccessedDirtyUpdate), Error> {
+        assure!(self.paging_system.translates(address.usize()), Error::AddressTranslationFailed())?;
+        self.page_table.set_accessed_dirty_bits(self.paging_system, address, access)
+    }
+
+    pub fn confidential_page(&self, address: ConfidentialVmPhysicalAddress) -> Result<&Page<Allocated>, Error> {
+        self.page_table.confidential_page(self.paging_system, address)
+    }
+
+    pub fn confidential_page_mut(&mut self, address: ConfidentialVmPhysicalAddress) -> Result<&mut Page<Allocated>, Error> {
+        self.page_table.confidential_page_mut(self.paging_system, address)
+    }
+
+    pub fn for_each_confidential_page(
+        &self, op: &mut dyn FnMut(ConfidentialVmPhysicalAddress, &Page<Allocated>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.page_table.for_each_confidential_page(self.paging_system, 0, op)
+    }
+
+    /// Calls the operation on every leaf entry, including entries that map shared pages, in the ascending order of guest physical
+    /// addresses. The operation receives the guest physical address, the host physical address, the size, and the permissions of the
+    /// mapped page.
+    pub fn for_each_mapping(&self, op: &mut dyn FnMut(ConfidentialVmPhysicalAddress, usize, PageSize, AccessPermissions)) {
+        self.page_table.for_each_mapping(self.paging_system, 0, op)
+    }
+
+    /// Unmaps and returns to the page allocator at most `max_number_of_pages` pages owned by this page table configuration. Returns
+    /// true when no more pages are left to reclaim.
+    pub fn reclaim_pages(&mut self, max_number_of_pages: usize) -> bool {
+        let mut budget = max_number_of_pages;
+        self.page_table.reclaim_pages(&mut budget)
+    }
+
+    /// Returns the physical page number of the root page table, as encoded in the `hgatp` register.
+    pub fn ppn(&self) -> usize {
+        self.page_table.address() / PageSize::Size4KiB.in_bytes()
     }
 
     pub fn paging_system(&self) -> &PagingSystem {
@@ -54,65 +169,127 @@ impl PageTable {
     /// This functions copies recursively page table structure from non-confidential memory to confidential memory. It
     /// allocated a page in confidential memory for every page table. After this function executes, a valid page table
     /// configuration is in the confidential memory.
+    ///
+    /// The page tables in the confidential memory are built from scratch, starting from zeroed pages: every entry of the hypervisor's
+    /// page table is read once and re-encoded, so no content chosen by the hypervisor, e.g., bits of invalid entries, is adopted.
+    /// The hypervisor's page tables are never modified and are not referenced after this function returns.
+    ///
+    /// The page table is constructed in place, entry by entry. If copying any entry fails, the partially constructed page table is
+    /// dropped, which returns all pages allocated so far, including pages of lower-level page tables, to the page allocator.
+    ///
+    /// Every valid entry is checked by the validator before it is followed. The validator rejects page tables referenced more than once,
+    /// so the copy terminates even if the hypervisor built a cyclic page table hierarchy.
     fn copy_from_non_confidential_memory(
-        address: NonConfidentialMemoryAddress, paging_system: PagingSystem, level: PageTableLevel,
+        address: NonConfidentialMemoryAddress, paging_system: PagingSystem, level: PageTableLevel, base_address: usize,
+        validator: &mut PageTableValidator, usage: &Arc<PageTableUsage>,
     ) -> Result<Self, Error> {
-        let mut page_table_memory = PageTableMemory::copy_from_non_confidential_memory(address, paging_system, level)?;
-        // TODO: make sure there are no cycles in the page table hierarchy, otherwise we might get
-        // in an infinite loop.
-        let entries = page_table_memory
-            .indices()
-            .map(|index| {
-                let entry_raw = page_table_memory.entry(index).unwrap();
-                let page_table_entry = if !PageTableBits::is_valid(entry_raw) {
-                    PageTableEntry::NotValid
-                } else if PageTableBits::is_leaf(entry_raw) {
-                    let address = NonConfidentialMemoryAddress::new(PageTableAddress::decode(entry_raw))?;
-                    let page_size = paging_system.page_size(level);
-                    let page = PageAllocator::acquire_continous_pages(1, page_size)?
-                        .remove(0)
-                        .copy_from_non_confidential_memory(address)
-                        .map_err(|_| Error::PageTableCorrupted())?;
-                    let configuration = PageTableConfiguration::decode(entry_raw);
-                    let permission = PageTablePermission::decode(entry_raw);
-                    PageTableEntry::Leaf(Box::new(page), configuration, permission)
-                } else {
-                    let lower_level = level.lower().ok_or(Error::PageTableCorrupted())?;
-                    let address = NonConfidentialMemoryAddress::new(PageTableAddress::decode(entry_raw))?;
-                    let page_table = Self::copy_from_non_confidential_memory(address, paging_system, lower_level)?;
-                    let configuration = PageTableConfiguration::decode(entry_raw);
-                    PageTableEntry::Pointer(Box::new(page_table), configuration)
-                };
-                page_table_memory.set_entry(index, &page_table_entry);
-                Ok(page_table_entry)
-            })
-            .collect::<Result<Vec<PageTableEntry>, Error>>()?;
-        Ok(Self { level, page_table_memory, entries })
+        let mut page_table = Self::empty(paging_system, level, usage)?;
+        let entry_span_in_bytes = paging_system.entry_span_in_bytes(level);
+        for index in page_table.page_table_memory.indices() {
+            let entry_raw = PageTableMemory::read_non_confidential_entry(&address, paging_system, index)?;
+            let guest_physical_address = base_address + index * entry_span_in_bytes;
+            if PageTableBits::is_valid(entry_raw) {
+                validator.check_entry(entry_raw, level, guest_physical_address)?;
+            }
+            let page_table_entry = if !PageTableBits::is_valid(entry_raw) {
+                PageTableEntry::NotValid
+            } else if PageTableBits::is_leaf(entry_raw) {
+                let address = NonConfidentialMemoryAddress::new(PageTableAddress::decode(entry_raw))?;
+                let page_size = paging_system.page_size(level);
+                let mut page_guard = PageGuard::acquire(1, page_size)?;
+                page_guard.copy_from_non_confidential_memory(address).map_err(|_| Error::PageTableCorrupted())?;
+                let configuration = PageTableConfiguration::decode(entry_raw);
+                let permission = PageTablePermission::decode(entry_raw);
+                PageTableEntry::Leaf(Box::new(page_guard.commit().remove(0)), configuration, permission)
+            } else {
+                let lower_level = level.lower().ok_or(Error::PageTableCorrupted())?;
+                let address = NonConfidentialMemoryAddress::new(PageTableAddress::decode(entry_raw))?;
+                validator.check_page_table(address.usize(), lower_level, guest_physical_address)?;
+                let lower_page_table =
+                    Self::copy_from_non_confidential_memory(address, paging_system, lower_level, guest_physical_address, validator, usage)?;
+                let configuration = PageTableConfiguration::decode(entry_raw);
+                PageTableEntry::Pointer(Box::new(lower_page_table), configuration)
+            };
+            page_table.set_entry(index, page_table_entry);
+        }
+        Ok(page_table)
     }
 
-    fn empty(paging_system: PagingSystem, level: PageTableLevel) -> Result<Self, Error> {
-        let page_table_memory = PageTableMemory::empty(paging_system, level)?;
-        let entries = Vec::with_capacity(page_table_memory.number_of_entries());
+    fn empty(paging_system: PagingSystem, level: PageTableLevel, usage: &Arc<PageTableUsage>) -> Result<Self, Error> {
+        let page_table_memory = PageTableMemory::empty(paging_system, level, usage)?;
+        let entries = page_table_memory.indices().map(|_| PageTableEntry::NotValid).collect();
         Ok(Self { level, page_table_memory, entries })
     }
 
+    /// Maps the page owned by the confidential VM at the given guest physical address, creating intermediary page tables if
+    /// necessary. The leaf entry is created at the level whose page size equals the size of the page, so huge pages are mapped with a
+    /// single entry and the entry records the size of the mapping. Returns error if the address, or any part of the page, is already
+    /// mapped, so that a page can never be silently replaced.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn map_confidential_page(
+        &mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>,
+    ) -> Result<(), (Error, Page<Allocated>)> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        let is_leaf_level = paging_system.page_size(self.level) == *page.size();
+        let entry = match self.entries.get_mut(virtual_page_number) {
+            Some(entry) => entry,
+            None => return Err((Error::PageTableConfiguration(), page)),
+        };
+        match (entry, self.level.lower()) {
+            (PageTableEntry::Pointer(next_page_table, _), Some(_)) if !is_leaf_level => {
+                next_page_table.map_confidential_page(paging_system, address, page)
+            }
+            (PageTableEntry::NotValid, _) if is_leaf_level => {
+                let new_entry = PageTableEntry::Leaf(
+                    Box::new(page),
+                    PageTableConfiguration::confidential_page_configuration(),
+                    PageTablePermission::confidential_page_permission(),
+                );
+                self.set_entry(virtual_page_number, new_entry);
+                Ok(())
+            }
+            (PageTableEntry::NotValid, Some(lower_level)) if !is_leaf_level => {
+                let mut next_page_table = match PageTable::empty(paging_system, lower_level, self.page_table_memory.usage()) {
+                    Ok(next_page_table) => next_page_table,
+                    Err(error) => return Err((error, page)),
+                };
+                next_page_table.map_confidential_page(paging_system, address, page)?;
+                let new_entry = PageTableEntry::Pointer(Box::new(next_page_table), PageTableConfiguration::empty());
+                self.set_entry(virtual_page_number, new_entry);
+                Ok(())
+            }
+            _ => Err((Error::AddressAlreadyMapped(), page)),
+        }
+    }
+
     /// This function maps the confidential VM's physical address into the address of the page allocated by the
     /// hypervisor. The second-level page table is modified. If there was already a mapping, the address of a previosuly
-    /// mapped page is returned. The below function works only for shared pages of size 4KiB.
-    fn map_shared_page(&mut self, paging_system: PagingSystem, shared_page: SharedPage) -> Result<(), Error> {
+    /// mapped page is returned. The below function works only for shared pages of size 4KiB. A huge page that contains the address
+    /// is split first (see `split_huge_page`), so that only the 4KiB page at the address is replaced.
+    ///
+    /// Returns the guest physical address and size of the largest huge page that was split. Its translations are not fenced here.
+    fn map_shared_page(
+        &mut self, paging_system: PagingSystem, shared_page: SharedPage,
+    ) -> Result<Option<(ConfidentialVmPhysicalAddress, PageSize)>, Error> {
         // walk from the root page table until the leaf node recreating the intermediary page tables if necessary.
-        let virtual_page_number = paging_system.vpn(shared_page.confidential_vm_virtual_address(), self.level);
+        let address = shared_page.confidential_vm_virtual_address();
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        let mut split_page = None;
+        if self.level != PageTableLevel::Level1 && matches!(self.entries.get(virtual_page_number), Some(PageTableEntry::Leaf(_, _, _))) {
+            self.split_huge_page(paging_system, virtual_page_number)?;
+            let page_size = paging_system.page_size(self.level);
+            split_page = Some((ConfidentialVmPhysicalAddress::new(address.usize() & !(page_size.in_bytes() - 1)), page_size));
+        }
         let entry = self.entries.get_mut(virtual_page_number).ok_or_else(|| Error::PageTableConfiguration())?;
         match entry {
             PageTableEntry::Pointer(next_page_table, _) => {
-                next_page_table.map_shared_page(paging_system, shared_page)?;
+                let lower_split_page = next_page_table.map_shared_page(paging_system, shared_page)?;
+                split_page = split_page.or(lower_split_page);
             }
             PageTableEntry::Leaf(_page, _configuration, _permission) => {
                 // The virtual address is already mapped to this physical address. Let's detach the old address and map
                 // the requested address
-                // TODO: deallocate the old page only if it is the same size a the requested shared page size.
-                // if not, return an error because it might be that a huge page is already mapped and the 4KiB
-                // shared page is supposed to be inside this huge page --- this is not allowed.
                 let new_entry = PageTableEntry::Shared(
                     shared_page,
                     PageTableConfiguration::shared_page_configuration(),
@@ -141,18 +318,198 @@ impl PageTable {
                     self.set_entry(virtual_page_number, new_entry);
                 } else {
                     // intermediary page table does not exist, let's create it
-                    let mut next_page_table = PageTable::empty(paging_system, self.level)?;
+                    let lower_level = self.level.lower().ok_or(Error::PageTableCorrupted())?;
+                    let mut next_page_table = PageTable::empty(paging_system, lower_level, self.page_table_memory.usage())?;
                     next_page_table.map_shared_page(paging_system, shared_page)?;
                     let new_entry = PageTableEntry::Pointer(Box::new(next_page_table), PageTableConfiguration::empty());
                     self.set_entry(virtual_page_number, new_entry);
                 }
             }
         }
+        Ok(split_page)
+    }
+
+    /// Replaces the huge page mapped by the entry with a page table of the lower level that maps the same memory with smaller pages.
+    /// Every smaller page keeps its physical address, content, configuration, and permissions, so the confidential VM observes no
+    /// change. The page table is allocated before the entry is modified, thus the mapping is left intact if there is no memory.
+    ///
+    /// The lower-level page table is entirely populated before the entry pointing to it replaces the huge page in a single write.
+    /// Hence, there is no point in time at which a part of the huge page is unmapped or mapped twice, and stale TLB entries of the
+    /// huge page translate to the same memory as the new entries until the caller fences them.
+    fn split_huge_page(&mut self, paging_system: PagingSystem, index: usize) -> Result<(), Error> {
+        let lower_level = self.level.lower().ok_or(Error::PageTableCorrupted())?;
+        let mut lower_page_table = PageTable::empty(paging_system, lower_level, self.page_table_memory.usage())?;
+        // The entry is detached only from the software representation of the page table. The hardware keeps translating through the
+        // huge page until it is replaced below.
+        let (page, configuration, permission) = match core::mem::replace(&mut self.entries[index], PageTableEntry::NotValid) {
+            PageTableEntry::Leaf(page, configuration, permission) => (page, configuration, permission),
+            entry => {
+                self.entries[index] = entry;
+                return Err(Error::PageTableCorrupted());
+            }
+        };
+        // The smaller pages are accounted when they are set in the lower-level page table.
+        self.page_table_memory.usage().remove_data_page(page.size());
+        let smaller_pages = match (*page).split().or_else(Page::split_gigapage) {
+            Ok(smaller_pages) => Vec::from(smaller_pages),
+            Err(page) => page.divide(),
+        };
+        smaller_pages.into_iter().enumerate().for_each(|(lower_index, smaller_page)| {
+            lower_page_table.set_entry(lower_index, PageTableEntry::Leaf(Box::new(smaller_page), configuration, permission));
+        });
+        // The entries of the lower-level page table must be visible to other harts' page walkers before the pointer to it.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+        self.set_entry(index, PageTableEntry::Pointer(Box::new(lower_page_table), PageTableConfiguration::empty()));
         Ok(())
     }
 
-    pub fn unmap_shared_page(&mut self, _paging_system: PagingSystem, _address: ConfidentialVmPhysicalAddress) -> Result<(), Error> {
-        panic!("Unimplemented");
+    /// Merges page tables on the path to the given guest physical address back into huge pages, starting from the lowest level. Thus,
+    /// once a page table is merged, the page table above it can be merged in turn, e.g., 4KiB pages into a 2MiB page and then 2MiB
+    /// pages into a 1GiB page.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn merge_pages(&mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress) {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        if let Some(PageTableEntry::Pointer(next_page_table, _)) = self.entries.get_mut(virtual_page_number) {
+            next_page_table.merge_pages(paging_system, address);
+            self.merge_page_table(virtual_page_number);
+        }
+    }
+
+    /// Replaces the entry pointing to a lower-level page table with a huge page that maps the same memory, the inverse of
+    /// `split_huge_page`. The page table is merged only if all its entries map pages owned by the confidential VM with the same
+    /// configuration and permissions, and these pages form a single physically contiguous and aligned huge page (see `Page::merge`).
+    /// A page table that maps a shared page is never merged because the shared page belongs to the hypervisor.
+    ///
+    /// The memory of the merged page table is returned to the page allocator only after the TLB shutdown, because until then page
+    /// walkers might still read its entries.
+    fn merge_page_table(&mut self, index: usize) {
+        let lower_page_table = match self.entries.get_mut(index) {
+            Some(PageTableEntry::Pointer(lower_page_table, _)) => lower_page_table,
+            _ => return,
+        };
+        let (configuration, permission) = match lower_page_table.entries.first() {
+            Some(PageTableEntry::Leaf(_, configuration, permission)) => (*configuration, *permission),
+            _ => return,
+        };
+        let is_uniform = lower_page_table.entries.iter().all(|entry| {
+            matches!(entry, PageTableEntry::Leaf(_, entry_configuration, entry_permission) if *entry_configuration == configuration && *entry_permission == permission)
+        });
+        if !is_uniform {
+            return;
+        }
+        // The pages are detached only from the software representation of the page table. The hardware keeps translating through the
+        // lower-level page table until the entry pointing to it is replaced below.
+        let pages = lower_page_table
+            .entries
+            .iter_mut()
+            .filter_map(|entry| match core::mem::replace(entry, PageTableEntry::NotValid) {
+                PageTableEntry::Leaf(page, _, _) => Some(*page),
+                _ => None,
+            })
+            .collect();
+        let huge_page = match Page::merge(pages) {
+            Ok(huge_page) => huge_page,
+            Err(pages) => {
+                lower_page_table.entries.iter_mut().zip(pages).for_each(|(entry, page)| {
+                    *entry = PageTableEntry::Leaf(Box::new(page), configuration, permission);
+                });
+                return;
+            }
+        };
+        let new_entry = PageTableEntry::Leaf(Box::new(huge_page), configuration, permission);
+        self.page_table_memory.set_entry(index, &new_entry);
+        let merged_page_table = core::mem::replace(&mut self.entries[index], new_entry);
+        crate::core::memory_protector::tlb::tlb_shutdown();
+        drop(merged_page_table);
+    }
+
+    /// Replaces the shared page mapped at the given guest physical address with the page owned by the confidential VM, so that the
+    /// address becomes private again. Returns the removed shared page, or error if the address does not map a shared page.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn unmap_shared_page(
+        &mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress, page: Page<Allocated>,
+    ) -> Result<SharedPage, Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get_mut(virtual_page_number) {
+            Some(PageTableEntry::Pointer(next_page_table, _)) => next_page_table.unmap_shared_page(paging_system, address, page),
+            Some(PageTableEntry::Shared(_, _, _)) => {
+                self.page_table_memory.usage().add_data_page(page.size());
+                let new_entry = PageTableEntry::Leaf(
+                    Box::new(page),
+                    PageTableConfiguration::confidential_page_configuration(),
+                    PageTablePermission::confidential_page_permission(),
+                );
+                self.page_table_memory.set_entry(virtual_page_number, &new_entry);
+                match core::mem::replace(&mut self.entries[virtual_page_number], new_entry) {
+                    PageTableEntry::Shared(shared_page, _, _) => Ok(shared_page),
+                    _ => Err(Error::PageTableCorrupted()),
+                }
+            }
+            _ => {
+                // The page was not used, so we must return it to the page allocator.
+                PageAllocator::release_zeroed_page(page.deallocate());
+                Err(Error::AddressTranslationFailed())
+            }
+        }
+    }
+
+    /// Invalidates the entry mapping the shared page at the given guest physical address without backing the address with another
+    /// page. Returns the removed shared page, or error if the address does not map a shared page.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn remove_shared_page(&mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress) -> Result<SharedPage, Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get_mut(virtual_page_number) {
+            Some(PageTableEntry::Pointer(next_page_table, _)) => next_page_table.remove_shared_page(paging_system, address),
+            Some(PageTableEntry::Shared(_, _, _)) => {
+                self.page_table_memory.clear_entry(virtual_page_number);
+                match core::mem::replace(&mut self.entries[virtual_page_number], PageTableEntry::NotValid) {
+                    PageTableEntry::Shared(shared_page, _, _) => Ok(shared_page),
+                    _ => Err(Error::PageTableCorrupted()),
+                }
+            }
+            _ => Err(Error::AddressTranslationFailed()),
+        }
+    }
+
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn unmap_confidential_page(
+        &mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress,
+    ) -> Result<Page<Allocated>, Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get_mut(virtual_page_number) {
+            Some(PageTableEntry::Pointer(next_page_table, _)) => next_page_table.unmap_confidential_page(paging_system, address),
+            Some(PageTableEntry::Leaf(page, _, _)) if address.usize() % page.size().in_bytes() == 0 => {
+                self.page_table_memory.clear_entry(virtual_page_number);
+                match core::mem::replace(&mut self.entries[virtual_page_number], PageTableEntry::NotValid) {
+                    PageTableEntry::Leaf(page, _, _) => {
+                        self.page_table_memory.usage().remove_data_page(page.size());
+                        Ok(*page)
+                    }
+                    _ => Err(Error::PageTableCorrupted()),
+                }
+            }
+            _ => Err(Error::AddressTranslationFailed()),
+        }
+    }
+
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn set_accessed_dirty_bits(
+        &mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress, access: GuestMemoryAccess,
+    ) -> Result<(PageSize, AccessedDirtyUpdate), Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get_mut(virtual_page_number) {
+            Some(PageTableEntry::Pointer(next_page_table, _)) => next_page_table.set_accessed_dirty_bits(paging_system, address, access),
+            Some(PageTableEntry::Leaf(page, configuration, permission)) if permission.allows(access) => {
+                let update = configuration.accessed_dirty_update(access).ok_or(Error::AddressTranslationFailed())?;
+                let mask = configuration.apply(&update);
+                self.page_table_memory.set_entry_bits(virtual_page_number, mask);
+                Ok((*page.size(), update))
+            }
+            _ => Err(Error::AddressTranslationFailed()),
+        }
     }
 
     /// Translates the guest physical address to host physical address by doing a page walk. Error is returned if there exists no mapping
@@ -171,15 +528,134 @@ impl PageTable {
         }
     }
 
+    fn host_translation(
+        &self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress,
+    ) -> Result<(usize, AccessPermissions), Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get(virtual_page_number).ok_or_else(|| Error::PageTableConfiguration())? {
+            PageTableEntry::Pointer(next_page_table, _) => next_page_table.host_translation(paging_system, address),
+            PageTableEntry::Leaf(page, _configuration, permission) => {
+                let offset_in_bytes = address.usize() % page.size().in_bytes();
+                Ok((page.start_address() + offset_in_bytes, permission.access_permissions()))
+            }
+            PageTableEntry::Shared(shared_page, _configuration, permission) => {
+                let offset_in_bytes = address.usize() % shared_page.page_size().in_bytes();
+                Ok((shared_page.non_confidential_address() + offset_in_bytes, permission.access_permissions()))
+            }
+            PageTableEntry::NotValid => Err(Error::AddressTranslationFailed()),
+        }
+    }
+
+    /// Returns the page owned by the confidential VM that is mapped at the given guest physical address. Error is returned if there
+    /// exists no mapping for the address or the address translates to a shared page.
+    fn confidential_page(&self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress) -> Result<&Page<Allocated>, Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get(virtual_page_number).ok_or_else(|| Error::PageTableConfiguration())? {
+            PageTableEntry::Pointer(next_page_table, _) => next_page_table.confidential_page(paging_system, address),
+            PageTableEntry::Leaf(page, _configuration, _permission) => Ok(page),
+            _ => Err(Error::AddressTranslationFailed()),
+        }
+    }
+
+    fn confidential_page_mut(
+        &mut self, paging_system: PagingSystem, address: ConfidentialVmPhysicalAddress,
+    ) -> Result<&mut Page<Allocated>, Error> {
+        let virtual_page_number = paging_system.vpn(address, self.level);
+        match self.entries.get_mut(virtual_page_number).ok_or_else(|| Error::PageTableConfiguration())? {
+            PageTableEntry::Pointer(next_page_table, _) => next_page_table.confidential_page_mut(paging_system, address),
+            PageTableEntry::Leaf(page, _configuration, _permission) => Ok(page),
+            _ => Err(Error::AddressTranslationFailed()),
+        }
+    }
+
+    /// Returns true if this page table, or any page table it points to, maps a shared page located in the given region of the
+    /// non-confidential memory.
+    fn contains_shared_page(&self, memory_start: usize, memory_end: usize) -> bool {
+        self.entries.iter().any(|entry| match entry {
+            PageTableEntry::Pointer(next_page_table, _) => next_page_table.contains_shared_page(memory_start, memory_end),
+            PageTableEntry::Shared(shared_page, _, _) => {
+                memory_start <= shared_page.non_confidential_address() && shared_page.non_confidential_address() < memory_end
+            }
+            _ => false,
+        })
+    }
+
+    /// Calls the operation on every page in the confidential memory mapped by this page table, or any page table it points to, in the
+    /// ascending order of guest physical addresses. Shared pages are skipped because they are not owned by the confidential VM.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn for_each_confidential_page(
+        &self, paging_system: PagingSystem, base_address: usize,
+        op: &mut dyn FnMut(ConfidentialVmPhysicalAddress, &Page<Allocated>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let entry_span_in_bytes = paging_system.entry_span_in_bytes(self.level);
+        self.entries.iter().enumerate().try_for_each(|(index, entry)| {
+            let address = base_address + index * entry_span_in_bytes;
+            match entry {
+                PageTableEntry::Pointer(next_page_table, _) => next_page_table.for_each_confidential_page(paging_system, address, op),
+                PageTableEntry::Leaf(page, _, _) => op(ConfidentialVmPhysicalAddress::new(address), page),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn for_each_mapping(
+        &self, paging_system: PagingSystem, base_address: usize,
+        op: &mut dyn FnMut(ConfidentialVmPhysicalAddress, usize, PageSize, AccessPermissions),
+    ) {
+        let entry_span_in_bytes = paging_system.entry_span_in_bytes(self.level);
+        self.entries.iter().enumerate().for_each(|(index, entry)| {
+            let address = ConfidentialVmPhysicalAddress::new(base_address + index * entry_span_in_bytes);
+            match entry {
+                PageTableEntry::Pointer(next_page_table, _) => next_page_table.for_each_mapping(paging_system, address.usize(), op),
+                PageTableEntry::Leaf(page, _, permission) => {
+                    op(address, page.start_address(), *page.size(), permission.access_permissions())
+                }
+                PageTableEntry::Shared(shared_page, _, permission) => {
+                    op(address, shared_page.non_confidential_address(), shared_page.page_size(), permission.access_permissions())
+                }
+                PageTableEntry::NotValid => {}
+            }
+        })
+    }
+
+    /// Incrementally tears down the page table, starting from the last entry. Every removed entry is first invalidated in the page
+    /// table memory and only then the page it maps is zeroized and returned to the page allocator. Page tables of lower levels are
+    /// deallocated once all their entries have been reclaimed. Returns true if this page table has no more entries.
+    ///
+    /// This is a recursive function, which deepest execution is not larger than the number of paging system levels.
+    fn reclaim_pages(&mut self, budget: &mut usize) -> bool {
+        while *budget > 0 && !self.entries.is_empty() {
+            let index = self.entries.len() - 1;
+            if let PageTableEntry::Pointer(next_page_table, _) = &mut self.entries[index] {
+                if !next_page_table.reclaim_pages(budget) {
+                    return false;
+                }
+            }
+            self.page_table_memory.clear_entry(index);
+            if let Some(PageTableEntry::Leaf(page, _, _)) = self.entries.pop() {
+                self.page_table_memory.usage().remove_data_page(page.size());
+                PageAllocator::release_zeroed_page(page.deallocate());
+                *budget -= 1;
+            }
+        }
+        self.entries.is_empty()
+    }
+
     pub(super) fn address(&self) -> usize {
         self.page_table_memory.start_address()
     }
 
     fn set_entry(&mut self, index: usize, entry: PageTableEntry) {
         self.page_table_memory.set_entry(index, &entry);
+        if let PageTableEntry::Leaf(page, _, _) = &entry {
+            self.page_table_memory.usage().add_data_page(page.size());
+        }
         let entry_to_remove = core::mem::replace(&mut self.entries[index], entry);
         if let PageTableEntry::Leaf(page, _, _) = entry_to_remove {
-            PageAllocator::release_page(page.deallocate());
+            self.page_table_memory.usage().remove_data_page(page.size());
+            PageAllocator::release_zeroed_page(page.deallocate());
         }
     }
 }
@@ -190,8 +666,62 @@ impl Drop for PageTable {
         // that own a page.
         self.entries.drain(..).for_each(|entry| {
             if let PageTableEntry::Leaf(page, _, _) = entry {
-                PageAllocator::release_page(page.deallocate());
+                self.page_table_memory.usage().remove_data_page(page.size());
+                PageAllocator::release_zeroed_page(page.deallocate());
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::page_allocator::test_memory::setup;
+    use crate::core::transformations::SharePageRequest;
+
+    /// Translates the guest physical address like the hardware does, i.e., by reading the encoded entries from the memory of the page
+    /// tables instead of from their software representation. Returns None if the address is not mapped.
+    fn walk(root_page_table: &RootPageTable, address: usize) -> Option<usize> {
+        let paging_system = root_page_table.paging_system;
+        let mut page_table_address = root_page_table.page_table.address();
+        let mut level = Some(paging_system.levels());
+        while let Some(current_level) = level {
+            let index = paging_system.vpn(ConfidentialVmPhysicalAddress::new(address), current_level);
+            // Safety: the page table is owned by the root page table, which outlives this function.
+            let entry = unsafe { ((page_table_address + index * paging_system.entry_size()) as *const usize).read_volatile() };
+            if !PageTableBits::is_valid(entry) {
+                return None;
+            }
+            if PageTableBits::is_leaf(entry) {
+                return Some(PageTableAddress::decode(entry) as usize + address % paging_system.page_size(current_level).in_bytes());
+            }
+            page_table_address = PageTableAddress::decode(entry) as usize;
+            level = current_level.lower();
+        }
+        None
+    }
+
+    #[test]
+    fn split_huge_page_keeps_translations_of_other_pages() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let huge_page_address = 0x8000_0000;
+        let shared_page_address = huge_page_address + 5 * PageSize::Size4KiB.in_bytes();
+        let mut root_page_table = RootPageTable::empty(PagingSystem::Sv39x4).unwrap();
+        let huge_page = PageAllocator::acquire_continous_pages(1, PageSize::Size2MiB).unwrap().remove(0).zeroize();
+        let huge_page_start = huge_page.start_address();
+        root_page_table.map_confidential_page(ConfidentialVmPhysicalAddress::new(huge_page_address), huge_page).unwrap();
+        let offsets = (0..PageSize::Size2MiB.in_bytes()).step_by(PageSize::Size4KiB.in_bytes());
+        offsets.clone().for_each(|offset| assert_eq!(walk(&root_page_table, huge_page_address + offset), Some(huge_page_start + offset)));
+
+        let shared_page = SharedPage::new(non_confidential_memory_start, SharePageRequest::new(shared_page_address).unwrap()).unwrap();
+        let (split_page_address, split_page_size) = root_page_table.map_shared_page(shared_page).unwrap().unwrap();
+        assert_eq!(split_page_address.usize(), huge_page_address);
+        assert_eq!(split_page_size, PageSize::Size2MiB);
+        for offset in offsets {
+            let address = huge_page_address + offset;
+            let expected_address = if address == shared_page_address { non_confidential_memory_start } else { huge_page_start + offset };
+            assert_eq!(walk(&root_page_table, address), Some(expected_address));
+            assert_eq!(root_page_table.host_translation(ConfidentialVmPhysicalAddress::new(address)).unwrap().0, expected_address);
+        }
+    }
+}
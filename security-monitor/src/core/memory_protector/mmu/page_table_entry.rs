@@ -2,6 +2,7 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::memory_protector::mmu::page_table::PageTable;
+use crate::core::memory_protector::{AccessPermissions, AccessedDirtyUpdate, GuestMemoryAccess};
 use crate::core::page_allocator::{Allocated, Page, SharedPage};
 use alloc::boxed::Box;
 
@@ -77,6 +78,7 @@ impl PageTableAddress {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub(super) struct PageTablePermission {
     can_read: bool,
     can_write: bool,
@@ -88,6 +90,10 @@ impl PageTablePermission {
         Self { can_read: true, can_write: true, can_execute: false }
     }
 
+    pub fn confidential_page_permission() -> Self {
+        Self { can_read: true, can_write: true, can_execute: true }
+    }
+
     pub fn decode(raw_entry: usize) -> Self {
         let can_read = PageTableBits::Read.is_set(raw_entry);
         let can_write = PageTableBits::Write.is_set(raw_entry);
@@ -95,6 +101,18 @@ impl PageTablePermission {
         Self { can_read, can_write, can_execute }
     }
 
+    pub fn allows(&self, access: GuestMemoryAccess) -> bool {
+        match access {
+            GuestMemoryAccess::Fetch => self.can_execute,
+            GuestMemoryAccess::Load => self.can_read,
+            GuestMemoryAccess::Store => self.can_write,
+        }
+    }
+
+    pub fn access_permissions(&self) -> AccessPermissions {
+        AccessPermissions { can_read: self.can_read, can_write: self.can_write, can_execute: self.can_execute }
+    }
+
     pub fn encode(&self) -> usize {
         let mut encoded_value = 0;
         if self.can_read {
@@ -110,6 +128,7 @@ impl PageTablePermission {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub(super) struct PageTableConfiguration {
     is_accessible_to_user: bool,
     was_accessed: bool,
@@ -126,6 +145,31 @@ impl PageTableConfiguration {
         Self { is_accessible_to_user: true, was_accessed: true, is_global_mapping: false, is_dirty: true }
     }
 
+    /// G-stage translations require the user bit set. Accessed and dirty bits are preset because the security monitor does not
+    /// track them.
+    pub fn confidential_page_configuration() -> Self {
+        Self { is_accessible_to_user: true, was_accessed: true, is_global_mapping: false, is_dirty: true }
+    }
+
+    /// Returns the update of the accessed and dirty bits that the access requires, or None if the bits are already set.
+    pub fn accessed_dirty_update(&self, access: GuestMemoryAccess) -> Option<AccessedDirtyUpdate> {
+        AccessedDirtyUpdate::required(self.was_accessed, self.is_dirty, access)
+    }
+
+    /// Sets the bits of the update and returns their mask in the encoded entry.
+    pub fn apply(&mut self, update: &AccessedDirtyUpdate) -> usize {
+        let mut mask = 0;
+        if update.sets_accessed() {
+            self.was_accessed = true;
+            mask = mask | PageTableBits::Accessed.mask();
+        }
+        if update.sets_dirty() {
+            self.is_dirty = true;
+            mask = mask | PageTableBits::Dirty.mask();
+        }
+        mask
+    }
+
     pub fn decode(raw_entry: usize) -> Self {
         let is_accessible_to_user = PageTableBits::User.is_set(raw_entry);
         let was_accessed = PageTableBits::Accessed.is_set(raw_entry);
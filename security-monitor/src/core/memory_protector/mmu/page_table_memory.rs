@@ -4,48 +4,56 @@
 use super::PagingSystem;
 use crate::core::memory_layout::{MemoryLayout, NonConfidentialMemoryAddress};
 use crate::core::memory_protector::mmu::page_table_entry::PageTableEntry;
+use crate::core::memory_protector::mmu::page_table_usage::PageTableUsage;
 use crate::core::memory_protector::mmu::paging_system::PageTableLevel;
 use crate::core::memory_protector::mmu::PageSize;
 use crate::core::page_allocator::{Allocated, Page, PageAllocator};
 use crate::error::Error;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ops::Range;
 
 /// Abstraction over the physical memory region of the page table giving an
-/// interface to easily access raw page table entries organized accross Pages.
+/// interface to easily access raw page table entries organized accross Pages. Its pages are accounted to the confidential VM's
+/// page table usage from the allocation until the drop.
 pub(super) struct PageTableMemory {
     pages: Vec<Page<Allocated>>,
     number_of_entries: usize,
     entry_size: usize,
+    usage: Arc<PageTableUsage>,
 }
 
 impl PageTableMemory {
     const PAGE_SIZE: PageSize = PageSize::Size4KiB;
 
-    pub(super) fn copy_from_non_confidential_memory(
-        address: NonConfidentialMemoryAddress, paging_system: PagingSystem, level: PageTableLevel,
-    ) -> Result<Self, Error> {
+    pub(super) fn empty(paging_system: PagingSystem, level: PageTableLevel, usage: &Arc<PageTableUsage>) -> Result<Self, Error> {
         let number_of_pages = paging_system.configuration_pages(level);
-        let pages = PageAllocator::acquire_continous_pages(number_of_pages, Self::PAGE_SIZE)?
-            .into_iter()
-            .enumerate()
-            .map(|(i, page)| {
-                let offset_in_bytes = i * page.size().in_bytes();
-                let page_address = MemoryLayout::read().non_confidential_address_at_offset(&address, offset_in_bytes)?;
-                page.copy_from_non_confidential_memory(page_address)
-            })
-            .collect::<Result<Vec<Page<Allocated>>, Error>>()?;
+        usage.reserve_page_table_pages(number_of_pages)?;
+        let pages = match number_of_pages {
+            1 => PageAllocator::acquire_zeroed_page().map(|page| vec![page]),
+            _ => PageAllocator::acquire_continous_pages(number_of_pages, Self::PAGE_SIZE)
+                .map(|pages| pages.into_iter().map(|page| page.zeroize()).collect()),
+        }
+        .inspect_err(|_| usage.release_page_table_pages(number_of_pages))?;
         let number_of_entries = paging_system.entries(level);
         let entry_size = paging_system.entry_size();
-        Ok(Self { pages, number_of_entries, entry_size })
+        Ok(Self { pages, number_of_entries, entry_size, usage: usage.clone() })
     }
 
-    pub(super) fn empty(paging_system: PagingSystem, level: PageTableLevel) -> Result<Self, Error> {
-        let number_of_pages = paging_system.configuration_pages(level);
-        let pages = PageAllocator::acquire_continous_pages(number_of_pages, Self::PAGE_SIZE)?.into_iter().map(|f| f.zeroize()).collect();
-        let number_of_entries = paging_system.entries(level);
-        let entry_size = paging_system.entry_size();
-        Ok(Self { pages, number_of_entries, entry_size })
+    /// Reads the raw entry at the given index of a page table located in the non-confidential memory. The caller must read every
+    /// entry once and use only the returned value, so that the hypervisor cannot modify the entry after it has been checked.
+    pub(super) fn read_non_confidential_entry(
+        address: &NonConfidentialMemoryAddress, paging_system: PagingSystem, index: usize,
+    ) -> Result<usize, Error> {
+        let offset_in_bytes = index.checked_mul(paging_system.entry_size()).ok_or(Error::PageTableCorrupted())?;
+        let entry_address = MemoryLayout::read().non_confidential_address_at_offset(address, offset_in_bytes)?;
+        // Safety: the address is in the non-confidential memory and aligned to the entry size because page tables are aligned to
+        // their size, so reading it cannot disclose the security monitor's or confidential VMs' memory.
+        Ok(unsafe { entry_address.read() })
+    }
+
+    pub(super) fn usage(&self) -> &Arc<PageTableUsage> {
+        &self.usage
     }
 
     pub(super) fn start_address(&self) -> usize {
@@ -61,13 +69,6 @@ impl PageTableMemory {
         Range { start: 0, end: self.number_of_entries }
     }
 
-    pub(super) fn entry(&self, index: usize) -> Option<usize> {
-        self.resolve_index(index).and_then(|(page_id, index_in_page)| {
-            let offset_in_page = self.entry_size * index_in_page;
-            self.pages.get(page_id).and_then(|page| page.read(offset_in_page).ok())
-        })
-    }
-
     pub(super) fn set_entry(&mut self, index: usize, entry: &PageTableEntry) {
         // skip unnecessary write to the memory. Pages in the confidential memory
         // are guaranteed to be zeroed when allocated and a not valid entry is just 0
@@ -82,6 +83,24 @@ impl PageTableMemory {
         });
     }
 
+    /// Writes an invalid entry at the given index. Unlike `set_entry`, it always writes to the memory because it is used to remove
+    /// existing mappings.
+    pub(super) fn clear_entry(&mut self, index: usize) {
+        self.resolve_index(index).and_then(|(page_id, index_in_page)| {
+            let offset_in_page = self.entry_size * index_in_page;
+            self.pages.get_mut(page_id).and_then(|ref mut page| page.write(offset_in_page, 0).ok())
+        });
+    }
+
+    /// Sets the bits of the entry at the given index with an atomic read-modify-write, so that updates of other bits of the entry, e.g.,
+    /// by the hardware, are not lost.
+    pub(super) fn set_entry_bits(&mut self, index: usize, mask: usize) {
+        self.resolve_index(index).and_then(|(page_id, index_in_page)| {
+            let offset_in_page = self.entry_size * index_in_page;
+            self.pages.get_mut(page_id).and_then(|page| page.fetch_or(offset_in_page, mask).ok())
+        });
+    }
+
     fn resolve_index(&self, index: usize) -> Option<(usize, usize)> {
         if index < self.number_of_entries {
             // we can do this calculations because 1) pages are continous 2) vector stores pages
@@ -99,6 +118,7 @@ impl PageTableMemory {
 impl Drop for PageTableMemory {
     fn drop(&mut self) {
         let deallocated_pages: Vec<_> = self.pages.drain(..).map(|p| p.deallocate()).collect();
-        PageAllocator::release_pages(deallocated_pages);
+        self.usage.release_page_table_pages(deallocated_pages.len());
+        PageAllocator::release_zeroed_pages(deallocated_pages);
     }
 }
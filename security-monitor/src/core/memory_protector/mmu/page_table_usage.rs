@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_protector::mmu::PageSize;
+use crate::error::Error;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static DATA_PAGES_PER_PAGE_TABLE_PAGE: AtomicUsize = AtomicUsize::new(PageTableUsage::DEFAULT_DATA_PAGES_PER_PAGE_TABLE_PAGE);
+
+/// Accounts the confidential memory used by the page tables of a confidential VM and by the pages they map, in 4KiB pages. A hostile
+/// hypervisor could lay out the guest physical address space so sparsely that the security monitor allocates a page table for almost
+/// every mapped page. Thus, the number of page table pages is limited relative to the number of mapped pages, and a mapping that would
+/// exceed the limit fails before any confidential memory is allocated for it.
+#[derive(Default)]
+pub struct PageTableUsage {
+    page_table_pages: AtomicUsize,
+    data_pages: AtomicUsize,
+}
+
+impl PageTableUsage {
+    // Page tables that a confidential VM may use regardless of how much memory it owns, enough for the root page table and the page
+    // tables mapping a handful of disjoint memory regions, e.g., the kernel image, the device tree, and the initial ramdisk.
+    const BASE_LIMIT: usize = 64;
+    const DEFAULT_DATA_PAGES_PER_PAGE_TABLE_PAGE: usize = 8;
+
+    /// Sets how many mapped pages, at least, a confidential VM must own for every page table page above the base limit. Called during
+    /// the boot if the platform configures the ratio. A zero ratio is ignored.
+    pub fn set_data_pages_per_page_table_page(ratio: usize) {
+        if ratio > 0 {
+            DATA_PAGES_PER_PAGE_TABLE_PAGE.store(ratio, Ordering::Release);
+        }
+    }
+
+    pub fn page_table_pages(&self) -> usize {
+        self.page_table_pages.load(Ordering::Relaxed)
+    }
+
+    pub fn data_pages(&self) -> usize {
+        self.data_pages.load(Ordering::Relaxed)
+    }
+
+    pub fn max_page_table_pages(&self) -> usize {
+        Self::BASE_LIMIT + self.data_pages() / DATA_PAGES_PER_PAGE_TABLE_PAGE.load(Ordering::Acquire)
+    }
+
+    /// Accounts page table pages before they are allocated. Returns error if the confidential VM would exceed its limit.
+    pub(super) fn reserve_page_table_pages(&self, number_of_pages: usize) -> Result<(), Error> {
+        let page_table_pages = self.page_table_pages().checked_add(number_of_pages).ok_or(Error::PageTableLimitExceeded())?;
+        assure!(page_table_pages <= self.max_page_table_pages(), Error::PageTableLimitExceeded())?;
+        self.page_table_pages.store(page_table_pages, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub(super) fn release_page_table_pages(&self, number_of_pages: usize) {
+        self.page_table_pages.fetch_sub(number_of_pages, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_data_page(&self, page_size: &PageSize) {
+        self.data_pages.fetch_add(Self::in_4kib_pages(page_size), Ordering::Relaxed);
+    }
+
+    pub(super) fn remove_data_page(&self, page_size: &PageSize) {
+        self.data_pages.fetch_sub(Self::in_4kib_pages(page_size), Ordering::Relaxed);
+    }
+
+    fn in_4kib_pages(page_size: &PageSize) -> usize {
+        page_size.in_bytes() / PageSize::Size4KiB.in_bytes()
+    }
+}
@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::MemoryLayout;
+use crate::core::memory_protector::mmu::page_table_entry::{PageTableAddress, PageTableBits};
+use crate::core::memory_protector::mmu::paging_system::{PageTableLevel, PagingSystem};
+use crate::error::{Error, PageTableViolation};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+/// Checks the raw entries of the G-stage page tables that the hypervisor built, while `PageTable::copy_from_non_confidential_memory`
+/// decodes them. Every entry is read from the hypervisor's memory once, and the checked value is the one that is decoded, so the
+/// hypervisor cannot modify an entry after it has been checked.
+pub(super) struct PageTableValidator {
+    paging_system: PagingSystem,
+    // host physical addresses of all page tables seen so far.
+    page_tables: BTreeSet<usize>,
+    // host physical memory regions mapped by leaf entries, indexed by their start addresses and pointing to their end addresses.
+    // Adjacent regions are merged into a single region, so the size of the map stays proportional to the fragmentation of the VM's
+    // memory.
+    mapped_regions: BTreeMap<usize, usize>,
+}
+
+impl PageTableValidator {
+    // Bits 54-63 are reserved for the Svpbmt and Svnapot extensions, and for future standard use. The security monitor implements
+    // neither of these extensions.
+    const RESERVED_BITS_MASK: usize = 0xFFC0_0000_0000_0000;
+    // The specification reserves the accessed, dirty, and user bits in non-leaf entries.
+    const NON_LEAF_RESERVED_BITS_MASK: usize = PageTableBits::User.mask() | PageTableBits::Accessed.mask() | PageTableBits::Dirty.mask();
+
+    pub fn new(paging_system: PagingSystem) -> Self {
+        Self { paging_system, page_tables: BTreeSet::new(), mapped_regions: BTreeMap::new() }
+    }
+
+    /// Records the page table located at the given host physical address. Returns error if the page table is not aligned to its size,
+    /// is located outside of the non-confidential memory, or it has already been seen, in which case the page table hierarchy has a
+    /// cycle or two entries point to the same page table.
+    pub fn check_page_table(&mut self, address: usize, level: PageTableLevel, guest_physical_address: usize) -> Result<(), Error> {
+        let size_in_bytes = self.paging_system.size_in_bytes(level);
+        assure!(address % size_in_bytes == 0, Self::violation(PageTableViolation::MisalignedPage, guest_physical_address))?;
+        assure!(
+            Self::is_in_non_confidential_memory(address, size_in_bytes),
+            Self::violation(PageTableViolation::MemoryNotOwned, guest_physical_address)
+        )?;
+        assure!(self.page_tables.insert(address), Self::violation(PageTableViolation::PageTableReused, guest_physical_address))
+    }
+
+    /// Checks a valid entry of the page table at the given level that translates the given guest physical address. A leaf entry must
+    /// map a page of the non-confidential memory, aligned to the page size, that no other leaf entry maps.
+    pub fn check_entry(&mut self, raw_entry: usize, level: PageTableLevel, guest_physical_address: usize) -> Result<(), Error> {
+        assure!(raw_entry & Self::RESERVED_BITS_MASK == 0, Self::violation(PageTableViolation::ReservedBitsSet, guest_physical_address))?;
+        if !PageTableBits::is_leaf(raw_entry) {
+            assure!(level.lower().is_some(), Self::violation(PageTableViolation::PointerAtLowestLevel, guest_physical_address))?;
+            return assure!(
+                raw_entry & Self::NON_LEAF_RESERVED_BITS_MASK == 0,
+                Self::violation(PageTableViolation::ReservedBitsSet, guest_physical_address)
+            );
+        }
+        // Write-only pages are reserved by the specification.
+        let is_write_only = PageTableBits::Write.is_set(raw_entry) && !PageTableBits::Read.is_set(raw_entry);
+        assure_not!(is_write_only, Self::violation(PageTableViolation::WriteOnlyPage, guest_physical_address))?;
+        let address = PageTableAddress::decode(raw_entry) as usize;
+        let page_size_in_bytes = self.paging_system.page_size(level).in_bytes();
+        assure!(address % page_size_in_bytes == 0, Self::violation(PageTableViolation::MisalignedPage, guest_physical_address))?;
+        assure!(
+            Self::is_in_non_confidential_memory(address, page_size_in_bytes),
+            Self::violation(PageTableViolation::MemoryNotOwned, guest_physical_address)
+        )?;
+        self.record_mapped_region(address, address + page_size_in_bytes)
+            .map_err(|violation| Self::violation(violation, guest_physical_address))
+    }
+
+    /// Records the host physical memory region mapped by a leaf entry. Returns error if any part of the region is already mapped. Every
+    /// leaf page is copied to its own confidential page during the promotion, so aliased guest physical addresses would silently stop
+    /// sharing data and the same content would be measured several times.
+    fn record_mapped_region(&mut self, mut start: usize, mut end: usize) -> Result<(), PageTableViolation> {
+        // Recorded regions never overlap, so their end addresses are ordered like their start addresses. Hence, all regions that
+        // overlap or are adjacent to the new region are found by walking back from its end.
+        let neighbouring_regions: Vec<(usize, usize)> = self
+            .mapped_regions
+            .range(..=end)
+            .rev()
+            .take_while(|(_, region_end)| **region_end >= start)
+            .map(|(region_start, region_end)| (*region_start, *region_end))
+            .collect();
+        for (region_start, region_end) in neighbouring_regions {
+            assure_not!(region_start < end && start < region_end, PageTableViolation::AliasedPage)?;
+            self.mapped_regions.remove(&region_start);
+            start = start.min(region_start);
+            end = end.max(region_end);
+        }
+        self.mapped_regions.insert(start, end);
+        Ok(())
+    }
+
+    fn is_in_non_confidential_memory(address: usize, size_in_bytes: usize) -> bool {
+        let memory_layout = MemoryLayout::read();
+        address.checked_add(size_in_bytes - 1).is_some_and(|last_address| {
+            memory_layout.is_in_non_confidential_range(address as *const usize)
+                && memory_layout.is_in_non_confidential_range(last_address as *const usize)
+        })
+    }
+
+    fn violation(violation: PageTableViolation, guest_physical_address: usize) -> Error {
+        Error::MalformedPageTable(violation, guest_physical_address)
+    }
+}
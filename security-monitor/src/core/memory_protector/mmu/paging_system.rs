@@ -5,32 +5,44 @@ use crate::core::architecture::HgatpMode;
 use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
 use crate::core::memory_protector::PageSize;
 
-// TODO: add more 2nd-level paging systems corresponding to 3 and 4 level page
-// tables.
 #[derive(Debug, Copy, Clone)]
 pub enum PagingSystem {
+    Sv39x4,
+    Sv48x4,
     Sv57x4,
 }
 
 impl PagingSystem {
     pub fn from(mode: &HgatpMode) -> Option<Self> {
         match mode {
+            HgatpMode::Sv39x4 => Some(PagingSystem::Sv39x4),
+            HgatpMode::Sv48x4 => Some(PagingSystem::Sv48x4),
             HgatpMode::Sv57x4 => Some(PagingSystem::Sv57x4),
         }
     }
 
     pub fn hgatp_mode(&self) -> HgatpMode {
         match self {
+            Self::Sv39x4 => HgatpMode::Sv39x4,
+            Self::Sv48x4 => HgatpMode::Sv48x4,
             Self::Sv57x4 => HgatpMode::Sv57x4,
         }
     }
 
     pub fn levels(&self) -> PageTableLevel {
         match self {
+            PagingSystem::Sv39x4 => PageTableLevel::Level3,
+            PagingSystem::Sv48x4 => PageTableLevel::Level4,
             PagingSystem::Sv57x4 => PageTableLevel::Level5,
         }
     }
 
+    /// Returns true if the guest physical address is within the address space translated by this paging system. Page table indices
+    /// ignore higher bits of the address, so an address outside of the address space would alias an address inside it.
+    pub fn translates(&self, address: usize) -> bool {
+        address >> self.hgatp_mode().guest_physical_address_bits() == 0
+    }
+
     // number of pages required to store the configuration of the page table at the given level
     // in RISC-V all page tables fit at 4KiB pages except for the root page table in 2-level page table system
     pub fn configuration_pages(&self, level: PageTableLevel) -> usize {
@@ -39,9 +51,7 @@ impl PagingSystem {
 
     // returns the size of the entry in bytes
     pub fn entry_size(&self) -> usize {
-        match self {
-            PagingSystem::Sv57x4 => 8,
-        }
+        8
     }
 
     pub fn size_in_bytes(&self, level: PageTableLevel) -> usize {
@@ -50,24 +60,20 @@ impl PagingSystem {
 
     // 2nd level page table's root is extended by 2 bits according to the spec.
     pub fn entries(&self, level: PageTableLevel) -> usize {
-        match self {
-            PagingSystem::Sv57x4 => match level {
-                PageTableLevel::Level5 => 1 << 11,
-                _ => 1 << 9,
-            },
+        if level == self.levels() {
+            1 << 11
+        } else {
+            1 << 9
         }
     }
 
     pub fn vpn(&self, virtual_address: ConfidentialVmPhysicalAddress, level: PageTableLevel) -> usize {
-        match self {
-            PagingSystem::Sv57x4 => match level {
-                PageTableLevel::Level5 => (virtual_address.usize() >> 48) & 0x3ff,
-                PageTableLevel::Level4 => (virtual_address.usize() >> 39) & 0x1ff,
-                PageTableLevel::Level3 => (virtual_address.usize() >> 30) & 0x1ff,
-                PageTableLevel::Level2 => (virtual_address.usize() >> 21) & 0x1ff,
-                PageTableLevel::Level1 => (virtual_address.usize() >> 12) & 0x1ff,
-            },
-        }
+        (virtual_address.usize() >> level.address_shift()) & (self.entries(level) - 1)
+    }
+
+    /// Returns the size of the guest physical address range translated by a single page table entry at the given level.
+    pub fn entry_span_in_bytes(&self, level: PageTableLevel) -> usize {
+        1 << level.address_shift()
     }
 
     pub fn page_size(&self, level: PageTableLevel) -> PageSize {
@@ -91,6 +97,17 @@ pub enum PageTableLevel {
 }
 
 impl PageTableLevel {
+    // The position of the lowest bit of the guest physical address that indexes page tables at this level.
+    fn address_shift(&self) -> usize {
+        match self {
+            Self::Level5 => 48,
+            Self::Level4 => 39,
+            Self::Level3 => 30,
+            Self::Level2 => 21,
+            Self::Level1 => 12,
+        }
+    }
+
     pub fn lower(&self) -> Option<Self> {
         match self {
             Self::Level5 => Some(Self::Level4),
@@ -1,13 +1,25 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+pub use accessed_dirty_update::{AccessedDirtyUpdate, GuestMemoryAccess};
+#[cfg(feature = "pmp_audit_log")]
+pub use audit_log::{AuditLog, MemoryRegion, PmpOperation};
 pub use confidential_vm_memory_protector::ConfidentialVmMemoryProtector;
-pub use hypervisor_memory_protector::HypervisorMemoryProtector;
-pub use mmu::PageSize;
+pub use hypervisor_memory_protector::{AccessDuration, HypervisorMemoryProtector, TemporaryGrant};
+pub use mmu::{PageSize, PageTableUsage};
+pub use page_table_walker::{AccessPermissions, GuestTranslation, PageTableWalker};
+pub use tlb::{PendingTlbShootdown, TlbFence, TlbShootdown};
+pub use vmid_allocator::{Vmid, VmidAllocator};
 
+mod accessed_dirty_update;
+#[cfg(feature = "pmp_audit_log")]
+mod audit_log;
 mod confidential_vm_memory_protector;
+mod demand_page_tracker;
 mod hypervisor_memory_protector;
 mod iopmp;
 mod mmu;
+mod page_table_walker;
 mod pmp;
 mod tlb;
+mod vmid_allocator;
@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::Satp;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::ConfidentialVmMemoryProtector;
+use crate::error::Error;
+
+/// Read, write, and execute permissions granted by a page table entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccessPermissions {
+    pub can_read: bool,
+    pub can_write: bool,
+    pub can_execute: bool,
+}
+
+impl AccessPermissions {
+    /// Returns the permissions granted by both stages of the address translation.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            can_read: self.can_read && other.can_read,
+            can_write: self.can_write && other.can_write,
+            can_execute: self.can_execute && other.can_execute,
+        }
+    }
+}
+
+/// The result of the two-stage translation of a guest virtual address.
+pub struct GuestTranslation {
+    guest_physical_address: ConfidentialVmPhysicalAddress,
+    host_physical_address: usize,
+    permissions: AccessPermissions,
+    is_user_page: bool,
+    page_size_in_bytes: usize,
+}
+
+impl GuestTranslation {
+    pub fn guest_physical_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.guest_physical_address
+    }
+
+    pub fn host_physical_address(&self) -> usize {
+        self.host_physical_address
+    }
+
+    /// Returns the permissions granted by both the VS-stage and the G-stage leaf entries.
+    pub fn permissions(&self) -> AccessPermissions {
+        self.permissions
+    }
+
+    /// Returns true if the VS-stage leaf entry grants access to the guest's user mode (the U bit).
+    pub fn is_user_page(&self) -> bool {
+        self.is_user_page
+    }
+
+    /// Returns the size of the page mapped by the VS-stage leaf entry.
+    pub fn page_size_in_bytes(&self) -> usize {
+        self.page_size_in_bytes
+    }
+}
+
+/// Walks the VS-stage page tables of a confidential hart like the hardware does, so that the security monitor can resolve guest virtual
+/// addresses, for example, those passed by the guest to emulated instructions. Page tables are read through the G-stage translation,
+/// thus the walk fails if they are not located in the confidential VM's own memory.
+pub struct PageTableWalker {
+    vsatp: usize,
+}
+
+impl PageTableWalker {
+    const PAGE_SHIFT: usize = 12;
+    const VPN_BITS: usize = 9;
+    const PTE_SIZE_IN_BYTES: usize = 8;
+    const PTE_VALID: usize = 1 << 0;
+    const PTE_READ: usize = 1 << 1;
+    const PTE_WRITE: usize = 1 << 2;
+    const PTE_EXECUTE: usize = 1 << 3;
+    const PTE_USER: usize = 1 << 4;
+    const PTE_PPN_SHIFT: usize = 10;
+    const PTE_PPN_MASK: usize = 0x00000FFFFFFFFFFF;
+
+    pub fn new(vsatp: usize) -> Self {
+        Self { vsatp }
+    }
+
+    /// Translates the guest virtual address into the guest physical and host physical addresses. Returns `Error::PageFault` if
+    /// any level of the VS-stage or the G-stage translation does not map the address, in the same cases in which the hardware would
+    /// raise a page fault.
+    pub fn translate(
+        &self, guest_virtual_address: usize, memory_protector: &ConfidentialVmMemoryProtector,
+    ) -> Result<GuestTranslation, Error> {
+        let levels = Satp::from(self.vsatp).mode().ok_or(Error::PageFault())?.levels();
+        if levels == 0 {
+            // The VS-stage translation is disabled, so guest virtual addresses are guest physical addresses.
+            let guest_physical_address = ConfidentialVmPhysicalAddress::new(guest_virtual_address);
+            let (host_physical_address, permissions) = Self::g_stage_translation(guest_physical_address, memory_protector)?;
+            let page_size_in_bytes = 1 << Self::PAGE_SHIFT;
+            return Ok(GuestTranslation {
+                guest_physical_address,
+                host_physical_address,
+                permissions,
+                is_user_page: true,
+                page_size_in_bytes,
+            });
+        }
+
+        // Addresses are sign-extended from the highest bit translated by the page tables.
+        let translated_bits = Self::PAGE_SHIFT + levels * Self::VPN_BITS;
+        let sign_extension = (guest_virtual_address as isize) >> (translated_bits - 1);
+        assure!(sign_extension == 0 || sign_extension == -1, Error::PageFault())?;
+
+        let mut page_table_address = Satp::from(self.vsatp).address();
+        for level in (0..levels).rev() {
+            let shift = Self::PAGE_SHIFT + level * Self::VPN_BITS;
+            let index = (guest_virtual_address >> shift) & ((1 << Self::VPN_BITS) - 1);
+            let entry_address = ConfidentialVmPhysicalAddress::new(page_table_address + index * Self::PTE_SIZE_IN_BYTES);
+            let entry = memory_protector.read_word(entry_address).map_err(|_| Error::PageFault())?;
+            assure!(entry & Self::PTE_VALID != 0, Error::PageFault())?;
+            // Write-only pages are reserved by the specification.
+            assure!(entry & Self::PTE_READ != 0 || entry & Self::PTE_WRITE == 0, Error::PageFault())?;
+            let ppn = (entry >> Self::PTE_PPN_SHIFT) & Self::PTE_PPN_MASK;
+            if entry & (Self::PTE_READ | Self::PTE_EXECUTE) == 0 {
+                page_table_address = ppn << Self::PAGE_SHIFT;
+                continue;
+            }
+            // A leaf above the lowest level maps a superpage, whose physical address must be aligned to its size.
+            let page_size_in_bytes = 1 << shift;
+            let page_address = ppn << Self::PAGE_SHIFT;
+            assure!(page_address % page_size_in_bytes == 0, Error::PageFault())?;
+            let guest_physical_address =
+                ConfidentialVmPhysicalAddress::new(page_address | (guest_virtual_address & (page_size_in_bytes - 1)));
+            let (host_physical_address, g_stage_permissions) = Self::g_stage_translation(guest_physical_address, memory_protector)?;
+            let vs_stage_permissions = AccessPermissions {
+                can_read: entry & Self::PTE_READ != 0,
+                can_write: entry & Self::PTE_WRITE != 0,
+                can_execute: entry & Self::PTE_EXECUTE != 0,
+            };
+            return Ok(GuestTranslation {
+                guest_physical_address,
+                host_physical_address,
+                permissions: vs_stage_permissions.intersection(&g_stage_permissions),
+                is_user_page: entry & Self::PTE_USER != 0,
+                page_size_in_bytes,
+            });
+        }
+        // The lowest level entry points to yet another page table.
+        Err(Error::PageFault())
+    }
+
+    fn g_stage_translation(
+        guest_physical_address: ConfidentialVmPhysicalAddress, memory_protector: &ConfidentialVmMemoryProtector,
+    ) -> Result<(usize, AccessPermissions), Error> {
+        memory_protector.host_translation(guest_physical_address).map_err(|_| Error::PageFault())
+    }
+}
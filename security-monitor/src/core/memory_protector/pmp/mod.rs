@@ -1,26 +1,40 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{CSR, PMP_ADDRESS_SHIFT, PMP_CONFIG_SHIFT, PMP_OFF_MASK, PMP_PERMISSION_RWX_MASK, PMP_TOR_MASK};
+use crate::core::architecture::{
+    CSR, PMP_ADDRESS_SHIFT, PMP_CONFIG_SHIFT, PMP_LOCK_MASK, PMP_NAPOT_MASK, PMP_OFF_MASK, PMP_PERMISSION_RWX_MASK, PMP_TOR_MASK,
+};
+use crate::core::memory_layout::MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS;
 use crate::error::{Error, HardwareFeatures};
 
 // OpenSBI set already PMPs to isolate OpenSBI firmware from the rest of the
 // system PMP0 protects OpenSBI memory region while PMP1 defines the system
-// range We will use PMP0 and PMP1 to protect the confidential memory region,
-// PMP2 to protect the OpenSBI, and PMP3 to define the system range.
+// range We will use PMP1 and PMP2 to protect the confidential memory region,
+// PMP3 and PMP4 to protect memory regions converted into the confidential
+// memory at runtime, PMP5 to protect the OpenSBI, and PMP6 to define the system
+// range. PMP0 has the highest priority and is used to temporarily grant the
+// hypervisor access to a single confidential page.
+const TEMPORARY_GRANT_PMP_INDEX: usize = 0;
+const CONVERTED_MEMORY_PMP_INDICES: [usize; MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS] = [3, 4];
+// PMP entries that the security monitor reprograms at runtime.
+const SECURITY_MONITOR_PMP_INDICES: [usize; 5] =
+    [TEMPORARY_GRANT_PMP_INDEX, 1, 2, CONVERTED_MEMORY_PMP_INDICES[0], CONVERTED_MEMORY_PMP_INDICES[1]];
+
 pub(super) fn split_memory_into_confidential_and_non_confidential(
     confidential_memory_start: usize, confidential_memory_end: usize,
 ) -> Result<(), Error> {
     // TODO: read how many PMPs are supported
-    const MINIMUM_NUMBER_OF_PMP_REQUIRED: usize = 4;
+    const MINIMUM_NUMBER_OF_PMP_REQUIRED: usize = 7;
     let number_of_pmps = 16;
     debug!("Number of PMPs={}", number_of_pmps);
     assure!(number_of_pmps >= MINIMUM_NUMBER_OF_PMP_REQUIRED, Error::NotSupportedHardware(HardwareFeatures::NotEnoughPmps))?;
 
     // TODO: simplify use of PMP by using a single PMP entry to isolate the confidential memory.
-    // We assume here that the first two PMPs are not used by anyone else, e.g., OpenSBI firmware
-    CSR.pmpaddr0.set(confidential_memory_start >> PMP_ADDRESS_SHIFT);
-    CSR.pmpaddr1.set(confidential_memory_end >> PMP_ADDRESS_SHIFT);
+    // We assume here that the first three PMPs are not used by anyone else, e.g., OpenSBI firmware. Writes to locked entries are
+    // silently ignored by the hardware, so we check that the firmware did not lock them.
+    ensure_entries_not_locked()?;
+    CSR.pmpaddr1.set(confidential_memory_start >> PMP_ADDRESS_SHIFT);
+    CSR.pmpaddr2.set(confidential_memory_end >> PMP_ADDRESS_SHIFT);
 
     close_access_to_confidential_memory();
     crate::debug::__print_pmp_configuration();
@@ -28,20 +42,135 @@ pub(super) fn split_memory_into_confidential_and_non_confidential(
 }
 
 pub fn open_access_to_confidential_memory() {
-    let mask = (PMP_OFF_MASK | PMP_PERMISSION_RWX_MASK) | (PMP_TOR_MASK | PMP_PERMISSION_RWX_MASK) << (1 * PMP_CONFIG_SHIFT);
+    let mask = (PMP_OFF_MASK | PMP_PERMISSION_RWX_MASK) << (1 * PMP_CONFIG_SHIFT)
+        | (PMP_TOR_MASK | PMP_PERMISSION_RWX_MASK) << (2 * PMP_CONFIG_SHIFT)
+        | converted_memory_permission_mask();
     CSR.pmpcfg0.read_and_set_bits(mask);
-    clear_caches();
+    clear_hypervisor_caches();
 }
 
 pub fn close_access_to_confidential_memory() {
-    let mask = PMP_PERMISSION_RWX_MASK | (PMP_PERMISSION_RWX_MASK << (1 * PMP_CONFIG_SHIFT));
+    // Closing the access to the confidential memory also revokes any temporary access grant.
+    let mask = (PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK) << (TEMPORARY_GRANT_PMP_INDEX * PMP_CONFIG_SHIFT)
+        | PMP_PERMISSION_RWX_MASK << (1 * PMP_CONFIG_SHIFT)
+        | PMP_PERMISSION_RWX_MASK << (2 * PMP_CONFIG_SHIFT)
+        | converted_memory_permission_mask();
     CSR.pmpcfg0.read_and_clear_bits(mask);
+    clear_hypervisor_caches();
+}
+
+/// Returns error if the PMP configuration of this hart does not deny accesses to the confidential memory, e.g., because the firmware
+/// locked one of the PMP entries used by the security monitor, so that clearing its permissions had no effect.
+pub fn ensure_access_to_confidential_memory_closed() -> Result<(), Error> {
+    ensure_access_closed(CSR.pmpcfg0.read())
+}
+
+fn ensure_entries_not_locked() -> Result<(), Error> {
+    ensure_not_locked(CSR.pmpcfg0.read())
+}
+
+fn ensure_access_closed(pmpcfg0: usize) -> Result<(), Error> {
+    ensure_not_locked(pmpcfg0)?;
+    let permissions_left =
+        SECURITY_MONITOR_PMP_INDICES.iter().any(|pmp_index| pmpcfg0 & (PMP_PERMISSION_RWX_MASK << (pmp_index * PMP_CONFIG_SHIFT)) != 0);
+    assure_not!(permissions_left, Error::PmpConfigurationFailed())
+}
+
+fn ensure_not_locked(pmpcfg0: usize) -> Result<(), Error> {
+    match SECURITY_MONITOR_PMP_INDICES.iter().find(|pmp_index| pmpcfg0 & (PMP_LOCK_MASK << (**pmp_index * PMP_CONFIG_SHIFT)) != 0) {
+        Some(pmp_index) => Err(Error::PmpEntryLocked(*pmp_index)),
+        None => Ok(()),
+    }
+}
+
+/// Opens access to a single naturally aligned memory region located in the confidential memory. The PMP entry used for this purpose has
+/// a higher priority than the PMP entries protecting the confidential memory.
+pub fn open_temporary_access(address: usize, size_in_bytes: usize) {
+    CSR.pmpaddr0.set(napot_address(address, size_in_bytes));
+    CSR.pmpcfg0.read_and_set_bits((PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK) << (TEMPORARY_GRANT_PMP_INDEX * PMP_CONFIG_SHIFT));
+    clear_caches();
+}
+
+pub fn close_temporary_access() {
+    CSR.pmpcfg0.read_and_clear_bits((PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK) << (TEMPORARY_GRANT_PMP_INDEX * PMP_CONFIG_SHIFT));
+    CSR.pmpaddr0.set(0);
     clear_caches();
 }
 
+/// Configures the PMP entry protecting a memory region that has been converted into the confidential memory at runtime. The region
+/// must be naturally aligned and its size must be a power of two. `None` disables the PMP entry, so that the hypervisor regains access
+/// to the region. The access to the region is opened and closed together with the access to the rest of the confidential memory.
+pub fn configure_converted_memory_region(slot: usize, region: Option<(usize, usize)>) {
+    let pmp_index = CONVERTED_MEMORY_PMP_INDICES[slot];
+    let pmp_address = region.map(|(address, size_in_bytes)| napot_address(address, size_in_bytes)).unwrap_or(0);
+    match pmp_index {
+        3 => CSR.pmpaddr3.set(pmp_address),
+        4 => CSR.pmpaddr4.set(pmp_address),
+        _ => panic!("Bug: PMP entry {} is not reserved for converted memory regions", pmp_index),
+    }
+    match region {
+        Some(_) => CSR.pmpcfg0.read_and_set_bits(PMP_NAPOT_MASK << (pmp_index * PMP_CONFIG_SHIFT)),
+        None => CSR.pmpcfg0.read_and_clear_bits((PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK) << (pmp_index * PMP_CONFIG_SHIFT)),
+    };
+    clear_caches();
+}
+
+fn converted_memory_permission_mask() -> usize {
+    CONVERTED_MEMORY_PMP_INDICES.iter().fold(0, |mask, pmp_index| mask | PMP_PERMISSION_RWX_MASK << (pmp_index * PMP_CONFIG_SHIFT))
+}
+
+fn napot_address(address: usize, size_in_bytes: usize) -> usize {
+    assert!(size_in_bytes.is_power_of_two() && address % size_in_bytes == 0);
+    // NAPOT encoding of the region, see Section 3.7.1 of RISC-V privileged specification v1.12.
+    (address >> PMP_ADDRESS_SHIFT) | ((size_in_bytes >> 3) - 1)
+}
+
+/// Fences the hypervisor's own address translations after the access to the confidential memory has been opened or closed, which
+/// happens whenever the hart switches between the hypervisor and a confidential VM. G-stage translations are fenced by the caller only
+/// for the VMID of the confidential VM, see `tlb::fence_domain_switch`.
+fn clear_hypervisor_caches() {
+    // See Section 3.7.2 of RISC-V privileged specification v1.12.
+    crate::core::architecture::sfence_vma();
+}
+
 fn clear_caches() {
     // See Section 3.7.2 of RISC-V privileged specification v1.12.
     // PMP translations can be cached and address translation can be done speculatively. Thus, it is adviced to flush caching structures.
     crate::core::architecture::sfence_vma();
     crate::core::architecture::hfence_gvma();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pmp_config(pmp_index: usize, config: usize) -> usize {
+        config << (pmp_index * PMP_CONFIG_SHIFT)
+    }
+
+    #[test]
+    fn closed_access_is_accepted() {
+        let pmpcfg0 = pmp_config(1, PMP_OFF_MASK) | pmp_config(2, PMP_TOR_MASK);
+        assert!(ensure_access_closed(pmpcfg0).is_ok());
+    }
+
+    #[test]
+    fn locked_entry_is_detected() {
+        let pmpcfg0 = pmp_config(2, PMP_TOR_MASK | PMP_LOCK_MASK);
+        assert!(matches!(ensure_access_closed(pmpcfg0), Err(Error::PmpEntryLocked(2))));
+        assert!(matches!(ensure_not_locked(pmpcfg0), Err(Error::PmpEntryLocked(2))));
+    }
+
+    #[test]
+    fn permissive_entry_is_detected() {
+        let pmpcfg0 = pmp_config(CONVERTED_MEMORY_PMP_INDICES[1], PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK);
+        assert!(matches!(ensure_access_closed(pmpcfg0), Err(Error::PmpConfigurationFailed())));
+    }
+
+    #[test]
+    fn entries_of_firmware_are_ignored() {
+        // The firmware configures the entries following the ones used by the security monitor.
+        let pmpcfg0 = pmp_config(5, PMP_NAPOT_MASK | PMP_PERMISSION_RWX_MASK | PMP_LOCK_MASK);
+        assert!(ensure_access_closed(pmpcfg0).is_ok());
+    }
+}
@@ -1,9 +1,224 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::CSR;
+use crate::core::interrupt_controller::InterruptController;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::{PageSize, Vmid};
+use crate::error::Error;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use spin::Once;
+
+static FENCE_COUNTERS: [AtomicUsize; TlbFence::NUMBER_OF_FENCE_TYPES] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+static ARE_NARROW_FENCES_ENABLED: AtomicBool = AtomicBool::new(true);
+static IS_SVINVAL_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Counts TLB shootdowns requested since the boot, see `TlbShootdown::request`.
+static SHOOTDOWN_EPOCH: AtomicUsize = AtomicUsize::new(0);
+/// Tracks which harts execute confidential harts and which TLB shootdowns they acknowledged, indexed by the hart id.
+static HART_SHOOTDOWN_STATES: Once<Vec<HartShootdownState>> = Once::new();
+
+/// The forms of the `hfence.gvma` instruction used by the security monitor, from the broadest to the narrowest one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TlbFence {
+    // Fences translations of all address spaces, required whenever the PMP configuration changes.
+    Global = 0,
+    // Fences all translations of one confidential VM.
+    Vmid = 1,
+    // Fences translations of one guest physical address of one confidential VM.
+    GuestPhysicalAddress = 2,
+}
+
+impl TlbFence {
+    pub const NUMBER_OF_FENCE_TYPES: usize = 3;
+
+    /// Makes all fences global. Called during the boot on processors that do not correctly implement `hfence.gvma` with the address
+    /// or the VMID operand.
+    pub fn disable_narrow_fences() {
+        ARE_NARROW_FENCES_ENABLED.store(false, Ordering::Release);
+    }
+
+    /// Batches invalidations of several guest physical addresses with the Svinval extension. Called during the boot if all harts
+    /// implement the extension.
+    pub fn enable_svinval() {
+        IS_SVINVAL_ENABLED.store(true, Ordering::Release);
+    }
+
+    pub fn is_svinval_enabled() -> bool {
+        IS_SVINVAL_ENABLED.load(Ordering::Acquire) && ARE_NARROW_FENCES_ENABLED.load(Ordering::Acquire)
+    }
+
+    /// Returns how many fences of every type all harts executed since the boot, indexed by the fence type. Fences downgraded to global
+    /// fences, because narrow fences are disabled or the confidential VM has no VMID, count as global fences.
+    pub fn counters() -> [usize; Self::NUMBER_OF_FENCE_TYPES] {
+        [Self::Global, Self::Vmid, Self::GuestPhysicalAddress].map(|fence| FENCE_COUNTERS[fence as usize].load(Ordering::Relaxed))
+    }
+
+    fn count(self) {
+        FENCE_COUNTERS[self as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct HartShootdownState {
+    // Set while the hart executes a confidential hart, i.e., while the hardware can use G-stage translations of confidential VMs.
+    is_executing_confidential_hart: AtomicBool,
+    // The last shootdown epoch, for which the hart fenced its translations.
+    acknowledged_epoch: AtomicUsize,
+}
+
+/// Fences G-stage translations on harts executing confidential harts when another hart changes the ownership of confidential memory.
+///
+/// A hart caches translations of a confidential VM only while it executes one of its confidential harts, because translations of the
+/// confidential VM are fenced whenever the hart switches to the hypervisor. Thus, a shootdown interrupts only harts executing
+/// confidential harts and waits until they trap into the security monitor. All other harts acknowledge the shootdown by fencing their
+/// translations before they execute a confidential hart again.
+pub struct TlbShootdown {}
+
+impl TlbShootdown {
+    /// Prepares tracking of shootdowns on all harts. Must be called during the boot, before any hart executes a confidential hart.
+    pub fn initialize(number_of_harts: usize) {
+        HART_SHOOTDOWN_STATES.call_once(|| (0..number_of_harts).map(|_| HartShootdownState::default()).collect());
+    }
+
+    /// Starts fencing G-stage translations of all address spaces on all harts. This hart fences its translations immediately, and every
+    /// hart fences them before it executes a confidential hart again, so VMIDs can be reassigned as soon as this function returns. Only
+    /// harts executing confidential harts at this moment might still use translations cached before the call. The returned shootdown
+    /// must be completed before the caller gives memory to another owner.
+    pub fn request() -> PendingTlbShootdown {
+        let epoch = SHOOTDOWN_EPOCH.fetch_add(1, Ordering::SeqCst) + 1;
+        tlb_shutdown();
+        PendingTlbShootdown { epoch }
+    }
+
+    /// Registers that the hart is about to execute a confidential hart. Fences all translations cached by the hart if it has not
+    /// acknowledged the latest shootdown.
+    ///
+    /// The hart is registered before the shootdown epoch is read, so that the hart requesting a shootdown either observes this hart as
+    /// executing a confidential hart and waits for it, or this hart observes the new epoch and fences its translations.
+    pub fn enter_confidential_hart(hart_id: usize) {
+        let state = Self::hart_state(hart_id);
+        state.is_executing_confidential_hart.store(true, Ordering::SeqCst);
+        let epoch = SHOOTDOWN_EPOCH.load(Ordering::SeqCst);
+        if state.acknowledged_epoch.load(Ordering::SeqCst) < epoch {
+            tlb_shutdown();
+            state.acknowledged_epoch.store(epoch, Ordering::SeqCst);
+        }
+    }
+
+    /// Registers that the hart stopped executing a confidential hart because it trapped into the security monitor. Code of the
+    /// security monitor does not use G-stage translations, so the hart does not have to acknowledge shootdowns until it executes a
+    /// confidential hart again.
+    pub fn exit_confidential_hart(hart_id: usize) {
+        Self::hart_state(hart_id).is_executing_confidential_hart.store(false, Ordering::SeqCst);
+    }
+
+    fn hart_state(hart_id: usize) -> &'static HartShootdownState {
+        HART_SHOOTDOWN_STATES.get().and_then(|states| states.get(hart_id)).expect("Bug: TLB shootdowns are not initialized for the hart")
+    }
+}
 
 pub fn tlb_shutdown() {
-    // TODO: implement TLB shutdown for a processor composed of multiple HARTs
+    TlbFence::Global.count();
     crate::core::architecture::hfence_gvma();
     crate::core::architecture::hfence_vvma();
 }
+
+/// A TLB shootdown requested with `TlbShootdown::request` that harts executing confidential harts might not have acknowledged yet.
+#[derive(Clone, Copy, Debug)]
+pub struct PendingTlbShootdown {
+    epoch: usize,
+}
+
+impl PendingTlbShootdown {
+    /// Interrupts harts executing confidential harts with an IPI and spins until they trap into the security monitor. When this
+    /// function returns, no hart uses a translation cached before the shootdown was requested. Completing an already completed shootdown
+    /// returns immediately. Returns error if an IPI could not be sent, in which case the shootdown must be completed again later.
+    ///
+    /// The caller must not hold locks of the control data, so that other harts are not blocked while this hart waits. The mscratch
+    /// register must contain the value expected by OpenSBI because IPIs are sent using OpenSBI.
+    pub fn complete(&self) -> Result<(), Error> {
+        let this_hart_id = CSR.mhartid.read();
+        let states = HART_SHOOTDOWN_STATES.get().expect("Bug: TLB shootdowns are not initialized");
+        let is_pending = |state: &HartShootdownState| {
+            state.is_executing_confidential_hart.load(Ordering::SeqCst) && state.acknowledged_epoch.load(Ordering::SeqCst) < self.epoch
+        };
+        states
+            .iter()
+            .enumerate()
+            .filter(|(hart_id, state)| *hart_id != this_hart_id && is_pending(state))
+            .try_for_each(|(hart_id, _)| InterruptController::try_read(|interrupt_controller| interrupt_controller.send_ipi(hart_id)))?;
+        states.iter().enumerate().filter(|(hart_id, _)| *hart_id != this_hart_id).for_each(|(_, state)| {
+            while is_pending(state) {
+                core::hint::spin_loop();
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Fences translations tagged with the VMID of the confidential VM when this hart starts or stops executing the confidential VM, so
+/// translations of other VMIDs stay cached across the switch. The hypervisor sets VMIDs of its own VMs, so it can tag translations with
+/// the confidential VM's VMID. These translations are fenced when the confidential VM starts executing, and the confidential VM's
+/// translations, cached while the confidential memory was accessible, are fenced when it stops executing. Must be called while hgatp
+/// holds the confidential VM's VMID, because VS-stage translations are fenced for the VMID in hgatp. Without the VMID, all translations
+/// are fenced.
+pub fn fence_domain_switch(vmid: Option<&Vmid>) {
+    match vmid.filter(|_| ARE_NARROW_FENCES_ENABLED.load(Ordering::Acquire)) {
+        Some(vmid) => {
+            TlbFence::Vmid.count();
+            crate::core::architecture::hfence_vvma();
+            crate::core::architecture::hfence_gvma_vmid(vmid.value().into());
+        }
+        None => tlb_shutdown(),
+    }
+}
+
+/// Fences all G-stage translations cached on behalf of the confidential VM. Required after changes to non-leaf page table entries.
+pub fn fence_confidential_vm(vmid: Option<&Vmid>) {
+    match vmid.filter(|_| ARE_NARROW_FENCES_ENABLED.load(Ordering::Acquire)) {
+        Some(vmid) => {
+            TlbFence::Vmid.count();
+            crate::core::architecture::hfence_gvma_vmid(vmid.value().into());
+        }
+        None => tlb_shutdown(),
+    }
+}
+
+/// Fences G-stage translations of the page mapped at the guest physical address after a change of its leaf page table entry. Pages
+/// larger than 4KiB might have been cached as several smaller translations, so for them all translations of the confidential VM are
+/// fenced.
+pub fn fence_guest_physical_address(address: ConfidentialVmPhysicalAddress, page_size: PageSize, vmid: Option<&Vmid>) {
+    match vmid.filter(|_| page_size == PageSize::Size4KiB && ARE_NARROW_FENCES_ENABLED.load(Ordering::Acquire)) {
+        Some(vmid) => {
+            TlbFence::GuestPhysicalAddress.count();
+            crate::core::architecture::hfence_gvma_gpa_vmid(address.usize(), vmid.value().into());
+        }
+        None => fence_confidential_vm(vmid),
+    }
+}
+
+/// Fences G-stage translations of several memory regions of the confidential VM after changes of their leaf page table entries. With
+/// the Svinval extension, every 4KiB page of the regions is invalidated separately, all within a single pair of ordering fences, and
+/// each invalidation counts as a guest physical address fence. Otherwise, or if the regions are too large to be invalidated page by
+/// page, the translations are fenced like by `fence_guest_physical_address`.
+pub fn fence_guest_physical_addresses(regions: &[(ConfidentialVmPhysicalAddress, PageSize)], vmid: Option<&Vmid>) {
+    // Invalidating a 2MiB region page by page is still cheaper than refilling the TLB with translations of the whole confidential VM.
+    const MAX_INVALIDATIONS: usize = 512;
+    let number_of_invalidations: usize = regions.iter().map(|(_, page_size)| page_size.in_bytes() / PageSize::Size4KiB.in_bytes()).sum();
+    match (regions, vmid.filter(|_| TlbFence::is_svinval_enabled() && number_of_invalidations <= MAX_INVALIDATIONS)) {
+        ([], _) => {}
+        (regions, Some(vmid)) => {
+            crate::core::architecture::sfence_w_inval();
+            regions.iter().for_each(|(address, page_size)| {
+                (0..page_size.in_bytes()).step_by(PageSize::Size4KiB.in_bytes()).for_each(|offset_in_bytes| {
+                    TlbFence::GuestPhysicalAddress.count();
+                    crate::core::architecture::hinval_gvma_gpa_vmid(address.usize() + offset_in_bytes, vmid.value().into());
+                });
+            });
+            crate::core::architecture::sfence_inval_ir();
+        }
+        ([(address, page_size)], vmid) => fence_guest_physical_address(*address, *page_size, vmid),
+        (_, vmid) => fence_confidential_vm(vmid),
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use alloc::vec::Vec;
+
+/// A VMID tags G-stage address translations cached by the hardware. It is valid only in the generation of the allocator in which it
+/// was assigned, because VMIDs are reassigned from scratch when all of them are in use.
+#[derive(Clone, Copy, Debug)]
+pub struct Vmid {
+    value: u16,
+    generation: usize,
+}
+
+impl Vmid {
+    pub fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+/// Assigns VMIDs to confidential VMs. The hardware implements only VMIDLEN bits of the VMID, so there might be more confidential VMs
+/// than VMIDs. When all VMIDs of the current generation are assigned, the allocator flushes the G-stage translations and starts a new
+/// generation in which all VMIDs are free again. Confidential VMs holding VMIDs of an older generation get a new VMID the next time one
+/// of their harts executes.
+///
+/// A physical hart fences translations tagged with the confidential VM's VMID when it starts and stops executing the confidential VM
+/// (see `tlb::fence_domain_switch`), so translations of the hypervisor and of other confidential VMs stay cached across switches. VMIDs
+/// are reassigned only after a TLB shootdown has been requested, so that every hart fences its translations before it executes a
+/// confidential hart with a reassigned VMID (see `TlbShootdown::request`).
+///
+/// If the hardware does not implement VMIDs, all confidential VMs and the hypervisor's VMs use the VMID 0. Confidential VMs then hold no
+/// VMID and all G-stage translations are fenced whenever a hart starts or stops executing a confidential VM.
+pub struct VmidAllocator {
+    number_of_vmids: usize,
+    generation: usize,
+    next_vmid: usize,
+    // VMIDs of the current generation returned by terminated confidential VMs.
+    released_vmids: Vec<u16>,
+}
+
+impl VmidAllocator {
+    pub fn empty() -> Self {
+        Self { number_of_vmids: 1, generation: 0, next_vmid: 0, released_vmids: Vec::new() }
+    }
+
+    /// Sizes the VMID space after the number of VMID bits implemented by the hardware.
+    pub fn initialize(&mut self, vmid_length: usize) {
+        self.number_of_vmids = 1 << vmid_length;
+        self.next_vmid = 0;
+        self.released_vmids.clear();
+    }
+
+    /// Returns a VMID of the current generation, starting a new generation if all VMIDs are in use. Returns `None` if the hardware does
+    /// not implement VMIDs, in which case the confidential VM shares the VMID 0 with all other VMs. Starting a new generation requests
+    /// a TLB shootdown, see `flush_and_reset`.
+    pub fn alloc(&mut self) -> Option<Vmid> {
+        if self.number_of_vmids <= 1 {
+            return None;
+        }
+        if let Some(value) = self.released_vmids.pop() {
+            return Some(Vmid { value, generation: self.generation });
+        }
+        if self.next_vmid == self.number_of_vmids {
+            self.flush_and_reset();
+        }
+        let value = self.next_vmid as u16;
+        self.next_vmid += 1;
+        Some(Vmid { value, generation: self.generation })
+    }
+
+    /// Returns the VMID to the pool. The caller must fence the address translation caches tagged with this VMID beforehand.
+    pub fn free(&mut self, vmid: Vmid) {
+        if vmid.generation == self.generation {
+            self.released_vmids.push(vmid.value);
+        }
+    }
+
+    /// Assigns a new VMID if the given one belongs to an older generation. Returns true if the VMID changed.
+    pub fn refresh(&mut self, vmid: &mut Vmid) -> bool {
+        if vmid.generation == self.generation {
+            return false;
+        }
+        self.alloc().map(|new_vmid| *vmid = new_vmid).is_some()
+    }
+
+    /// Requests fencing G-stage translations of all VMIDs on all harts and starts a new generation in which all VMIDs are free. A hart
+    /// executing a confidential VM of the previous generation keeps using only that confidential VM's translations, because they were
+    /// fenced when the hart started executing it, so this function does not wait for such harts.
+    pub fn flush_and_reset(&mut self) {
+        super::TlbShootdown::request();
+        self.generation = self.generation.wrapping_add(1);
+        self.next_vmid = 0;
+        self.released_vmids.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocator(vmid_length: usize) -> VmidAllocator {
+        let mut allocator = VmidAllocator::empty();
+        allocator.initialize(vmid_length);
+        allocator
+    }
+
+    #[test]
+    fn no_vmids_without_hardware_support() {
+        let mut allocator = allocator(0);
+        assert!(allocator.alloc().is_none());
+        assert!(allocator.alloc().is_none());
+    }
+
+    #[test]
+    fn vmids_are_distinct() {
+        let mut allocator = allocator(2);
+        let values: Vec<u16> = (0..4).map(|_| allocator.alloc().unwrap().value()).collect();
+        assert_eq!(values, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn released_vmid_is_reused() {
+        let mut allocator = allocator(2);
+        let vmid = allocator.alloc().unwrap();
+        allocator.alloc().unwrap();
+        allocator.free(vmid);
+        assert_eq!(allocator.alloc().unwrap().value(), vmid.value());
+    }
+
+    #[test]
+    fn vmids_are_reassigned_in_new_generation() {
+        let mut allocator = allocator(1);
+        let mut vmid = allocator.alloc().unwrap();
+        allocator.alloc().unwrap();
+        // All VMIDs are in use, so the next confidential VM starts a new generation.
+        assert_eq!(allocator.alloc().unwrap().value(), 0);
+        assert!(allocator.refresh(&mut vmid));
+        assert_eq!(vmid.value(), 1);
+        // VMIDs of the previous generation are not returned to the pool.
+        allocator.free(Vmid { value: 0, generation: 0 });
+        assert!(allocator.released_vmids.is_empty());
+    }
+
+    #[test]
+    fn vmid_of_current_generation_is_not_refreshed() {
+        let mut allocator = allocator(1);
+        let mut vmid = allocator.alloc().unwrap();
+        assert!(!allocator.refresh(&mut vmid));
+        assert_eq!(vmid.value(), 0);
+    }
+}
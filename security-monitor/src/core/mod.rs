@@ -2,7 +2,14 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 pub mod architecture;
+pub mod attestation;
 pub mod control_data;
+pub mod crypto;
+#[cfg(feature = "declassification_log")]
+pub mod declassification_log;
+pub mod entropy;
+pub mod measurement;
+pub mod memory_encryption;
 pub mod memory_layout;
 pub mod memory_protector;
 pub mod page_allocator;
@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
+
+#[cfg(feature = "memory-encryption")]
+extern "C" {
+    // Functions provided by the platform code that drives the memory encryption engine (e.g., an IOPMP with encryption support or a
+    // vendor-specific engine). They transform the memory region in place and return 0 on success.
+    fn ace_platform_encrypt_memory(address: usize, size_in_bytes: usize, key: *const u8) -> isize;
+    fn ace_platform_decrypt_memory(address: usize, size_in_bytes: usize, key: *const u8) -> isize;
+    // Returns a non-zero value if the engine keeps the content of the DRAM confidential while the system is suspended to RAM.
+    fn ace_platform_is_memory_confidential_during_suspend() -> isize;
+}
+
+/// Encrypts in place the memory region of the given size starting at the given address.
+#[cfg(feature = "memory-encryption")]
+pub(super) fn encrypt(address: usize, size_in_bytes: usize, key: &[u8; 32]) -> Result<(), Error> {
+    // Safety: the caller owns the memory region because it is the memory of a page token.
+    let result = unsafe { ace_platform_encrypt_memory(address, size_in_bytes, key.as_ptr()) };
+    assure!(result == 0, Error::MemoryEncryptionFailed())
+}
+
+/// Decrypts in place the memory region of the given size starting at the given address.
+#[cfg(feature = "memory-encryption")]
+pub(super) fn decrypt(address: usize, size_in_bytes: usize, key: &[u8; 32]) -> Result<(), Error> {
+    // Safety: the caller owns the memory region because it is the memory of a page token.
+    let result = unsafe { ace_platform_decrypt_memory(address, size_in_bytes, key.as_ptr()) };
+    assure!(result == 0, Error::MemoryEncryptionFailed())
+}
+
+/// Returns true if the platform guarantees that the content of the DRAM stays confidential while the system is suspended to RAM, for
+/// example, because the memory encryption engine keeps the DRAM encrypted and its keys are never exposed outside of the chip.
+#[cfg(feature = "memory-encryption")]
+pub fn is_memory_confidential_during_suspend() -> bool {
+    // Safety: the function does not take any arguments and only reports the capability of the platform.
+    unsafe { ace_platform_is_memory_confidential_during_suspend() != 0 }
+}
+
+/// Without a memory encryption engine, the content of the DRAM is in plaintext while the system is suspended.
+#[cfg(not(feature = "memory-encryption"))]
+pub fn is_memory_confidential_during_suspend() -> bool {
+    false
+}
+
+/// Platforms without a memory encryption engine keep the content of pages in plaintext.
+#[cfg(not(feature = "memory-encryption"))]
+pub(super) fn encrypt(_address: usize, _size_in_bytes: usize, _key: &[u8; 32]) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(not(feature = "memory-encryption"))]
+pub(super) fn decrypt(_address: usize, _size_in_bytes: usize, _key: &[u8; 32]) -> Result<(), Error> {
+    Ok(())
+}
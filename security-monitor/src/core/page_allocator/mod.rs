@@ -1,10 +1,16 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-pub use page::{Allocated, Page, UnAllocated};
+pub use memory_encryption::is_memory_confidential_during_suspend;
+pub use page::{Allocated, Encrypted, Page, UnAllocated};
+#[cfg(test)]
+pub(crate) use page_allocator::tests as test_memory;
 pub use page_allocator::PageAllocator;
+pub use page_guard::PageGuard;
 pub use shared_page::SharedPage;
 
+mod memory_encryption;
 mod page;
 mod page_allocator;
+mod page_guard;
 mod shared_page;
@@ -1,6 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use super::memory_encryption;
 use crate::core::memory_layout::{ConfidentialMemoryAddress, MemoryLayout, NonConfidentialMemoryAddress};
 use crate::core::memory_protector::PageSize;
 use crate::error::Error;
@@ -13,9 +14,14 @@ pub trait PageState {}
 
 pub enum UnAllocated {}
 pub enum Allocated {}
+pub enum Encrypted {}
+/// The content of the page has been cleared and not written since, so the page can be allocated without clearing it again.
+pub enum Zeroed {}
 
 impl PageState for UnAllocated {}
 impl PageState for Allocated {}
+impl PageState for Encrypted {}
+impl PageState for Zeroed {}
 
 #[derive(Debug)]
 pub struct Page<S: PageState> {
@@ -47,50 +53,124 @@ impl Page<UnAllocated> {
         Page { address: self.address, size: self.size, _marker: PhantomData }
     }
 
-    /// Moves a page to the Allocated state after filling its content with the
-    /// content of a page located in the non-confidential memory.
-    pub fn copy_from_non_confidential_memory(mut self, mut address: NonConfidentialMemoryAddress) -> Result<Page<Allocated>, Error> {
+    /// Fills the page with the content of a page located in the non-confidential memory. The page stays in the UnAllocated state, so
+    /// that its owner can return it to the page allocator if the copy fails halfway (see `PageGuard`).
+    pub(super) fn copy_from_non_confidential_memory(&mut self, mut address: NonConfidentialMemoryAddress) -> Result<(), Error> {
         self.offsets().into_iter().try_for_each(|offset_in_bytes| {
             let non_confidential_address = MemoryLayout::read().non_confidential_address_at_offset(&mut address, offset_in_bytes)?;
             // TODO: describe why below unsafe block is safe in this invocation.
             let data_to_copy = unsafe { non_confidential_address.read() };
             self.write(offset_in_bytes, data_to_copy)?;
             Ok::<(), Error>(())
-        })?;
-        Ok(Page { address: self.address, size: self.size, _marker: PhantomData })
+        })
+    }
+
+    /// Moves a page, which content has been entirely initialized by `copy_from_non_confidential_memory`, to the Allocated state.
+    pub(super) fn into_copied(self) -> Page<Allocated> {
+        Page { address: self.address, size: self.size, _marker: PhantomData }
     }
 
     /// Returns a collection of all smaller pages that fit within the current page and
     /// are correctly alligned. If this page is the smallest page (4KiB for RISC-V), then
     /// the same page is returned.
-    pub fn divide(mut self) -> Vec<Page<UnAllocated>> {
-        let memory_layout = MemoryLayout::read();
-        let smaller_page_size = self.size.smaller().unwrap_or(self.size);
-        let number_of_smaller_pages = self.size.in_bytes() / smaller_page_size.in_bytes();
-        let page_end = self.end_address_ptr();
-        (0..number_of_smaller_pages)
-            .map(|i| {
-                let offset_in_bytes = i * smaller_page_size.in_bytes();
-                // Safety: below unwrap is safe because a size of a larger page is a
-                // multiply of a smaller page size, thus we will never exceed the outer page boundary.
-                let smaller_page_start =
-                    memory_layout.confidential_address_at_offset_bounded(&mut self.address, offset_in_bytes, page_end).unwrap();
-                // Safety: The below token creation is safe because the current page owns the entire memory
-                // associated with the page and within this function it partitions this memory into smaller
-                // disjoined pages, passing the ownership to these smaller memory regions to new tokens.
-                unsafe { Page::init(smaller_page_start, smaller_page_size) }
-            })
-            .collect()
+    pub fn divide(self) -> Vec<Page<UnAllocated>> {
+        self.into_smaller_pages()
     }
 }
 
 impl Page<Allocated> {
-    /// Clears the entire memory content by writing 0s to it and then converts the Page from Allocated to UnAllocated so it can be returned
+    /// The number of smaller pages into which `split` and `split_gigapage` break a page.
+    pub const PAGES_PER_SPLIT: usize = 512;
+
+    /// Clears the entire memory content by writing 0s to it and then converts the Page from Allocated to Zeroed so it can be returned
     /// to the page allocator.
-    pub fn deallocate(mut self) -> Page<UnAllocated> {
+    pub fn deallocate(mut self) -> Page<Zeroed> {
         self.clear();
         Page { address: self.address, size: self.size, _marker: PhantomData }
     }
+
+    /// Returns all smaller pages that fit within the current page, like `Page<UnAllocated>::divide`. The content of the memory is
+    /// preserved, so the smaller pages can replace a huge page in the confidential VM's address space.
+    pub fn divide(self) -> Vec<Page<Allocated>> {
+        self.into_smaller_pages()
+    }
+
+    /// Splits a 2MiB page into 4KiB pages, so that parts of a huge mapping, e.g., a page that the confidential VM shares with the
+    /// hypervisor, can be remapped independently. The content of the memory is preserved. Page tokens live outside of the memory they
+    /// describe, so the smaller pages need no bookkeeping to be initialized. The caller owns the page, thus no concurrent allocation can
+    /// observe it partially split, and it returns the smaller pages it does not need with `PageAllocator::release_page`. A page of any
+    /// other size is handed back unchanged.
+    pub fn split(self) -> Result<[Page<Allocated>; Self::PAGES_PER_SPLIT], Page<Allocated>> {
+        self.split_page_of_size(PageSize::Size2MiB)
+    }
+
+    /// Splits a 1GiB page into 2MiB pages, see `split`.
+    pub fn split_gigapage(self) -> Result<[Page<Allocated>; Self::PAGES_PER_SPLIT], Page<Allocated>> {
+        self.split_page_of_size(PageSize::Size1GiB)
+    }
+
+    fn split_page_of_size(self, page_size: PageSize) -> Result<[Page<Allocated>; Self::PAGES_PER_SPLIT], Page<Allocated>> {
+        if self.size != page_size {
+            return Err(self);
+        }
+        let pages: Vec<Page<Allocated>> = self.into_smaller_pages();
+        // The conversion never fails because every page that can be split is exactly `PAGES_PER_SPLIT` times larger than the next
+        // smaller page size.
+        Ok(pages.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+
+    /// Merges the pages into a single page of the next larger size, the inverse of `divide`. The content of the memory is preserved.
+    /// The pages must be of equal size, physically contiguous, ordered by their addresses, and together fill exactly one page of the
+    /// larger size at an address aligned to that size. Otherwise, the pages are handed back to the caller unchanged.
+    pub fn merge(mut pages: Vec<Page<Allocated>>) -> Result<Page<Allocated>, Vec<Page<Allocated>>> {
+        if pages.is_empty() {
+            return Err(pages);
+        }
+        let smaller_page_size = pages[0].size;
+        let larger_page_size = match smaller_page_size.larger() {
+            Some(page_size) => page_size,
+            None => return Err(pages),
+        };
+        let start_address = pages[0].start_address();
+        let is_mergeable = pages.len() == larger_page_size.in_bytes() / smaller_page_size.in_bytes()
+            && start_address % larger_page_size.in_bytes() == 0
+            && pages.iter().enumerate().all(|(i, page)| {
+                page.size == smaller_page_size && page.start_address() == start_address + i * smaller_page_size.in_bytes()
+            });
+        if !is_mergeable {
+            return Err(pages);
+        }
+        // Safety: the pages own disjoint parts that together cover the entire memory region of the larger page. Their tokens are
+        // consumed here, so the ownership of the whole region passes to the single token of the larger page.
+        let first_page = pages.swap_remove(0);
+        Ok(Page { address: first_page.address, size: larger_page_size, _marker: PhantomData })
+    }
+
+    /// Encrypts the content of the page in DRAM with the given key. The page stays encrypted until it is decrypted with the same key.
+    /// Without the `memory-encryption` feature, the content is kept in plaintext and only the state of the page changes.
+    pub fn encrypt(self, key: &[u8; 32]) -> Result<Page<Encrypted>, Error> {
+        memory_encryption::encrypt(self.start_address(), self.size.in_bytes(), key)?;
+        Ok(Page { address: self.address, size: self.size, _marker: PhantomData })
+    }
+}
+
+impl Page<Encrypted> {
+    /// Decrypts the content of the page with the given key, so that the security monitor can operate on the plaintext.
+    pub fn decrypt(self, key: &[u8; 32]) -> Result<Page<Allocated>, Error> {
+        memory_encryption::decrypt(self.start_address(), self.size.in_bytes(), key)?;
+        Ok(Page { address: self.address, size: self.size, _marker: PhantomData })
+    }
+}
+
+impl Page<Zeroed> {
+    /// Hands out the page without clearing it, because no one wrote to it since it was cleared.
+    pub(super) fn allocate(self) -> Page<Allocated> {
+        Page { address: self.address, size: self.size, _marker: PhantomData }
+    }
+
+    pub(super) fn into_unallocated(self) -> Page<UnAllocated> {
+        Page { address: self.address, size: self.size, _marker: PhantomData }
+    }
 }
 
 impl<T: PageState> Page<T> {
@@ -152,11 +232,44 @@ impl<T: PageState> Page<T> {
         Ok(())
     }
 
+    /// Atomically sets the bits of the mask in the data at the given offset, see `write`. Returns the previous value of the data.
+    pub fn fetch_or(&mut self, offset_in_bytes: usize, mask: usize) -> Result<usize, Error> {
+        assure!(offset_in_bytes % mem::size_of::<usize>() == 0, Error::MemoryAccessAuthorization())?;
+        let previous_value = unsafe {
+            // Safety: the pointer is within the page boundary, see `write`. It is aligned to the size of usize because both the page
+            // and the offset are.
+            let pointer = self.address.add(offset_in_bytes, self.end_address_ptr())?;
+            pointer.fetch_or(mask)
+        };
+        Ok(previous_value)
+    }
+
     /// Returns all usize-aligned offsets within the page.
     fn offsets(&self) -> core::iter::StepBy<Range<usize>> {
         (0..self.size.in_bytes()).step_by(mem::size_of::<usize>())
     }
 
+    /// Partitions the page into all smaller pages that fit within it, passing them the ownership of the memory.
+    fn into_smaller_pages<S: PageState>(mut self) -> Vec<Page<S>> {
+        let memory_layout = MemoryLayout::read();
+        let smaller_page_size = self.size.smaller().unwrap_or(self.size);
+        let number_of_smaller_pages = self.size.in_bytes() / smaller_page_size.in_bytes();
+        let page_end = self.end_address_ptr();
+        (0..number_of_smaller_pages)
+            .map(|i| {
+                let offset_in_bytes = i * smaller_page_size.in_bytes();
+                // Safety: below unwrap is safe because a size of a larger page is a
+                // multiply of a smaller page size, thus we will never exceed the outer page boundary.
+                let smaller_page_start =
+                    memory_layout.confidential_address_at_offset_bounded(&mut self.address, offset_in_bytes, page_end).unwrap();
+                // Safety: The below token creation is safe because the current page owns the entire memory
+                // associated with the page and within this function it partitions this memory into smaller
+                // disjoined pages, passing the ownership to these smaller memory regions to new tokens.
+                Page { address: smaller_page_start, size: smaller_page_size, _marker: PhantomData }
+            })
+            .collect()
+    }
+
     fn end_address_ptr(&self) -> *const usize {
         self.end_address() as *const usize
     }
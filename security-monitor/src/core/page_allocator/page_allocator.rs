@@ -1,14 +1,14 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use super::page::{Page, UnAllocated};
+use super::page::{Allocated, Page, UnAllocated, Zeroed};
 use crate::core::memory_layout::{ConfidentialMemoryAddress, MemoryLayout};
 use crate::core::memory_protector::PageSize;
 use crate::error::Error;
 use alloc::collections::BTreeMap;
 use alloc::vec;
 use alloc::vec::Vec;
-use spin::{Once, RwLock, RwLockWriteGuard};
+use spin::{Once, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// A static global structure containing unallocated pages. Once<> guarantees that the PageAllocator can only be initialized once.
 static PAGE_ALLOCATOR: Once<RwLock<PageAllocator>> = Once::new();
@@ -19,12 +19,19 @@ static PAGE_ALLOCATOR: Once<RwLock<PageAllocator>> = Once::new();
 /// page tokens describing the same physical address).
 pub struct PageAllocator {
     map: BTreeMap<PageSize, Vec<Page<UnAllocated>>>,
+    // Released 4KiB pages, which were cleared on deallocation. They are handed out first by `acquire_zeroed_page`, so that allocating
+    // page tables, e.g., during the promotion of a VM, does not clear the same memory again.
+    zeroed_pages: Vec<Page<Zeroed>>,
+    // The size of the confidential memory owned by the `PageAllocator`, including the memory of pages that are currently allocated.
+    total_size_in_bytes: usize,
 }
 
 impl<'a> PageAllocator {
     // Usually there are 512 pages of size x that can fit in a single page of size y, where y is next page size larger than x (e.g., 2MiB
     // and 4KiB).
     const EXPECTED_NUMBER_OF_TOKENS_PER_SIZE: usize = 512;
+    // The pool of zeroed pages holds at most 4MiB of memory. Further released pages return to the general pool of free pages.
+    const MAX_NUMBER_OF_ZEROED_PAGES: usize = 1024;
     const NOT_INITIALIZED: &'static str = "Bug. Could not access page allocator because it is not initialized";
 
     /// Initializes the global instance of a `PageAllocator`. Returns error if the `PageAllocator` has already been initialized.
@@ -44,6 +51,35 @@ impl<'a> PageAllocator {
         Ok(())
     }
 
+    /// Passes the ownership of a memory region that has been converted into the confidential memory at runtime to the `PageAllocator`.
+    ///
+    /// # Safety
+    ///
+    /// See the `PageAllocator::add_memory_region` for safety requirements.
+    pub unsafe fn extend(memory_start: ConfidentialMemoryAddress, memory_end: *const usize) -> Result<(), Error> {
+        Self::try_write(|page_allocator| Ok(page_allocator.add_memory_region(memory_start, memory_end)))
+    }
+
+    /// Removes all page tokens describing the given memory region and returns them to the caller. Returns error if any part of the memory
+    /// region is currently allocated. In such a case, no page token is removed from the `PageAllocator`.
+    pub fn remove_memory_region(memory_start: usize, memory_end: usize) -> Result<Vec<Page<UnAllocated>>, Error> {
+        Self::try_write(|page_allocator| {
+            let is_in_region = |page: &Page<UnAllocated>| memory_start <= page.start_address() && page.end_address() <= memory_end;
+            page_allocator.return_zeroed_pages(|page| memory_start <= page.start_address() && page.end_address() <= memory_end);
+            let free_bytes: usize =
+                page_allocator.map.values().flatten().filter(|page| is_in_region(page)).map(|page| page.size().in_bytes()).sum();
+            assure!(free_bytes == memory_end - memory_start, Error::MemoryRegionInUse())?;
+            page_allocator.total_size_in_bytes -= free_bytes;
+            let mut removed_pages = Vec::new();
+            page_allocator.map.values_mut().for_each(|pages| {
+                let (pages_in_region, other_pages): (Vec<_>, Vec<_>) = core::mem::take(pages).into_iter().partition(is_in_region);
+                *pages = other_pages;
+                removed_pages.extend(pages_in_region);
+            });
+            Ok(removed_pages)
+        })
+    }
+
     /// Constructs an empty page allocator that contains no tokens.
     ///
     /// # Guarantees
@@ -55,7 +91,7 @@ impl<'a> PageAllocator {
             let page_tokens = Vec::<_>::with_capacity(Self::EXPECTED_NUMBER_OF_TOKENS_PER_SIZE);
             map.insert(page_size.clone(), page_tokens);
         }
-        Self { map }
+        Self { map, zeroed_pages: Vec::with_capacity(Self::MAX_NUMBER_OF_ZEROED_PAGES), total_size_in_bytes: 0 }
     }
 
     /// Adds a physial memory region to the PageAllocator. The ownership over this memory region is passed from the caller to the
@@ -86,6 +122,7 @@ impl<'a> PageAllocator {
         // keep increasing it until we find the largest possible page size. Then, we keep decreasing the page size until we reach the end of
         // the memory region.
         let memory_layout = MemoryLayout::read();
+        self.total_size_in_bytes += memory_region_end as usize - memory_region_start.as_usize();
         let mut memory_address = Some(memory_region_start);
         let mut page_size = PageSize::smallest();
 
@@ -172,6 +209,54 @@ impl<'a> PageAllocator {
         Self::release_pages(vec![page])
     }
 
+    /// Returns a 4KiB page with zeroed content. Pages that were cleared when they were released are handed out first, so that the
+    /// caller clears the page only if no such page is left.
+    pub fn acquire_zeroed_page() -> Result<Page<Allocated>, Error> {
+        match Self::try_write(|page_allocator| Ok(page_allocator.zeroed_pages.pop()))? {
+            Some(page) => Ok(page.allocate()),
+            None => Ok(Self::acquire_continous_pages(1, PageSize::smallest())?.remove(0).zeroize()),
+        }
+    }
+
+    /// Consumes the page tokens of pages cleared by `Page::deallocate`. The `PageAllocator` keeps up to `MAX_NUMBER_OF_ZEROED_PAGES`
+    /// of the 4KiB pages for `acquire_zeroed_page` and releases all other pages like `release_pages`.
+    pub fn release_zeroed_pages(pages: Vec<Page<Zeroed>>) {
+        let _ = Self::try_write(|page_allocator| {
+            Ok(pages.into_iter().for_each(|page| {
+                let page_size = *page.size();
+                if page_size == PageSize::smallest() && page_allocator.zeroed_pages.len() < Self::MAX_NUMBER_OF_ZEROED_PAGES {
+                    page_allocator.zeroed_pages.push(page);
+                } else {
+                    page_allocator.map.get_mut(&page_size).and_then(|v| Some(v.push(page.into_unallocated())));
+                }
+            }))
+        })
+        .inspect_err(|_| debug!("Memory leak: failed to store released pages in the page allocator"));
+    }
+
+    pub fn release_zeroed_page(page: Page<Zeroed>) {
+        Self::release_zeroed_pages(vec![page])
+    }
+
+    /// Returns the number of pages of the given size that can still be allocated. Free pages of larger sizes count as many pages of
+    /// the given size as fit in them, because the `PageAllocator` divides them on demand. Free pages of smaller sizes do not count, even
+    /// if they are contiguous, because the `PageAllocator` never combines them. Thus, the result reflects the fragmentation of the
+    /// free memory, e.g., there might be plenty of free 4KiB pages but no 2MiB page.
+    pub fn free_page_count(page_size: PageSize) -> usize {
+        Self::try_read(|page_allocator| {
+            let number_of_zeroed_pages = if page_size == PageSize::smallest() { page_allocator.zeroed_pages.len() } else { 0 };
+            let number_of_pages: usize =
+                page_allocator.map.range(page_size..).map(|(size, pages)| pages.len() * (size.in_bytes() / page_size.in_bytes())).sum();
+            Ok(number_of_pages + number_of_zeroed_pages)
+        })
+        .unwrap_or(0)
+    }
+
+    /// Returns the number of 4KiB pages of the confidential memory owned by the `PageAllocator`, both free and allocated.
+    pub fn total_page_count() -> usize {
+        Self::try_read(|page_allocator| Ok(page_allocator.total_size_in_bytes / PageSize::smallest().in_bytes())).unwrap_or(0)
+    }
+
     /// Returns vector of unallocated page tokens representing a continous memory region. If it failes to find allocation within free pages
     /// of the requested size, it divides larger page tokens. Empty vector is returned if there are not enough page tokens in the system
     /// that meet the requested criteria.
@@ -183,9 +268,22 @@ impl<'a> PageAllocator {
             self.divide_pages(page_size);
             available_pages = self.acquire_continous_pages_of_given_size(number_of_pages, page_size);
         }
+        // The last resort are the zeroed pages, which might complete a continous memory region.
+        if available_pages.is_empty() && page_size == PageSize::smallest() && !self.zeroed_pages.is_empty() {
+            self.return_zeroed_pages(|_| true);
+            available_pages = self.acquire_continous_pages_of_given_size(number_of_pages, page_size);
+        }
         available_pages
     }
 
+    /// Moves the zeroed pages selected by the filter to the other free pages.
+    fn return_zeroed_pages<F: Fn(&Page<Zeroed>) -> bool>(&mut self, filter: F) {
+        let (selected_pages, other_pages): (Vec<_>, Vec<_>) = core::mem::take(&mut self.zeroed_pages).into_iter().partition(filter);
+        self.zeroed_pages = other_pages;
+        // Below unwrap is safe because the PageAllocator constructor guarantees that the map contains keys for every possible page size.
+        self.map.get_mut(&PageSize::smallest()).unwrap().extend(selected_pages.into_iter().map(|page| page.into_unallocated()));
+    }
+
     /// Tries to allocate a continous chunk of physical memory composed of the requested number of pages. Returns a vector of unallocated
     /// page tokens, all of them having the same size, or an empty vector if the allocation fails.
     fn acquire_continous_pages_of_given_size(&mut self, number_of_pages: usize, page_size: PageSize) -> Vec<Page<UnAllocated>> {
@@ -256,9 +354,130 @@ impl<'a> PageAllocator {
             .unwrap_or(false)
     }
 
+    /// returns a reference to the PageAllocator after obtaining a shared lock on the mutex
+    fn try_read<F, O>(op: O) -> Result<F, Error>
+    where O: FnOnce(&RwLockReadGuard<'static, PageAllocator>) -> Result<F, Error> {
+        op(&PAGE_ALLOCATOR.get().expect(Self::NOT_INITIALIZED).read())
+    }
+
     /// returns a mutable reference to the PageAllocator after obtaining a lock on the mutex
     fn try_write<F, O>(op: O) -> Result<F, Error>
     where O: FnOnce(&mut RwLockWriteGuard<'static, PageAllocator>) -> Result<F, Error> {
         op(&mut PAGE_ALLOCATOR.get().expect(Self::NOT_INITIALIZED).write())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+
+    pub(crate) const PAGE_SIZE: PageSize = PageSize::Size4KiB;
+    pub(crate) const NUMBER_OF_NON_CONFIDENTIAL_PAGES: usize = 2;
+    // The confidential memory contains two 2MiB pages, which is enough for tests of huge page mappings.
+    pub(crate) const NUMBER_OF_CONFIDENTIAL_PAGES: usize = 1536;
+    pub(crate) const CONTENT: usize = 0xaaaa_5555_aaaa_5555;
+
+    static NON_CONFIDENTIAL_MEMORY_START: OnceLock<usize> = OnceLock::new();
+    // Tests compare the number of free pages of the global page allocator, so they must not run concurrently.
+    static PAGE_ALLOCATOR_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Initializes the memory layout and the page allocator over the memory of the test process. Returns the start address of the
+    /// non-confidential memory, whose pages contain `CONTENT`. Tests of all modules that use the global page allocator must call it
+    /// and hold the returned lock.
+    pub(crate) fn setup() -> (MutexGuard<'static, ()>, usize) {
+        let lock = PAGE_ALLOCATOR_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+        let non_confidential_memory_start = *NON_CONFIDENTIAL_MEMORY_START.get_or_init(|| {
+            let number_of_pages = NUMBER_OF_NON_CONFIDENTIAL_PAGES + NUMBER_OF_CONFIDENTIAL_PAGES;
+            let alignment = PageSize::Size2MiB.in_bytes();
+            let layout = std::alloc::Layout::from_size_align(number_of_pages * PAGE_SIZE.in_bytes(), alignment).unwrap();
+            // Safety: the memory is never deallocated, so it stays owned by the memory layout for the lifetime of the test process.
+            unsafe {
+                let memory_start = std::alloc::alloc_zeroed(layout) as *mut usize;
+                let confidential_memory_start = memory_start.byte_add(NUMBER_OF_NON_CONFIDENTIAL_PAGES * PAGE_SIZE.in_bytes());
+                let memory_end = memory_start.byte_add(layout.size());
+                (0..NUMBER_OF_NON_CONFIDENTIAL_PAGES * PAGE_SIZE.in_bytes() / core::mem::size_of::<usize>())
+                    .for_each(|i| memory_start.add(i).write(CONTENT));
+                let (confidential_memory_start, confidential_memory_end) =
+                    MemoryLayout::init(memory_start, confidential_memory_start, confidential_memory_start, memory_end).unwrap();
+                PageAllocator::initialize(confidential_memory_start, confidential_memory_end).unwrap();
+                memory_start as usize
+            }
+        });
+        (lock, non_confidential_memory_start)
+    }
+
+    #[test]
+    fn total_page_count_covers_allocated_pages() {
+        let (_lock, _) = setup();
+        assert_eq!(PageAllocator::total_page_count(), NUMBER_OF_CONFIDENTIAL_PAGES);
+        let pages = PageAllocator::acquire_continous_pages(4, PAGE_SIZE).unwrap();
+        assert_eq!(PageAllocator::total_page_count(), NUMBER_OF_CONFIDENTIAL_PAGES);
+        PageAllocator::release_pages(pages);
+        assert_eq!(PageAllocator::total_page_count(), NUMBER_OF_CONFIDENTIAL_PAGES);
+    }
+
+    #[test]
+    fn free_page_count_follows_allocation_and_release() {
+        let (_lock, _) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let pages = PageAllocator::acquire_continous_pages(4, PAGE_SIZE).unwrap();
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 4);
+        let page = PageAllocator::acquire_zeroed_page().unwrap();
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 5);
+        PageAllocator::release_pages(pages);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 1);
+        PageAllocator::release_zeroed_page(page.deallocate());
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn free_page_count_excludes_pages_larger_than_free_memory() {
+        let (_lock, _) = setup();
+        // The confidential memory is smaller than a 1GiB page.
+        assert_eq!(PageAllocator::free_page_count(PageSize::Size1GiB), 0);
+        assert!(matches!(PageAllocator::acquire_continous_pages(1, PageSize::Size1GiB), Err(Error::OutOfPages())));
+    }
+
+    fn number_of_zeroed_pages() -> usize {
+        PageAllocator::try_read(|page_allocator| Ok(page_allocator.zeroed_pages.len())).unwrap()
+    }
+
+    #[test]
+    fn released_zeroed_page_is_handed_out_without_content() {
+        let (_lock, _) = setup();
+        let mut page = PageAllocator::acquire_zeroed_page().unwrap();
+        let offsets = (0..PAGE_SIZE.in_bytes()).step_by(core::mem::size_of::<usize>());
+        offsets.clone().for_each(|offset| page.write(offset, CONTENT).unwrap());
+        let address = page.start_address();
+        let pooled_pages = number_of_zeroed_pages();
+        PageAllocator::release_zeroed_page(page.deallocate());
+        assert_eq!(number_of_zeroed_pages(), pooled_pages + 1);
+        // The page most recently released to the pool is handed out first.
+        let page = PageAllocator::acquire_zeroed_page().unwrap();
+        assert_eq!(number_of_zeroed_pages(), pooled_pages);
+        assert_eq!(page.start_address(), address);
+        assert!(offsets.into_iter().all(|offset| page.read(offset).unwrap() == 0));
+        PageAllocator::release_zeroed_page(page.deallocate());
+    }
+
+    #[test]
+    fn pooled_page_is_drained_when_its_memory_region_is_removed() {
+        let (_lock, _) = setup();
+        let page = PageAllocator::acquire_zeroed_page().unwrap();
+        let (start_address, end_address) = (page.start_address(), page.end_address());
+        PageAllocator::release_zeroed_page(page.deallocate());
+        let pooled_pages = number_of_zeroed_pages();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let pages = PageAllocator::remove_memory_region(start_address, end_address).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].start_address(), start_address);
+        assert_eq!(number_of_zeroed_pages(), pooled_pages - 1);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 1);
+        assert_eq!(PageAllocator::total_page_count(), NUMBER_OF_CONFIDENTIAL_PAGES - 1);
+        // Returns the memory region, so that other tests see the entire confidential memory.
+        PageAllocator::release_pages(pages);
+        PageAllocator::try_write(|page_allocator| Ok(page_allocator.total_size_in_bytes += PAGE_SIZE.in_bytes())).unwrap();
+        assert_eq!(PageAllocator::total_page_count(), NUMBER_OF_CONFIDENTIAL_PAGES);
+    }
+}
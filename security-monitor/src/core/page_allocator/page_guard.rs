@@ -0,0 +1,133 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::PageSize;
+use crate::core::page_allocator::{Allocated, Page, PageAllocator, UnAllocated};
+use crate::error::Error;
+use alloc::vec::Vec;
+
+/// Owns pages acquired from the page allocator while they are being filled with content. Unless the guard is committed, all its pages
+/// are returned to the page allocator when the guard is dropped, filled pages after being cleared. Thus, an operation that fails after
+/// acquiring pages, e.g., because the source page lies outside the non-confidential memory, does not leak confidential memory.
+pub struct PageGuard {
+    unused_pages: Vec<Page<UnAllocated>>,
+    filled_pages: Vec<Page<Allocated>>,
+}
+
+impl PageGuard {
+    pub fn acquire(number_of_pages: usize, page_size: PageSize) -> Result<Self, Error> {
+        let mut unused_pages = PageAllocator::acquire_continous_pages(number_of_pages, page_size)?;
+        // Pages are filled in the order of their addresses, see `copy_from_non_confidential_memory`.
+        unused_pages.reverse();
+        Ok(Self { unused_pages, filled_pages: Vec::with_capacity(number_of_pages) })
+    }
+
+    /// Fills the next page with the content of the page at the given address of the non-confidential memory. Returns error if there
+    /// is no unused page left or the source page is not entirely in the non-confidential memory.
+    pub fn copy_from_non_confidential_memory(&mut self, address: NonConfidentialMemoryAddress) -> Result<(), Error> {
+        let page = self.unused_pages.last_mut().ok_or(Error::OutOfPages())?;
+        page.copy_from_non_confidential_memory(address)?;
+        // The page has just been filled, so the below unwrap never fails.
+        self.filled_pages.push(self.unused_pages.pop().unwrap().into_copied());
+        Ok(())
+    }
+
+    /// Hands over the filled pages to the caller, which becomes responsible for returning them to the page allocator.
+    pub fn commit(mut self) -> Vec<Page<Allocated>> {
+        core::mem::take(&mut self.filled_pages)
+    }
+}
+
+impl Drop for PageGuard {
+    fn drop(&mut self) {
+        let filled_pages: Vec<_> = self.filled_pages.drain(..).map(|page| page.deallocate()).collect();
+        if !filled_pages.is_empty() {
+            PageAllocator::release_zeroed_pages(filled_pages);
+        }
+        // Unused pages might have been partially filled by a failed copy, but they hold only data of the non-confidential memory and
+        // are overwritten before their next use anyway.
+        let unused_pages = core::mem::take(&mut self.unused_pages);
+        if !unused_pages.is_empty() {
+            PageAllocator::release_pages(unused_pages);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::page_allocator::page_allocator::tests::{
+        setup, CONTENT, NUMBER_OF_CONFIDENTIAL_PAGES, NUMBER_OF_NON_CONFIDENTIAL_PAGES, PAGE_SIZE,
+    };
+
+    fn non_confidential_address(address: usize) -> NonConfidentialMemoryAddress {
+        NonConfidentialMemoryAddress::new(address as *mut usize).unwrap()
+    }
+
+    #[test]
+    fn dropped_guard_returns_all_pages() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let mut page_guard = PageGuard::acquire(4, PAGE_SIZE).unwrap();
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 4);
+        page_guard.copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start)).unwrap();
+        page_guard
+            .copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start + PAGE_SIZE.in_bytes()))
+            .unwrap();
+        drop(page_guard);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn failed_copy_returns_all_pages() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let mut page_guard = PageGuard::acquire(2, PAGE_SIZE).unwrap();
+        page_guard.copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start)).unwrap();
+        // The source page starts in the last word of the non-confidential memory, so the copy fails after its first word.
+        let last_word =
+            non_confidential_memory_start + NUMBER_OF_NON_CONFIDENTIAL_PAGES * PAGE_SIZE.in_bytes() - core::mem::size_of::<usize>();
+        assert!(page_guard.copy_from_non_confidential_memory(non_confidential_address(last_word)).is_err());
+        drop(page_guard);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn copy_without_unused_pages_fails() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let mut page_guard = PageGuard::acquire(1, PAGE_SIZE).unwrap();
+        page_guard.copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start)).unwrap();
+        assert!(matches!(
+            page_guard.copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start)),
+            Err(Error::OutOfPages())
+        ));
+        drop(page_guard);
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn failed_acquisition_takes_no_pages() {
+        let (_lock, _) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        assert!(PageGuard::acquire(NUMBER_OF_CONFIDENTIAL_PAGES + 1, PAGE_SIZE).is_err());
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+
+    #[test]
+    fn committed_pages_are_handed_over_with_their_content() {
+        let (_lock, non_confidential_memory_start) = setup();
+        let free_pages = PageAllocator::free_page_count(PAGE_SIZE);
+        let mut page_guard = PageGuard::acquire(2, PAGE_SIZE).unwrap();
+        page_guard.copy_from_non_confidential_memory(non_confidential_address(non_confidential_memory_start)).unwrap();
+        let pages = page_guard.commit();
+        // The unused page is returned when the guard is committed, the filled page belongs to the caller.
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages - 1);
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].read(0).unwrap(), CONTENT);
+        assert_eq!(pages[0].read(PAGE_SIZE.in_bytes() - core::mem::size_of::<usize>()).unwrap(), CONTENT);
+        PageAllocator::release_zeroed_pages(pages.into_iter().map(|page| page.deallocate()).collect());
+        assert_eq!(PageAllocator::free_page_count(PAGE_SIZE), free_pages);
+    }
+}
@@ -44,4 +44,8 @@ impl SharedPage {
     pub fn confidential_vm_virtual_address(&self) -> ConfidentialVmPhysicalAddress {
         self.confidential_vm_virtual_address
     }
+
+    pub fn page_size(&self) -> PageSize {
+        self.page_size
+    }
 }
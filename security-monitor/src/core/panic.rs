@@ -1,13 +1,16 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::put_hart_to_sleep;
+use crate::core::architecture::specification::CSR_MSTATUS_MPP;
+use crate::core::architecture::{halt_hart, HartArchitecturalState, CSR};
+use crate::core::initialization::executing_hart_state_address;
 use crate::core::memory_layout::MemoryLayout;
 
 /// This piece of code executes on a panic, which is a runtime error that indicates an implementation bug from which we
 /// cannot recover. Examples are integer overflow, asserts, explicit statements like panic!(), unwrap(), expect().
 ///
 /// This function halts all other harts in the system and clear the confidential memory.
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     // TODO: halt all other harts and make sure the below code executes exclusively on one hart
@@ -28,8 +31,19 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
     // that there is no panic! executed on two different harts at the same time.
     unsafe { MemoryLayout::read().clear_confidential_memory() };
 
-    // sleep or loop forever since there is nothing else we can do
-    loop {
-        put_hart_to_sleep();
+    // The panic might have interrupted a trap handler in the middle of modifying the hart state. We replace the state so that any
+    // path resuming this hart, e.g., the context switch, ends in the halt loop instead of re-entering the panic with garbage.
+    if let Some(address) = executing_hart_state_address() {
+        // Safety: the address points to the state of this hart, which is never accessed by other harts. The code that used it
+        // before the panic will never resume, so we do not create an aliasing mutable reference that is ever used.
+        if let Some(hart_state) = unsafe { (address as *mut HartArchitecturalState).as_mut() } {
+            hart_state.restore_known_safe_defaults();
+        }
     }
+    // Interrupts would only wake this hart up from the halt loop.
+    CSR.mie.set(0);
+    CSR.mstatus.set(0b11 << CSR_MSTATUS_MPP);
+
+    // sleep or loop forever since there is nothing else we can do
+    halt_hart()
 }
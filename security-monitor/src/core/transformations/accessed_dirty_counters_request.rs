@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// The hypervisor's request for the number of accessed and dirty bits that the security monitor set in G-stage page table entries on
+/// behalf of the hardware. The counters are written to the buffer in the non-confidential memory.
+pub struct AccessedDirtyCountersRequest {
+    buffer_address: usize,
+}
+
+impl AccessedDirtyCountersRequest {
+    pub fn new(buffer_address: usize) -> Self {
+        Self { buffer_address }
+    }
+
+    pub fn buffer_address(&self) -> usize {
+        self.buffer_address
+    }
+}
@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+
+/// A request of the confidential VM for a signed attestation report. The confidential VM passes the address of the challenge, the
+/// address of the buffer to which the report is written, and the size of this buffer.
+pub struct AttestationReportRequest {
+    challenge_address: ConfidentialVmPhysicalAddress,
+    report_address: ConfidentialVmPhysicalAddress,
+    report_buffer_size: usize,
+}
+
+impl AttestationReportRequest {
+    pub fn new(challenge_address: usize, report_address: usize, report_buffer_size: usize) -> Self {
+        Self {
+            challenge_address: ConfidentialVmPhysicalAddress::new(challenge_address),
+            report_address: ConfidentialVmPhysicalAddress::new(report_address),
+            report_buffer_size,
+        }
+    }
+
+    pub fn challenge_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.challenge_address
+    }
+
+    pub fn report_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.report_address
+    }
+
+    pub fn report_buffer_size(&self) -> usize {
+        self.report_buffer_size
+    }
+}
+
+/// A request of the confidential VM for the certificate chain that endorses the key signing attestation reports. The relying party
+/// needs the chain to verify the report.
+pub struct CertificateChainRequest {
+    buffer_address: ConfidentialVmPhysicalAddress,
+    buffer_size: usize,
+}
+
+impl CertificateChainRequest {
+    pub fn new(buffer_address: usize, buffer_size: usize) -> Self {
+        Self { buffer_address: ConfidentialVmPhysicalAddress::new(buffer_address), buffer_size }
+    }
+
+    pub fn buffer_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.buffer_address
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+}
@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::{ConfidentialVmPhysicalAddress, NonConfidentialMemoryAddress};
+use crate::error::Error;
+
+pub struct CreateConfidentialVmRequest {
+    is_debuggable: bool,
+    maps_memory_on_demand: bool,
+    guest_physical_address_end: usize,
+}
+
+impl CreateConfidentialVmRequest {
+    const DEBUGGABLE_FLAG: usize = 0x1;
+    const ON_DEMAND_MAPPING_FLAG: usize = 0x2;
+
+    pub fn new(flags: usize, guest_physical_address_end: usize) -> Self {
+        Self {
+            is_debuggable: flags & Self::DEBUGGABLE_FLAG != 0,
+            maps_memory_on_demand: flags & Self::ON_DEMAND_MAPPING_FLAG != 0,
+            guest_physical_address_end,
+        }
+    }
+
+    pub fn is_debuggable(&self) -> bool {
+        self.is_debuggable
+    }
+
+    /// Returns true if memory added to the confidential VM must be mapped only when the confidential VM accesses it for the first time.
+    pub fn maps_memory_on_demand(&self) -> bool {
+        self.maps_memory_on_demand
+    }
+
+    /// Returns the exclusive end of the guest physical address space declared by the hypervisor, or `None` if the hypervisor did not
+    /// declare it.
+    pub fn guest_physical_address_end(&self) -> Option<usize> {
+        Some(self.guest_physical_address_end).filter(|end| *end != 0)
+    }
+}
+
+pub struct AddMemoryRegionRequest {
+    confidential_vm_id: ConfidentialVmId,
+    address: ConfidentialVmPhysicalAddress,
+    source_address: usize,
+    number_of_pages: usize,
+}
+
+impl AddMemoryRegionRequest {
+    pub fn new(confidential_vm_id: usize, address: usize, source_address: usize, number_of_pages: usize) -> Self {
+        let confidential_vm_id = ConfidentialVmId::new(confidential_vm_id);
+        Self { confidential_vm_id, address: ConfidentialVmPhysicalAddress::new(address), source_address, number_of_pages }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn address(&self) -> ConfidentialVmPhysicalAddress {
+        self.address
+    }
+
+    /// Returns the address in the non-confidential memory from which the content of the region is copied.
+    pub fn source_address(&self) -> usize {
+        self.source_address
+    }
+
+    pub fn number_of_pages(&self) -> usize {
+        self.number_of_pages
+    }
+}
+
+pub struct AddHartRequest {
+    confidential_vm_id: ConfidentialVmId,
+    confidential_hart_id: usize,
+    start_address: usize,
+    opaque: usize,
+}
+
+impl AddHartRequest {
+    pub fn new(confidential_vm_id: usize, confidential_hart_id: usize, start_address: usize, opaque: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), confidential_hart_id, start_address, opaque }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn confidential_hart_id(&self) -> usize {
+        self.confidential_hart_id
+    }
+
+    pub fn start_address(&self) -> usize {
+        self.start_address
+    }
+
+    pub fn opaque(&self) -> usize {
+        self.opaque
+    }
+}
+
+/// Finalizes the confidential VM under construction. If the hypervisor provides the address of an expected launch digest, the security
+/// monitor refuses to finalize a confidential VM whose launch digest differs, so that only approved images can ever execute.
+pub struct FinalizeRequest {
+    confidential_vm_id: ConfidentialVmId,
+    expected_launch_digest: Option<[u8; Sha384::DIGEST_SIZE_IN_BYTES]>,
+    // The start address and the opaque argument of the boot hart, if they are provided at finalization rather than when the boot hart
+    // was added.
+    boot_hart_entry: Option<(usize, usize)>,
+}
+
+impl FinalizeRequest {
+    /// Copies the expected launch digest from the hypervisor's memory, unless the address is zero. The digest is copied immediately, so
+    /// the hypervisor cannot change it during finalization. Returns error if the digest is not entirely in the non-confidential memory.
+    pub fn new(confidential_vm_id: usize, expected_launch_digest_address: usize) -> Result<Self, Error> {
+        let expected_launch_digest = match expected_launch_digest_address {
+            0 => None,
+            address => Some(Self::read_digest(address)?),
+        };
+        Ok(Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), expected_launch_digest, boot_hart_entry: None })
+    }
+
+    /// Starts the boot hart at the given guest physical address as part of the finalization, see `ConfidentialVmBuilder::start_boot_hart`.
+    pub fn with_boot_hart_entry(mut self, start_address: usize, opaque: usize) -> Self {
+        self.boot_hart_entry = Some((start_address, opaque));
+        self
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn expected_launch_digest(&self) -> Option<&[u8; Sha384::DIGEST_SIZE_IN_BYTES]> {
+        self.expected_launch_digest.as_ref()
+    }
+
+    pub fn boot_hart_entry(&self) -> Option<(usize, usize)> {
+        self.boot_hart_entry
+    }
+
+    fn read_digest(address: usize) -> Result<[u8; Sha384::DIGEST_SIZE_IN_BYTES], Error> {
+        assure!(address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+        let mut digest = [0u8; Sha384::DIGEST_SIZE_IN_BYTES];
+        digest.chunks_exact_mut(core::mem::size_of::<usize>()).enumerate().try_for_each(|(word_index, chunk)| {
+            let word_address = address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+            let word_address = NonConfidentialMemoryAddress::new(word_address as *mut usize)?;
+            // Safety: the address is in the non-confidential memory, so reading it cannot leak confidential information.
+            chunk.copy_from_slice(&unsafe { word_address.read() }.to_le_bytes());
+            Ok::<(), Error>(())
+        })?;
+        Ok(digest)
+    }
+}
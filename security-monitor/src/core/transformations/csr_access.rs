@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::GeneralPurposeRegister;
+
+/// An access of a confidential hart to a CSR, decoded from the instruction that trapped.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CsrAccess {
+    csr: u16,
+    result_gpr: GeneralPurposeRegister,
+    is_write: bool,
+}
+
+impl CsrAccess {
+    const SYSTEM_OPCODE: usize = 0b1110011;
+    const CSRRW_FUNCT3: usize = 0b001;
+    const CSRRWI_FUNCT3: usize = 0b101;
+
+    /// Decodes the instructions of the Zicsr extension. `csrrw` and `csrrwi` always write the CSR, while `csrrs` and `csrrc` and their
+    /// immediate variants write it only if the source register is not `x0` or the immediate is not zero.
+    pub fn decode(instruction: usize) -> Option<Self> {
+        let opcode = instruction & 0x7f;
+        let destination_register = (instruction >> 7) & 0x1f;
+        let funct3 = (instruction >> 12) & 0b111;
+        let source = (instruction >> 15) & 0x1f;
+        let csr = (instruction >> 20) & 0xfff;
+        if opcode != Self::SYSTEM_OPCODE || funct3 & 0b11 == 0 {
+            return None;
+        }
+        let is_write = funct3 == Self::CSRRW_FUNCT3 || funct3 == Self::CSRRWI_FUNCT3 || source != 0;
+        Some(Self { csr: csr as u16, result_gpr: GeneralPurposeRegister::from_index(destination_register)?, is_write })
+    }
+
+    pub fn csr(&self) -> u16 {
+        self.csr
+    }
+
+    pub fn result_gpr(&self) -> GeneralPurposeRegister {
+        self.result_gpr
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.is_write
+    }
+}
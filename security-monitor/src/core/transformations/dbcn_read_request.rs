@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::error::Error;
+
+/// The `console read` call of the SBI debug console (DBCN) extension. A confidential hart asks for up to `number_of_bytes` bytes of
+/// console input to be written to its memory at the given guest physical address.
+pub struct DbcnReadRequest {
+    address: ConfidentialVmPhysicalAddress,
+    number_of_bytes: usize,
+}
+
+impl DbcnReadRequest {
+    /// The maximum number of bytes read from the console in a single call. The SBI specification permits returning fewer bytes than
+    /// requested, so larger requests are truncated instead of rejected.
+    pub const MAX_NUMBER_OF_BYTES: usize = 256;
+
+    /// Returns error if the buffer does not fit in the address space.
+    pub fn new(number_of_bytes: usize, address_lo: usize, address_hi: usize) -> Result<Self, Error> {
+        assure!(address_hi == 0, Error::InvalidArgument())?;
+        assure!(address_lo.checked_add(number_of_bytes).is_some(), Error::InvalidArgument())?;
+        Ok(Self { address: ConfidentialVmPhysicalAddress::new(address_lo), number_of_bytes })
+    }
+
+    pub fn address(&self) -> ConfidentialVmPhysicalAddress {
+        self.address
+    }
+
+    pub fn number_of_bytes(&self) -> usize {
+        core::cmp::min(self.number_of_bytes, Self::MAX_NUMBER_OF_BYTES)
+    }
+}
@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::GeneralPurposeRegister;
+use crate::core::control_data::ConfidentialVmId;
+use crate::error::Error;
+
+/// A subset of the confidential hart's registers that the hypervisor can access when debugging a debuggable confidential VM. The
+/// S-mode CSRs refer to the guest's view of these registers, i.e., their VS-mode counterparts.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DebugRegister {
+    Pc,
+    Gpr(GeneralPurposeRegister),
+    Sstatus,
+    Scause,
+    Stval,
+}
+
+impl DebugRegister {
+    const PC_ID: usize = 0x1000;
+    const SSTATUS_ID: usize = 0x1001;
+    const SCAUSE_ID: usize = 0x1002;
+    const STVAL_ID: usize = 0x1003;
+
+    /// Decodes the register identifier used by the hypervisor. Identifiers lower than 32 are indices of general purpose registers, of
+    /// which only a* and t* registers are accessible. Returns error if the identifier does not point to an accessible register.
+    pub fn from_id(id: usize) -> Result<Self, Error> {
+        use GeneralPurposeRegister::*;
+        match id {
+            Self::PC_ID => Ok(Self::Pc),
+            Self::SSTATUS_ID => Ok(Self::Sstatus),
+            Self::SCAUSE_ID => Ok(Self::Scause),
+            Self::STVAL_ID => Ok(Self::Stval),
+            id => match GeneralPurposeRegister::from_index(id) {
+                Some(gpr @ (a0 | a1 | a2 | a3 | a4 | a5 | a6 | a7 | t0 | t1 | t2 | t3 | t4 | t5 | t6)) => Ok(Self::Gpr(gpr)),
+                _ => Err(Error::InvalidArgument()),
+            },
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub struct ReadRegisterRequest {
+    confidential_vm_id: ConfidentialVmId,
+    confidential_hart_id: usize,
+    register_id: usize,
+}
+
+impl ReadRegisterRequest {
+    pub fn new(confidential_vm_id: usize, confidential_hart_id: usize, register_id: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), confidential_hart_id, register_id }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn confidential_hart_id(&self) -> usize {
+        self.confidential_hart_id
+    }
+
+    pub fn register(&self) -> Result<DebugRegister, Error> {
+        DebugRegister::from_id(self.register_id)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct WriteRegisterRequest {
+    confidential_vm_id: ConfidentialVmId,
+    confidential_hart_id: usize,
+    register_id: usize,
+    value: usize,
+}
+
+impl WriteRegisterRequest {
+    pub fn new(confidential_vm_id: usize, confidential_hart_id: usize, register_id: usize, value: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), confidential_hart_id, register_id, value }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn confidential_hart_id(&self) -> usize {
+        self.confidential_hart_id
+    }
+
+    pub fn register(&self) -> Result<DebugRegister, Error> {
+        DebugRegister::from_id(self.register_id)
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+}
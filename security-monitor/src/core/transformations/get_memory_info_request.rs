@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// The hypervisor's request for the amount of the confidential memory that is still available for confidential VMs. The counts of
+/// free pages are written to the buffer in the non-confidential memory.
+pub struct GetMemoryInfoRequest {
+    buffer_address: usize,
+}
+
+impl GetMemoryInfoRequest {
+    pub fn new(buffer_address: usize) -> Self {
+        Self { buffer_address }
+    }
+
+    pub fn buffer_address(&self) -> usize {
+        self.buffer_address
+    }
+}
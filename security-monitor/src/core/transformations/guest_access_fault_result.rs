@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::{CAUSE_LOAD_PAGE_FAULT, CAUSE_STORE_PAGE_FAULT};
+use crate::core::transformations::{GuestLoadPageFaultRequest, GuestStorePageFaultRequest};
+
+/// The outcome of a load or store that the hypervisor refused to emulate, for example, because there is no device at the accessed
+/// address. The fault is reflected to the confidential hart as a page fault, so the confidential VM can handle it instead of being
+/// killed.
+///
+/// The hardware hart only detects the denial, see `HardwareHart::guest_load_page_fault_result`. The result is applied by
+/// `ConfidentialHart::apply_guest_access_fault_denied` because the page fault is injected into the confidential hart's state, which
+/// the hardware hart does not own while the hypervisor executes.
+#[derive(PartialEq)]
+pub struct GuestAccessFaultResult {
+    code: usize,
+    stval: usize,
+}
+
+impl GuestAccessFaultResult {
+    pub fn denied(code: usize, stval: usize) -> Self {
+        Self { code, stval }
+    }
+
+    pub fn load_denied(request: &GuestLoadPageFaultRequest) -> Self {
+        Self::denied(CAUSE_LOAD_PAGE_FAULT.into(), request.fault_address())
+    }
+
+    pub fn store_denied(request: &GuestStorePageFaultRequest) -> Self {
+        Self::denied(CAUSE_STORE_PAGE_FAULT.into(), request.fault_address())
+    }
+
+    pub fn code(&self) -> usize {
+        self.code
+    }
+
+    pub fn stval(&self) -> usize {
+        self.stval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::GeneralPurposeRegister;
+
+    const FAULT_ADDRESS: usize = 0x1000_0008;
+
+    #[test]
+    fn denied_load_is_reported_as_load_page_fault() {
+        let result = GuestAccessFaultResult::load_denied(&GuestLoadPageFaultRequest::new(4, GeneralPurposeRegister::a0, FAULT_ADDRESS));
+        assert_eq!(result.code(), CAUSE_LOAD_PAGE_FAULT.into());
+        assert_eq!(result.stval(), FAULT_ADDRESS);
+    }
+
+    #[test]
+    fn denied_store_is_reported_as_store_page_fault() {
+        let result = GuestAccessFaultResult::store_denied(&GuestStorePageFaultRequest::new(2, FAULT_ADDRESS));
+        assert_eq!(result.code(), CAUSE_STORE_PAGE_FAULT.into());
+        assert_eq!(result.stval(), FAULT_ADDRESS);
+    }
+}
@@ -7,11 +7,12 @@ use crate::core::architecture::GeneralPurposeRegister;
 pub struct GuestLoadPageFaultRequest {
     instruction_length: usize,
     result_gpr: GeneralPurposeRegister,
+    fault_address: usize,
 }
 
 impl GuestLoadPageFaultRequest {
-    pub fn new(instruction_length: usize, result_gpr: GeneralPurposeRegister) -> Self {
-        Self { instruction_length, result_gpr }
+    pub fn new(instruction_length: usize, result_gpr: GeneralPurposeRegister, fault_address: usize) -> Self {
+        Self { instruction_length, result_gpr, fault_address }
     }
 
     pub fn instruction_length(&self) -> usize {
@@ -21,4 +22,9 @@ impl GuestLoadPageFaultRequest {
     pub fn result_gpr(&self) -> GeneralPurposeRegister {
         self.result_gpr
     }
+
+    /// Returns the guest virtual address accessed by the faulting instruction.
+    pub fn fault_address(&self) -> usize {
+        self.fault_address
+    }
 }
@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState};
+use crate::core::architecture::GeneralPurposeRegister;
 use crate::core::transformations::GuestLoadPageFaultRequest;
 
 pub struct GuestLoadPageFaultResult {
@@ -11,12 +11,8 @@ pub struct GuestLoadPageFaultResult {
 }
 
 impl GuestLoadPageFaultResult {
-    pub fn new(hart_state: &HartArchitecturalState, request: GuestLoadPageFaultRequest) -> Self {
-        Self {
-            result_gpr: request.result_gpr(),
-            value: hart_state.gpr(request.result_gpr()),
-            instruction_length: request.instruction_length(),
-        }
+    pub fn new(value: usize, request: GuestLoadPageFaultRequest) -> Self {
+        Self { result_gpr: request.result_gpr(), value, instruction_length: request.instruction_length() }
     }
 
     pub fn value(&self) -> usize {
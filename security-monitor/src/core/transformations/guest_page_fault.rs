@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::GuestMemoryAccess;
+
+/// A guest page fault raised by the G-stage translation of the confidential hart's memory access.
+pub struct GuestPageFault {
+    guest_physical_address: ConfidentialVmPhysicalAddress,
+    fault_address: usize,
+    access: GuestMemoryAccess,
+}
+
+impl GuestPageFault {
+    pub fn new(guest_physical_address: ConfidentialVmPhysicalAddress, fault_address: usize, access: GuestMemoryAccess) -> Self {
+        Self { guest_physical_address, fault_address, access }
+    }
+
+    pub fn guest_physical_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.guest_physical_address
+    }
+
+    /// Returns the guest virtual address of the faulting access.
+    pub fn fault_address(&self) -> usize {
+        self.fault_address
+    }
+
+    pub fn access(&self) -> GuestMemoryAccess {
+        self.access
+    }
+}
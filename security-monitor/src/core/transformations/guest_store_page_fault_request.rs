@@ -5,14 +5,20 @@
 #[derive(PartialEq)]
 pub struct GuestStorePageFaultRequest {
     instruction_length: usize,
+    fault_address: usize,
 }
 
 impl GuestStorePageFaultRequest {
-    pub fn new(instruction_length: usize) -> Self {
-        Self { instruction_length }
+    pub fn new(instruction_length: usize, fault_address: usize) -> Self {
+        Self { instruction_length, fault_address }
     }
 
     pub fn instruction_length(&self) -> usize {
         self.instruction_length
     }
+
+    /// Returns the guest virtual address accessed by the faulting instruction.
+    pub fn fault_address(&self) -> usize {
+        self.fault_address
+    }
 }
@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+
+/// The hypervisor's query about the runstate of a confidential hart. It identifies the confidential hart the same way as the resume
+/// request does.
+pub struct HartRunstateRequest {
+    confidential_vm_id: ConfidentialVmId,
+    confidential_hart_id: usize,
+}
+
+impl HartRunstateRequest {
+    pub fn new(confidential_vm_id: usize, confidential_hart_id: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), confidential_hart_id }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn confidential_hart_id(&self) -> usize {
+        self.confidential_hart_id
+    }
+}
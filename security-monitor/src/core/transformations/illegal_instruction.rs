@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::{CSR_CYCLE, CSR_FCSR, CSR_FFLAGS, CSR_FRM, CSR_MISA, CSR_SEED, CSR_TIME};
+use crate::core::transformations::CsrAccess;
+
+#[derive(PartialEq)]
+pub struct IllegalInstructionRequest {
+    instruction: usize,
+}
+
+impl IllegalInstructionRequest {
+    const LOAD_FP_OPCODE: usize = 0b0000111;
+    const STORE_FP_OPCODE: usize = 0b0100111;
+    const MADD_OPCODE: usize = 0b1000011;
+    const MSUB_OPCODE: usize = 0b1000111;
+    const NMSUB_OPCODE: usize = 0b1001011;
+    const NMADD_OPCODE: usize = 0b1001111;
+    const OP_FP_OPCODE: usize = 0b1010011;
+    // C.FLD/C.FSD in quadrant 0 and C.FLDSP/C.FSDSP in quadrant 2. On RV64, funct3 of 0b011 and 0b111 encode integer loads and stores.
+    const COMPRESSED_FP_FUNCT3: [usize; 2] = [0b001, 0b101];
+
+    pub fn new(instruction: usize) -> Self {
+        Self { instruction }
+    }
+
+    pub fn instruction(&self) -> usize {
+        self.instruction
+    }
+
+    /// Returns the CSR access if the instruction is one of the Zicsr instructions. Whether the access is emulated is decided by the
+    /// confidential VM's `CsrEmulationPolicy`.
+    pub fn csr_access(&self) -> Option<CsrAccess> {
+        CsrAccess::decode(self.instruction)
+    }
+
+    /// Returns true if the instruction might have trapped because the floating-point unit is off, i.e., it is a floating-point
+    /// instruction or accesses a floating-point CSR. The hardware is allowed to report 0 instead of the faulting instruction, in which
+    /// case the instruction is unknown and might be a floating-point instruction too.
+    pub fn may_require_floating_point_unit(&self) -> bool {
+        let instruction = self.instruction;
+        if instruction == 0 {
+            return true;
+        }
+        if instruction & 0b11 != 0b11 {
+            let quadrant = instruction & 0b11;
+            let funct3 = (instruction >> 13) & 0b111;
+            return (quadrant == 0b00 || quadrant == 0b10) && Self::COMPRESSED_FP_FUNCT3.contains(&funct3);
+        }
+        match instruction & 0x7f {
+            Self::LOAD_FP_OPCODE
+            | Self::STORE_FP_OPCODE
+            | Self::MADD_OPCODE
+            | Self::MSUB_OPCODE
+            | Self::NMSUB_OPCODE
+            | Self::NMADD_OPCODE
+            | Self::OP_FP_OPCODE => true,
+            _ => self.csr_access().is_some_and(|access| [CSR_FFLAGS, CSR_FRM, CSR_FCSR].contains(&access.csr())),
+        }
+    }
+}
+
+/// CSRs that are not accessible to confidential harts but whose accesses the security monitor emulates.
+#[derive(Clone, Copy, PartialEq)]
+pub enum VirtualizedCsr {
+    /// The supported ISA, as seen by the confidential hart.
+    Misa,
+    /// The entropy source, backed by the confidential hart's virtual entropy source.
+    Seed,
+    /// The time, read when the `time` CSR is not enabled in `mcounteren` or `hcounteren`.
+    Time,
+    /// The number of cycles executed by the confidential hart, backed by a counter that does not advance while the confidential hart
+    /// is descheduled.
+    Cycle,
+}
+
+impl VirtualizedCsr {
+    pub fn from_csr(csr: u16) -> Option<Self> {
+        match csr {
+            CSR_MISA => Some(Self::Misa),
+            CSR_SEED => Some(Self::Seed),
+            CSR_TIME => Some(Self::Time),
+            CSR_CYCLE => Some(Self::Cycle),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the CSR is emulated only for accesses that write it. The Zkr extension requires the `seed` CSR to be accessed
+    /// with a read-write instruction. Other virtualized CSRs are emulated only for read-only accesses, because confidential harts must
+    /// not modify them.
+    pub fn requires_write_access(&self) -> bool {
+        *self == Self::Seed
+    }
+}
+
+#[derive(PartialEq)]
+pub struct IllegalInstructionResult {
+    instruction: usize,
+}
+
+impl IllegalInstructionResult {
+    pub fn new(instruction: usize) -> Self {
+        Self { instruction }
+    }
+
+    pub fn instruction(&self) -> usize {
+        self.instruction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn may_require_floating_point_unit(instruction: usize) -> bool {
+        IllegalInstructionRequest::new(instruction).may_require_floating_point_unit()
+    }
+
+    #[test]
+    fn floating_point_instructions_require_floating_point_unit() {
+        // fadd.d f0, f1, f2
+        assert!(may_require_floating_point_unit(0x0220_8053));
+        // fld f0, 0(a0)
+        assert!(may_require_floating_point_unit(0x0005_3007));
+        // csrr a0, fcsr
+        assert!(may_require_floating_point_unit(0x0030_2573));
+        // c.fld fs0, 0(a0) and c.fsdsp fs0, 0(sp)
+        assert!(may_require_floating_point_unit(0x2100));
+        assert!(may_require_floating_point_unit(0xa022));
+    }
+
+    #[test]
+    fn other_instructions_do_not_require_floating_point_unit() {
+        // add a0, a0, a1
+        assert!(!may_require_floating_point_unit(0x00b5_0533));
+        // csrr a0, misa
+        assert!(!may_require_floating_point_unit(0x3010_2573));
+        // c.ld s0, 0(a0) and c.sdsp s0, 0(sp)
+        assert!(!may_require_floating_point_unit(0x6100));
+        assert!(!may_require_floating_point_unit(0xe022));
+    }
+
+    #[test]
+    fn unreported_instruction_might_require_floating_point_unit() {
+        assert!(may_require_floating_point_unit(0));
+    }
+}
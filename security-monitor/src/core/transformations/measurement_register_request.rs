@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+
+/// A request of the confidential VM to extend or read one of its measurement registers. The digest is exchanged via a buffer in the
+/// confidential VM's memory, so it never leaves the confidential memory.
+pub struct MeasurementRegisterRequest {
+    index: usize,
+    buffer_address: ConfidentialVmPhysicalAddress,
+}
+
+impl MeasurementRegisterRequest {
+    pub fn new(index: usize, buffer_address: usize) -> Self {
+        Self { index, buffer_address: ConfidentialVmPhysicalAddress::new(buffer_address) }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn buffer_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.buffer_address
+    }
+
+    /// Returns true if the confidential VM passed no buffer, asking to return the read value in registers instead of memory.
+    pub fn is_register_read(&self) -> bool {
+        self.buffer_address.usize() == 0
+    }
+}
+
+/// A request of the confidential VM for entries of its measurement log starting from `first_entry`. The entries are written to the
+/// buffer in the confidential VM's memory.
+pub struct GetMeasurementLogRequest {
+    first_entry: usize,
+    buffer_address: ConfidentialVmPhysicalAddress,
+    buffer_size: usize,
+}
+
+impl GetMeasurementLogRequest {
+    pub fn new(first_entry: usize, buffer_address: usize, buffer_size: usize) -> Self {
+        Self { first_entry, buffer_address: ConfidentialVmPhysicalAddress::new(buffer_address), buffer_size }
+    }
+
+    pub fn first_entry(&self) -> usize {
+        self.first_entry
+    }
+
+    pub fn buffer_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.buffer_address
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+}
+
+/// The value of a measurement register returned in the confidential hart's registers. Following the SBI calling convention, a0
+/// carries the error code and a1 the size of the digest in bytes. The digest does not fit in a1 alone, so it is returned in six
+/// consecutive registers a2-a7, each holding 8 bytes of the digest in little endian.
+pub struct MeasurementRegisterValue {
+    digest: [u8; Sha384::DIGEST_SIZE_IN_BYTES],
+}
+
+impl MeasurementRegisterValue {
+    pub fn new(digest: [u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Self {
+        Self { digest }
+    }
+
+    pub fn digest_size(&self) -> usize {
+        self.digest.len()
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = usize> + '_ {
+        self.digest.chunks_exact(core::mem::size_of::<u64>()).map(|chunk| {
+            let mut word = [0u8; core::mem::size_of::<u64>()];
+            word.copy_from_slice(chunk);
+            u64::from_le_bytes(word) as usize
+        })
+    }
+}
@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::PageSize;
+use crate::error::Error;
+
+/// A request of the confidential VM to give a region of its memory back to the hypervisor (inflating the memory balloon), or to get
+/// back a region that it gave away before (deflating the memory balloon).
+#[derive(PartialEq)]
+pub struct MemoryBalloonRequest {
+    address: ConfidentialVmPhysicalAddress,
+    size_in_bytes: usize,
+}
+
+impl MemoryBalloonRequest {
+    /// Returns error if the region is empty, wraps around the address space, or its start address or size is not aligned to 4KiB.
+    pub fn new(address: usize, size_in_bytes: usize) -> Result<Self, Error> {
+        let page_size_in_bytes = PageSize::Size4KiB.in_bytes();
+        assure!(address % page_size_in_bytes == 0 && size_in_bytes % page_size_in_bytes == 0, Error::AddressNotAligned())?;
+        assure!(size_in_bytes > 0 && address.checked_add(size_in_bytes).is_some(), Error::InvalidMemoryRegion())?;
+        Ok(Self { address: ConfidentialVmPhysicalAddress::new(address), size_in_bytes })
+    }
+
+    pub fn address(&self) -> ConfidentialVmPhysicalAddress {
+        self.address
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+
+    pub fn number_of_pages(&self) -> usize {
+        self.size_in_bytes / PageSize::Size4KiB.in_bytes()
+    }
+}
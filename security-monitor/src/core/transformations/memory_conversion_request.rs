@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// A hypervisor request to confirm that a memory region finished moving between the non-confidential and confidential memory.
+pub struct MemoryConversionRequest {
+    start_address: usize,
+    size_in_bytes: usize,
+}
+
+impl MemoryConversionRequest {
+    pub fn new(start_address: usize, size_in_bytes: usize) -> Self {
+        Self { start_address, size_in_bytes }
+    }
+
+    pub fn start_address(&self) -> usize {
+        self.start_address
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
+
+/// A hypervisor request to mark a region of the non-confidential memory for the conversion into the confidential memory. This is the
+/// first step of the conversion, which completes after a global memory fence. Unlike in CoVE, where the conversion state is kept for
+/// every page, the region is converted as a whole, because a single PMP entry protects it (see `ConvertedMemoryRegion`). Thus, the
+/// region must be naturally aligned and its size must be a power of two, and at most `MAX_NUMBER_OF_CONVERTED_MEMORY_REGIONS` regions
+/// can be converted at the same time.
+pub struct ConvertToConfidentialRequest {
+    start_address: usize,
+    size_in_bytes: usize,
+}
+
+impl ConvertToConfidentialRequest {
+    pub fn new(start_address: usize, size_in_bytes: usize) -> Self {
+        Self { start_address, size_in_bytes }
+    }
+
+    pub fn start_address(&self) -> usize {
+        self.start_address
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
+
+/// A hypervisor request to mark a previously converted memory region for the return to the non-confidential memory. This is the first
+/// step of the reclaim, which completes after a global memory fence.
+pub struct ReclaimToNonConfidentialRequest {
+    start_address: usize,
+    size_in_bytes: usize,
+}
+
+impl ReclaimToNonConfidentialRequest {
+    pub fn new(start_address: usize, size_in_bytes: usize) -> Self {
+        Self { start_address, size_in_bytes }
+    }
+
+    pub fn start_address(&self) -> usize {
+        self.start_address
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// Informs the hypervisor that the security monitor ran out of confidential memory while handling a request of the confidential hart.
+/// The hypervisor should convert more memory to confidential memory and resume the confidential hart. The confidential hart then
+/// retries the request because its state has not been modified.
+pub struct MemoryFaultNotification {
+    failed_address: usize,
+    required_pages: usize,
+}
+
+impl MemoryFaultNotification {
+    pub fn new(failed_address: usize, required_pages: usize) -> Self {
+        Self { failed_address, required_pages }
+    }
+
+    /// The guest physical address whose mapping could not be changed.
+    pub fn failed_address(&self) -> usize {
+        self.failed_address
+    }
+
+    /// The number of 4KiB pages the security monitor needs to complete the request.
+    pub fn required_pages(&self) -> usize {
+        self.required_pages
+    }
+}
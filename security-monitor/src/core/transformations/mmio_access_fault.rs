@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// An access fault reflected to the confidential VM when it touches an MMIO region whose emulation is denied by the confidential
+/// VM's MMIO policy.
+#[derive(PartialEq)]
+pub struct MmioAccessFault {
+    code: usize,
+    stval: usize,
+}
+
+impl MmioAccessFault {
+    pub fn new(code: usize, stval: usize) -> Self {
+        Self { code, stval }
+    }
+
+    pub fn code(&self) -> usize {
+        self.code
+    }
+
+    pub fn stval(&self) -> usize {
+        self.stval
+    }
+}
@@ -26,6 +26,12 @@ impl MmioLoadRequest {
         self.htval
     }
 
+    /// Returns the guest physical address of the faulting load. The hardware reports the address shifted right by 2 bits in `htval`, the
+    /// lowest bits are the same as in the guest virtual address because both addresses share the page offset.
+    pub fn fault_address(&self) -> usize {
+        (self.htval << 2) | (self.stval & 0b11)
+    }
+
     pub fn instruction(&self) -> usize {
         self.instruction
     }
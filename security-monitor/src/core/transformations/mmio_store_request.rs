@@ -2,6 +2,7 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::architecture::GeneralPurposeRegister;
+use crate::error::Error;
 
 pub struct MmioStoreRequest {
     code: usize,
@@ -10,11 +11,16 @@ pub struct MmioStoreRequest {
     instruction: usize,
     gpr: GeneralPurposeRegister,
     gpr_value: usize,
+    width: MmioStoreWidth,
 }
 
 impl MmioStoreRequest {
-    pub fn new(code: usize, stval: usize, htval: usize, instruction: usize, gpr: GeneralPurposeRegister, gpr_value: usize) -> Self {
-        Self { code, stval, htval, instruction, gpr, gpr_value: gpr_value }
+    /// The value of the source register is truncated to the width of the store. Sub-word stores write only the low bytes of the
+    /// register, so its remaining bytes are not needed for the emulation and must not reach the hypervisor.
+    pub fn new(
+        code: usize, stval: usize, htval: usize, instruction: usize, gpr: GeneralPurposeRegister, gpr_value: usize, width: MmioStoreWidth,
+    ) -> Self {
+        Self { code, stval, htval, instruction, gpr, gpr_value: gpr_value & width.mask(), width }
     }
 
     pub fn code(&self) -> usize {
@@ -29,6 +35,12 @@ impl MmioStoreRequest {
         self.htval
     }
 
+    /// Returns the guest physical address of the faulting store. The hardware reports the address shifted right by 2 bits in `htval`, the
+    /// lowest bits are the same as in the guest virtual address because both addresses share the page offset.
+    pub fn fault_address(&self) -> usize {
+        (self.htval << 2) | (self.stval & 0b11)
+    }
+
     pub fn instruction(&self) -> usize {
         self.instruction
     }
@@ -40,4 +52,37 @@ impl MmioStoreRequest {
     pub fn gpr_value(&self) -> usize {
         self.gpr_value
     }
+
+    pub fn width(&self) -> MmioStoreWidth {
+        self.width
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MmioStoreWidth {
+    Byte,
+    Halfword,
+    Word,
+    Doubleword,
+}
+
+impl MmioStoreWidth {
+    pub fn from_size_in_bytes(size_in_bytes: usize) -> Result<Self, Error> {
+        match size_in_bytes {
+            1 => Ok(Self::Byte),
+            2 => Ok(Self::Halfword),
+            4 => Ok(Self::Word),
+            8 => Ok(Self::Doubleword),
+            _ => Err(Error::InvalidArgument()),
+        }
+    }
+
+    fn mask(&self) -> usize {
+        match self {
+            Self::Byte => 0xff,
+            Self::Halfword => 0xffff,
+            Self::Word => 0xffff_ffff,
+            Self::Doubleword => usize::MAX,
+        }
+    }
 }
@@ -1,53 +1,127 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+pub use accessed_dirty_counters_request::AccessedDirtyCountersRequest;
+pub use attestation_report_request::{AttestationReportRequest, CertificateChainRequest};
+pub use confidential_vm_construction::{AddHartRequest, AddMemoryRegionRequest, CreateConfidentialVmRequest, FinalizeRequest};
+pub use csr_access::CsrAccess;
+pub use dbcn_read_request::DbcnReadRequest;
+pub use debug_register_request::{DebugRegister, ReadRegisterRequest, WriteRegisterRequest};
+#[cfg(feature = "declassification_log")]
+pub use declassification_log_request::DeclassificationLogRequest;
+pub use get_memory_info_request::GetMemoryInfoRequest;
+pub use guest_access_fault_result::GuestAccessFaultResult;
 pub use guest_load_page_fault_request::GuestLoadPageFaultRequest;
 pub use guest_load_page_fault_result::GuestLoadPageFaultResult;
+pub use guest_page_fault::GuestPageFault;
 pub use guest_store_page_fault_request::GuestStorePageFaultRequest;
 pub use guest_store_page_fault_result::GuestStorePageFaultResult;
+pub use hart_runstate_request::HartRunstateRequest;
+pub use illegal_instruction::{IllegalInstructionRequest, IllegalInstructionResult, VirtualizedCsr};
 pub use interrupt_request::{EnabledInterrupts, InjectedInterrupts, InterruptRequest};
+pub use measurement_register_request::{GetMeasurementLogRequest, MeasurementRegisterRequest, MeasurementRegisterValue};
+pub use memory_balloon_request::MemoryBalloonRequest;
+pub use memory_conversion_request::{ConvertToConfidentialRequest, MemoryConversionRequest, ReclaimToNonConfidentialRequest};
+pub use memory_fault_notification::MemoryFaultNotification;
+pub use mmio_access_fault::MmioAccessFault;
 pub use mmio_load_request::MmioLoadRequest;
-pub use mmio_store_request::MmioStoreRequest;
+pub use mmio_store_request::{MmioStoreRequest, MmioStoreWidth};
+pub use nacl_shared_memory_request::NaclSharedMemoryRequest;
 pub use opensbi_request::OpensbiRequest;
 pub use opensbi_result::OpensbiResult;
+#[cfg(feature = "pmp_audit_log")]
+pub use pmp_audit_log_request::PmpAuditLogRequest;
+pub use probe_extension_request::ProbeExtensionRequest;
 pub use promote_to_confidential_vm_request::PromoteToConfidentialVm;
+pub use reclaim_memory_request::ReclaimMemoryRequest;
 pub use resume_request::ResumeRequest;
+pub use rotate_memory_key_request::RotateMemoryKeyRequest;
+pub use sbi_cppc::{CppcRequest, CppcResult};
+pub use sbi_fwft::{FwftRequest, FwftResult};
 pub use sbi_hsm::{SbiHsmHartStart, SbiHsmHartStatus, SbiHsmHartSuspend};
 pub use sbi_ipi::SbiIpi;
+pub use sbi_pmu::SbiPmuRequest;
 pub use sbi_request::SbiRequest;
 pub use sbi_result::SbiResult;
 pub use sbi_rfence::{SbiRemoteFenceI, SbiRemoteSfenceVma, SbiRemoteSfenceVmaAsid};
 pub use sbi_srst::SbiSrstSystemReset;
+pub use sbi_sse::{SseInterruptedState, SseRequest, SseResult};
 pub use sbi_vm_request::SbiVmRequest;
+pub use seal_request::{SealRequest, SealingKeyRequest, UnsealRequest};
+pub use security_monitor_info_request::SecurityMonitorInfoRequest;
 pub use share_page_request::SharePageRequest;
 pub use share_page_result::SharePageResult;
+pub use steal_time_request::StealTimeRequest;
 pub use terminate_request::TerminateRequest;
-pub use unshare_page_request::UnsharePageRequest;
+pub use tlb_fence_counters_request::TlbFenceCountersRequest;
+pub use trace_buffer_request::TraceBufferRequest;
+pub use unshare_page_request::{UnsharePageRequest, UnsharePageResult};
+pub use verify_code_integrity_request::VerifyCodeIntegrityRequest;
 pub use virtual_instruction::{VirtualInstructionRequest, VirtualInstructionResult};
+pub use virtualized_csr_result::VirtualizedCsrResult;
+pub use vm_measurement_request::GetVmMeasurementRequest;
+pub use vm_memory_info_request::GetVmMemoryInfoRequest;
 
+mod accessed_dirty_counters_request;
+mod attestation_report_request;
+mod confidential_vm_construction;
+mod csr_access;
+mod dbcn_read_request;
+mod debug_register_request;
+#[cfg(feature = "declassification_log")]
+mod declassification_log_request;
+mod get_memory_info_request;
+mod guest_access_fault_result;
 mod guest_load_page_fault_request;
 mod guest_load_page_fault_result;
+mod guest_page_fault;
 mod guest_store_page_fault_request;
 mod guest_store_page_fault_result;
+mod hart_runstate_request;
+mod illegal_instruction;
 mod interrupt_request;
+mod measurement_register_request;
+mod memory_balloon_request;
+mod memory_conversion_request;
+mod memory_fault_notification;
+mod mmio_access_fault;
 mod mmio_load_request;
 mod mmio_store_request;
+mod nacl_shared_memory_request;
 mod opensbi_request;
 mod opensbi_result;
+#[cfg(feature = "pmp_audit_log")]
+mod pmp_audit_log_request;
+mod probe_extension_request;
 mod promote_to_confidential_vm_request;
+mod reclaim_memory_request;
 mod resume_request;
+mod rotate_memory_key_request;
+mod sbi_cppc;
+mod sbi_fwft;
 mod sbi_hsm;
 mod sbi_ipi;
+mod sbi_pmu;
 mod sbi_request;
 mod sbi_result;
 mod sbi_rfence;
 mod sbi_srst;
+mod sbi_sse;
 mod sbi_vm_request;
+mod seal_request;
+mod security_monitor_info_request;
 mod share_page_request;
 mod share_page_result;
+mod steal_time_request;
 mod terminate_request;
+mod tlb_fence_counters_request;
+mod trace_buffer_request;
 mod unshare_page_request;
+mod verify_code_integrity_request;
 mod virtual_instruction;
+mod virtualized_csr_result;
+mod vm_measurement_request;
+mod vm_memory_info_request;
 
 /// Declassifiers that expose part of the confidential VM's hart state to the hypervisor.
 pub enum ExposeToHypervisor {
@@ -59,6 +133,7 @@ pub enum ExposeToHypervisor {
     MmioStoreRequest(MmioStoreRequest),
     InterruptRequest(InterruptRequest),
     EnabledInterrupts(EnabledInterrupts),
+    MemoryFaultNotification(MemoryFaultNotification),
 }
 
 /// Declassifiers that expose part of the hypervisor's state to a confidential VM's hart.
@@ -66,7 +141,11 @@ pub enum ExposeToConfidentialVm {
     SbiResult(SbiResult),
     GuestLoadPageFaultResult(GuestLoadPageFaultResult),
     VirtualInstructionResult(VirtualInstructionResult),
+    IllegalInstructionResult(IllegalInstructionResult),
+    VirtualizedCsrResult(VirtualizedCsrResult),
+    MmioAccessFault(MmioAccessFault),
     GuestStorePageFaultResult(GuestStorePageFaultResult),
+    GuestAccessFaultDenied(GuestAccessFaultResult),
     Resume(),
     SbiIpi(SbiIpi),
     SbiRemoteFenceI(SbiRemoteFenceI),
@@ -75,6 +154,9 @@ pub enum ExposeToConfidentialVm {
     SbiHsmHartStart(),
     SbiHsmHartStartPending(),
     SbiSrstSystemReset(),
+    DebugRegisterWrite(DebugRegister, usize),
+    SseResult(SseResult),
+    MeasurementRegisterValue(MeasurementRegisterValue),
 }
 
 /// An intermediate confidential hart state that requested certain operation from the hypervisor and is waiting for the
@@ -82,6 +164,7 @@ pub enum ExposeToConfidentialVm {
 #[derive(PartialEq)]
 pub enum PendingRequest {
     SharePage(SharePageRequest),
+    UnsharePage(UnsharePageRequest),
     GuestLoadPageFault(GuestLoadPageFaultRequest),
     GuestStorePageFault(GuestStorePageFaultRequest),
     SbiHsmHartStart(),
@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// The hypervisor's request to register (or unregister) the shared memory of the SBI nested acceleration extension on the physical hart.
+pub struct NaclSharedMemoryRequest {
+    address_lo: usize,
+    address_hi: usize,
+    flags: usize,
+}
+
+impl NaclSharedMemoryRequest {
+    pub fn new(address_lo: usize, address_hi: usize, flags: usize) -> Self {
+        Self { address_lo, address_hi, flags }
+    }
+
+    /// According to the SBI specification, the shared memory is unregistered when all bits of the address are set.
+    pub fn is_unregister_request(&self) -> bool {
+        self.address_lo == usize::MAX && self.address_hi == usize::MAX
+    }
+
+    /// Returns the physical address of the shared memory. Returns None if the address does not fit in XLEN bits.
+    pub fn address(&self) -> Option<usize> {
+        match self.address_hi {
+            0 => Some(self.address_lo),
+            _ => None,
+        }
+    }
+
+    pub fn flags(&self) -> usize {
+        self.flags
+    }
+}
@@ -1,14 +1,62 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::error::Error;
 
 #[derive(Debug)]
 pub struct OpensbiResult {
-    pub trap_regs: opensbi_sys::sbi_trap_regs,
+    mstatus: usize,
+    mepc: usize,
+    a0: usize,
+    a1: usize,
 }
 
 impl OpensbiResult {
-    pub fn from_opensbi_handler(trap_regs: opensbi_sys::sbi_trap_regs) -> Self {
-        Self { trap_regs }
+    /// Extracts the registers that OpenSBI's trap handler modified. Returns error if any of them does not fit into a register of the
+    /// hart, which indicates that the trap frame returned by OpenSBI is corrupted.
+    pub fn from_opensbi_handler(trap_regs: opensbi_sys::sbi_trap_regs) -> Result<Self, Error> {
+        Ok(Self {
+            mstatus: Self::register(trap_regs.mstatus)?,
+            mepc: Self::register(trap_regs.mepc)?,
+            a0: Self::register(trap_regs.a0)?,
+            a1: Self::register(trap_regs.a1)?,
+        })
+    }
+
+    fn register<T: TryInto<usize>>(value: T) -> Result<usize, Error> {
+        value.try_into().map_err(|_| Error::InvalidOpensbiResult())
+    }
+
+    pub fn mstatus(&self) -> usize {
+        self.mstatus
+    }
+
+    pub fn mepc(&self) -> usize {
+        self.mepc
+    }
+
+    pub fn a0(&self) -> usize {
+        self.a0
+    }
+
+    pub fn a1(&self) -> usize {
+        self.a1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_value_is_preserved() {
+        assert_eq!(OpensbiResult::register(0x8000_0000_0000_1800u64).unwrap(), 0x8000_0000_0000_1800);
+        assert_eq!(OpensbiResult::register(usize::MAX as u128).unwrap(), usize::MAX);
+    }
+
+    #[test]
+    fn out_of_range_register_value_is_an_error() {
+        assert!(matches!(OpensbiResult::register(usize::MAX as u128 + 1), Err(Error::InvalidOpensbiResult())));
+        assert!(matches!(OpensbiResult::register(-1i64), Err(Error::InvalidOpensbiResult())));
     }
 }
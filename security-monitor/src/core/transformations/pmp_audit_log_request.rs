@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// The hypervisor's request for the log of PMP configuration changes made on the physical hart executing the call. The log is
+/// written to the buffer in the non-confidential memory.
+pub struct PmpAuditLogRequest {
+    buffer_address: usize,
+}
+
+impl PmpAuditLogRequest {
+    pub fn new(buffer_address: usize) -> Self {
+        Self { buffer_address }
+    }
+
+    pub fn buffer_address(&self) -> usize {
+        self.buffer_address
+    }
+}
@@ -0,0 +1,14 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ProbeExtensionRequest {
+    pub extension_id: usize,
+}
+
+impl ProbeExtensionRequest {
+    pub fn new(extension_id: usize) -> Self {
+        Self { extension_id }
+    }
+}
@@ -9,6 +9,8 @@ pub struct PromoteToConfidentialVm {
 }
 
 impl PromoteToConfidentialVm {
+    const DEBUGGABLE_FLAG: usize = 0x1;
+
     pub fn new(from_state: &HartArchitecturalState) -> Self {
         let hart_state = HartArchitecturalState::from_existing(0, from_state);
         Self { hart_state }
@@ -19,6 +21,31 @@ impl PromoteToConfidentialVm {
         ConfidentialVmPhysicalAddress::new(self.hart_state.gpr(GeneralPurposeRegister::a0))
     }
 
+    /// Returns true if the VM requested to be debuggable, i.e., the lowest bit of the second argument of the call is set.
+    pub fn is_debuggable(&self) -> bool {
+        self.hart_state.gpr(GeneralPurposeRegister::a1) & Self::DEBUGGABLE_FLAG != 0
+    }
+
+    /// Returns the number of confidential harts (vCPUs) requested in the third argument of the call. `None` means that the VM did not
+    /// specify it and the number of harts declared in the device tree should be used.
+    pub fn number_of_harts(&self) -> Option<usize> {
+        Some(self.hart_state.gpr(GeneralPurposeRegister::a2)).filter(|number_of_harts| *number_of_harts > 0)
+    }
+
+    /// Returns the guest physical address and the size of the initial ramdisk given in the fourth and fifth arguments of the call.
+    /// `None` means that the VM did not specify it and the initial ramdisk declared in the device tree, if any, should be used.
+    pub fn initrd_region(&self) -> Option<(ConfidentialVmPhysicalAddress, usize)> {
+        let size = self.hart_state.gpr(GeneralPurposeRegister::a4);
+        (size > 0).then(|| (ConfidentialVmPhysicalAddress::new(self.hart_state.gpr(GeneralPurposeRegister::a3)), size))
+    }
+
+    /// Returns the address of the launch signature structure given in the sixth argument of the call. `None` means that the VM did not
+    /// request the verification of its kernel image (secure launch).
+    pub fn launch_signature_address(&self) -> Option<ConfidentialVmPhysicalAddress> {
+        let address = self.hart_state.gpr(GeneralPurposeRegister::a5);
+        (address > 0).then(|| ConfidentialVmPhysicalAddress::new(address))
+    }
+
     pub fn into(self) -> (ConfidentialVmPhysicalAddress, HartArchitecturalState) {
         (self.fdt_address(), self.hart_state)
     }
@@ -0,0 +1,23 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+
+pub struct ReclaimMemoryRequest {
+    confidential_vm_id: ConfidentialVmId,
+    max_number_of_pages: usize,
+}
+
+impl ReclaimMemoryRequest {
+    pub fn new(confidential_vm_id: usize, max_number_of_pages: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), max_number_of_pages }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn max_number_of_pages(&self) -> usize {
+        self.max_number_of_pages
+    }
+}
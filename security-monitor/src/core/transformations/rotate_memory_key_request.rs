@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+
+/// The hypervisor's request to re-encrypt the memory of a confidential VM with a fresh key.
+pub struct RotateMemoryKeyRequest {
+    confidential_vm_id: ConfidentialVmId,
+}
+
+impl RotateMemoryKeyRequest {
+    pub fn new(confidential_vm_id: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id) }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+}
@@ -0,0 +1,23 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::SbiError;
+
+/// A call of the confidential hart to the SBI Collaborative Processor Performance Control (CPPC) extension. Register ids follow the
+/// SBI specification, i.e., the ACPI CPPC registers and the RISC-V specific ones.
+#[derive(PartialEq, Debug, Clone)]
+pub enum CppcRequest {
+    Probe { register_id: usize },
+    Read { register_id: usize },
+    ReadHi { register_id: usize },
+    Write { register_id: usize, value: usize },
+    Unsupported,
+}
+
+/// The outcome of the CPPC call that the security monitor exposes to the confidential hart. CPPC calls report specific SBI errors,
+/// e.g., a write to a read-only register is denied, so the result carries the error code instead of the generic security monitor error.
+#[derive(PartialEq, Debug, Clone)]
+pub enum CppcResult {
+    Success(usize),
+    Failure(SbiError),
+}
@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::SbiError;
+
+/// A call of the confidential hart to the SBI firmware features (FWFT) extension. Feature ids and flags follow the SBI specification.
+#[derive(PartialEq, Debug, Clone)]
+pub enum FwftRequest {
+    Set { feature: usize, value: usize, flags: usize },
+    Get { feature: usize },
+    Unsupported,
+}
+
+/// The outcome of the FWFT call that the security monitor exposes to the confidential hart. The SBI specification defines which error
+/// reports that a feature is locked, unsupported, or denied, so the result carries the SBI error code.
+#[derive(PartialEq, Debug, Clone)]
+pub enum FwftResult {
+    Success(usize),
+    Failure(SbiError),
+}
@@ -0,0 +1,17 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// A call of the confidential hart to the SBI Performance Monitoring Unit (PMU) extension. Counter indices are the indices of virtual
+/// counters.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SbiPmuRequest {
+    NumCounters,
+    CounterGetInfo { counter_index: usize },
+    CounterConfigMatching { counter_index_base: usize, counter_index_mask: usize, config_flags: usize, event_index: usize },
+    CounterStart { counter_index_base: usize, counter_index_mask: usize, start_flags: usize, initial_value: usize },
+    CounterStop { counter_index_base: usize, counter_index_mask: usize, stop_flags: usize },
+    CounterFwRead { counter_index: usize },
+    CounterFwReadHi { counter_index: usize },
+    Unsupported,
+}
@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState};
+use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState, SbiExtension};
 use crate::core::control_data::ConfidentialVmId;
 
 pub struct SbiRequest {
@@ -19,6 +19,8 @@ impl SbiRequest {
     const KVM_ACE_EXTID: usize = 0x509999;
     const KVM_ACE_REGISTER_FID: usize = 1;
     const KVM_ACE_PAGE_IN_FID: usize = 2;
+    const KVM_ACE_PAGE_OUT_FID: usize = 3;
+    const KVM_ACE_MEMORY_FAULT_FID: usize = 4;
 
     pub fn kvm_ace_register(confidential_vm_id: ConfidentialVmId, confidential_hart_id: usize) -> Self {
         Self::new(Self::KVM_ACE_EXTID, Self::KVM_ACE_REGISTER_FID, confidential_vm_id.usize(), confidential_hart_id, 0, 0, 0, 0)
@@ -28,6 +30,18 @@ impl SbiRequest {
         Self::new(Self::KVM_ACE_EXTID, Self::KVM_ACE_PAGE_IN_FID, page_address, 0, 0, 0, 0, 0)
     }
 
+    /// Informs the hypervisor that the confidential VM stopped sharing the page at the given guest physical address, so that the
+    /// hypervisor can reuse its page located at the given host physical address.
+    pub fn kvm_ace_page_out(page_address: usize, hypervisor_page_address: usize) -> Self {
+        Self::new(Self::KVM_ACE_EXTID, Self::KVM_ACE_PAGE_OUT_FID, page_address, hypervisor_page_address, 0, 0, 0, 0)
+    }
+
+    /// Asks the hypervisor to provide the security monitor with the given number of pages of confidential memory, so that the mapping of
+    /// the given guest physical address can be changed.
+    pub fn kvm_ace_memory_fault(failed_address: usize, required_pages: usize) -> Self {
+        Self::new(Self::KVM_ACE_EXTID, Self::KVM_ACE_MEMORY_FAULT_FID, failed_address, required_pages, 0, 0, 0, 0)
+    }
+
     pub fn kvm_hsm_hart_start(virtual_hart_id: usize) -> Self {
         use crate::core::architecture::HsmExtension;
         Self::new(HsmExtension::EXTID, HsmExtension::HART_START_FID, virtual_hart_id, 0, 0, 0, 0, 0)
@@ -61,12 +75,26 @@ impl SbiRequest {
             hart_state.gpr(GeneralPurposeRegister::a4),
             hart_state.gpr(GeneralPurposeRegister::a5),
         )
+        .without_unused_arguments()
     }
 
     pub fn new(extension_id: usize, function_id: usize, a0: usize, a1: usize, a2: usize, a3: usize, a4: usize, a5: usize) -> Self {
         Self { extension_id, function_id, a0, a1, a2, a3, a4, a5 }
     }
 
+    /// Zeroes argument registers that the SBI function does not define because they might contain residual confidential data. All
+    /// argument registers of unknown functions are kept, because the hypervisor, or the firmware, might implement functions that the
+    /// security monitor does not know.
+    fn without_unused_arguments(mut self) -> Self {
+        if let Some(number_of_arguments) = self.function_arity() {
+            [&mut self.a0, &mut self.a1, &mut self.a2, &mut self.a3, &mut self.a4, &mut self.a5]
+                .into_iter()
+                .skip(number_of_arguments)
+                .for_each(|argument| *argument = 0);
+        }
+        self
+    }
+
     pub fn extension_id(&self) -> usize {
         self.extension_id
     }
@@ -75,6 +103,11 @@ impl SbiRequest {
         self.function_id
     }
 
+    /// Returns the number of argument registers, from 0 to 6, that the called SBI function uses, or `None` if the function is unknown.
+    pub fn function_arity(&self) -> Option<usize> {
+        SbiExtension::decode(self.extension_id, self.function_id).number_of_arguments()
+    }
+
     pub fn a0(&self) -> usize {
         self.a0
     }
@@ -99,3 +132,40 @@ impl SbiRequest {
         self.a5
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::{HsmExtension, SrstExtension};
+
+    fn forwarded(extension_id: usize, function_id: usize) -> SbiRequest {
+        SbiRequest::new(extension_id, function_id, 1, 2, 3, 4, 5, 6).without_unused_arguments()
+    }
+
+    fn arguments(request: &SbiRequest) -> [usize; 6] {
+        [request.a0(), request.a1(), request.a2(), request.a3(), request.a4(), request.a5()]
+    }
+
+    #[test]
+    fn two_argument_call_zeroes_remaining_arguments() {
+        let request = forwarded(SrstExtension::EXTID, SrstExtension::SYSTEM_RESET_FID);
+        assert_eq!(request.function_arity(), Some(2));
+        assert_eq!(arguments(&request), [1, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn call_without_arguments_zeroes_all_arguments() {
+        let request = forwarded(HsmExtension::EXTID, HsmExtension::HART_STOP_FID);
+        assert_eq!(request.function_arity(), Some(0));
+        assert_eq!(arguments(&request), [0; 6]);
+    }
+
+    #[test]
+    fn unknown_call_forwards_all_arguments() {
+        for (extension_id, function_id) in [(HsmExtension::EXTID, 0x100), (0x0a00_0000, 0)] {
+            let request = forwarded(extension_id, function_id);
+            assert_eq!(request.function_arity(), None);
+            assert_eq!(arguments(&request), [1, 2, 3, 4, 5, 6]);
+        }
+    }
+}
@@ -1,7 +1,8 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState};
+use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState, SbiError};
+use crate::error::Error;
 
 /// Sbi is a result of the SBI call from the Hypervisor to the SBI
 /// firmware or a result of the SBI call to the security monitor.
@@ -9,11 +10,17 @@ use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState};
 pub struct SbiResult {
     a0: usize,
     a1: usize,
+    // Functions that return structured data inline return additional values in `a2` and the following registers. Registers beyond
+    // the number of extended values keep their content.
+    extended_values: [usize; 4],
+    number_of_extended_values: usize,
     pc_offset: usize,
 }
 
 impl SbiResult {
     const ECALL_INSTRUCTION_LENGTH: usize = 4;
+    const EXTENDED_VALUE_REGISTERS: [GeneralPurposeRegister; 4] =
+        [GeneralPurposeRegister::a2, GeneralPurposeRegister::a3, GeneralPurposeRegister::a4, GeneralPurposeRegister::a5];
 
     pub fn ecall(hart_state: &HartArchitecturalState) -> Self {
         Self::new(hart_state.gpr(GeneralPurposeRegister::a0), hart_state.gpr(GeneralPurposeRegister::a1), Self::ECALL_INSTRUCTION_LENGTH)
@@ -27,8 +34,35 @@ impl SbiResult {
         Self::new(code, 0, Self::ECALL_INSTRUCTION_LENGTH)
     }
 
+    /// Interprets the values returned by OpenSBI. OpenSBI has already advanced the program counter, so no offset is applied. Returns
+    /// `Error::SbiCallFailed` preserving the value of `a1` if `a0` carries an SBI error code, i.e., a negative signed value.
+    pub fn from_opensbi_retval(a0: usize, a1: usize) -> Result<Self, Error> {
+        match SbiError::from_code(a0) {
+            Some(sbi_error) => Err(Error::SbiCallFailed(sbi_error, a1)),
+            None => Ok(Self::new(a0, a1, 0)),
+        }
+    }
+
+    /// Returns the result that reports the failure of a call handled by OpenSBI to its caller. Errors that do not carry an SBI error
+    /// code are reported as `SbiError::Failed`.
+    pub fn from_opensbi_error(error: Error) -> Self {
+        match error {
+            Error::SbiCallFailed(sbi_error, a1) => Self::new(sbi_error.code(), a1, 0),
+            _ => Self::new(SbiError::Failed.code(), 0, 0),
+        }
+    }
+
     fn new(a0: usize, a1: usize, pc_offset: usize) -> Self {
-        Self { a0, a1, pc_offset }
+        Self { a0, a1, extended_values: [0; Self::EXTENDED_VALUE_REGISTERS.len()], number_of_extended_values: 0, pc_offset }
+    }
+
+    /// Returns the result extended with values returned in the registers `a2` to `a5`, in this order. Returns error if there are more
+    /// than four values, because they do not fit in these registers.
+    pub fn with_extended_values(mut self, values: &[usize]) -> Result<Self, Error> {
+        assure!(values.len() <= Self::EXTENDED_VALUE_REGISTERS.len(), Error::InvalidArgument())?;
+        self.extended_values[..values.len()].copy_from_slice(values);
+        self.number_of_extended_values = values.len();
+        Ok(self)
     }
 
     pub fn a0(&self) -> usize {
@@ -39,7 +73,76 @@ impl SbiResult {
         self.a1
     }
 
+    /// Returns the registers that carry the extended values together with these values. Empty for results with two values.
+    pub fn extended_values(&self) -> impl Iterator<Item = (GeneralPurposeRegister, usize)> + '_ {
+        Self::EXTENDED_VALUE_REGISTERS.into_iter().zip(self.extended_values[..self.number_of_extended_values].iter().copied())
+    }
+
     pub fn pc_offset(&self) -> usize {
         self.pc_offset
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opensbi_success_is_forwarded_without_pc_offset() {
+        let result = SbiResult::from_opensbi_retval(0, 0x1234).unwrap();
+        assert_eq!((result.a0(), result.a1(), result.pc_offset()), (0, 0x1234, 0));
+    }
+
+    #[test]
+    fn opensbi_error_code_is_mapped_to_error() {
+        let error = SbiResult::from_opensbi_retval(SbiError::Denied.code(), 0x1234).unwrap_err();
+        assert!(matches!(error, Error::SbiCallFailed(SbiError::Denied, 0x1234)));
+        let result = SbiResult::from_opensbi_error(error);
+        assert_eq!((result.a0(), result.a1(), result.pc_offset()), (SbiError::Denied.code(), 0x1234, 0));
+    }
+
+    #[test]
+    fn unknown_opensbi_error_code_is_preserved() {
+        let error = SbiResult::from_opensbi_retval(-100isize as usize, 0).unwrap_err();
+        assert_eq!(SbiResult::from_opensbi_error(error).a0(), -100isize as usize);
+    }
+
+    #[test]
+    fn results_have_no_extended_values_by_default() {
+        assert_eq!(SbiResult::success(1).extended_values().count(), 0);
+        assert_eq!(SbiResult::failure(SbiError::Denied.code()).extended_values().count(), 0);
+    }
+
+    #[test]
+    fn extended_values_are_returned_in_a2_to_a5() {
+        let result = SbiResult::success(1).with_extended_values(&[2, 3, 4, 5]).unwrap();
+        let expected = [
+            (GeneralPurposeRegister::a2, 2),
+            (GeneralPurposeRegister::a3, 3),
+            (GeneralPurposeRegister::a4, 4),
+            (GeneralPurposeRegister::a5, 5),
+        ];
+        assert!(result.extended_values().eq(expected));
+        assert_eq!((result.a0(), result.a1()), (0, 1));
+    }
+
+    #[test]
+    fn registers_beyond_extended_values_are_not_written() {
+        let result = SbiResult::success(1).with_extended_values(&[2, 3]).unwrap();
+        assert!(result.extended_values().eq([(GeneralPurposeRegister::a2, 2), (GeneralPurposeRegister::a3, 3)]));
+        // Extending again replaces the previous values.
+        let result = result.with_extended_values(&[7]).unwrap();
+        assert!(result.extended_values().eq([(GeneralPurposeRegister::a2, 7)]));
+    }
+
+    #[test]
+    fn more_than_four_extended_values_are_rejected() {
+        assert!(matches!(SbiResult::success(1).with_extended_values(&[2, 3, 4, 5, 6]), Err(Error::InvalidArgument())));
+    }
+
+    #[test]
+    fn error_without_sbi_error_code_is_reported_as_failure() {
+        let result = SbiResult::from_opensbi_error(Error::InvalidOpensbiResult());
+        assert_eq!((result.a0(), result.a1()), (SbiError::Failed.code(), 0));
+    }
+}
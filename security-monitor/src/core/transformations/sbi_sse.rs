@@ -0,0 +1,63 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+
+/// A call of the confidential hart to the SBI supervisor software events (SSE) extension.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SseRequest {
+    Register { event_id: usize, entry_pc: usize, entry_arg: usize },
+    Unregister { event_id: usize },
+    Enable { event_id: usize },
+    Disable { event_id: usize },
+    Complete,
+    Inject { event_id: usize, hart_id: usize },
+    HartUnmask,
+    HartMask,
+    Unsupported,
+}
+
+/// The outcome of the SSE call that the security monitor exposes to the confidential hart.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SseResult {
+    /// The call succeeded and returns to the instruction following the `ecall`.
+    Success,
+    /// The event handler finished, so the confidential hart resumes the execution interrupted by the event.
+    EventCompleted(SseInterruptedState),
+}
+
+/// The part of the confidential hart state that the event delivery overwrites. The SSE specification requires restoring it when the
+/// event handler completes.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SseInterruptedState {
+    sepc: usize,
+    spp: bool,
+    spie: bool,
+    a6: usize,
+    a7: usize,
+}
+
+impl SseInterruptedState {
+    pub fn new(sepc: usize, spp: bool, spie: bool, a6: usize, a7: usize) -> Self {
+        Self { sepc, spp, spie, a6, a7 }
+    }
+
+    pub fn sepc(&self) -> usize {
+        self.sepc
+    }
+
+    pub fn spp(&self) -> bool {
+        self.spp
+    }
+
+    pub fn spie(&self) -> bool {
+        self.spie
+    }
+
+    pub fn a6(&self) -> usize {
+        self.a6
+    }
+
+    pub fn a7(&self) -> usize {
+        self.a7
+    }
+}
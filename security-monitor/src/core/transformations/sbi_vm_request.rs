@@ -1,14 +1,21 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::architecture::{GeneralPurposeRegister, HartArchitecturalState};
-use crate::core::transformations::SbiRequest;
+use crate::core::architecture::{BaseExtension, GeneralPurposeRegister, HartArchitecturalState, SbiError, SbiExtension, CSR};
+use crate::core::attestation::TcbInfo;
+use crate::core::control_data::ConfidentialHart;
+use crate::core::transformations::{SbiRequest, SbiResult};
 
 pub struct SbiVmRequest {
     sbi_request: SbiRequest,
 }
 
 impl SbiVmRequest {
+    // SBI specification version 2.0, encoded as `major << 24 | minor`.
+    const SBI_SPEC_VERSION: usize = 2 << 24;
+    // The SBI implementation ID of OpenSBI, which hosts the security monitor.
+    const SBI_IMPLEMENTATION_ID: usize = 1;
+
     pub fn from_hart_state(hart_state: &HartArchitecturalState) -> Self {
         let sbi_request = SbiRequest::new(
             hart_state.gpr(GeneralPurposeRegister::a7),
@@ -23,7 +30,69 @@ impl SbiVmRequest {
         Self { sbi_request }
     }
 
+    /// Returns the result of the SBI call made by the confidential hart if the call belongs to an extension that the security
+    /// monitor answers itself, or `None` if the call must be forwarded to the hypervisor. See `handle_locally` for the extensions.
+    pub fn try_handle_locally(hart: &ConfidentialHart) -> Option<SbiResult> {
+        Self::handle_locally(&hart.hypercall_request())
+    }
+
+    /// Dispatches the SBI call to the extensions answered by the security monitor:
+    ///   * Base describes the SBI implementation and the processor, so a confidential VM must not receive values chosen by the hypervisor.
+    ///     The implementation version is the version of the security monitor, as reported in its TCB information.
+    ///   * TEE guest exposes the confidential VM's measurements and keys, so its calls must never reach the hypervisor.
+    ///
+    /// Functions of these extensions that need the confidential VM's state, e.g., probing extensions or getting evidence, are
+    /// dispatched to their handlers by the confidential flow. Should such a call reach this point, it is reported as not supported.
+    fn handle_locally(sbi_request: &SbiRequest) -> Option<SbiResult> {
+        let value = match SbiExtension::decode(sbi_request.extension_id(), sbi_request.function_id()) {
+            SbiExtension::Base(BaseExtension::GetSpecVersion) => Self::SBI_SPEC_VERSION,
+            SbiExtension::Base(BaseExtension::GetImplId) => Self::SBI_IMPLEMENTATION_ID,
+            SbiExtension::Base(BaseExtension::GetImplVersion) => TcbInfo::version() as usize,
+            SbiExtension::Base(BaseExtension::GetMvendorId) => CSR.mvendorid.read(),
+            SbiExtension::Base(BaseExtension::GetMarchid) => CSR.marchid.read(),
+            SbiExtension::Base(BaseExtension::GetMimpid) => CSR.mimpid.read(),
+            SbiExtension::Base(_) | SbiExtension::TeeGuest(_) => return Some(SbiResult::failure(SbiError::NotSupported.code())),
+            _ => return None,
+        };
+        Some(SbiResult::success(value))
+    }
+
     pub fn sbi_request(&self) -> &SbiRequest {
         &self.sbi_request
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::architecture::{HsmExtension, TeeGuestExtension};
+
+    fn handle_locally(extension_id: usize, function_id: usize) -> Option<SbiResult> {
+        SbiVmRequest::handle_locally(&SbiRequest::new(extension_id, function_id, 0, 0, 0, 0, 0, 0))
+    }
+
+    #[test]
+    fn base_calls_are_answered_by_security_monitor() {
+        let result = handle_locally(BaseExtension::EXTID, 0).unwrap();
+        assert_eq!((result.a0(), result.a1()), (0, SbiVmRequest::SBI_SPEC_VERSION));
+        let result = handle_locally(BaseExtension::EXTID, 1).unwrap();
+        assert_eq!((result.a0(), result.a1()), (0, SbiVmRequest::SBI_IMPLEMENTATION_ID));
+        let result = handle_locally(BaseExtension::EXTID, 2).unwrap();
+        assert_eq!((result.a0(), result.a1()), (0, TcbInfo::version() as usize));
+    }
+
+    #[test]
+    fn unknown_calls_of_locally_handled_extensions_are_not_forwarded() {
+        for (extension_id, function_id) in [(BaseExtension::EXTID, 0x100), (TeeGuestExtension::EXTID, 0x100), (TeeGuestExtension::EXTID, 1)]
+        {
+            let result = handle_locally(extension_id, function_id).unwrap();
+            assert_eq!(result.a0(), SbiError::NotSupported.code());
+        }
+    }
+
+    #[test]
+    fn calls_of_other_extensions_are_forwarded() {
+        assert!(handle_locally(HsmExtension::EXTID, HsmExtension::HART_STOP_FID).is_none());
+        assert!(handle_locally(0x0a00_0000, 0).is_none());
+    }
+}
@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+
+/// A request of the confidential VM to seal data to its launch measurement. The confidential VM passes the address and the size of
+/// the data, and the address and the size of the buffer to which the sealed data is written.
+pub struct SealRequest {
+    data_address: ConfidentialVmPhysicalAddress,
+    data_size: usize,
+    output_address: ConfidentialVmPhysicalAddress,
+    output_buffer_size: usize,
+}
+
+impl SealRequest {
+    pub fn new(data_address: usize, data_size: usize, output_address: usize, output_buffer_size: usize) -> Self {
+        Self {
+            data_address: ConfidentialVmPhysicalAddress::new(data_address),
+            data_size,
+            output_address: ConfidentialVmPhysicalAddress::new(output_address),
+            output_buffer_size,
+        }
+    }
+
+    pub fn data_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.data_address
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    pub fn output_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.output_address
+    }
+
+    pub fn output_buffer_size(&self) -> usize {
+        self.output_buffer_size
+    }
+}
+
+/// A request of the confidential VM to recover data that it sealed before. The confidential VM passes the address and the size of
+/// the sealed data, and the address and the size of the buffer to which the recovered data is written.
+pub struct UnsealRequest {
+    sealed_data_address: ConfidentialVmPhysicalAddress,
+    sealed_data_size: usize,
+    output_address: ConfidentialVmPhysicalAddress,
+    output_buffer_size: usize,
+}
+
+impl UnsealRequest {
+    pub fn new(sealed_data_address: usize, sealed_data_size: usize, output_address: usize, output_buffer_size: usize) -> Self {
+        Self {
+            sealed_data_address: ConfidentialVmPhysicalAddress::new(sealed_data_address),
+            sealed_data_size,
+            output_address: ConfidentialVmPhysicalAddress::new(output_address),
+            output_buffer_size,
+        }
+    }
+
+    pub fn sealed_data_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.sealed_data_address
+    }
+
+    pub fn sealed_data_size(&self) -> usize {
+        self.sealed_data_size
+    }
+
+    pub fn output_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.output_address
+    }
+
+    pub fn output_buffer_size(&self) -> usize {
+        self.output_buffer_size
+    }
+}
+
+/// A request of the confidential VM for its sealing key. The confidential VM passes the address and the size of the buffer to which
+/// the key is written.
+pub struct SealingKeyRequest {
+    buffer_address: ConfidentialVmPhysicalAddress,
+    buffer_size: usize,
+}
+
+impl SealingKeyRequest {
+    pub fn new(buffer_address: usize, buffer_size: usize) -> Self {
+        Self { buffer_address: ConfidentialVmPhysicalAddress::new(buffer_address), buffer_size }
+    }
+
+    pub fn buffer_address(&self) -> ConfidentialVmPhysicalAddress {
+        self.buffer_address
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+}
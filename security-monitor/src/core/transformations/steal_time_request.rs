@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::StealTimeState;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::error::Error;
+
+/// The `set shared memory` call of the SBI steal-time accounting (STA) extension. A confidential hart registers the memory where the
+/// security monitor publishes its steal time, or disables the steal-time accounting if the address is all ones.
+pub struct StealTimeRequest {
+    address: Option<ConfidentialVmPhysicalAddress>,
+}
+
+impl StealTimeRequest {
+    const DISABLE_ADDRESS: usize = usize::MAX;
+
+    /// Returns error if the address is not aligned to 64 bytes, does not fit in the address space, or the reserved flags are set.
+    pub fn new(address_lo: usize, address_hi: usize, flags: usize) -> Result<Self, Error> {
+        assure!(flags == 0, Error::InvalidArgument())?;
+        if address_lo == Self::DISABLE_ADDRESS && address_hi == Self::DISABLE_ADDRESS {
+            return Ok(Self { address: None });
+        }
+        assure!(address_hi == 0, Error::InvalidArgument())?;
+        assure!(address_lo % StealTimeState::SIZE_IN_BYTES == 0, Error::AddressNotAligned())?;
+        Ok(Self { address: Some(ConfidentialVmPhysicalAddress::new(address_lo)) })
+    }
+
+    pub fn address(&self) -> Option<ConfidentialVmPhysicalAddress> {
+        self.address
+    }
+}
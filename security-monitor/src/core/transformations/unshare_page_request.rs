@@ -2,6 +2,7 @@
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::PageSize;
 use crate::error::Error;
 
 #[derive(PartialEq)]
@@ -10,7 +11,9 @@ pub struct UnsharePageRequest {
 }
 
 impl UnsharePageRequest {
+    /// Returns error if the address is not aligned to 4KiB, the size of pages that the confidential VM can share.
     pub fn new(address: usize) -> Result<Self, Error> {
+        assure!(address % PageSize::Size4KiB.in_bytes() == 0, Error::AddressNotAligned())?;
         let confidential_vm_virtual_address = ConfidentialVmPhysicalAddress::new(address);
         Ok(Self { confidential_vm_virtual_address })
     }
@@ -19,3 +22,24 @@ impl UnsharePageRequest {
         self.confidential_vm_virtual_address
     }
 }
+
+/// The response of the hypervisor to the notification that the confidential VM stopped sharing a page. A non-zero response code means
+/// that the hypervisor failed to release its page. The page is private to the confidential VM regardless of the response.
+#[derive(PartialEq)]
+pub struct UnsharePageResult {
+    response_code: usize,
+}
+
+impl UnsharePageResult {
+    pub fn new(response_code: usize) -> Self {
+        Self { response_code }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.response_code > 0
+    }
+
+    pub fn response_code(&self) -> usize {
+        self.response_code
+    }
+}
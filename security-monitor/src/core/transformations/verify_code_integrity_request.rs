@@ -0,0 +1,24 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+
+/// A request of the confidential VM to check that a code region still has the content it had when the confidential VM was created.
+pub struct VerifyCodeIntegrityRequest {
+    address: ConfidentialVmPhysicalAddress,
+    size_in_bytes: usize,
+}
+
+impl VerifyCodeIntegrityRequest {
+    pub fn new(address: usize, size_in_bytes: usize) -> Self {
+        Self { address: ConfidentialVmPhysicalAddress::new(address), size_in_bytes }
+    }
+
+    pub fn address(&self) -> ConfidentialVmPhysicalAddress {
+        self.address
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
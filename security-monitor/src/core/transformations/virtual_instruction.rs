@@ -1,6 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::core::transformations::CsrAccess;
 
 #[derive(PartialEq)]
 pub struct VirtualInstructionRequest {
@@ -8,6 +9,14 @@ pub struct VirtualInstructionRequest {
     pub instruction_length: usize,
 }
 
+impl VirtualInstructionRequest {
+    /// Returns the CSR access if the instruction is one of the Zicsr instructions, e.g., a read of a counter disabled in `hcounteren`
+    /// or of a CSR gated by `Smstateen`.
+    pub fn csr_access(&self) -> Option<CsrAccess> {
+        CsrAccess::decode(self.instruction)
+    }
+}
+
 #[derive(PartialEq)]
 pub struct VirtualInstructionResult {
     pub instruction_length: usize,
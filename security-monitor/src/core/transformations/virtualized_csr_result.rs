@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::GeneralPurposeRegister;
+
+/// The value of a CSR emulated by the security monitor, returned to the confidential hart's destination register.
+#[derive(PartialEq)]
+pub struct VirtualizedCsrResult {
+    result_gpr: GeneralPurposeRegister,
+    value: usize,
+}
+
+impl VirtualizedCsrResult {
+    const INSTRUCTION_LENGTH: usize = 4;
+
+    pub fn new(result_gpr: GeneralPurposeRegister, value: usize) -> Self {
+        Self { result_gpr, value }
+    }
+
+    pub fn result_gpr(&self) -> GeneralPurposeRegister {
+        self.result_gpr
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn instruction_length(&self) -> usize {
+        Self::INSTRUCTION_LENGTH
+    }
+}
@@ -0,0 +1,30 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+
+/// The hypervisor's request for one of the launch measurements of a confidential VM. The measurement is written to the buffer in the
+/// non-confidential memory.
+pub struct GetVmMeasurementRequest {
+    confidential_vm_id: ConfidentialVmId,
+    index: usize,
+    buffer_address: usize,
+}
+
+impl GetVmMeasurementRequest {
+    pub fn new(confidential_vm_id: usize, index: usize, buffer_address: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), index, buffer_address }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn buffer_address(&self) -> usize {
+        self.buffer_address
+    }
+}
@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ConfidentialVmId;
+
+/// The hypervisor's request for the confidential memory used by a confidential VM. The usage is written to the buffer in the
+/// non-confidential memory.
+pub struct GetVmMemoryInfoRequest {
+    confidential_vm_id: ConfidentialVmId,
+    buffer_address: usize,
+}
+
+impl GetVmMemoryInfoRequest {
+    pub fn new(confidential_vm_id: usize, buffer_address: usize) -> Self {
+        Self { confidential_vm_id: ConfidentialVmId::new(confidential_vm_id), buffer_address }
+    }
+
+    pub fn confidential_vm_id(&self) -> ConfidentialVmId {
+        self.confidential_vm_id
+    }
+
+    pub fn buffer_address(&self) -> usize {
+        self.buffer_address
+    }
+}
@@ -44,6 +44,11 @@ pub fn __print_pmp_configuration() {
     let pmp1cfg = pmpcfg0 & 0b1111111100000000;
     debug!("pmp1 value: {:x}, shifted {:x}", pmp1, pmp1 << PMP_ADDRESS_SHIFT);
     debug!("pmp1 cfg: {:?}", pmp1cfg);
+
+    let pmp2 = CSR.pmpaddr2.read();
+    let pmp2cfg = pmpcfg0 & 0b111111110000000000000000;
+    debug!("pmp2 value: {:x}, shifted {:x}", pmp2, pmp2 << PMP_ADDRESS_SHIFT);
+    debug!("pmp2 cfg: {:?}", pmp2cfg);
 }
 
 #[cfg(feature = "verbose")]
@@ -92,6 +97,27 @@ macro_rules! debug {
 
 pub(crate) use {_debug, debug};
 
+// The below macro records which registers the security monitor wrote when declassifying information to the hypervisor. It is emitted
+// only in builds with the declassification_log feature, so production builds do not pay for the bookkeeping.
+#[cfg(feature = "declassification_log")]
+macro_rules! log_declassification {
+    ($exit_type:ident, $register:ident($location:expr), $category:ident) => {
+        crate::core::declassification_log::record(
+            crate::core::declassification_log::ExitType::$exit_type,
+            crate::core::declassification_log::DeclassifiedRegister::$register($location),
+            crate::core::declassification_log::ValueCategory::$category,
+        )
+    };
+}
+
+// Without the feature, the register location is still evaluated, so that variables naming the register do not become unused.
+#[cfg(not(feature = "declassification_log"))]
+macro_rules! log_declassification {
+    ($exit_type:ident, $register:ident($location:expr), $category:ident) => {
+        let _ = $location;
+    };
+}
+
 #[cfg(feature = "verbose")]
 pub struct Console {}
 
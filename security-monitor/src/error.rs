@@ -1,6 +1,7 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::SbiError;
 use crate::core::transformations::{ExposeToConfidentialVm, ExposeToHypervisor, SbiResult};
 use core::num::TryFromIntError;
 use pointers_utility::PointerError;
@@ -31,22 +32,62 @@ pub enum Error {
     OutOfMemory(),
     #[error("Not enough memory to allocate a page")]
     OutOfPages(),
+    #[error("Memory encryption engine failed")]
+    MemoryEncryptionFailed(),
+    #[error("All key slots of the memory encryption engine are in use")]
+    NoFreeMemoryKeySlot(),
+    #[error("Hardware entropy source is not available")]
+    EntropySourceUnavailable(),
+    #[error("Hardware entropy source failed")]
+    EntropySourceFailure(),
+    #[error("Hardware entropy source is not ready")]
+    EntropySourceNotReady(),
     #[error("Page table error")]
     PageTableConfiguration(),
     #[error("Address translation failed")]
     AddressTranslationFailed(),
+    #[error("Guest virtual address does not translate to the confidential VM's memory")]
+    PageFault(),
     #[error("Page Table is corrupted")]
     PageTableCorrupted(),
+    #[error("Confidential VM exceeded its limit of page table pages")]
+    PageTableLimitExceeded(),
+    #[error("G-stage page table provided by the hypervisor is malformed: {0} (guest physical address {1:x})")]
+    MalformedPageTable(PageTableViolation, usize),
+    #[error("Guest physical address is already mapped")]
+    AddressAlreadyMapped(),
+    #[error("Page is already shared with the hypervisor")]
+    PageAlreadyShared(),
+    #[error("Address is not aligned")]
+    AddressNotAligned(),
+    #[error("Invalid memory region")]
+    InvalidMemoryRegion(),
+    #[error("Reached a maximum number of converted memory regions")]
+    TooManyConvertedMemoryRegions(),
+    #[error("Memory region is in use")]
+    MemoryRegionInUse(),
+    #[error("Memory region has not been fenced since its conversion state changed")]
+    MemoryConversionNotFenced(),
     #[error("Reached a maximum number of confidential VMs")]
     TooManyConfidentialVms(),
     #[error("Unsupported paging mode")]
     UnsupportedPagingMode(),
+    #[error("Guest physical address space of the confidential VM is larger than the hardware can translate")]
+    GuestPhysicalAddressSpaceTooLarge(),
+    #[error("PMP entry {0} is locked by the firmware")]
+    PmpEntryLocked(usize),
+    #[error("PMP configuration does not deny accesses to the confidential memory")]
+    PmpConfigurationFailed(),
+    #[error("CSR {0:x} diverged from its value stored in the main memory: expected {1:x}, found {2:x}")]
+    InconsistentCsr(u16, usize, usize),
     #[error("Memory access not authorized")]
     MemoryAccessAuthorization(),
     #[error("There is a pending request")]
     PendingRequest(),
     #[error("Invalid Hart ID")]
     InvalidHartId(),
+    #[error("The number of harts does not match the device tree")]
+    InvalidNumberOfHarts(),
     #[error("Exceeded the max number of harts per VM")]
     ReachedMaxNumberOfHartsPerVm(),
     #[error("Invalid confidential VM ID")]
@@ -55,16 +96,64 @@ pub enum Error {
     HartAlreadyRunning(),
     #[error("Hart is not executable")]
     HartNotExecutable(),
+    #[error("Confidential VM is paused")]
+    ConfidentialVmPaused(),
+    #[error("Cannot suspend the system while confidential VMs exist")]
+    SuspendWithConfidentialVms(),
+    #[error("Confidential VM has already been finalized")]
+    ConfidentialVmAlreadyFinalized(),
+    #[error("Confidential VM has no runnable boot hart")]
+    NoBootHart(),
+    #[error("Measurement register does not exist")]
+    InvalidMeasurementRegister(),
+    #[error("Measurement register is locked")]
+    MeasurementRegisterLocked(),
+    #[error("Launch measurement of the confidential VM does not match the expected one")]
+    LaunchMeasurementMismatch(),
+    #[error("Signature over the kernel image of the confidential VM is invalid")]
+    LaunchSignatureVerificationFailed(),
+    #[error("Code region was not measured when the confidential VM was created")]
+    CodeRegionNotMeasured(),
+    #[error("Confidential VM declares too many code regions")]
+    TooManyCodeRegions(),
+    #[error("Attestation key is not available")]
+    AttestationKeyUnavailable(),
+    #[error("Sealed data is corrupted or was sealed by a confidential VM with a different measurement")]
+    SealedDataCorrupted(),
+    #[error("Sealing key is not released to debuggable confidential VMs")]
+    SealingKeyDenied(),
+    #[error("Attestation key material handed over by the previous boot stage is malformed")]
+    MalformedKeyHandover(),
     #[error("Invalid riscv instruction: {0:x}")]
     InvalidRiscvInstruction(usize),
+    #[error("Trap with cause {0:x} is delegated away from the security monitor")]
+    InvalidTrapDelegation(usize),
     #[error("Invalid call cause: {0}")]
     InvalidCall(usize),
+    #[error("Invalid argument")]
+    InvalidArgument(),
+    #[error("Confidential VM is not debuggable")]
+    NotDebuggableConfidentialVm(),
+    #[error("Invalid MMIO policy")]
+    InvalidMmioPolicy(),
+    #[error("Invalid CSR emulation policy")]
+    InvalidCsrEmulationPolicy(),
+    #[error("PMU event is not exposed to confidential VMs")]
+    UnsupportedPmuEvent(),
+    #[error("Supervisor software event is not exposed to confidential VMs")]
+    UnsupportedSseEvent(),
+    #[error("Supervisor software event is in a state that does not permit the operation")]
+    InvalidSseEventState(),
     #[error("Internal error")]
     Pointer(#[from] PointerError),
     #[error("Reached max number of remote hart requests")]
     ReachedMaxNumberOfRemoteHartRequests(),
     #[error("Sending interrupt error")]
     InterruptSendingError(),
+    #[error("OpenSBI returned an invalid trap frame")]
+    InvalidOpensbiResult(),
+    #[error("SBI call failed: {0:?}, a1={1:x}")]
+    SbiCallFailed(SbiError, usize),
     // SBI HSM extension related errors
     #[error("Cannot start a confidential hart because it is not in the Stopped state.")]
     CannotStartNotStoppedHart(),
@@ -98,6 +187,24 @@ pub enum InitType {
     MemoryBoundary,
 }
 
+#[derive(Error, Debug)]
+pub enum PageTableViolation {
+    #[error("reserved bits are set")]
+    ReservedBitsSet,
+    #[error("write-only page")]
+    WriteOnlyPage,
+    #[error("page is not aligned to its size")]
+    MisalignedPage,
+    #[error("lowest-level entry points to another page table")]
+    PointerAtLowestLevel,
+    #[error("entry points outside of the non-confidential memory")]
+    MemoryNotOwned,
+    #[error("page table is referenced more than once")]
+    PageTableReused,
+    #[error("memory is already mapped at another guest physical address")]
+    AliasedPage,
+}
+
 #[derive(Error, Debug)]
 pub enum HardwareFeatures {
     #[error("ACE requires 64-bit processor")]
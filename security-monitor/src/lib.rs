@@ -1,8 +1,10 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-#![no_std]
-#![no_main]
+// Unit tests link against the standard library, which provides the panic handler and the allocator. The security monitor contains
+// RISC-V assembly, so the tests must be built for a RISC-V target.
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 #![crate_type = "staticlib"]
 // used for meaningful panic code
 #![feature(panic_info_message)]
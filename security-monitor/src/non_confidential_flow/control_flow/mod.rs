@@ -3,9 +3,12 @@
 // SPDX-License-Identifier: Apache-2.0
 use crate::confidential_flow::ConfidentialFlow;
 use crate::core::architecture::AceExtension::*;
+use crate::core::architecture::NaclExtension::*;
 use crate::core::architecture::SbiExtension::*;
+use crate::core::architecture::SuspExtension::*;
+use crate::core::architecture::TeeHostExtension::*;
 use crate::core::architecture::TrapCause::*;
-use crate::core::control_data::{ControlData, HardwareHart};
+use crate::core::control_data::{ControlData, HardwareHart, NaclSharedMemory, TraceBuffer, TraceEvent, TRACE_BUFFER_CAPACITY};
 use crate::core::transformations::{ExposeToHypervisor, ResumeRequest};
 use crate::error::Error;
 use crate::non_confidential_flow::handlers::*;
@@ -39,6 +42,9 @@ impl<'a> NonConfidentialFlow<'a> {
     extern "C" fn route_non_confidential_flow(hart_ptr: *mut HardwareHart) -> ! {
         let hardware_hart = unsafe { hart_ptr.as_mut().expect(crate::error::CTX_SWITCH_ERROR_MSG) };
         hardware_hart.store_volatile_control_status_registers_in_main_memory();
+        hardware_hart.guard_stack();
+        hardware_hart.hypervisor_memory_protector_mut().revoke_expired_temporary_access();
+        hardware_hart.hypervisor_memory_protector_mut().synchronize_pmp_configuration();
         let control_flow = Self::create(hardware_hart);
 
         match control_flow.hardware_hart.trap_reason() {
@@ -51,9 +57,95 @@ impl<'a> NonConfidentialFlow<'a> {
             HsEcall(Ace(ResumeConfidentialHart)) => {
                 resume_confidential_hart::handle(control_flow.hardware_hart.resume_request(), control_flow)
             }
+            HsEcall(Ace(ConfidentialHartRunstate)) => {
+                confidential_hart_runstate::handle(control_flow.hardware_hart.hart_runstate_request(), control_flow)
+            }
+            HsEcall(Ace(ConfidentialHartExit)) => {
+                confidential_hart_exit::handle(control_flow.hardware_hart.hart_runstate_request(), control_flow)
+            }
+            HsEcall(Ace(CreateConfidentialVm)) => {
+                create_confidential_vm::handle(control_flow.hardware_hart.create_confidential_vm_request(), control_flow)
+            }
+            HsEcall(Ace(AddConfidentialVmMemory)) => {
+                add_confidential_vm_memory::handle(control_flow.hardware_hart.add_memory_region_request(), control_flow)
+            }
+            HsEcall(Ace(AddConfidentialHart)) => add_confidential_hart::handle(control_flow.hardware_hart.add_hart_request(), control_flow),
+            HsEcall(Ace(FinalizeConfidentialVm)) => {
+                finalize_confidential_vm::handle(control_flow.hardware_hart.finalize_request(), control_flow)
+            }
             HsEcall(Ace(TerminateConfidentialVm)) => {
                 terminate_confidential_vm::handle(control_flow.hardware_hart.terminate_request(), control_flow)
             }
+            HsEcall(Ace(ReclaimConfidentialVmMemory)) => {
+                reclaim_confidential_vm_memory::handle(control_flow.hardware_hart.reclaim_memory_request(), control_flow)
+            }
+            HsEcall(Ace(RotateConfidentialVmMemoryKey)) => {
+                rotate_confidential_vm_memory_key::handle(control_flow.hardware_hart.rotate_memory_key_request(), control_flow)
+            }
+            HsEcall(Ace(GetSecurityMonitorInfo)) => {
+                get_security_monitor_info::handle(control_flow.hardware_hart.security_monitor_info_request(), control_flow)
+            }
+            HsEcall(Ace(GetMemoryInfo)) => get_memory_info::handle(control_flow.hardware_hart.get_memory_info_request(), control_flow),
+            HsEcall(Ace(GetConfidentialVmMemoryInfo)) => {
+                get_vm_memory_info::handle(control_flow.hardware_hart.get_vm_memory_info_request(), control_flow)
+            }
+            HsEcall(Ace(GetConfidentialVmMeasurement)) => {
+                get_vm_measurement::handle(control_flow.hardware_hart.get_vm_measurement_request(), control_flow)
+            }
+            HsEcall(Ace(ReadConfidentialHartRegister)) => {
+                read_confidential_hart_register::handle(control_flow.hardware_hart.read_register_request(), control_flow)
+            }
+            HsEcall(Ace(WriteConfidentialHartRegister)) => {
+                write_confidential_hart_register::handle(control_flow.hardware_hart.write_register_request(), control_flow)
+            }
+            HsEcall(Ace(ConvertToConfidentialMemory)) => {
+                convert_to_confidential_memory::handle(control_flow.hardware_hart.convert_to_confidential_request(), control_flow)
+            }
+            HsEcall(Ace(ReleaseConfidentialMemory)) => {
+                release_confidential_memory::handle(control_flow.hardware_hart.reclaim_to_non_confidential_request(), control_flow)
+            }
+            HsEcall(Ace(GlobalMemoryFence)) => global_memory_fence::handle(control_flow),
+            HsEcall(Ace(ConfirmMemoryConversion)) => {
+                confirm_memory_conversion::handle(control_flow.hardware_hart.memory_conversion_request(), control_flow)
+            }
+            #[cfg(feature = "declassification_log")]
+            HsEcall(Ace(ReadDeclassificationLog)) => {
+                read_declassification_log::handle(control_flow.hardware_hart.declassification_log_request(), control_flow)
+            }
+            HsEcall(Ace(ReadTlbFenceCounters)) => {
+                read_tlb_fence_counters::handle(control_flow.hardware_hart.tlb_fence_counters_request(), control_flow)
+            }
+            HsEcall(Ace(ReadAccessedDirtyCounters)) => {
+                read_accessed_dirty_counters::handle(control_flow.hardware_hart.accessed_dirty_counters_request(), control_flow)
+            }
+            HsEcall(Ace(ReadTraceBuffer)) => read_trace_buffer::handle(control_flow.hardware_hart.trace_buffer_request(), control_flow),
+            #[cfg(feature = "pmp_audit_log")]
+            HsEcall(Ace(ReadPmpAuditLog)) => read_pmp_audit_log::handle(control_flow.hardware_hart.pmp_audit_log_request(), control_flow),
+            HsEcall(Susp(SystemSuspend)) => system_suspend::handle(control_flow.hardware_hart.opensbi_request(), control_flow),
+            HsEcall(Nacl(SetSharedMemory)) => {
+                set_nacl_shared_memory::handle(control_flow.hardware_hart.nacl_shared_memory_request(), control_flow)
+            }
+            HsEcall(TeeHost(ConvertPages)) => {
+                convert_to_confidential_memory::handle(control_flow.hardware_hart.convert_pages_request(), control_flow)
+            }
+            HsEcall(TeeHost(ReclaimPages)) => {
+                release_confidential_memory::handle(control_flow.hardware_hart.reclaim_pages_request(), control_flow)
+            }
+            HsEcall(TeeHost(GlobalFence)) => global_memory_fence::handle(control_flow),
+            HsEcall(TeeHost(CreateTvm)) => create_confidential_vm::handle(control_flow.hardware_hart.create_tvm_request(), control_flow),
+            HsEcall(TeeHost(FinalizeTvm)) => {
+                finalize_confidential_vm::handle(control_flow.hardware_hart.finalize_tvm_request(), control_flow)
+            }
+            HsEcall(TeeHost(DestroyTvm)) => destroy_confidential_vm::handle(control_flow.hardware_hart.destroy_tvm_request(), control_flow),
+            HsEcall(TeeHost(AddTvmMeasuredPages)) => {
+                add_confidential_vm_memory::handle(control_flow.hardware_hart.add_tvm_measured_pages_request(), control_flow)
+            }
+            HsEcall(TeeHost(CreateTvmVcpu)) => {
+                add_confidential_hart::handle(control_flow.hardware_hart.create_tvm_vcpu_request(), control_flow)
+            }
+            HsEcall(TeeHost(RunTvmVcpu)) => {
+                resume_confidential_hart::handle(control_flow.hardware_hart.run_tvm_vcpu_request(), control_flow)
+            }
             HsEcall(_) => delegate_to_opensbi::handle(control_flow.hardware_hart.opensbi_request(), control_flow),
             VsEcall(Ace(PromoteToConfidentialVm)) => {
                 promote_to_confidential_vm::handle(control_flow.hardware_hart.promote_to_confidential_vm_request(), control_flow)
@@ -65,10 +157,19 @@ impl<'a> NonConfidentialFlow<'a> {
     }
 
     pub fn into_confidential_flow(self, resume_request: ResumeRequest) -> (NonConfidentialFlow<'a>, Error) {
-        match ControlData::try_confidential_vm(resume_request.confidential_vm_id(), |mut confidential_vm| {
-            confidential_vm.steal_confidential_hart(resume_request.confidential_hart_id(), self.hardware_hart)
-        }) {
-            Ok(_) => ConfidentialFlow::resume_confidential_hart_execution(self.hardware_hart),
+        match ControlData::steal_confidential_hart(
+            resume_request.confidential_vm_id(),
+            resume_request.confidential_hart_id(),
+            self.hardware_hart,
+        ) {
+            Ok(_) => {
+                self.hardware_hart.emit_trace_event(TraceEvent::ContextSwitch {
+                    confidential_vm_id: resume_request.confidential_vm_id().usize(),
+                    confidential_hart_id: resume_request.confidential_hart_id(),
+                    into_confidential_hart: true,
+                });
+                ConfidentialFlow::resume_confidential_hart_execution(self.hardware_hart)
+            }
             Err(error) => (self, error),
         }
     }
@@ -76,9 +177,27 @@ impl<'a> NonConfidentialFlow<'a> {
     pub fn exit_to_hypervisor(self, transformation: ExposeToHypervisor) -> ! {
         self.hardware_hart.apply(&transformation);
         self.hardware_hart.load_volatile_control_status_registers_from_main_memory();
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.hardware_hart.check_csrs_consistency() {
+            panic!("Bug: Exiting to the hypervisor with corrupted CSRs: {:?}", error);
+        }
         unsafe { exit_to_hypervisor_asm() }
     }
 
+    /// Registers the shared memory of the NACL extension for the physical hart. `None` unregisters the shared memory.
+    pub fn set_nacl_shared_memory(&mut self, nacl_shared_memory: Option<NaclSharedMemory>) {
+        self.hardware_hart.set_nacl_shared_memory(nacl_shared_memory)
+    }
+
+    pub fn trace_buffer(&self) -> &TraceBuffer<TRACE_BUFFER_CAPACITY> {
+        self.hardware_hart.trace_buffer()
+    }
+
+    #[cfg(feature = "pmp_audit_log")]
+    pub fn pmp_audit_log(&self) -> &crate::core::memory_protector::AuditLog {
+        self.hardware_hart.hypervisor_memory_protector().audit_log()
+    }
+
     /// Swaps the mscratch register value with the original mascratch value used by OpenSBI. This function must be
     /// called before executing any OpenSBI function. We can remove this once we get rid of the OpenSBI firmware.
     pub fn swap_mscratch(&mut self) {
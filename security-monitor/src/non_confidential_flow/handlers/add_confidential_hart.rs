@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{AddHartRequest, ExposeToHypervisor, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to add a confidential hart to a confidential VM under construction. Confidential harts must be added in
+/// the order of their ids, starting from the boot hart. The hart's entry state extends the confidential VM's measurement.
+pub fn handle(add_hart_request: AddHartRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_builder(add_hart_request.confidential_vm_id(), |mut builder| {
+        builder.add_hart(add_hart_request.confidential_hart_id(), add_hart_request.start_address(), add_hart_request.opaque())
+    })
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{AddMemoryRegionRequest, ExposeToHypervisor, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to copy a memory region from the non-confidential memory into a confidential VM under construction. The
+/// content of every page and its guest physical address extend the confidential VM's measurement.
+pub fn handle(add_memory_region_request: AddMemoryRegionRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm_builder(add_memory_region_request.confidential_vm_id(), |mut builder| {
+        builder.add_memory_region(
+            add_memory_region_request.address(),
+            add_memory_region_request.source_address(),
+            add_memory_region_request.number_of_pages(),
+        )
+    })
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, HartRunstateRequest, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor polls why a confidential hart last exited, e.g., after the confidential hart returned from the CoVE `tvm_vcpu_run`
+/// call. The call returns the code of `PendingExit`, or 0 if the last exit only returned a result to the hypervisor.
+pub fn handle(request: HartRunstateRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(request.confidential_vm_id(), |confidential_vm| {
+        confidential_vm.confidential_hart_pending_exit(request.confidential_hart_id())
+    })
+    .and_then(|pending_exit| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(pending_exit.map_or(0, |exit| exit.code())))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, HartRunstateRequest, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor polls the runstate of a confidential hart to decide whether resuming it makes sense, e.g., a confidential hart blocked
+/// on MMIO should not be resumed until the hypervisor emulated the access. The call returns the code of `VcpuRunstate`.
+pub fn handle(request: HartRunstateRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(request.confidential_vm_id(), |confidential_vm| {
+        confidential_vm.confidential_hart_runstate(request.confidential_hart_id())
+    })
+    .and_then(|runstate| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(runstate.code()))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::{ConversionState, MemoryLayout};
+use crate::core::memory_protector::HypervisorMemoryProtector;
+use crate::core::page_allocator::PageAllocator;
+use crate::core::transformations::{ExposeToHypervisor, MemoryConversionRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to complete the conversion or release of a memory region. Returns 1 if the region reached its final state,
+/// and 0 if some harts have not yet applied the PMP configuration of the global memory fence, in which case the hypervisor should make
+/// them trap and repeat this call. Returns error if no fence was requested since the region was marked, because no hart is then
+/// guaranteed to ever drop its stale access to the region.
+pub fn handle(request: MemoryConversionRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = confirm_memory_conversion(request)
+        .and_then(|is_completed| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(is_completed as usize))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn confirm_memory_conversion(request: MemoryConversionRequest) -> Result<bool, Error> {
+    let memory_layout = MemoryLayout::read();
+    let region =
+        memory_layout.converted_memory_region(request.start_address(), request.size_in_bytes()).ok_or(Error::InvalidMemoryRegion())?;
+    if region.state() == ConversionState::Confidential {
+        return Ok(true);
+    }
+    region.assure_confirmable()?;
+    if !HypervisorMemoryProtector::is_pmp_synchronized() {
+        return Ok(false);
+    }
+
+    match region.state() {
+        ConversionState::Converting => {
+            // Only one hart can win the transition, so the region is added to the page allocator exactly once.
+            if memory_layout.change_converted_memory_region_state(&region, ConversionState::Converting, ConversionState::Confidential) {
+                // All harts deny the hypervisor access to the region, so its content cannot change anymore.
+                let (memory_start, memory_end) = memory_layout.converted_memory_region_boundary(&region)?;
+                // Safety: the security monitor owns the region and no page token describing it exists yet.
+                unsafe {
+                    memory_layout.clear_converted_memory_region(&region);
+                    PageAllocator::extend(memory_start, memory_end)?;
+                }
+                debug!("Converted memory region {:x}-{:x} into confidential memory", region.start(), region.end());
+            }
+        }
+        ConversionState::Releasing => {
+            if memory_layout.remove_converted_memory_region(&region) {
+                debug!("Released memory region {:x}-{:x} to the hypervisor", region.start(), region.end());
+            }
+        }
+        ConversionState::Confidential => {}
+    }
+    Ok(true)
+}
@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::memory_layout::{ConversionState, MemoryLayout};
+use crate::core::transformations::{ConvertToConfidentialRequest, ExposeToHypervisor, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to mark a naturally aligned, power-of-two sized region of its memory for the conversion into the confidential
+/// memory. This is the first step of the two-step protocol: the security monitor takes the ownership of the region immediately, but the
+/// region is added to the page allocator only after the hypervisor requested a global memory fence, made all harts trap (e.g., by
+/// sending an IPI), and confirmed the conversion.
+pub fn handle(request: ConvertToConfidentialRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = convert_to_confidential_memory(request)
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn convert_to_confidential_memory(request: ConvertToConfidentialRequest) -> Result<(), Error> {
+    let memory_layout = MemoryLayout::read();
+    memory_layout.add_converted_memory_region(request.start_address(), request.size_in_bytes())?;
+    let region =
+        memory_layout.converted_memory_region(request.start_address(), request.size_in_bytes()).ok_or(Error::InvalidMemoryRegion())?;
+    // A confidential VM must not get access to the confidential memory via a page it shares with the hypervisor. No page in this
+    // region can be shared anymore, so it is enough to check the pages that are already shared.
+    if ControlData::is_shared_with_confidential_vms(region.start(), region.end())? {
+        memory_layout.change_converted_memory_region_state(&region, ConversionState::Converting, ConversionState::Releasing);
+        memory_layout.remove_converted_memory_region(&region);
+        return Err(Error::MemoryRegionInUse());
+    }
+    Ok(())
+}
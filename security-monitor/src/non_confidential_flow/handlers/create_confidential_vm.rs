@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::{ConfidentialVmBuilder, ConfidentialVmId, ControlData};
+use crate::core::transformations::{CreateConfidentialVmRequest, ExposeToHypervisor, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to create an empty confidential VM, which is the first step of the staged construction of a confidential VM.
+/// The hypervisor then adds memory and harts to it and finalizes it. Returns the id of the confidential VM in `a1`.
+///
+/// The hypervisor passes flags in `a0`, where bit 0 makes the confidential VM debuggable and bit 1 makes it map memory on demand, and
+/// the end of the confidential VM's guest physical address space in `a1` (0 if unknown). The security monitor selects the smallest
+/// G-stage translation mode covering that space and fails if the hardware cannot translate it.
+///
+/// Unlike the `promote to confidential VM` call, this call does not require a running VM, so large images can be loaded in pieces.
+pub fn handle(create_confidential_vm_request: CreateConfidentialVmRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = create_confidential_vm(create_confidential_vm_request)
+        .and_then(|id| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(id.usize()))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn create_confidential_vm(create_confidential_vm_request: CreateConfidentialVmRequest) -> Result<ConfidentialVmId, Error> {
+    ControlData::try_write(|control_data| {
+        let id = control_data.unique_id()?;
+        let builder = ConfidentialVmBuilder::new(
+            id,
+            create_confidential_vm_request.is_debuggable(),
+            create_confidential_vm_request.maps_memory_on_demand(),
+            create_confidential_vm_request.guest_physical_address_end(),
+        )?;
+        control_data.insert_confidential_vm_builder(builder)
+    })
+}
@@ -18,6 +18,9 @@ pub fn handle(mut opensbi_request: OpensbiRequest, mut non_confidential_flow: No
     unsafe { sbi_trap_handler(&mut opensbi_request.regs as *mut _) };
     non_confidential_flow.swap_mscratch();
 
-    let transformation = ExposeToHypervisor::OpensbiResult(OpensbiResult::from_opensbi_handler(opensbi_request.regs));
+    // A corrupted trap frame must not bring down the security monitor, so we return an error to the hypervisor instead.
+    let transformation = OpensbiResult::from_opensbi_handler(opensbi_request.regs)
+        .map(ExposeToHypervisor::OpensbiResult)
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
     non_confidential_flow.exit_to_hypervisor(transformation)
 }
@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, SbiResult, TerminateRequest};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The CoVE hypervisor command to destroy a confidential VM. Unlike the ACE termination, which the hypervisor follows with batched
+/// reclaim calls, CoVE has no reclaim step bound to a confidential VM, so the confidential VM's memory is reclaimed entirely within
+/// this call. Afterwards, the hypervisor can release the confidential memory back to the non-confidential memory.
+pub fn handle(terminate_request: TerminateRequest, mut non_confidential_flow: NonConfidentialFlow) -> ! {
+    let confidential_vm_id = terminate_request.confidential_vm_id();
+    // Pages are released only after translations have been fenced on all harts, which requires sending IPIs via OpenSBI, which expects
+    // its own value in mscratch.
+    non_confidential_flow.swap_mscratch();
+    let result = ControlData::remove_confidential_vm(confidential_vm_id).and_then(|_| {
+        match ControlData::reclaim_confidential_vm_memory(confidential_vm_id, usize::MAX) {
+            // A confidential VM under construction is released as soon as it is removed, so there is nothing left to reclaim.
+            Err(Error::InvalidConfidentialVmId()) => Ok(true),
+            result => result,
+        }
+    });
+    non_confidential_flow.swap_mscratch();
+
+    let transformation = result
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, FinalizeRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command that completes the staged construction of a confidential VM. After this call, the measurement of the
+/// confidential VM is final, its content cannot be changed anymore, and the hypervisor can resume its confidential harts.
+///
+/// If the hypervisor provided an expected launch digest that differs from the computed one, the confidential VM is destroyed and
+/// `Error::LaunchMeasurementMismatch` is returned.
+pub fn handle(finalize_request: Result<FinalizeRequest, Error>, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let result = finalize_request.and_then(|request| ControlData::finalize_confidential_vm(&request));
+
+    let transformation = result
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,38 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::PageSize;
+use crate::core::page_allocator::PageAllocator;
+use crate::core::transformations::{ExposeToHypervisor, GetMemoryInfoRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor reads how much confidential memory is left, so that it can size confidential VMs, or convert more memory, before
+/// an allocation fails. The buffer receives one word with the total number of 4KiB pages of the confidential memory, followed by one
+/// word per page size, from the smallest to the largest, with the number of pages of that size that can still be allocated (see
+/// `PageAllocator::free_page_count`). The number of free 4KiB pages is also returned in the result register, so a hypervisor that
+/// needs only this number can pass a null buffer address.
+pub fn handle(request: GetMemoryInfoRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = match request.buffer_address() {
+        0 => Ok(()),
+        buffer_address => write_to_hypervisor_memory(buffer_address),
+    }
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(PageAllocator::free_page_count(PageSize::smallest())))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    let free_page_counts = PageSize::all_from_largest_to_smallest().into_iter().rev().map(PageAllocator::free_page_count);
+    core::iter::once(PageAllocator::total_page_count()).chain(free_page_counts).enumerate().try_for_each(|(word_index, value)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(value) };
+        Ok(())
+    })
+}
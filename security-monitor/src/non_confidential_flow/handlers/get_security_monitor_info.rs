@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::attestation::{TcbInfo, SECURITY_VERSION_NUMBER};
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::transformations::{ExposeToHypervisor, SbiResult, SecurityMonitorInfoRequest};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor reads the TCB information of the security monitor, i.e., its version, security version number (SVN), and build
+/// identity. It is the same information that the security monitor embeds in attestation reports, so the host can decide whether
+/// the security monitor is recent enough before it hands over any secrets. The SVN is also returned in the result register, so a
+/// hypervisor that only compares the SVN can pass a null buffer address.
+pub fn handle(request: SecurityMonitorInfoRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = match request.buffer_address() {
+        0 => Ok(()),
+        buffer_address => write_to_hypervisor_memory(buffer_address, TcbInfo::current().as_bytes()),
+    }
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(SECURITY_VERSION_NUMBER as usize))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, tcb_info: &[u8; TcbInfo::SIZE_IN_BYTES]) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    tcb_info.chunks_exact(core::mem::size_of::<usize>()).enumerate().try_for_each(|(word_index, chunk)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        let value = usize::from_le_bytes(chunk.try_into().map_err(|_| Error::InvalidArgument())?);
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(value) };
+        Ok(())
+    })
+}
@@ -0,0 +1,38 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::measurement::Sha384;
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::transformations::{ExposeToHypervisor, GetVmMeasurementRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor reads a launch measurement of a confidential VM, e.g., to check it against a policy before engaging a remote
+/// verifier. Launch measurements are digests of the content the hypervisor itself provided, so they do not reveal any confidential
+/// information. Runtime measurements are not exposed because they reflect what the confidential VM did during its execution. Only
+/// finalized confidential VMs are visible, because the measurements of a confidential VM under construction are not final yet.
+pub fn handle(request: GetVmMeasurementRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(request.confidential_vm_id(), |confidential_vm| {
+        confidential_vm.measurements().read_launch_measurement(request.index())
+    })
+    .and_then(|measurement| write_to_hypervisor_memory(request.buffer_address(), &measurement))
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, digest: &[u8; Sha384::DIGEST_SIZE_IN_BYTES]) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    digest.chunks_exact(core::mem::size_of::<usize>()).enumerate().try_for_each(|(word_index, chunk)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        // The chunk has exactly the size of the word, so the conversion never fails.
+        let value = usize::from_le_bytes(chunk.try_into().map_err(|_| Error::InvalidArgument())?);
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(value) };
+        Ok(())
+    })
+}
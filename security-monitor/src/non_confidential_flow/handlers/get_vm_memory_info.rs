@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::transformations::{ExposeToHypervisor, GetVmMemoryInfoRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor reads how much confidential memory a confidential VM uses, e.g., to find out why mapping more memory fails with the
+/// page table limit. The buffer receives three words: the number of 4KiB pages used by the confidential VM's page tables, the number
+/// of 4KiB pages they map, and the maximum number of page table pages the confidential VM may currently use. The numbers reveal only
+/// the layout of the guest physical address space, which the hypervisor already knows.
+pub fn handle(request: GetVmMemoryInfoRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(request.confidential_vm_id(), |confidential_vm| {
+        let usage = confidential_vm.memory_protector().page_table_usage();
+        Ok([usage.page_table_pages(), usage.data_pages(), usage.max_page_table_pages()])
+    })
+    .and_then(|words| write_to_hypervisor_memory(request.buffer_address(), &words))
+    .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, words: &[usize]) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    words.iter().enumerate().try_for_each(|(word_index, value)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(*value) };
+        Ok(())
+    })
+}
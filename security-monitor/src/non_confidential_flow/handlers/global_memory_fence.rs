@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::MemoryLayout;
+use crate::core::memory_protector::HypervisorMemoryProtector;
+use crate::core::transformations::{ExposeToHypervisor, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to start a global memory fence covering all memory regions marked for the conversion or release. Every hart
+/// applies the new PMP configuration, and flushes its stale translations, on its next trap into the security monitor. The hypervisor
+/// should therefore make all harts trap and then confirm the conversion of each marked region.
+pub fn handle(non_confidential_flow: NonConfidentialFlow) -> ! {
+    // Regions must be marked before the synchronization is requested, so that a hart applying the new configuration also observes
+    // them as fenced.
+    MemoryLayout::read().fence_converted_memory_regions();
+    HypervisorMemoryProtector::request_pmp_synchronization();
+    non_confidential_flow.exit_to_hypervisor(ExposeToHypervisor::SbiResult(SbiResult::success(0)))
+}
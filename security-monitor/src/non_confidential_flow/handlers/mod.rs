@@ -1,8 +1,36 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
+pub mod add_confidential_hart;
+pub mod add_confidential_vm_memory;
+pub mod confidential_hart_exit;
+pub mod confidential_hart_runstate;
+pub mod confirm_memory_conversion;
+pub mod convert_to_confidential_memory;
+pub mod create_confidential_vm;
 pub mod delegate_hypercall;
 pub mod delegate_to_opensbi;
+pub mod destroy_confidential_vm;
+pub mod finalize_confidential_vm;
+pub mod get_memory_info;
+pub mod get_security_monitor_info;
+pub mod get_vm_measurement;
+pub mod get_vm_memory_info;
+pub mod global_memory_fence;
 pub mod promote_to_confidential_vm;
+pub mod read_accessed_dirty_counters;
+pub mod read_confidential_hart_register;
+#[cfg(feature = "declassification_log")]
+pub mod read_declassification_log;
+#[cfg(feature = "pmp_audit_log")]
+pub mod read_pmp_audit_log;
+pub mod read_tlb_fence_counters;
+pub mod read_trace_buffer;
+pub mod reclaim_confidential_vm_memory;
+pub mod release_confidential_memory;
 pub mod resume_confidential_hart;
+pub mod rotate_confidential_vm_memory_key;
+pub mod set_nacl_shared_memory;
+pub mod system_suspend;
 pub mod terminate_confidential_vm;
+pub mod write_confidential_hart_register;
@@ -1,15 +1,30 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::control_data::{ConfidentialHart, ConfidentialVm, ConfidentialVmId, ConfidentialVmMeasurement, ControlData};
-use crate::core::memory_protector::ConfidentialVmMemoryProtector;
+use crate::core::control_data::{
+    ConfidentialHart, ConfidentialVm, ConfidentialVmId, ConfidentialVmMeasurement, ControlData, CsrEmulationPolicy, MeasuredComponent,
+    MeasurementLog, MeasurementRegisters, MmioPolicy,
+};
+use crate::core::crypto::{ed25519_verify, ED25519_PUBLIC_KEY_SIZE_IN_BYTES, ED25519_SIGNATURE_SIZE_IN_BYTES};
+use crate::core::measurement::Sha384;
+use crate::core::memory_encryption::MemoryKeySlot;
+use crate::core::memory_layout::ConfidentialVmPhysicalAddress;
+use crate::core::memory_protector::{ConfidentialVmMemoryProtector, PageSize};
 use crate::core::transformations::{ExposeToHypervisor, PromoteToConfidentialVm, SbiRequest};
 use crate::error::Error;
 use crate::non_confidential_flow::NonConfidentialFlow;
+use alloc::vec::Vec;
 use flattened_device_tree::FlattenedDeviceTree;
 
 /// Our convention is to give the boot hart a fixed id.
 const BOOT_HART_ID: usize = 0;
+const MEMORY_MEASUREMENT_INDEX: usize = 0;
+const FDT_MEASUREMENT_INDEX: usize = 1;
+const INITRD_MEASUREMENT_INDEX: usize = 2;
+const CONFIGURATION_MEASUREMENT_INDEX: usize = 3;
+/// The launch signature structure consists of the kernel's guest physical address (u64), the kernel's size (u64), the signer's public
+/// key, and the signature, all laid out without padding.
+const LAUNCH_SIGNATURE_SIZE_IN_BYTES: usize = 2 * 8 + ED25519_PUBLIC_KEY_SIZE_IN_BYTES + ED25519_SIGNATURE_SIZE_IN_BYTES;
 
 /// Handles the `promote to confidential VM` call requested by the non-confidential VM via an environment call. The call traps in the
 /// security monitor as an `environment call from VS-mode` (see `mcause` register specification). In a response to this call, the security
@@ -21,6 +36,9 @@ const BOOT_HART_ID: usize = 0;
 /// In case of a Linux kernel confidential VM, Linux kernel must make this call before 1) it uses parameters from the Linux command line, 2)
 /// before it changes the content of the VM's memory.
 ///
+/// If the VM passes the address of a launch signature structure, the security monitor verifies the Ed25519 signature over the digest
+/// of the kernel image before creating the confidential VM. The confidential VM is not created if the signature is invalid.
+///
 /// # Safety
 ///
 /// The virtual machine must make this call on a boot hart before other harts come out of reset.
@@ -43,10 +61,18 @@ fn create_confidential_vm(promote_to_confidential_vm_request: PromoteToConfident
     //
     // We use only the hart state of the currently executing hart, i.e., the hart that triggered the `promote to confidential VM call`. All
     // other harts are assumed to be in the reset state (safety requirement).
+    let is_debuggable = promote_to_confidential_vm_request.is_debuggable();
+    let requested_number_of_harts = promote_to_confidential_vm_request.number_of_harts();
+    let requested_initrd_region = promote_to_confidential_vm_request.initrd_region();
+    let launch_signature_address = promote_to_confidential_vm_request.launch_signature_address();
     let (fdt_address, hart_state) = promote_to_confidential_vm_request.into();
 
-    // Copy the entire VM's state to the confidential memory, recreating the MMU configuration.
+    // Copy the entire VM's state to the confidential memory, recreating the MMU configuration. Every resource acquired from now on is
+    // owned by a value whose drop releases it (copied pages, page tables, the memory key slot), so returning an error at any step below
+    // rolls back everything acquired so far.
     let memory_protector = ConfidentialVmMemoryProtector::from_vm_state(&hart_state)?;
+    #[cfg(feature = "verbose")]
+    memory_protector.dump_mappings();
 
     // Below use of unsafe is ok because (1) the security monitor owns the memory region containing the data of the not-yet-created
     // confidential VM's and (2) there is only one physical hart executing this code.
@@ -56,28 +82,100 @@ fn create_confidential_vm(promote_to_confidential_vm_request: PromoteToConfident
     // `FlattenedDeviceTree::from_raw_pointer`).
     let device_tree = unsafe { FlattenedDeviceTree::from_raw_pointer(fdt_address_in_confidential_memory)? };
 
-    // We create a fixed number of harts (all but the boot hart are in the reset state) according to the FDT configuration. An alternative
-    // approach (to discuss) is to create just a boot hart and then allow creation of more harts when getting a call from the confidential
-    // VM to start a hart.
-    let number_of_confidential_harts = device_tree.harts().count();
-    assure!(number_of_confidential_harts < ConfidentialVm::MAX_NUMBER_OF_HARTS_PER_VM, Error::ReachedMaxNumberOfHartsPerVm())?;
+    // We create exactly as many harts (all but the boot hart are in the reset state) as the VM requested. The device tree describes the
+    // harts to the guest, so it must declare the same number of harts. Otherwise, the guest would try to use harts that do not exist or
+    // never use some of the harts that the security monitor created.
+    let number_of_harts_in_device_tree = device_tree.harts().count();
+    let number_of_confidential_harts = requested_number_of_harts.unwrap_or(number_of_harts_in_device_tree);
+    assure!(number_of_confidential_harts == number_of_harts_in_device_tree, Error::InvalidNumberOfHarts())?;
+    assure!(number_of_confidential_harts > 0, Error::InvalidNumberOfHarts())?;
+    assure!(number_of_confidential_harts <= ConfidentialVm::MAX_NUMBER_OF_HARTS_PER_VM, Error::ReachedMaxNumberOfHartsPerVm())?;
     let confidential_harts = (0..number_of_confidential_harts)
         .map(|confidential_hart_id| match confidential_hart_id {
             0 => ConfidentialHart::from_vm_hart(confidential_hart_id, &hart_state),
             _ => ConfidentialHart::from_vm_hart_reset(confidential_hart_id, &hart_state),
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    // MMIO regions that must never be emulated by the hypervisor on behalf of the confidential VM are declared in the FDT.
+    let mmio_policy = MmioPolicy::from_device_tree(&device_tree)?;
+    // So are CSRs whose trapped accesses the security monitor emulates, e.g., to expose a per-VM `cycle` counter.
+    let csr_emulation_policy = CsrEmulationPolicy::from_device_tree(&device_tree)?;
+
+    // The first measurement register reflects the initial content of the confidential VM's memory. Pages are measured in the ascending
+    // order of guest physical addresses, so identical images always result in the same measurement. Every extension is recorded in the
+    // measurement log, so a verifier can attribute the final value to individual components.
+    let mut measurements = [ConfidentialVmMeasurement::empty(); MeasurementRegisters::NUMBER_OF_LAUNCH_REGISTERS];
+    let mut measurement_log = MeasurementLog::empty();
+    memory_protector.for_each_confidential_page(&mut |address, page| {
+        ConfidentialVmMeasurement::page_digests(address, page)?.iter().for_each(|(chunk_address, digest)| {
+            let region = Some((*chunk_address, PageSize::Size4KiB.in_bytes()));
+            measurement_log.extend(&mut measurements, MEMORY_MEASUREMENT_INDEX, MeasuredComponent::Memory, region, digest)
+        });
+        Ok(())
+    })?;
+    // The entry point and the initial registers of the boot hart determine what the measured image executes, so they are measured
+    // too. Otherwise, the hypervisor could resume the right kernel at a wrong instruction or with wrong arguments.
+    let boot_hart_digest = Sha384::digest(&confidential_harts[BOOT_HART_ID].boot_state());
+    measurement_log.extend(&mut measurements, MEMORY_MEASUREMENT_INDEX, MeasuredComponent::BootHart, None, &boot_hart_digest);
+    // Two images with the same content but different initial VS-stage page tables lay out the guest's address space differently. If the
+    // boot hart starts with the VS-stage translation enabled, its page tables are measured as well.
+    if let Some(digest) = memory_protector.digest_of_vs_stage_page_tables(hart_state.vsatp)? {
+        measurement_log.extend(&mut measurements, MEMORY_MEASUREMENT_INDEX, MeasuredComponent::VsStagePageTables, None, &digest);
+    }
+    // The device tree and the initial ramdisk define the kernel command line and the initial userspace, so they get dedicated
+    // registers that a relying party can check independently of the rest of the memory. We hash them after the VM's data has been
+    // copied to the confidential memory, so the hypervisor cannot change the measured bytes before the guest reads them.
+    let fdt_region = (fdt_address, device_tree.total_size());
+    let fdt_digest = memory_protector.digest_of_region(fdt_region.0, fdt_region.1)?;
+    measurement_log.extend(&mut measurements, FDT_MEASUREMENT_INDEX, MeasuredComponent::DeviceTree, Some(fdt_region), &fdt_digest);
+    let initrd_region = requested_initrd_region.or_else(|| {
+        let region = device_tree.initrd()?;
+        Some((ConfidentialVmPhysicalAddress::new(usize::try_from(region.base).ok()?), usize::try_from(region.size).ok()?))
+    });
+    if let Some((initrd_address, initrd_size)) = initrd_region {
+        let initrd_digest = memory_protector.digest_of_region(initrd_address, initrd_size)?;
+        let region = Some((initrd_address, initrd_size));
+        measurement_log.extend(&mut measurements, INITRD_MEASUREMENT_INDEX, MeasuredComponent::Initrd, region, &initrd_digest);
+    }
+    // Code regions declared in the FDT are hashed now, so that the guest can later detect modifications of its code (see
+    // `ConfidentialVm::verify_code_integrity`). The declaration is part of the measured FDT, so it is covered by the attestation.
+    assure!(device_tree.code_regions().count() <= ConfidentialVm::MAX_NUMBER_OF_CODE_REGIONS, Error::TooManyCodeRegions())?;
+    let code_regions = device_tree
+        .code_regions()
+        .map(|region| {
+            let address = ConfidentialVmPhysicalAddress::new(usize::try_from(region.base).map_err(|_| Error::InvalidArgument())?);
+            let size = usize::try_from(region.size).map_err(|_| Error::InvalidArgument())?;
+            Ok((address, size, memory_protector.digest_of_region(address, size)?))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
-    // TODO: measure the confidential VM
-    let measurements = [ConfidentialVmMeasurement::empty(); 4];
+    // The last measurement register reflects the configuration of the confidential VM. A debuggable confidential VM exposes its
+    // harts' state to the hypervisor, thus a relying party must be able to recognize it.
+    let configuration = ConfidentialVmMeasurement::from_configuration(is_debuggable);
+    measurement_log.assign_configuration(&mut measurements, CONFIGURATION_MEASUREMENT_INDEX, configuration);
 
     // TODO: perform local attestation (optional) if there is a `confidential VM's blob`
 
+    // The launch signature is read from the confidential memory, so the hypervisor cannot swap the kernel after it has been verified.
+    // If the verification fails, the memory protector is dropped, which releases all pages of the partially built confidential VM.
+    let launch_signer = launch_signature_address.map(|address| verify_launch_signature(&memory_protector, address)).transpose()?;
+
+    // From now on, the confidential VM's memory is encrypted with a key that is distinct from keys of other confidential VMs.
+    let memory_key_slot = MemoryKeySlot::assign(&memory_protector)?;
+
     let confidential_vm_id = ControlData::try_write(|control_data| {
         // We have a write lock on the entire control data! Spend as little time here as possible because we are
         // blocking all other harts from accessing the control data. This influences all confidential VMs in the system!
+        // The id is reserved and the confidential VM inserted in this single critical section, so there is no partially registered
+        // confidential VM to roll back if the insertion fails.
         let id = control_data.unique_id()?;
-        let confidential_vm = ConfidentialVm::new(id, confidential_harts, measurements, memory_protector);
+        let confidential_vm =
+            ConfidentialVm::new(id, confidential_harts, measurements, memory_protector, memory_key_slot, mmio_policy, is_debuggable)
+                .with_launch_signer(launch_signer)
+                .with_csr_emulation_policy(csr_emulation_policy)
+                .with_code_regions(code_regions)
+                .with_measurement_log(measurement_log);
         control_data.insert_confidential_vm(confidential_vm)
     })?;
 
@@ -85,3 +183,24 @@ fn create_confidential_vm(promote_to_confidential_vm_request: PromoteToConfident
 
     Ok(confidential_vm_id)
 }
+
+/// Verifies the launch signature structure located at the given address and returns the public key of the signer. The signed message
+/// is the digest of the kernel image as computed by `ConfidentialVmMemoryProtector::digest_of_region`.
+fn verify_launch_signature(
+    memory_protector: &ConfidentialVmMemoryProtector, address: ConfidentialVmPhysicalAddress,
+) -> Result<[u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES], Error> {
+    let mut launch_signature = [0u8; LAUNCH_SIGNATURE_SIZE_IN_BYTES];
+    memory_protector.read_bytes(address, &mut launch_signature)?;
+    let (kernel_address, rest) = launch_signature.split_at(8);
+    let (kernel_size, rest) = rest.split_at(8);
+    let (public_key, signature) = rest.split_at(ED25519_PUBLIC_KEY_SIZE_IN_BYTES);
+    let kernel_address = usize::from_le_bytes(kernel_address.try_into().map_err(|_| Error::InvalidArgument())?);
+    let kernel_size = usize::from_le_bytes(kernel_size.try_into().map_err(|_| Error::InvalidArgument())?);
+    assure!(kernel_size > 0, Error::InvalidArgument())?;
+    let public_key: [u8; ED25519_PUBLIC_KEY_SIZE_IN_BYTES] = public_key.try_into().map_err(|_| Error::InvalidArgument())?;
+    let signature: [u8; ED25519_SIGNATURE_SIZE_IN_BYTES] = signature.try_into().map_err(|_| Error::InvalidArgument())?;
+
+    let kernel_digest = memory_protector.digest_of_region(ConfidentialVmPhysicalAddress::new(kernel_address), kernel_size)?;
+    assure!(ed25519_verify(&public_key, &kernel_digest, &signature), Error::LaunchSignatureVerificationFailed())?;
+    Ok(public_key)
+}
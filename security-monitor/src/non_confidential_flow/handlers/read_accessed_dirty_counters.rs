@@ -0,0 +1,32 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::AccessedDirtyUpdate;
+use crate::core::transformations::{AccessedDirtyCountersRequest, ExposeToHypervisor, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Copies the number of accessed and dirty bits that the security monitor set in G-stage entries of confidential VMs to the hypervisor's
+/// buffer, one word per bit in this order (see `AccessedDirtyUpdate::counters`). Non-zero counters on hardware that implements the
+/// Svadu extension mean that hardware updates are disabled. Returns the total number of set bits.
+pub fn handle(request: AccessedDirtyCountersRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let counters = AccessedDirtyUpdate::counters();
+    let transformation = write_to_hypervisor_memory(request.buffer_address(), &counters)
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(counters.iter().sum()))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, counters: &[usize]) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    counters.iter().enumerate().try_for_each(|(word_index, value)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(*value) };
+        Ok(())
+    })
+}
@@ -0,0 +1,18 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, ReadRegisterRequest, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to read a register of a confidential hart. The call succeeds only for confidential VMs that were promoted
+/// as debuggable and only when the confidential hart is not running.
+pub fn handle(request: ReadRegisterRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = ControlData::try_confidential_vm(request.confidential_vm_id(), |confidential_vm| {
+        confidential_vm.read_confidential_hart_register(request.confidential_hart_id(), request.register()?)
+    })
+    .and_then(|value| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(value))))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::declassification_log;
+use crate::core::transformations::{DeclassificationLogRequest, ExposeToHypervisor, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Returns an entry of the declassification log, so that a reviewer can audit which registers carried confidential information to
+/// the hypervisor. Entries are indexed from the oldest one retained in the log. The entry is returned in an encoded form (see
+/// `DeclassificationEntry::encode`). An error is returned when there is no entry at the requested index.
+pub fn handle(declassification_log_request: DeclassificationLogRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = declassification_log::read(declassification_log_request.index())
+        .ok_or(Error::InvalidArgument())
+        .and_then(|entry| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(entry.encode()))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
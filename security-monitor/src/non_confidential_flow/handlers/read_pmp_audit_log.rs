@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::AuditLog;
+use crate::core::transformations::{ExposeToHypervisor, PmpAuditLogRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Copies the log of PMP configuration changes made on the physical hart executing this call to the hypervisor's buffer of
+/// `AuditLog::SIZE_IN_BYTES` bytes. Entries are written from the oldest to the most recent one and unused entries are zeroed.
+/// Returns the total number of changes recorded on this hart.
+pub fn handle(request: PmpAuditLogRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let audit_log = non_confidential_flow.pmp_audit_log();
+    let transformation = write_to_hypervisor_memory(request.buffer_address(), audit_log)
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(audit_log.number_of_records()))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, audit_log: &AuditLog) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    assure!(buffer_address.checked_add(AuditLog::SIZE_IN_BYTES).is_some(), Error::InvalidArgument())?;
+    let words = audit_log.encoded_entries().flatten().chain(core::iter::repeat(0));
+    (0..AuditLog::SIZE_IN_BYTES).step_by(core::mem::size_of::<usize>()).zip(words).try_for_each(|(offset, value)| {
+        let address = NonConfidentialMemoryAddress::new((buffer_address + offset) as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so the write cannot reach memory of the security monitor or of any
+        // confidential VM.
+        unsafe { address.write(value) };
+        Ok(())
+    })
+}
@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::memory_protector::TlbFence;
+use crate::core::transformations::{ExposeToHypervisor, SbiResult, TlbFenceCountersRequest};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Copies the number of global, VMID, and guest physical address fences of G-stage translations to the hypervisor's buffer, one word
+/// per fence type in this order (see `TlbFence::counters`). It helps to tell whether workloads that often share and unshare pages
+/// fall back to global fences. Returns the total number of fences, followed by the counters in `a2` to `a4` in the same order, so a
+/// hypervisor that reads the counters from registers can pass a null buffer address.
+pub fn handle(request: TlbFenceCountersRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let counters = TlbFence::counters();
+    let transformation = match request.buffer_address() {
+        0 => Ok(()),
+        buffer_address => write_to_hypervisor_memory(buffer_address, &counters),
+    }
+    .and_then(|_| SbiResult::success(counters.iter().sum()).with_extended_values(&counters))
+    .and_then(|result| Ok(ExposeToHypervisor::SbiResult(result)))
+    .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, counters: &[usize]) -> Result<(), Error> {
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    counters.iter().enumerate().try_for_each(|(word_index, value)| {
+        let address = buffer_address.checked_add(word_index * core::mem::size_of::<usize>()).ok_or(Error::InvalidArgument())?;
+        let address = NonConfidentialMemoryAddress::new(address as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(*value) };
+        Ok(())
+    })
+}
@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::{TraceBuffer, TRACE_BUFFER_CAPACITY};
+use crate::core::memory_layout::NonConfidentialMemoryAddress;
+use crate::core::transformations::{ExposeToHypervisor, SbiResult, TraceBufferRequest};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Copies the trace of the physical hart executing this call to the hypervisor's page, so that the sequence of events preceding a
+/// misbehavior can be inspected without halting the security monitor. Events are written from the oldest to the most recent one
+/// (see `TraceBuffer::encoded_events`) and unused entries are zeroed. Returns the total number of events recorded on this hart, so
+/// the hypervisor can tell how many events were lost since its last read.
+pub fn handle(request: TraceBufferRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let trace_buffer = non_confidential_flow.trace_buffer();
+    let transformation = write_to_hypervisor_memory(request.buffer_address(), trace_buffer)
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(trace_buffer.number_of_records()))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn write_to_hypervisor_memory(buffer_address: usize, trace_buffer: &TraceBuffer<TRACE_BUFFER_CAPACITY>) -> Result<(), Error> {
+    let size_in_bytes = TraceBuffer::<TRACE_BUFFER_CAPACITY>::SIZE_IN_BYTES;
+    assure!(buffer_address % core::mem::size_of::<usize>() == 0, Error::AddressNotAligned())?;
+    assure!(buffer_address.checked_add(size_in_bytes).is_some(), Error::InvalidArgument())?;
+    let words = trace_buffer.encoded_events().flatten().chain(core::iter::repeat(0));
+    (0..size_in_bytes).step_by(core::mem::size_of::<usize>()).zip(words).try_for_each(|(offset, value)| {
+        let address = NonConfidentialMemoryAddress::new((buffer_address + offset) as *mut usize)?;
+        // Safety: the address is in the non-confidential memory, so writing it cannot corrupt the security monitor's or confidential
+        // VMs' memory.
+        unsafe { address.write(value) };
+        Ok(())
+    })
+}
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, ReclaimMemoryRequest, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to reclaim a batch of pages of a terminated confidential VM. The hypervisor invokes it repeatedly until
+/// the returned value is 1, which indicates that the confidential VM's memory and control structures have been entirely released.
+/// Returned value 0 means that there are still pages to reclaim.
+pub fn handle(reclaim_memory_request: ReclaimMemoryRequest, mut non_confidential_flow: NonConfidentialFlow) -> ! {
+    // Pages are released only after translations have been fenced on all harts, which requires sending IPIs via OpenSBI, which expects
+    // its own value in mscratch.
+    non_confidential_flow.swap_mscratch();
+    let result = ControlData::reclaim_confidential_vm_memory(
+        reclaim_memory_request.confidential_vm_id(),
+        reclaim_memory_request.max_number_of_pages(),
+    );
+    non_confidential_flow.swap_mscratch();
+
+    let transformation = result
+        .and_then(|is_reclaimed| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(is_reclaimed as usize))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,38 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::fence_wo;
+use crate::core::memory_layout::{ConversionState, MemoryLayout};
+use crate::core::page_allocator::PageAllocator;
+use crate::core::transformations::{ExposeToHypervisor, ReclaimToNonConfidentialRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor command to mark a memory region, which it previously converted into the confidential memory, for the return to the
+/// non-confidential memory. Only a region whose pages are all free in the page allocator can be released. Like the conversion, the
+/// release completes only after a global memory fence and its confirmation, because until then some harts still deny the hypervisor
+/// access to the region.
+pub fn handle(request: ReclaimToNonConfidentialRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = release_confidential_memory(request)
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn release_confidential_memory(request: ReclaimToNonConfidentialRequest) -> Result<(), Error> {
+    let memory_layout = MemoryLayout::read();
+    let region =
+        memory_layout.converted_memory_region(request.start_address(), request.size_in_bytes()).ok_or(Error::InvalidMemoryRegion())?;
+    // A region that is still converting or already releasing has not reached the page allocator, so there is nothing to reclaim.
+    region.assure_reclaimable()?;
+    // Page tokens are removed atomically, so at most one hart can succeed here and no page of the region can be allocated later.
+    let pages = PageAllocator::remove_memory_region(region.start(), region.end())?;
+    pages.into_iter().for_each(|page| {
+        page.zeroize();
+    });
+    memory_layout.change_converted_memory_region_state(&region, ConversionState::Confidential, ConversionState::Releasing);
+    // The zeroized content must reach the memory before any hart reopens the hypervisor access to the region.
+    fence_wo();
+    Ok(())
+}
@@ -1,17 +1,23 @@
 // SPDX-FileCopyrightText: 2023 IBM Corporation
 // SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
 // SPDX-License-Identifier: Apache-2.0
-use crate::core::transformations::{ExposeToHypervisor, ResumeRequest, SbiRequest};
+use crate::core::architecture::SbiError;
+use crate::core::transformations::{ExposeToHypervisor, ResumeRequest, SbiRequest, SbiResult};
+use crate::error::Error;
 use crate::non_confidential_flow::NonConfidentialFlow;
 
 /// Resume handler is called by the hypervisor to resume the confidential VM execution.
 pub fn handle(resume_request: ResumeRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
-    let (non_confidential_flow, _error) = non_confidential_flow.into_confidential_flow(resume_request);
+    let (non_confidential_flow, error) = non_confidential_flow.into_confidential_flow(resume_request);
 
     // Properly implemented hypervisor should never let us enter this code. Entering this code means that the transition into confidential
     // flow failed. This might indicate an error in the hypervisor implementation because the hypervisor tried to schedule an invalid
-    // confidential VM, an invalid confidential hart, or a confidential hart that is already running on another physical hart. Let's
-    // keep informing the hypervisor that the confidential VM is shutdown regardless of what the real reason is.
-    let transformation = ExposeToHypervisor::SbiRequest(SbiRequest::kvm_srst_system_reset());
+    // confidential VM, an invalid confidential hart, or a confidential hart that is already running on another physical hart. A hart id
+    // beyond the number of the confidential VM's harts is reported as an invalid parameter. In all other cases, let's keep informing the
+    // hypervisor that the confidential VM is shutdown regardless of what the real reason is.
+    let transformation = match error {
+        Error::InvalidHartId() => ExposeToHypervisor::SbiResult(SbiResult::failure(SbiError::InvalidParam.code())),
+        _ => ExposeToHypervisor::SbiRequest(SbiRequest::kvm_srst_system_reset()),
+    };
     non_confidential_flow.exit_to_hypervisor(transformation)
 }
@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::ControlData;
+use crate::core::transformations::{ExposeToHypervisor, RotateMemoryKeyRequest, SbiResult};
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// The hypervisor requests re-keying the memory of a long-running confidential VM. The confidential VM's harts are paused while its
+/// pages are re-encrypted, which the hypervisor could also achieve by not scheduling them, so the call does not give the hypervisor
+/// any new capability. On platforms without memory encryption, the call succeeds without any effect.
+pub fn handle(request: RotateMemoryKeyRequest, mut non_confidential_flow: NonConfidentialFlow) -> ! {
+    // Pausing confidential harts requires sending IPIs via OpenSBI, which expects its own value in mscratch.
+    non_confidential_flow.swap_mscratch();
+    let result =
+        ControlData::try_confidential_vm_mut(request.confidential_vm_id(), |mut confidential_vm| confidential_vm.rotate_memory_key());
+    non_confidential_flow.swap_mscratch();
+
+    let transformation = result
+        .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
+        .unwrap_or_else(|error| error.into_non_confidential_transformation());
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::control_data::NaclSharedMemory;
+use crate::core::transformations::{ExposeToHypervisor, NaclSharedMemoryRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Registers the shared memory of the SBI nested acceleration (NACL) extension on the physical hart that executes this call. Once
+/// registered, the security monitor exchanges MMIO-related information and arguments of security monitor calls with the hypervisor
+/// via this shared memory instead of using CSRs. The shared memory must be located in the non-confidential memory.
+pub fn handle(nacl_shared_memory_request: NaclSharedMemoryRequest, mut non_confidential_flow: NonConfidentialFlow) -> ! {
+    let transformation = match validate(nacl_shared_memory_request) {
+        Ok(nacl_shared_memory) => {
+            non_confidential_flow.set_nacl_shared_memory(nacl_shared_memory);
+            ExposeToHypervisor::SbiResult(SbiResult::success(0))
+        }
+        Err(error) => error.into_non_confidential_transformation(),
+    };
+    non_confidential_flow.exit_to_hypervisor(transformation)
+}
+
+fn validate(nacl_shared_memory_request: NaclSharedMemoryRequest) -> Result<Option<NaclSharedMemory>, Error> {
+    // The SBI specification reserves flags for the future use, they must be zero.
+    assure!(nacl_shared_memory_request.flags() == 0, Error::InvalidArgument())?;
+    if nacl_shared_memory_request.is_unregister_request() {
+        return Ok(None);
+    }
+    let address = nacl_shared_memory_request.address().ok_or(Error::InvalidArgument())?;
+    Ok(Some(NaclSharedMemory::new(address)?))
+}
@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: 2023 IBM Corporation
+// SPDX-FileContributor: Wojciech Ozga <woz@zurich.ibm.com>, IBM Research - Zurich
+// SPDX-License-Identifier: Apache-2.0
+use crate::core::architecture::SbiError;
+use crate::core::control_data::ControlData;
+use crate::core::page_allocator::is_memory_confidential_during_suspend;
+use crate::core::transformations::{ExposeToHypervisor, OpensbiRequest, SbiResult};
+use crate::error::Error;
+use crate::non_confidential_flow::handlers::delegate_to_opensbi;
+use crate::non_confidential_flow::NonConfidentialFlow;
+
+/// Handles the hypervisor's request to suspend the system to RAM (the `system suspend` call of the SBI SUSP extension).
+///
+/// While the system is suspended, the DRAM stays powered and keeps the state of confidential VMs, including the state of their
+/// confidential harts, which the security monitor stores in the confidential memory. Unless the platform guarantees that the DRAM
+/// content stays confidential during suspend, we refuse to suspend while any confidential VM exists and return `SBI_ERR_DENIED`.
+/// Otherwise, the request is processed by OpenSBI, which requires all other harts to be stopped, so no confidential hart executes.
+///
+/// Suspend does not leave confidential data in processor registers of this hart because, in the non-confidential flow, they hold
+/// only the hypervisor's state. On resume, OpenSBI reconfigures PMPs and calls `ace_setup_this_hart`, which re-establishes the
+/// protection of the confidential memory and the security monitor's trap vector before the hypervisor executes again.
+pub fn handle(opensbi_request: OpensbiRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
+    match assure_suspend_allowed() {
+        Ok(()) => delegate_to_opensbi::handle(opensbi_request, non_confidential_flow),
+        Err(_) => {
+            let transformation = ExposeToHypervisor::SbiResult(SbiResult::failure(SbiError::Denied.code()));
+            non_confidential_flow.exit_to_hypervisor(transformation)
+        }
+    }
+}
+
+fn assure_suspend_allowed() -> Result<(), Error> {
+    assure_suspend_allowed_on_platform(is_memory_confidential_during_suspend(), ControlData::contains_confidential_vm_data)
+}
+
+fn assure_suspend_allowed_on_platform(
+    is_memory_confidential_during_suspend: bool, contains_confidential_vm_data: impl FnOnce() -> Result<bool, Error>,
+) -> Result<(), Error> {
+    if is_memory_confidential_during_suspend {
+        return Ok(());
+    }
+    assure_not!(contains_confidential_vm_data()?, Error::SuspendWithConfidentialVms())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspend_with_confidential_vm_is_denied() {
+        let result = assure_suspend_allowed_on_platform(false, || Ok(true));
+        assert!(matches!(result, Err(Error::SuspendWithConfidentialVms())));
+    }
+
+    #[test]
+    fn suspend_without_confidential_vm_is_allowed() {
+        assert!(assure_suspend_allowed_on_platform(false, || Ok(false)).is_ok());
+    }
+
+    #[test]
+    fn suspend_is_allowed_if_memory_stays_confidential() {
+        assert!(assure_suspend_allowed_on_platform(true, || panic!("confidential VMs do not need to be checked")).is_ok());
+    }
+}
@@ -6,8 +6,14 @@ use crate::core::transformations::{ExposeToHypervisor, SbiResult, TerminateReque
 use crate::non_confidential_flow::NonConfidentialFlow;
 
 /// The hypervisor command to terminate the confidential VM and remove it from the memory.
+///
+/// The hypervisor might terminate the confidential VM while some of its harts wait for the emulation of an MMIO access or another
+/// response from the hypervisor. These harts are shut down and their requests dropped, so a response delivered after the termination
+/// is never applied. The confidential VM cannot be terminated while any of its harts executes or is runnable.
 pub fn handle(terminate_request: TerminateRequest, non_confidential_flow: NonConfidentialFlow) -> ! {
-    let transformation = ControlData::remove_confidential_vm(terminate_request.confidential_vm_id())
+    let result = ControlData::remove_confidential_vm(terminate_request.confidential_vm_id());
+
+    let transformation = result
         .and_then(|_| Ok(ExposeToHypervisor::SbiResult(SbiResult::success(0))))
         .unwrap_or_else(|error| error.into_non_confidential_transformation());
 